@@ -0,0 +1,66 @@
+// This benchmark measures how fast labels are parsed from files of various shapes. Run it with
+// `cargo bench --bench parse`.
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use std::{hint::black_box, path::Path};
+use tagref::directive::{self, compile_block_regex, compile_directive_regex};
+
+// This function returns a file with the given number of lines, where every `stride`th line has a
+// label on it.
+fn contents(lines: usize, stride: usize) -> String {
+    (0..lines)
+        .map(|i| {
+            if i % stride == 0 {
+                format!(
+                    "    // See [{}:label-{i}] for why.\n",
+                    ["tag", "ref"][i % 2]
+                )
+            } else {
+                format!("    let value_{i} = compute(&inputs[{i}], {i});\n")
+            }
+        })
+        .collect()
+}
+
+// This function measures the throughput of parsing files with sparse and dense labels.
+fn parse(criterion: &mut Criterion) {
+    let tag_regex = compile_directive_regex("tag");
+    let tag_block_regex = compile_block_regex("tag");
+    let ref_regex = compile_directive_regex("ref");
+    let file_regex = compile_directive_regex("file");
+    let dir_regex = compile_directive_regex("dir");
+    let url_regex = compile_directive_regex("url");
+
+    let sparse = contents(20_000, 100);
+    let dense = contents(20_000, 2);
+
+    let mut group = criterion.benchmark_group("parse");
+    for (name, contents, comments_only) in [
+        ("sparse labels", &sparse, false),
+        ("dense labels", &dense, false),
+        ("sparse labels, comments", &sparse, true),
+    ] {
+        group.throughput(Throughput::Bytes(contents.len() as u64));
+        group.bench_function(name, |bencher| {
+            bencher.iter(|| {
+                directive::parse(
+                    &tag_regex,
+                    Some(&tag_block_regex),
+                    &ref_regex,
+                    &file_regex,
+                    &dir_regex,
+                    &url_regex,
+                    &[],
+                    comments_only,
+                    None,
+                    Path::new("file.rs"),
+                    black_box(contents.as_bytes()),
+                )
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
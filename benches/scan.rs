@@ -0,0 +1,98 @@
+// This benchmark measures how fast a synthetic tree of files is walked, scanned, and checked. Run
+// it with `cargo bench --bench scan`. The size of the tree can be configured with the
+// `TAGREF_BENCH_FILES`, `TAGREF_BENCH_LINES`, and `TAGREF_BENCH_TAGS` environment variables. The
+// same kind of tree can be generated with the hidden `tagref bench` command for measuring the whole
+// program.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use std::{env, fs, path::Path, process};
+use tagref::{
+    directive::{Directive, compile_block_regex, compile_directive_regex},
+    extractors,
+    index::{Rules, Scanner},
+    synthetic::Tree,
+    walk,
+};
+
+// This function reads a setting from an environment variable.
+fn setting(name: &str, default: usize) -> usize {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+// This function measures how long the steps take on a synthetic tree.
+fn scan(criterion: &mut Criterion) {
+    let tree = Tree {
+        files: setting("TAGREF_BENCH_FILES", 2000),
+        lines: setting("TAGREF_BENCH_LINES", 200),
+        tags: setting("TAGREF_BENCH_TAGS", 5),
+    };
+    let root = env::temp_dir().join(format!("tagref-bench-{}", process::id()));
+    tree.generate(&root).unwrap();
+
+    // File references are resolved from the working directory, which is the root of the tree.
+    env::set_current_dir(&root).unwrap();
+    let scanner = Scanner {
+        paths: vec![Path::new(".").to_owned()],
+        walk_options: walk::Options::default(),
+        tag_regex: compile_directive_regex("tag"),
+        tag_block_regex: Some(compile_block_regex("tag")),
+        ref_regex: compile_directive_regex("ref"),
+        file_regex: compile_directive_regex("file"),
+        dir_regex: compile_directive_regex("dir"),
+        url_regex: compile_directive_regex("url"),
+        custom_regexes: Vec::new(),
+        comments_only: false,
+        archives: false,
+        extractors: extractors::defaults(),
+        fallback_encoding: None,
+        case_insensitive_labels: false,
+        relative_paths: false,
+        roots: Vec::new(),
+        memory_map: true,
+    };
+
+    // Each step takes a while on a tree of this size, so fewer samples are taken than usual.
+    let mut group = criterion.benchmark_group(format!(
+        "{} files with {} lines and {} tags each",
+        tree.files, tree.lines, tree.tags,
+    ));
+    group.sample_size(10);
+    group.bench_function("walk", |bencher| {
+        bencher.iter(|| walk::walk(&scanner.paths, &scanner.walk_options, |_, _| {}).unwrap());
+    });
+    group.bench_function("scan", |bencher| {
+        bencher.iter(|| scanner.scan(None).unwrap());
+    });
+    let (index, _) = scanner.scan(None).unwrap();
+    assert!(index.check(&Rules::default()).is_empty());
+    group.bench_function("check", |bencher| {
+        bencher.iter(|| index.check(&Rules::default()));
+    });
+
+    // Dangling references are slower to check, since they're compared with the tags to suggest
+    // the closest ones.
+    let mut dangling = index.clone();
+    let typos = dangling
+        .refs
+        .iter()
+        .take(1000)
+        .map(|r#ref| Directive {
+            label: format!("{}-typo", r#ref.label),
+            ..r#ref.clone()
+        })
+        .collect::<Vec<_>>();
+    dangling.refs.extend(typos);
+    group.bench_function("check (dangling refs)", |bencher| {
+        bencher.iter(|| dangling.check(&Rules::default()));
+    });
+    group.finish();
+
+    env::set_current_dir(env::temp_dir()).unwrap();
+    fs::remove_dir_all(&root).unwrap();
+}
+
+criterion_group!(benches, scan);
+criterion_main!(benches);
@@ -0,0 +1,34 @@
+/*
+ * The C interface to Tagref. Build the library with:
+ *
+ *   cargo rustc --lib --release --crate-type cdylib
+ *
+ * (or `--crate-type staticlib` for a static library). Strings are UTF-8 and null-terminated. Each
+ * function returns a JSON object with the `violations` and the `suppressed` violations, in the
+ * same form as `tagref --format json`, or an `error`. The result must be freed with
+ * `tagref_free_string`.
+ *
+ * The options are a JSON object named like the command-line options (e.g.,
+ * `{"deny_unused": true}`), or null for the defaults.
+ */
+
+#ifndef TAGREF_H
+#define TAGREF_H
+
+/* This file is generated by cbindgen. Don't edit it by hand. */
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+char *tagref_check_directory(const char *directory, const char *options);
+
+char *tagref_check_files(const char *request);
+
+void tagref_free_string(char *response);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* TAGREF_H */
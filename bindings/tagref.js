@@ -0,0 +1,58 @@
+// JavaScript bindings for the WebAssembly build of Tagref. Build the module with:
+//
+//   cargo rustc --lib --release --target wasm32-unknown-unknown --crate-type cdylib
+//
+// and load it with `load`, which accepts anything `WebAssembly.instantiate` does (e.g., the bytes
+// of `tagref.wasm`), or a `Response` from `fetch`:
+//
+//   import { load } from './tagref.js';
+//
+//   const tagref = await load(fetch('tagref.wasm'));
+//   const { violations } = tagref.check([{ path: 'src/main.rs', contents: '...' }]);
+//
+// Files aren't read from disk, so every file which is scanned or referenced must be given.
+
+const encoder = new TextEncoder();
+const decoder = new TextDecoder();
+
+// The functions the module imports
+const imports = {
+  tagref: {
+    now: () => Date.now(),
+  },
+};
+
+// This function instantiates the module and returns the bindings.
+export async function load(source) {
+  const resolved = await source;
+  const { instance } =
+    typeof Response !== 'undefined' && resolved instanceof Response
+      ? await WebAssembly.instantiateStreaming(resolved, imports)
+      : await WebAssembly.instantiate(resolved, imports);
+  const exports = instance.exports;
+
+  return {
+    // This function checks files, given as objects with a `path` and `contents`. The options are
+    // named like the command-line options, in snake case (e.g., `deny_unused`). It returns the
+    // `violations` and the `suppressed` violations, in the same form as `--format json`.
+    check(files, options = {}) {
+      const request = encoder.encode(JSON.stringify({ files, options }));
+      const requestPointer = exports.tagref_alloc(request.length);
+      new Uint8Array(exports.memory.buffer, requestPointer, request.length).set(request);
+      const responsePointer = exports.tagref_check(requestPointer, request.length);
+      exports.tagref_free(requestPointer, request.length);
+
+      // The response is prefixed with its length.
+      const length = new DataView(exports.memory.buffer).getUint32(responsePointer, true);
+      const response = JSON.parse(
+        decoder.decode(new Uint8Array(exports.memory.buffer, responsePointer + 4, length)),
+      );
+      exports.tagref_free(responsePointer, length + 4);
+
+      if (response.error !== undefined) {
+        throw new Error(response.error);
+      }
+      return response;
+    },
+  };
+}
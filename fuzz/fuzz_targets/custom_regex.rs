@@ -0,0 +1,48 @@
+// This fuzz target compiles the first line of arbitrary text as the regular expression for a
+// custom kind of label, as if it came from the configuration file, and parses the rest of the text
+// with it. Adversarial patterns must be rejected or matched in reasonable time, without panicking.
+// Run it with `cargo +nightly fuzz run custom_regex` in the `fuzz` directory.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use std::{path::Path, str};
+use tagref::directive::{self, compile_custom_regex, compile_directive_regex};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(data) = str::from_utf8(data) else {
+        return;
+    };
+    let (pattern, contents) = data.split_once('\n').unwrap_or((data, ""));
+    let Ok(regex) = compile_custom_regex(pattern) else {
+        return;
+    };
+
+    let directives = directive::parse(
+        &compile_directive_regex("tag"),
+        None,
+        &compile_directive_regex("ref"),
+        &compile_directive_regex("file"),
+        &compile_directive_regex("dir"),
+        &compile_directive_regex("url"),
+        &[("custom".to_owned(), regex)],
+        false,
+        None,
+        Path::new("file.txt"),
+        contents.as_bytes(),
+    );
+
+    // Every custom label must be within its line. A byte order mark isn't part of the first line.
+    let lines = contents
+        .strip_prefix('\u{feff}')
+        .unwrap_or(contents)
+        .lines()
+        .collect::<Vec<_>>();
+    for custom in &directives.custom {
+        assert!(
+            lines[custom.line_number - 1]
+                .get(custom.span.clone())
+                .is_some(),
+        );
+    }
+});
@@ -0,0 +1,33 @@
+// This fuzz target loads arbitrary text as a drop-in configuration file, and compiles the regexes
+// for any sigils it sets. Invalid files must be rejected without panicking. Run it with
+// `cargo +nightly fuzz run drop_in` in the `fuzz` directory.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use std::{path::Path, str};
+use tagref::{
+    directive::{compile_block_regex, compile_directive_regex},
+    drop_ins::{self, FILE_NAME, Sigils},
+};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(contents) = str::from_utf8(data) else {
+        return;
+    };
+    let Ok(drop_in) = drop_ins::parse(contents, Path::new(FILE_NAME)) else {
+        return;
+    };
+
+    let Sigils {
+        tag,
+        r#ref,
+        file,
+        dir,
+        url,
+    } = drop_in.sigils;
+    for sigil in [tag, r#ref, file, dir, url].iter().flatten() {
+        let _ = compile_directive_regex(sigil);
+        let _ = compile_block_regex(sigil);
+    }
+});
@@ -0,0 +1,78 @@
+// This fuzz target parses arbitrary bytes as the contents of a file. Besides not panicking, every
+// label must be found within its line as split by `str::lines`, whatever the line endings. Run it
+// with `cargo +nightly fuzz run parse` in the `fuzz` directory.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use regex::Regex;
+use std::{path::Path, str, sync::LazyLock};
+use tagref::directive::{self, compile_block_regex, compile_directive_regex};
+
+// These are the regexes for the built-in kinds of labels, in the order `directive::parse` takes
+// them.
+static REGEXES: LazyLock<[Regex; 6]> = LazyLock::new(|| {
+    [
+        compile_directive_regex("tag"),
+        compile_block_regex("tag"),
+        compile_directive_regex("ref"),
+        compile_directive_regex("file"),
+        compile_directive_regex("dir"),
+        compile_directive_regex("url"),
+    ]
+});
+
+// The file names determine the comment syntax when only comments are considered.
+const PATHS: [&str; 4] = ["file.rs", "file.py", "file.html", "file.txt"];
+
+fuzz_target!(|data: &[u8]| {
+    // The first byte chooses the options, and the rest are the contents.
+    let Some((&options, contents)) = data.split_first() else {
+        return;
+    };
+    let path = Path::new(PATHS[usize::from(options) % PATHS.len()]);
+    let comments_only = options & 0x80_u8 != 0;
+
+    let [tag, tag_block, r#ref, file, dir, url] = &*REGEXES;
+    let directives = directive::parse(
+        tag,
+        Some(tag_block),
+        r#ref,
+        file,
+        dir,
+        url,
+        &[],
+        comments_only,
+        None,
+        path,
+        contents,
+    );
+
+    // Text which isn't UTF-8 is decoded differently, so the lines can only be compared for UTF-8.
+    let Ok(text) = str::from_utf8(contents) else {
+        return;
+    };
+    let lines = text
+        .strip_prefix('\u{feff}')
+        .unwrap_or(text)
+        .lines()
+        .collect::<Vec<_>>();
+    for directive in directives
+        .tags
+        .iter()
+        .chain(&directives.refs)
+        .chain(&directives.files)
+        .chain(&directives.dirs)
+        .chain(&directives.urls)
+        .chain(&directives.anchors)
+    {
+        let line = lines[directive.line_number - 1];
+        let matched = &line[directive.span.clone()];
+        assert!(matched.starts_with('['));
+        assert!(matched.contains(&*directive.label));
+        assert_eq!(
+            directive.column,
+            line[..directive.span.start].chars().count() + 1,
+        );
+    }
+});
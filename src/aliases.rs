@@ -0,0 +1,200 @@
+use crate::{
+    directive::{self, Directive, compile_directive_regex},
+    violation::{Kind, Severity, Violation},
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, collections::BTreeMap, sync::LazyLock};
+
+// This is the sigil of aliases.
+pub const SIGIL: &str = "alias";
+
+// This regex matches aliases.
+pub static REGEX: LazyLock<Regex> = LazyLock::new(|| compile_directive_regex(SIGIL));
+
+// This struct represents an alternative label for a tag, which is declared right after the tag on
+// the same line. References can use it instead of the label of the tag, e.g., while they're being
+// migrated to a new label.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Alias {
+    // The label of the tag
+    pub tag: String,
+
+    // The alias itself, whose label is the alternative label
+    pub directive: Directive,
+}
+
+// This function combines the aliases declared in the files with the ones from the configuration
+// file (which map each alias to the label of its tag) into a map from the form of each alias used
+// for matching (as in the keys of the tags) to the label of its tag. Aliases for tags which don't
+// exist are left out, as are aliases which are the labels of tags themselves.
+#[must_use]
+pub fn resolve(
+    tags: &BTreeMap<String, Vec<Directive>>,
+    aliases: &[Alias],
+    configured: &BTreeMap<String, String>,
+    case_insensitive_labels: bool,
+) -> BTreeMap<String, String> {
+    aliases
+        .iter()
+        .map(|alias| (alias.directive.label.as_str(), alias.tag.as_str()))
+        .chain(
+            configured
+                .iter()
+                .map(|(alias, tag)| (alias.as_str(), tag.as_str())),
+        )
+        .filter_map(|(alias, tag)| {
+            let alias = directive::key(alias, case_insensitive_labels);
+            let tag = tags.get(&*directive::key(tag, case_insensitive_labels))?;
+            (!tags.contains_key(&*alias)).then(|| (alias.into_owned(), tag[0].label.clone()))
+        })
+        .collect()
+}
+
+// This function replaces the aliases in the labels of the given references with the labels of
+// their tags, so the references are checked as if they used those labels. Without any aliases,
+// the references are borrowed as they are.
+#[must_use]
+pub fn apply<'a>(
+    refs: &'a [Directive],
+    aliases: &BTreeMap<String, String>,
+    case_insensitive_labels: bool,
+) -> Cow<'a, [Directive]> {
+    if aliases.is_empty() {
+        return Cow::Borrowed(refs);
+    }
+
+    refs.iter()
+        .map(
+            |r#ref| match aliases.get(&*directive::key(&r#ref.label, case_insensitive_labels)) {
+                Some(tag) => Directive {
+                    label: tag.clone(),
+                    ..r#ref.clone()
+                },
+                None => r#ref.clone(),
+            },
+        )
+        .collect()
+}
+
+// This function reports the references which use aliases rather than the labels of their tags. If
+// `deny` is set, they're errors. Otherwise, they're warnings. Each violation suggests the label of
+// the tag. It returns a vector of violations.
+#[must_use]
+pub fn check(
+    refs: &[Directive],
+    aliases: &BTreeMap<String, String>,
+    deny: bool,
+    case_insensitive_labels: bool,
+) -> Vec<Violation> {
+    refs.iter()
+        .filter_map(|r#ref| {
+            aliases
+                .get(&*directive::key(&r#ref.label, case_insensitive_labels))
+                .map(|tag| Violation {
+                    kind: Kind::DeprecatedAlias,
+                    severity: if deny {
+                        Severity::Error
+                    } else {
+                        Severity::Warning
+                    },
+                    message: format!(
+                        "{ref} uses the deprecated alias `{}` for the tag `{tag}`.",
+                        r#ref.label,
+                    ),
+                    directives: vec![r#ref.clone()],
+                    suggestions: vec![tag.clone()],
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        aliases::{Alias, apply, check, resolve},
+        directive::{Directive, Type},
+        violation::Severity,
+    };
+    use std::collections::BTreeMap;
+
+    fn tags(labels: &[&str]) -> BTreeMap<String, Vec<Directive>> {
+        labels
+            .iter()
+            .map(|label| {
+                (
+                    (*label).to_owned(),
+                    vec![Directive::new(Type::Tag, label, "file.rs", 1)],
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn resolve_declared_and_configured() {
+        let aliases = [Alias {
+            tag: "new".to_owned(),
+            directive: Directive::new(Type::Alias, "old", "file.rs", 1),
+        }];
+        let configured = BTreeMap::from([
+            ("older".to_owned(), "new".to_owned()),
+            ("gone".to_owned(), "missing".to_owned()),
+            ("other".to_owned(), "new".to_owned()),
+        ]);
+
+        assert_eq!(
+            resolve(&tags(&["new", "other"]), &aliases, &configured, false),
+            BTreeMap::from([
+                ("old".to_owned(), "new".to_owned()),
+                ("older".to_owned(), "new".to_owned()),
+            ]),
+        );
+    }
+
+    #[test]
+    fn resolve_case_insensitive() {
+        let configured = BTreeMap::from([("Old".to_owned(), "NEW".to_owned())]);
+        let mut tags = tags(&["new"]);
+        tags.get_mut("new").unwrap()[0].label = "New".to_owned();
+
+        assert_eq!(
+            resolve(&tags, &[], &configured, true),
+            BTreeMap::from([("old".to_owned(), "New".to_owned())]),
+        );
+    }
+
+    #[test]
+    fn apply_aliases() {
+        let aliases = BTreeMap::from([("old".to_owned(), "new".to_owned())]);
+        let refs = [
+            Directive::new(Type::Ref, "old", "file.rs", 1),
+            Directive::new(Type::Ref, "other", "file.rs", 1),
+        ];
+
+        assert_eq!(
+            apply(&refs, &aliases, false)
+                .iter()
+                .map(|r#ref| r#ref.label.clone())
+                .collect::<Vec<_>>(),
+            vec!["new".to_owned(), "other".to_owned()],
+        );
+    }
+
+    #[test]
+    fn check_deprecated() {
+        let aliases = BTreeMap::from([("old".to_owned(), "new".to_owned())]);
+        let refs = [
+            Directive::new(Type::Ref, "old", "file.rs", 1),
+            Directive::new(Type::Ref, "new", "file.rs", 1),
+        ];
+
+        let warnings = check(&refs, &aliases, false, false);
+        let errors = check(&refs, &aliases, true, false);
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].severity, Severity::Warning);
+        assert_eq!(warnings[0].suggestions, vec!["new".to_owned()]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].severity, Severity::Error);
+    }
+}
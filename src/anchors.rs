@@ -0,0 +1,66 @@
+use crate::{
+    directive::{Directive, compile_directive_regex},
+    walk,
+};
+use regex::Regex;
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::LazyLock,
+};
+
+// This is the sigil of anchors.
+pub const SIGIL: &str = "anchor";
+
+// This regex matches anchors.
+pub static REGEX: LazyLock<Regex> = LazyLock::new(|| compile_directive_regex(SIGIL));
+
+// This function splits a file reference into the path and the optional anchor after it, as in
+// `docs/design.md#retries`.
+#[must_use]
+pub fn split(label: &str) -> (&str, Option<&str>) {
+    match label.rsplit_once('#') {
+        Some((path, anchor)) if !path.trim().is_empty() && !anchor.trim().is_empty() => {
+            (path.trim_end(), Some(anchor.trim()))
+        }
+        _ => (label, None),
+    }
+}
+
+// This function groups the labels of the anchors by the file declaring them. The paths are
+// normalized, as in `walk::resolve`.
+#[must_use]
+pub fn by_path(anchors: &[Directive]) -> HashMap<PathBuf, HashSet<&str>> {
+    let mut by_path = HashMap::<PathBuf, HashSet<&str>>::new();
+    for anchor in anchors {
+        by_path
+            .entry(walk::normalize(&anchor.path))
+            .or_default()
+            .insert(anchor.label.trim());
+    }
+    by_path
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::anchors::split;
+
+    #[test]
+    fn split_none() {
+        assert_eq!(split("docs/design.md"), ("docs/design.md", None));
+    }
+
+    #[test]
+    fn split_anchor() {
+        assert_eq!(
+            split("docs/design.md#retries"),
+            ("docs/design.md", Some("retries"))
+        );
+    }
+
+    #[test]
+    fn split_empty() {
+        assert_eq!(split("docs/design.md#"), ("docs/design.md#", None));
+        assert_eq!(split("#retries"), ("#retries", None));
+    }
+}
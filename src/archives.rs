@@ -0,0 +1,383 @@
+use crate::{git::Tree, walk};
+use flate2::read::GzDecoder;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    ffi::OsString,
+    fmt::Display,
+    fs,
+    io::{self, Cursor, Read},
+    path::{Component, Path, PathBuf},
+};
+use tar::EntryType;
+use zip::ZipArchive;
+
+// This is appended to the path of an archive to form the paths of the files inside it, as in
+// `docs.tar.gz!/guide.md`.
+pub const SEPARATOR: char = '!';
+
+// These are the extensions of the supported archives.
+const TAR_EXTENSIONS: [&str; 1] = [".tar"];
+const GZIPPED_TAR_EXTENSIONS: [&str; 2] = [".tar.gz", ".tgz"];
+const ZIP_EXTENSIONS: [&str; 1] = [".zip"];
+
+// Archives whose files add up to more than this many bytes are skipped, so a small archive which
+// decompresses to something enormous can't exhaust the memory.
+pub const MAX_DECOMPRESSED: u64 = 256 << 20_u64;
+
+// This function determines whether a file name ends with one of the given extensions, regardless
+// of case.
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.file_name().is_some_and(|name| {
+        let name = name.to_string_lossy().to_lowercase();
+        extensions.iter().any(|extension| name.ends_with(extension))
+    })
+}
+
+// This function determines whether a file is an archive which can be scanned, based on its name.
+#[must_use]
+pub fn is_archive(path: &Path) -> bool {
+    has_extension(path, &TAR_EXTENSIONS)
+        || has_extension(path, &GZIPPED_TAR_EXTENSIONS)
+        || has_extension(path, &ZIP_EXTENSIONS)
+}
+
+// This function returns the path of a file inside an archive, as in `docs.tar.gz!/guide.md`.
+#[must_use]
+pub fn join(archive: &Path, inner: &Path) -> PathBuf {
+    let mut path = OsString::from(archive.as_os_str());
+    path.push(SEPARATOR.to_string());
+    PathBuf::from(path).join(inner)
+}
+
+// This function splits the path of a file inside an archive into the path of the archive and the
+// path inside it. It returns `None` for paths which aren't inside an archive.
+#[must_use]
+pub fn split(path: &Path) -> Option<(PathBuf, PathBuf)> {
+    let mut archive = PathBuf::new();
+    let mut components = path.components();
+    while let Some(component) = components.next() {
+        if let Component::Normal(name) = component
+            && let Some(name) = name.to_string_lossy().strip_suffix(SEPARATOR)
+            && is_archive(Path::new(name))
+        {
+            archive.push(name);
+            return Some((archive, components.collect()));
+        }
+        archive.push(component);
+    }
+    None
+}
+
+// This function returns the directory which file and directory references in a file inside an
+// archive are resolved from, which is the top of the archive (e.g., `docs.tar.gz!`).
+#[must_use]
+pub fn root(path: &Path) -> Option<PathBuf> {
+    split(path).map(|(archive, _)| join(&archive, Path::new("")))
+}
+
+// This struct holds the files in an archive, keyed by their paths inside it.
+#[derive(Clone, Debug, Default)]
+pub struct Archive {
+    files: BTreeMap<PathBuf, Vec<u8>>,
+    dirs: HashSet<PathBuf>,
+
+    // The files which were skipped for being larger than the limit
+    too_large: Vec<PathBuf>,
+}
+
+impl Archive {
+    // This function reads an archive from its contents. The path determines the format. Entries
+    // other than files and directories (e.g., symlinks), and entries with paths which would escape
+    // the archive, are skipped, as are files larger than the limit (if given). It's an error for
+    // the files to add up to more than `MAX_DECOMPRESSED` bytes.
+    pub fn parse(path: &Path, contents: &[u8], max_filesize: Option<u64>) -> Result<Self, String> {
+        let unreadable = |error: &dyn Display| {
+            format!("Unable to read archive {}: {error}", path.to_string_lossy())
+        };
+        let mut archive = Self::default();
+        let mut remaining = MAX_DECOMPRESSED;
+
+        if has_extension(path, &ZIP_EXTENSIONS) {
+            let mut zip =
+                ZipArchive::new(Cursor::new(contents)).map_err(|error| unreadable(&error))?;
+            for index in 0..zip.len() {
+                let mut entry = zip.by_index(index).map_err(|error| unreadable(&error))?;
+                let Some(name) = entry.enclosed_name() else {
+                    continue;
+                };
+                if entry.is_dir() {
+                    archive.insert_dir(&name);
+                } else if entry.is_file() {
+                    archive
+                        .read_file(&name, &mut entry, max_filesize, &mut remaining)
+                        .map_err(|error| unreadable(&error))?;
+                }
+            }
+        } else {
+            let reader: Box<dyn Read> = if has_extension(path, &GZIPPED_TAR_EXTENSIONS) {
+                Box::new(GzDecoder::new(contents))
+            } else {
+                Box::new(contents)
+            };
+            let mut tar = tar::Archive::new(reader);
+            for entry in tar.entries().map_err(|error| unreadable(&error))? {
+                let mut entry = entry.map_err(|error| unreadable(&error))?;
+                let name = entry
+                    .path()
+                    .map_err(|error| unreadable(&error))?
+                    .into_owned();
+                if name.components().any(|component| {
+                    matches!(
+                        component,
+                        Component::ParentDir | Component::RootDir | Component::Prefix(_),
+                    )
+                }) {
+                    continue;
+                }
+                match entry.header().entry_type() {
+                    EntryType::Directory => archive.insert_dir(&name),
+                    EntryType::Regular | EntryType::Continuous => archive
+                        .read_file(&name, &mut entry, max_filesize, &mut remaining)
+                        .map_err(|error| unreadable(&error))?,
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(archive)
+    }
+
+    // This function records a directory, along with the directories containing it.
+    fn insert_dir(&mut self, path: &Path) {
+        let path = walk::normalize(path);
+        self.dirs.extend(
+            path.ancestors()
+                .filter(|dir| !dir.as_os_str().is_empty() && *dir != Path::new("."))
+                .map(Path::to_owned),
+        );
+    }
+
+    // This function reads a file from the archive. Only as much as the limits allow is read, since
+    // the sizes recorded in the archive can't be trusted. A file larger than the limit is recorded
+    // as such rather than kept. The `remaining` number of bytes is reduced by the size of the
+    // file, and it's an error for the file to be larger than that.
+    fn read_file(
+        &mut self,
+        path: &Path,
+        entry: impl Read,
+        max_filesize: Option<u64>,
+        remaining: &mut u64,
+    ) -> io::Result<()> {
+        let limit = max_filesize.map_or(*remaining, |max_filesize| max_filesize.min(*remaining));
+        let mut file = Vec::new();
+        entry.take(limit.saturating_add(1)).read_to_end(&mut file)?;
+
+        let length = file.len() as u64;
+        *remaining = remaining.checked_sub(length).ok_or_else(|| {
+            io::Error::other(format!(
+                "Its files add up to more than {MAX_DECOMPRESSED} bytes."
+            ))
+        })?;
+        if max_filesize.is_some_and(|max_filesize| length > max_filesize) {
+            self.too_large.push(walk::normalize(path));
+        } else {
+            self.insert_file(path, file);
+        }
+        Ok(())
+    }
+
+    // This function records a file, along with the directories containing it. Archives don't
+    // always have entries for the directories.
+    fn insert_file(&mut self, path: &Path, contents: Vec<u8>) {
+        let path = walk::normalize(path);
+        if let Some(parent) = path.parent() {
+            self.insert_dir(parent);
+        }
+        self.files.insert(path, contents);
+    }
+
+    // This function returns the files in the archive, in order of path.
+    pub fn files(&self) -> impl Iterator<Item = (&Path, &[u8])> {
+        self.files
+            .iter()
+            .map(|(path, contents)| (path.as_path(), contents.as_slice()))
+    }
+
+    // This function returns the files in the archive which were skipped for being larger than the
+    // limit.
+    #[must_use]
+    pub fn too_large(&self) -> &[PathBuf] {
+        &self.too_large
+    }
+
+    // This function returns the contents of a file in the archive, if there is such a file.
+    #[must_use]
+    pub fn contents(&self, path: &Path) -> Option<&[u8]> {
+        self.files.get(&walk::normalize(path)).map(Vec::as_slice)
+    }
+
+    // This function determines whether a path is a directory in the archive. The top of the
+    // archive counts as a directory.
+    #[must_use]
+    pub fn is_dir(&self, path: &Path) -> bool {
+        let path = walk::normalize(path);
+        path == Path::new(".") || self.dirs.contains(&path)
+    }
+}
+
+// This struct opens archives as they're needed by the checks, so each one is only read once. If a
+// Git tree is given, the archives are read from it rather than the filesystem.
+#[derive(Default)]
+pub struct Archives<'a> {
+    tree: Option<&'a Tree>,
+    archives: HashMap<PathBuf, Result<Archive, String>>,
+}
+
+impl<'a> Archives<'a> {
+    #[must_use]
+    pub fn new(tree: Option<&'a Tree>) -> Self {
+        Self {
+            tree,
+            archives: HashMap::new(),
+        }
+    }
+
+    // This function returns the archive at the given path.
+    pub fn open(&mut self, path: &Path) -> Result<&Archive, String> {
+        let tree = self.tree;
+        self.archives
+            .entry(walk::normalize(path))
+            .or_insert_with(|| {
+                let contents = match tree {
+                    Some(tree) => tree.read(path),
+                    None => fs::read(path).map_err(|error| error.to_string()),
+                }
+                .map_err(|error| format!("Unable to read {}: {error}", path.to_string_lossy()))?;
+                Archive::parse(path, &contents, None)
+            })
+            .as_ref()
+            .map_err(Clone::clone)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::archives::{Archive, is_archive, join, root, split};
+    use std::{
+        io::Write,
+        path::{Path, PathBuf},
+    };
+    use zip::{ZipWriter, write::SimpleFileOptions};
+
+    #[test]
+    fn is_archive_extensions() {
+        assert!(is_archive(Path::new("dist/docs.tar.gz")));
+        assert!(is_archive(Path::new("docs.TGZ")));
+        assert!(is_archive(Path::new("docs.zip")));
+        assert!(!is_archive(Path::new("docs.gz")));
+        assert!(!is_archive(Path::new("zip")));
+    }
+
+    #[test]
+    fn join_and_split() {
+        let path = join(Path::new("./dist/docs.tar.gz"), Path::new("src/main.rs"));
+
+        assert_eq!(path, PathBuf::from("./dist/docs.tar.gz!/src/main.rs"));
+        assert_eq!(
+            split(&path),
+            Some((
+                PathBuf::from("./dist/docs.tar.gz"),
+                PathBuf::from("src/main.rs"),
+            )),
+        );
+        assert_eq!(root(&path), Some(PathBuf::from("./dist/docs.tar.gz!")));
+        assert_eq!(split(Path::new("./dist/wow!/main.rs")), None);
+    }
+
+    #[test]
+    fn parse_tar() {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(3);
+        builder
+            .append_data(&mut header, "./src/a.rs", b"foo".as_slice())
+            .unwrap();
+        let contents = builder.into_inner().unwrap();
+
+        let archive = Archive::parse(Path::new("docs.tar"), &contents, None).unwrap();
+
+        assert_eq!(
+            archive.files().collect::<Vec<_>>(),
+            vec![(Path::new("src/a.rs"), b"foo".as_slice())],
+        );
+        assert!(archive.is_dir(Path::new("src")));
+        assert!(archive.is_dir(Path::new("")));
+        assert!(!archive.is_dir(Path::new("src/a.rs")));
+    }
+
+    #[test]
+    fn parse_zip() {
+        let mut writer = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        writer
+            .add_directory("docs/", SimpleFileOptions::default())
+            .unwrap();
+        writer
+            .start_file("docs/guide.md", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"bar").unwrap();
+        let contents = writer.finish().unwrap().into_inner();
+
+        let archive = Archive::parse(Path::new("docs.zip"), &contents, None).unwrap();
+
+        assert_eq!(
+            archive.contents(Path::new("./docs/guide.md")),
+            Some(b"bar".as_slice()),
+        );
+        assert!(archive.is_dir(Path::new("docs")));
+    }
+
+    #[test]
+    fn parse_zip_too_large() {
+        let mut writer = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        writer
+            .start_file("docs/big.md", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(&[b'x'; 100]).unwrap();
+        writer
+            .start_file("docs/small.md", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"bar").unwrap();
+        let contents = writer.finish().unwrap().into_inner();
+
+        let archive = Archive::parse(Path::new("docs.zip"), &contents, Some(10)).unwrap();
+
+        assert_eq!(archive.too_large(), [PathBuf::from("docs/big.md")]);
+        assert_eq!(archive.contents(Path::new("docs/big.md")), None);
+        assert_eq!(
+            archive.contents(Path::new("docs/small.md")),
+            Some(b"bar".as_slice()),
+        );
+    }
+
+    #[test]
+    fn read_file_beyond_total() {
+        let mut archive = Archive::default();
+        let mut remaining = 5;
+
+        archive
+            .read_file(Path::new("a.md"), b"foo".as_slice(), None, &mut remaining)
+            .unwrap();
+        assert_eq!(remaining, 2);
+        assert!(
+            archive
+                .read_file(Path::new("b.md"), b"bar".as_slice(), None, &mut remaining)
+                .is_err()
+        );
+        assert_eq!(archive.contents(Path::new("b.md")), None);
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(Archive::parse(Path::new("docs.zip"), b"nope", None).is_err());
+    }
+}
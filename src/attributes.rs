@@ -0,0 +1,104 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+// This function determines whether a word is an attribute, i.e., `key=value` where the key starts
+// with a letter and consists of letters, digits, hyphens, underscores, and dots.
+fn parse(word: &str) -> Option<(&str, &str)> {
+    let (key, value) = word.split_once('=')?;
+    (key.starts_with(|c: char| c.is_ascii_alphabetic())
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+    .then_some((key, value))
+}
+
+// This function splits a label into the label proper and the attributes after it, as in
+// `invariant-x since=2023-04 severity=high`. Attributes are the `key=value` words at the end of
+// the label, after some whitespace, so the first word is always part of the label. The label is a
+// prefix of the given one. If an attribute is given more than once, the last value wins.
+#[must_use]
+pub fn split(label: &str) -> (&str, BTreeMap<String, String>) {
+    let mut attributes = BTreeMap::new();
+    let mut rest = label.trim_end();
+    while let Some((before, word)) = rest.rsplit_once(char::is_whitespace) {
+        let Some((key, value)) = parse(word) else {
+            break;
+        };
+        if before.trim().is_empty() {
+            break;
+        }
+        attributes
+            .entry(key.to_owned())
+            .or_insert_with(|| value.to_owned());
+        rest = before.trim_end();
+    }
+    (rest, attributes)
+}
+
+// This function formats attributes as they're written after a label, with a leading space unless
+// there are none.
+#[must_use]
+pub fn format(attributes: &BTreeMap<String, String>) -> String {
+    attributes
+        .iter()
+        .fold(String::new(), |mut text, (key, value)| {
+            // Writing to a string can't fail, so the `unwrap` is safe.
+            write!(text, " {key}={value}").unwrap();
+            text
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::attributes::{format, split};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn split_none() {
+        assert_eq!(split("foo bar"), ("foo bar", BTreeMap::new()));
+        assert_eq!(split("a=b"), ("a=b", BTreeMap::new()));
+        assert_eq!(split("foo 1=2"), ("foo 1=2", BTreeMap::new()));
+    }
+
+    #[test]
+    fn split_former_label() {
+        assert_eq!(
+            split("foo since=x"),
+            (
+                "foo",
+                BTreeMap::from([("since".to_owned(), "x".to_owned())]),
+            ),
+        );
+    }
+
+    #[test]
+    fn split_attributes() {
+        let (label, attributes) = split("invariant x  since=2023-04 severity=high");
+
+        assert_eq!(label, "invariant x");
+        assert_eq!(
+            attributes,
+            BTreeMap::from([
+                ("severity".to_owned(), "high".to_owned()),
+                ("since".to_owned(), "2023-04".to_owned()),
+            ]),
+        );
+        assert_eq!(format(&attributes), " severity=high since=2023-04");
+    }
+
+    #[test]
+    fn split_repeated() {
+        let (label, attributes) = split("foo a=1 b= a=2");
+
+        assert_eq!(label, "foo");
+        assert_eq!(attributes["a"], "2");
+        assert_eq!(attributes["b"], "");
+    }
+
+    #[test]
+    fn split_stops_at_other_words() {
+        let (label, attributes) = split("foo a=1 bar b=2");
+
+        assert_eq!(label, "foo a=1 bar");
+        assert_eq!(attributes.len(), 1);
+    }
+}
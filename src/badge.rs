@@ -0,0 +1,142 @@
+use serde_json::json;
+use tagref::{
+    count::count,
+    violation::{Severity, Violation},
+};
+
+// This is the text on the left side of the badge.
+const LABEL: &str = "tagref";
+
+// This struct describes the right side of a status badge.
+pub struct Badge {
+    pub message: String,
+
+    // The name of the color, as shields.io understands it
+    pub color: &'static str,
+}
+
+impl Badge {
+    // This function summarizes the result of a check. The check passes unless there are more
+    // errors than the maximum, in which case the badge shows how many there are. A passing check
+    // with tolerated errors or other violations gets a yellow badge rather than a green one.
+    pub fn new(violations: &[Violation], max_violations: usize) -> Self {
+        let errors = violations
+            .iter()
+            .filter(|violation| violation.severity == Severity::Error)
+            .count();
+        if errors > max_violations {
+            Self {
+                message: count(errors, "violation"),
+                color: "red",
+            }
+        } else if violations.is_empty() {
+            Self {
+                message: "passing".to_owned(),
+                color: "brightgreen",
+            }
+        } else {
+            Self {
+                message: "passing".to_owned(),
+                color: "yellow",
+            }
+        }
+    }
+
+    // This function formats the badge for a shields.io endpoint, as described at
+    // https://shields.io/badges/endpoint-badge.
+    pub fn json(&self) -> String {
+        json!({
+            "schemaVersion": 1,
+            "label": LABEL,
+            "message": self.message,
+            "color": self.color,
+        })
+        .to_string()
+    }
+
+    // This function draws the badge as an SVG image in the flat style of shields.io, so it can be
+    // served without depending on shields.io. The widths are estimated from the lengths of the
+    // texts, which is close enough for the digits and lowercase letters on the badge.
+    pub fn svg(&self) -> String {
+        let width = |text: &str| text.chars().count() * 7 + 10;
+        let (left, right) = (width(LABEL), width(&self.message));
+        let total = left + right;
+        let fill = match self.color {
+            "brightgreen" => "#4c1",
+            "yellow" => "#dfb317",
+            _ => "#e05d44",
+        };
+        format!(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="{total}" height="20" role="img"
+  aria-label="{LABEL}: {message}">
+  <title>{LABEL}: {message}</title>
+  <linearGradient id="s" x2="0" y2="100%">
+    <stop offset="0" stop-color="#bbb" stop-opacity=".1"/>
+    <stop offset="1" stop-opacity=".1"/>
+  </linearGradient>
+  <clipPath id="r"><rect width="{total}" height="20" rx="3" fill="#fff"/></clipPath>
+  <g clip-path="url(#r)">
+    <rect width="{left}" height="20" fill="#555"/>
+    <rect x="{left}" width="{right}" height="20" fill="{fill}"/>
+    <rect width="{total}" height="20" fill="url(#s)"/>
+  </g>
+  <g fill="#fff" text-anchor="middle" font-size="11"
+    font-family="Verdana,Geneva,DejaVu Sans,sans-serif">
+    <text x="{label_x}" y="14">{LABEL}</text>
+    <text x="{message_x}" y="14">{message}</text>
+  </g>
+</svg>
+"##,
+            message = self.message,
+            label_x = left / 2,
+            message_x = left + right / 2,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::badge::Badge;
+    use tagref::violation::{Kind, Severity, Violation};
+
+    fn violation(severity: Severity) -> Violation {
+        Violation {
+            kind: Kind::DanglingRef,
+            severity,
+            message: String::new(),
+            directives: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn badge_status() {
+        let badge = Badge::new(&[], 0);
+        assert_eq!(
+            badge.json(),
+            r#"{"color":"brightgreen","label":"tagref","message":"passing","schemaVersion":1}"#,
+        );
+
+        let violations = [violation(Severity::Error), violation(Severity::Error)];
+        let badge = Badge::new(&violations, 0);
+        assert_eq!(
+            (badge.message.as_str(), badge.color),
+            ("2 violations", "red")
+        );
+
+        let badge = Badge::new(&violations, 2);
+        assert_eq!((badge.message.as_str(), badge.color), ("passing", "yellow"));
+
+        let badge = Badge::new(&[violation(Severity::Warning)], 0);
+        assert_eq!((badge.message.as_str(), badge.color), ("passing", "yellow"));
+    }
+
+    #[test]
+    fn badge_svg() {
+        let svg = Badge::new(&[violation(Severity::Error)], 0).svg();
+
+        assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="139""#));
+        assert!(svg.contains(r##"fill="#e05d44""##));
+        assert!(svg.contains(">1 violation</text>"));
+    }
+}
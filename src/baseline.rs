@@ -0,0 +1,224 @@
+use crate::{
+    count::count,
+    directive::Type,
+    violation::{Kind, Severity, Violation},
+    walk,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+// This is the name of the baseline file which is used by default.
+pub const DEFAULT_PATH: &str = ".tagref-baseline.json";
+
+// This struct identifies a label involved in a violation. Line and column numbers are left out so
+// the violation is still recognized after unrelated edits move the label around.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+struct Item {
+    r#type: Type,
+    label: String,
+    path: PathBuf,
+}
+
+// This struct identifies a violation independently of where exactly it occurs in its files.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct Entry {
+    kind: Kind,
+    labels: Vec<Item>,
+}
+
+impl Entry {
+    #[must_use]
+    pub fn new(violation: &Violation) -> Self {
+        Self {
+            kind: violation.kind,
+            labels: violation
+                .directives
+                .iter()
+                .map(|directive| Item {
+                    r#type: directive.r#type.clone(),
+                    label: directive.label.clone(),
+                    path: walk::relative(&directive.path),
+                })
+                .collect(),
+        }
+    }
+}
+
+// A baseline is a snapshot of the violations which are known to exist, so they can be ignored
+// while new ones are still reported.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Baseline {
+    violations: Vec<Entry>,
+}
+
+impl Baseline {
+    // This function records the errors among the given violations. Warnings don't cause the check
+    // to fail, so they don't need to be recorded.
+    #[must_use]
+    pub fn new(violations: &[Violation]) -> Self {
+        let mut violations = violations
+            .iter()
+            .filter(|violation| violation.severity == Severity::Error)
+            .map(Entry::new)
+            .collect::<Vec<_>>();
+
+        // Sorting the entries keeps the file stable, so it produces small diffs when committed.
+        violations.sort();
+
+        Self { violations }
+    }
+
+    // This function returns the number of violations in the baseline.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.violations.len()
+    }
+
+    // This function determines whether the baseline has no violations.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    // This function reads a baseline from a file. It's an error for an explicitly given file to be
+    // missing, but the default one is optional.
+    pub fn load(path: &Path, required: bool) -> Result<Option<Self>, String> {
+        match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents).map(Some).map_err(|error| {
+                format!(
+                    "Error when parsing baseline file {}: {error}",
+                    path.to_string_lossy(),
+                )
+            }),
+            Err(error) if error.kind() == ErrorKind::NotFound && !required => Ok(None),
+            Err(error) => Err(format!(
+                "Error when reading baseline file {}: {error}",
+                path.to_string_lossy(),
+            )),
+        }
+    }
+
+    // This function writes the baseline to a file.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        // Serializing plain data to a string can't fail, so the `unwrap` is safe.
+        fs::write(path, serde_json::to_string_pretty(self).unwrap() + "\n").map_err(|error| {
+            format!(
+                "Unable to write baseline file {}: {error}",
+                path.to_string_lossy(),
+            )
+        })
+    }
+
+    // This function removes the violations which are in the baseline. Each entry in the baseline
+    // only covers one violation, so new copies of a known violation are still reported. It
+    // returns the number of violations which were removed.
+    pub fn filter(&self, violations: &mut Vec<Violation>) -> usize {
+        let mut remaining = HashMap::<&Entry, usize>::new();
+        for entry in &self.violations {
+            *remaining.entry(entry).or_insert(0) += 1;
+        }
+
+        let before = violations.len();
+        violations.retain(|violation| {
+            if violation.severity != Severity::Error {
+                return true;
+            }
+
+            match remaining.get_mut(&Entry::new(violation)) {
+                Some(count) if *count > 0 => {
+                    *count -= 1;
+                    false
+                }
+                _ => true,
+            }
+        });
+
+        before - violations.len()
+    }
+}
+
+// This function removes the violations recorded in the baseline file, if there is one, and mentions
+// how many were removed.
+pub fn apply(path: &Path, required: bool, violations: &mut Vec<Violation>) -> Result<(), String> {
+    if let Some(baseline) = Baseline::load(path, required)? {
+        let ignored = baseline.filter(violations);
+        if ignored > 0 {
+            log::warn!(
+                "Ignored {} recorded in {}.",
+                count(ignored, "violation"),
+                path.to_string_lossy(),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        baseline::Baseline,
+        directive::{Directive, Type},
+        violation::{Kind, Severity, Violation},
+    };
+
+    fn violation(label: &str, line_number: usize, severity: Severity) -> Violation {
+        Violation {
+            kind: Kind::DanglingRef,
+            severity,
+            message: String::new(),
+            directives: vec![Directive::new(Type::Ref, label, "./foo.rs", line_number)],
+            suggestions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn new_skips_warnings() {
+        let baseline = Baseline::new(&[
+            violation("foo", 1, Severity::Error),
+            violation("bar", 2, Severity::Warning),
+        ]);
+
+        assert_eq!(baseline.len(), 1);
+    }
+
+    #[test]
+    fn filter_ignores_line_numbers() {
+        let baseline = Baseline::new(&[violation("foo", 1, Severity::Error)]);
+        let mut violations = vec![
+            violation("foo", 5, Severity::Error),
+            violation("bar", 6, Severity::Error),
+        ];
+
+        assert_eq!(baseline.filter(&mut violations), 1);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].directives[0].label, "bar");
+    }
+
+    #[test]
+    fn filter_new_copies() {
+        let baseline = Baseline::new(&[violation("foo", 1, Severity::Error)]);
+        let mut violations = vec![
+            violation("foo", 1, Severity::Error),
+            violation("foo", 2, Severity::Error),
+        ];
+
+        assert_eq!(baseline.filter(&mut violations), 1);
+        assert_eq!(violations.len(), 1);
+    }
+
+    #[test]
+    fn round_trip() {
+        let baseline = Baseline::new(&[violation("foo", 1, Severity::Error)]);
+
+        let decoded =
+            serde_json::from_str::<Baseline>(&serde_json::to_string(&baseline).unwrap()).unwrap();
+
+        assert_eq!(decoded, baseline);
+    }
+}
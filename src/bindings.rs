@@ -0,0 +1,218 @@
+use crate::{
+    directive::{compile_block_regex, compile_directive_regex},
+    extractors,
+    index::{Index, Rules, Scanner},
+    violation::Violation,
+    walk,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+
+// This struct is a file whose contents are given by the caller.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct File {
+    path: PathBuf,
+    contents: String,
+}
+
+// These are the settings for a check. They're named like the command-line options they correspond
+// to.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+#[allow(clippy::struct_excessive_bools)]
+struct Options {
+    tag_sigil: String,
+    ref_sigil: String,
+    file_sigil: String,
+    dir_sigil: String,
+    url_sigil: String,
+    comments_only: bool,
+    case_insensitive_labels: bool,
+    relative_paths: bool,
+    deny_unused: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            tag_sigil: "tag".to_owned(),
+            ref_sigil: "ref".to_owned(),
+            file_sigil: "file".to_owned(),
+            dir_sigil: "dir".to_owned(),
+            url_sigil: "url".to_owned(),
+            comments_only: false,
+            case_insensitive_labels: false,
+            relative_paths: false,
+            deny_unused: false,
+        }
+    }
+}
+
+impl Options {
+    // This function builds a scanner for the given paths with these settings.
+    fn scanner(&self, paths: Vec<PathBuf>, roots: Vec<PathBuf>) -> Scanner {
+        Scanner {
+            paths,
+            walk_options: walk::Options::default(),
+            tag_regex: compile_directive_regex(&self.tag_sigil),
+            tag_block_regex: Some(compile_block_regex(&self.tag_sigil)),
+            ref_regex: compile_directive_regex(&self.ref_sigil),
+            file_regex: compile_directive_regex(&self.file_sigil),
+            dir_regex: compile_directive_regex(&self.dir_sigil),
+            url_regex: compile_directive_regex(&self.url_sigil),
+            custom_regexes: Vec::new(),
+            comments_only: self.comments_only,
+            archives: false,
+            extractors: extractors::defaults(),
+            fallback_encoding: None,
+            case_insensitive_labels: self.case_insensitive_labels,
+            relative_paths: self.relative_paths,
+            roots,
+            memory_map: false,
+        }
+    }
+
+    // This function returns the rules for checking with these settings.
+    fn rules(&self) -> Rules {
+        Rules {
+            deny_unused: self.deny_unused,
+            ..Rules::default()
+        }
+    }
+}
+
+// This is a request to check some files.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Request {
+    files: Vec<File>,
+
+    #[serde(default)]
+    options: Options,
+}
+
+// This is the result of a check.
+#[derive(Serialize)]
+struct Report {
+    violations: Vec<Violation>,
+    suppressed: Vec<Violation>,
+}
+
+// This function returns a response with an error.
+fn error_response(message: &str) -> String {
+    json!({ "error": message }).to_string()
+}
+
+// This function checks an index and returns a response with the report.
+fn report(index: &Index, rules: &Rules) -> String {
+    let mut violations = index.check(rules);
+    let suppressed = index.suppress(&mut violations);
+
+    // Serializing plain data to a string can't fail, so the `unwrap` is safe.
+    serde_json::to_string(&Report {
+        violations,
+        suppressed,
+    })
+    .unwrap()
+}
+
+// This function checks files whose contents are given by the caller, for bindings to environments
+// without a filesystem (e.g., WebAssembly in a browser). The request is a JSON object with the
+// `files` (each with a `path` and `contents`) and optionally the `options`. The response is a JSON
+// object with the `violations` and the `suppressed` violations, or an `error` if the request is
+// invalid. File and directory references are checked against the given files.
+#[must_use]
+pub fn check(request: &str) -> String {
+    let request = match serde_json::from_str::<Request>(request) {
+        Ok(request) => request,
+        Err(error) => return error_response(&format!("Invalid request: {error}")),
+    };
+
+    let index = request.options.scanner(Vec::new(), Vec::new()).scan_files(
+        request
+            .files
+            .into_iter()
+            .map(|file| (file.path, file.contents.into_bytes()))
+            .collect(),
+    );
+    report(&index, &request.options.rules())
+}
+
+// This function checks the files in a directory, for bindings to other languages (see `ffi`). The
+// options are a JSON object like the `options` for `check`, and the response is the same too.
+// File and directory references are resolved from the directory.
+#[must_use]
+pub fn check_directory(directory: &Path, options: &str) -> String {
+    let options = match serde_json::from_str::<Options>(options) {
+        Ok(options) => options,
+        Err(error) => return error_response(&format!("Invalid options: {error}")),
+    };
+
+    let scanner = options.scanner(vec![directory.to_owned()], vec![directory.to_owned()]);
+    match scanner.scan(None) {
+        Ok((index, _)) => report(&index, &options.rules()),
+        Err(error) => error_response(&error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bindings::check;
+    use serde_json::{Value, json};
+
+    // This function checks files with the given paths and contents.
+    fn check_files(files: &[(&str, &str)], options: &Value) -> Value {
+        let files = files
+            .iter()
+            .map(|(path, contents)| json!({ "path": path, "contents": contents.replace('?', "") }))
+            .collect::<Vec<_>>();
+        serde_json::from_str(&check(
+            &json!({ "files": files, "options": options }).to_string(),
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn check_valid() {
+        let report = check_files(
+            &[
+                (
+                    "src/a.rs",
+                    "// [?tag:foo]\n// [?file:src/b.rs] [?dir:src]\n",
+                ),
+                ("src/b.rs", "// [?ref:foo]\n"),
+            ],
+            &json!({}),
+        );
+
+        assert_eq!(report["violations"], json!([]));
+    }
+
+    #[test]
+    fn check_violations() {
+        let report = check_files(
+            &[("a.rs", "// [?ref:foo]\n// [?file:b.rs]\n// [?tag:bar]\n")],
+            &json!({ "deny_unused": true }),
+        );
+
+        let kinds = report["violations"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|violation| violation["kind"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec!["dangling_ref", "invalid_file_ref", "unused_tag"]
+        );
+    }
+
+    #[test]
+    fn check_invalid_request() {
+        let report = serde_json::from_str::<Value>(&check("{\"files\": 3}")).unwrap();
+
+        assert!(report["error"].is_string());
+    }
+}
@@ -0,0 +1,253 @@
+use crate::directive::Directives;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::{self, Metadata},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+// This is the name of the cache file which is used by default.
+pub const DEFAULT_PATH: &str = ".tagref-cache";
+
+// This struct identifies a particular version of a file's contents without reading it.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Fingerprint {
+    pub modified: Option<SystemTime>,
+    pub size: u64,
+}
+
+impl Fingerprint {
+    #[must_use]
+    pub fn new(metadata: &Metadata) -> Self {
+        Self {
+            modified: metadata.modified().ok(),
+            size: metadata.len(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct Entry {
+    fingerprint: Fingerprint,
+    directives: Directives,
+}
+
+// This struct maps file paths to the directives that were parsed from them. The `key` captures
+// everything that affects parsing other than the file contents (e.g., the sigils), so a cache
+// produced with a different configuration is discarded rather than reused.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Cache {
+    key: String,
+    entries: HashMap<PathBuf, Entry>,
+}
+
+impl Cache {
+    #[must_use]
+    pub fn new(key: &str) -> Self {
+        Self {
+            key: key.to_owned(),
+            entries: HashMap::new(),
+        }
+    }
+
+    // This function reads a cache from a string. If the string is malformed or was produced with
+    // a different key, an empty cache is returned.
+    #[must_use]
+    pub fn decode(contents: &str, key: &str) -> Self {
+        match serde_json::from_str::<Self>(contents) {
+            Ok(mut cache) if cache.key == key => {
+                // Each label is read with its own copy of its path, so they're shared again.
+                for entry in cache.entries.values_mut() {
+                    entry.directives.share_paths();
+                }
+                cache
+            }
+            _ => Self::new(key),
+        }
+    }
+
+    // This function reads a cache from a file. If the file doesn't exist or can't be used, an
+    // empty cache is returned.
+    #[must_use]
+    pub fn load(path: &Path, key: &str) -> Self {
+        fs::read_to_string(path).map_or_else(|_| Self::new(key), |s| Self::decode(&s, key))
+    }
+
+    // This function writes the cache to a file.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        // Serializing plain data to a string can't fail, so the `unwrap` is safe.
+        fs::write(path, serde_json::to_string(self).unwrap()).map_err(|error| {
+            format!(
+                "Unable to write cache file {}: {error}",
+                path.to_string_lossy(),
+            )
+        })
+    }
+
+    // This function returns the cached directives for a file, if they are still fresh.
+    #[must_use]
+    pub fn get(&self, path: &Path, fingerprint: &Fingerprint) -> Option<&Directives> {
+        self.entries
+            .get(path)
+            .filter(|entry| entry.fingerprint == *fingerprint)
+            .map(|entry| &entry.directives)
+    }
+
+    // This function records the directives parsed from a file.
+    pub fn insert(&mut self, path: &Path, fingerprint: Fingerprint, directives: Directives) {
+        self.entries.insert(
+            path.to_owned(),
+            Entry {
+                fingerprint,
+                directives,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cache::{Cache, Fingerprint},
+        directive::{Directive, Directives, Type},
+    };
+    use std::{
+        collections::BTreeMap,
+        path::Path,
+        time::{Duration, SystemTime},
+    };
+
+    fn directives(path: &Path) -> Directives {
+        Directives {
+            tags: vec![Directive {
+                r#type: Type::Tag,
+                label: "tag1".to_owned(),
+                path: path.into(),
+                line_number: 1,
+                column: 1,
+                span: 0..0,
+                description: None,
+                attributes: BTreeMap::new(),
+                cell: None,
+            }],
+            refs: vec![],
+            files: vec![],
+            dirs: vec![],
+            urls: vec![],
+            custom: vec![],
+            undecodable: vec![],
+            too_large: vec![],
+            suppressions: vec![],
+            aliases: vec![],
+            anchors: vec![],
+            pins: vec![],
+            links: vec![],
+            headings: vec![],
+        }
+    }
+
+    #[test]
+    fn get_fresh() {
+        let path = Path::new("file.rs");
+        let fingerprint = Fingerprint {
+            modified: Some(SystemTime::UNIX_EPOCH),
+            size: 42,
+        };
+
+        let mut cache = Cache::new("key");
+        cache.insert(path, fingerprint.clone(), directives(path));
+
+        let cached = cache.get(path, &fingerprint).unwrap();
+        assert_eq!(cached.tags.len(), 1);
+        assert_eq!(cached.tags[0].label, "tag1");
+    }
+
+    #[test]
+    fn get_stale() {
+        let path = Path::new("file.rs");
+        let fingerprint = Fingerprint {
+            modified: Some(SystemTime::UNIX_EPOCH),
+            size: 42,
+        };
+
+        let mut cache = Cache::new("key");
+        cache.insert(path, fingerprint, directives(path));
+
+        assert!(
+            cache
+                .get(
+                    path,
+                    &Fingerprint {
+                        modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1)),
+                        size: 42,
+                    },
+                )
+                .is_none(),
+        );
+        assert!(
+            cache
+                .get(
+                    path,
+                    &Fingerprint {
+                        modified: Some(SystemTime::UNIX_EPOCH),
+                        size: 43,
+                    },
+                )
+                .is_none(),
+        );
+    }
+
+    #[test]
+    fn decode_round_trip() {
+        let path = Path::new("file.rs");
+        let fingerprint = Fingerprint {
+            modified: Some(SystemTime::UNIX_EPOCH),
+            size: 42,
+        };
+
+        let mut cache = Cache::new("key");
+        cache.insert(path, fingerprint.clone(), directives(path));
+        let encoded = serde_json::to_string(&cache).unwrap();
+
+        assert!(
+            Cache::decode(&encoded, "key")
+                .get(path, &fingerprint)
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn decode_different_key() {
+        let path = Path::new("file.rs");
+        let fingerprint = Fingerprint {
+            modified: Some(SystemTime::UNIX_EPOCH),
+            size: 42,
+        };
+
+        let mut cache = Cache::new("key");
+        cache.insert(path, fingerprint.clone(), directives(path));
+        let encoded = serde_json::to_string(&cache).unwrap();
+
+        assert!(
+            Cache::decode(&encoded, "other key")
+                .get(path, &fingerprint)
+                .is_none(),
+        );
+    }
+
+    #[test]
+    fn decode_malformed() {
+        let path = Path::new("file.rs");
+        let fingerprint = Fingerprint {
+            modified: Some(SystemTime::UNIX_EPOCH),
+            size: 42,
+        };
+
+        assert!(
+            Cache::decode("not json", "key")
+                .get(path, &fingerprint)
+                .is_none(),
+        );
+    }
+}
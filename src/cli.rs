@@ -0,0 +1,1227 @@
+use crate::{completions::Shell, failure, format::Format, hook::Hook, serve};
+use clap::{
+    ArgAction, ArgMatches, Args, Parser, Subcommand as ClapSubcommand, ValueEnum,
+    parser::ValueSource,
+};
+use regex::Regex;
+use std::{
+    env,
+    io::{self, IsTerminal},
+    path::PathBuf,
+    process::exit,
+    time::Duration,
+};
+use tagref::{
+    cache,
+    directive::{Type, compile_custom_regex, compile_directive_regex},
+    expiry, walk,
+};
+
+// This struct represents the command-line arguments.
+#[derive(Parser)]
+#[command(
+    about = concat!(
+        env!("CARGO_PKG_DESCRIPTION"),
+        "\n\n",
+        "You can annotate your code with tags like [tag:foo] and reference them like [ref:foo]. ",
+        "You can also reference files like [file:src/main.rs] and directories like [dir:src]. ",
+        "Tagref checks that tags are unique and that references are not dangling.\n\n",
+        "More information can be found at: ",
+        env!("CARGO_PKG_HOMEPAGE"),
+    ),
+    version,
+    disable_version_flag = true
+)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Cli {
+    #[arg(short, long, help = "Print version", action = ArgAction::Version)]
+    pub _version: Option<bool>,
+
+    #[arg(
+        short,
+        long,
+        value_name = "PATH",
+        help = "Load settings from a configuration file [default: tagref.toml]"
+    )]
+    pub config: Option<PathBuf>,
+
+    #[arg(
+        long,
+        action = ArgAction::Count,
+        help = "Print additional information to STDERR (repeat for more detail)"
+    )]
+    pub verbose: u8,
+
+    #[arg(
+        short,
+        long,
+        conflicts_with = "verbose",
+        help = "Only print errors, and nothing at all if the check succeeds"
+    )]
+    pub quiet: bool,
+
+    #[arg(
+        short,
+        long = "path",
+        value_name = "PATH",
+        help = "Add a directory to scan",
+        default_value = "."
+    )]
+    pub paths: Vec<PathBuf>,
+
+    #[arg(
+        long = "root",
+        value_name = "DIR",
+        conflicts_with = "paths",
+        help = "Add a project root to scan, whose file and directory references are resolved from \
+                it"
+    )]
+    pub roots: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Only match references with tags in the same project root (see --root)"
+    )]
+    pub isolate_roots: bool,
+
+    #[arg(
+        short,
+        long,
+        help = "Set the sigil used for tags",
+        default_value = "tag"
+    )]
+    pub tag_sigil: String,
+
+    #[arg(
+        short,
+        long,
+        help = "Set the sigil used for tag references",
+        default_value = "ref"
+    )]
+    pub ref_sigil: String,
+
+    #[arg(
+        short,
+        long,
+        help = "Set the sigil used for file references",
+        default_value = "file"
+    )]
+    pub file_sigil: String,
+
+    #[arg(
+        short,
+        long,
+        help = "Set the sigil used for directory references",
+        default_value = "dir"
+    )]
+    pub dir_sigil: String,
+
+    #[arg(
+        short,
+        long,
+        help = "Set the sigil used for URL references",
+        default_value = "url"
+    )]
+    pub url_sigil: String,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Set the output format",
+        default_value = "text"
+    )]
+    pub format: Format,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "WHEN",
+        help = "Set when to use colors in the output (`auto` uses them on terminals unless \
+                NO_COLOR is set)",
+        default_value = "auto"
+    )]
+    pub color: Color,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "BASE",
+        help = "Report paths relative to the scanned path containing them (`root`) or to the \
+                working directory (`cwd`), rather than as they were scanned"
+    )]
+    pub relative_to: Option<RelativeTo>,
+
+    #[arg(long, conflicts_with = "relative_to", help = "Report absolute paths")]
+    pub absolute_paths: bool,
+
+    #[arg(
+        long,
+        help = "Only match labels inside comments in recognized file types"
+    )]
+    pub comments_only: bool,
+
+    #[arg(
+        long,
+        help = "Scan the files in archives (.tar, .tar.gz, .tgz, and .zip) as if they were \
+                directories"
+    )]
+    pub scan_archives: bool,
+
+    #[arg(
+        long,
+        value_name = "ENCODING",
+        help = "Set the encoding for files which aren't UTF-8 and have no byte order mark (e.g., \
+                latin1)"
+    )]
+    pub encoding: Option<String>,
+
+    #[arg(
+        long,
+        overrides_with = "case_sensitive_labels",
+        help = "Match tags and references regardless of the case of their labels"
+    )]
+    pub case_insensitive_labels: bool,
+
+    #[arg(
+        long,
+        overrides_with = "case_insensitive_labels",
+        help = "Only match tags and references whose labels have the same case (default)"
+    )]
+    pub case_sensitive_labels: bool,
+
+    #[arg(
+        long,
+        overrides_with = "no_relative_paths",
+        help = "Resolve file and directory paths starting with ./ or ../ from the file containing \
+                them"
+    )]
+    pub relative_paths: bool,
+
+    #[arg(
+        long,
+        overrides_with = "relative_paths",
+        help = "Resolve all file and directory paths from the working directory (default)"
+    )]
+    pub no_relative_paths: bool,
+
+    #[arg(long, help = "Report duplicate tags as warnings rather than errors")]
+    pub allow_duplicates: bool,
+
+    #[arg(
+        long,
+        help = "Check that URL references point to reachable URLs (requires network access)"
+    )]
+    pub check_urls: bool,
+
+    #[arg(
+        long,
+        help = "Check that issue labels refer to open issues in their trackers (requires network \
+                access)"
+    )]
+    pub check_issues: bool,
+
+    #[arg(
+        long,
+        help = "Only let file and directory references point to files tracked by Git, checking \
+                them against the Git index rather than the filesystem"
+    )]
+    pub tracked_only: bool,
+
+    #[arg(
+        long,
+        help = "Scan files even if they are ignored by .gitignore, .ignore, and similar files"
+    )]
+    pub no_ignore: bool,
+
+    #[arg(
+        long,
+        overrides_with = "no_follow_symlinks",
+        help = "Follow symlinks when scanning, visiting each file only once"
+    )]
+    pub follow_symlinks: bool,
+
+    #[arg(
+        long,
+        overrides_with = "follow_symlinks",
+        help = "Skip symlinks when scanning (default)"
+    )]
+    pub no_follow_symlinks: bool,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Reuse the labels parsed from unchanged files via a cache file",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = cache::DEFAULT_PATH
+    )]
+    pub cache: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Ignore the violations recorded in a baseline file [default: .tagref-baseline.json]"
+    )]
+    pub baseline: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "SIZE",
+        value_parser = walk::parse_size,
+        help = "Skip files larger than this size (e.g., `512K` or `20M`), or 0 for no limit \
+                [default: 5M]"
+    )]
+    pub max_filesize: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "DURATION",
+        value_parser = parse_timeout,
+        help = "Give up if the command takes longer than this (e.g., `90s` or `10m`), except for \
+                `watch`, `lsp`, `serve`, and `tui`"
+    )]
+    pub timeout: Option<Duration>,
+
+    #[command(subcommand)]
+    pub command: Option<Subcommand>,
+}
+
+#[derive(Args, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct CheckArgs {
+    #[arg(
+        long,
+        value_name = "REV",
+        help = "Only report problems in files which changed since a Git revision"
+    )]
+    pub changed_since: Option<String>,
+
+    #[arg(long, help = "Report tags which aren't referenced anywhere as errors")]
+    pub deny_unused: bool,
+
+    #[arg(
+        long,
+        help = "Report tag references which use aliases rather than the labels of the tags as \
+                errors"
+    )]
+    pub deny_deprecated_aliases: bool,
+
+    #[arg(
+        long,
+        help = "Report directory references to empty directories as errors"
+    )]
+    pub deny_empty_dirs: bool,
+
+    #[arg(
+        long,
+        help = "Report file and directory references whose case differs from the paths on disk"
+    )]
+    pub strict_path_case: bool,
+
+    #[arg(
+        long,
+        help = "Check that links in Markdown files point to files or directories"
+    )]
+    pub markdown_links: bool,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Report tags which don't have a section in the Markdown files in this directory"
+    )]
+    pub docs_dir: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Only fail if there are more than this many violations [default: 0]"
+    )]
+    pub max_violations: Option<usize>,
+
+    #[arg(
+        long,
+        help = "Report warnings as errors, so they cause the check to fail"
+    )]
+    pub fail_on_warn: bool,
+
+    #[arg(
+        long = "extern",
+        value_name = "ALIAS:PATH",
+        help = "Check the references to another repository against its tag index file"
+    )]
+    pub externs: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Check the files in two passes without keeping all the labels in memory, for very \
+                large trees"
+    )]
+    pub streaming: bool,
+}
+
+#[derive(Args)]
+pub struct CheckStdinArgs {
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "The path of the file whose contents are read from standard input"
+    )]
+    pub path: PathBuf,
+}
+
+#[derive(Args)]
+pub struct ServeArgs {
+    #[arg(
+        long,
+        value_name = "HOST:PORT",
+        default_value = serve::DEFAULT_ADDRESS,
+        help = "The address to listen on"
+    )]
+    pub address: String,
+
+    #[arg(
+        long,
+        conflicts_with = "address",
+        help = "Read requests from standard input and write responses to standard output, one \
+                JSON-RPC message per line, instead of listening for HTTP requests"
+    )]
+    pub stdio: bool,
+}
+
+#[derive(Args)]
+pub struct ListTagsArgs {
+    #[arg(
+        long,
+        value_name = "NAMESPACE",
+        help = "Only list the tags in this namespace, including nested namespaces"
+    )]
+    pub namespace: Option<String>,
+
+    #[arg(short, long, help = "Show the descriptions of the tags")]
+    pub long: bool,
+
+    #[arg(
+        long,
+        conflicts_with = "long",
+        help = "Only print the labels of the tags, one per line"
+    )]
+    pub labels: bool,
+}
+
+#[derive(Args)]
+pub struct GrepArgs {
+    #[arg(help = "The regular expression to match against the labels")]
+    pub pattern: String,
+
+    #[arg(
+        long = "type",
+        value_enum,
+        value_name = "TYPE",
+        help = "Only search the labels of this type (can be given more than once)"
+    )]
+    pub types: Vec<LabelType>,
+
+    #[arg(short, long, help = "Match the pattern regardless of case")]
+    pub ignore_case: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum LabelType {
+    Tag,
+    Ref,
+    File,
+    Dir,
+    Url,
+    Custom,
+}
+
+impl LabelType {
+    // This function determines whether a directive is of this type.
+    pub fn includes(self, r#type: &Type) -> bool {
+        matches!(
+            (self, r#type),
+            (Self::Tag, Type::Tag)
+                | (Self::Ref, Type::Ref)
+                | (Self::File, Type::File)
+                | (Self::Dir, Type::Dir)
+                | (Self::Url, Type::Url)
+                | (Self::Custom, Type::Custom(_)),
+        )
+    }
+}
+
+#[derive(Args)]
+pub struct CompletionsArgs {
+    #[arg(value_enum, help = "The shell to generate the completion script for")]
+    pub shell: Shell,
+}
+
+#[derive(Args)]
+pub struct ListCustomArgs {
+    #[arg(long, help = "Only list the custom labels with this sigil")]
+    pub sigil: Option<String>,
+}
+
+#[derive(Args)]
+pub struct ListUnusedArgs {
+    #[arg(
+        long,
+        help = "Exit with an error status code if any tags are unreferenced"
+    )]
+    pub fail_if_any: bool,
+}
+
+#[derive(Args)]
+pub struct LintTagsArgs {
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Set the maximum number of edits between similar labels, or 0 to only report labels \
+                which differ by case, hyphens, and underscores",
+        default_value_t = 1
+    )]
+    pub max_distance: usize,
+}
+
+#[derive(Args)]
+pub struct HotspotsArgs {
+    #[arg(
+        long,
+        value_name = "N",
+        help = "List the tags with more than this many references [default: 10]"
+    )]
+    pub threshold: Option<usize>,
+}
+
+#[derive(Args)]
+pub struct OwnersArgs {
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Read the owners from this CODEOWNERS file instead of the one in .github, the \
+                working directory, or docs"
+    )]
+    pub codeowners: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Only list the tags which are referenced from files with other owners"
+    )]
+    pub shared: bool,
+}
+
+#[derive(Args)]
+pub struct ExpiringArgs {
+    #[arg(
+        long,
+        value_name = "DURATION",
+        value_parser = expiry::parse_days,
+        help = "List the labels which expire within this many days (e.g., `30d`) or weeks (e.g., \
+                `4w`), including the ones which already expired",
+        default_value = "30d"
+    )]
+    pub within: i64,
+}
+
+#[derive(Args)]
+pub struct TodoArgs {
+    #[arg(
+        long,
+        value_enum,
+        help = "Group the items by label, by the `owner` attribute or CODEOWNERS, or by the \
+                `expires` attribute",
+        default_value = "label"
+    )]
+    pub group_by: TodoGroupBy,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Read the owners from this CODEOWNERS file instead of the one in .github, the \
+                working directory, or docs"
+    )]
+    pub codeowners: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum TodoGroupBy {
+    Label,
+    Owner,
+    Expiry,
+}
+
+#[derive(Args)]
+pub struct StatsArgs {
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Set the number of tags and files to show in the rankings",
+        default_value_t = 10
+    )]
+    pub top: usize,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "GROUPING",
+        help = "Count the labels in each top-level directory, file extension, or tag namespace \
+                instead"
+    )]
+    pub group_by: Option<GroupBy>,
+
+    #[arg(
+        long,
+        value_enum,
+        requires = "group_by",
+        help = "Set the order of the groups (counts are listed from highest to lowest)",
+        default_value = "labels"
+    )]
+    pub sort: SortBy,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Color {
+    Auto,
+    Always,
+    Never,
+}
+
+impl Color {
+    // This function determines whether to print colored output. Following https://no-color.org,
+    // a nonempty `NO_COLOR` environment variable turns colors off unless they're forced.
+    pub fn enabled(self) -> bool {
+        match self {
+            Self::Always => true,
+            Self::Never => false,
+            Self::Auto => {
+                io::stdout().is_terminal()
+                    && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum RelativeTo {
+    Root,
+    Cwd,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum GroupBy {
+    Dir,
+    Extension,
+    Namespace,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum SortBy {
+    Name,
+    Tags,
+    Refs,
+    Labels,
+    Files,
+}
+
+#[derive(Args)]
+pub struct OpenArgs {
+    #[arg(help = "The name of the tag")]
+    pub label: String,
+
+    #[arg(
+        long = "ref",
+        value_name = "N",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Open the Nth reference to the tag (counting from 1) instead of the tag"
+    )]
+    pub r#ref: Option<u64>,
+}
+
+#[derive(Args)]
+pub struct RenameArgs {
+    #[arg(help = "The current name of the tag")]
+    pub old: String,
+
+    #[arg(help = "The new name of the tag")]
+    pub new: String,
+
+    #[arg(long, help = "Print the edits without applying them")]
+    pub dry_run: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum NewTagPrefix {
+    Ulid,
+    Date,
+}
+
+#[derive(Args)]
+pub struct NewTagArgs {
+    #[arg(
+        help = "The name of the tag, which gets a number appended if it's already taken, or its \
+                description if it's named automatically"
+    )]
+    pub name: String,
+
+    #[arg(long, value_enum, help = "Prefix the name with a ULID or today's date")]
+    pub prefix: Option<NewTagPrefix>,
+
+    #[arg(
+        long,
+        overrides_with = "no_auto",
+        conflicts_with = "prefix",
+        help = "Name the tag with a short random identifier, and describe it with the argument"
+    )]
+    pub auto: bool,
+
+    #[arg(
+        long,
+        overrides_with = "auto",
+        help = "Name the tag with the argument, even if `auto-tag-names` is set in the \
+                configuration file"
+    )]
+    pub no_auto: bool,
+
+    #[arg(
+        long,
+        value_name = "FILE:LINE",
+        help = "Insert the tag in a comment on a new line before a line of a file"
+    )]
+    pub edit: Option<String>,
+
+    #[arg(
+        long,
+        requires = "edit",
+        help = "Print the edits without applying them"
+    )]
+    pub dry_run: bool,
+}
+
+#[derive(Args)]
+pub struct ChangedRefsArgs {
+    #[arg(help = "The Git revision to compare with, e.g., the base branch of a pull request")]
+    pub revision: String,
+}
+
+#[derive(Args)]
+pub struct DiffArgs {
+    #[arg(help = "The Git revision to compare from")]
+    pub from: String,
+
+    #[arg(help = "The Git revision to compare to [default: the working tree]")]
+    pub to: Option<String>,
+}
+
+#[derive(Args)]
+pub struct RepinArgs {
+    #[arg(help = "Only repin the references to these tags")]
+    pub labels: Vec<String>,
+
+    #[arg(long, help = "Print the edits without applying them")]
+    pub dry_run: bool,
+}
+
+#[derive(Args)]
+pub struct MvArgs {
+    #[arg(
+        help = "The file or directory to move",
+        required_unless_present = "from_git",
+        conflicts_with = "from_git"
+    )]
+    pub from: Option<PathBuf>,
+
+    #[arg(help = "Where to move it", required_unless_present = "from_git")]
+    pub to: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Update the references to files renamed in the Git index instead of moving anything"
+    )]
+    pub from_git: bool,
+
+    #[arg(long, help = "Print the edits without applying them")]
+    pub dry_run: bool,
+}
+
+#[derive(Args)]
+pub struct FixArgs {
+    #[arg(short, long, help = "Apply all the fixes without prompting")]
+    pub yes: bool,
+
+    #[arg(
+        long,
+        help = "Also fix tag references which have exactly one suggested tag"
+    )]
+    pub refs: bool,
+
+    #[arg(long, help = "Print the edits without applying them")]
+    pub dry_run: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum MigrateFrom {
+    TodoComments,
+    DoxygenRefs,
+    CustomRegex,
+}
+
+#[derive(Args)]
+pub struct MigrateArgs {
+    #[arg(long, value_enum, help = "Set the annotation scheme to migrate from")]
+    pub from: MigrateFrom,
+
+    #[arg(
+        long,
+        value_name = "REGEX",
+        help = "With `--from custom-regex`, match the annotations which become tags, with a group \
+                for the label"
+    )]
+    pub tag_pattern: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "REGEX",
+        help = "With `--from custom-regex`, match the annotations which become references, with a \
+                group for the label"
+    )]
+    pub ref_pattern: Option<String>,
+
+    #[arg(long, help = "Print the edits without applying them")]
+    pub dry_run: bool,
+}
+
+#[derive(Args)]
+pub struct UndoArgs {
+    #[arg(long, help = "Print the edits without applying them")]
+    pub dry_run: bool,
+}
+
+#[derive(Args)]
+pub struct ShowArgs {
+    #[arg(help = "The name of the tag")]
+    pub label: String,
+
+    #[arg(
+        short = 'C',
+        long,
+        value_name = "LINES",
+        help = "Set the number of lines to show before and after each label",
+        default_value_t = 2
+    )]
+    pub context: usize,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum GraphSyntax {
+    Dot,
+    Mermaid,
+}
+
+#[derive(Args)]
+pub struct TrendArgs {
+    #[arg(
+        help = "The range of Git revisions to measure, e.g., `v1.0..HEAD`, or a single revision \
+                for all of its history"
+    )]
+    pub range: String,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Only measure every Nth commit, counting back from the newest",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub every: u64,
+}
+
+#[derive(Args)]
+pub struct BadgeArgs {
+    #[command(flatten)]
+    pub check: CheckArgs,
+
+    #[arg(
+        long,
+        help = "Print an SVG image rather than JSON for a shields.io endpoint"
+    )]
+    pub svg: bool,
+}
+
+#[derive(Args)]
+pub struct GraphArgs {
+    #[arg(
+        long,
+        value_enum,
+        help = "Set the graph description language",
+        default_value = "dot"
+    )]
+    pub syntax: GraphSyntax,
+}
+
+#[derive(ClapSubcommand)]
+pub enum BaselineCommand {
+    #[command(about = "Record the current violations in the baseline file")]
+    Write,
+}
+
+#[derive(Args)]
+pub struct BaselineArgs {
+    #[command(subcommand)]
+    pub command: BaselineCommand,
+}
+
+#[derive(ClapSubcommand)]
+pub enum IndexCommand {
+    #[command(
+        about = "Write the tag index to a file, for checking references from other repositories"
+    )]
+    Write {
+        #[arg(help = "The file to write")]
+        path: PathBuf,
+    },
+}
+
+#[derive(Args)]
+pub struct IndexArgs {
+    #[command(subcommand)]
+    pub command: IndexCommand,
+}
+
+#[derive(Args)]
+pub struct BenchArgs {
+    #[arg(help = "The directory to generate the tree in, which must be empty or not exist yet")]
+    pub dir: PathBuf,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Set the number of files",
+        default_value_t = 1000
+    )]
+    pub files: usize,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Set the number of lines in each file, apart from the labels",
+        default_value_t = 200
+    )]
+    pub lines: usize,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Set the number of tags in each file",
+        default_value_t = 5
+    )]
+    pub tags: usize,
+}
+
+#[derive(Args)]
+pub struct InstallHookArgs {
+    #[arg(
+        long,
+        value_enum,
+        help = "The Git hook to install",
+        default_value = "pre-commit"
+    )]
+    pub hook: Hook,
+
+    #[arg(
+        long,
+        help = "Replace an existing hook, even if it's managed by another tool"
+    )]
+    pub force: bool,
+}
+
+#[derive(Args)]
+pub struct UninstallHookArgs {
+    #[arg(
+        long,
+        value_enum,
+        help = "The Git hook to remove",
+        default_value = "pre-commit"
+    )]
+    pub hook: Hook,
+}
+
+#[derive(Args)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct InitArgs {
+    #[arg(long, help = "Replace any existing files and hook")]
+    pub force: bool,
+
+    #[arg(
+        long,
+        help = "Convert ad-hoc to-do items like TODO(label) into ones with labels, asking about \
+                each label"
+    )]
+    pub convert_todos: bool,
+
+    #[arg(
+        short,
+        long,
+        help = "Convert all the ad-hoc to-do items without prompting"
+    )]
+    pub yes: bool,
+
+    #[arg(long, help = "Print what would be done without doing it")]
+    pub dry_run: bool,
+}
+
+#[derive(ClapSubcommand)]
+pub enum Subcommand {
+    #[command(
+        about = "Set up Tagref in a repository with a configuration file, a Git hook, and a GitHub \
+                 Actions workflow"
+    )]
+    Init(InitArgs),
+
+    #[command(about = "Check all the tags and references (default)")]
+    Check(CheckArgs),
+
+    #[command(about = "Check all the tags and references, and print a status badge for the result")]
+    Badge(BadgeArgs),
+
+    #[command(
+        about = "Check the labels in standard input as if they were the contents of a file, \
+                 reporting only the violations which involve that file"
+    )]
+    CheckStdin(CheckStdinArgs),
+
+    #[command(about = "List all the tags")]
+    ListTags(ListTagsArgs),
+
+    #[command(about = "List all the tag references")]
+    ListRefs,
+
+    #[command(about = "List all the file references")]
+    ListFiles,
+
+    #[command(about = "List all the directory references")]
+    ListDirs,
+
+    #[command(about = "List all the URL references")]
+    ListUrls,
+
+    #[command(about = "List all the custom labels")]
+    ListCustom(ListCustomArgs),
+
+    #[command(about = "List the unreferenced tags")]
+    ListUnused(ListUnusedArgs),
+
+    #[command(about = "Search the labels (not the rest of the files) for a regular expression")]
+    Grep(GrepArgs),
+
+    #[command(about = "Report pairs of tags whose labels are so similar that one may be a typo")]
+    LintTags(LintTagsArgs),
+
+    #[command(
+        about = "List the owners of each tag and the owners of the files which reference it, \
+                 according to CODEOWNERS"
+    )]
+    Owners(OwnersArgs),
+
+    #[command(about = "List the labels which expire soon, according to their `expires` attributes")]
+    Expiring(ExpiringArgs),
+
+    #[command(
+        about = "List the tags and references on the same lines as TODO, FIXME, XXX, and HACK \
+                 markers, grouped by label, owner, or expiry date"
+    )]
+    Todo(TodoArgs),
+
+    #[command(about = "Print metrics about the tags and references")]
+    Stats(StatsArgs),
+
+    #[command(
+        about = "List the tags with more references than a threshold, with the referencing files \
+                 grouped by directory"
+    )]
+    Hotspots(HotspotsArgs),
+
+    #[command(about = "Show a tag and all the references to it, with the lines around them")]
+    Show(ShowArgs),
+
+    #[command(
+        about = "Open a tag or a reference to it in an editor, at the line where it's written"
+    )]
+    Open(OpenArgs),
+
+    #[command(about = "Rename a tag and all the references to it")]
+    Rename(RenameArgs),
+
+    #[command(
+        about = "Generate a unique name for a new tag, and print the tag and a reference to it"
+    )]
+    NewTag(NewTagArgs),
+
+    #[command(about = "Pin references to the current content of their tags after reviewing them")]
+    Repin(RepinArgs),
+
+    #[command(
+        about = "List the references to tags which moved, changed, or were removed since a Git \
+                 revision"
+    )]
+    ChangedRefs(ChangedRefsArgs),
+
+    #[command(
+        about = "List the tags and references which were added, removed, or renamed between two \
+                 Git revisions, and the violations which were introduced or fixed"
+    )]
+    Diff(DiffArgs),
+
+    #[command(about = "Move a file or directory and update the references to it")]
+    Mv(MvArgs),
+
+    #[command(about = "Update file and directory references to files and directories which moved")]
+    Fix(FixArgs),
+
+    #[command(
+        about = "Rewrite the annotations of another cross-referencing scheme as tags and references"
+    )]
+    Migrate(MigrateArgs),
+
+    #[command(
+        about = "Revert the last command which rewrote files (`rename`, `new-tag --edit`, `repin`, \
+                 `mv`, `fix`, `migrate`, or `init --convert-todos`)"
+    )]
+    Undo(UndoArgs),
+
+    #[command(about = "Browse the tags and the references to them interactively")]
+    Tui,
+
+    #[command(about = "Print a graph of the tags and the files which reference them")]
+    Graph(GraphArgs),
+
+    #[command(
+        about = "Print the numbers of labels and violations as of each commit in a range, to \
+                 track trends"
+    )]
+    Trend(TrendArgs),
+
+    #[command(
+        about = "Print a Markdown table of the tags in Rust doc comments and the items they \
+                 document"
+    )]
+    Rustdoc,
+
+    #[command(about = "Manage the baseline of known violations")]
+    Baseline(BaselineArgs),
+
+    #[command(about = "Export the tags for other repositories to reference")]
+    Index(IndexArgs),
+
+    #[command(about = "Check all the tags and references whenever files change")]
+    Watch,
+
+    #[command(about = "Run a language server over standard input and output")]
+    Lsp,
+
+    #[command(about = "Answer queries about the tags and references over HTTP with JSON-RPC")]
+    Serve(ServeArgs),
+
+    #[command(about = "Install a Git hook which checks the files that changed")]
+    InstallHook(InstallHookArgs),
+
+    #[command(about = "Remove a Git hook installed by Tagref")]
+    UninstallHook(UninstallHookArgs),
+
+    #[command(
+        about = "Check the revisions pushed to a repository, as a server-side pre-receive hook"
+    )]
+    PreReceive,
+
+    #[command(about = "Print a shell completion script")]
+    Completions(CompletionsArgs),
+
+    #[command(
+        about = "Generate a synthetic tree of files for measuring performance",
+        hide = true
+    )]
+    Bench(BenchArgs),
+}
+
+// This function determines whether an argument was given explicitly on the command line.
+pub fn from_command_line(matches: &ArgMatches, id: &str) -> bool {
+    matches.value_source(id) == Some(ValueSource::CommandLine)
+}
+
+// This function determines the sigil for writing a kind of directive. The one in the configuration
+// file is used, unless another was given on the command line.
+pub fn sigil(matches: &ArgMatches, id: &str, sigil: &str, config_sigil: Option<&str>) -> String {
+    config_sigil
+        .filter(|_| !from_command_line(matches, id))
+        .unwrap_or(sigil)
+        .to_owned()
+}
+
+// This function determines the regular expression for a kind of directive. A sigil given on the
+// command line takes precedence over a custom regular expression in the configuration file, which
+// in turn takes precedence over a sigil in the configuration file.
+pub fn directive_regex(
+    matches: &ArgMatches,
+    id: &str,
+    sigil: &str,
+    config_sigil: Option<&str>,
+    config_regex: Option<&str>,
+) -> Result<Regex, String> {
+    if from_command_line(matches, id) {
+        return Ok(compile_directive_regex(sigil));
+    }
+
+    match config_regex {
+        Some(pattern) => compile_custom_regex(pattern),
+        None => Ok(compile_directive_regex(config_sigil.unwrap_or(sigil))),
+    }
+}
+
+// This function parses a timeout in seconds, optionally followed by `s` (seconds), `m` (minutes),
+// or `h` (hours), as in `90s` or `10m`.
+pub fn parse_timeout(text: &str) -> Result<Duration, String> {
+    let (number, unit) = match text.strip_suffix(['s', 'm', 'h']) {
+        Some(number) => (number, &text[number.len()..]),
+        None => (text, "s"),
+    };
+    let number = number.parse::<u64>().map_err(|_| {
+        format!("Invalid timeout `{text}`. Expected a number of seconds, like `90s` or `10m`.")
+    })?;
+    Ok(Duration::from_secs(match unit {
+        "m" => number.saturating_mul(60),
+        "h" => number.saturating_mul(60 * 60),
+        _ => number,
+    }))
+}
+
+// This function reports a problem with the command-line arguments and exits. Clap would exit with
+// code 2, but that's reserved for dangling references, so these are treated like configuration
+// errors instead. Requests for help or the version information aren't errors at all.
+pub fn usage_error(error: &clap::Error) -> ! {
+    let _ = error.print();
+    exit(if error.use_stderr() {
+        failure::ERROR
+    } else {
+        0
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::{Cli, parse_timeout};
+    use clap::CommandFactory;
+    use std::time::Duration;
+
+    #[test]
+    fn verify_cli() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn parse_timeout_units() {
+        assert_eq!(parse_timeout("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_timeout("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_timeout("10m"), Ok(Duration::from_mins(10)));
+        assert_eq!(parse_timeout("1h"), Ok(Duration::from_hours(1)));
+        assert!(parse_timeout("soon").is_err());
+    }
+}
@@ -0,0 +1,1251 @@
+use crate::{
+    badge::Badge,
+    cli::{
+        BaselineArgs, BaselineCommand, BenchArgs, ChangedRefsArgs, CheckArgs, CheckStdinArgs,
+        DiffArgs, ExpiringArgs, FixArgs, GraphArgs, GraphSyntax, GrepArgs, GroupBy, HotspotsArgs,
+        IndexArgs, IndexCommand, InitArgs, InstallHookArgs, LintTagsArgs, ListCustomArgs,
+        ListTagsArgs, ListUnusedArgs, MigrateArgs, MigrateFrom, MvArgs, NewTagArgs, NewTagPrefix,
+        OpenArgs, OwnersArgs, RenameArgs, RepinArgs, ServeArgs, ShowArgs, SortBy, StatsArgs,
+        TodoArgs, TodoGroupBy, TrendArgs, UndoArgs, UninstallHookArgs,
+    },
+    config, editor,
+    failure::{self, Failure},
+    format,
+    hook::{self, Hook},
+    init, lsp, pre_receive,
+    pretty::Sources,
+    serve,
+    settings::Settings,
+    tui, watch,
+};
+use colored::Colorize;
+use regex::RegexBuilder;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fs,
+    io::{self, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tagref::{
+    baseline::{self, Baseline},
+    context::{self, Snippet},
+    count::count,
+    diff,
+    directive::Directive,
+    edits::Edits,
+    expiry::{self, Date},
+    fix::{self, Candidates},
+    git::{self, Changes},
+    graph::Graph,
+    hotspots,
+    index::Index,
+    migrate::{self, Migration},
+    moves::{self, Move},
+    namespaces, new_tag,
+    owners::{self, Codeowners},
+    repositories, rewrite, rustdoc, similar_tags,
+    stats::{self, Grouping, Order, Stats},
+    streaming,
+    synthetic::Tree,
+    todos, trend,
+    undo::{self, Journal},
+    unused,
+    violation::{self, Violation},
+    walk,
+};
+
+// This function reports the paths in violations as requested (see `--relative-to`). The lines shown
+// under the messages are still read from the scanned paths.
+fn report_violations(
+    violations: &mut [Violation],
+    sources: &mut Sources,
+    report_path: &dyn Fn(&Path) -> PathBuf,
+) {
+    for violation in violations {
+        violation.map_paths(|path| {
+            let reported = report_path(path);
+            sources.report_as(path, &reported);
+            reported
+        });
+    }
+}
+
+// This function copies directives with their paths reported as requested (see `--relative-to`).
+fn report_directives<'a, I: IntoIterator<Item = &'a Directive>>(
+    directives: I,
+    report_path: &dyn Fn(&Path) -> PathBuf,
+) -> Vec<Directive> {
+    directives
+        .into_iter()
+        .map(|directive| Directive {
+            path: report_path(&directive.path).into(),
+            ..directive.clone()
+        })
+        .collect()
+}
+
+// This function prints directives with their paths reported as requested.
+fn print_directives<'a, I: IntoIterator<Item = &'a Directive>>(
+    settings: &Settings,
+    directives: I,
+) -> Result<(), Failure> {
+    let report_path = settings.report_path()?;
+    format::print_directives(
+        settings.cli.format,
+        &report_directives(directives, &report_path),
+    )?;
+    Ok(())
+}
+
+// This function prints edits as a diff.
+fn print_diff(edits: &Edits) {
+    // Carriage returns are kept, so the diff can be saved and applied.
+    for line in edits.diff().split_inclusive('\n') {
+        let line = line.strip_suffix('\n').unwrap_or(line);
+        if line.starts_with("---") || line.starts_with("+++") {
+            println!("{}", line.bold());
+        } else if line.starts_with("@@") {
+            println!("{}", line.cyan());
+        } else if line.starts_with('-') {
+            println!("{}", line.red());
+        } else if line.starts_with('+') {
+            println!("{}", line.green());
+        } else {
+            println!("{line}");
+        }
+    }
+}
+
+// This function reports how many lines and files were edited.
+fn report_edits(edits: &Edits, dry_run: bool, done: &str, would: &str) {
+    println!(
+        "{}",
+        format!(
+            "{} {} in {}.",
+            if dry_run { would } else { done },
+            count(edits.line_count(), "line"),
+            count(edits.file_count(), "file"),
+        )
+        .green(),
+    );
+}
+
+// This function applies the edits made by a command which rewrites files, performs any moves after
+// them, and reports what was done (see `undo::apply`). In dry-run mode, the edits are printed as a
+// diff instead.
+fn apply_edits(
+    command: &str,
+    edits: &Edits,
+    moves: &[Move],
+    dry_run: bool,
+    done: &str,
+    would: &str,
+) -> Result<(), String> {
+    if dry_run {
+        print_diff(edits);
+        for r#move in moves {
+            println!(
+                "Would move {} to {}.",
+                r#move.from.to_string_lossy(),
+                r#move.to.to_string_lossy(),
+            );
+        }
+    } else {
+        let _rewriting = failure::REWRITING.lock();
+        undo::apply(
+            command,
+            edits,
+            moves,
+            Path::new(undo::DEFAULT_PATH),
+            |r#move| {
+                println!(
+                    "Moved {} to {}.",
+                    r#move.from.to_string_lossy(),
+                    r#move.to.to_string_lossy(),
+                );
+            },
+        )?;
+    }
+
+    report_edits(edits, dry_run, done, would);
+    Ok(())
+}
+
+// This function asks the user a yes-or-no question. Anything other than a yes counts as a no.
+fn confirm(question: &str) -> Result<bool, String> {
+    print!("{question} [y/N] ");
+    io::stdout()
+        .flush()
+        .map_err(|error| format!("Unable to write to STDOUT: {error}"))?;
+
+    let mut answer = String::new();
+    io::stdin()
+        .read_line(&mut answer)
+        .map_err(|error| format!("Unable to read from STDIN: {error}"))?;
+
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+// This function makes sure the user can be asked for confirmation, if there's anything to ask
+// about. The flag is mentioned in the error.
+fn check_prompt(prompt: bool, pending: bool, action: &str) -> Result<(), String> {
+    if prompt && pending && !io::stdin().is_terminal() {
+        return Err(format!(
+            "Unable to prompt for confirmation. Pass --yes to {action}.",
+        ));
+    }
+    Ok(())
+}
+
+// This function converts ad-hoc to-do items like `TODO(label)` into ones with labels, asking about
+// each label unless told not to. The first item with each label (in order of path) becomes a tag,
+// and the rest become references to it.
+fn convert_todos(settings: &Settings, args: &InitArgs) -> Result<(), String> {
+    let scanner = &settings.scanner;
+    let labels = todos::collect_ad_hoc(&scanner.paths, &scanner.walk_options)?;
+
+    // Ask which labels to convert, unless we've been told to convert all of them.
+    let prompt = !args.yes && !args.dry_run;
+    check_prompt(prompt, !labels.is_empty(), "convert the to-do items")?;
+    let mut tags = BTreeMap::new();
+    for (label, first) in labels {
+        if prompt
+            && !confirm(&format!(
+                "Convert {} with `{label}`?",
+                count(first.count, "to-do item"),
+            ))?
+        {
+            continue;
+        }
+        tags.insert(label, first);
+    }
+
+    let edits = todos::convert_all_ad_hoc(
+        &scanner.paths,
+        &scanner.walk_options,
+        tags,
+        settings.tag_sigil(),
+        settings.ref_sigil(),
+    )?;
+    apply_edits(
+        "init",
+        &edits,
+        &[],
+        args.dry_run,
+        "Converted",
+        "Would convert",
+    )
+}
+
+// This function sets up Tagref in a repository.
+pub fn init(settings: &Settings, args: &InitArgs) -> Result<(), Failure> {
+    // Write the configuration file and the workflow, leaving any existing ones alone.
+    for (path, contents) in [
+        (Path::new(config::DEFAULT_PATH), init::CONFIG),
+        (Path::new(init::WORKFLOW_PATH), init::WORKFLOW),
+    ] {
+        let path_string = path.to_string_lossy();
+        if !init::write(path, contents, args.force, args.dry_run)? {
+            log::warn!("Skipping {path_string}, which already exists. Pass --force to replace it.");
+        } else if args.dry_run {
+            println!("Would write {path_string}.");
+        } else {
+            println!("{}", format!("Wrote {path_string}.").green());
+        }
+    }
+
+    // Install the hook, unless there's no Git repository or the hooks are managed by another tool.
+    // That doesn't stop the rest of the setup.
+    if args.dry_run {
+        println!("Would install a pre-commit hook.");
+    } else {
+        match hook::install(Hook::PreCommit, args.force) {
+            Ok(path) => println!(
+                "{}",
+                format!("Installed the hook at {}.", path.to_string_lossy()).green(),
+            ),
+            Err(error) => log::warn!("Skipping the hook. {error}"),
+        }
+    }
+
+    if args.convert_todos {
+        convert_todos(settings, args)?;
+    }
+    Ok(())
+}
+
+// This function installs a Git hook.
+pub fn install_hook(args: &InstallHookArgs) -> Result<(), Failure> {
+    let path = hook::install(args.hook, args.force)?;
+    println!(
+        "{}",
+        format!("Installed the hook at {}.", path.to_string_lossy()).green(),
+    );
+    Ok(())
+}
+
+// This function removes a Git hook.
+pub fn uninstall_hook(args: &UninstallHookArgs) -> Result<(), Failure> {
+    let path = hook::uninstall(args.hook)?;
+    println!(
+        "{}",
+        format!("Removed the hook at {}.", path.to_string_lossy()).green(),
+    );
+    Ok(())
+}
+
+// This function reverts the last command which rewrote files: the edits, and then the moves in
+// reverse order.
+pub fn undo(args: &UndoArgs) -> Result<(), Failure> {
+    let path = Path::new(undo::DEFAULT_PATH);
+    let Some(journal) = Journal::load(path)? else {
+        return Err("There's nothing to undo.".to_owned().into());
+    };
+    let edits = journal.revert()?;
+    let moves = journal.inverse_moves();
+    if args.dry_run {
+        print_diff(&edits);
+        for r#move in moves {
+            println!(
+                "Would move {} back to {}.",
+                r#move.from.to_string_lossy(),
+                r#move.to.to_string_lossy(),
+            );
+        }
+    } else {
+        let _rewriting = failure::REWRITING.lock();
+        undo::undo(&edits, &moves, path, |r#move| {
+            println!(
+                "Moved {} back to {}.",
+                r#move.from.to_string_lossy(),
+                r#move.to.to_string_lossy(),
+            );
+        })?;
+    }
+    report_edits(&edits, args.dry_run, "Reverted", "Would revert");
+    Ok(())
+}
+
+// This function generates a synthetic tree for benchmarking.
+pub fn bench(args: &BenchArgs) -> Result<(), Failure> {
+    Tree {
+        files: args.files,
+        lines: args.lines,
+        tags: args.tags,
+    }
+    .generate(&args.dir)?;
+    println!(
+        "{}",
+        format!(
+            "Generated {} in {}. Run Tagref from there to measure it.",
+            count(args.files, "file"),
+            args.dir.to_string_lossy(),
+        )
+        .green(),
+    );
+    Ok(())
+}
+
+// This function checks the files whenever they change. It does its own scanning, without mapping
+// files into memory, since it runs until it's stopped (see `Scanner::memory_map`).
+pub fn watch(settings: &mut Settings) -> Result<(), Failure> {
+    settings.load_external_tags(&[])?;
+    settings.scanner.memory_map = false;
+    Ok(watch::watch(
+        &settings.scanner,
+        &settings.rules,
+        settings.cli.format,
+    )?)
+}
+
+// This function runs the language server, which also does its own scanning.
+pub fn lsp(settings: &mut Settings) -> Result<(), Failure> {
+    settings.load_external_tags(&[])?;
+    settings.scanner.memory_map = false;
+    Ok(lsp::serve(&settings.scanner, &settings.rules)?)
+}
+
+// This function serves queries about the labels, keeping its index up to date.
+pub fn serve(settings: &mut Settings, args: &ServeArgs) -> Result<(), Failure> {
+    settings.load_external_tags(&[])?;
+    settings.scanner.memory_map = false;
+    let transport = if args.stdio {
+        serve::Transport::Stdio
+    } else {
+        serve::Transport::Http(&args.address)
+    };
+    Ok(serve::serve(&settings.scanner, &settings.rules, transport)?)
+}
+
+// This function checks the revisions pushed to a repository. It reads them from Git rather than
+// scanning the working tree, which bare repositories don't have.
+pub fn pre_receive(settings: &mut Settings) -> Result<(), Failure> {
+    settings.load_external_tags(&[])?;
+    let mut input = String::new();
+    io::stdin()
+        .read_to_string(&mut input)
+        .map_err(|error| format!("Unable to read the ref updates: {error}"))?;
+
+    // Check each ref, and only fail once they've all been reported.
+    let mut errors = Vec::new();
+    let mut failures = Vec::new();
+    for update in pre_receive::parse(&input)? {
+        eprintln!("Checking {} at {}.", update.name, update.revision);
+        let index = settings.scanner.scan_revision(&update.revision)?;
+        let mut violations = index.check_all(
+            &settings.rules,
+            settings.cli.check_urls,
+            settings.cli.check_issues,
+        );
+        let suppressed = index.suppress(&mut violations);
+        baseline::apply(
+            &settings.baseline_path,
+            settings.baseline_required,
+            &mut violations,
+        )?;
+        if settings.config.fail_on_warn.unwrap_or(false) {
+            violation::fail_on_warn(&mut violations);
+        }
+
+        // Show the lines involved as of the revision.
+        let mut sources = Sources::new(settings.scanner.fallback_encoding);
+        if let Some(tree) = &index.tree {
+            let paths = violations
+                .iter()
+                .flat_map(|violation| &violation.directives)
+                .map(|directive| &directive.path)
+                .collect::<HashSet<_>>();
+            for path in paths {
+                if let Ok(contents) = tree.read(path) {
+                    sources.insert(path, &contents);
+                }
+            }
+        }
+
+        if let Err(message) = format::print_report(
+            settings.cli.format,
+            &violations,
+            &suppressed,
+            &index.summary(),
+            settings.config.max_violations.unwrap_or(0),
+            settings.cli.quiet,
+            &mut sources,
+        ) {
+            failures.push(format!("{}: {message}", update.name));
+            errors.extend(violations);
+        }
+    }
+
+    if failures.is_empty() {
+        return Ok(());
+    }
+    Err(Failure {
+        code: failure::code(&errors),
+        message: failures.join("\n"),
+    })
+}
+
+// This function runs the checks and reports the result, or prints a badge for it if requested.
+pub fn check(settings: &mut Settings, args: CheckArgs, badge: Option<bool>) -> Result<(), Failure> {
+    settings.load_external_tags(&args.externs)?;
+    let rules = &mut settings.rules;
+    rules.deny_unused |= args.deny_unused;
+    rules.deny_deprecated_aliases |= args.deny_deprecated_aliases;
+    rules.deny_empty_dirs |= args.deny_empty_dirs;
+    rules.strict_path_case |= args.strict_path_case;
+    rules.markdown_links |= args.markdown_links;
+    if let Some(docs_dir) = args.docs_dir {
+        rules.docs = Some(docs_dir);
+    }
+    let settings = &*settings;
+    let (cli, config) = (&settings.cli, &settings.config);
+    let max_violations = args.max_violations.or(config.max_violations).unwrap_or(0);
+
+    // A streaming check scans the files itself, in two passes, rather than building the index
+    // first. It doesn't keep the URL references or custom labels, so they can't be checked, and
+    // it doesn't keep all the labels for the validators either.
+    let (index, mut violations, summary) = if args.streaming {
+        if cli.check_urls || cli.check_issues {
+            return Err(
+                "--streaming can't be combined with --check-urls or --check-issues."
+                    .to_owned()
+                    .into(),
+            );
+        }
+        if !settings.rules.validators.is_empty() {
+            return Err("--streaming can't be used with validators."
+                .to_owned()
+                .into());
+        }
+        let checked = streaming::check(&settings.scanner, &settings.rules, settings.tracked()?)?;
+        (checked.index, checked.violations, checked.summary)
+    } else {
+        let index = settings.scan()?;
+        let violations = index.check_all(&settings.rules, cli.check_urls, cli.check_issues);
+        let summary = index.summary();
+        (index, violations, summary)
+    };
+
+    // Only keep the violations which could have been caused by recent changes, if requested. Tags
+    // are still resolved against all the files.
+    if let Some(revision) = &args.changed_since {
+        let changes = Changes::since(revision, &settings.scanner)?;
+        violations.retain(|violation| changes.affects(violation));
+    }
+
+    // Set aside the violations suppressed by comments, and ignore the known violations.
+    let mut suppressed = index.suppress(&mut violations);
+    baseline::apply(
+        &settings.baseline_path,
+        settings.baseline_required,
+        &mut violations,
+    )?;
+
+    // Treat the remaining warnings as errors, if requested.
+    if args.fail_on_warn || config.fail_on_warn.unwrap_or(false) {
+        violation::fail_on_warn(&mut violations);
+    }
+
+    // Report the result, or print a badge for it. The badge is printed even if the check fails,
+    // since it reports the failure.
+    if let Some(svg) = badge {
+        let badge = Badge::new(&violations, max_violations);
+        if svg {
+            print!("{}", badge.svg());
+        } else {
+            println!("{}", badge.json());
+        }
+        return Ok(());
+    }
+    let report_path = settings.report_path()?;
+    let mut sources = Sources::new(settings.scanner.fallback_encoding);
+    report_violations(&mut violations, &mut sources, &report_path);
+    report_violations(&mut suppressed, &mut sources, &report_path);
+    format::print_report(
+        cli.format,
+        &violations,
+        &suppressed,
+        &summary,
+        max_violations,
+        cli.quiet,
+        &mut sources,
+    )
+    .map_err(|message| Failure {
+        code: failure::code(&violations),
+        message,
+    })
+}
+
+// This function checks a file whose contents are read from standard input, which may contain
+// unsaved changes.
+pub fn check_stdin(settings: &mut Settings, args: &CheckStdinArgs) -> Result<(), Failure> {
+    settings.load_external_tags(&[])?;
+    let settings = &*settings;
+    let mut index = settings.scan()?;
+
+    // Replace the directives from the file with the ones from standard input. Errors when reading
+    // are ignored, so whatever was read before the error is still parsed.
+    let removed = index.remove(&args.path);
+    let mut contents = Vec::new();
+    let _ = io::stdin().read_to_end(&mut contents);
+    index.insert(settings.scanner.parse(&args.path, &contents));
+
+    // Run the checks, and only keep the violations which could have been caused by the contents of
+    // the file, including references elsewhere to tags which were removed from it.
+    let changes = Changes {
+        files: [walk::relative(&args.path)].into(),
+        old_tags: removed
+            .tags
+            .iter()
+            .map(|tag| index.key(&tag.label).into_owned())
+            .collect(),
+        case_insensitive_labels: index.case_insensitive_labels,
+    };
+    let mut violations = index.check(&settings.rules);
+    violations.retain(|violation| changes.affects(violation));
+    let mut suppressed = index.suppress(&mut violations);
+    baseline::apply(
+        &settings.baseline_path,
+        settings.baseline_required,
+        &mut violations,
+    )?;
+
+    // Report the result, showing the lines from standard input rather than the file.
+    let report_path = settings.report_path()?;
+    let mut sources = Sources::new(settings.scanner.fallback_encoding);
+    sources.insert(&args.path, &contents);
+    report_violations(&mut violations, &mut sources, &report_path);
+    report_violations(&mut suppressed, &mut sources, &report_path);
+    format::print_report(
+        settings.cli.format,
+        &violations,
+        &suppressed,
+        &index.summary(),
+        0,
+        settings.cli.quiet,
+        &mut sources,
+    )
+    .map_err(|message| Failure {
+        code: failure::code(&violations),
+        message,
+    })
+}
+
+// This function prints all the tags, or only the ones in the given namespace.
+pub fn list_tags(settings: &Settings, args: &ListTagsArgs) -> Result<(), Failure> {
+    let index = settings.scan()?;
+    let tags = index.tags.values().flatten().filter(|tag| {
+        args.namespace
+            .as_deref()
+            .is_none_or(|namespace| namespaces::contains(namespace, &tag.label))
+    });
+    if args.labels {
+        // Each tag is printed once, even if it's duplicated.
+        let labels = tags.map(|tag| &tag.label).collect::<BTreeSet<_>>();
+        for label in labels {
+            println!("{label}");
+        }
+    } else if args.long || settings.config.auto_tag_names.unwrap_or(false) {
+        // Automatically named tags are only recognizable by their descriptions.
+        let report_path = settings.report_path()?;
+        format::print_directives_long(settings.cli.format, &report_directives(tags, &report_path))?;
+    } else {
+        print_directives(settings, tags)?;
+    }
+    Ok(())
+}
+
+// This function prints one kind of reference, which `references` selects from the index.
+pub fn list_references(
+    settings: &Settings,
+    references: impl FnOnce(&Index) -> &[Directive],
+) -> Result<(), Failure> {
+    let index = settings.scan()?;
+    print_directives(settings, references(&index))
+}
+
+// This function prints all the custom labels, or only the ones with the given sigil.
+pub fn list_custom(settings: &Settings, args: &ListCustomArgs) -> Result<(), Failure> {
+    let index = settings.scan()?;
+    let labels = index.custom.iter().filter(|label| {
+        args.sigil
+            .as_deref()
+            .is_none_or(|sigil| label.r#type.sigil() == sigil)
+    });
+    print_directives(settings, labels)
+}
+
+// This function prints the tags which aren't referenced anywhere, except for the allowed ones.
+pub fn list_unused(settings: &Settings, args: &ListUnusedArgs) -> Result<(), Failure> {
+    let index = settings.scan()?;
+    let unused = unused::find(
+        &index.tags,
+        &index.refs,
+        &settings.rules.allow_unused,
+        index.case_insensitive_labels,
+    );
+    print_directives(settings, unused.iter().copied())?;
+
+    // Error out if the error flag has been passed and there are unused tags.
+    if args.fail_if_any && !unused.is_empty() {
+        return Err(Failure {
+            code: failure::VIOLATIONS,
+            message: "Found unused tags while using --fail-if-any".to_owned(),
+        });
+    }
+    Ok(())
+}
+
+// This function prints the labels which match the pattern, optionally only the ones of the given
+// types.
+pub fn grep(settings: &Settings, args: &GrepArgs) -> Result<(), Failure> {
+    let regex = RegexBuilder::new(&args.pattern)
+        .case_insensitive(args.ignore_case)
+        .build()
+        .map_err(|error| format!("Invalid regex `{}`: {error}", args.pattern))?;
+    let index = settings.scan()?;
+    let labels = index.labels().filter(|directive| {
+        (args.types.is_empty()
+            || args
+                .types
+                .iter()
+                .any(|label_type| label_type.includes(&directive.r#type)))
+            && regex.is_match(&directive.label)
+    });
+    print_directives(settings, labels)
+}
+
+// This function prints the pairs of similar tags, and fails if there are any.
+pub fn lint_tags(settings: &Settings, args: &LintTagsArgs) -> Result<(), Failure> {
+    let index = settings.scan()?;
+    let pairs = similar_tags::find(&index.tags, args.max_distance);
+    format::print_similar_tags(settings.cli.format, &pairs)?;
+    if !pairs.is_empty() {
+        return Err(Failure {
+            code: failure::VIOLATIONS,
+            message: format!("Found {} of similar tags.", count(pairs.len(), "pair")),
+        });
+    }
+    Ok(())
+}
+
+// This function determines the owners of each tag, and prints all of them or only the shared ones.
+pub fn owners(settings: &Settings, args: &OwnersArgs) -> Result<(), Failure> {
+    let index = settings.scan()?;
+    let Some((_, codeowners)) = Codeowners::load(args.codeowners.as_deref())? else {
+        return Err(Failure::from(format!(
+            "No CODEOWNERS file found in any of: {}.",
+            owners::LOCATIONS.join(", "),
+        )));
+    };
+    let ownership = owners::find(
+        &index.tags,
+        &index.refs,
+        &codeowners,
+        index.case_insensitive_labels,
+    );
+    format::print_ownership(
+        settings.cli.format,
+        ownership
+            .iter()
+            .filter(|ownership| !args.shared || ownership.is_shared()),
+    )?;
+    Ok(())
+}
+
+// This function prints the labels which expire by the given date.
+pub fn expiring(settings: &Settings, args: &ExpiringArgs) -> Result<(), Failure> {
+    let index = settings.scan()?;
+    let today = Date::today();
+    let labels = report_directives(index.labels(), &settings.report_path()?);
+    let expirations = expiry::find(&labels, today, today.add_days(args.within));
+    format::print_expirations(settings.cli.format, &expirations)?;
+    Ok(())
+}
+
+// This function finds the labels next to to-do markers and groups them. CODEOWNERS is only needed
+// to group by owner, and then it's optional, since owners can be given as attributes.
+pub fn todo(settings: &Settings, args: &TodoArgs) -> Result<(), Failure> {
+    let index = settings.scan()?;
+    let todos = todos::find(
+        index.tags.values().flatten().chain(&index.refs),
+        settings.scanner.fallback_encoding,
+    );
+    let (grouping, codeowners) = match args.group_by {
+        TodoGroupBy::Label => (todos::Grouping::Label, None),
+        TodoGroupBy::Owner => (
+            todos::Grouping::Owner,
+            Codeowners::load(args.codeowners.as_deref())?.map(|(_, codeowners)| codeowners),
+        ),
+        TodoGroupBy::Expiry => (todos::Grouping::Expiry, None),
+    };
+    let groups = todos::group(
+        todos,
+        grouping,
+        codeowners.as_ref(),
+        index.case_insensitive_labels,
+    );
+    format::print_todos(settings.cli.format, &groups)?;
+    Ok(())
+}
+
+// This function prints the metrics, or a breakdown of them if one was requested.
+pub fn stats(settings: &Settings, args: &StatsArgs) -> Result<(), Failure> {
+    let index = settings.scan()?;
+    if let Some(group_by) = args.group_by {
+        let (grouping, heading) = match group_by {
+            GroupBy::Dir => (Grouping::Directory, "Directory"),
+            GroupBy::Extension => (Grouping::Extension, "Extension"),
+            GroupBy::Namespace => (Grouping::Namespace, "Namespace"),
+        };
+        let order = match args.sort {
+            SortBy::Name => Order::Name,
+            SortBy::Tags => Order::Tags,
+            SortBy::Refs => Order::Refs,
+            SortBy::Labels => Order::Labels,
+            SortBy::Files => Order::Files,
+        };
+        let groups = stats::breakdown(&index, grouping, order);
+        format::print_breakdown(settings.cli.format, heading, &groups)?;
+    } else {
+        let stats = Stats::new(&index, &settings.rules.allow_unused, args.top);
+        format::print_stats(settings.cli.format, &stats)?;
+    }
+    Ok(())
+}
+
+// This function prints the tags which are referenced more than the threshold allows.
+pub fn hotspots(settings: &Settings, args: &HotspotsArgs) -> Result<(), Failure> {
+    let index = settings.scan()?;
+    let hotspots = hotspots::find(
+        &index.tags,
+        &index.refs,
+        args.threshold
+            .or(settings.config.hotspot_threshold)
+            .unwrap_or(hotspots::DEFAULT_THRESHOLD),
+        index.case_insensitive_labels,
+    );
+    format::print_hotspots(settings.cli.format, &hotspots)?;
+    Ok(())
+}
+
+// This function prints a tag and the references to it, along with the lines around them.
+pub fn show(settings: &Settings, args: &ShowArgs) -> Result<(), Failure> {
+    let index = settings.scan()?;
+    let label = args.label.trim();
+    let key = index.key(label);
+    let directives = index
+        .tags
+        .get(&*key)
+        .into_iter()
+        .flatten()
+        .chain(
+            index
+                .refs
+                .iter()
+                .filter(|r#ref| index.key(&r#ref.label) == key),
+        )
+        .collect::<Vec<_>>();
+    if directives.is_empty() {
+        return Err(format!("No tag or references found for label `{label}`.").into());
+    }
+
+    // The lines around them are read from the scanned paths.
+    let reported = report_directives(directives.iter().copied(), &settings.report_path()?);
+    let snippets = directives
+        .into_iter()
+        .zip(&reported)
+        .map(|(directive, reported)| {
+            context::snippet(directive, args.context, settings.scanner.fallback_encoding).map(
+                |snippet| Snippet {
+                    directive: reported,
+                    ..snippet
+                },
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    format::print_snippets(settings.cli.format, &snippets)?;
+    Ok(())
+}
+
+// This function opens a tag, or a reference to it in the order they're listed, in an editor.
+pub fn open(settings: &Settings, args: &OpenArgs) -> Result<(), Failure> {
+    let index = settings.scan()?;
+    let label = args.label.trim();
+    let key = index.key(label);
+    let Some(tags) = index.tags.get(&*key) else {
+        return Err(format!("No tag found for label `{label}`.").into());
+    };
+    let directive = if let Some(n) = args.r#ref {
+        let mut refs = index
+            .refs
+            .iter()
+            .filter(|r#ref| index.key(&r#ref.label) == key)
+            .collect::<Vec<_>>();
+        refs.sort_by(|x, y| x.cmp_position(y));
+        let Some(r#ref) = usize::try_from(n - 1).ok().and_then(|i| refs.get(i)) else {
+            return Err(format!(
+                "The tag `{label}` has only {}.",
+                count(refs.len(), "reference"),
+            )
+            .into());
+        };
+        *r#ref
+    } else {
+        // Duplicate tags are allowed in some places, in which case the first one is opened. The
+        // `unwrap` is safe since every tag in the index has at least one directive.
+        tags.iter().min_by(|x, y| x.cmp_position(y)).unwrap()
+    };
+
+    editor::open(
+        &settings.config.editor,
+        &directive.path,
+        directive.line_number,
+        directive.column,
+    )?;
+    Ok(())
+}
+
+// This function renames a tag in every file containing it or a reference to it.
+pub fn rename(settings: &Settings, args: &RenameArgs) -> Result<(), Failure> {
+    let index = settings.scan()?;
+    let edits = rewrite::rename_tag(&settings.scanner, &index, &args.old, &args.new)?;
+    apply_edits(
+        "rename",
+        &edits,
+        &[],
+        args.dry_run,
+        "Renamed",
+        "Would rename",
+    )?;
+    Ok(())
+}
+
+// This function prints a new tag and a reference to it, and inserts the tag into a file if asked
+// to.
+pub fn new_tag(settings: &Settings, args: &NewTagArgs) -> Result<(), Failure> {
+    let index = settings.scan()?;
+
+    // Labels which are already referenced are taken too, so the new tag doesn't quietly resolve
+    // dangling references.
+    let taken = index
+        .tags
+        .keys()
+        .cloned()
+        .chain(
+            index
+                .refs
+                .iter()
+                .map(|r#ref| index.key(&r#ref.label).into_owned()),
+        )
+        .collect::<HashSet<_>>();
+    let prefix = match args.prefix {
+        Some(NewTagPrefix::Ulid) => {
+            let milliseconds = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| {
+                    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+                });
+            Some(new_tag::ulid(milliseconds, new_tag::random_bits()?))
+        }
+        Some(NewTagPrefix::Date) => Some(Date::today().to_string()),
+        None => None,
+    };
+
+    // An automatically named tag gets a random identifier, and the argument describes it.
+    let (name, description) =
+        if args.auto || (settings.config.auto_tag_names.unwrap_or(false) && !args.no_auto) {
+            if prefix.is_some() {
+                return Err("Automatically named tags can't have prefixes."
+                    .to_owned()
+                    .into());
+            }
+            let description = args.name.trim();
+            if description.is_empty() {
+                return Err("An automatically named tag needs a description."
+                    .to_owned()
+                    .into());
+            }
+            (
+                new_tag::identifier(new_tag::random_bits()?),
+                Some(description),
+            )
+        } else {
+            (args.name.clone(), None)
+        };
+    let name = new_tag::name(&name, prefix.as_deref(), |name| {
+        taken.contains(&*index.key(name))
+    })?;
+
+    // Write the tag and a reference to it with the configured sigils.
+    let tag = format!(
+        "[{}:{name}]{}",
+        settings.tag_sigil(),
+        description.map_or_else(String::new, |description| format!(" {description}")),
+    );
+    let r#ref = format!("[{}:{name}]", settings.ref_sigil());
+
+    // Work out where to insert the tag before printing anything, if asked to insert it.
+    let mut edits = Edits::default();
+    if let Some(location) = &args.edit {
+        let Some((path, line_number)) = location
+            .rsplit_once(':')
+            .and_then(|(path, line)| Some((Path::new(path), line.parse().ok()?)))
+        else {
+            return Err(format!("`{location}` isn't of the form `FILE:LINE`.").into());
+        };
+        let contents = fs::read_to_string(path)
+            .map_err(|error| format!("Unable to read {}: {error}", path.to_string_lossy()))?;
+        let edit = new_tag::insert(path, &contents, line_number, &tag)?;
+        edits.insert(path, contents, vec![edit]);
+    }
+
+    println!("{tag}");
+    println!("{ref}");
+    if args.edit.is_some() {
+        apply_edits(
+            "new-tag",
+            &edits,
+            &[],
+            args.dry_run,
+            "Inserted",
+            "Would insert",
+        )?;
+    }
+    Ok(())
+}
+
+// This function pins the stale pinned references to the current content of their tags.
+pub fn repin(settings: &Settings, args: &RepinArgs) -> Result<(), Failure> {
+    let index = settings.scan()?;
+    let edits = rewrite::repin(&settings.scanner, &index, &settings.rules, &args.labels)?;
+    apply_edits(
+        "repin",
+        &edits,
+        &[],
+        args.dry_run,
+        "Repinned",
+        "Would repin",
+    )?;
+    Ok(())
+}
+
+// This function prints the tags which changed since the revision, with the references to them.
+pub fn changed_refs(settings: &Settings, args: &ChangedRefsArgs) -> Result<(), Failure> {
+    let index = settings.scan()?;
+    let tags = git::changed_tags(&args.revision, &settings.scanner, &index, &settings.rules)?;
+    format::print_changed_tags(settings.cli.format, &tags)?;
+    Ok(())
+}
+
+// This function compares the labels as of the first revision with the ones as of the second, or
+// with the ones in the working tree.
+pub fn diff(settings: &Settings, args: &DiffArgs) -> Result<(), Failure> {
+    let before = settings.scanner.scan_revision(&args.from)?;
+    let after = match &args.to {
+        Some(revision) => settings.scanner.scan_revision(revision)?,
+        None => settings.scan()?,
+    };
+    let diff = diff::compare(&before, &after, &settings.rules);
+    format::print_diff(settings.cli.format, &diff)?;
+    Ok(())
+}
+
+// This function moves a file or directory and updates the references to it, or only updates the
+// references if Git already moved it.
+pub fn mv(settings: &Settings, args: &MvArgs) -> Result<(), Failure> {
+    let index = settings.scan()?;
+
+    // Determine what moves, and whether it already has.
+    let moves = if args.from_git {
+        let mut moves = git::staged_renames()?;
+        moves.extend(moves::directories(&moves));
+        moves
+    } else {
+        // Clap requires both paths unless `--from-git` is given, so the `unwrap`s are safe.
+        let r#move = Move::new(args.from.as_deref().unwrap(), args.to.as_deref().unwrap());
+        if !r#move.from.exists() {
+            return Err(format!("{} does not exist.", r#move.from.to_string_lossy()).into());
+        }
+        if r#move.to.exists() {
+            return Err(format!("{} already exists.", r#move.to.to_string_lossy()).into());
+        }
+        vec![r#move]
+    };
+
+    // Rewrite the references. This happens before the move, so the files are found where the
+    // index says they are.
+    let relabels = moves::relabel(&index, &moves, args.from_git);
+    let edits = rewrite::move_references(&settings.scanner, relabels)?;
+
+    // Move the file or directory, unless Git already did.
+    apply_edits(
+        "mv",
+        &edits,
+        if args.from_git { &[] } else { &moves },
+        args.dry_run,
+        "Updated",
+        "Would update",
+    )?;
+    Ok(())
+}
+
+// This function fixes broken references, asking about each fix unless told not to.
+pub fn fix(settings: &Settings, args: &FixArgs) -> Result<(), Failure> {
+    let index = settings.scan()?;
+
+    // Look for files and directories with the same names as the missing ones.
+    let scanner = &settings.scanner;
+    let candidates = Candidates::collect(&scanner.paths, &scanner.walk_options)?;
+    let (mut fixes, unfixable) = fix::suggest(&index, &candidates);
+    if args.refs {
+        let mut violations = index.check(&settings.rules);
+        index.suppress(&mut violations);
+        fixes.extend(fix::suggest_refs(&violations));
+    }
+    for directive in &unfixable {
+        log::warn!("Unable to find a unique replacement for {directive}.");
+    }
+
+    // Ask which fixes to apply, unless we've been told to apply all of them.
+    let prompt = !args.yes && !args.dry_run;
+    check_prompt(prompt, !fixes.is_empty(), "apply the fixes")?;
+    let mut replacements = Vec::new();
+    for fix in fixes {
+        if prompt
+            && !confirm(&format!(
+                "Replace `{}` with `{}` in {}?",
+                fix.old,
+                fix.new,
+                count(fix.directives.len(), "reference"),
+            ))?
+        {
+            continue;
+        }
+        replacements.push(fix);
+    }
+
+    let edits = rewrite::fix(scanner, replacements)?;
+    apply_edits("fix", &edits, &[], args.dry_run, "Fixed", "Would fix")?;
+    Ok(())
+}
+
+// This function converts the annotations of another scheme into labels.
+pub fn migrate(settings: &Settings, args: MigrateArgs) -> Result<(), Failure> {
+    let scheme = match args.from {
+        MigrateFrom::CustomRegex => migrate::Scheme::CustomRegex {
+            tag: args.tag_pattern,
+            r#ref: args.ref_pattern,
+        },
+        _ if args.tag_pattern.is_some() || args.ref_pattern.is_some() => {
+            return Err(
+                "--tag-pattern and --ref-pattern only apply to --from custom-regex."
+                    .to_owned()
+                    .into(),
+            );
+        }
+        MigrateFrom::TodoComments => migrate::Scheme::TodoComments,
+        MigrateFrom::DoxygenRefs => migrate::Scheme::DoxygenRefs,
+    };
+    let migration = Migration::new(&scheme)?;
+    let index = settings.scan()?;
+
+    // Find the annotations, decide what each one becomes, and report the ones which need manual
+    // attention.
+    let scanner = &settings.scanner;
+    let plan = migrate::plan(
+        migration.find_all(&scanner.paths, &scanner.walk_options)?,
+        |key| index.tags.contains_key(key),
+        index.case_insensitive_labels,
+    );
+    for ambiguity in &plan.ambiguities {
+        log::warn!(
+            "Skipping `{}` at {}:{}. {}",
+            ambiguity.text,
+            ambiguity.path.to_string_lossy(),
+            ambiguity.line_number,
+            ambiguity.reason,
+        );
+    }
+
+    // Rewrite the annotations.
+    let edits = migration.rewrite_all(
+        &scanner.paths,
+        &scanner.walk_options,
+        plan.types,
+        settings.tag_sigil(),
+        settings.ref_sigil(),
+    )?;
+    apply_edits(
+        "migrate",
+        &edits,
+        &[],
+        args.dry_run,
+        "Migrated",
+        "Would migrate",
+    )?;
+    if !plan.ambiguities.is_empty() {
+        log::warn!(
+            "Skipped {} which can't be migrated automatically.",
+            count(plan.ambiguities.len(), "annotation"),
+        );
+    }
+    Ok(())
+}
+
+// This function browses the index until the user quits.
+pub fn tui(settings: &Settings) -> Result<(), Failure> {
+    let index = settings.scan()?;
+    tui::browse(&index, &settings.config.editor)?;
+    Ok(())
+}
+
+// This function prints the reference graph.
+pub fn graph(settings: &Settings, args: &GraphArgs) -> Result<(), Failure> {
+    let index = settings.scan()?;
+    let graph = Graph::new(&index);
+    print!(
+        "{}",
+        match args.syntax {
+            GraphSyntax::Dot => graph.dot(),
+            GraphSyntax::Mermaid => graph.mermaid(),
+        },
+    );
+    Ok(())
+}
+
+// This function measures each of the sampled commits, oldest first.
+pub fn trend(settings: &Settings, args: &TrendArgs) -> Result<(), Failure> {
+    let every = usize::try_from(args.every).unwrap_or(usize::MAX);
+    let commits = trend::sample(git::history(&args.range)?, every);
+    if commits.is_empty() {
+        return Err(format!("There are no commits in `{}`.", args.range).into());
+    }
+    let points = commits
+        .iter()
+        .map(|commit| trend::measure(&settings.scanner, &settings.rules, commit))
+        .collect::<Result<Vec<_>, _>>()?;
+    format::print_trend(settings.cli.format, &points)?;
+    Ok(())
+}
+
+// This function prints the tags in doc comments with the items they document.
+pub fn rustdoc(settings: &Settings) -> Result<(), Failure> {
+    let index = settings.scan()?;
+    let entries = rustdoc::find(index.tags.values().flatten());
+    format::print_rustdoc(settings.cli.format, &entries)?;
+    Ok(())
+}
+
+// This function records all the violations in the baseline, regardless of any existing baseline.
+// The ones suppressed by comments don't need to be recorded.
+pub fn baseline(settings: &mut Settings, args: &BaselineArgs) -> Result<(), Failure> {
+    match args.command {
+        BaselineCommand::Write => {
+            settings.load_external_tags(&[])?;
+            let index = settings.scan()?;
+            let mut violations = index.check_all(
+                &settings.rules,
+                settings.cli.check_urls,
+                settings.cli.check_issues,
+            );
+            index.suppress(&mut violations);
+            let baseline = Baseline::new(&violations);
+            baseline.save(&settings.baseline_path)?;
+
+            println!(
+                "{}",
+                format!(
+                    "Recorded {} in {}.",
+                    count(baseline.len(), "violation"),
+                    settings.baseline_path.to_string_lossy(),
+                )
+                .green(),
+            );
+        }
+    }
+    Ok(())
+}
+
+// This function writes the tags to an index file, which other repositories can check references
+// against.
+pub fn index(settings: &Settings, args: &IndexArgs) -> Result<(), Failure> {
+    match &args.command {
+        IndexCommand::Write { path } => {
+            let index = settings.scan()?;
+            repositories::write_index(&index, path)?;
+
+            println!(
+                "{}",
+                format!(
+                    "Wrote {} to {}.",
+                    count(index.tags.values().map(Vec::len).sum(), "tag"),
+                    path.to_string_lossy(),
+                )
+                .green(),
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,291 @@
+use std::{ops::Range, path::Path};
+
+// This struct describes how comments (and the string literals which might contain comment-like
+// text) are written in a language.
+#[derive(Debug, Eq, PartialEq)]
+pub struct Syntax {
+    line: &'static [&'static str],
+    block: &'static [(&'static str, &'static str)],
+    quotes: &'static [char],
+}
+
+const C_LIKE: Syntax = Syntax {
+    line: &["//"],
+    block: &[("/*", "*/")],
+    quotes: &['"', '\''],
+};
+
+// Rust uses `'` for lifetimes, so it can't be treated as a quote.
+const RUST: Syntax = Syntax {
+    line: &["//"],
+    block: &[("/*", "*/")],
+    quotes: &['"'],
+};
+
+const JAVASCRIPT: Syntax = Syntax {
+    line: &["//"],
+    block: &[("/*", "*/")],
+    quotes: &['"', '\'', '`'],
+};
+
+const CSS: Syntax = Syntax {
+    line: &[],
+    block: &[("/*", "*/")],
+    quotes: &['"', '\''],
+};
+
+const HASH: Syntax = Syntax {
+    line: &["#"],
+    block: &[],
+    quotes: &['"', '\''],
+};
+
+const DASH_DASH: Syntax = Syntax {
+    line: &["--"],
+    block: &[],
+    quotes: &['"', '\''],
+};
+
+const HASKELL: Syntax = Syntax {
+    line: &["--"],
+    block: &[("{-", "-}")],
+    quotes: &['"'],
+};
+
+const LUA: Syntax = Syntax {
+    line: &["--"],
+    block: &[("--[[", "]]")],
+    quotes: &['"', '\''],
+};
+
+const SQL: Syntax = Syntax {
+    line: &["--"],
+    block: &[("/*", "*/")],
+    quotes: &['"', '\''],
+};
+
+const LISP: Syntax = Syntax {
+    line: &[";"],
+    block: &[],
+    quotes: &['"'],
+};
+
+const PERCENT: Syntax = Syntax {
+    line: &["%"],
+    block: &[],
+    quotes: &['"'],
+};
+
+const MARKUP: Syntax = Syntax {
+    line: &[],
+    block: &[("<!--", "-->")],
+    quotes: &[],
+};
+
+impl Syntax {
+    // This function wraps text in a comment, preferring a line comment if the language has them.
+    #[must_use]
+    pub fn comment(&self, text: &str) -> String {
+        match (self.line.first(), self.block.first()) {
+            (Some(start), _) => format!("{start} {text}"),
+            (None, Some((start, end))) => format!("{start} {text} {end}"),
+            (None, None) => text.to_owned(),
+        }
+    }
+}
+
+// This function returns the comment syntax for a file based on its name or extension. It returns
+// `None` for file types which aren't recognized (e.g., prose), in which case labels should be
+// matched anywhere.
+pub fn syntax(path: &Path) -> Option<&'static Syntax> {
+    let file_name = path.file_name()?.to_string_lossy();
+    match file_name.as_ref() {
+        "Makefile" | "makefile" | "GNUmakefile" | "Dockerfile" | "CMakeLists.txt" | "BUILD"
+        | "Gemfile" | "Rakefile" => return Some(&HASH),
+        _ => {}
+    }
+
+    match path.extension()?.to_string_lossy().to_lowercase().as_str() {
+        "c" | "cc" | "cpp" | "cxx" | "h" | "hh" | "hpp" | "hxx" | "cs" | "d" | "dart" | "go"
+        | "java" | "kt" | "kts" | "m" | "mm" | "proto" | "scala" | "sc" | "swift" | "zig"
+        | "groovy" | "gradle" => Some(&C_LIKE),
+        "rs" => Some(&RUST),
+        "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" | "mts" | "cts" => Some(&JAVASCRIPT),
+        "css" | "scss" | "less" => Some(&CSS),
+        "py" | "pyi" | "rb" | "sh" | "bash" | "zsh" | "fish" | "pl" | "pm" | "r" | "yml"
+        | "yaml" | "toml" | "cfg" | "ini" | "conf" | "mk" | "cmake" | "nix" | "tf" | "ps1"
+        | "jl" | "ex" | "exs" | "coffee" | "dockerfile" | "bzl" | "star" => Some(&HASH),
+        "hs" | "lhs" | "elm" | "purs" => Some(&HASKELL),
+        "lua" => Some(&LUA),
+        "sql" => Some(&SQL),
+        "ada" | "adb" | "ads" | "vhd" | "vhdl" => Some(&DASH_DASH),
+        "lisp" | "lsp" | "cl" | "el" | "clj" | "cljs" | "cljc" | "edn" | "scm" | "ss" | "rkt"
+        | "asm" | "s" => Some(&LISP),
+        "erl" | "hrl" | "tex" | "sty" | "cls" | "pro" => Some(&PERCENT),
+        "html" | "htm" | "xml" | "xhtml" | "svg" | "vue" => Some(&MARKUP),
+        _ => None,
+    }
+}
+
+// This struct finds the comments in a file one line at a time. It needs to be stateful because
+// block comments can span several lines.
+pub struct Scanner {
+    syntax: &'static Syntax,
+    open_block: Option<&'static str>,
+}
+
+impl Scanner {
+    pub fn new(syntax: &'static Syntax) -> Self {
+        Self {
+            syntax,
+            open_block: None,
+        }
+    }
+
+    // This function returns the byte ranges of the given line which are inside comments. It must
+    // be called on each line of the file in order.
+    pub fn comments(&mut self, line: &str) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+        let mut quote = None;
+        let mut escaped = false;
+
+        // If a block comment was left open on a previous line, this line starts inside it.
+        let mut comment_start = self.open_block.map(|_| 0);
+
+        let mut position = 0;
+        while position < line.len() {
+            let rest = &line[position..];
+
+            if let Some(end) = self.open_block {
+                // We're inside a block comment, so look for the end of it.
+                if rest.starts_with(end) {
+                    position += end.len();
+                    // `comment_start` is always set when `open_block` is.
+                    ranges.push(comment_start.unwrap_or(0)..position);
+                    comment_start = None;
+                    self.open_block = None;
+                    continue;
+                }
+            } else if let Some(q) = quote {
+                // We're inside a string literal, so look for the end of it.
+                if escaped {
+                    escaped = false;
+                } else if rest.starts_with('\\') {
+                    escaped = true;
+                } else if rest.starts_with(q) {
+                    quote = None;
+                }
+            } else if let Some((start, end)) = self
+                .syntax
+                .block
+                .iter()
+                .find(|(start, _)| rest.starts_with(start))
+            {
+                // Block comments are checked first since their delimiters may begin with a line
+                // comment delimiter (e.g., in Lua).
+                comment_start = Some(position);
+                self.open_block = Some(end);
+                position += start.len();
+                continue;
+            } else if self.syntax.line.iter().any(|start| rest.starts_with(start)) {
+                // A line comment runs to the end of the line.
+                ranges.push(position..line.len());
+                return ranges;
+            } else if let Some(q) = self.syntax.quotes.iter().find(|q| rest.starts_with(**q)) {
+                quote = Some(*q);
+            }
+
+            // Advance to the next character. `rest` is nonempty, so the `unwrap` is safe.
+            position += rest.chars().next().unwrap().len_utf8();
+        }
+
+        // A block comment which is still open extends to the end of the line.
+        if let Some(start) = comment_start {
+            ranges.push(start..line.len());
+        }
+
+        ranges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::comments::{Scanner, syntax};
+    use std::path::Path;
+
+    #[test]
+    fn syntax_by_extension() {
+        assert!(syntax(Path::new("src/main.rs")).is_some());
+        assert!(syntax(Path::new("script.PY")).is_some());
+        assert!(syntax(Path::new("Makefile")).is_some());
+        assert!(syntax(Path::new("README.md")).is_none());
+        assert!(syntax(Path::new("LICENSE")).is_none());
+    }
+
+    #[test]
+    fn line_comment() {
+        let mut scanner = Scanner::new(syntax(Path::new("file.rs")).unwrap());
+        let line = "let x = 1; // comment";
+
+        assert_eq!(scanner.comments(line), vec![11..line.len()]);
+    }
+
+    #[test]
+    fn no_comment() {
+        let mut scanner = Scanner::new(syntax(Path::new("file.rs")).unwrap());
+
+        assert!(scanner.comments("let x = 1;").is_empty());
+    }
+
+    #[test]
+    fn comment_marker_in_string() {
+        let mut scanner = Scanner::new(syntax(Path::new("file.rs")).unwrap());
+        let line = r#"let url = "http://example.com/\"//"; // comment"#;
+
+        assert_eq!(scanner.comments(line), vec![37..line.len()]);
+    }
+
+    #[test]
+    fn block_comment_single_line() {
+        let mut scanner = Scanner::new(syntax(Path::new("file.c")).unwrap());
+
+        assert_eq!(
+            scanner.comments("int /* x */ y; /* z */"),
+            vec![4..11, 15..22]
+        );
+    }
+
+    #[test]
+    fn block_comment_multiple_lines() {
+        let mut scanner = Scanner::new(syntax(Path::new("file.c")).unwrap());
+
+        assert_eq!(scanner.comments("int x; /* start"), vec![7..15]);
+        assert_eq!(scanner.comments("middle"), vec![0..6]);
+        assert_eq!(scanner.comments("end */ int y;"), vec![0..6]);
+        assert!(scanner.comments("int z;").is_empty());
+    }
+
+    #[test]
+    fn hash_comment() {
+        let mut scanner = Scanner::new(syntax(Path::new("file.py")).unwrap());
+        let line = "x = '#' # comment";
+
+        assert_eq!(scanner.comments(line), vec![8..line.len()]);
+    }
+
+    #[test]
+    fn lua_block_comment() {
+        let mut scanner = Scanner::new(syntax(Path::new("file.lua")).unwrap());
+
+        assert_eq!(scanner.comments("x = 1 --[[ start"), vec![6..16]);
+        assert_eq!(scanner.comments("end ]] -- comment"), vec![0..6, 7..17]);
+    }
+
+    #[test]
+    fn rust_lifetimes() {
+        let mut scanner = Scanner::new(syntax(Path::new("file.rs")).unwrap());
+        let line = "fn f<'a>(x: &'a str) {} // comment";
+
+        assert_eq!(scanner.comments(line), vec![24..line.len()]);
+    }
+}
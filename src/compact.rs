@@ -0,0 +1,120 @@
+use std::fmt::Write;
+use tagref::{
+    attributes,
+    directive::Directive,
+    violation::{Severity, Violation},
+    walk,
+};
+
+// This function formats the location of a directive as compilers do, e.g., `src/a.rs:3:7`.
+fn location(directive: &Directive) -> String {
+    format!(
+        "{}:{}:{}",
+        walk::relative(&directive.path).to_string_lossy(),
+        directive.line_number,
+        directive.column,
+    )
+}
+
+// This function joins the lines of a message, so each violation takes up a single line.
+fn one_line(message: &str) -> String {
+    message
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// This function formats a directive as a line starting with its location, followed by the label
+// and its description, if it has one.
+pub fn directive(directive: &Directive) -> String {
+    let mut line = format!(
+        "{}: [{}:{}{}]",
+        location(directive),
+        directive.r#type.sigil(),
+        directive.label,
+        attributes::format(&directive.attributes),
+    );
+    if let Some(description) = &directive.description {
+        let _ = write!(line, " {}", one_line(description));
+    }
+    line
+}
+
+// This function formats violations as `path:line:column: severity: message` lines, like the
+// diagnostics of compilers, so editors can jump to them (e.g., with Vim's quickfix list or Emacs'
+// compilation mode). The location is that of the first directive involved, and violations which
+// don't involve any directives have no location.
+pub fn report(violations: &[Violation]) -> String {
+    let mut output = String::new();
+    for violation in violations {
+        let severity = match violation.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "note",
+        };
+        if let Some(directive) = violation.directives.first() {
+            let _ = write!(output, "{}: ", location(directive));
+        }
+        let _ = writeln!(output, "{severity}: {}", one_line(&violation.message));
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        compact::{directive, report},
+        fixtures,
+    };
+    use std::collections::BTreeMap;
+    use tagref::{
+        directive::{Directive, Type},
+        violation::{Kind, Severity, Violation},
+    };
+
+    fn tag() -> Directive {
+        Directive {
+            column: 7,
+            description: Some("Never zero.".to_owned()),
+            attributes: BTreeMap::from([("owner".to_owned(), "@org/core".to_owned())]),
+            ..fixtures::directive(Type::Tag, "foo", "./src/a.rs", 3)
+        }
+    }
+
+    #[test]
+    fn directive_line() {
+        assert_eq!(
+            directive(&tag()),
+            "src/a.rs:3:7: [?tag:foo owner=@org/core] Never zero.".replace('?', ""),
+        );
+    }
+
+    #[test]
+    fn report_lines() {
+        let violations = [
+            Violation {
+                kind: Kind::DuplicateTag,
+                severity: Severity::Error,
+                message: "Duplicate tags found for label `foo`:\n  one\n  two\n".to_owned(),
+                directives: vec![tag(), tag()],
+                suggestions: Vec::new(),
+            },
+            Violation {
+                kind: Kind::UndecodableFile,
+                severity: Severity::Info,
+                message: "Unable to decode b.rs.".to_owned(),
+                directives: Vec::new(),
+                suggestions: Vec::new(),
+            },
+        ];
+
+        assert_eq!(
+            report(&violations),
+            "src/a.rs:3:7: error: Duplicate tags found for label `foo`: one two\n\
+             note: Unable to decode b.rs.\n",
+        );
+    }
+}
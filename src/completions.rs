@@ -0,0 +1,334 @@
+use clap::{Arg, ArgAction, Command, ValueEnum};
+
+// This enum represents the shells for which completion scripts can be generated.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+// These positional arguments are the labels of existing tags, identified by subcommand and
+// argument. They're completed by running `list-tags --labels` when the completion is requested.
+const TAG_ARGUMENTS: [(&str, &str); 4] = [
+    ("show", "label"),
+    ("open", "label"),
+    ("rename", "old"),
+    ("repin", "labels"),
+];
+
+// This struct describes where the labels of tags go on the command line: which subcommands take
+// them, and which of their positional arguments are labels.
+struct Hook {
+    program: String,
+
+    // The subcommand, the position of the label among its positional arguments, and whether the
+    // argument takes more than one label
+    positions: Vec<(String, usize, bool)>,
+
+    // The options whose values are separate arguments, which shouldn't be counted as positional
+    // arguments
+    value_flags: Vec<String>,
+}
+
+impl Hook {
+    // This function finds the tag arguments in the command.
+    fn new(command: &Command) -> Self {
+        let takes_value = |arg: &&Arg| {
+            !arg.is_positional() && arg.get_action().takes_values() && !arg.is_require_equals_set()
+        };
+        let mut value_flags = command
+            .get_arguments()
+            .filter(takes_value)
+            .flat_map(flags)
+            .collect::<Vec<_>>();
+
+        let mut positions = Vec::new();
+        for (name, id) in TAG_ARGUMENTS {
+            // Every entry names a subcommand and one of its arguments, as the tests check, so
+            // nothing is skipped here.
+            let Some(subcommand) = command.find_subcommand(name) else {
+                continue;
+            };
+            let Some((position, arg)) = subcommand
+                .get_positionals()
+                .enumerate()
+                .find(|(_, arg)| arg.get_id() == id)
+            else {
+                continue;
+            };
+            let repeated = matches!(arg.get_action(), ArgAction::Append)
+                || arg
+                    .get_num_args()
+                    .is_some_and(|range| range.max_values() > 1);
+            positions.push((name.to_owned(), position, repeated));
+            value_flags.extend(
+                subcommand
+                    .get_arguments()
+                    .filter(takes_value)
+                    .flat_map(flags),
+            );
+        }
+        value_flags.sort();
+        value_flags.dedup();
+
+        Self {
+            program: command.get_name().to_owned(),
+            positions,
+            value_flags,
+        }
+    }
+
+    // This function returns the patterns which match the subcommand and position of a label, like
+    // `show,0` or `repin,*`, with `any` standing for any position.
+    fn patterns(&self, any: &str) -> Vec<String> {
+        self.positions
+            .iter()
+            .map(|(name, position, repeated)| {
+                if *repeated && *position == 0 {
+                    format!("{name},{any}")
+                } else {
+                    format!("{name},{position}")
+                }
+            })
+            .collect()
+    }
+
+    // This function generates the start of the Bash completion function, which completes labels
+    // and leaves everything else to the rest of the function.
+    fn bash(&self) -> String {
+        format!(
+            r#"    # Complete the labels of existing tags, which aren't known in advance.
+    local word subcommand="" positional=0 i
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        word="${{COMP_WORDS[i]}}"
+        case "${{word}}" in
+            {}) i=$((i + 1)) ;;
+            -*) ;;
+            *)
+                if [[ -z "${{subcommand}}" ]]; then
+                    subcommand="${{word}}"
+                else
+                    positional=$((positional + 1))
+                fi
+                ;;
+        esac
+    done
+    if [[ "${{COMP_WORDS[COMP_CWORD]}}" != -* ]]; then
+        case "${{subcommand}},${{positional}}" in
+            {})
+                local IFS=$'\n' cur="${{COMP_WORDS[COMP_CWORD]}}"
+                COMPREPLY=($(compgen -W "$({} list-tags --labels 2>/dev/null)" -- "${{cur}}"))
+                return 0
+                ;;
+        esac
+    fi
+
+"#,
+            self.value_flags.join(" | "),
+            self.patterns("*").join(" | "),
+            self.program,
+        )
+    }
+
+    // This function generates the start of the Zsh completion function.
+    fn zsh(&self) -> String {
+        format!(
+            r#"    # Complete the labels of existing tags, which aren't known in advance.
+    local word subcommand="" positional=0 i
+    for ((i = 2; i < CURRENT; i++)); do
+        word="${{words[i]}}"
+        case "$word" in
+            ({}) ((i++)) ;;
+            (-*) ;;
+            (*) [[ -z "$subcommand" ]] && subcommand="$word" || ((positional++)) ;;
+        esac
+    done
+    if [[ "${{words[CURRENT]}}" != -* ]]; then
+        case "$subcommand,$positional" in
+            ({})
+                compadd -- ${{(f)"$({} list-tags --labels 2>/dev/null)"}}
+                return
+                ;;
+        esac
+    fi
+
+"#,
+            self.value_flags.join("|"),
+            self.patterns("*").join("|"),
+            self.program,
+        )
+    }
+
+    // This function generates the completions for Fish, which are added to the others.
+    fn fish(&self) -> String {
+        let mut names = self
+            .positions
+            .iter()
+            .map(|(name, _, _)| name.as_str())
+            .collect::<Vec<_>>();
+        names.dedup();
+        format!(
+            "\n# Complete the labels of existing tags, which aren't known in advance.\ncomplete -c \
+             {program} -n \"__fish_{program}_using_subcommand {}\" -f -a \"({program} list-tags \
+             --labels 2>/dev/null)\"\n",
+            names.join(" "),
+            program = self.program,
+        )
+    }
+
+    // This function generates the start of the PowerShell completion script block.
+    fn powershell(&self) -> String {
+        let flags = self
+            .value_flags
+            .iter()
+            .map(|flag| format!("'{flag}'"))
+            .collect::<Vec<_>>();
+        format!(
+            r#"
+    # Complete the labels of existing tags, which aren't known in advance.
+    $subcommand = $null
+    $positional = 0
+    $elements = $commandAst.CommandElements
+    for ($i = 1; $i -lt $elements.Count; $i++) {{
+        # The word being completed doesn't count.
+        if ($elements[$i].Extent.EndOffset -ge $cursorPosition) {{ break }}
+        $word = $elements[$i].Extent.Text
+        if ($word -in @({})) {{ $i++ }}
+        elseif ($word.StartsWith('-')) {{ }}
+        elseif ($null -eq $subcommand) {{ $subcommand = $word }}
+        else {{ $positional++ }}
+    }}
+    if (-not $wordToComplete.StartsWith('-') -and
+        "$subcommand,$positional" -match '^({})$') {{
+        {} list-tags --labels 2>$null |
+            Where-Object {{ $_ -like "$wordToComplete*" }} |
+            ForEach-Object {{
+                [CompletionResult]::new($_, $_, [CompletionResultType]::ParameterValue, $_)
+            }}
+        return
+    }}
+"#,
+            flags.join(", "),
+            self.patterns("\\d+").join("|"),
+            self.program,
+        )
+    }
+}
+
+// This function returns the flags for an option, like `-c` and `--config`.
+fn flags(arg: &Arg) -> Vec<String> {
+    arg.get_short()
+        .map(|short| format!("-{short}"))
+        .into_iter()
+        .chain(arg.get_long().map(|long| format!("--{long}")))
+        .collect()
+}
+
+// This function inserts text into a script after the first occurrence of a line.
+fn insert_after(script: &str, line: &str, text: &str) -> String {
+    script.replacen(line, &format!("{line}{text}"), 1)
+}
+
+// This function generates the completion script for a shell. The script is generated by
+// `clap_complete`, and the labels of tags are completed by a hook at the start of its completion
+// function.
+pub fn generate(mut command: Command, shell: Shell) -> String {
+    // This fills in the automatically generated arguments and subcommands, like `--help`.
+    command.build();
+    let hook = Hook::new(&command);
+
+    let program = command.get_name().to_owned();
+    let mut script = Vec::new();
+    clap_complete::generate(
+        match shell {
+            Shell::Bash => clap_complete::Shell::Bash,
+            Shell::Zsh => clap_complete::Shell::Zsh,
+            Shell::Fish => clap_complete::Shell::Fish,
+            Shell::Powershell => clap_complete::Shell::PowerShell,
+        },
+        &mut command,
+        &program,
+        &mut script,
+    );
+    let script = String::from_utf8_lossy(&script);
+
+    match shell {
+        Shell::Bash => insert_after(&script, &format!("_{program}() {{\n"), &hook.bash()),
+        Shell::Zsh => insert_after(&script, &format!("_{program}() {{\n"), &hook.zsh()),
+        Shell::Fish => script.into_owned() + &hook.fish(),
+        Shell::Powershell => insert_after(
+            &script,
+            "param($wordToComplete, $commandAst, $cursorPosition)\n",
+            &hook.powershell(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cli::Cli,
+        completions::{Hook, Shell, TAG_ARGUMENTS, generate},
+    };
+    use clap::CommandFactory;
+
+    #[test]
+    fn tag_arguments_exist() {
+        let mut command = Cli::command();
+        command.build();
+        let hook = Hook::new(&command);
+        assert_eq!(
+            hook.positions,
+            vec![
+                ("show".to_owned(), 0, false),
+                ("open".to_owned(), 0, false),
+                ("rename".to_owned(), 0, false),
+                ("repin".to_owned(), 0, true),
+            ],
+        );
+        assert_eq!(hook.positions.len(), TAG_ARGUMENTS.len());
+    }
+
+    #[test]
+    fn bash_tags() {
+        let script = generate(Cli::command(), Shell::Bash);
+        assert!(script.starts_with(
+            "_tagref() {\n    # Complete the labels of existing tags, which aren't known in \
+             advance.\n",
+        ));
+        assert!(script.contains("            show,0 | open,0 | rename,0 | repin,*)\n"));
+        assert!(script.contains(" | --format | "));
+        assert!(!script.contains(" | --cache | "));
+        assert!(script.contains("complete -F _tagref"));
+    }
+
+    #[test]
+    fn zsh_tags() {
+        let script = generate(Cli::command(), Shell::Zsh);
+        assert!(script.starts_with("#compdef tagref\n"));
+        assert!(script.contains("            (show,0|open,0|rename,0|repin,*)\n"));
+        assert!(script.contains("|--config|--context|"));
+        assert!(script.contains("compadd -- ${(f)\"$(tagref list-tags --labels 2>/dev/null)\"}"));
+    }
+
+    #[test]
+    fn fish_tags() {
+        let script = generate(Cli::command(), Shell::Fish);
+        assert!(script.contains(
+            "complete -c tagref -n \"__fish_tagref_using_subcommand show open rename repin\" -f \
+             -a \"(tagref list-tags --labels 2>/dev/null)\"",
+        ));
+    }
+
+    #[test]
+    fn powershell_tags() {
+        let script = generate(Cli::command(), Shell::Powershell);
+        assert!(script.contains(
+            "param($wordToComplete, $commandAst, $cursorPosition)\n\n    # Complete the labels",
+        ));
+        assert!(script.contains("-match '^(show,0|open,0|rename,0|repin,\\d+)$'"));
+        assert!(script.contains("\"$subcommand,$positional\""));
+    }
+}
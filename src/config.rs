@@ -0,0 +1,369 @@
+use crate::format::Format;
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+use tagref::{
+    custom_labels, duplicates, extractors, namespaces, naming, pairings, repositories, severities,
+    validators,
+};
+
+// This is the name of the configuration file which is loaded automatically, if it exists.
+pub const DEFAULT_PATH: &str = "tagref.toml";
+
+// This struct holds a value for each kind of directive.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PerType {
+    pub tag: Option<String>,
+    pub r#ref: Option<String>,
+    pub file: Option<String>,
+    pub dir: Option<String>,
+    pub url: Option<String>,
+}
+
+// This struct represents the contents of a configuration file. Every setting is optional, and
+// command-line flags take precedence over it.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    pub paths: Option<Vec<PathBuf>>,
+    pub roots: Option<Vec<PathBuf>>,
+    pub isolate_roots: Option<bool>,
+    pub scopes: Vec<PathBuf>,
+    pub sigils: PerType,
+    pub regexes: PerType,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub format: Option<Format>,
+    pub comments_only: Option<bool>,
+    pub scan_archives: Option<bool>,
+    pub case_insensitive_labels: Option<bool>,
+    pub relative_paths: Option<bool>,
+    pub encoding: Option<String>,
+    pub check_urls: Option<bool>,
+    pub check_issues: Option<bool>,
+    pub duplicates: Vec<duplicates::Rule>,
+    pub naming: naming::Conventions,
+    pub namespaces: Vec<namespaces::Rule>,
+    pub pairings: Vec<pairings::Rule>,
+    pub deny_unused: Option<bool>,
+    pub allow_unused: Vec<String>,
+    pub min_refs: BTreeMap<String, usize>,
+    pub hotspot_threshold: Option<usize>,
+    pub auto_tag_names: Option<bool>,
+    pub editor: Vec<String>,
+    pub deny_empty_dirs: Option<bool>,
+    pub strict_path_case: Option<bool>,
+    pub markdown_links: Option<bool>,
+    pub docs_dir: Option<PathBuf>,
+    pub tracked_only: Option<bool>,
+    pub max_violations: Option<usize>,
+    pub fail_on_warn: Option<bool>,
+    pub severities: Vec<severities::Rule>,
+    pub custom_labels: Vec<custom_labels::Definition>,
+    pub extractors: Vec<extractors::Command>,
+    pub validators: Vec<validators::Command>,
+    pub aliases: BTreeMap<String, String>,
+    pub deny_deprecated_aliases: Option<bool>,
+    pub repositories: BTreeMap<String, repositories::Repository>,
+    pub no_ignore: Option<bool>,
+    pub follow_symlinks: Option<bool>,
+    pub cache: Option<PathBuf>,
+    pub baseline: Option<PathBuf>,
+    pub max_filesize: Option<String>,
+    pub timeout: Option<String>,
+}
+
+// This function parses a configuration file.
+pub fn parse(contents: &str, path: &Path) -> Result<Config, String> {
+    toml::from_str(contents)
+        .map_err(|error| format!("Error when parsing {}: {error}", path.to_string_lossy()))
+}
+
+// This function loads the configuration file at the given path, or the default one if no path is
+// given. It's an error for an explicitly given file to be missing, but the default one is
+// optional. The path of the file that was loaded is returned along with its contents.
+pub fn load(path: Option<&Path>) -> Result<Option<(PathBuf, Config)>, String> {
+    let (path, required) = path.map_or_else(
+        || (Path::new(DEFAULT_PATH).to_owned(), false),
+        |path| (path.to_owned(), true),
+    );
+
+    match fs::read_to_string(&path) {
+        Ok(contents) => parse(&contents, &path).map(|config| Some((path, config))),
+        Err(error) if error.kind() == ErrorKind::NotFound && !required => Ok(None),
+        Err(error) => Err(format!(
+            "Error when reading {}: {error}",
+            path.to_string_lossy(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::{Config, parse},
+        format::Format,
+    };
+    use std::path::{Path, PathBuf};
+    use tagref::{
+        duplicates, pairings,
+        violation::{Kind, Severity},
+    };
+
+    #[test]
+    fn parse_empty() {
+        assert_eq!(
+            parse("", Path::new("tagref.toml")).unwrap(),
+            Config::default(),
+        );
+    }
+
+    #[test]
+    fn parse_full() {
+        let config = parse(
+            r#"
+paths = ["src", "docs"]
+roots = ["services/api", "services/web"]
+isolate-roots = true
+scopes = ["crates/a", "crates/b"]
+include = ["*.rs"]
+exclude = ["vendor/"]
+format = "json"
+comments-only = true
+scan-archives = true
+case-insensitive-labels = true
+relative-paths = true
+encoding = "latin1"
+check-urls = true
+check-issues = true
+no-ignore = true
+follow-symlinks = true
+cache = ".cache"
+baseline = "baseline.json"
+max-filesize = "20M"
+timeout = "10m"
+deny-unused = true
+deny-deprecated-aliases = true
+allow-unused = ["legacy"]
+deny-empty-dirs = true
+strict-path-case = true
+markdown-links = true
+docs-dir = "docs"
+tracked-only = true
+auto-tag-names = true
+editor = ["code", "--goto", "{path}:{line}:{column}"]
+max-violations = 12
+fail-on-warn = true
+
+[aliases]
+old-name = "new-name"
+
+[min-refs]
+wire-format = 2
+
+[sigils]
+tag = "note"
+ref = "see"
+
+[regexes]
+file = '<<([^>]*)>>'
+
+[[duplicates]]
+prefix = "generated/"
+policy = "canonical"
+canonical-paths = ["src"]
+
+[[severities]]
+kind = "unused_tag"
+severity = "warn"
+paths = ["docs/**"]
+
+[[namespaces]]
+namespace = "security"
+pattern = "^[a-z-]+$"
+allowed-paths = ["src/auth"]
+
+[[pairings]]
+first = "schema.sql"
+second = "migrations"
+
+[[extractors]]
+paths = ["docs/*.pdf"]
+command = ["pdftotext", "-", "-"]
+
+[[validators]]
+command = ["./scripts/check-epics"]
+"#,
+            Path::new("tagref.toml"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.paths,
+            Some(vec![PathBuf::from("src"), PathBuf::from("docs")]),
+        );
+        assert_eq!(
+            config.roots,
+            Some(vec![
+                PathBuf::from("services/api"),
+                PathBuf::from("services/web"),
+            ]),
+        );
+        assert_eq!(config.isolate_roots, Some(true));
+        assert_eq!(
+            config.scopes,
+            vec![PathBuf::from("crates/a"), PathBuf::from("crates/b")],
+        );
+        assert_eq!(config.include, vec!["*.rs".to_owned()]);
+        assert_eq!(config.exclude, vec!["vendor/".to_owned()]);
+        assert_eq!(config.format, Some(Format::Json));
+        assert_eq!(config.comments_only, Some(true));
+        assert_eq!(config.scan_archives, Some(true));
+        assert_eq!(config.case_insensitive_labels, Some(true));
+        assert_eq!(config.relative_paths, Some(true));
+        assert_eq!(config.encoding.as_deref(), Some("latin1"));
+        assert_eq!(config.check_urls, Some(true));
+        assert_eq!(config.check_issues, Some(true));
+        assert_eq!(config.no_ignore, Some(true));
+        assert_eq!(config.follow_symlinks, Some(true));
+        assert_eq!(config.cache, Some(PathBuf::from(".cache")));
+        assert_eq!(config.baseline, Some(PathBuf::from("baseline.json")));
+        assert_eq!(config.max_filesize.as_deref(), Some("20M"));
+        assert_eq!(config.timeout.as_deref(), Some("10m"));
+        assert_eq!(config.deny_unused, Some(true));
+        assert_eq!(config.deny_deprecated_aliases, Some(true));
+        assert_eq!(config.aliases["old-name"], "new-name");
+        assert_eq!(config.deny_empty_dirs, Some(true));
+        assert_eq!(config.strict_path_case, Some(true));
+        assert_eq!(config.markdown_links, Some(true));
+        assert_eq!(config.docs_dir, Some(PathBuf::from("docs")));
+        assert_eq!(config.tracked_only, Some(true));
+        assert_eq!(config.auto_tag_names, Some(true));
+        assert_eq!(config.editor[2], "{path}:{line}:{column}");
+        assert_eq!(config.allow_unused, vec!["legacy".to_owned()]);
+        assert_eq!(config.min_refs["wire-format"], 2);
+        assert_eq!(config.max_violations, Some(12));
+        assert_eq!(config.fail_on_warn, Some(true));
+        assert_eq!(config.severities.len(), 1);
+        assert_eq!(config.severities[0].kind, Kind::UnusedTag);
+        assert_eq!(config.severities[0].severity, Severity::Warning);
+        assert_eq!(config.sigils.tag.as_deref(), Some("note"));
+        assert_eq!(config.sigils.r#ref.as_deref(), Some("see"));
+        assert_eq!(config.sigils.file, None);
+        assert_eq!(config.regexes.file.as_deref(), Some("<<([^>]*)>>"));
+        assert_eq!(
+            config.duplicates,
+            vec![duplicates::Rule {
+                prefix: "generated/".to_owned(),
+                policy: duplicates::Policy::Canonical,
+                canonical_paths: vec![PathBuf::from("src")],
+            }],
+        );
+        assert_eq!(config.namespaces.len(), 1);
+        assert_eq!(config.namespaces[0].namespace, "security");
+        assert_eq!(
+            config.namespaces[0]
+                .pattern
+                .as_ref()
+                .map(|pattern| pattern.0.as_str()),
+            Some("^[a-z-]+$"),
+        );
+        assert_eq!(
+            config.namespaces[0].allowed_paths,
+            vec![PathBuf::from("src/auth")],
+        );
+        assert_eq!(
+            config.pairings,
+            vec![pairings::Rule {
+                first: PathBuf::from("schema.sql"),
+                second: PathBuf::from("migrations"),
+            }],
+        );
+        assert_eq!(config.extractors.len(), 1);
+        assert_eq!(
+            config.extractors[0].command,
+            vec!["pdftotext".to_owned(), "-".to_owned(), "-".to_owned()],
+        );
+        assert_eq!(config.validators.len(), 1);
+        assert_eq!(
+            config.validators[0].command,
+            vec!["./scripts/check-epics".to_owned()],
+        );
+    }
+
+    #[test]
+    fn parse_naming() {
+        let config = parse(
+            r#"
+[naming]
+pattern = "^[a-z0-9_/-]+$"
+max-length = 40
+reserved = ["todo"]
+"#,
+            Path::new("tagref.toml"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            config
+                .naming
+                .pattern
+                .as_ref()
+                .map(|pattern| pattern.0.as_str()),
+            Some("^[a-z0-9_/-]+$"),
+        );
+        assert_eq!(config.naming.max_length, Some(40));
+        assert_eq!(config.naming.reserved, vec!["todo".to_owned()]);
+    }
+
+    #[test]
+    fn parse_repositories() {
+        let config = parse(
+            r#"
+[repositories]
+shared = { path = "../shared" }
+api = { url = "https://example.com/tags.json" }
+billing = { index = "tags.json" }
+"#,
+            Path::new("tagref.toml"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.repositories["shared"].path,
+            Some(PathBuf::from("../shared")),
+        );
+        assert_eq!(
+            config.repositories["api"].url.as_deref(),
+            Some("https://example.com/tags.json"),
+        );
+        assert_eq!(
+            config.repositories["billing"].index,
+            Some(PathBuf::from("tags.json")),
+        );
+    }
+
+    #[test]
+    fn parse_invalid_pattern() {
+        let error = parse(
+            "[[namespaces]]\nnamespace = \"foo\"\npattern = \"(\"\n",
+            Path::new("tagref.toml"),
+        )
+        .unwrap_err();
+
+        assert!(error.contains("Invalid regex"));
+    }
+
+    #[test]
+    fn parse_unknown_field() {
+        let error = parse("colour = true", Path::new("tagref.toml")).unwrap_err();
+
+        assert!(error.contains("tagref.toml"));
+        assert!(error.contains("colour"));
+    }
+}
@@ -0,0 +1,97 @@
+use crate::{
+    directive::{self, Directive},
+    encoding::{self, Decoded},
+};
+use encoding_rs::Encoding;
+use serde::Serialize;
+use std::fs;
+
+// This struct represents a line of a file.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct Line {
+    pub line_number: usize,
+    pub text: String,
+}
+
+// This struct represents a directive along with the lines around it.
+#[derive(Clone, Debug, Serialize)]
+pub struct Snippet<'a> {
+    #[serde(flatten)]
+    pub directive: &'a Directive,
+
+    pub context: Vec<Line>,
+}
+
+// This function returns the given line of some text, along with up to `context` lines on either
+// side of it. Line numbers start at 1.
+#[must_use]
+pub fn lines(text: &str, line_number: usize, context: usize) -> Vec<Line> {
+    let first = line_number.saturating_sub(context).max(1);
+    directive::lines(text)
+        .map(|(line, _)| line)
+        .enumerate()
+        .skip(first - 1)
+        .take(line_number + context + 1 - first)
+        .map(|(index, text)| Line {
+            line_number: index + 1,
+            text: text.to_owned(),
+        })
+        .collect()
+}
+
+// This function reads the lines around a directive from its file.
+pub fn snippet<'a>(
+    directive: &'a Directive,
+    context: usize,
+    fallback_encoding: Option<&'static Encoding>,
+) -> Result<Snippet<'a>, String> {
+    let bytes = fs::read(&directive.path).map_err(|error| {
+        format!(
+            "Error when reading file {}: {error}",
+            directive.path.to_string_lossy(),
+        )
+    })?;
+
+    let context = match encoding::decode(&bytes, fallback_encoding) {
+        Decoded::Text(text) | Decoded::Lossy(text) => lines(&text, directive.line_number, context),
+        // The file must have changed since it was scanned, so there's nothing sensible to show.
+        Decoded::Binary => Vec::new(),
+    };
+
+    Ok(Snippet { directive, context })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::context::lines;
+
+    fn line_numbers(text: &str, line_number: usize, context: usize) -> Vec<usize> {
+        lines(text, line_number, context)
+            .iter()
+            .map(|line| line.line_number)
+            .collect()
+    }
+
+    #[test]
+    fn lines_middle() {
+        let text = "a\nb\nc\nd\ne\n";
+
+        assert_eq!(line_numbers(text, 3, 1), vec![2, 3, 4]);
+        assert_eq!(lines(text, 3, 1)[1].text, "c");
+    }
+
+    #[test]
+    fn lines_start() {
+        assert_eq!(line_numbers("a\nb\nc\nd\ne\n", 1, 2), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn lines_end() {
+        assert_eq!(line_numbers("a\nb\nc\nd\ne\n", 5, 2), vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn lines_no_context() {
+        assert_eq!(line_numbers("a\nb\nc\n", 2, 0), vec![2]);
+    }
+}
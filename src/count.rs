@@ -1,5 +1,6 @@
 // This function takes a number and a noun and returns a string representing the noun with the given
 // multiplicity (pluralizing if necessary). For example, `count(3, "cow")` becomes "3 cows".
+#[must_use]
 pub fn count(n: usize, noun: &str) -> String {
     if n == 1 {
         format!("{n} {noun}")
@@ -0,0 +1,286 @@
+use crate::{
+    directive::{Directive, Type, compile_custom_regex, compile_directive_regex},
+    issues::Tracker,
+    namespaces::Pattern,
+    violation::{Kind, Severity, Violation},
+};
+use regex::Regex;
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    process::Command,
+};
+
+// This struct defines a kind of label beyond the built-in ones, such as `[issue:1234]`, along with
+// the validators its labels must pass.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Definition {
+    pub sigil: String,
+
+    // A regex for matching the labels instead of the default one for the sigil. The first capture
+    // group is taken to be the label.
+    pub regex: Option<String>,
+
+    // If given, each label must match this regex.
+    pub pattern: Option<Pattern>,
+
+    // If given, this path must exist after `{label}` is replaced with each label.
+    pub path: Option<String>,
+
+    // If given, this command must succeed after `{label}` is replaced with each label in its
+    // arguments. The first element is the program to run.
+    pub command: Vec<String>,
+
+    // If given, the labels are issues in this tracker, and `--check-issues` reports the ones which
+    // are closed.
+    pub tracker: Option<Tracker>,
+}
+
+impl Definition {
+    // This function runs the validators for a label. It returns an explanation if the label is
+    // invalid.
+    fn validate(&self, label: &str) -> Option<String> {
+        if let Some(Pattern(regex)) = &self.pattern
+            && !regex.is_match(label)
+        {
+            return Some(format!("does not match the pattern `{}`", regex.as_str()));
+        }
+
+        if let Some(template) = &self.path {
+            let path = template.replace("{label}", label);
+            if !Path::new(&path).exists() {
+                return Some(format!("points to `{path}`, which does not exist"));
+            }
+        }
+
+        if let Some((program, args)) = self.command.split_first() {
+            let output = Command::new(program)
+                .args(args.iter().map(|arg| arg.replace("{label}", label)))
+                .output();
+            match output {
+                Ok(output) if output.status.success() => {}
+                Ok(output) => {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    return Some(if stderr.trim().is_empty() {
+                        format!("was rejected by `{program}`")
+                    } else {
+                        format!("was rejected by `{program}`: {}", stderr.trim())
+                    });
+                }
+                Err(error) => return Some(format!("could not be validated: {error}")),
+            }
+        }
+
+        None
+    }
+}
+
+// This function compiles the regexes for matching the custom labels, each paired with its sigil.
+// Every kind needs a sigil which isn't used by any other kind, including the built-in ones.
+pub fn regexes(definitions: &[Definition]) -> Result<Vec<(String, Regex)>, String> {
+    let mut sigils = [
+        Type::Tag,
+        Type::Ref,
+        Type::File,
+        Type::Dir,
+        Type::Url,
+        Type::Alias,
+        Type::Anchor,
+    ]
+    .iter()
+    .map(|r#type| r#type.sigil().to_owned())
+    .collect::<HashSet<_>>();
+
+    definitions
+        .iter()
+        .map(|definition| {
+            let sigil = definition.sigil.trim();
+            if sigil.is_empty() {
+                return Err("Every kind of custom label needs a sigil.".to_owned());
+            }
+            if !sigils.insert(sigil.to_owned()) {
+                return Err(format!(
+                    "The sigil `{sigil}` is used by more than one kind of label."
+                ));
+            }
+
+            let regex = match &definition.regex {
+                Some(pattern) => compile_custom_regex(pattern)?,
+                None => compile_directive_regex(sigil),
+            };
+            Ok((sigil.to_owned(), regex))
+        })
+        .collect()
+}
+
+// This function checks that the custom labels pass the validators for their kinds. Each distinct
+// label is only validated once, since validators may be slow. It returns a vector of violations.
+#[must_use]
+pub fn check(labels: &[Directive], definitions: &[Definition]) -> Vec<Violation> {
+    let mut errors = Vec::<Violation>::new();
+    let mut results = HashMap::<(&str, &str), Option<String>>::new();
+
+    for label in labels {
+        let Type::Custom(sigil) = &label.r#type else {
+            continue;
+        };
+        let Some(definition) = definitions
+            .iter()
+            .find(|definition| definition.sigil.trim() == sigil)
+        else {
+            continue;
+        };
+
+        let result = results
+            .entry((sigil, &label.label))
+            .or_insert_with(|| definition.validate(&label.label));
+        if let Some(explanation) = result {
+            errors.push(Violation {
+                kind: Kind::InvalidCustomLabel,
+                severity: Severity::Error,
+                message: format!("{label} {explanation}."),
+                directives: vec![label.clone()],
+                suggestions: Vec::new(),
+            });
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        custom_labels::{Definition, check, regexes},
+        directive::{Directive, Type},
+        namespaces::Pattern,
+        violation::Kind,
+    };
+    use regex::Regex;
+
+    #[test]
+    fn regexes_default() {
+        let definitions = [Definition {
+            sigil: "issue".to_owned(),
+            ..Definition::default()
+        }];
+
+        let regexes = regexes(&definitions).unwrap();
+
+        assert_eq!(regexes.len(), 1);
+        assert_eq!(regexes[0].0, "issue");
+        assert_eq!(
+            &regexes[0]
+                .1
+                .captures("[?issue:42]".replace('?', "").as_str())
+                .unwrap()[1],
+            "42"
+        );
+    }
+
+    #[test]
+    fn regexes_builtin_sigil() {
+        let definitions = [Definition {
+            sigil: "tag".to_owned(),
+            ..Definition::default()
+        }];
+
+        assert!(regexes(&definitions).is_err());
+    }
+
+    #[test]
+    fn check_no_validators() {
+        let definitions = [Definition {
+            sigil: "issue".to_owned(),
+            ..Definition::default()
+        }];
+
+        assert!(
+            check(
+                &[Directive::new(
+                    Type::Custom("issue".to_owned()),
+                    "anything",
+                    "./foo.rs",
+                    1
+                )],
+                &definitions
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn check_pattern() {
+        let definitions = [Definition {
+            sigil: "issue".to_owned(),
+            pattern: Some(Pattern(Regex::new("^[0-9]+$").unwrap())),
+            ..Definition::default()
+        }];
+
+        let violations = check(
+            &[
+                Directive::new(Type::Custom("issue".to_owned()), "1234", "./foo.rs", 1),
+                Directive::new(Type::Custom("issue".to_owned()), "abc", "./foo.rs", 1),
+                Directive::new(Type::Custom("adr".to_owned()), "abc", "./foo.rs", 1),
+            ],
+            &definitions,
+        );
+
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].kind, Kind::InvalidCustomLabel);
+        assert_eq!(violations[0].directives[0].label, "abc");
+    }
+
+    #[test]
+    fn check_path() {
+        let definitions = [Definition {
+            sigil: "src".to_owned(),
+            path: Some("src/{label}.rs".to_owned()),
+            ..Definition::default()
+        }];
+
+        let violations = check(
+            &[
+                Directive::new(
+                    Type::Custom("src".to_owned()),
+                    "custom_labels",
+                    "./foo.rs",
+                    1,
+                ),
+                Directive::new(Type::Custom("src".to_owned()), "nonexistent", "./foo.rs", 1),
+            ],
+            &definitions,
+        );
+
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].directives[0].label, "nonexistent");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_command() {
+        let definitions = [Definition {
+            sigil: "issue".to_owned(),
+            command: vec![
+                "test".to_owned(),
+                "{label}".to_owned(),
+                "=".to_owned(),
+                "ok".to_owned(),
+            ],
+            ..Definition::default()
+        }];
+
+        let violations = check(
+            &[
+                Directive::new(Type::Custom("issue".to_owned()), "ok", "./foo.rs", 1),
+                Directive::new(Type::Custom("issue".to_owned()), "bad", "./foo.rs", 1),
+            ],
+            &definitions,
+        );
+
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].directives[0].label, "bad");
+    }
+}
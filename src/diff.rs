@@ -0,0 +1,260 @@
+use crate::{
+    baseline::Entry,
+    directive::{self, Directive},
+    index::{Index, Rules},
+    violation::Violation,
+    walk,
+};
+use serde::Serialize;
+use std::{
+    collections::{BTreeSet, HashMap},
+    hash::Hash,
+    path::PathBuf,
+};
+
+// This struct describes a tag which seems to have been renamed.
+#[derive(Clone, Debug, Serialize)]
+pub struct Rename {
+    pub before: Directive,
+    pub after: Directive,
+}
+
+// This struct describes how the labels changed between two versions of the files, e.g., two Git
+// revisions.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Diff {
+    pub tags_added: Vec<Directive>,
+    pub tags_removed: Vec<Directive>,
+    pub tags_renamed: Vec<Rename>,
+    pub refs_added: Vec<Directive>,
+    pub refs_removed: Vec<Directive>,
+    pub violations_introduced: Vec<Violation>,
+    pub violations_fixed: Vec<Violation>,
+}
+
+impl Diff {
+    // This function determines whether nothing changed.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.tags_added.is_empty()
+            && self.tags_removed.is_empty()
+            && self.tags_renamed.is_empty()
+            && self.refs_added.is_empty()
+            && self.refs_removed.is_empty()
+            && self.violations_introduced.is_empty()
+            && self.violations_fixed.is_empty()
+    }
+}
+
+// This function returns the items in `xs` which aren't in `ys`, as identified by the given key.
+// Duplicates are counted, so an item which is in `xs` twice but in `ys` once is returned once.
+fn difference<T: Clone, K: Eq + Hash>(xs: &[T], ys: &[T], key: impl Fn(&T) -> K) -> Vec<T> {
+    let mut remaining = HashMap::<K, usize>::new();
+    for y in ys {
+        *remaining.entry(key(y)).or_insert(0) += 1;
+    }
+
+    xs.iter()
+        .filter(|x| match remaining.get_mut(&key(x)) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                false
+            }
+            _ => true,
+        })
+        .cloned()
+        .collect()
+}
+
+// This function returns the files which reference each tag, keyed as in the index.
+fn referrers(index: &Index) -> HashMap<String, BTreeSet<PathBuf>> {
+    let mut referrers = HashMap::<String, BTreeSet<PathBuf>>::new();
+    for r#ref in &index.refs {
+        referrers
+            .entry(index.key(&r#ref.label).into_owned())
+            .or_default()
+            .insert(walk::relative(&r#ref.path));
+    }
+    referrers
+}
+
+// This function guesses which of the removed tags were renamed to which of the added ones. A
+// removed tag and an added tag are considered the same tag if they're in the same file and they
+// have the same description, they're on the same line, or they're referenced from the same files.
+// The matched tags are moved out of `removed` and `added`.
+fn renames(
+    removed: &mut Vec<Directive>,
+    added: &mut Vec<Directive>,
+    before: &Index,
+    after: &Index,
+) -> Vec<Rename> {
+    let before_referrers = referrers(before);
+    let after_referrers = referrers(after);
+    let mut renames = Vec::new();
+
+    removed.retain(|old| {
+        let old_referrers = before_referrers.get(&*before.key(&old.label));
+        let position = added.iter().position(|new| {
+            walk::relative(&new.path) == walk::relative(&old.path)
+                && ((new.description.is_some() && new.description == old.description)
+                    || new.line_number == old.line_number
+                    || (old_referrers.is_some()
+                        && old_referrers == after_referrers.get(&*after.key(&new.label))))
+        });
+        if let Some(position) = position {
+            renames.push(Rename {
+                before: old.clone(),
+                after: added.remove(position),
+            });
+        }
+        position.is_none()
+    });
+
+    renames
+}
+
+// This function compares the labels in two indexes and the violations found in them. Tags are
+// compared by label, references by label and file, and violations by the labels and files
+// involved, so labels which merely moved within their files aren't reported.
+#[must_use]
+pub fn compare(before: &Index, after: &Index, rules: &Rules) -> Diff {
+    let tags = |index: &Index| index.tags.values().flatten().cloned().collect::<Vec<_>>();
+    let (before_tags, after_tags) = (tags(before), tags(after));
+    let mut tags_removed = difference(&before_tags, &after_tags, |tag| {
+        directive::key(&tag.label, before.case_insensitive_labels).into_owned()
+    });
+    let mut tags_added = difference(&after_tags, &before_tags, |tag| {
+        directive::key(&tag.label, after.case_insensitive_labels).into_owned()
+    });
+    let tags_renamed = renames(&mut tags_removed, &mut tags_added, before, after);
+
+    let ref_key = |r#ref: &Directive| {
+        (
+            walk::relative(&r#ref.path),
+            directive::key(&r#ref.label, after.case_insensitive_labels).into_owned(),
+        )
+    };
+
+    let violations = |index: &Index| {
+        let mut violations = index.check(rules);
+        index.suppress(&mut violations);
+        violations
+    };
+    let (before_violations, after_violations) = (violations(before), violations(after));
+
+    Diff {
+        tags_added,
+        tags_removed,
+        tags_renamed,
+        refs_added: difference(&after.refs, &before.refs, ref_key),
+        refs_removed: difference(&before.refs, &after.refs, ref_key),
+        violations_introduced: difference(&after_violations, &before_violations, Entry::new),
+        violations_fixed: difference(&before_violations, &after_violations, Entry::new),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        diff::compare,
+        directive::{Directive, Type},
+        index::{Index, Rules},
+        violation::Kind,
+    };
+    use std::collections::BTreeMap;
+
+    fn index(tags: Vec<Directive>, refs: Vec<Directive>) -> Index {
+        let mut tags_map = BTreeMap::<String, Vec<Directive>>::new();
+        for tag in tags {
+            tags_map.entry(tag.label.clone()).or_default().push(tag);
+        }
+        Index {
+            tags: tags_map,
+            refs,
+            ..Index::default()
+        }
+    }
+
+    #[test]
+    fn compare_unchanged() {
+        let before = index(
+            vec![Directive::new(Type::Tag, "foo", "./a.rs", 1)],
+            vec![Directive::new(Type::Ref, "foo", "./b.rs", 1)],
+        );
+        let after = index(
+            vec![Directive::new(Type::Tag, "foo", "./a.rs", 5)],
+            vec![Directive::new(Type::Ref, "foo", "./b.rs", 9)],
+        );
+
+        assert!(compare(&before, &after, &Rules::default()).is_empty());
+    }
+
+    #[test]
+    fn compare_added_and_removed() {
+        let before = index(
+            vec![Directive::new(Type::Tag, "foo", "./a.rs", 1)],
+            vec![
+                Directive::new(Type::Ref, "foo", "./b.rs", 1),
+                Directive::new(Type::Ref, "foo", "./b.rs", 2),
+            ],
+        );
+        let after = index(
+            vec![Directive::new(Type::Tag, "bar", "./c.rs", 1)],
+            vec![
+                Directive::new(Type::Ref, "foo", "./b.rs", 1),
+                Directive::new(Type::Ref, "bar", "./b.rs", 2),
+            ],
+        );
+
+        let diff = compare(&before, &after, &Rules::default());
+
+        assert_eq!(diff.tags_added[0].label, "bar");
+        assert_eq!(diff.tags_removed[0].label, "foo");
+        assert!(diff.tags_renamed.is_empty());
+        assert_eq!(diff.refs_added.len(), 1);
+        assert_eq!(diff.refs_added[0].label, "bar");
+        assert_eq!(diff.refs_removed.len(), 1);
+        assert_eq!(diff.refs_removed[0].label, "foo");
+        assert_eq!(diff.violations_introduced.len(), 1);
+        assert_eq!(diff.violations_introduced[0].kind, Kind::DanglingRef);
+        assert!(diff.violations_fixed.is_empty());
+    }
+
+    #[test]
+    fn compare_renamed() {
+        let mut described = Directive::new(Type::Tag, "old-b", "./a.rs", 3);
+        described.description = Some("Never zero.".to_owned());
+        let before = index(
+            vec![
+                Directive::new(Type::Tag, "old-a", "./a.rs", 1),
+                described.clone(),
+                Directive::new(Type::Tag, "old-c", "./a.rs", 5),
+            ],
+            vec![Directive::new(Type::Ref, "old-c", "./b.rs", 1)],
+        );
+        described.label = "new-b".to_owned();
+        described.line_number = 4;
+        let after = index(
+            vec![
+                Directive::new(Type::Tag, "new-a", "./a.rs", 1),
+                described,
+                Directive::new(Type::Tag, "new-c", "./a.rs", 9),
+                Directive::new(Type::Tag, "new-d", "./d.rs", 5),
+            ],
+            vec![Directive::new(Type::Ref, "new-c", "./b.rs", 1)],
+        );
+
+        let diff = compare(&before, &after, &Rules::default());
+
+        assert_eq!(
+            diff.tags_renamed
+                .iter()
+                .map(|rename| (rename.before.label.as_str(), rename.after.label.as_str()))
+                .collect::<Vec<_>>(),
+            vec![("old-a", "new-a"), ("old-b", "new-b"), ("old-c", "new-c")],
+        );
+        assert!(diff.tags_removed.is_empty());
+        assert_eq!(diff.tags_added.len(), 1);
+        assert_eq!(diff.tags_added[0].label, "new-d");
+    }
+}
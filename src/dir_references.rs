@@ -1,24 +1,218 @@
-use crate::directive::Directive;
-use std::fs::metadata;
+use crate::{
+    archives::{self, Archives},
+    directive::Directive,
+    git::Tree,
+    violation::{Kind, Severity, Violation},
+    walk,
+};
+use globset::GlobBuilder;
+use ignore::WalkBuilder;
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
 
-// This function checks that directory references actually point to directories. It returns a vector
-// of error strings.
-pub fn check(refs: &[Directive]) -> Vec<String> {
-    let mut errors = Vec::<String>::new();
+// These characters make a directory reference a glob rather than a path.
+const GLOB_CHARACTERS: [char; 4] = ['*', '?', '[', '{'];
 
-    for dir in refs {
-        match metadata(&dir.label) {
+// This function determines whether a directory reference is a glob, like `src/**/fixtures`.
+pub(crate) fn is_glob(label: &str) -> bool {
+    label.contains(GLOB_CHARACTERS)
+}
+
+// This function determines whether a directory has no entries. Errors are treated as the directory
+// being nonempty, since they're reported elsewhere if they matter.
+fn is_empty(path: &Path) -> bool {
+    fs::read_dir(path).is_ok_and(|mut entries| entries.next().is_none())
+}
+
+// This function finds the directories matching a glob, in the given Git tree if there is one.
+// Otherwise, only the directory named by the components before the first one with a glob character
+// is searched.
+fn matching_dirs(glob: &str, tree: Option<&Tree>) -> Result<Vec<PathBuf>, String> {
+    let glob = walk::normalize(Path::new(glob));
+    let matcher = GlobBuilder::new(&glob.to_string_lossy())
+        .literal_separator(true)
+        .build()
+        .map_err(|error| error.kind().to_string())?
+        .compile_matcher();
+
+    if let Some(tree) = tree {
+        return Ok(tree
+            .dirs()
+            .filter(|path| matcher.is_match(path))
+            .map(Path::to_owned)
+            .collect());
+    }
+
+    let base = glob
+        .components()
+        .take_while(|component| {
+            !matches!(
+                component,
+                Component::Normal(name) if name.to_string_lossy().contains(GLOB_CHARACTERS)
+            )
+        })
+        .collect::<PathBuf>();
+    let base = if base.as_os_str().is_empty() {
+        Path::new(".").to_owned()
+    } else {
+        base
+    };
+
+    Ok(WalkBuilder::new(base)
+        .standard_filters(false)
+        .filter_entry(|entry| entry.file_name() != ".git" && entry.file_name() != ".hg")
+        .build()
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry
+                .file_type()
+                .is_some_and(|file_type| file_type.is_dir())
+        })
+        .map(|entry| walk::relative(entry.path()))
+        .filter(|path| matcher.is_match(path))
+        .collect())
+}
+
+// This function checks that directory references actually point to directories. A reference which
+// is a glob must match at least one directory. Paths are resolved as described in `walk::resolve`.
+// If `deny_empty` is set, the directories must also be nonempty (for a glob, at least one of
+// them). If `strict_case` is set, the paths must have the same case as on disk. Paths inside
+// archives are checked against the directories in the archives, but globs don't match them. If a
+// Git tree is given, the paths are checked against it rather than the filesystem. It returns a
+// vector of violations.
+pub fn check(
+    refs: &[Directive],
+    relative_paths: bool,
+    roots: &[PathBuf],
+    deny_empty: bool,
+    strict_case: bool,
+    tree: Option<&Tree>,
+) -> Vec<Violation> {
+    let mut errors = Vec::<Violation>::new();
+    let mut archives = Archives::new(tree);
+
+    // Look up the directories on disk all at once, since there may be many of them.
+    let paths = refs
+        .iter()
+        .map(|dir| walk::resolve(&dir.label, &dir.path, relative_paths, roots))
+        .collect::<Vec<_>>();
+    let targets = if tree.is_none() {
+        walk::Targets::look_up(
+            refs.iter()
+                .zip(&paths)
+                .filter(|(dir, path)| !is_glob(&dir.label) && archives::split(path).is_none())
+                .map(|(_, path)| path.clone()),
+            strict_case,
+        )
+    } else {
+        walk::Targets::default()
+    };
+
+    for (dir, path) in refs.iter().zip(paths) {
+        let mut push_error = |message| {
+            errors.push(Violation {
+                kind: Kind::InvalidDirRef,
+                severity: Severity::Error,
+                message,
+                directives: vec![dir.clone()],
+                suggestions: Vec::new(),
+            });
+        };
+
+        if is_glob(&dir.label) {
+            match matching_dirs(&path.to_string_lossy(), tree) {
+                Ok(paths) if paths.is_empty() => {
+                    push_error(format!("{dir} does not match any directories."));
+                }
+                Ok(paths)
+                    if deny_empty && tree.is_none() && paths.iter().all(|path| is_empty(path)) =>
+                {
+                    push_error(format!("{dir} only matches empty directories."));
+                }
+                Ok(_) => {}
+                Err(error) => push_error(format!("{dir} has an invalid glob: {error}")),
+            }
+            continue;
+        }
+
+        // Paths in archives and Git trees which differ only by case are different paths. Empty
+        // directories aren't reported in either, since Git doesn't track them.
+        if let Some((archive, inner)) = archives::split(&path) {
+            match archives.open(&archive) {
+                Ok(archive) if archive.is_dir(&inner) => {}
+                Ok(_) => push_error(format!("{dir} does not point to a directory.")),
+                Err(error) => push_error(format!("Error when validating {dir}: {error}")),
+            }
+            continue;
+        }
+        if let Some(tree) = tree {
+            if !tree.is_dir(&path) {
+                if tree.is_untracked(&path) {
+                    push_error(format!(
+                        "{dir} points to a directory without any files tracked by Git."
+                    ));
+                } else {
+                    push_error(format!("{dir} does not point to a directory."));
+                }
+            }
+            continue;
+        }
+
+        match targets.metadata(&path) {
             Ok(metadata) => {
                 if !metadata.is_dir() {
-                    errors.push(format!("{dir} does not point to a directory."));
+                    push_error(format!("{dir} does not point to a directory."));
+                } else if let Some(error) = strict_case
+                    .then(|| targets.check_case(&path).err())
+                    .flatten()
+                {
+                    push_error(format!("{dir} has the wrong case: {error}."));
+                } else if deny_empty && is_empty(&path) {
+                    push_error(format!("{dir} points to an empty directory."));
                 }
             }
             Err(error) => {
-                let error_string = error.to_string();
-                errors.push(format!("Error when validating {dir}: {error_string}"));
+                push_error(format!("Error when validating {dir}: {error}"));
             }
         }
     }
 
     errors
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::dir_references::{is_glob, matching_dirs};
+    use std::path::PathBuf;
+
+    #[test]
+    fn is_glob_path() {
+        assert!(!is_glob("src/fixtures"));
+        assert!(is_glob("src/**/fixtures"));
+        assert!(is_glob("src/{a,b}"));
+    }
+
+    #[test]
+    fn matching_dirs_found() {
+        assert_eq!(
+            matching_dirs("./s?c", None).unwrap(),
+            vec![PathBuf::from("src")]
+        );
+    }
+
+    #[test]
+    fn matching_dirs_missing_base() {
+        assert!(
+            matching_dirs("missing/**/fixtures", None)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn matching_dirs_invalid() {
+        assert!(matching_dirs("src/{a", None).is_err());
+    }
+}
@@ -1,24 +1,162 @@
-use regex::{Regex, escape};
+use crate::{
+    aliases::{self, Alias},
+    anchors, attributes,
+    comments::{self, Scanner},
+    encoding::{self, Decoded},
+    mapped, markdown, pins,
+    suppressions::{self, Suppression},
+};
+use encoding_rs::Encoding;
+use memchr::memchr2_iter;
+use regex::{Captures, Regex, RegexSet, escape};
+use serde::{Deserialize, Serialize, Serializer, ser::SerializeStruct};
 use std::{
-    fmt,
-    io::BufRead,
+    borrow::Cow,
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+    fmt, iter,
+    ops::Range,
     path::{Path, PathBuf},
+    sync::{Arc, LazyLock, Mutex},
 };
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Type {
     Tag,
     Ref,
     File,
     Dir,
+    Url,
+    Alias,
+    Anchor,
+
+    // A kind of label defined in the configuration file, identified by its sigil
+    #[serde(untagged)]
+    Custom(String),
+}
+
+impl Type {
+    // This is the sigil used for the type by default.
+    #[must_use]
+    pub fn sigil(&self) -> &str {
+        match self {
+            Self::Tag => "tag",
+            Self::Ref => "ref",
+            Self::File => "file",
+            Self::Dir => "dir",
+            Self::Url => "url",
+            Self::Alias => aliases::SIGIL,
+            Self::Anchor => anchors::SIGIL,
+            Self::Custom(sigil) => sigil,
+        }
+    }
 }
 
-#[derive(Clone, Debug)]
+// This struct locates a directive within a cell of a notebook, counting both from 1.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Cell {
+    pub number: usize,
+    pub line_number: usize,
+}
+
+#[derive(Clone, Debug, Deserialize)]
 pub struct Directive {
     pub r#type: Type,
     pub label: String,
-    pub path: PathBuf,
+    pub path: Arc<Path>,
     pub line_number: usize,
+
+    // The column where the directive starts, counting characters from 1
+    pub column: usize,
+
+    // The byte offsets of the directive within its line
+    pub span: Range<usize>,
+
+    // For tags, the prose after the tag on the same line or, for block tags, the text between the
+    // name and the closing bracket
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    // The `key=value` attributes after the label, e.g., `since=2023-04`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attributes: BTreeMap<String, String>,
+
+    // For directives in notebooks, the cell containing the directive and the line within it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cell: Option<Cell>,
+}
+
+// Directives are serialized with zero-based positions (as most editors count them) alongside the
+// one-based ones, so integrations don't have to guess which convention is used. The zero-based
+// positions are ignored when deserializing.
+impl Serialize for Directive {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Directive", 11)?;
+        state.serialize_field("type", &self.r#type)?;
+        state.serialize_field("label", &self.label)?;
+        state.serialize_field("path", &self.path)?;
+        state.serialize_field("line_number", &self.line_number)?;
+        state.serialize_field("column", &self.column)?;
+        state.serialize_field("line_index", &self.line_number.saturating_sub(1))?;
+        state.serialize_field("column_index", &self.column.saturating_sub(1))?;
+        state.serialize_field("span", &self.span)?;
+        match &self.description {
+            Some(description) => state.serialize_field("description", description)?,
+            None => state.skip_field("description")?,
+        }
+        if self.attributes.is_empty() {
+            state.skip_field("attributes")?;
+        } else {
+            state.serialize_field("attributes", &self.attributes)?;
+        }
+        match &self.cell {
+            Some(cell) => state.serialize_field("cell", cell)?,
+            None => state.skip_field("cell")?,
+        }
+        state.end()
+    }
+}
+
+impl Directive {
+    // This function builds a directive at the start of a line, without a description, attributes,
+    // or a cell, for the tests.
+    #[cfg(test)]
+    #[must_use]
+    pub fn new(r#type: Type, label: &str, path: &str, line_number: usize) -> Self {
+        Self {
+            r#type,
+            label: label.to_owned(),
+            path: Path::new(path).into(),
+            line_number,
+            column: 1,
+            span: 0..0,
+            description: None,
+            attributes: BTreeMap::new(),
+            cell: None,
+        }
+    }
+
+    // This function orders directives by path, then line, then label, with the column and type
+    // breaking any ties. Listings and reports use this order, so the output is the same on every
+    // run regardless of the order the files were scanned in.
+    #[must_use]
+    pub fn cmp_position(&self, other: &Self) -> Ordering {
+        (
+            &self.path,
+            self.line_number,
+            &self.label,
+            self.column,
+            &self.r#type,
+        )
+            .cmp(&(
+                &other.path,
+                other.line_number,
+                &other.label,
+                other.column,
+                &other.r#type,
+            ))
+    }
 }
 
 // Sometimes we need to be able to print a directive.
@@ -26,29 +164,119 @@ impl fmt::Display for Directive {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "[{}:{}] @ {}:{}",
-            match self.r#type {
-                Type::Tag => "tag",
-                Type::Ref => "ref",
-                Type::File => "file",
-                Type::Dir => "dir",
-            },
+            "[{}:{}{}] @ {}:{}:{}",
+            self.r#type.sigil(),
             self.label,
+            attributes::format(&self.attributes),
             self.path.to_string_lossy(),
             self.line_number,
-        )
+            self.column,
+        )?;
+        if let Some(cell) = self.cell {
+            write!(f, " (cell {}, line {})", cell.number, cell.line_number)?;
+        }
+        Ok(())
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Directives {
     pub tags: Vec<Directive>,
     pub refs: Vec<Directive>,
     pub files: Vec<Directive>,
     pub dirs: Vec<Directive>,
+    pub urls: Vec<Directive>,
+
+    // Labels of the kinds defined in the configuration file
+    pub custom: Vec<Directive>,
+
+    // Files which couldn't be decoded completely, so some labels in them may have been missed
+    pub undecodable: Vec<PathBuf>,
+
+    // Files inside archives which were skipped for being larger than the limit
+    pub too_large: Vec<PathBuf>,
+
+    // Comments which suppress violations
+    pub suppressions: Vec<Suppression>,
+
+    // Alternative labels for the tags
+    pub aliases: Vec<Alias>,
+
+    // Targets within the files for file references
+    pub anchors: Vec<Directive>,
+
+    // Tag references which are pinned to the content of their tags. Their labels include the
+    // hashes, unlike the same references in `refs`.
+    pub pins: Vec<Directive>,
+
+    // Links in Markdown files to other files, as file references labeled with the destinations
+    pub links: Vec<Directive>,
+
+    // Headings in Markdown files, as anchors labeled with their text
+    pub headings: Vec<Directive>,
+}
+
+impl Directives {
+    // This function adds the directives from another file, e.g., another file in the same archive.
+    pub fn extend(&mut self, other: Self) {
+        self.tags.extend(other.tags);
+        self.refs.extend(other.refs);
+        self.files.extend(other.files);
+        self.dirs.extend(other.dirs);
+        self.urls.extend(other.urls);
+        self.custom.extend(other.custom);
+        self.undecodable.extend(other.undecodable);
+        self.too_large.extend(other.too_large);
+        self.suppressions.extend(other.suppressions);
+        self.aliases.extend(other.aliases);
+        self.anchors.extend(other.anchors);
+        self.pins.extend(other.pins);
+        self.links.extend(other.links);
+        self.headings.extend(other.headings);
+    }
+
+    // This function iterates over all the labels, including the aliases.
+    pub fn labels_mut(&mut self) -> impl Iterator<Item = &mut Directive> {
+        self.tags
+            .iter_mut()
+            .chain(&mut self.refs)
+            .chain(&mut self.files)
+            .chain(&mut self.dirs)
+            .chain(&mut self.urls)
+            .chain(&mut self.custom)
+            .chain(&mut self.anchors)
+            .chain(&mut self.pins)
+            .chain(&mut self.links)
+            .chain(&mut self.headings)
+            .chain(self.aliases.iter_mut().map(|alias| &mut alias.directive))
+    }
+
+    // This function makes the labels from the same file share one copy of its path, e.g., after
+    // they're read from a cache.
+    pub fn share_paths(&mut self) {
+        let mut shared = None::<Arc<Path>>;
+        for label in self.labels_mut() {
+            match &shared {
+                Some(path) if *path == label.path => label.path = path.clone(),
+                _ => shared = Some(label.path.clone()),
+            }
+        }
+    }
+}
+
+// This function returns the form of a label which is used to match tags with references. If labels
+// are case-insensitive, that's the lowercase form. Otherwise, it's the label itself.
+#[must_use]
+pub fn key(label: &str, case_insensitive: bool) -> Cow<'_, str> {
+    if case_insensitive {
+        Cow::Owned(label.to_lowercase())
+    } else {
+        Cow::Borrowed(label)
+    }
 }
 
 // This function compiles a regular expression for matching a directive.
+#[must_use]
 pub fn compile_directive_regex(sigil: &str) -> Regex {
     Regex::new(&format!(
         "(?i)\\[\\s*{}\\s*:\\s*([^\\]]*?)\\s*\\]",
@@ -57,84 +285,560 @@ pub fn compile_directive_regex(sigil: &str) -> Regex {
     .unwrap() // Safe by manual inspection
 }
 
-// This function returns all the directives in a file for a given type.
-pub fn parse<R: BufRead>(
+// This function compiles a regular expression for matching the opening of a block tag, which is a
+// tag whose closing bracket is on a later line. The name takes up the rest of the opening line. It
+// must start with a word character and can't contain quotes or semicolons, so code which builds
+// labels in strings doesn't open a block.
+#[must_use]
+pub fn compile_block_regex(sigil: &str) -> Regex {
+    Regex::new(&format!(
+        "(?i)\\[\\s*{}\\s*:\\s*(\\w[^\\]\"'`;]*?)\\s*$",
+        escape(sigil),
+    ))
+    .unwrap() // Safe by manual inspection
+}
+
+// This function extracts the description of a tag from the text after it on the same line. An
+// optional `--` delimiter before the description is dropped, as is the end of a block comment
+// after it.
+fn trailing_description(text: &str) -> Option<String> {
+    let text = text.trim();
+    let text = text.strip_prefix("--").unwrap_or(text);
+    let text = text
+        .strip_suffix("*/")
+        .or_else(|| text.strip_suffix("-->"))
+        .unwrap_or(text)
+        .trim();
+    (!text.is_empty()).then(|| text.to_owned())
+}
+
+// This function determines whether the text before the opening of a block tag looks like the start
+// of a comment, i.e., nothing but whitespace and punctuation other than quotes. Block tags which
+// aren't in comments would swallow the code after them.
+fn comment_prefix(text: &str) -> bool {
+    !text
+        .chars()
+        .any(|character| character.is_alphanumeric() || matches!(character, '"' | '\'' | '`'))
+}
+
+// This function compiles a user-provided regular expression for matching a directive. The first
+// capture group is taken to be the label.
+pub fn compile_custom_regex(pattern: &str) -> Result<Regex, String> {
+    let regex = Regex::new(pattern)
+        .map_err(|error| format!("Invalid regular expression `{pattern}`: {error}"))?;
+
+    if regex.captures_len() < 2 {
+        return Err(format!(
+            "The regular expression `{pattern}` must have a capture group for the label.",
+        ));
+    }
+
+    Ok(regex)
+}
+
+// This struct indexes the lines of a text by the positions where they start, so the line containing
+// any position can be found with a binary search rather than by scanning the text. Lines end with
+// `\n`, `\r\n`, or a lone `\r` (as in files from classic Mac OS), and the last line doesn't need an
+// ending. Line numbers are based on these lines everywhere, so they agree with editors.
+struct Lines<'a> {
+    text: &'a str,
+    starts: Vec<usize>,
+}
+
+impl<'a> Lines<'a> {
+    fn new(text: &'a str) -> Self {
+        let bytes = text.as_bytes();
+        let starts = iter::once(0)
+            .chain(
+                memchr2_iter(b'\n', b'\r', bytes)
+                    .filter(|position| {
+                        bytes[*position] == b'\n' || bytes.get(position + 1) != Some(&b'\n')
+                    })
+                    .map(|position| position + 1),
+            )
+            .filter(|start| *start < text.len())
+            .collect();
+        Self { text, starts }
+    }
+
+    fn len(&self) -> usize {
+        self.starts.len()
+    }
+
+    // This function returns a line along with its line ending, which may be empty.
+    fn get_with_ending(&self, index: usize) -> (&'a str, &'a str) {
+        let end = self
+            .starts
+            .get(index + 1)
+            .copied()
+            .unwrap_or(self.text.len());
+        let line = &self.text[self.starts[index]..end];
+        let length = line
+            .strip_suffix("\r\n")
+            .or_else(|| line.strip_suffix(['\n', '\r']))
+            .unwrap_or(line)
+            .len();
+        line.split_at(length)
+    }
+
+    // This function returns a line without its line ending.
+    fn get(&self, index: usize) -> &'a str {
+        self.get_with_ending(index).0
+    }
+
+    // This function returns the index of the line containing a position. A position at the end of
+    // the text belongs to the last line.
+    fn line_of(&self, position: usize) -> usize {
+        self.starts
+            .partition_point(|start| *start <= position)
+            .saturating_sub(1)
+    }
+}
+
+// This function splits a text into lines as described in `Lines`, each paired with its line ending,
+// which may be empty.
+pub fn lines(text: &str) -> impl Iterator<Item = (&str, &str)> {
+    let lines = Lines::new(text);
+    (0..lines.len()).map(move |index| lines.get_with_ending(index))
+}
+
+// This function finds the lines which may contain matches for any of the regexes by searching the
+// whole text with each one, rather than searching each line separately. That's much faster for
+// large files with few labels, since the regexes skip ahead to their literals. The regexes are
+// searched in multi-line mode so `^` and `$` still match at the ends of lines, and a match which
+// spans several lines marks all of them, since the matches in the lines themselves may differ. It
+// returns `None` if the regexes use anchors which can't be searched this way.
+fn candidates(lines: &Lines, regexes: &[&Regex]) -> Option<Vec<bool>> {
+    let mut candidates = vec![false; lines.len()];
+
+    for regex in regexes {
+        let pattern = regex.as_str();
+        if pattern.contains("\\A") || pattern.contains("\\z") {
+            return None;
+        }
+
+        let regex = Regex::new(&format!("(?Rm:{pattern})")).ok()?;
+        for found in regex.find_iter(lines.text) {
+            let first = lines.line_of(found.start());
+            let last = lines.line_of(found.end().saturating_sub(1).max(found.start()));
+            candidates[first..=last].fill(true);
+        }
+    }
+
+    Some(candidates)
+}
+
+// These are the sets built by `regex_set`, by the patterns of their regexes.
+static REGEX_SETS: LazyLock<Mutex<HashMap<Vec<String>, Option<RegexSet>>>> =
+    LazyLock::new(Mutex::default);
+
+// This function returns a set of the given regexes, which finds the ones matching a line in a
+// single pass. Sets are remembered, since building them is expensive and the same regexes are used
+// for every file, whereas cloning them is cheap. It returns `None` if the set can't be built (e.g.,
+// if it would be too big).
+fn regex_set(regexes: &[&Regex]) -> Option<RegexSet> {
+    let patterns = regexes
+        .iter()
+        .map(|regex| regex.as_str().to_owned())
+        .collect::<Vec<_>>();
+
+    // The `unwrap` is safe assuming no poisoning.
+    REGEX_SETS
+        .lock()
+        .unwrap()
+        .entry(patterns)
+        .or_insert_with_key(|patterns| RegexSet::new(patterns).ok())
+        .clone()
+}
+
+// This function returns all the directives in a file for a given type. Custom labels are matched
+// with the given regexes, each paired with its sigil. If `comments_only` is set and the file is of
+// a recognized type, only directives inside comments are returned. Files which aren't valid UTF-8
+// are decoded with the fallback encoding, if given, and binary files are skipped. Comments which
+// suppress violations are collected too, and invalid ones are logged as warnings.
+//
+// If a block regex is given, tags can also span several lines, with the lines between the opening
+// and the closing bracket describing the tag. The comment marker before the opening bracket is
+// stripped from those lines, as are the asterisks which often start lines in block comments.
+#[allow(clippy::too_many_arguments, clippy::too_many_lines)]
+pub fn parse(
     tag_regex: &Regex,
+    tag_block_regex: Option<&Regex>,
     ref_regex: &Regex,
     file_regex: &Regex,
     dir_regex: &Regex,
+    url_regex: &Regex,
+    custom_regexes: &[(String, Regex)],
+    comments_only: bool,
+    fallback_encoding: Option<&'static Encoding>,
     path: &Path,
-    reader: R,
+    contents: &[u8],
 ) -> Directives {
+    // These are the positions of the regexes in the set, with the ones for custom labels last.
+    const TAG: usize = 0;
+    const REF: usize = 1;
+    const FILE: usize = 2;
+    const DIR: usize = 3;
+    const URL: usize = 4;
+    const ALIAS: usize = 5;
+    const ANCHOR: usize = 6;
+    const SUPPRESSION: usize = 7;
+    const CUSTOM: usize = 8;
+
     let mut tags: Vec<Directive> = Vec::new();
     let mut refs: Vec<Directive> = Vec::new();
     let mut files: Vec<Directive> = Vec::new();
     let mut dirs: Vec<Directive> = Vec::new();
+    let mut urls: Vec<Directive> = Vec::new();
+    let mut custom: Vec<Directive> = Vec::new();
+    let mut suppressions: Vec<Suppression> = Vec::new();
+    let mut aliases: Vec<Alias> = Vec::new();
+    let mut anchors: Vec<Directive> = Vec::new();
+    let mut pins: Vec<Directive> = Vec::new();
 
-    for (line_number, line_result) in reader.lines().enumerate() {
-        if let Ok(line) = line_result {
-            // Tags
-            for captures in tag_regex.captures_iter(&line) {
-                // If we got a match, then `captures.get(1)` is guaranteed to return a `Some`. Hence
-                // we are justified in unwrapping.
-                tags.push(Directive {
-                    r#type: Type::Tag,
-                    label: captures.get(1).unwrap().as_str().to_owned(),
-                    path: path.to_owned(),
-                    line_number: line_number + 1,
-                });
+    // Set up a scanner for finding the comments, if applicable.
+    let mut scanner = if comments_only {
+        comments::syntax(path).map(Scanner::new)
+    } else {
+        None
+    };
+
+    // Decode the contents. Valid UTF-8 is borrowed rather than copied.
+    let mut undecodable = Vec::new();
+    let text = match encoding::decode(contents, fallback_encoding) {
+        Decoded::Text(text) => text,
+        Decoded::Lossy(text) => {
+            undecodable.push(path.to_owned());
+            text
+        }
+        Decoded::Binary => Cow::Borrowed(""),
+    };
+
+    // The directives share one copy of the path.
+    let shared_path = Arc::<Path>::from(path);
+
+    // This is the block tag which is currently open, if any, along with the comment marker before
+    // it, the lines of its description so far, and the number of brackets open in the description.
+    let mut block: Option<(Directive, String, Vec<String>, usize)> = None;
+
+    // Each line is matched against all the regexes at once with a set, and only the regexes which
+    // match are searched again to find the matches themselves. Most lines don't match any.
+    let mut regexes = vec![
+        tag_regex,
+        ref_regex,
+        file_regex,
+        dir_regex,
+        url_regex,
+        &aliases::REGEX,
+        &anchors::REGEX,
+        &suppressions::REGEX,
+    ];
+    regexes.extend(custom_regexes.iter().map(|(_, regex)| regex));
+    let block_index = regexes.len();
+    regexes.extend(tag_block_regex);
+    let set = regex_set(&regexes);
+
+    // In large files, only the lines which may contain labels are parsed. That isn't possible if
+    // only comments are considered, since the comment scanner needs to see every line.
+    let lines = Lines::new(&text);
+    let candidates = if scanner.is_none() && text.len() as u64 >= mapped::THRESHOLD {
+        candidates(&lines, &regexes)
+    } else {
+        None
+    };
+
+    for line_number in 0..lines.len() {
+        // Lines inside a block tag are always parsed, since they're part of its description.
+        if block.is_none()
+            && candidates
+                .as_ref()
+                .is_some_and(|candidates| !candidates[line_number])
+        {
+            continue;
+        }
+        let line = lines.get(line_number);
+
+        // Inside a block tag, each line is part of the description until the closing bracket. The
+        // brackets in the description (e.g., around labels) are matched, so only an unmatched one
+        // closes the block. The labels in the description are found like any others.
+        if let Some((_, marker, description, depth)) = block.as_mut() {
+            let end = line.char_indices().find_map(|(index, character)| {
+                match character {
+                    '[' => *depth += 1,
+                    ']' if *depth == 0 => return Some(index),
+                    ']' => *depth -= 1,
+                    _ => {}
+                }
+                None
+            });
+            let text = end.map_or(line, |end| &line[..end]).trim();
+            description.push(
+                text.strip_prefix(marker.as_str())
+                    .or_else(|| text.strip_prefix("*/"))
+                    .or_else(|| text.strip_prefix('*'))
+                    .unwrap_or(text)
+                    .trim()
+                    .to_owned(),
+            );
+            if end.is_some() {
+                // The `unwrap` is safe because a block is open.
+                let (mut tag, _, description, _) = block.take().unwrap();
+                let description = description.join("\n").trim().to_owned();
+                tag.description = Some(description).filter(|description| !description.is_empty());
+                tags.push(tag);
+            }
+        }
+
+        // This closure finds the matches for one of the regexes, if the set found any.
+        let found = set.as_ref().map(|set| set.matches(line));
+        let matched = |index: usize| found.as_ref().is_none_or(|found| found.matched(index));
+        let find = |index: usize| {
+            let regex: &Regex = regexes[index];
+            matched(index)
+                .then(|| regex.captures_iter(line))
+                .into_iter()
+                .flatten()
+        };
+
+        // Determine which matches should be kept. If there's no scanner, all of them are, except
+        // for matches of custom regexes whose label group didn't participate (e.g., `(a)|b`).
+        let comments = scanner.as_mut().map(|scanner| scanner.comments(line));
+        let keep = |captures: &Captures| {
+            // A match always has a `get(0)`, so the `unwrap` is safe.
+            let range = captures.get(0).unwrap().range();
+            captures.get(1).is_some()
+                && comments.as_ref().is_none_or(|comments| {
+                    comments
+                        .iter()
+                        .any(|comment| comment.start <= range.start && range.end <= comment.end)
+                })
+        };
+
+        // This closure builds a directive from a match.
+        let directive = |r#type, captures: &Captures| {
+            // If we got a match which was kept, then `captures.get(0)` and `captures.get(1)` are
+            // guaranteed to return a `Some`. Hence we are justified in unwrapping.
+            let span = captures.get(0).unwrap().range();
+            let (label, attributes) = attributes::split(captures.get(1).unwrap().as_str());
+            Directive {
+                r#type,
+                label: label.to_owned(),
+                path: shared_path.clone(),
+                line_number: line_number + 1,
+                column: line[..span.start].chars().count() + 1,
+                span,
+                description: None,
+                attributes,
+                cell: None,
             }
+        };
 
-            // Refs
-            for captures in ref_regex.captures_iter(&line) {
-                // If we got a match, then `captures.get(1)` is guaranteed to return a `Some`. Hence
-                // we are justified in unwrapping.
+        // Tags. A tag which starts a comment (i.e., with nothing but whitespace and punctuation
+        // before it) is described by the text after it (apart from any aliases), up to the next
+        // tag on the line. Tags in the middle of a sentence aren't, since the rest of the sentence
+        // isn't about them.
+        let tag_matches = find(TAG).filter(keep).collect::<Vec<_>>();
+        for (index, captures) in tag_matches.iter().enumerate() {
+            // A match always has a `get(0)`, so the `unwrap`s are safe.
+            let range = captures.get(0).unwrap().range();
+            let end = tag_matches
+                .get(index + 1)
+                .map_or(line.len(), |next| next.get(0).unwrap().start());
+            let leading = !line[..range.start].chars().any(char::is_alphanumeric);
+            tags.push(Directive {
+                description: leading
+                    .then(|| {
+                        trailing_description(&aliases::REGEX.replace_all(&line[range.end..end], ""))
+                    })
+                    .flatten(),
+                ..directive(Type::Tag, captures)
+            });
+        }
+
+        // Aliases, each of which belongs to the closest tag before it on the line
+        for captures in find(ALIAS).filter(keep) {
+            let alias = directive(Type::Alias, &captures);
+
+            // A match always has a `get(0)`, so the `unwrap` is safe.
+            match tag_matches
+                .iter()
+                .rev()
+                .find(|tag| tag.get(0).unwrap().end() <= alias.span.start)
+            {
+                Some(tag) => aliases.push(Alias {
+                    tag: attributes::split(&tag[1]).0.to_owned(),
+                    directive: alias,
+                }),
+                None => {
+                    log::warn!("{alias} doesn't follow a tag on the same line, so it's ignored.");
+                }
+            }
+        }
+
+        // Refs, any of which may be pinned
+        for captures in find(REF).filter(keep) {
+            let r#ref = directive(Type::Ref, &captures);
+            if let (label, Some(_)) = pins::split(&r#ref.label) {
                 refs.push(Directive {
-                    r#type: Type::Ref,
-                    label: captures.get(1).unwrap().as_str().to_owned(),
-                    path: path.to_owned(),
-                    line_number: line_number + 1,
+                    label: label.to_owned(),
+                    ..r#ref.clone()
                 });
+                pins.push(r#ref);
+            } else {
+                refs.push(r#ref);
             }
+        }
 
-            // Files
-            for captures in file_regex.captures_iter(&line) {
-                // If we got a match, then `captures.get(1)` is guaranteed to return a `Some`. Hence
-                // we are justified in unwrapping.
-                files.push(Directive {
-                    r#type: Type::File,
-                    label: captures.get(1).unwrap().as_str().to_owned(),
-                    path: path.to_owned(),
-                    line_number: line_number + 1,
-                });
+        // Files
+        files.extend(
+            find(FILE)
+                .filter(keep)
+                .map(|captures| directive(Type::File, &captures)),
+        );
+
+        // Directories
+        dirs.extend(
+            find(DIR)
+                .filter(keep)
+                .map(|captures| directive(Type::Dir, &captures)),
+        );
+
+        // Anchors
+        anchors.extend(
+            find(ANCHOR)
+                .filter(keep)
+                .map(|captures| directive(Type::Anchor, &captures)),
+        );
+
+        // URLs
+        urls.extend(
+            find(URL)
+                .filter(keep)
+                .map(|captures| directive(Type::Url, &captures)),
+        );
+
+        // Custom labels
+        for (index, (sigil, _)) in custom_regexes.iter().enumerate() {
+            custom.extend(
+                find(CUSTOM + index)
+                    .filter(keep)
+                    .map(|captures| directive(Type::Custom(sigil.clone()), &captures)),
+            );
+        }
+
+        // Suppressions
+        for captures in find(SUPPRESSION).filter(keep) {
+            // If we got a match, then `captures.get(1)` is guaranteed to return a `Some`.
+            let label = captures.get(1).unwrap().as_str();
+            match Suppression::parse(label, path, line_number + 1) {
+                Ok(suppression) => suppressions.push(suppression),
+                Err(error) => {
+                    log::warn!("{error} @ {}:{}", path.to_string_lossy(), line_number + 1);
+                }
             }
+        }
 
-            // Directories
-            for captures in dir_regex.captures_iter(&line) {
-                // If we got a match, then `captures.get(1)` is guaranteed to return a `Some`. Hence
-                // we are justified in unwrapping.
-                dirs.push(Directive {
-                    r#type: Type::Dir,
-                    label: captures.get(1).unwrap().as_str().to_owned(),
-                    path: path.to_owned(),
-                    line_number: line_number + 1,
-                });
+        // Block tags, which must open in a comment outside any other block tag
+        if let Some(captures) = tag_block_regex
+            .filter(|_| block.is_none() && matched(block_index))
+            .and_then(|regex| regex.captures(line))
+            && keep(&captures)
+        {
+            // A match always has a `get(0)`, so the `unwrap` is safe.
+            let prefix = &line[..captures.get(0).unwrap().start()];
+            if comment_prefix(prefix) {
+                let marker = prefix.trim().to_owned();
+                block = Some((directive(Type::Tag, &captures), marker, Vec::new(), 0));
             }
         }
     }
 
+    // A block tag which is never closed is ignored, since it's probably not meant to be a tag.
+
+    // Links and headings are found in Markdown files regardless of comments, since they're part of
+    // the prose.
+    let (markdown_links, headings) = if markdown::is_markdown(path) {
+        markdown::parse(&shared_path, &text)
+    } else {
+        (Vec::new(), Vec::new())
+    };
+
     Directives {
         tags,
         refs,
         files,
         dirs,
+        urls,
+        custom,
+        undecodable,
+        too_large: Vec::new(),
+        suppressions,
+        aliases,
+        anchors,
+        pins,
+        links: markdown_links,
+        headings,
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::directive::{Type, compile_directive_regex, parse};
-    use std::path::Path;
+    use crate::{
+        directive::{
+            Directive, Lines, Type, compile_block_regex, compile_custom_regex,
+            compile_directive_regex, parse,
+        },
+        mapped::THRESHOLD,
+        violation::Kind,
+    };
+    use std::{collections::BTreeMap, path::Path};
+
+    #[test]
+    fn compile_custom_regex_valid() {
+        let regex = compile_custom_regex("<<([^>]*)>>").unwrap();
+
+        assert_eq!(&regex.captures("<<foo>>").unwrap()[1], "foo");
+    }
+
+    #[test]
+    fn compile_custom_regex_invalid() {
+        assert!(compile_custom_regex("(").is_err());
+    }
+
+    #[test]
+    fn parse_custom_regex_optional_group() {
+        let path = Path::new("file.txt").to_owned();
+        let contents = b"<<foo>> <bar>" as &[u8];
+
+        let tag_regex = compile_directive_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let file_regex = compile_directive_regex("file");
+        let dir_regex = compile_directive_regex("dir");
+        let url_regex = compile_directive_regex("url");
+        let custom_regex = compile_custom_regex("<<([^>]*)>>|<[^>]*>").unwrap();
+
+        let directives = parse(
+            &tag_regex,
+            None,
+            &ref_regex,
+            &file_regex,
+            &dir_regex,
+            &url_regex,
+            &[("custom".to_owned(), custom_regex)],
+            false,
+            None,
+            &path,
+            contents,
+        );
+
+        assert_eq!(directives.custom.len(), 1);
+        assert_eq!(directives.custom[0].label, "foo");
+    }
+
+    #[test]
+    fn compile_custom_regex_no_capture_group() {
+        assert!(compile_custom_regex("<<[^>]*>>").is_err());
+    }
 
     #[test]
     fn parse_empty() {
@@ -145,12 +849,18 @@ mod tests {
         let ref_regex = compile_directive_regex("ref");
         let file_regex = compile_directive_regex("file");
         let dir_regex = compile_directive_regex("dir");
+        let url_regex = compile_directive_regex("url");
 
         let directives = parse(
             &tag_regex,
+            None,
             &ref_regex,
             &file_regex,
             &dir_regex,
+            &url_regex,
+            &[],
+            false,
+            None,
             &path,
             contents,
         );
@@ -176,12 +886,18 @@ mod tests {
         let ref_regex = compile_directive_regex("ref");
         let file_regex = compile_directive_regex("file");
         let dir_regex = compile_directive_regex("dir");
+        let url_regex = compile_directive_regex("url");
 
         let directives = parse(
             &tag_regex,
+            None,
             &ref_regex,
             &file_regex,
             &dir_regex,
+            &url_regex,
+            &[],
+            false,
+            None,
             &path,
             contents.as_ref(),
         );
@@ -189,7 +905,7 @@ mod tests {
         assert_eq!(directives.tags.len(), 1);
         assert_eq!(directives.tags[0].r#type, Type::Tag);
         assert_eq!(directives.tags[0].label, "label");
-        assert_eq!(directives.tags[0].path, path);
+        assert_eq!(*directives.tags[0].path, path);
         assert_eq!(directives.tags[0].line_number, 1);
         assert!(directives.refs.is_empty());
         assert!(directives.files.is_empty());
@@ -197,10 +913,303 @@ mod tests {
     }
 
     #[test]
-    fn parse_ref_basic() {
+    fn parse_ref_basic() {
+        let path = Path::new("file.rs").to_owned();
+        let contents = r"
+      [?ref:label]
+    "
+        .trim()
+        .replace('?', "")
+        .as_bytes()
+        .to_owned();
+
+        let tag_regex = compile_directive_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let file_regex = compile_directive_regex("file");
+        let dir_regex = compile_directive_regex("dir");
+        let url_regex = compile_directive_regex("url");
+
+        let directives = parse(
+            &tag_regex,
+            None,
+            &ref_regex,
+            &file_regex,
+            &dir_regex,
+            &url_regex,
+            &[],
+            false,
+            None,
+            &path,
+            contents.as_ref(),
+        );
+
+        assert!(directives.tags.is_empty());
+        assert_eq!(directives.refs.len(), 1);
+        assert_eq!(directives.refs[0].r#type, Type::Ref);
+        assert_eq!(directives.refs[0].label, "label");
+        assert_eq!(*directives.refs[0].path, path);
+        assert_eq!(directives.refs[0].line_number, 1);
+        assert!(directives.files.is_empty());
+        assert!(directives.dirs.is_empty());
+    }
+
+    #[test]
+    fn parse_columns_and_spans() {
+        let path = Path::new("file.rs").to_owned();
+        let contents = "// é [?ref:foo] [?ref:bar]"
+            .replace('?', "")
+            .as_bytes()
+            .to_owned();
+
+        let tag_regex = compile_directive_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let file_regex = compile_directive_regex("file");
+        let dir_regex = compile_directive_regex("dir");
+        let url_regex = compile_directive_regex("url");
+
+        let directives = parse(
+            &tag_regex,
+            None,
+            &ref_regex,
+            &file_regex,
+            &dir_regex,
+            &url_regex,
+            &[],
+            false,
+            None,
+            &path,
+            contents.as_ref(),
+        );
+
+        // The `é` is one character but two bytes.
+        assert_eq!(directives.refs.len(), 2);
+        assert_eq!(directives.refs[0].column, 6);
+        assert_eq!(directives.refs[0].span, 6..15);
+        assert_eq!(directives.refs[1].column, 16);
+        assert_eq!(directives.refs[1].span, 16..25);
+    }
+
+    #[test]
+    fn parse_block_tag() {
+        let path = Path::new("file.rs").to_owned();
+        let contents = r"
+// [?tag:retry policy
+//   Retries back off exponentially,
+//   up to a minute.
+// ] See [?ref:backoff].
+    "
+        .trim()
+        .replace('?', "")
+        .as_bytes()
+        .to_owned();
+
+        let tag_regex = compile_directive_regex("tag");
+        let tag_block_regex = compile_block_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let file_regex = compile_directive_regex("file");
+        let dir_regex = compile_directive_regex("dir");
+        let url_regex = compile_directive_regex("url");
+
+        let directives = parse(
+            &tag_regex,
+            Some(&tag_block_regex),
+            &ref_regex,
+            &file_regex,
+            &dir_regex,
+            &url_regex,
+            &[],
+            false,
+            None,
+            &path,
+            contents.as_ref(),
+        );
+
+        assert_eq!(directives.tags.len(), 1);
+        assert_eq!(directives.tags[0].label, "retry policy");
+        assert_eq!(directives.tags[0].line_number, 1);
+        assert_eq!(directives.tags[0].column, 4);
+        assert_eq!(
+            directives.tags[0].description.as_deref(),
+            Some("Retries back off exponentially,\nup to a minute."),
+        );
+        assert_eq!(directives.refs.len(), 1);
+        assert_eq!(directives.refs[0].label, "backoff");
+    }
+
+    #[test]
+    fn parse_block_tag_with_labels() {
+        let path = Path::new("file.rs").to_owned();
+        let contents = r"
+// [?tag:retry
+//   See [?ref:backoff] for details.
+// ]
+    "
+        .trim()
+        .replace('?', "")
+        .as_bytes()
+        .to_owned();
+
+        let tag_regex = compile_directive_regex("tag");
+        let tag_block_regex = compile_block_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let file_regex = compile_directive_regex("file");
+        let dir_regex = compile_directive_regex("dir");
+        let url_regex = compile_directive_regex("url");
+
+        let directives = parse(
+            &tag_regex,
+            Some(&tag_block_regex),
+            &ref_regex,
+            &file_regex,
+            &dir_regex,
+            &url_regex,
+            &[],
+            false,
+            None,
+            &path,
+            contents.as_ref(),
+        );
+
+        assert_eq!(directives.tags.len(), 1);
+        assert_eq!(directives.tags[0].label, "retry");
+        assert_eq!(
+            directives.tags[0].description,
+            Some("See [?ref:backoff] for details.".replace('?', "")),
+        );
+        assert_eq!(directives.refs.len(), 1);
+        assert_eq!(directives.refs[0].label, "backoff");
+        assert_eq!(directives.refs[0].line_number, 2);
+    }
+
+    #[test]
+    fn parse_block_tag_unclosed() {
+        let path = Path::new("file.rs").to_owned();
+        let contents = r"
+// [?tag:foo
+// This is never closed.
+    "
+        .trim()
+        .replace('?', "")
+        .as_bytes()
+        .to_owned();
+
+        let tag_regex = compile_directive_regex("tag");
+        let tag_block_regex = compile_block_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let file_regex = compile_directive_regex("file");
+        let dir_regex = compile_directive_regex("dir");
+        let url_regex = compile_directive_regex("url");
+
+        let directives = parse(
+            &tag_regex,
+            Some(&tag_block_regex),
+            &ref_regex,
+            &file_regex,
+            &dir_regex,
+            &url_regex,
+            &[],
+            false,
+            None,
+            &path,
+            contents.as_ref(),
+        );
+
+        assert!(directives.tags.is_empty());
+    }
+
+    #[test]
+    fn parse_block_tag_in_code() {
+        let path = Path::new("file.rs").to_owned();
+        let contents = r#"
+let prefix = "[?tag:";
+let x = arr[0];
+const OPEN: &str = "[?tag:";
+let y = v[1];
+let z = format!("[?tag:{name}");
+    "#
+        .trim()
+        .replace('?', "")
+        .as_bytes()
+        .to_owned();
+
+        let tag_regex = compile_directive_regex("tag");
+        let tag_block_regex = compile_block_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let file_regex = compile_directive_regex("file");
+        let dir_regex = compile_directive_regex("dir");
+        let url_regex = compile_directive_regex("url");
+
+        let directives = parse(
+            &tag_regex,
+            Some(&tag_block_regex),
+            &ref_regex,
+            &file_regex,
+            &dir_regex,
+            &url_regex,
+            &[],
+            false,
+            None,
+            &path,
+            contents.as_ref(),
+        );
+
+        assert!(directives.tags.is_empty());
+    }
+
+    #[test]
+    fn parse_tag_description() {
+        let path = Path::new("file.rs").to_owned();
+        let contents = r"
+// [?tag:foo] Never returns zero. [?tag:bar] -- Must be sorted.
+/* [?tag:baz] Only called once. */
+See [?tag:qux] for details.
+    "
+        .trim()
+        .replace('?', "")
+        .as_bytes()
+        .to_owned();
+
+        let tag_regex = compile_directive_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let file_regex = compile_directive_regex("file");
+        let dir_regex = compile_directive_regex("dir");
+        let url_regex = compile_directive_regex("url");
+
+        let directives = parse(
+            &tag_regex,
+            None,
+            &ref_regex,
+            &file_regex,
+            &dir_regex,
+            &url_regex,
+            &[],
+            false,
+            None,
+            &path,
+            contents.as_ref(),
+        );
+
+        let descriptions = directives
+            .tags
+            .iter()
+            .map(|tag| (tag.label.as_str(), tag.description.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            descriptions,
+            vec![
+                ("foo", Some("Never returns zero.")),
+                ("bar", None),
+                ("baz", Some("Only called once.")),
+                ("qux", None),
+            ],
+        );
+    }
+
+    #[test]
+    fn parse_tag_description_delimiter() {
         let path = Path::new("file.rs").to_owned();
         let contents = r"
-      [?ref:label]
+<!-- [?tag:foo] -- Keep this in sync with the schema. -->
     "
         .trim()
         .replace('?', "")
@@ -211,24 +1220,27 @@ mod tests {
         let ref_regex = compile_directive_regex("ref");
         let file_regex = compile_directive_regex("file");
         let dir_regex = compile_directive_regex("dir");
+        let url_regex = compile_directive_regex("url");
 
         let directives = parse(
             &tag_regex,
+            None,
             &ref_regex,
             &file_regex,
             &dir_regex,
+            &url_regex,
+            &[],
+            false,
+            None,
             &path,
             contents.as_ref(),
         );
 
-        assert!(directives.tags.is_empty());
-        assert_eq!(directives.refs.len(), 1);
-        assert_eq!(directives.refs[0].r#type, Type::Ref);
-        assert_eq!(directives.refs[0].label, "label");
-        assert_eq!(directives.refs[0].path, path);
-        assert_eq!(directives.refs[0].line_number, 1);
-        assert!(directives.files.is_empty());
-        assert!(directives.dirs.is_empty());
+        assert_eq!(directives.tags.len(), 1);
+        assert_eq!(
+            directives.tags[0].description.as_deref(),
+            Some("Keep this in sync with the schema."),
+        );
     }
 
     #[test]
@@ -246,12 +1258,18 @@ mod tests {
         let ref_regex = compile_directive_regex("ref");
         let file_regex = compile_directive_regex("file");
         let dir_regex = compile_directive_regex("dir");
+        let url_regex = compile_directive_regex("url");
 
         let directives = parse(
             &tag_regex,
+            None,
             &ref_regex,
             &file_regex,
             &dir_regex,
+            &url_regex,
+            &[],
+            false,
+            None,
             &path,
             contents.as_ref(),
         );
@@ -261,7 +1279,7 @@ mod tests {
         assert_eq!(directives.files.len(), 1);
         assert_eq!(directives.files[0].r#type, Type::File);
         assert_eq!(directives.files[0].label, "foo/bar/baz.txt");
-        assert_eq!(directives.files[0].path, path);
+        assert_eq!(*directives.files[0].path, path);
         assert_eq!(directives.files[0].line_number, 1);
         assert!(directives.dirs.is_empty());
     }
@@ -281,12 +1299,18 @@ mod tests {
         let ref_regex = compile_directive_regex("ref");
         let file_regex = compile_directive_regex("file");
         let dir_regex = compile_directive_regex("dir");
+        let url_regex = compile_directive_regex("url");
 
         let directives = parse(
             &tag_regex,
+            None,
             &ref_regex,
             &file_regex,
             &dir_regex,
+            &url_regex,
+            &[],
+            false,
+            None,
             &path,
             contents.as_ref(),
         );
@@ -297,10 +1321,52 @@ mod tests {
         assert_eq!(directives.dirs.len(), 1);
         assert_eq!(directives.dirs[0].r#type, Type::Dir);
         assert_eq!(directives.dirs[0].label, "foo/bar/baz");
-        assert_eq!(directives.dirs[0].path, path);
+        assert_eq!(*directives.dirs[0].path, path);
         assert_eq!(directives.dirs[0].line_number, 1);
     }
 
+    #[test]
+    fn parse_url_basic() {
+        let path = Path::new("file.rs").to_owned();
+        let contents = r"
+      [?url:https://example.com/foo?bar=baz]
+    "
+        .trim()
+        .replace("[?", "[")
+        .as_bytes()
+        .to_owned();
+
+        let tag_regex = compile_directive_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let file_regex = compile_directive_regex("file");
+        let dir_regex = compile_directive_regex("dir");
+        let url_regex = compile_directive_regex("url");
+
+        let directives = parse(
+            &tag_regex,
+            None,
+            &ref_regex,
+            &file_regex,
+            &dir_regex,
+            &url_regex,
+            &[],
+            false,
+            None,
+            &path,
+            contents.as_ref(),
+        );
+
+        assert!(directives.tags.is_empty());
+        assert!(directives.refs.is_empty());
+        assert!(directives.files.is_empty());
+        assert!(directives.dirs.is_empty());
+        assert_eq!(directives.urls.len(), 1);
+        assert_eq!(directives.urls[0].r#type, Type::Url);
+        assert_eq!(directives.urls[0].label, "https://example.com/foo?bar=baz");
+        assert_eq!(*directives.urls[0].path, path);
+        assert_eq!(directives.urls[0].line_number, 1);
+    }
+
     #[test]
     fn parse_multiple_per_line() {
         let path = Path::new("file.rs").to_owned();
@@ -316,12 +1382,18 @@ mod tests {
         let ref_regex = compile_directive_regex("ref");
         let file_regex = compile_directive_regex("file");
         let dir_regex = compile_directive_regex("dir");
+        let url_regex = compile_directive_regex("url");
 
         let directives = parse(
             &tag_regex,
+            None,
             &ref_regex,
             &file_regex,
             &dir_regex,
+            &url_regex,
+            &[],
+            false,
+            None,
             &path,
             contents.as_ref(),
         );
@@ -329,25 +1401,25 @@ mod tests {
         assert_eq!(directives.tags.len(), 1);
         assert_eq!(directives.tags[0].r#type, Type::Tag);
         assert_eq!(directives.tags[0].label, "label");
-        assert_eq!(directives.tags[0].path, path);
+        assert_eq!(*directives.tags[0].path, path);
         assert_eq!(directives.tags[0].line_number, 1);
 
         assert_eq!(directives.refs.len(), 1);
         assert_eq!(directives.refs[0].r#type, Type::Ref);
         assert_eq!(directives.refs[0].label, "label");
-        assert_eq!(directives.refs[0].path, path);
+        assert_eq!(*directives.refs[0].path, path);
         assert_eq!(directives.refs[0].line_number, 1);
 
         assert_eq!(directives.files.len(), 1);
         assert_eq!(directives.files[0].r#type, Type::File);
         assert_eq!(directives.files[0].label, "foo/bar/baz.txt");
-        assert_eq!(directives.files[0].path, path);
+        assert_eq!(*directives.files[0].path, path);
         assert_eq!(directives.files[0].line_number, 1);
 
         assert_eq!(directives.dirs.len(), 1);
         assert_eq!(directives.dirs[0].r#type, Type::Dir);
         assert_eq!(directives.dirs[0].label, "foo/bar/baz");
-        assert_eq!(directives.dirs[0].path, path);
+        assert_eq!(*directives.dirs[0].path, path);
         assert_eq!(directives.dirs[0].line_number, 1);
     }
 
@@ -369,12 +1441,18 @@ mod tests {
         let ref_regex = compile_directive_regex("ref");
         let file_regex = compile_directive_regex("file");
         let dir_regex = compile_directive_regex("dir");
+        let url_regex = compile_directive_regex("url");
 
         let directives = parse(
             &tag_regex,
+            None,
             &ref_regex,
             &file_regex,
             &dir_regex,
+            &url_regex,
+            &[],
+            false,
+            None,
             &path,
             contents.as_ref(),
         );
@@ -382,25 +1460,25 @@ mod tests {
         assert_eq!(directives.tags.len(), 1);
         assert_eq!(directives.tags[0].r#type, Type::Tag);
         assert_eq!(directives.tags[0].label, "label");
-        assert_eq!(directives.tags[0].path, path);
+        assert_eq!(*directives.tags[0].path, path);
         assert_eq!(directives.tags[0].line_number, 1);
 
         assert_eq!(directives.refs.len(), 1);
         assert_eq!(directives.refs[0].r#type, Type::Ref);
         assert_eq!(directives.refs[0].label, "label");
-        assert_eq!(directives.refs[0].path, path);
+        assert_eq!(*directives.refs[0].path, path);
         assert_eq!(directives.refs[0].line_number, 2);
 
         assert_eq!(directives.files.len(), 1);
         assert_eq!(directives.files[0].r#type, Type::File);
         assert_eq!(directives.files[0].label, "foo/bar/baz.txt");
-        assert_eq!(directives.files[0].path, path);
+        assert_eq!(*directives.files[0].path, path);
         assert_eq!(directives.files[0].line_number, 3);
 
         assert_eq!(directives.dirs.len(), 1);
         assert_eq!(directives.dirs[0].r#type, Type::Dir);
         assert_eq!(directives.dirs[0].label, "foo/bar/baz");
-        assert_eq!(directives.dirs[0].path, path);
+        assert_eq!(*directives.dirs[0].path, path);
         assert_eq!(directives.dirs[0].line_number, 4);
     }
 
@@ -422,12 +1500,18 @@ mod tests {
         let ref_regex = compile_directive_regex("ref");
         let file_regex = compile_directive_regex("file");
         let dir_regex = compile_directive_regex("dir");
+        let url_regex = compile_directive_regex("url");
 
         let directives = parse(
             &tag_regex,
+            None,
             &ref_regex,
             &file_regex,
             &dir_regex,
+            &url_regex,
+            &[],
+            false,
+            None,
             &path,
             contents.as_ref(),
         );
@@ -435,25 +1519,25 @@ mod tests {
         assert_eq!(directives.tags.len(), 1);
         assert_eq!(directives.tags[0].r#type, Type::Tag);
         assert_eq!(directives.tags[0].label, "foo  bar");
-        assert_eq!(directives.tags[0].path, path);
+        assert_eq!(*directives.tags[0].path, path);
         assert_eq!(directives.tags[0].line_number, 1);
 
         assert_eq!(directives.refs.len(), 1);
         assert_eq!(directives.refs[0].r#type, Type::Ref);
         assert_eq!(directives.refs[0].label, "foo  bar");
-        assert_eq!(directives.refs[0].path, path);
+        assert_eq!(*directives.refs[0].path, path);
         assert_eq!(directives.refs[0].line_number, 2);
 
         assert_eq!(directives.files.len(), 1);
         assert_eq!(directives.files[0].r#type, Type::File);
         assert_eq!(directives.files[0].label, "foo  bar/baz  qux.txt");
-        assert_eq!(directives.files[0].path, path);
+        assert_eq!(*directives.files[0].path, path);
         assert_eq!(directives.files[0].line_number, 3);
 
         assert_eq!(directives.dirs.len(), 1);
         assert_eq!(directives.dirs[0].r#type, Type::Dir);
         assert_eq!(directives.dirs[0].label, "foo  bar/baz  qux");
-        assert_eq!(directives.dirs[0].path, path);
+        assert_eq!(*directives.dirs[0].path, path);
         assert_eq!(directives.dirs[0].line_number, 4);
     }
 
@@ -479,12 +1563,18 @@ mod tests {
         let ref_regex = compile_directive_regex("ref");
         let file_regex = compile_directive_regex("file");
         let dir_regex = compile_directive_regex("dir");
+        let url_regex = compile_directive_regex("url");
 
         let directives = parse(
             &tag_regex,
+            None,
             &ref_regex,
             &file_regex,
             &dir_regex,
+            &url_regex,
+            &[],
+            false,
+            None,
             &path,
             contents.as_ref(),
         );
@@ -492,41 +1582,429 @@ mod tests {
         assert_eq!(directives.tags.len(), 2);
         assert_eq!(directives.tags[0].r#type, Type::Tag);
         assert_eq!(directives.tags[0].label, "label");
-        assert_eq!(directives.tags[0].path, path);
+        assert_eq!(*directives.tags[0].path, path);
         assert_eq!(directives.tags[0].line_number, 1);
         assert_eq!(directives.tags[1].r#type, Type::Tag);
         assert_eq!(directives.tags[1].label, "LABEL");
-        assert_eq!(directives.tags[1].path, path);
+        assert_eq!(*directives.tags[1].path, path);
         assert_eq!(directives.tags[1].line_number, 2);
 
         assert_eq!(directives.refs.len(), 2);
         assert_eq!(directives.refs[0].r#type, Type::Ref);
         assert_eq!(directives.refs[0].label, "label");
-        assert_eq!(directives.refs[0].path, path);
+        assert_eq!(*directives.refs[0].path, path);
         assert_eq!(directives.refs[0].line_number, 3);
         assert_eq!(directives.refs[1].r#type, Type::Ref);
         assert_eq!(directives.refs[1].label, "LABEL");
-        assert_eq!(directives.refs[1].path, path);
+        assert_eq!(*directives.refs[1].path, path);
         assert_eq!(directives.refs[1].line_number, 4);
 
         assert_eq!(directives.files.len(), 2);
         assert_eq!(directives.files[0].r#type, Type::File);
         assert_eq!(directives.files[0].label, "foo/bar/baz.txt");
-        assert_eq!(directives.files[0].path, path);
+        assert_eq!(*directives.files[0].path, path);
         assert_eq!(directives.files[0].line_number, 5);
         assert_eq!(directives.files[1].r#type, Type::File);
         assert_eq!(directives.files[1].label, "FOO/BAR/BAZ.TXT");
-        assert_eq!(directives.files[1].path, path);
+        assert_eq!(*directives.files[1].path, path);
         assert_eq!(directives.files[1].line_number, 6);
 
         assert_eq!(directives.dirs.len(), 2);
         assert_eq!(directives.dirs[0].r#type, Type::Dir);
         assert_eq!(directives.dirs[0].label, "foo/bar/baz");
-        assert_eq!(directives.dirs[0].path, path);
+        assert_eq!(*directives.dirs[0].path, path);
         assert_eq!(directives.dirs[0].line_number, 7);
         assert_eq!(directives.dirs[1].r#type, Type::Dir);
         assert_eq!(directives.dirs[1].label, "FOO/BAR/BAZ");
-        assert_eq!(directives.dirs[1].path, path);
+        assert_eq!(*directives.dirs[1].path, path);
         assert_eq!(directives.dirs[1].line_number, 8);
     }
+
+    #[test]
+    fn parse_comments_only() {
+        let path = Path::new("file.rs").to_owned();
+        let contents = r#"
+      // [?tag:comment]
+      let s = "[?tag:string]"; /* [?ref:block]
+         [?file:foo/bar/baz.txt] */ [?dir:foo/bar/baz]
+    "#
+        .trim()
+        .replace('?', "")
+        .as_bytes()
+        .to_owned();
+
+        let tag_regex = compile_directive_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let file_regex = compile_directive_regex("file");
+        let dir_regex = compile_directive_regex("dir");
+        let url_regex = compile_directive_regex("url");
+
+        let directives = parse(
+            &tag_regex,
+            None,
+            &ref_regex,
+            &file_regex,
+            &dir_regex,
+            &url_regex,
+            &[],
+            true,
+            None,
+            &path,
+            contents.as_ref(),
+        );
+
+        assert_eq!(directives.tags.len(), 1);
+        assert_eq!(directives.tags[0].label, "comment");
+        assert_eq!(directives.tags[0].line_number, 1);
+
+        assert_eq!(directives.refs.len(), 1);
+        assert_eq!(directives.refs[0].label, "block");
+        assert_eq!(directives.refs[0].line_number, 2);
+
+        assert_eq!(directives.files.len(), 1);
+        assert_eq!(directives.files[0].label, "foo/bar/baz.txt");
+        assert_eq!(directives.files[0].line_number, 3);
+
+        assert!(directives.dirs.is_empty());
+    }
+
+    #[test]
+    fn parse_suppressions() {
+        let path = Path::new("file.rs").to_owned();
+        let contents = r"
+      // [?tagref:ignore-next-line dangling_ref]
+      [?ref:foo] // [?tagref:ignore]
+      [?tagref:unknown]
+    "
+        .trim()
+        .replace('?', "")
+        .as_bytes()
+        .to_owned();
+
+        let tag_regex = compile_directive_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let file_regex = compile_directive_regex("file");
+        let dir_regex = compile_directive_regex("dir");
+        let url_regex = compile_directive_regex("url");
+
+        let directives = parse(
+            &tag_regex,
+            None,
+            &ref_regex,
+            &file_regex,
+            &dir_regex,
+            &url_regex,
+            &[],
+            false,
+            None,
+            &path,
+            contents.as_ref(),
+        );
+
+        assert_eq!(directives.suppressions.len(), 2);
+        assert_eq!(directives.suppressions[0].line_number, Some(2));
+        assert_eq!(directives.suppressions[0].kinds, vec![Kind::DanglingRef]);
+        assert_eq!(directives.suppressions[1].line_number, Some(2));
+        assert!(directives.suppressions[1].kinds.is_empty());
+    }
+
+    #[test]
+    fn parse_aliases() {
+        let path = Path::new("file.rs").to_owned();
+        let contents = r"
+      // [?tag:new][?alias:old] [?alias:older] -- The description.
+      // [?alias:stray]
+    "
+        .trim()
+        .replace('?', "")
+        .as_bytes()
+        .to_owned();
+
+        let tag_regex = compile_directive_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let file_regex = compile_directive_regex("file");
+        let dir_regex = compile_directive_regex("dir");
+        let url_regex = compile_directive_regex("url");
+
+        let directives = parse(
+            &tag_regex,
+            None,
+            &ref_regex,
+            &file_regex,
+            &dir_regex,
+            &url_regex,
+            &[],
+            false,
+            None,
+            &path,
+            contents.as_ref(),
+        );
+
+        assert_eq!(
+            directives.tags[0].description.as_deref(),
+            Some("The description."),
+        );
+        assert_eq!(directives.aliases.len(), 2);
+        assert_eq!(directives.aliases[0].tag, "new");
+        assert_eq!(directives.aliases[0].directive.r#type, Type::Alias);
+        assert_eq!(directives.aliases[0].directive.label, "old");
+        assert_eq!(directives.aliases[1].tag, "new");
+        assert_eq!(directives.aliases[1].directive.label, "older");
+    }
+
+    #[test]
+    fn parse_attributes() {
+        let path = Path::new("file.rs").to_owned();
+        let contents = r"
+      // [?tag:invariant x since=2023-04 severity=high][?alias:old] -- The description.
+      // [?ref:invariant x@sha256:1a2b3c4d reviewed=yes] [?ref:y=z]
+    "
+        .trim()
+        .replace('?', "")
+        .as_bytes()
+        .to_owned();
+
+        let tag_regex = compile_directive_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let file_regex = compile_directive_regex("file");
+        let dir_regex = compile_directive_regex("dir");
+        let url_regex = compile_directive_regex("url");
+
+        let directives = parse(
+            &tag_regex,
+            None,
+            &ref_regex,
+            &file_regex,
+            &dir_regex,
+            &url_regex,
+            &[],
+            false,
+            None,
+            &path,
+            contents.as_ref(),
+        );
+
+        let tag = &directives.tags[0];
+        assert_eq!(tag.label, "invariant x");
+        assert_eq!(
+            tag.attributes,
+            BTreeMap::from([
+                ("severity".to_owned(), "high".to_owned()),
+                ("since".to_owned(), "2023-04".to_owned()),
+            ]),
+        );
+        assert_eq!(
+            tag.to_string(),
+            "[?tag:invariant x severity=high since=2023-04] @ file.rs:1:4".replace('?', ""),
+        );
+        assert_eq!(directives.aliases[0].tag, "invariant x");
+        assert_eq!(directives.refs[0].label, "invariant x");
+        assert_eq!(directives.refs[0].attributes["reviewed"], "yes");
+        assert_eq!(directives.pins[0].label, "invariant x@sha256:1a2b3c4d");
+        assert_eq!(directives.refs[1].label, "y=z");
+        assert!(directives.refs[1].attributes.is_empty());
+    }
+
+    #[test]
+    fn parse_anchors() {
+        let path = Path::new("file.md").to_owned();
+        let contents = r"
+      ## Retries <!-- [?anchor:retries] -->
+      See [?file:file.md#retries].
+    "
+        .trim()
+        .replace('?', "")
+        .as_bytes()
+        .to_owned();
+
+        let tag_regex = compile_directive_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let file_regex = compile_directive_regex("file");
+        let dir_regex = compile_directive_regex("dir");
+        let url_regex = compile_directive_regex("url");
+
+        let directives = parse(
+            &tag_regex,
+            None,
+            &ref_regex,
+            &file_regex,
+            &dir_regex,
+            &url_regex,
+            &[],
+            false,
+            None,
+            &path,
+            contents.as_ref(),
+        );
+
+        assert_eq!(directives.anchors.len(), 1);
+        assert_eq!(directives.anchors[0].r#type, Type::Anchor);
+        assert_eq!(directives.anchors[0].label, "retries");
+        assert_eq!(directives.files[0].label, "file.md#retries");
+    }
+
+    #[test]
+    fn parse_comments_only_unrecognized_file_type() {
+        let path = Path::new("file.md").to_owned();
+        let contents = r"
+      [?tag:label]
+    "
+        .trim()
+        .replace('?', "")
+        .as_bytes()
+        .to_owned();
+
+        let tag_regex = compile_directive_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let file_regex = compile_directive_regex("file");
+        let dir_regex = compile_directive_regex("dir");
+        let url_regex = compile_directive_regex("url");
+
+        let directives = parse(
+            &tag_regex,
+            None,
+            &ref_regex,
+            &file_regex,
+            &dir_regex,
+            &url_regex,
+            &[],
+            true,
+            None,
+            &path,
+            contents.as_ref(),
+        );
+
+        assert_eq!(directives.tags.len(), 1);
+        assert_eq!(directives.tags[0].label, "label");
+    }
+
+    #[test]
+    fn lines_index() {
+        let text = "foo\r\nbar\rbaz\n\r\rqux";
+        let lines = Lines::new(text);
+
+        assert_eq!(
+            super::lines(text).collect::<Vec<_>>(),
+            vec![
+                ("foo", "\r\n"),
+                ("bar", "\r"),
+                ("baz", "\n"),
+                ("", "\r"),
+                ("", "\r"),
+                ("qux", ""),
+            ],
+        );
+        assert_eq!(lines.line_of(0), 0);
+        assert_eq!(lines.line_of(4), 0);
+        assert_eq!(lines.line_of(5), 1);
+        assert_eq!(lines.line_of(9), 2);
+        assert_eq!(lines.line_of(text.len()), 5);
+        assert_eq!(Lines::new("").len(), 0);
+        assert_eq!(Lines::new("\n").len(), 1);
+    }
+
+    #[test]
+    fn serialize_positions() {
+        let directive = Directive {
+            column: 5,
+            span: 4..13,
+            ..Directive::new(Type::Ref, "foo", "file.rs", 3)
+        };
+
+        let json = serde_json::to_value(&directive).unwrap();
+
+        assert_eq!(json["line_number"], 3_usize);
+        assert_eq!(json["column"], 5_usize);
+        assert_eq!(json["line_index"], 2_usize);
+        assert_eq!(json["column_index"], 4_usize);
+        assert!(json.get("description").is_none());
+        assert_eq!(
+            serde_json::from_value::<Directive>(json)
+                .unwrap()
+                .line_number,
+            3,
+        );
+    }
+
+    #[test]
+    fn parse_line_endings() {
+        let path = Path::new("file.rs").to_owned();
+        let contents = "[?tag:a]\r\n[?tag:b]\r[?tag:c]\n\r[?tag:d]".replace('?', "");
+
+        let tag_regex = compile_directive_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let file_regex = compile_directive_regex("file");
+        let dir_regex = compile_directive_regex("dir");
+        let url_regex = compile_directive_regex("url");
+
+        let directives = parse(
+            &tag_regex,
+            None,
+            &ref_regex,
+            &file_regex,
+            &dir_regex,
+            &url_regex,
+            &[],
+            false,
+            None,
+            &path,
+            contents.as_bytes(),
+        );
+
+        assert_eq!(
+            directives
+                .tags
+                .iter()
+                .map(|tag| (tag.label.as_str(), tag.line_number, tag.column))
+                .collect::<Vec<_>>(),
+            vec![("a", 1, 1), ("b", 2, 1), ("c", 3, 1), ("d", 5, 1)],
+        );
+    }
+
+    #[test]
+    fn parse_large() {
+        let path = Path::new("file.rs").to_owned();
+        let filler = "let x = 1;\n".repeat(usize::try_from(THRESHOLD).unwrap() / 10);
+        let contents = format!(
+            "{filler}// [?tag:foo\n// Described here.\n// ]\r\n[?ref:foo]\r\n<<bar>>\n{filler}\
+             [?file:baz]",
+        )
+        .replace('?', "");
+
+        let tag_regex = compile_directive_regex("tag");
+        let tag_block_regex = compile_block_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let file_regex = compile_directive_regex("file");
+        let dir_regex = compile_directive_regex("dir");
+        let url_regex = compile_directive_regex("url");
+        let custom_regex = compile_custom_regex("^<<([^>]*)>>$").unwrap();
+
+        let directives = parse(
+            &tag_regex,
+            Some(&tag_block_regex),
+            &ref_regex,
+            &file_regex,
+            &dir_regex,
+            &url_regex,
+            &[("custom".to_owned(), custom_regex)],
+            false,
+            None,
+            &path,
+            contents.as_bytes(),
+        );
+
+        let line_count = filler.lines().count();
+        assert_eq!(directives.tags.len(), 1);
+        assert_eq!(directives.tags[0].line_number, line_count + 1);
+        assert_eq!(
+            directives.tags[0].description.as_deref(),
+            Some("Described here."),
+        );
+        assert_eq!(directives.refs.len(), 1);
+        assert_eq!(directives.refs[0].line_number, line_count + 4);
+        assert_eq!(directives.custom.len(), 1);
+        assert_eq!(directives.custom[0].label, "bar");
+        assert_eq!(directives.files.len(), 1);
+        assert_eq!(directives.files[0].line_number, line_count * 2 + 6);
+    }
 }
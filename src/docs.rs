@@ -0,0 +1,114 @@
+use crate::{
+    directive::{self, Directive},
+    violation::{Kind, Severity, Violation},
+    walk,
+};
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+// This function normalizes a label or the text of a heading for comparison, ignoring case, code
+// spans, and the difference between spaces, underscores, and hyphens.
+fn normalize(text: &str) -> String {
+    text.trim()
+        .replace('`', "")
+        .to_lowercase()
+        .chars()
+        .map(|character| match character {
+            ' ' | '_' => '-',
+            _ => character,
+        })
+        .collect()
+}
+
+// This function checks that every tag outside the documentation directory has a section in it: a
+// Markdown heading which either matches the label of the tag or contains a reference to it. The
+// tags are keyed as in the index, and the headings are the ones found in the Markdown files. It
+// returns a vector of violations.
+#[must_use]
+pub fn check(
+    tags_map: &BTreeMap<String, Vec<Directive>>,
+    refs: &[Directive],
+    headings: &[Directive],
+    docs: &Path,
+    case_insensitive_labels: bool,
+) -> Vec<Violation> {
+    let docs = walk::normalize(docs);
+    let in_docs = |directive: &Directive| walk::relative(&directive.path).starts_with(&docs);
+    let headings = headings
+        .iter()
+        .filter(|heading| in_docs(heading))
+        .collect::<Vec<_>>();
+
+    // Tags are documented by headings with matching text, or by references in headings.
+    let sections = headings
+        .iter()
+        .map(|heading| (walk::normalize(&heading.path), heading.line_number))
+        .collect::<HashSet<(PathBuf, usize)>>();
+    let titles = headings
+        .iter()
+        .map(|heading| normalize(&heading.label))
+        .collect::<HashSet<_>>();
+    let referenced = refs
+        .iter()
+        .filter(|r#ref| sections.contains(&(walk::normalize(&r#ref.path), r#ref.line_number)))
+        .map(|r#ref| directive::key(&r#ref.label, case_insensitive_labels))
+        .collect::<HashSet<_>>();
+
+    tags_map
+        .iter()
+        .filter(|(label, _)| {
+            !referenced.contains(label.as_str()) && !titles.contains(&normalize(label))
+        })
+        .flat_map(|(_, tags)| tags)
+        .filter(|tag| !in_docs(tag))
+        .map(|tag| Violation {
+            kind: Kind::UndocumentedTag,
+            severity: Severity::Error,
+            message: format!("{tag} has no section in `{}`.", docs.to_string_lossy()),
+            directives: vec![tag.clone()],
+            suggestions: Vec::new(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::{Directive, Type},
+        docs::check,
+    };
+    use std::{collections::BTreeMap, path::PathBuf};
+
+    #[test]
+    fn check_sections() {
+        let mut tags_map = BTreeMap::new();
+        for (label, path) in [
+            ("retry_policy", "./src/retry.rs"),
+            ("cache", "./src/cache.rs"),
+            ("rate-limits", "./src/limits.rs"),
+            ("glossary", "./docs/index.md"),
+        ] {
+            tags_map.insert(
+                label.to_owned(),
+                vec![Directive::new(Type::Tag, label, path, 1)],
+            );
+        }
+        let refs = [
+            Directive::new(Type::Ref, "cache", "./docs/caching.md", 3),
+            Directive::new(Type::Ref, "rate-limits", "./docs/limits.md", 2),
+        ];
+        let headings = [
+            Directive::new(Type::Anchor, "`Retry Policy`", "./docs/retries.md", 1),
+            Directive::new(Type::Anchor, "Caching", "./docs/caching.md", 3),
+            Directive::new(Type::Anchor, "Limits", "./docs/limits.md", 1),
+            Directive::new(Type::Anchor, "rate-limits", "./README.md", 1),
+        ];
+
+        let violations = check(&tags_map, &refs, &headings, &PathBuf::from("docs"), false);
+
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].directives[0].label, "rate-limits");
+    }
+}
@@ -0,0 +1,297 @@
+use crate::{
+    directive::{compile_block_regex, compile_directive_regex},
+    violation::Kind,
+    walk,
+};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use regex::Regex;
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+// This is the name of the files which adjust the settings for the directory containing them and
+// everything in it.
+pub const FILE_NAME: &str = ".tagref.toml";
+
+// This struct holds a sigil for each built-in kind of label.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Sigils {
+    pub tag: Option<String>,
+    pub r#ref: Option<String>,
+    pub file: Option<String>,
+    pub dir: Option<String>,
+    pub url: Option<String>,
+}
+
+// This struct represents the contents of a drop-in file. Every setting is optional.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DropIn {
+    // Files and directories to skip, in .gitignore syntax relative to the directory
+    pub exclude: Vec<String>,
+
+    // Checks to turn off (or back on, if a parent directory turned them off), by kind of violation
+    pub checks: BTreeMap<Kind, bool>,
+
+    pub sigils: Sigils,
+}
+
+// This struct holds the regular expressions for the labels whose sigils were changed.
+#[derive(Clone, Debug, Default)]
+pub struct Regexes {
+    // The regex for tags, paired with the one for the opening of block tags
+    pub tag: Option<(Regex, Regex)>,
+
+    pub r#ref: Option<Regex>,
+    pub file: Option<Regex>,
+    pub dir: Option<Regex>,
+    pub url: Option<Regex>,
+}
+
+// This struct holds the settings for the files in a directory, merged from the drop-in files in it
+// and its ancestors. Settings in deeper directories take precedence.
+#[derive(Clone, Debug, Default)]
+pub struct Settings {
+    // A matcher for each drop-in file which excludes anything
+    excludes: Vec<Gitignore>,
+
+    pub disabled: BTreeSet<Kind>,
+    pub sigils: Sigils,
+    pub regexes: Regexes,
+}
+
+impl Settings {
+    // This function applies a drop-in file in the given directory on top of these settings.
+    fn merge(&self, dir: &Path, drop_in: DropIn) -> Result<Self, String> {
+        let mut settings = self.clone();
+
+        if !drop_in.exclude.is_empty() {
+            let mut builder = GitignoreBuilder::new(dir);
+            for glob in &drop_in.exclude {
+                builder
+                    .add_line(None, glob)
+                    .map_err(|error| format!("Invalid glob `{glob}`: {error}"))?;
+            }
+            settings.excludes.push(
+                builder
+                    .build()
+                    .map_err(|error| format!("Unable to build the exclusions: {error}"))?,
+            );
+        }
+
+        for (kind, enabled) in drop_in.checks {
+            if enabled {
+                settings.disabled.remove(&kind);
+            } else {
+                settings.disabled.insert(kind);
+            }
+        }
+
+        let sigils = drop_in.sigils;
+        if let Some(sigil) = sigils.tag {
+            settings.regexes.tag =
+                Some((compile_directive_regex(&sigil), compile_block_regex(&sigil)));
+            settings.sigils.tag = Some(sigil);
+        }
+        for (sigil, current, regex) in [
+            (
+                sigils.r#ref,
+                &mut settings.sigils.r#ref,
+                &mut settings.regexes.r#ref,
+            ),
+            (
+                sigils.file,
+                &mut settings.sigils.file,
+                &mut settings.regexes.file,
+            ),
+            (
+                sigils.dir,
+                &mut settings.sigils.dir,
+                &mut settings.regexes.dir,
+            ),
+            (
+                sigils.url,
+                &mut settings.sigils.url,
+                &mut settings.regexes.url,
+            ),
+        ] {
+            if let Some(sigil) = sigil {
+                *regex = Some(compile_directive_regex(&sigil));
+                *current = Some(sigil);
+            }
+        }
+
+        Ok(settings)
+    }
+
+    // This function determines whether a path (relative to the working directory) is excluded.
+    #[must_use]
+    pub fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        self.excludes
+            .iter()
+            .any(|gitignore| gitignore.matched(path, is_dir).is_ignore())
+    }
+
+    // This function determines whether any sigils were changed, in which case the labels in the
+    // files can't be taken from a cache.
+    #[must_use]
+    pub fn changes_sigils(&self) -> bool {
+        self.sigils != Sigils::default()
+    }
+}
+
+// This function parses a drop-in file.
+pub fn parse(contents: &str, path: &Path) -> Result<DropIn, String> {
+    toml::from_str(contents)
+        .map_err(|error| format!("Error when parsing {}: {error}", path.to_string_lossy()))
+}
+
+// This function loads the drop-in file in a directory, if there is one.
+fn load(dir: &Path) -> Result<Option<DropIn>, String> {
+    let path = dir.join(FILE_NAME);
+    match fs::read_to_string(&path) {
+        Ok(contents) => parse(&contents, &path).map(Some),
+        // There's no filesystem in some environments (e.g., WebAssembly in a browser), in which
+        // case there are no drop-in files either.
+        Err(error) if matches!(error.kind(), ErrorKind::NotFound | ErrorKind::Unsupported) => {
+            Ok(None)
+        }
+        Err(error) => Err(format!(
+            "Error when reading {}: {error}",
+            path.to_string_lossy(),
+        )),
+    }
+}
+
+// This struct loads drop-in files as they're needed, and remembers the settings for each
+// directory.
+#[derive(Debug, Default)]
+pub struct DropIns {
+    settings: Mutex<HashMap<PathBuf, Arc<Settings>>>,
+}
+
+impl DropIns {
+    // This function forgets the drop-in files which were loaded, so changes to them are noticed.
+    pub fn clear(&self) {
+        // The `unwrap` is safe assuming no poisoning.
+        self.settings.lock().unwrap().clear();
+    }
+
+    // This function returns the settings for the files in a directory, which is relative to the
+    // working directory (e.g., as returned by `walk::relative`).
+    pub fn settings(&self, dir: &Path) -> Result<Arc<Settings>, String> {
+        // The `unwrap` is safe assuming no poisoning.
+        let cached = self.settings.lock().unwrap().get(dir).cloned();
+        if let Some(settings) = cached {
+            return Ok(settings);
+        }
+
+        let parent = match dir.parent() {
+            Some(parent) => self.settings(parent)?,
+            None => Arc::default(),
+        };
+        let settings = match load(dir)? {
+            Some(drop_in) => Arc::new(
+                parent
+                    .merge(dir, drop_in)
+                    .map_err(|error| format!("{error} in {}", dir.join(FILE_NAME).display()))?,
+            ),
+            None => parent,
+        };
+
+        // The `unwrap` is safe assuming no poisoning.
+        self.settings
+            .lock()
+            .unwrap()
+            .insert(dir.to_owned(), settings.clone());
+        Ok(settings)
+    }
+
+    // This function returns the settings for a file or directory, which come from the drop-in
+    // files in the directories containing it.
+    pub fn settings_for(&self, path: &Path) -> Result<Arc<Settings>, String> {
+        let path = walk::relative(path);
+        self.settings(path.parent().unwrap_or_else(|| Path::new("")))
+    }
+
+    // This function determines whether a check applies to a file. Drop-in files which can't be
+    // loaded are reported when scanning, so they're ignored here.
+    #[must_use]
+    pub fn is_enabled(&self, kind: Kind, path: &Path) -> bool {
+        !self
+            .settings_for(path)
+            .is_ok_and(|settings| settings.disabled.contains(&kind))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        drop_ins::{Settings, parse},
+        violation::Kind,
+    };
+    use std::path::Path;
+
+    fn merge(settings: &Settings, dir: &str, contents: &str) -> Settings {
+        settings
+            .merge(Path::new(dir), parse(contents, Path::new(dir)).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn parse_unknown_check() {
+        assert!(parse("[checks]\nfoo = false\n", Path::new(".tagref.toml")).is_err());
+    }
+
+    #[test]
+    fn merge_exclude() {
+        let settings = merge(
+            &Settings::default(),
+            "vendor",
+            "exclude = [\"generated/\"]\n",
+        );
+
+        assert!(settings.is_excluded(Path::new("vendor/generated"), true));
+        assert!(!settings.is_excluded(Path::new("vendor/generated"), false));
+        assert!(!settings.is_excluded(Path::new("vendor/src"), true));
+    }
+
+    #[test]
+    fn merge_checks() {
+        let parent = merge(
+            &Settings::default(),
+            "docs",
+            "[checks]\ndangling_ref = false\ninvalid_file_ref = false\n",
+        );
+        let child = merge(&parent, "docs/api", "[checks]\ndangling_ref = true\n");
+
+        assert!(parent.disabled.contains(&Kind::DanglingRef));
+        assert!(!child.disabled.contains(&Kind::DanglingRef));
+        assert!(child.disabled.contains(&Kind::InvalidFileRef));
+    }
+
+    #[test]
+    fn merge_sigils() {
+        let parent = merge(&Settings::default(), "docs", "[sigils]\ntag = \"anchor\"\n");
+        let child = merge(&parent, "docs/api", "[sigils]\nref = \"see\"\n");
+
+        assert!(!Settings::default().changes_sigils());
+        assert_eq!(child.sigils.tag.as_deref(), Some("anchor"));
+        assert_eq!(child.sigils.r#ref.as_deref(), Some("see"));
+        assert!(
+            child
+                .regexes
+                .tag
+                .as_ref()
+                .unwrap()
+                .0
+                .is_match(&"[?anchor:x]".replace('?', "")),
+        );
+    }
+}
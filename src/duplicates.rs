@@ -1,19 +1,121 @@
-use crate::directive::Directive;
-use std::{collections::HashMap, fmt::Write};
+use crate::{
+    directive::Directive,
+    violation::{Kind, Severity, Violation},
+    walk,
+};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    path::{Path, PathBuf},
+};
 
-// This function checks that all the vectors in `tags_map` have at most one element. It returns a
-// vector of error strings.
-pub fn check(tags_map: &HashMap<String, Vec<Directive>>) -> Vec<String> {
-    let mut errors = Vec::<String>::new();
+// This enum represents the ways duplicate tags can be treated.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Policy {
+    // Duplicate tags are errors.
+    #[default]
+    Error,
+
+    // Duplicate tags are reported as warnings.
+    Warn,
+
+    // Duplicate tags are allowed as long as exactly one of them is in the most preferred of the
+    // canonical paths where any of them appear. The others are considered copies of it.
+    Canonical,
+}
+
+// This struct specifies how duplicates are treated for tags whose labels start with `prefix`. If
+// several rules apply to a label, the one with the longest prefix wins.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Rule {
+    pub prefix: String,
+    pub policy: Policy,
+
+    // The paths where canonical tags live, in order of preference. Only used by the canonical
+    // policy.
+    pub canonical_paths: Vec<PathBuf>,
+}
+
+// This function finds the rule which applies to a label. Among rules with equally long prefixes,
+// the last one wins, so later rules can override earlier ones.
+fn rule<'a>(rules: &'a [Rule], label: &str) -> Option<&'a Rule> {
+    rules
+        .iter()
+        .filter(|rule| label.starts_with(&rule.prefix))
+        .max_by_key(|rule| rule.prefix.len())
+}
+
+// This function determines whether a tag is in one of the canonical paths and, if so, returns the
+// position of the first such path in the list.
+fn priority(path: &Path, canonical_paths: &[PathBuf]) -> Option<usize> {
+    let path = walk::relative(path);
+    canonical_paths
+        .iter()
+        .position(|canonical_path| path.starts_with(canonical_path))
+}
+
+// This function determines whether exactly one of the given tags is in the most preferred of the
+// canonical paths where any of them appear.
+fn has_canonical(directives: &[Directive], canonical_paths: &[PathBuf]) -> bool {
+    let priorities = directives
+        .iter()
+        .filter_map(|directive| priority(&directive.path, canonical_paths))
+        .collect::<Vec<_>>();
+    priorities.iter().min().is_some_and(|best| {
+        priorities
+            .iter()
+            .filter(|priority| *priority == best)
+            .count()
+            == 1
+    })
+}
+
+// This function checks that all the vectors in `tags_map` have at most one element, subject to the
+// given rules for tolerating duplicates. It returns a vector of violations.
+#[must_use]
+pub fn check(tags_map: &BTreeMap<String, Vec<Directive>>, rules: &[Rule]) -> Vec<Violation> {
+    let mut errors = Vec::<Violation>::new();
 
     for (label, directives) in tags_map {
         if directives.len() > 1 {
+            let (policy, canonical_paths) = rule(rules, label)
+                .map_or((Policy::Error, &[] as &[PathBuf]), |rule| {
+                    (rule.policy, &rule.canonical_paths)
+                });
+            let severity = match policy {
+                Policy::Error => Severity::Error,
+                Policy::Warn => Severity::Warning,
+                Policy::Canonical => {
+                    if has_canonical(directives, canonical_paths) {
+                        continue;
+                    }
+                    Severity::Error
+                }
+            };
+
             let mut error = String::new();
-            let _ = writeln!(error, "Duplicate tags found for label `{label}`:");
+            if policy == Policy::Canonical {
+                let _ = writeln!(
+                    error,
+                    "Duplicate tags found for label `{label}`, but no single one of them is \
+                     canonical:",
+                );
+            } else {
+                let _ = writeln!(error, "Duplicate tags found for label `{label}`:");
+            }
             for directive in directives {
                 let _ = writeln!(error, "  {directive}");
             }
-            errors.push(error);
+            errors.push(Violation {
+                kind: Kind::DuplicateTag,
+                severity,
+                message: error,
+                directives: directives.clone(),
+                suggestions: Vec::new(),
+            });
         }
     }
 
@@ -24,103 +126,149 @@ pub fn check(tags_map: &HashMap<String, Vec<Directive>>) -> Vec<String> {
 mod tests {
     use crate::{
         directive::{Directive, Type},
-        duplicates::check,
+        duplicates::{Policy, Rule, check},
+        violation::Severity,
     };
-    use std::{collections::HashMap, path::Path};
+    use std::{collections::BTreeMap, path::PathBuf};
 
     #[test]
     fn check_empty() {
-        assert!(check(&HashMap::new()).is_empty());
+        assert!(check(&BTreeMap::new(), &[]).is_empty());
     }
 
     #[test]
     fn check_no_dupes() {
-        let mut tags_map = HashMap::new();
+        let mut tags_map = BTreeMap::new();
 
-        let tags_vec1 = vec![Directive {
-            r#type: Type::Tag,
-            label: "tag1".to_owned(),
-            path: Path::new("file1.rs").to_owned(),
-            line_number: 1,
-        }];
+        let tags_vec1 = vec![Directive::new(Type::Tag, "tag1", "file1.rs", 1)];
 
-        let tags_vec2 = vec![Directive {
-            r#type: Type::Tag,
-            label: "tag2".to_owned(),
-            path: Path::new("file2.rs").to_owned(),
-            line_number: 2,
-        }];
+        let tags_vec2 = vec![Directive::new(Type::Tag, "tag2", "file2.rs", 2)];
 
         tags_map.insert("tag1".to_owned(), tags_vec1);
         tags_map.insert("tag2".to_owned(), tags_vec2);
 
-        assert!(check(&tags_map).is_empty());
+        assert!(check(&tags_map, &[]).is_empty());
     }
 
     #[test]
     fn check_dupes() {
-        let mut tags_map = HashMap::new();
+        let mut tags_map = BTreeMap::new();
 
-        let tags_vec1 = vec![Directive {
-            r#type: Type::Tag,
-            label: "tag1".to_owned(),
-            path: Path::new("file1.rs").to_owned(),
-            line_number: 1,
-        }];
+        let tags_vec1 = vec![Directive::new(Type::Tag, "tag1", "file1.rs", 1)];
 
         let tags_vec2 = vec![
-            Directive {
-                r#type: Type::Tag,
-                label: "tag2".to_owned(),
-                path: Path::new("file1.rs").to_owned(),
-                line_number: 1,
-            },
-            Directive {
-                r#type: Type::Tag,
-                label: "tag2".to_owned(),
-                path: Path::new("file2.rs").to_owned(),
-                line_number: 2,
-            },
+            Directive::new(Type::Tag, "tag2", "file1.rs", 1),
+            Directive::new(Type::Tag, "tag2", "file2.rs", 2),
         ];
 
         let tags_vec3 = vec![
-            Directive {
-                r#type: Type::Tag,
-                label: "tag3".to_owned(),
-                path: Path::new("file1.rs").to_owned(),
-                line_number: 1,
-            },
-            Directive {
-                r#type: Type::Tag,
-                label: "tag3".to_owned(),
-                path: Path::new("file2.rs").to_owned(),
-                line_number: 2,
-            },
-            Directive {
-                r#type: Type::Tag,
-                label: "tag3".to_owned(),
-                path: Path::new("file3.rs").to_owned(),
-                line_number: 2,
-            },
+            Directive::new(Type::Tag, "tag3", "file1.rs", 1),
+            Directive::new(Type::Tag, "tag3", "file2.rs", 2),
+            Directive::new(Type::Tag, "tag3", "file3.rs", 2),
         ];
 
         tags_map.insert("tag1".to_owned(), tags_vec1.clone());
         tags_map.insert("tag2".to_owned(), tags_vec2.clone());
         tags_map.insert("tag3".to_owned(), tags_vec3.clone());
 
-        let errors = check(&tags_map);
+        let errors = check(&tags_map, &[]);
         assert_eq!(errors.len(), 2);
         assert!(
-            (errors[0].contains(&format!("{}", tags_vec2[0]))
-                && errors[0].contains(&format!("{}", tags_vec2[1]))
-                && errors[1].contains(&format!("{}", tags_vec3[0]))
-                && errors[1].contains(&format!("{}", tags_vec3[1]))
-                && errors[1].contains(&format!("{}", tags_vec3[2])))
-                || (errors[0].contains(&format!("{}", tags_vec3[0]))
-                    && errors[0].contains(&format!("{}", tags_vec3[1]))
-                    && errors[0].contains(&format!("{}", tags_vec3[2]))
-                    && errors[1].contains(&format!("{}", tags_vec2[0]))
-                    && errors[1].contains(&format!("{}", tags_vec2[1]))),
+            (errors[0].message.contains(&format!("{}", tags_vec2[0]))
+                && errors[0].message.contains(&format!("{}", tags_vec2[1]))
+                && errors[1].message.contains(&format!("{}", tags_vec3[0]))
+                && errors[1].message.contains(&format!("{}", tags_vec3[1]))
+                && errors[1].message.contains(&format!("{}", tags_vec3[2])))
+                || (errors[0].message.contains(&format!("{}", tags_vec3[0]))
+                    && errors[0].message.contains(&format!("{}", tags_vec3[1]))
+                    && errors[0].message.contains(&format!("{}", tags_vec3[2]))
+                    && errors[1].message.contains(&format!("{}", tags_vec2[0]))
+                    && errors[1].message.contains(&format!("{}", tags_vec2[1]))),
+        );
+    }
+
+    fn duplicated_tag(paths: &[&str]) -> BTreeMap<String, Vec<Directive>> {
+        let mut tags_map = BTreeMap::new();
+        tags_map.insert(
+            "tag".to_owned(),
+            paths
+                .iter()
+                .map(|path| Directive::new(Type::Tag, "tag", path, 1))
+                .collect(),
+        );
+        tags_map
+    }
+
+    #[test]
+    fn check_dupes_warn() {
+        let rules = [Rule {
+            policy: Policy::Warn,
+            ..Rule::default()
+        }];
+
+        let errors = check(&duplicated_tag(&["file1.rs", "file2.rs"]), &rules);
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn check_dupes_longest_prefix_wins() {
+        let rules = [
+            Rule {
+                policy: Policy::Warn,
+                ..Rule::default()
+            },
+            Rule {
+                prefix: "ta".to_owned(),
+                policy: Policy::Error,
+                ..Rule::default()
+            },
+            Rule {
+                prefix: "other".to_owned(),
+                policy: Policy::Warn,
+                ..Rule::default()
+            },
+        ];
+
+        let errors = check(&duplicated_tag(&["file1.rs", "file2.rs"]), &rules);
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn check_dupes_canonical() {
+        let rules = [Rule {
+            policy: Policy::Canonical,
+            canonical_paths: vec![PathBuf::from("src"), PathBuf::from("proto")],
+            ..Rule::default()
+        }];
+
+        // One copy in the most preferred canonical path
+        assert!(check(&duplicated_tag(&["./src/a.rs", "./gen/a.rs"]), &rules).is_empty());
+        assert!(
+            check(
+                &duplicated_tag(&["./src/a.rs", "./proto/a.proto", "./gen/a.rs"]),
+                &rules,
+            )
+            .is_empty(),
+        );
+
+        // No copies in canonical paths
+        assert_eq!(
+            check(&duplicated_tag(&["./gen/a.rs", "./gen/b.rs"]), &rules).len(),
+            1,
+        );
+
+        // Several copies in the most preferred canonical path
+        assert_eq!(
+            check(
+                &duplicated_tag(&["./src/a.rs", "./src/b.rs", "./gen/a.rs"]),
+                &rules,
+            )
+            .len(),
+            1,
         );
     }
 }
@@ -0,0 +1,82 @@
+use std::{env, path::Path, process::Command};
+
+// This function builds the command line for opening a file at a line and column. A configured
+// command has `{path}`, `{line}`, and `{column}` replaced in each of its words. Otherwise, the
+// editor is assumed to accept the line as an argument like `+42`, as most terminal editors do.
+fn command_line(
+    command: &[String],
+    editor: &str,
+    path: &Path,
+    line_number: usize,
+    column: usize,
+) -> Vec<String> {
+    let path = path.to_string_lossy();
+    if command.is_empty() {
+        editor
+            .split_whitespace()
+            .map(ToOwned::to_owned)
+            .chain([format!("+{line_number}"), path.into_owned()])
+            .collect()
+    } else {
+        command
+            .iter()
+            .map(|word| {
+                word.replace("{path}", &path)
+                    .replace("{line}", &line_number.to_string())
+                    .replace("{column}", &column.to_string())
+            })
+            .collect()
+    }
+}
+
+// This function opens a file at a line and column with the configured command, or else with
+// `$VISUAL` or `$EDITOR` (or `vi` if neither is set). It waits for the editor to exit.
+pub fn open(
+    command: &[String],
+    path: &Path,
+    line_number: usize,
+    column: usize,
+) -> Result<(), String> {
+    let editor = ["VISUAL", "EDITOR"]
+        .into_iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_owned());
+    let words = command_line(command, &editor, path, line_number, column);
+    let Some((program, args)) = words.split_first() else {
+        return Err("The editor command is empty.".to_owned());
+    };
+    let status = Command::new(program)
+        .args(args)
+        .status()
+        .map_err(|error| format!("Unable to run `{program}`: {error}"))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("`{program}` exited with {status}."))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::command_line;
+    use std::path::Path;
+
+    #[test]
+    fn command_line_from_editor() {
+        assert_eq!(
+            command_line(&[], "emacs -nw", Path::new("src/main.rs"), 12, 4),
+            vec!["emacs", "-nw", "+12", "src/main.rs"],
+        );
+    }
+
+    #[test]
+    fn command_line_from_template() {
+        let command = ["code", "--goto", "{path}:{line}:{column}"].map(ToOwned::to_owned);
+
+        assert_eq!(
+            command_line(&command, "vi", Path::new("my file.rs"), 12, 4),
+            vec!["code", "--goto", "my file.rs:12:4"],
+        );
+    }
+}
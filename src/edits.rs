@@ -0,0 +1,506 @@
+use crate::{directive, walk};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    fs, io,
+    mem::take,
+    ops::Range,
+    path::{Path, PathBuf},
+    process,
+    sync::{Arc, Mutex},
+};
+
+// This is the number of unchanged lines shown around the changes in a diff.
+const CONTEXT: usize = 3;
+
+// This struct describes a textual edit to a file: a byte range of its contents, and the text to
+// replace it with.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Edit {
+    pub range: Range<usize>,
+    pub replacement: String,
+}
+
+// This struct holds the contents of a file along with the edits to it, sorted by position. The
+// edits don't overlap.
+#[derive(Clone, Debug)]
+struct File {
+    contents: String,
+    edits: Vec<Edit>,
+}
+
+// This struct describes a run of lines changed by edits: the indices of the lines before, and the
+// text which replaces them.
+struct Block {
+    old: Range<usize>,
+    new: String,
+}
+
+impl File {
+    // This function groups the edits into runs of changed lines, in order. Edits on the same line
+    // are in the same run.
+    fn blocks(&self) -> Vec<Block> {
+        let mut starts = Vec::new();
+        let mut position = 0;
+        for (line, ending) in directive::lines(&self.contents) {
+            starts.push(position);
+            position += line.len() + ending.len();
+        }
+        let line_of = |position| {
+            starts
+                .partition_point(|start| *start <= position)
+                .saturating_sub(1)
+        };
+
+        let mut runs = Vec::<(Range<usize>, Vec<&Edit>)>::new();
+        for edit in &self.edits {
+            // An edit which removes the ending of its last line joins the next line to it, so that
+            // line is part of the run too.
+            let last = line_of(edit.range.end.saturating_sub(1).max(edit.range.start));
+            let joined = self.contents[edit.range.clone()].ends_with(['\n', '\r'])
+                && !edit.replacement.ends_with(['\n', '\r']);
+            let lines =
+                line_of(edit.range.start)..(last + 1 + usize::from(joined)).min(starts.len());
+            match runs.last_mut() {
+                Some((run, edits)) if lines.start < run.end => {
+                    run.end = run.end.max(lines.end);
+                    edits.push(edit);
+                }
+                _ => runs.push((lines, vec![edit])),
+            }
+        }
+
+        runs.into_iter()
+            .map(|(old, edits)| {
+                let start = starts
+                    .get(old.start)
+                    .copied()
+                    .unwrap_or(self.contents.len());
+                let end = starts.get(old.end).copied().unwrap_or(self.contents.len());
+                let mut new = String::new();
+                let mut position = start;
+                for edit in edits {
+                    new.push_str(&self.contents[position..edit.range.start]);
+                    new.push_str(&edit.replacement);
+                    position = edit.range.end;
+                }
+                new.push_str(&self.contents[position..end]);
+                Block { old, new }
+            })
+            .collect()
+    }
+
+    // This function renders the edits as a unified diff, like `diff -u` or `git diff`.
+    fn diff(&self, output: &mut String, path: &Path) {
+        let path = walk::relative(path);
+        let path = path.to_string_lossy();
+        let _ = writeln!(output, "--- a/{path}\n+++ b/{path}");
+
+        let old_lines = directive::lines(&self.contents).collect::<Vec<_>>();
+        let blocks = self.blocks();
+        let (mut added, mut removed) = (0, 0);
+        let mut index = 0;
+        while index < blocks.len() {
+            // Runs which are close enough for their context to touch are shown in the same hunk.
+            let mut end = index + 1;
+            while end < blocks.len()
+                && blocks[end].old.start - blocks[end - 1].old.end <= 2 * CONTEXT
+            {
+                end += 1;
+            }
+            let hunk = &blocks[index..end];
+            let old_start = hunk[0].old.start.saturating_sub(CONTEXT);
+            let old_end = (hunk[hunk.len() - 1].old.end + CONTEXT).min(old_lines.len());
+            let new_start = old_start + added - removed;
+
+            let mut body = String::new();
+            let (mut old_count, mut new_count) = (0, 0);
+            let mut position = old_start;
+            for block in hunk {
+                for line in &old_lines[position..block.old.start] {
+                    diff_line(&mut body, ' ', *line);
+                }
+                for line in &old_lines[block.old.clone()] {
+                    diff_line(&mut body, '-', *line);
+                }
+                let mut new_lines = 0;
+                for line in directive::lines(&block.new) {
+                    diff_line(&mut body, '+', line);
+                    new_lines += 1;
+                }
+                old_count += block.old.start - position + block.old.len();
+                new_count += block.old.start - position + new_lines;
+                added += new_lines;
+                removed += block.old.len();
+                position = block.old.end;
+            }
+            for line in &old_lines[position..old_end] {
+                diff_line(&mut body, ' ', *line);
+            }
+            old_count += old_end - position;
+            new_count += old_end - position;
+
+            let _ = writeln!(
+                output,
+                "@@ -{} +{} @@",
+                hunk_range(old_start, old_count),
+                hunk_range(new_start, new_count),
+            );
+            output.push_str(&body);
+            index = end;
+        }
+    }
+}
+
+// This function writes a line of a diff. Carriage returns before line feeds are kept, so the diff
+// applies to files with Windows line endings. A line without an ending is the last line of the
+// file, which is noted as `diff` does.
+fn diff_line(output: &mut String, prefix: char, (line, ending): (&str, &str)) {
+    let _ = write!(output, "{prefix}{line}");
+    if ending == "\r\n" {
+        output.push_str(ending);
+    } else {
+        output.push('\n');
+    }
+    if ending.is_empty() {
+        output.push_str("\\ No newline at end of file\n");
+    }
+}
+
+// This function formats the range of lines in a hunk header. Lines are numbered from 1, and an
+// empty range is given by the line before it.
+fn hunk_range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{start},0"),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{count}", start + 1),
+    }
+}
+
+// This function applies edits to the contents of a file. The edits must not overlap.
+#[must_use]
+pub fn splice(contents: &str, edits: &[Edit]) -> String {
+    let mut edits = edits.iter().collect::<Vec<_>>();
+    edits.sort_by_key(|edit| edit.range.start);
+
+    let mut result = String::with_capacity(contents.len());
+    let mut position = 0;
+    for edit in edits {
+        result.push_str(&contents[position..edit.range.start]);
+        result.push_str(&edit.replacement);
+        position = edit.range.end;
+    }
+    result.push_str(&contents[position..]);
+    result
+}
+
+// This struct collects the edits to a set of files, so they can be shown as a diff or applied
+// together. Every command which rewrites files goes through it, so they all behave the same way.
+#[derive(Clone, Debug, Default)]
+pub struct Edits {
+    files: BTreeMap<PathBuf, File>,
+}
+
+impl Edits {
+    // This function calls `edit` on the path and contents of every file, and collects the edits it
+    // returns. Files which aren't valid UTF-8 are skipped, since they can't be rewritten
+    // faithfully.
+    pub fn collect<F: 'static + Clone + Send + Fn(&Path, &str) -> Vec<Edit>>(
+        paths: &[PathBuf],
+        options: &walk::Options,
+        edit: F,
+    ) -> Result<Self, String> {
+        let edits = Arc::new(Mutex::new(Self::default()));
+        let edits_clone = edits.clone();
+        walk::walk(paths, options, move |path, file| {
+            let Ok(contents) = io::read_to_string(file) else {
+                return;
+            };
+            let file_edits = edit(path, &contents);
+
+            // The `unwrap` is safe assuming no poisoning.
+            edits_clone
+                .lock()
+                .unwrap()
+                .insert(path, contents, file_edits);
+        })?;
+
+        // The `unwrap` is safe assuming no poisoning.
+        Ok(take(&mut *edits.lock().unwrap()))
+    }
+
+    // This function adds the edits to a file, given its current contents.
+    pub fn insert(&mut self, path: &Path, contents: String, mut edits: Vec<Edit>) {
+        if edits.is_empty() {
+            return;
+        }
+        edits.sort_by_key(|edit| edit.range.start);
+        self.files.insert(path.to_owned(), File { contents, edits });
+    }
+
+    // This function returns the path and current contents of each file with edits, along with the
+    // edits to it.
+    pub fn files(&self) -> impl Iterator<Item = (&Path, &str, &[Edit])> {
+        self.files.iter().map(|(path, file)| {
+            (
+                path.as_path(),
+                file.contents.as_str(),
+                file.edits.as_slice(),
+            )
+        })
+    }
+
+    // This function returns the number of files with edits.
+    #[must_use]
+    pub fn file_count(&self) -> usize {
+        self.files.len()
+    }
+
+    // This function returns the number of lines changed by the edits.
+    #[must_use]
+    pub fn line_count(&self) -> usize {
+        self.files
+            .values()
+            .flat_map(File::blocks)
+            .map(|block| block.old.len())
+            .sum()
+    }
+
+    // This function renders the edits as a unified diff, which `git apply` or `patch -p1` accept.
+    #[must_use]
+    pub fn diff(&self) -> String {
+        let mut output = String::new();
+        for (path, file) in &self.files {
+            file.diff(&mut output, path);
+        }
+        output
+    }
+
+    // This function writes the new contents of each file to a temporary file next to it, and then
+    // renames the temporary files over the originals, so no file is ever left half-written.
+    // Nothing is changed unless all the temporary files were written, and a file which changed
+    // since it was read isn't overwritten. Symlinks are followed, so the files they point to are
+    // edited rather than replaced.
+    pub fn apply(&self) -> Result<(), String> {
+        self.apply_partially().1
+    }
+
+    // This function applies the edits like `apply`, and also returns the edits which were applied.
+    // The temporary files are renamed one at a time, so some files may be rewritten even if there
+    // is an error.
+    pub fn apply_partially(&self) -> (Self, Result<(), String>) {
+        let mut staged = Vec::new();
+        for (path, file) in &self.files {
+            match stage(path, file) {
+                Ok((temporary_path, target)) => {
+                    staged.push((path.as_path(), temporary_path, target));
+                }
+                Err(error) => {
+                    for (_, temporary_path, _) in staged {
+                        let _ = fs::remove_file(temporary_path);
+                    }
+                    return (Self::default(), Err(error));
+                }
+            }
+        }
+        self.replace(staged)
+    }
+
+    // This function renames the temporary files written by `stage` over the files they're for. It
+    // returns the edits to the files which were renamed, along with any errors.
+    fn replace(&self, staged: Vec<(&Path, PathBuf, PathBuf)>) -> (Self, Result<(), String>) {
+        let mut applied = Self::default();
+        let mut errors = Vec::new();
+        for (path, temporary_path, target) in staged {
+            if let Err(error) = fs::rename(&temporary_path, &target) {
+                let _ = fs::remove_file(temporary_path);
+                errors.push(format!(
+                    "Unable to write {}: {error}",
+                    path.to_string_lossy(),
+                ));
+            } else if let Some(file) = self.files.get(path) {
+                applied.files.insert(path.to_owned(), file.clone());
+            }
+        }
+        let result = if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("\n"))
+        };
+        (applied, result)
+    }
+}
+
+// This function writes the new contents of a file to a temporary file next to it, with the same
+// permissions. It returns the path of the temporary file and the path to rename it to.
+fn stage(path: &Path, file: &File) -> Result<(PathBuf, PathBuf), String> {
+    let write_error = |error| format!("Unable to write {}: {error}", path.to_string_lossy());
+    let target = fs::canonicalize(path).map_err(write_error)?;
+    match fs::read_to_string(&target) {
+        Ok(contents) if contents == file.contents => {}
+        Ok(_) => {
+            return Err(format!(
+                "{} changed while it was being edited.",
+                path.to_string_lossy(),
+            ));
+        }
+        Err(error) => return Err(write_error(error)),
+    }
+
+    let name = target.file_name().unwrap_or_default().to_string_lossy();
+    let temporary_path = target.with_file_name(format!(".{name}.tagref-{}", process::id()));
+    let permissions = fs::metadata(&target).map_err(write_error)?.permissions();
+    let result = fs::write(&temporary_path, splice(&file.contents, &file.edits))
+        .and_then(|()| fs::set_permissions(&temporary_path, permissions));
+    if let Err(error) = result {
+        let _ = fs::remove_file(&temporary_path);
+        return Err(write_error(error));
+    }
+
+    Ok((temporary_path, target))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::edits::{Edit, Edits, splice, stage};
+    use std::{
+        env,
+        fmt::Write,
+        fs,
+        path::Path,
+        process,
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    fn edit(contents: &str, old: &str, nth: usize, replacement: &str) -> Edit {
+        let start = contents.match_indices(old).nth(nth).unwrap().0;
+        Edit {
+            range: start..start + old.len(),
+            replacement: replacement.to_owned(),
+        }
+    }
+
+    #[test]
+    fn splice_edits() {
+        let contents = "foo bar\r\nbaz foo\n";
+
+        let edits = [
+            edit(contents, "foo", 1, "quux"),
+            edit(contents, "foo", 0, "x"),
+        ];
+
+        assert_eq!(splice(contents, &edits), "x bar\r\nbaz quux\n");
+        assert_eq!(splice(contents, &[]), contents);
+    }
+
+    #[test]
+    fn diff_hunks() {
+        let mut contents = String::new();
+        for number in 1..=20_usize {
+            let _ = writeln!(contents, "line {number}");
+        }
+        contents.push_str("last");
+        let mut edits = Edits::default();
+        edits.insert(
+            Path::new("./a.rs"),
+            contents.clone(),
+            vec![
+                edit(&contents, "line 2\n", 0, "line two\n"),
+                edit(&contents, "line 5\n", 0, "line five\n"),
+                edit(&contents, "last", 0, "final"),
+            ],
+        );
+
+        assert_eq!(edits.file_count(), 1);
+        assert_eq!(edits.line_count(), 3);
+        assert_eq!(
+            edits.diff(),
+            "--- a/a.rs\n+++ b/a.rs\n@@ -1,8 +1,8 @@\n line 1\n-line 2\n+line two\n line 3\n line \
+             4\n-line 5\n+line five\n line 6\n line 7\n line 8\n@@ -18,4 +18,4 @@\n line 18\n \
+             line 19\n line 20\n-last\n\\ No newline at end of file\n+final\n\\ No newline at end \
+             of file\n",
+        );
+    }
+
+    #[test]
+    fn apply_edits() {
+        let path = env::temp_dir().join(format!(
+            "tagref-edits-{}-{}",
+            process::id(),
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos(),
+        ));
+        fs::write(&path, "foo\n").unwrap();
+        let mut edits = Edits::default();
+        edits.insert(
+            &path,
+            "foo\n".to_owned(),
+            vec![edit("foo\n", "foo", 0, "bar")],
+        );
+        let mut stale = Edits::default();
+        stale.insert(
+            &path,
+            "baz\n".to_owned(),
+            vec![edit("baz\n", "baz", 0, "bar")],
+        );
+
+        let applied = edits.apply();
+        let contents = fs::read_to_string(&path).unwrap();
+        let stale_applied = stale.apply();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(applied, Ok(()));
+        assert_eq!(contents, "bar\n");
+        assert!(stale_applied.is_err());
+    }
+
+    #[test]
+    fn apply_partially() {
+        let paths = ["a", "b"].map(|name| {
+            env::temp_dir().join(format!(
+                "tagref-edits-{name}-{}-{}",
+                process::id(),
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_nanos(),
+            ))
+        });
+        let mut edits = Edits::default();
+        for path in &paths {
+            fs::write(path, "foo\n").unwrap();
+            edits.insert(
+                path,
+                "foo\n".to_owned(),
+                vec![edit("foo\n", "foo", 0, "bar")],
+            );
+        }
+
+        // The second file can't be renamed into place, since its temporary file is gone.
+        let staged = edits
+            .files
+            .iter()
+            .map(|(path, file)| {
+                let (temporary_path, target) = stage(path, file).unwrap();
+                (path.as_path(), temporary_path, target)
+            })
+            .collect::<Vec<_>>();
+        fs::remove_file(&staged[1].1).unwrap();
+        let (applied, result) = edits.replace(staged);
+        let contents = paths.clone().map(|path| {
+            let contents = fs::read_to_string(&path).unwrap();
+            fs::remove_file(&path).unwrap();
+            contents
+        });
+
+        assert!(result.is_err());
+        assert_eq!(
+            applied.files().map(|(path, _, _)| path).collect::<Vec<_>>(),
+            vec![paths[0].as_path()],
+        );
+        assert_eq!(contents, ["bar\n", "foo\n"]);
+    }
+}
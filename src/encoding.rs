@@ -0,0 +1,111 @@
+use encoding_rs::{Encoding, UTF_8};
+use std::borrow::Cow;
+
+// This enum represents the result of decoding the contents of a file.
+#[derive(Debug, Eq, PartialEq)]
+pub enum Decoded<'a> {
+    // The contents were decoded without any errors.
+    Text(Cow<'a, str>),
+
+    // Some of the contents couldn't be decoded, so they were replaced with U+FFFD.
+    Lossy(Cow<'a, str>),
+
+    // The contents appear to be binary rather than text, so they weren't decoded.
+    Binary,
+}
+
+impl<'a> Decoded<'a> {
+    fn new((text, had_errors): (Cow<'a, str>, bool)) -> Self {
+        if had_errors {
+            Self::Lossy(text)
+        } else {
+            Self::Text(text)
+        }
+    }
+}
+
+// This function looks up an encoding by one of its labels in the WHATWG Encoding Standard (e.g.,
+// `latin1` or `shift_jis`).
+pub fn lookup(label: &str) -> Result<&'static Encoding, String> {
+    Encoding::for_label(label.trim().as_bytes())
+        .ok_or_else(|| format!("Unknown encoding `{label}`."))
+}
+
+// This function decodes the contents of a file. A byte order mark determines the encoding if there
+// is one, which is how UTF-16 files are recognized. Otherwise, the contents are decoded as UTF-8,
+// falling back to the given encoding (if any) if they aren't valid UTF-8.
+#[must_use]
+pub fn decode<'a>(bytes: &'a [u8], fallback: Option<&'static Encoding>) -> Decoded<'a> {
+    if let Some((encoding, bom_length)) = Encoding::for_bom(bytes) {
+        return Decoded::new(encoding.decode_without_bom_handling(&bytes[bom_length..]));
+    }
+
+    // Text files rarely contain null bytes, whereas binary files almost always do.
+    if bytes.contains(&0) {
+        return Decoded::Binary;
+    }
+
+    match UTF_8.decode_without_bom_handling(bytes) {
+        (text, false) => Decoded::Text(text),
+        (text, true) => match fallback {
+            Some(encoding) => Decoded::new(encoding.decode_without_bom_handling(bytes)),
+            None => Decoded::Lossy(text),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encoding::{Decoded, decode, lookup};
+
+    #[test]
+    fn decode_utf8() {
+        assert_eq!(decode("é".as_bytes(), None), Decoded::Text("é".into()));
+    }
+
+    #[test]
+    fn decode_utf8_bom() {
+        assert_eq!(
+            decode(b"\xef\xbb\xbfabc", None),
+            Decoded::Text("abc".into())
+        );
+    }
+
+    #[test]
+    fn decode_utf16_bom() {
+        assert_eq!(
+            decode(b"\xff\xfea\x00b\x00", None),
+            Decoded::Text("ab".into()),
+        );
+        assert_eq!(
+            decode(b"\xfe\xff\x00a\x00b", None),
+            Decoded::Text("ab".into()),
+        );
+    }
+
+    #[test]
+    fn decode_binary() {
+        assert_eq!(decode(b"a\x00b", None), Decoded::Binary);
+    }
+
+    #[test]
+    fn decode_fallback() {
+        assert_eq!(
+            decode(b"caf\xe9", Some(lookup("latin1").unwrap())),
+            Decoded::Text("café".into()),
+        );
+    }
+
+    #[test]
+    fn decode_lossy() {
+        assert_eq!(
+            decode(b"caf\xe9", None),
+            Decoded::Lossy("caf\u{fffd}".into())
+        );
+    }
+
+    #[test]
+    fn lookup_unknown() {
+        assert!(lookup("klingon").is_err());
+    }
+}
@@ -0,0 +1,330 @@
+use crate::{
+    count::count,
+    directive::Directive,
+    violation::{Kind, Severity, Violation},
+};
+use serde::{Serialize, Serializer};
+use std::fmt;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// This is the attribute which sets the expiry date of a label, e.g., `expires=2024-06-30`.
+pub const ATTRIBUTE: &str = "expires";
+
+// This struct represents a date in the proleptic Gregorian calendar as the number of days since
+// 1970-01-01, so dates can be compared and offset without a date library.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Date(i64);
+
+impl Date {
+    // This function parses a date written as `YYYY-MM-DD`.
+    #[must_use]
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut fields = text.splitn(3, '-');
+        let (year, month, day) = (fields.next()?, fields.next()?, fields.next()?);
+        if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+            return None;
+        }
+        let (year, month, day) = (
+            year.parse::<i64>().ok()?,
+            month.parse::<u32>().ok()?,
+            day.parse::<u32>().ok()?,
+        );
+        (1..=12).contains(&month).then_some(())?;
+        (1..=days_in_month(year, month))
+            .contains(&day)
+            .then_some(())?;
+        Some(Self::from_civil(year, month, day))
+    }
+
+    // This function returns the current date in UTC.
+    #[must_use]
+    pub fn today() -> Self {
+        // WebAssembly has no clock of its own, so the bindings supply the time.
+        #[cfg(target_arch = "wasm32")]
+        let seconds = crate::wasm::seconds_since_epoch();
+        #[cfg(not(target_arch = "wasm32"))]
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        Self(i64::try_from(seconds / 86_400).unwrap_or(i64::MAX))
+    }
+
+    // This function returns the date a number of days later (or earlier, if negative).
+    #[must_use]
+    pub fn add_days(self, days: i64) -> Self {
+        Self(self.0.saturating_add(days))
+    }
+
+    // This function returns the number of days from this date to another one.
+    #[must_use]
+    pub fn days_until(self, other: Self) -> i64 {
+        other.0 - self.0
+    }
+
+    // This function converts a year, month, and day into a date. The algorithm is from Howard
+    // Hinnant's "chrono-Compatible Low-Level Date Algorithms".
+    fn from_civil(year: i64, month: u32, day: u32) -> Self {
+        let year = if month <= 2 { year - 1 } else { year };
+        let era = year.div_euclid(400);
+        let year_of_era = year.rem_euclid(400);
+        let day_of_year = (153 * i64::from((month + 9) % 12) + 2) / 5 + i64::from(day) - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        Self(era * 146_097 + day_of_era - 719_468)
+    }
+
+    // This function converts a date into a year, month, and day, inverting `from_civil`.
+    fn to_civil(self) -> (i64, i64, i64) {
+        let days = self.0 + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+        let month = if month_index < 10 {
+            month_index + 3
+        } else {
+            month_index - 9
+        };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+        (year, month, day)
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (year, month, day) = self.to_civil();
+        write!(f, "{year:04}-{month:02}-{day:02}")
+    }
+}
+
+impl Serialize for Date {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+// This function returns the number of days in a month of a year.
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// This function parses a number of days, optionally followed by `d` (days) or `w` (weeks), as in
+// `30d` or `4w`.
+pub fn parse_days(text: &str) -> Result<i64, String> {
+    let (number, unit) = match text.strip_suffix(['d', 'w']) {
+        Some(number) => (number, &text[number.len()..]),
+        None => (text, "d"),
+    };
+    let number = number.parse::<i64>().map_err(|_| {
+        format!(
+            "Invalid duration `{text}`. Expected a number of days or weeks, like `30d` or `4w`."
+        )
+    })?;
+    Ok(if unit == "w" {
+        number.saturating_mul(7)
+    } else {
+        number
+    })
+}
+
+// This struct represents a label with an expiry date.
+#[derive(Clone, Debug, Serialize)]
+pub struct Expiration<'a> {
+    pub directive: &'a Directive,
+    pub expires: Date,
+
+    // The number of days from today until the label expires, which is negative if it expired
+    pub days_left: i64,
+}
+
+// This function finds the labels which expire before or on the given date, including the ones
+// which already expired, sorted by expiry date. Labels with invalid expiry dates are left out,
+// since `check` reports them.
+#[must_use]
+pub fn find<'a, I: IntoIterator<Item = &'a Directive>>(
+    directives: I,
+    today: Date,
+    until: Date,
+) -> Vec<Expiration<'a>> {
+    let mut expirations = directives
+        .into_iter()
+        .filter_map(|directive| {
+            let expires = Date::parse(directive.attributes.get(ATTRIBUTE)?)?;
+            (expires <= until).then(|| Expiration {
+                directive,
+                expires,
+                days_left: today.days_until(expires),
+            })
+        })
+        .collect::<Vec<_>>();
+    expirations.sort_by(|x, y| {
+        x.expires
+            .cmp(&y.expires)
+            .then_with(|| x.directive.cmp_position(y.directive))
+    });
+    expirations
+}
+
+// This function describes when a label expires relative to today, e.g., "in 3 days".
+#[must_use]
+pub fn relative(days_left: i64) -> String {
+    let days = count(
+        usize::try_from(days_left.unsigned_abs()).unwrap_or(usize::MAX),
+        "day",
+    );
+    match days_left {
+        0 => "today".to_owned(),
+        1.. => format!("in {days}"),
+        _ => format!("{days} ago"),
+    }
+}
+
+// This function checks that no label is past its expiry date, and that the expiry dates are valid.
+// A label expires at the end of its expiry date. It returns a vector of violations.
+#[must_use]
+pub fn check<'a, I: IntoIterator<Item = &'a Directive>>(
+    directives: I,
+    today: Date,
+) -> Vec<Violation> {
+    directives
+        .into_iter()
+        .filter_map(|directive| {
+            let text = directive.attributes.get(ATTRIBUTE)?;
+            let message = match Date::parse(text) {
+                Some(expires) if expires < today => format!(
+                    "{directive} expired on {expires} ({}).",
+                    relative(today.days_until(expires)),
+                ),
+                Some(_) => return None,
+                None => format!(
+                    "{directive} has an invalid expiry date `{text}`. Expected a date like \
+                     `2024-06-30`.",
+                ),
+            };
+            Some(Violation {
+                kind: Kind::ExpiredLabel,
+                severity: Severity::Error,
+                message,
+                directives: vec![directive.clone()],
+                suggestions: Vec::new(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::{Directive, Type},
+        expiry::{Date, check, find, parse_days, relative},
+        violation::Kind,
+    };
+    use std::collections::BTreeMap;
+
+    fn directive(label: &str, expires: &str) -> Directive {
+        Directive {
+            attributes: BTreeMap::from([("expires".to_owned(), expires.to_owned())]),
+            ..Directive::new(Type::Tag, label, "file.rs", 1)
+        }
+    }
+
+    #[test]
+    fn date_round_trip() {
+        for text in [
+            "1970-01-01",
+            "2000-02-29",
+            "2024-12-31",
+            "1969-12-31",
+            "2400-03-01",
+        ] {
+            assert_eq!(Date::parse(text).unwrap().to_string(), text);
+        }
+        assert_eq!(
+            Date::parse("1970-01-02").unwrap(),
+            Date::parse("1970-01-01").unwrap().add_days(1),
+        );
+    }
+
+    #[test]
+    fn date_invalid() {
+        for text in [
+            "2023-02-29",
+            "2024-13-01",
+            "2024-1-01",
+            "24-01-01",
+            "2024-01-00",
+            "soon",
+        ] {
+            assert!(Date::parse(text).is_none(), "{text}");
+        }
+    }
+
+    #[test]
+    fn parse_days_units() {
+        assert_eq!(parse_days("30d"), Ok(30));
+        assert_eq!(parse_days("4w"), Ok(28));
+        assert_eq!(parse_days("7"), Ok(7));
+        assert!(parse_days("a month").is_err());
+    }
+
+    #[test]
+    fn relative_days() {
+        assert_eq!(relative(0), "today");
+        assert_eq!(relative(1), "in 1 day");
+        assert_eq!(relative(-3), "3 days ago");
+    }
+
+    #[test]
+    fn find_within() {
+        let today = Date::parse("2024-06-01").unwrap();
+        let directives = [
+            directive("later", "2024-09-01"),
+            directive("soon", "2024-06-10"),
+            directive("past", "2024-05-01"),
+            directive("invalid", "tomorrow"),
+        ];
+
+        let expirations = find(&directives, today, today.add_days(30));
+
+        assert_eq!(
+            expirations
+                .iter()
+                .map(|expiration| (expiration.directive.label.as_str(), expiration.days_left))
+                .collect::<Vec<_>>(),
+            vec![("past", -31), ("soon", 9)],
+        );
+    }
+
+    #[test]
+    fn check_expired() {
+        let today = Date::parse("2024-06-01").unwrap();
+        let directives = [
+            directive("today", "2024-06-01"),
+            directive("past", "2024-05-31"),
+            directive("invalid", "2024-06-31"),
+        ];
+
+        let violations = check(&directives, today);
+
+        assert_eq!(violations.len(), 2);
+        assert!(
+            violations
+                .iter()
+                .all(|violation| violation.kind == Kind::ExpiredLabel)
+        );
+        assert!(
+            violations[0]
+                .message
+                .contains("expired on 2024-05-31 (1 day ago)")
+        );
+        assert!(violations[1].message.contains("invalid expiry date"));
+    }
+}
@@ -0,0 +1,404 @@
+use crate::{
+    directive::{Cell, Directives},
+    severities::PathGlob,
+    walk,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use std::{
+    fmt::Debug,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command as Process, Stdio},
+    sync::Arc,
+    thread,
+};
+
+// This struct records where a line of extracted text came from in the original file.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Origin {
+    pub line_number: usize,
+
+    // The number of bytes and characters before the text on the original line
+    pub offset: usize,
+    pub column: usize,
+}
+
+// This struct is a piece of text extracted from a file, such as a cell in a notebook.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Section {
+    // A file name which determines the comment syntax of the text (e.g., `cell.py`)
+    pub syntax: PathBuf,
+
+    pub text: String,
+
+    // For cells in notebooks, the number of the cell, counting from 1
+    pub cell: Option<usize>,
+
+    // The origin of each line of the text. Lines without one are attributed to the last line which
+    // has one.
+    pub origins: Vec<Origin>,
+}
+
+// This trait is for extracting the text which labels are parsed from in files which aren't plain
+// text, or which have parts that shouldn't be scanned (e.g., the outputs in a notebook). Library
+// users can implement it to support other formats.
+pub trait Extractor: Debug + Send + Sync {
+    // This function determines whether the extractor handles a file, based on its path.
+    fn handles(&self, path: &Path) -> bool;
+
+    // This function extracts the text from the contents of a file.
+    fn extract(&self, path: &Path, contents: &[u8]) -> Result<Vec<Section>, String>;
+
+    // This function returns a string which identifies the extractor and its settings. It's used
+    // to invalidate caches.
+    fn name(&self) -> String;
+}
+
+// This function returns the built-in extractors.
+#[must_use]
+pub fn defaults() -> Vec<Arc<dyn Extractor>> {
+    vec![Arc::new(Notebook)]
+}
+
+// This function moves the directives parsed from a section to where they came from in the original
+// file. Directives from a cell of a notebook also record their position within the cell.
+pub fn relocate(directives: &mut Directives, path: &Path, section: &Section) {
+    let origin = |line_number: usize| {
+        let origins = &section.origins;
+        origins
+            .get(..line_number.min(origins.len()))
+            .and_then(<[Origin]>::last)
+            .copied()
+            .unwrap_or_default()
+    };
+
+    // The directives share one copy of the path.
+    let path = Arc::<Path>::from(path);
+    for directive in directives.labels_mut() {
+        let origin = origin(directive.line_number);
+        directive.cell = section.cell.map(|number| Cell {
+            number,
+            line_number: directive.line_number,
+        });
+        directive.path = path.clone();
+        directive.line_number = origin.line_number;
+        directive.column += origin.column;
+        directive.span = directive.span.start + origin.offset..directive.span.end + origin.offset;
+    }
+
+    for suppression in &mut directives.suppressions {
+        suppression.path = path.to_path_buf();
+        suppression.line_number = suppression
+            .line_number
+            .map(|line_number| origin(line_number).line_number);
+    }
+
+    for undecodable in &mut directives.undecodable {
+        *undecodable = path.to_path_buf();
+    }
+}
+
+// This extractor reads the cells of Jupyter notebooks, leaving out their outputs. Code cells use
+// the comment syntax of the notebook's language.
+#[derive(Debug)]
+pub struct Notebook;
+
+impl Notebook {
+    // This function returns the file extension for the notebook's language (e.g., `py`).
+    fn extension(notebook: &Value) -> String {
+        let metadata = &notebook["metadata"];
+        metadata["language_info"]["file_extension"]
+            .as_str()
+            .map(|extension| extension.trim_start_matches('.').to_owned())
+            .or_else(|| {
+                metadata["kernelspec"]["language"].as_str().map(|language| {
+                    match language.to_lowercase().as_str() {
+                        "python" => "py".to_owned(),
+                        "julia" => "jl".to_owned(),
+                        "javascript" => "js".to_owned(),
+                        "typescript" => "ts".to_owned(),
+                        language => language.to_owned(),
+                    }
+                })
+            })
+            .unwrap_or_else(|| "py".to_owned())
+    }
+}
+
+impl Extractor for Notebook {
+    fn handles(&self, path: &Path) -> bool {
+        path.extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("ipynb"))
+    }
+
+    fn extract(&self, path: &Path, contents: &[u8]) -> Result<Vec<Section>, String> {
+        let notebook = serde_json::from_slice::<Value>(contents).map_err(|error| {
+            format!(
+                "Unable to read notebook {}: {error}",
+                path.to_string_lossy()
+            )
+        })?;
+        let extension = Self::extension(&notebook);
+
+        // Each line of a cell is found in the file by searching for it as a JSON string, starting
+        // from the line after the previous one. Notebooks are usually saved with one line of a cell
+        // per line of the file, so that's where the labels are reported. Columns don't account for
+        // escape sequences before a label (e.g., `\"`).
+        let raw = String::from_utf8_lossy(contents);
+        let raw_lines = raw.lines().collect::<Vec<_>>();
+        let mut cursor = 0;
+        let mut origin = Origin::default();
+
+        let mut sections = Vec::new();
+        for (index, cell) in notebook["cells"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .enumerate()
+        {
+            let syntax = match cell["cell_type"].as_str() {
+                Some("code") => format!("cell.{extension}"),
+                Some("markdown") => "cell.md".to_owned(),
+                _ => "cell.txt".to_owned(),
+            };
+            let text = match &cell["source"] {
+                Value::String(source) => source.clone(),
+                Value::Array(source) => source.iter().filter_map(Value::as_str).collect(),
+                _ => continue,
+            };
+
+            let mut origins = Vec::new();
+            for line in text.split_inclusive('\n') {
+                // Serializing a string can't fail, so the `unwrap` is safe.
+                let encoded = serde_json::to_string(line).unwrap();
+                if let Some((index, position)) =
+                    raw_lines
+                        .iter()
+                        .enumerate()
+                        .skip(cursor)
+                        .find_map(|(index, raw_line)| {
+                            raw_line
+                                .find(&encoded)
+                                .map(|position| (index, position + 1))
+                        })
+                {
+                    cursor = index + 1;
+                    origin = Origin {
+                        line_number: index + 1,
+                        offset: position,
+                        column: raw_lines[index][..position].chars().count(),
+                    };
+                }
+                origins.push(origin);
+            }
+
+            sections.push(Section {
+                syntax: PathBuf::from(syntax),
+                text,
+                cell: Some(index + 1),
+                origins,
+            });
+        }
+
+        Ok(sections)
+    }
+
+    fn name(&self) -> String {
+        "notebook".to_owned()
+    }
+}
+
+// This extractor runs a command to convert files to text (e.g., `pdftotext` for PDF files). The
+// contents of a file are written to the command's standard input, and the text is read from its
+// standard output.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Command {
+    // The files which are converted, as globs relative to the working directory
+    pub paths: Vec<PathGlob>,
+
+    // The program to run, followed by its arguments
+    pub command: Vec<String>,
+}
+
+impl Extractor for Command {
+    fn handles(&self, path: &Path) -> bool {
+        let path = walk::relative(path);
+        self.paths.iter().any(|PathGlob(glob)| glob.is_match(&path))
+    }
+
+    fn extract(&self, path: &Path, contents: &[u8]) -> Result<Vec<Section>, String> {
+        let failed = |error: &dyn std::fmt::Display| {
+            format!(
+                "Unable to extract the text from {} with `{}`: {error}",
+                path.to_string_lossy(),
+                self.command.join(" "),
+            )
+        };
+        let (program, args) = self
+            .command
+            .split_first()
+            .ok_or_else(|| failed(&"The command is empty."))?;
+
+        let mut child = Process::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|error| failed(&error))?;
+
+        // The input is written from another thread so the command doesn't block on a full output
+        // pipe. The streams were requested above, so the `unwrap` is safe.
+        let mut stdin = child.stdin.take().unwrap();
+        let input = contents.to_owned();
+        let writer = thread::spawn(move || stdin.write_all(&input));
+        let output = child.wait_with_output().map_err(|error| failed(&error))?;
+        if !output.status.success() {
+            return Err(failed(&String::from_utf8_lossy(&output.stderr).trim()));
+        }
+
+        // Commands may exit without reading all their input, so errors from writing it are only
+        // reported if the command failed. The thread doesn't panic, so the `unwrap` is safe.
+        let _ = writer.join().unwrap();
+
+        let text = String::from_utf8_lossy(&output.stdout).into_owned();
+        let origins = (1..=text.lines().count())
+            .map(|line_number| Origin {
+                line_number,
+                ..Origin::default()
+            })
+            .collect();
+        Ok(vec![Section {
+            syntax: PathBuf::from("extracted.txt"),
+            text,
+            cell: None,
+            origins,
+        }])
+    }
+
+    fn name(&self) -> String {
+        format!(
+            "{} {}",
+            self.paths
+                .iter()
+                .map(|PathGlob(glob)| glob.glob().glob())
+                .collect::<Vec<_>>()
+                .join(","),
+            self.command.join(" "),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::{Cell, Directive, Directives, Type},
+        extractors::{Extractor, Notebook, Origin, Section, relocate},
+    };
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn notebook_cells() {
+        let notebook = r#"{
+ "cells": [
+  {
+   "cell_type": "markdown",
+   "source": [
+    "Intro\n",
+    "More"
+   ]
+  },
+  {
+   "cell_type": "code",
+   "outputs": [{"text": ["ignored\n"]}],
+   "source": [
+    "x = 1\n",
+    "print(x)"
+   ]
+  }
+ ],
+ "metadata": {"language_info": {"file_extension": ".py"}}
+}"#;
+
+        let sections = Notebook
+            .extract(Path::new("a.ipynb"), notebook.as_bytes())
+            .unwrap();
+
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[0].syntax, PathBuf::from("cell.md"));
+        assert_eq!(sections[0].text, "Intro\nMore");
+        assert_eq!(sections[1].syntax, PathBuf::from("cell.py"));
+        assert_eq!(sections[1].text, "x = 1\nprint(x)");
+        assert_eq!(sections[1].cell, Some(2));
+        assert_eq!(
+            sections[1].origins,
+            vec![
+                Origin {
+                    line_number: 14,
+                    offset: 5,
+                    column: 5,
+                },
+                Origin {
+                    line_number: 15,
+                    offset: 5,
+                    column: 5,
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn notebook_invalid() {
+        assert!(Notebook.extract(Path::new("a.ipynb"), b"nope").is_err());
+    }
+
+    #[test]
+    fn relocate_directives() {
+        let mut directives = Directives {
+            refs: vec![Directive {
+                column: 3,
+                span: 2..11,
+                ..Directive::new(Type::Ref, "foo", "cell.py", 2)
+            }],
+            ..Directives::default()
+        };
+        let section = Section {
+            syntax: PathBuf::from("cell.py"),
+            text: String::new(),
+            cell: Some(3),
+            origins: vec![
+                Origin {
+                    line_number: 4,
+                    offset: 5,
+                    column: 5,
+                },
+                Origin {
+                    line_number: 7,
+                    offset: 6,
+                    column: 4,
+                },
+            ],
+        };
+
+        relocate(&mut directives, Path::new("./a.ipynb"), &section);
+
+        let r#ref = &directives.refs[0];
+        assert_eq!(*r#ref.path, PathBuf::from("./a.ipynb"));
+        assert_eq!(r#ref.line_number, 7);
+        assert_eq!(r#ref.column, 7);
+        assert_eq!(r#ref.span, 8..17);
+        assert_eq!(
+            r#ref.cell,
+            Some(Cell {
+                number: 3,
+                line_number: 2,
+            }),
+        );
+        assert_eq!(
+            r#ref.to_string(),
+            "[?ref:foo] @ ./a.ipynb:7:7 (cell 3, line 2)".replace('?', ""),
+        );
+    }
+}
@@ -0,0 +1,126 @@
+use std::sync::Mutex;
+use tagref::violation::{Kind, Severity, Violation};
+
+// These are the exit codes, so scripts can tell the different kinds of failures apart without
+// parsing the output. If the violations are of several kinds with different codes, the generic
+// code is used.
+pub const VIOLATIONS: i32 = 1;
+pub const DANGLING_REFS: i32 = 2;
+pub const DUPLICATE_TAGS: i32 = 3;
+pub const MISSING_PATHS: i32 = 4;
+pub const ERROR: i32 = 5;
+pub const TIMEOUT: i32 = 6;
+
+// Commands hold this lock while they rewrite or move files, and the timeout waits for it before
+// exiting. So a command which runs out of time before rewriting any files exits without touching
+// them, and one which runs out of time while rewriting them finishes first.
+pub static REWRITING: Mutex<()> = Mutex::new(());
+
+// This struct represents a reason for the program to fail, along with the exit code to report.
+pub struct Failure {
+    pub code: i32,
+    pub message: String,
+}
+
+// Errors which aren't about violations are I/O or configuration errors, such as an unreadable file
+// or an invalid regular expression.
+impl From<String> for Failure {
+    fn from(message: String) -> Self {
+        Self {
+            code: ERROR,
+            message,
+        }
+    }
+}
+
+// This function determines the exit code for a failed check from the kinds of the errors found.
+pub fn code(violations: &[Violation]) -> i32 {
+    let mut codes = violations
+        .iter()
+        .filter(|violation| violation.severity == Severity::Error)
+        .map(|violation| match violation.kind {
+            Kind::DanglingRef => DANGLING_REFS,
+            Kind::DuplicateTag => DUPLICATE_TAGS,
+            Kind::InvalidFileRef | Kind::InvalidDirRef => MISSING_PATHS,
+            Kind::UnreachableUrl
+            | Kind::InvalidTagName
+            | Kind::ForbiddenRef
+            | Kind::UnusedTag
+            | Kind::InvalidCustomLabel
+            | Kind::UndecodableFile
+            | Kind::NonconformingTagName
+            | Kind::DeprecatedAlias
+            | Kind::StalePin
+            | Kind::ExpiredLabel
+            | Kind::ClosedIssue
+            | Kind::TooFewRefs
+            | Kind::UnpairedTag
+            | Kind::UndocumentedTag
+            | Kind::ExternalRule => VIOLATIONS,
+        });
+
+    match codes.next() {
+        Some(first) if codes.all(|code| code == first) => first,
+        _ => VIOLATIONS,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::failure::{DANGLING_REFS, MISSING_PATHS, VIOLATIONS, code};
+    use tagref::violation::{Kind, Severity, Violation};
+
+    fn violation(kind: Kind, severity: Severity) -> Violation {
+        Violation {
+            kind,
+            severity,
+            message: String::new(),
+            directives: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn code_single_kind() {
+        assert_eq!(
+            code(&[
+                violation(Kind::DanglingRef, Severity::Error),
+                violation(Kind::DanglingRef, Severity::Error),
+            ]),
+            DANGLING_REFS,
+        );
+    }
+
+    #[test]
+    fn code_file_and_dir_refs() {
+        assert_eq!(
+            code(&[
+                violation(Kind::InvalidFileRef, Severity::Error),
+                violation(Kind::InvalidDirRef, Severity::Error),
+            ]),
+            MISSING_PATHS,
+        );
+    }
+
+    #[test]
+    fn code_mixed_kinds() {
+        assert_eq!(
+            code(&[
+                violation(Kind::DanglingRef, Severity::Error),
+                violation(Kind::DuplicateTag, Severity::Error),
+            ]),
+            VIOLATIONS,
+        );
+    }
+
+    #[test]
+    fn code_ignores_warnings() {
+        assert_eq!(
+            code(&[
+                violation(Kind::DanglingRef, Severity::Error),
+                violation(Kind::DuplicateTag, Severity::Warning),
+            ]),
+            DANGLING_REFS,
+        );
+    }
+}
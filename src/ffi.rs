@@ -0,0 +1,142 @@
+// These are the C-compatible functions declared in `bindings/tagref.h`, for tools written in C or
+// C++. Strings are UTF-8 and null-terminated. The responses are JSON, as described in `bindings`,
+// and must be freed with `tagref_free_string`.
+
+use crate::bindings;
+use std::{
+    borrow::Cow,
+    ffi::{CStr, CString, c_char},
+    path::Path,
+};
+
+// This function reads a string from the caller, or returns `None` for a null pointer.
+//
+// # Safety
+//
+// The pointer must be null or point to a null-terminated string which outlives the result.
+unsafe fn read<'a>(string: *const c_char) -> Option<Cow<'a, str>> {
+    // The caller guarantees that the pointer is valid if it isn't null, so the `unsafe` block is
+    // sound.
+    (!string.is_null()).then(|| unsafe { CStr::from_ptr(string) }.to_string_lossy())
+}
+
+// This function hands a response to the caller, who owns it until it's freed.
+fn write(response: &str) -> *mut c_char {
+    // JSON escapes null characters, so the `unwrap` is safe.
+    CString::new(response).unwrap().into_raw()
+}
+
+// This function checks the files in a directory. The options are a JSON object (e.g.,
+// `{"deny_unused": true}`), or null for the defaults.
+//
+// # Safety
+//
+// The arguments must be null or point to null-terminated strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tagref_check_directory(
+    directory: *const c_char,
+    options: *const c_char,
+) -> *mut c_char {
+    // The caller guarantees that the arguments are valid, so the `unsafe` blocks are sound.
+    let Some(directory) = (unsafe { read(directory) }) else {
+        return write(r#"{"error":"No directory was given."}"#);
+    };
+    let options = unsafe { read(options) }.unwrap_or(Cow::Borrowed("{}"));
+    write(&bindings::check_directory(Path::new(&*directory), &options))
+}
+
+// This function checks files whose contents are given, rather than read from disk. The request is
+// a JSON object with the `files` and the `options`.
+//
+// # Safety
+//
+// The request must be null or point to a null-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tagref_check_files(request: *const c_char) -> *mut c_char {
+    // The caller guarantees that the request is valid, so the `unsafe` block is sound.
+    let request = unsafe { read(request) }.unwrap_or_default();
+    write(&bindings::check(&request))
+}
+
+// This function frees a response. Freeing a null pointer does nothing.
+//
+// # Safety
+//
+// The response must have been returned by this library, and not freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tagref_free_string(response: *mut c_char) {
+    if !response.is_null() {
+        // The caller guarantees that the response came from `CString::into_raw`, so the `unsafe`
+        // block is sound.
+        drop(unsafe { CString::from_raw(response) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ffi::{tagref_check_directory, tagref_check_files, tagref_free_string};
+    use serde_json::Value;
+    use std::{
+        env,
+        ffi::{CStr, CString, c_char},
+        fs, process, ptr,
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    // This function reads a response and frees it.
+    fn take(response: *mut c_char) -> Value {
+        let value = serde_json::from_slice(unsafe { CStr::from_ptr(response) }.to_bytes()).unwrap();
+        unsafe { tagref_free_string(response) };
+        value
+    }
+
+    #[test]
+    fn check_directory() {
+        let directory = env::temp_dir().join(format!(
+            "tagref-ffi-{}-{}",
+            process::id(),
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos(),
+        ));
+        fs::create_dir_all(directory.join("lib")).unwrap();
+        fs::write(
+            directory.join("lib/a.rs"),
+            "// [?tag:foo]\n// [?file:lib/b.rs]\n".replace('?', ""),
+        )
+        .unwrap();
+        fs::write(
+            directory.join("lib/b.rs"),
+            "// [?ref:foo] [?ref:bar]\n".replace('?', ""),
+        )
+        .unwrap();
+
+        let path = CString::new(directory.to_string_lossy().as_bytes()).unwrap();
+        let report = take(unsafe { tagref_check_directory(path.as_ptr(), ptr::null()) });
+        fs::remove_dir_all(&directory).unwrap();
+
+        let violations = report["violations"].as_array().unwrap();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0]["kind"], "dangling_ref");
+    }
+
+    #[test]
+    fn check_directory_null() {
+        let report = take(unsafe { tagref_check_directory(ptr::null(), ptr::null()) });
+
+        assert!(report["error"].is_string());
+    }
+
+    #[test]
+    fn check_files() {
+        let request = CString::new(
+            r#"{"files": [{"path": "a.rs", "contents": "// [?ref:foo]"}]}"#.replace('?', ""),
+        )
+        .unwrap();
+
+        let report = take(unsafe { tagref_check_files(request.as_ptr()) });
+
+        assert_eq!(report["violations"][0]["kind"], "dangling_ref");
+    }
+}
@@ -1,24 +1,244 @@
-use crate::directive::Directive;
-use std::fs::metadata;
-
-// This function checks that file references actually point to files. It returns a vector of error
-// strings.
-pub fn check(refs: &[Directive]) -> Vec<String> {
-    let mut errors = Vec::<String>::new();
-
-    for file in refs {
-        match metadata(&file.label) {
-            Ok(metadata) => {
-                if !metadata.is_file() {
-                    errors.push(format!("{file} does not point to a file."));
+use crate::{
+    anchors,
+    archives::{self, Archives},
+    count::count,
+    directive::Directive,
+    git::Tree,
+    violation::{Kind, Severity, Violation},
+    walk,
+};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+// This function splits a file reference into the path and the optional line number or range of
+// line numbers after it, as in `src/main.rs:42` or `src/main.rs:10-20`. A single line number is
+// returned as a range containing just that line. An anchor after the path, as in
+// `docs/design.md#retries`, is left out, and there are no line numbers in that case.
+pub(crate) fn split_location(label: &str) -> (&str, Option<RangeInclusive<usize>>) {
+    if let (path, Some(_)) = anchors::split(label) {
+        return (path, None);
+    }
+
+    let Some((path, suffix)) = label.rsplit_once(':') else {
+        return (label, None);
+    };
+
+    let (start, end) = suffix.split_once('-').unwrap_or((suffix, suffix));
+    match (start.trim().parse::<usize>(), end.trim().parse::<usize>()) {
+        (Ok(start), Ok(end)) if !path.is_empty() => (path.trim_end(), Some(start..=end)),
+        _ => (label, None),
+    }
+}
+
+// This function counts the lines in a file, reading it from the archive containing it or the
+// given Git tree if there is one. A trailing newline doesn't start a new line.
+fn count_lines(path: &Path, tree: Option<&Tree>, archives: &mut Archives) -> Result<usize, String> {
+    let lines = if let Some((archive, inner)) = archives::split(path) {
+        // The file was found in the archive before its lines are counted, so the `unwrap` is safe.
+        lines(archives.open(&archive)?.contents(&inner).unwrap())
+    } else if let Some(tree) = tree {
+        lines(tree.read(path)?.as_slice())
+    } else {
+        File::open(path).and_then(|file| lines(BufReader::new(file)))
+    };
+    lines.map_err(|error| error.to_string())
+}
+
+// This function counts the lines read from a reader.
+fn lines(reader: impl BufRead) -> io::Result<usize> {
+    let mut lines = 0;
+    for line in reader.split(b'\n') {
+        line?;
+        lines += 1;
+    }
+    Ok(lines)
+}
+
+// This function checks that file references actually point to files. If a reference includes line
+// numbers, it also checks that the file has at least that many lines. If it includes an anchor, it
+// also checks that the file declares that anchor. Paths are resolved as described in
+// `walk::resolve`. If `strict_case` is set, the paths must have the same case as on disk. Paths
+// inside archives are checked against the files in the archives. If a Git tree is given, the paths
+// are checked against it rather than the filesystem. It returns a vector of violations.
+#[allow(clippy::too_many_lines)]
+pub fn check(
+    refs: &[Directive],
+    anchors: &[Directive],
+    relative_paths: bool,
+    roots: &[PathBuf],
+    strict_case: bool,
+    tree: Option<&Tree>,
+) -> Vec<Violation> {
+    let mut errors = Vec::<Violation>::new();
+    let anchors = anchors::by_path(anchors);
+
+    // Several references may point to different lines of the same file, so only count the lines
+    // in each file once.
+    let mut line_counts = HashMap::<PathBuf, usize>::new();
+    let mut archives = Archives::new(tree);
+
+    // Look up the files on disk all at once, since there may be many of them.
+    let paths = refs
+        .iter()
+        .map(|file| {
+            walk::resolve(
+                split_location(&file.label).0,
+                &file.path,
+                relative_paths,
+                roots,
+            )
+        })
+        .collect::<Vec<_>>();
+    let targets = if tree.is_none() {
+        walk::Targets::look_up(
+            paths
+                .iter()
+                .filter(|path| archives::split(path).is_none())
+                .cloned(),
+            strict_case,
+        )
+    } else {
+        walk::Targets::default()
+    };
+
+    for (file, path) in refs.iter().zip(paths) {
+        let (_, lines) = split_location(&file.label);
+        let mut push_error = |message| {
+            errors.push(Violation {
+                kind: Kind::InvalidFileRef,
+                severity: Severity::Error,
+                message,
+                directives: vec![file.clone()],
+                suggestions: Vec::new(),
+            });
+        };
+
+        // Paths in archives and Git trees which differ only by case are different paths.
+        if let Some((archive, inner)) = archives::split(&path) {
+            match archives.open(&archive) {
+                Ok(archive) if archive.contents(&inner).is_some() => {}
+                Ok(_) => {
+                    push_error(format!("{file} does not point to a file."));
+                    continue;
+                }
+                Err(error) => {
+                    push_error(format!("Error when validating {file}: {error}"));
+                    continue;
+                }
+            }
+        } else if let Some(tree) = tree {
+            if !tree.is_file(&path) {
+                if tree.is_untracked(&path) {
+                    push_error(format!(
+                        "{file} points to a file which isn't tracked by Git."
+                    ));
+                } else {
+                    push_error(format!("{file} does not point to a file."));
+                }
+                continue;
+            }
+        } else {
+            match targets.metadata(&path) {
+                Ok(metadata) => {
+                    if !metadata.is_file() {
+                        push_error(format!("{file} does not point to a file."));
+                        continue;
+                    }
+                    if strict_case && let Err(error) = targets.check_case(&path) {
+                        push_error(format!("{file} has the wrong case: {error}."));
+                        continue;
+                    }
+                }
+                Err(error) => {
+                    push_error(format!("Error when validating {file}: {error}"));
+                    continue;
                 }
             }
-            Err(error) => {
-                let error_string = error.to_string();
-                errors.push(format!("Error when validating {file}: {error_string}"));
+        }
+
+        if let (_, Some(anchor)) = anchors::split(&file.label) {
+            if !anchors
+                .get(&path)
+                .is_some_and(|labels| labels.contains(anchor))
+            {
+                push_error(format!("{file} points to an anchor which doesn't exist."));
             }
+            continue;
+        }
+
+        let Some(lines) = lines else {
+            continue;
+        };
+
+        if *lines.start() == 0 || lines.start() > lines.end() {
+            push_error(format!("{file} has an invalid line range."));
+            continue;
+        }
+
+        let line_count = if let Some(line_count) = line_counts.get(&path) {
+            *line_count
+        } else {
+            match count_lines(&path, tree, &mut archives) {
+                Ok(line_count) => *line_counts.entry(path).or_insert(line_count),
+                Err(error) => {
+                    push_error(format!("Error when validating {file}: {error}"));
+                    continue;
+                }
+            }
+        };
+
+        if *lines.end() > line_count {
+            push_error(format!(
+                "{file} points past the end of the file, which has {}.",
+                count(line_count, "line"),
+            ));
         }
     }
 
     errors
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::file_references::split_location;
+
+    #[test]
+    fn split_location_none() {
+        assert_eq!(split_location("src/main.rs"), ("src/main.rs", None));
+    }
+
+    #[test]
+    fn split_location_line() {
+        assert_eq!(
+            split_location("src/main.rs:42"),
+            ("src/main.rs", Some(42..=42))
+        );
+    }
+
+    #[test]
+    fn split_location_range() {
+        assert_eq!(
+            split_location("src/main.rs:10-20"),
+            ("src/main.rs", Some(10..=20))
+        );
+    }
+
+    #[test]
+    fn split_location_anchor() {
+        assert_eq!(
+            split_location("docs/design.md#retries"),
+            ("docs/design.md", None)
+        );
+    }
+
+    #[test]
+    fn split_location_not_a_number() {
+        assert_eq!(split_location("C:foo"), ("C:foo", None));
+        assert_eq!(split_location(":42"), (":42", None));
+    }
+}
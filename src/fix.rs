@@ -0,0 +1,290 @@
+use crate::{
+    dir_references::is_glob,
+    directive::{Directive, Type},
+    file_references::split_location,
+    index::Index,
+    violation::{Kind, Violation},
+    walk,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    ffi::OsString,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+// This struct describes a suggested replacement for the label of a broken file or directory
+// reference, along with all the references which have that label.
+#[derive(Clone, Debug)]
+pub struct Fix {
+    pub r#type: Type,
+    pub old: String,
+    pub new: String,
+    pub directives: Vec<Directive>,
+}
+
+// This struct holds the files and directories in the tree, organized by name, so broken references
+// can be matched with their new locations.
+#[derive(Clone, Debug, Default)]
+pub struct Candidates {
+    files: HashMap<OsString, BTreeSet<PathBuf>>,
+    dirs: HashMap<OsString, BTreeSet<PathBuf>>,
+}
+
+impl Candidates {
+    // This function organizes the given file paths, along with the directories containing them, by
+    // name. Leading `./` components are dropped so the paths look like the ones in labels.
+    pub fn new<I: IntoIterator<Item = PathBuf>>(file_paths: I) -> Self {
+        let mut candidates = Self::default();
+        for file_path in file_paths {
+            let file_path = file_path
+                .components()
+                .skip_while(|component| *component == Component::CurDir)
+                .collect::<PathBuf>();
+
+            for (index, path) in file_path.ancestors().enumerate() {
+                let Some(name) = path.file_name() else {
+                    continue;
+                };
+                let map = if index == 0 {
+                    &mut candidates.files
+                } else {
+                    &mut candidates.dirs
+                };
+                map.entry(name.to_owned())
+                    .or_default()
+                    .insert(path.to_owned());
+            }
+        }
+        candidates
+    }
+
+    // This function finds all the files and directories in the given paths. Files larger than the
+    // limit for scanning are included, since references can still point to them.
+    pub fn collect(paths: &[PathBuf], options: &walk::Options) -> Result<Self, String> {
+        let file_paths = Arc::new(Mutex::new(Vec::new()));
+        let file_paths_clone = file_paths.clone();
+        let options = walk::Options {
+            max_filesize: None,
+            ..options.clone()
+        };
+        walk::walk(paths, &options, move |file_path, _| {
+            // The `unwrap` is safe assuming no poisoning.
+            file_paths_clone.lock().unwrap().push(file_path.to_owned());
+        })?;
+
+        // The `unwrap` is safe assuming no poisoning.
+        Ok(Self::new(file_paths.lock().unwrap().drain(..)))
+    }
+
+    // This function returns the only file or directory with the same name as the given path, if
+    // there is exactly one.
+    fn find(&self, r#type: &Type, path: &Path) -> Option<&PathBuf> {
+        let map = if *r#type == Type::Dir {
+            &self.dirs
+        } else {
+            &self.files
+        };
+        let matches = map.get(path.file_name()?)?;
+        if matches.len() == 1 {
+            matches.iter().next()
+        } else {
+            None
+        }
+    }
+}
+
+// This function computes the new label for a broken reference given where the file or directory
+// is now. Any line numbers after the path are kept.
+pub(crate) fn replacement(r#type: &Type, label: &str, new_path: &Path) -> String {
+    let suffix = if *r#type == Type::File {
+        let (path, _) = split_location(label);
+        &label[path.len()..]
+    } else {
+        ""
+    };
+    format!("{}{suffix}", new_path.to_string_lossy().replace('\\', "/"))
+}
+
+// This function finds the file and directory references which don't point to a file or directory,
+// respectively, and suggests fixes for the ones whose target can be found unambiguously by name.
+// It returns the fixes, along with the broken references which couldn't be fixed.
+#[must_use]
+pub fn suggest(index: &Index, candidates: &Candidates) -> (Vec<Fix>, Vec<Directive>) {
+    // Group the broken references by label and root. If paths starting with `./` or `../` are
+    // relative to the file containing them, the same label can point to different paths from
+    // different files, so replacing it everywhere could break other references. Those are left to
+    // be fixed by hand.
+    let mut broken = BTreeMap::<(Type, String, Option<PathBuf>), Vec<Directive>>::new();
+    let mut unfixable = Vec::new();
+    let mut add = |r#type: Type, path: &str, directive: &Directive| {
+        if index.relative_paths && walk::is_relative(path) {
+            unfixable.push(directive.clone());
+        } else {
+            let root = walk::root(&directive.path, &index.roots).map(walk::normalize);
+            broken
+                .entry((r#type, directive.label.clone(), root))
+                .or_default()
+                .push(directive.clone());
+        }
+    };
+    for file in &index.files {
+        let (path, _) = split_location(&file.label);
+        if !walk::target_metadata(&walk::resolve(
+            path,
+            &file.path,
+            index.relative_paths,
+            &index.roots,
+        ))
+        .is_ok_and(|metadata| metadata.is_file())
+        {
+            add(Type::File, path, file);
+        }
+    }
+    // Globs don't name a particular directory, so they can't be fixed this way.
+    for dir in index.dirs.iter().filter(|dir| !is_glob(&dir.label)) {
+        if !walk::target_metadata(&walk::resolve(
+            &dir.label,
+            &dir.path,
+            index.relative_paths,
+            &index.roots,
+        ))
+        .is_ok_and(|metadata| metadata.is_dir())
+        {
+            add(Type::Dir, &dir.label, dir);
+        }
+    }
+
+    // Look for the new location of each one.
+    let mut fixes = Vec::new();
+    for ((r#type, label, root), directives) in broken {
+        let path = if r#type == Type::File {
+            split_location(&label).0
+        } else {
+            label.as_str()
+        };
+        // Within a root, the new path has to be relative to it.
+        let new_path = candidates
+            .find(&r#type, Path::new(path))
+            .and_then(|new_path| match &root {
+                Some(root) => new_path.strip_prefix(root).ok(),
+                None => Some(new_path.as_path()),
+            });
+        match new_path {
+            Some(new_path) => fixes.push(Fix {
+                new: replacement(&r#type, &label, new_path),
+                r#type,
+                old: label,
+                directives,
+            }),
+            None => unfixable.extend(directives),
+        }
+    }
+
+    (fixes, unfixable)
+}
+
+// This function suggests fixes for the dangling tag references among the violations which have
+// exactly one suggestion, i.e., whose closest tag is unambiguous.
+#[must_use]
+pub fn suggest_refs(violations: &[Violation]) -> Vec<Fix> {
+    let mut fixes = BTreeMap::<(String, String), Vec<Directive>>::new();
+    for violation in violations {
+        if let (Kind::DanglingRef | Kind::DeprecatedAlias, [suggestion], [r#ref]) = (
+            violation.kind,
+            violation.suggestions.as_slice(),
+            violation.directives.as_slice(),
+        ) {
+            fixes
+                .entry((r#ref.label.clone(), suggestion.clone()))
+                .or_default()
+                .push(r#ref.clone());
+        }
+    }
+
+    fixes
+        .into_iter()
+        .map(|((old, new), directives)| Fix {
+            r#type: Type::Ref,
+            old,
+            new,
+            directives,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::{Directive, Type},
+        fix::{Candidates, replacement, suggest_refs},
+        violation::{Kind, Severity, Violation},
+    };
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn candidates_find_unique() {
+        let candidates = Candidates::new([
+            PathBuf::from("./src/foo.rs"),
+            PathBuf::from("./src/bar/baz.rs"),
+        ]);
+
+        assert_eq!(
+            candidates.find(&Type::File, Path::new("old/foo.rs")),
+            Some(&PathBuf::from("src/foo.rs")),
+        );
+        assert_eq!(
+            candidates.find(&Type::Dir, Path::new("old/bar")),
+            Some(&PathBuf::from("src/bar")),
+        );
+        assert_eq!(candidates.find(&Type::File, Path::new("qux.rs")), None);
+    }
+
+    #[test]
+    fn candidates_find_ambiguous() {
+        let candidates = Candidates::new([
+            PathBuf::from("./src/mod.rs"),
+            PathBuf::from("./src/bar/mod.rs"),
+        ]);
+
+        assert_eq!(candidates.find(&Type::File, Path::new("mod.rs")), None);
+    }
+
+    #[test]
+    fn replacement_keeps_line_numbers() {
+        assert_eq!(
+            replacement(&Type::File, "old/foo.rs:10-20", Path::new("src/foo.rs")),
+            "src/foo.rs:10-20",
+        );
+        assert_eq!(
+            replacement(&Type::Dir, "old/bar", Path::new("src/bar")),
+            "src/bar",
+        );
+    }
+
+    #[test]
+    fn suggest_refs_unambiguous() {
+        let violation = |label: &str, suggestions: &[&str]| Violation {
+            kind: Kind::DanglingRef,
+            severity: Severity::Error,
+            message: String::new(),
+            directives: vec![Directive::new(Type::Ref, label, "./foo.rs", 1)],
+            suggestions: suggestions.iter().map(|&label| label.to_owned()).collect(),
+        };
+
+        let fixes = suggest_refs(&[
+            violation("fo", &["foo"]),
+            violation("fo", &["foo"]),
+            violation("ba", &["bar", "baz"]),
+            violation("qux", &[]),
+        ]);
+
+        assert_eq!(fixes.len(), 1);
+        assert_eq!(fixes[0].r#type, Type::Ref);
+        assert_eq!(
+            (fixes[0].old.as_str(), fixes[0].new.as_str()),
+            ("fo", "foo")
+        );
+        assert_eq!(fixes[0].directives.len(), 2);
+    }
+}
@@ -0,0 +1,18 @@
+use std::{collections::BTreeMap, path::Path};
+use tagref::directive::{Directive, Type};
+
+// This function builds a directive at the start of a line, without a description, attributes, or a
+// cell, like `Directive::new` does for the tests of the library, which can't be seen from here.
+pub fn directive(r#type: Type, label: &str, path: &str, line_number: usize) -> Directive {
+    Directive {
+        r#type,
+        label: label.to_owned(),
+        path: Path::new(path).into(),
+        line_number,
+        column: 1,
+        span: 0..0,
+        description: None,
+        attributes: BTreeMap::new(),
+        cell: None,
+    }
+}
@@ -0,0 +1,832 @@
+use crate::{
+    compact, github, gitlab, junit,
+    pretty::{self, Sources},
+    sarif,
+};
+use clap::ValueEnum;
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use tagref::{
+    context::Snippet,
+    count::count,
+    diff::Diff,
+    directive::Directive,
+    expiry::{self, Expiration},
+    git::ChangedTag,
+    hotspots::Hotspot,
+    index::Summary,
+    owners::Ownership,
+    rustdoc::Entry,
+    similar_tags::{Difference, Pair},
+    stats::{Group, Stats},
+    todos,
+    trend::Point,
+    violation::{Severity, Violation},
+    walk,
+};
+
+// This enum represents the supported output formats.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Text,
+    Json,
+    Sarif,
+    Junit,
+    Github,
+    Gitlab,
+    Csv,
+    Compact,
+}
+
+impl Format {
+    // This is the name of the format as it appears in error messages.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Json => "JSON",
+            Self::Sarif => "SARIF",
+            Self::Junit => "JUnit",
+            Self::Github => "GitHub",
+            Self::Gitlab => "GitLab",
+            Self::Csv => "CSV",
+            Self::Compact => "compact",
+        }
+    }
+
+    // This function returns the error for a command which doesn't support the format.
+    pub fn unsupported(self) -> String {
+        if self == Self::Csv {
+            "The CSV format is only supported by the `trend` command and the `stats` command with \
+             `--group-by`."
+                .to_owned()
+        } else if self == Self::Compact {
+            "The compact format is only supported by the `check` command and the commands which \
+             list labels."
+                .to_owned()
+        } else {
+            format!(
+                "The {} format is only supported by the `check` command.",
+                self.name(),
+            )
+        }
+    }
+}
+
+// This is the top-level JSON object emitted by `check`.
+#[derive(Serialize)]
+struct Report<'a> {
+    violations: &'a [Violation],
+
+    // The violations which were suppressed by comments
+    suppressed: &'a [Violation],
+
+    summary: &'a Summary,
+}
+
+// This function sorts directives for listing them (see `Directive::cmp_position`).
+fn sorted<'a, I: IntoIterator<Item = &'a Directive>>(directives: I) -> Vec<&'a Directive> {
+    let mut directives = directives.into_iter().collect::<Vec<_>>();
+    directives.sort_by(|x, y| x.cmp_position(y));
+    directives
+}
+
+// This function prints a list of directives in the given format, sorted by path, then line, then
+// label.
+pub fn print_directives<'a, I: IntoIterator<Item = &'a Directive>>(
+    format: Format,
+    directives: I,
+) -> Result<(), String> {
+    let directives = sorted(directives);
+    match format {
+        Format::Text => {
+            for directive in directives {
+                println!("{directive}");
+            }
+        }
+        Format::Json => {
+            // Serializing plain data to a string can't fail, so the `unwrap` is safe.
+            println!("{}", serde_json::to_string(&directives).unwrap());
+        }
+        Format::Compact => {
+            for directive in directives {
+                println!("{}", compact::directive(directive));
+            }
+        }
+        Format::Sarif | Format::Junit | Format::Github | Format::Gitlab | Format::Csv => {
+            return Err(format.unsupported());
+        }
+    }
+
+    Ok(())
+}
+
+// This function prints a list of directives along with their descriptions, if they have any. Only
+// the text format is affected, since the other formats always include the descriptions.
+pub fn print_directives_long<'a, I: IntoIterator<Item = &'a Directive>>(
+    format: Format,
+    directives: I,
+) -> Result<(), String> {
+    if format != Format::Text {
+        return print_directives(format, directives);
+    }
+
+    for directive in sorted(directives) {
+        println!("{directive}");
+        if let Some(description) = &directive.description {
+            for line in description.lines() {
+                println!("    {line}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// This function prints directives along with the lines around them. In the text format, the line
+// numbers are followed by `:` for the lines containing the directives and `-` for the others, as
+// in `grep --context`.
+pub fn print_snippets(format: Format, snippets: &[Snippet]) -> Result<(), String> {
+    match format {
+        Format::Text => {
+            for (index, snippet) in snippets.iter().enumerate() {
+                if index > 0 {
+                    println!();
+                }
+                println!("{}", snippet.directive);
+                let width = snippet
+                    .context
+                    .last()
+                    .map_or(0, |line| line.line_number.to_string().len());
+                for line in &snippet.context {
+                    let separator = if line.line_number == snippet.directive.line_number {
+                        ':'
+                    } else {
+                        '-'
+                    };
+                    let output = format!("{:>width$}{separator} {}", line.line_number, line.text);
+                    println!("{}", output.trim_end());
+                }
+            }
+        }
+        Format::Json => {
+            // Serializing plain data to a string can't fail, so the `unwrap` is safe.
+            println!("{}", serde_json::to_string(snippets).unwrap());
+        }
+        Format::Sarif
+        | Format::Junit
+        | Format::Github
+        | Format::Gitlab
+        | Format::Csv
+        | Format::Compact => {
+            return Err(format.unsupported());
+        }
+    }
+
+    Ok(())
+}
+
+// This function prints aggregate metrics about the labels in the given format.
+pub fn print_stats(format: Format, stats: &Stats) -> Result<(), String> {
+    match format {
+        Format::Text => {
+            let summary = &stats.summary;
+            let rows = [
+                ("Tags", summary.tags),
+                ("Tag references", summary.tag_references),
+                ("File references", summary.file_references),
+                ("Files referenced", stats.files_referenced),
+                ("Directory references", summary.directory_references),
+                ("Directories referenced", stats.directories_referenced),
+                ("URL references", summary.url_references),
+                ("Custom labels", summary.custom_labels),
+                ("Unreferenced tags", stats.unused_tags.len()),
+                ("Files scanned", summary.files_scanned),
+            ];
+            for (name, value) in rows {
+                println!("{name:<24}{value:>8}");
+            }
+
+            println!("\nReferences per tag:");
+            for bucket in &stats.references_per_tag {
+                println!("{:>8}  {}", bucket.references, count(bucket.tags, "tag"));
+            }
+
+            println!("\nMost referenced tags:");
+            for tag in &stats.top_tags {
+                println!("{:>8}  {}", tag.references, tag.label);
+            }
+
+            println!("\nFiles with the most labels:");
+            for file in &stats.top_files {
+                println!("{:>8}  {}", file.labels, file.path.to_string_lossy());
+            }
+
+            if !stats.unused_tags.is_empty() {
+                println!("\nUnreferenced tags:");
+                for label in &stats.unused_tags {
+                    println!("          {label}");
+                }
+            }
+        }
+        Format::Json => {
+            // Serializing plain data to a string can't fail, so the `unwrap` is safe.
+            println!("{}", serde_json::to_string(stats).unwrap());
+        }
+        Format::Sarif
+        | Format::Junit
+        | Format::Github
+        | Format::Gitlab
+        | Format::Csv
+        | Format::Compact => {
+            return Err(format.unsupported());
+        }
+    }
+
+    Ok(())
+}
+
+// This function prints the tags which moved, changed, or were removed, each followed by the
+// references to it.
+pub fn print_changed_tags(format: Format, tags: &[ChangedTag]) -> Result<(), String> {
+    match format {
+        Format::Text => {
+            for (index, tag) in tags.iter().enumerate() {
+                if index > 0 {
+                    println!();
+                }
+                let before = &tag.before;
+                let location = format!("{}:{}", before.path.to_string_lossy(), before.line_number);
+                match (&tag.after, tag.moved(), tag.content_changed) {
+                    (None, _, _) => println!("{before} was removed."),
+                    (Some(after), true, true) => {
+                        println!("{after} moved from {location}, and its content changed.");
+                    }
+                    (Some(after), true, false) => println!("{after} moved from {location}."),
+                    (Some(after), false, _) => println!("{after} changed."),
+                }
+                for r#ref in &tag.refs {
+                    println!("    {ref}");
+                }
+            }
+        }
+        Format::Json => {
+            // Serializing plain data to a string can't fail, so the `unwrap` is safe.
+            println!("{}", serde_json::to_string(tags).unwrap());
+        }
+        Format::Sarif
+        | Format::Junit
+        | Format::Github
+        | Format::Gitlab
+        | Format::Csv
+        | Format::Compact => {
+            return Err(format.unsupported());
+        }
+    }
+
+    Ok(())
+}
+
+// This function prints how the labels changed between two revisions, in sections which are left
+// out if they're empty.
+pub fn print_diff(format: Format, diff: &Diff) -> Result<(), String> {
+    match format {
+        Format::Text => {
+            let renamed = diff
+                .tags_renamed
+                .iter()
+                .map(|rename| format!("{} -> {}", rename.before, rename.after))
+                .collect::<Vec<_>>();
+            let lines = |directives: &[Directive]| {
+                directives
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+            };
+            let messages = |violations: &[Violation]| {
+                violations
+                    .iter()
+                    .map(|violation| violation.message.trim_end().replace('\n', "\n    "))
+                    .collect::<Vec<_>>()
+            };
+            let sections = [
+                ("Tags added", lines(&diff.tags_added)),
+                ("Tags removed", lines(&diff.tags_removed)),
+                ("Tags renamed", renamed),
+                ("References added", lines(&diff.refs_added)),
+                ("References removed", lines(&diff.refs_removed)),
+                (
+                    "Violations introduced",
+                    messages(&diff.violations_introduced),
+                ),
+                ("Violations fixed", messages(&diff.violations_fixed)),
+            ];
+
+            let mut first = true;
+            for (heading, lines) in sections {
+                if lines.is_empty() {
+                    continue;
+                }
+                if !first {
+                    println!();
+                }
+                first = false;
+                println!("{heading}:");
+                for line in lines {
+                    println!("    {line}");
+                }
+            }
+            if first {
+                println!("No labels changed.");
+            }
+        }
+        Format::Json => {
+            // Serializing plain data to a string can't fail, so the `unwrap` is safe.
+            println!("{}", serde_json::to_string(diff).unwrap());
+        }
+        Format::Sarif
+        | Format::Junit
+        | Format::Github
+        | Format::Gitlab
+        | Format::Csv
+        | Format::Compact => {
+            return Err(format.unsupported());
+        }
+    }
+
+    Ok(())
+}
+
+// This function prints the pairs of similar tags in the given format.
+pub fn print_similar_tags(format: Format, pairs: &[Pair]) -> Result<(), String> {
+    match format {
+        Format::Text => {
+            for pair in pairs {
+                let difference = match pair.difference {
+                    Difference::Case => "differ only by case".to_owned(),
+                    Difference::Separators => {
+                        "differ only by case, hyphens, and underscores".to_owned()
+                    }
+                    Difference::Spelling => format!("are {} apart", count(pair.distance, "edit")),
+                };
+                println!("{} and {} {difference}.", pair.first, pair.second);
+            }
+        }
+        Format::Json => {
+            // Serializing plain data to a string can't fail, so the `unwrap` is safe.
+            println!("{}", serde_json::to_string(pairs).unwrap());
+        }
+        Format::Sarif
+        | Format::Junit
+        | Format::Github
+        | Format::Gitlab
+        | Format::Csv
+        | Format::Compact => {
+            return Err(format.unsupported());
+        }
+    }
+
+    Ok(())
+}
+
+// This function prints the owners of the tags and the owners of the files which reference them in
+// the given format.
+pub fn print_ownership<'a, I: IntoIterator<Item = &'a Ownership>>(
+    format: Format,
+    ownership: I,
+) -> Result<(), String> {
+    match format {
+        Format::Text => {
+            for ownership in ownership {
+                let owners = if ownership.owners.is_empty() {
+                    "no one".to_owned()
+                } else {
+                    ownership.owners.join(" ")
+                };
+                println!("{} is owned by {owners}.", ownership.tag);
+                if !ownership.referenced_by.is_empty() {
+                    println!(
+                        "    Referenced from files owned by {}.",
+                        ownership.referenced_by.join(" "),
+                    );
+                }
+            }
+        }
+        Format::Json => {
+            let ownership = ownership.into_iter().collect::<Vec<_>>();
+
+            // Serializing plain data to a string can't fail, so the `unwrap` is safe.
+            println!("{}", serde_json::to_string(&ownership).unwrap());
+        }
+        Format::Sarif
+        | Format::Junit
+        | Format::Github
+        | Format::Gitlab
+        | Format::Csv
+        | Format::Compact => {
+            return Err(format.unsupported());
+        }
+    }
+
+    Ok(())
+}
+
+// This function prints the tags in Rust doc comments and the items they document in the given
+// format. The text format is a Markdown table whose items are intra-doc links, so it can be
+// included in the documentation of the crate.
+pub fn print_rustdoc(format: Format, entries: &[Entry]) -> Result<(), String> {
+    match format {
+        Format::Text => {
+            if entries.is_empty() {
+                return Ok(());
+            }
+            println!("| Tag | Item | Location |");
+            println!("| --- | --- | --- |");
+            for entry in entries {
+                println!(
+                    "| `{}` | [`{}`] | {}:{} |",
+                    entry.tag.label.replace('|', "\\|"),
+                    entry.item,
+                    walk::relative(&entry.tag.path).to_string_lossy(),
+                    entry.tag.line_number,
+                );
+            }
+        }
+        Format::Json => {
+            // Serializing plain data to a string can't fail, so the `unwrap` is safe.
+            println!("{}", serde_json::to_string(entries).unwrap());
+        }
+        Format::Sarif
+        | Format::Junit
+        | Format::Github
+        | Format::Gitlab
+        | Format::Csv
+        | Format::Compact => {
+            return Err(format.unsupported());
+        }
+    }
+
+    Ok(())
+}
+
+// This function prints the most referenced tags in the given format.
+pub fn print_hotspots(format: Format, hotspots: &[Hotspot]) -> Result<(), String> {
+    match format {
+        Format::Text => {
+            for hotspot in hotspots {
+                println!("{} has {}:", hotspot.tag, count(hotspot.refs, "reference"));
+                for directory in &hotspot.directories {
+                    // The files are listed by name, since they're under the directory.
+                    let files = directory
+                        .files
+                        .iter()
+                        .map(|file| {
+                            let name = file.path.file_name().unwrap_or(file.path.as_os_str());
+                            format!("{} ({})", name.to_string_lossy(), file.refs)
+                        })
+                        .collect::<Vec<_>>();
+                    println!(
+                        "    {} ({}): {}",
+                        directory.path.to_string_lossy(),
+                        count(directory.refs, "reference"),
+                        files.join(", "),
+                    );
+                }
+            }
+        }
+        Format::Json => {
+            // Serializing plain data to a string can't fail, so the `unwrap` is safe.
+            println!("{}", serde_json::to_string(hotspots).unwrap());
+        }
+        Format::Sarif
+        | Format::Junit
+        | Format::Github
+        | Format::Gitlab
+        | Format::Csv
+        | Format::Compact => {
+            return Err(format.unsupported());
+        }
+    }
+
+    Ok(())
+}
+
+// This function prints the labels with expiry dates in the given format.
+pub fn print_expirations(format: Format, expirations: &[Expiration]) -> Result<(), String> {
+    match format {
+        Format::Text => {
+            for expiration in expirations {
+                let verb = if expiration.days_left < 0 {
+                    "expired"
+                } else {
+                    "expires"
+                };
+                println!(
+                    "{} {verb} on {} ({}).",
+                    expiration.directive,
+                    expiration.expires,
+                    expiry::relative(expiration.days_left),
+                );
+            }
+        }
+        Format::Json => {
+            // Serializing plain data to a string can't fail, so the `unwrap` is safe.
+            println!("{}", serde_json::to_string(expirations).unwrap());
+        }
+        Format::Sarif
+        | Format::Junit
+        | Format::Github
+        | Format::Gitlab
+        | Format::Csv
+        | Format::Compact => {
+            return Err(format.unsupported());
+        }
+    }
+
+    Ok(())
+}
+
+// This function prints groups of to-do items in the given format.
+pub fn print_todos(format: Format, groups: &[todos::Group]) -> Result<(), String> {
+    match format {
+        Format::Text => {
+            for group in groups {
+                println!("{}:", group.name);
+                for todo in &group.todos {
+                    if todo.text.is_empty() {
+                        println!("    {} {}", todo.marker, todo.directive);
+                    } else {
+                        println!("    {}: {} {}", todo.marker, todo.text, todo.directive);
+                    }
+                }
+            }
+        }
+        Format::Json => {
+            // Serializing plain data to a string can't fail, so the `unwrap` is safe.
+            println!("{}", serde_json::to_string(groups).unwrap());
+        }
+        Format::Sarif
+        | Format::Junit
+        | Format::Github
+        | Format::Gitlab
+        | Format::Csv
+        | Format::Compact => {
+            return Err(format.unsupported());
+        }
+    }
+
+    Ok(())
+}
+
+// This function quotes a field for CSV output if necessary.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+// This function prints the number of labels in each group in the given format. The heading
+// describes the groups, e.g., `Directory`.
+pub fn print_breakdown(format: Format, heading: &str, groups: &[Group]) -> Result<(), String> {
+    match format {
+        Format::Text => {
+            let width = groups
+                .iter()
+                .map(|group| group.name.chars().count())
+                .chain([heading.len()])
+                .max()
+                .unwrap_or_default()
+                + 2;
+            println!(
+                "{heading:<width$}{:>8}{:>8}{:>8}{:>8}",
+                "Tags", "Refs", "Labels", "Files",
+            );
+            for group in groups {
+                println!(
+                    "{:<width$}{:>8}{:>8}{:>8}{:>8}",
+                    group.name, group.tags, group.refs, group.labels, group.files,
+                );
+            }
+        }
+        Format::Json => {
+            // Serializing plain data to a string can't fail, so the `unwrap` is safe.
+            println!("{}", serde_json::to_string(groups).unwrap());
+        }
+        Format::Csv => {
+            println!("{},tags,refs,labels,files", heading.to_lowercase());
+            for group in groups {
+                println!(
+                    "{},{},{},{},{}",
+                    csv_field(&group.name),
+                    group.tags,
+                    group.refs,
+                    group.labels,
+                    group.files,
+                );
+            }
+        }
+        Format::Sarif | Format::Junit | Format::Github | Format::Gitlab | Format::Compact => {
+            return Err(format.unsupported());
+        }
+    }
+
+    Ok(())
+}
+
+// This function prints the numbers of labels and violations as of each commit in the given format.
+pub fn print_trend(format: Format, points: &[Point]) -> Result<(), String> {
+    match format {
+        Format::Text => {
+            println!(
+                "{:<14}{:<27}{:>8}{:>8}{:>8}{:>8}{:>8}{:>12}",
+                "Commit", "Date", "Tags", "Refs", "Files", "Dirs", "URLs", "Violations",
+            );
+            for point in points {
+                println!(
+                    "{:<14}{:<27}{:>8}{:>8}{:>8}{:>8}{:>8}{:>12}",
+                    point.commit.get(..12).unwrap_or(&point.commit),
+                    point.date,
+                    point.tags,
+                    point.tag_references,
+                    point.file_references,
+                    point.directory_references,
+                    point.url_references,
+                    point.violations,
+                );
+            }
+        }
+        Format::Json => {
+            // Serializing plain data to a string can't fail, so the `unwrap` is safe.
+            println!("{}", serde_json::to_string(points).unwrap());
+        }
+        Format::Csv => {
+            println!(
+                "commit,date,tags,tag_references,file_references,directory_references,\
+                 url_references,violations,errors"
+            );
+            for point in points {
+                println!(
+                    "{},{},{},{},{},{},{},{},{}",
+                    point.commit,
+                    point.date,
+                    point.tags,
+                    point.tag_references,
+                    point.file_references,
+                    point.directory_references,
+                    point.url_references,
+                    point.violations,
+                    point.errors,
+                );
+            }
+        }
+        Format::Sarif | Format::Junit | Format::Github | Format::Gitlab | Format::Compact => {
+            return Err(format.unsupported());
+        }
+    }
+
+    Ok(())
+}
+
+// This function describes what was validated in a sentence.
+fn summary_text(summary: &Summary) -> String {
+    // Custom labels are only mentioned if there are any, since most projects don't define them.
+    let mut counts = vec![
+        count(summary.tags, "tag"),
+        count(summary.tag_references, "tag reference"),
+        count(summary.file_references, "file reference"),
+        count(summary.directory_references, "directory reference"),
+        count(summary.url_references, "URL reference"),
+    ];
+    if summary.custom_labels > 0 {
+        counts.push(count(summary.custom_labels, "custom label"));
+    }
+
+    // The `unwrap` is safe because `counts` isn't empty.
+    let last = counts.pop().unwrap();
+    format!(
+        "{}, and {last} validated in {}.",
+        counts.join(", "),
+        count(summary.files_scanned, "file"),
+    )
+}
+
+// This function explains how the number of errors compares to the maximum number allowed.
+fn limit_text(errors: usize, max_violations: usize) -> String {
+    format!(
+        "{} found, which {} the limit of {max_violations}.",
+        count(errors, "violation"),
+        if errors > max_violations {
+            "exceeds"
+        } else {
+            "is within"
+        },
+    )
+}
+
+// This function summarizes the errors which caused a check to fail.
+fn failure_text(errors: usize, max_violations: usize) -> String {
+    if max_violations > 0 {
+        limit_text(errors, max_violations)
+    } else {
+        format!("{} found.", count(errors, "violation"))
+    }
+}
+
+// This function reports the result of a check in the given format. It returns an error if there
+// are more violations other than warnings than the maximum allowed. Suppressed violations never
+// cause the check to fail, but the formats which have a place for them include them. In quiet
+// mode, the text format prints nothing unless the check fails.
+pub fn print_report(
+    format: Format,
+    violations: &[Violation],
+    suppressed: &[Violation],
+    summary: &Summary,
+    max_violations: usize,
+    quiet: bool,
+    sources: &mut Sources,
+) -> Result<(), String> {
+    let errors = violations
+        .iter()
+        .filter(|violation| violation.severity == Severity::Error)
+        .count();
+    let failed = errors > max_violations;
+
+    match format {
+        Format::Csv => return Err(format.unsupported()),
+        Format::Text => {
+            // The violations go to STDERR, grouped by kind. In quiet mode, a successful check
+            // prints nothing, and a failed one only prints the errors.
+            if !quiet || failed {
+                eprint!("{}", pretty::report(violations, sources, quiet));
+            }
+            if failed {
+                return Err(failure_text(errors, max_violations));
+            }
+            if quiet {
+                return Ok(());
+            }
+
+            // Tolerated errors are still reported, but they don't cause the check to fail.
+            if errors > 0 {
+                eprintln!("{}", limit_text(errors, max_violations).yellow());
+            }
+            println!("{}", summary_text(summary).green());
+            if !suppressed.is_empty() {
+                println!(
+                    "{}",
+                    format!(
+                        "{} suppressed by comments.",
+                        count(suppressed.len(), "violation"),
+                    )
+                    .yellow(),
+                );
+            }
+        }
+        Format::Json
+        | Format::Sarif
+        | Format::Junit
+        | Format::Github
+        | Format::Gitlab
+        | Format::Compact => {
+            // Serializing plain data to a string can't fail, so the `unwrap`s are safe.
+            match format {
+                Format::Json => println!(
+                    "{}",
+                    serde_json::to_string(&Report {
+                        violations,
+                        suppressed,
+                        summary,
+                    })
+                    .unwrap(),
+                ),
+                Format::Sarif => {
+                    println!(
+                        "{}",
+                        serde_json::to_string(&sarif::report(violations, suppressed)).unwrap()
+                    );
+                }
+                Format::Gitlab => {
+                    println!(
+                        "{}",
+                        serde_json::to_string(&gitlab::report(violations)).unwrap()
+                    );
+                }
+                Format::Github => print!("{}", github::report(violations, &summary_text(summary))),
+                Format::Compact => print!("{}", compact::report(violations)),
+                _ => print!(
+                    "{}",
+                    junit::report(violations, suppressed, &summary_text(summary)),
+                ),
+            }
+
+            // The details are on STDOUT, so only a short summary goes to STDERR.
+            if failed {
+                return Err(failure_text(errors, max_violations));
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,752 @@
+use crate::{
+    aliases,
+    directive::{self, Directive},
+    index::{Index, Rules, Scanner},
+    moves::Move,
+    pins,
+    violation::{Kind, Violation},
+    walk,
+};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    io::{BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+};
+
+// This function runs a Git command and returns its output.
+fn git(args: &[&str]) -> Result<Vec<u8>, String> {
+    let output = Command::new("git")
+        .args(args)
+        .output()
+        .map_err(|error| format!("Unable to run Git: {error}"))?;
+
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(format!(
+            "Error when running `git {}`: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim(),
+        ))
+    }
+}
+
+// This function parses the output of `git diff --name-status -z` and returns the renames. Each
+// entry is a status followed by one path, or two for renames and copies.
+fn parse_renames(output: &[u8]) -> Vec<Move> {
+    let mut fields = output
+        .split(|byte| *byte == 0)
+        .map(|field| String::from_utf8_lossy(field).into_owned());
+    let mut renames = Vec::new();
+    while let Some(status) = fields.next() {
+        match status.chars().next() {
+            Some('R') => {
+                if let (Some(from), Some(to)) = (fields.next(), fields.next()) {
+                    renames.push(Move::new(Path::new(&from), Path::new(&to)));
+                }
+            }
+            Some('C') => {
+                fields.next();
+                fields.next();
+            }
+            Some(_) => {
+                fields.next();
+            }
+            None => {}
+        }
+    }
+    renames
+}
+
+// This struct is an entry in the output of `git ls-tree`.
+#[derive(Debug, Eq, PartialEq)]
+struct TreeEntry {
+    mode: String,
+
+    // `blob` for files and symlinks, `tree` for directories, or `commit` for submodules
+    r#type: String,
+
+    object: String,
+    path: PathBuf,
+}
+
+// This function parses the output of `git ls-tree -z`.
+fn parse_tree(output: &[u8]) -> Vec<TreeEntry> {
+    output
+        .split(|byte| *byte == 0)
+        .filter_map(|entry| {
+            let entry = String::from_utf8_lossy(entry);
+            let (info, path) = entry.split_once('\t')?;
+            let mut fields = info.split(' ');
+            Some(TreeEntry {
+                mode: fields.next()?.to_owned(),
+                r#type: fields.next()?.to_owned(),
+                object: fields.next()?.to_owned(),
+                path: PathBuf::from(path),
+            })
+        })
+        .collect()
+}
+
+// This function parses the output of `git ls-files -s -z`, which lists the files in the index.
+// Its entries have a stage number rather than a type, so the type is inferred from the mode.
+fn parse_index(output: &[u8]) -> Vec<TreeEntry> {
+    output
+        .split(|byte| *byte == 0)
+        .filter_map(|entry| {
+            let entry = String::from_utf8_lossy(entry);
+            let (info, path) = entry.split_once('\t')?;
+            let mut fields = info.split(' ');
+            let mode = fields.next()?.to_owned();
+            Some(TreeEntry {
+                r#type: if mode == "160000" { "commit" } else { "blob" }.to_owned(),
+                mode,
+                object: fields.next()?.to_owned(),
+                path: PathBuf::from(path),
+            })
+        })
+        .collect()
+}
+
+// The contents of a file in a tree are in a Git object, given directly, or in the working tree.
+#[derive(Clone, Debug)]
+enum Blob {
+    Object(String),
+    Contents(Vec<u8>),
+    WorkingTree,
+}
+
+// This struct lists the files and directories as of a Git revision, so file and directory
+// references can be checked against the revision rather than the working tree. Paths are relative
+// to the working directory, as Git only lists the files under it. A tree can also be made of the
+// files in the index (see `Tree::tracked`), or of files which aren't on disk at all (see
+// `Tree::from_files`).
+#[derive(Clone, Debug, Default)]
+pub struct Tree {
+    // The contents of each file, including symlinks
+    files: HashMap<PathBuf, Blob>,
+
+    // The directories, including submodules
+    dirs: HashSet<PathBuf>,
+
+    // Whether the files are the ones tracked in the working tree, so files which aren't in the tree
+    // may still be on disk
+    tracked: bool,
+}
+
+impl Tree {
+    // This function lists the files and directories as of the given revision.
+    pub fn at(revision: &str) -> Result<Self, String> {
+        let mut tree = Self::default();
+        for entry in parse_tree(&git(&["ls-tree", "-r", "-t", "-z", revision])?) {
+            if entry.r#type == "blob" {
+                tree.files.insert(entry.path, Blob::Object(entry.object));
+            } else {
+                tree.dirs.insert(entry.path);
+            }
+        }
+        Ok(tree)
+    }
+
+    // This function lists the files tracked by Git and the directories containing them, so file and
+    // directory references can be checked without reading the filesystem or matching untracked
+    // files. The files are listed as of the index, but their contents are read from the working
+    // tree.
+    pub fn tracked() -> Result<Self, String> {
+        let mut tree = Self {
+            tracked: true,
+            ..Self::default()
+        };
+        for entry in parse_index(&git(&["ls-files", "-s", "-z"])?) {
+            tree.dirs.extend(
+                entry
+                    .path
+                    .ancestors()
+                    .skip(1)
+                    .filter(|dir| !dir.as_os_str().is_empty())
+                    .map(Path::to_owned),
+            );
+            if entry.r#type == "blob" {
+                tree.files.insert(entry.path, Blob::WorkingTree);
+            } else {
+                tree.dirs.insert(entry.path);
+            }
+        }
+        Ok(tree)
+    }
+
+    // This function makes a tree of files whose contents are given, e.g., by a caller which has no
+    // filesystem. The directories are the ones containing the files.
+    pub fn from_files<'a, I: IntoIterator<Item = (&'a Path, &'a [u8])>>(files: I) -> Self {
+        let mut tree = Self::default();
+        for (path, contents) in files {
+            let path = walk::normalize(path);
+            tree.dirs.extend(
+                path.ancestors()
+                    .skip(1)
+                    .filter(|dir| !dir.as_os_str().is_empty())
+                    .map(Path::to_owned),
+            );
+            tree.files.insert(path, Blob::Contents(contents.to_owned()));
+        }
+        tree
+    }
+
+    // This function determines whether a path is a file. Git paths are case-sensitive, so the path
+    // must have the right case.
+    #[must_use]
+    pub fn is_file(&self, path: &Path) -> bool {
+        self.files.contains_key(&walk::normalize(path))
+    }
+
+    // This function determines whether a path is a directory. Git doesn't track empty
+    // directories, so the directory isn't empty.
+    #[must_use]
+    pub fn is_dir(&self, path: &Path) -> bool {
+        let path = walk::normalize(path);
+        path.as_os_str().is_empty() || self.dirs.contains(&path)
+    }
+
+    // This function determines whether a path which isn't in the tree exists on disk anyway,
+    // because it isn't tracked by Git. This only applies to trees of the files which are tracked
+    // (see `Tree::tracked`).
+    #[must_use]
+    pub fn is_untracked(&self, path: &Path) -> bool {
+        self.tracked && fs::symlink_metadata(walk::normalize(path)).is_ok()
+    }
+
+    // This function returns the directories.
+    pub fn dirs(&self) -> impl Iterator<Item = &Path> {
+        self.dirs.iter().map(PathBuf::as_path)
+    }
+
+    // This function reads the contents of a file.
+    pub fn read(&self, path: &Path) -> Result<Vec<u8>, String> {
+        match self.files.get(&walk::normalize(path)) {
+            Some(Blob::Object(object)) => git(&["cat-file", "blob", object]),
+            Some(Blob::Contents(contents)) => Ok(contents.clone()),
+            Some(Blob::WorkingTree) => fs::read(path).map_err(|error| error.to_string()),
+            None => Err("No such file in the revision".to_owned()),
+        }
+    }
+}
+
+// This function reads the contents of the given objects with a single Git process. The object IDs
+// are written from another thread, since Git may block on writing the contents until they're read.
+fn read_objects(objects: Vec<String>) -> Result<Vec<Vec<u8>>, String> {
+    let mut child = Command::new("git")
+        .args(["cat-file", "--batch"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|error| format!("Unable to run Git: {error}"))?;
+
+    // The streams were requested above, so the `unwrap`s are safe.
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let count = objects.len();
+    let writer = thread::spawn(move || {
+        for object in objects {
+            writeln!(stdin, "{object}")?;
+        }
+        Ok::<_, std::io::Error>(())
+    });
+
+    // Each object is a header line with its ID, type, and size, followed by the contents and a
+    // newline.
+    let error = |error| format!("Unable to read objects from Git: {error}");
+    let mut contents = Vec::with_capacity(count);
+    for _ in 0..count {
+        let mut header = String::new();
+        stdout.read_line(&mut header).map_err(error)?;
+        let size = header
+            .split_whitespace()
+            .nth(2)
+            .and_then(|size| size.parse::<usize>().ok())
+            .ok_or_else(|| format!("Unexpected output from `git cat-file`: {}", header.trim()))?;
+        let mut object = vec![0; size + 1];
+        stdout.read_exact(&mut object).map_err(error)?;
+        object.pop();
+        contents.push(object);
+    }
+
+    // The thread doesn't panic, so the `unwrap` is safe.
+    writer.join().unwrap().map_err(error)?;
+    child.wait().map_err(error)?;
+    Ok(contents)
+}
+
+// This function reads the files in the given paths as of a Git revision, using the same paths as
+// the walk would for the files in the working tree (e.g., `./src/main.rs` for `.`). Only the files
+// included by the globs in the options are read (see `walk::filter`).
+pub fn files_at(
+    revision: &str,
+    paths: &[PathBuf],
+    options: &walk::Options,
+) -> Result<Vec<(PathBuf, Vec<u8>)>, String> {
+    let included = walk::filter(options)?;
+    let mut objects = Vec::new();
+    let mut files = Vec::new();
+    for path in paths {
+        let path_string = path.to_string_lossy();
+        let output = git(&["ls-tree", "-r", "-z", revision, "--", &path_string])?;
+        let prefix = walk::relative(path);
+        // Symlinks and submodules are left out, since the walk doesn't visit them by default.
+        for entry in parse_tree(&output) {
+            if entry.r#type != "blob" || entry.mode == "120000" {
+                continue;
+            }
+
+            // Git prints the paths relative to the working directory, without a leading `./`.
+            let file = match entry.path.strip_prefix(&prefix) {
+                Ok(rest) if !rest.as_os_str().is_empty() => path.join(rest),
+                _ => path.clone(),
+            };
+            if included(&file) {
+                objects.push(entry.object);
+                files.push(file);
+            }
+        }
+    }
+
+    Ok(files.into_iter().zip(read_objects(objects)?).collect())
+}
+
+// This struct identifies a commit in the history of a repository.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct Commit {
+    pub hash: String,
+
+    // The committer date in ISO 8601 format
+    pub date: String,
+}
+
+// This function parses the output of `git log -z` with the format `%H %cI`.
+fn parse_commits(output: &[u8]) -> Vec<Commit> {
+    output
+        .split(|byte| *byte == 0)
+        .filter_map(|entry| {
+            let entry = String::from_utf8_lossy(entry);
+            let (hash, date) = entry.trim().split_once(' ')?;
+            Some(Commit {
+                hash: hash.to_owned(),
+                date: date.to_owned(),
+            })
+        })
+        .collect()
+}
+
+// This function lists the commits in a range of revisions (e.g., `v1.0..HEAD`, or a single
+// revision for all of its history), oldest first. Only the first parent of each merge is
+// followed, so the commits trace the history of the branch rather than of everything merged into
+// it.
+pub fn history(range: &str) -> Result<Vec<Commit>, String> {
+    git(&[
+        "log",
+        "--first-parent",
+        "--reverse",
+        "-z",
+        "--format=%H %cI",
+        range,
+        "--",
+    ])
+    .map(|output| parse_commits(&output))
+}
+
+// This function finds the files whose renames are staged, relative to the working directory.
+pub fn staged_renames() -> Result<Vec<Move>, String> {
+    git(&[
+        "diff",
+        "--cached",
+        "--name-status",
+        "--relative",
+        "-M",
+        "-z",
+    ])
+    .map(|output| parse_renames(&output))
+}
+
+// This struct describes what changed in the working tree since a given Git revision.
+#[derive(Clone, Debug, Default)]
+pub struct Changes {
+    // The files which were added, modified, or deleted, relative to the working directory.
+    pub files: HashSet<PathBuf>,
+
+    // The tags which were in the changed files as of the revision, keyed as in the index.
+    // References to these tags might have been broken by the changes, even if the references
+    // themselves are in unchanged files.
+    pub old_tags: HashSet<String>,
+
+    // Whether tags and references match regardless of the case of their labels
+    pub case_insensitive_labels: bool,
+}
+
+impl Changes {
+    // This function determines what changed since the given revision. This includes uncommitted
+    // changes and untracked files.
+    pub fn since(revision: &str, scanner: &Scanner) -> Result<Self, String> {
+        let mut changes = Self {
+            case_insensitive_labels: scanner.case_insensitive_labels,
+            ..Self::default()
+        };
+
+        // Find the files which changed.
+        let diff = git(&["diff", "--name-only", "--relative", "-z", revision, "--"])?;
+        let untracked = git(&["ls-files", "--others", "--exclude-standard", "-z"])?;
+        for path in diff
+            .split(|byte| *byte == 0)
+            .chain(untracked.split(|byte| *byte == 0))
+        {
+            if !path.is_empty() {
+                changes
+                    .files
+                    .insert(PathBuf::from(String::from_utf8_lossy(path).as_ref()));
+            }
+        }
+
+        // Find the tags which were in those files as of the revision. Files which didn't exist yet
+        // are skipped.
+        for path in &changes.files {
+            let Ok(contents) = git(&["show", &format!("{revision}:./{}", path.to_string_lossy())])
+            else {
+                continue;
+            };
+
+            let directives = scanner.parse(path, contents.as_slice());
+            changes.old_tags.extend(directives.tags.iter().map(|tag| {
+                directive::key(&tag.label, scanner.case_insensitive_labels).into_owned()
+            }));
+        }
+
+        Ok(changes)
+    }
+
+    // This function determines whether a violation could have been caused by the changes. That's
+    // the case if any of the directives involved are in a changed file, or if a reference is
+    // dangling because a tag was removed from a changed file.
+    #[must_use]
+    pub fn affects(&self, violation: &Violation) -> bool {
+        violation.directives.iter().any(|directive| {
+            self.files.contains(&walk::relative(&directive.path))
+                || (violation.kind == Kind::DanglingRef
+                    && self.old_tags.contains(&*directive::key(
+                        &directive.label,
+                        self.case_insensitive_labels,
+                    )))
+        })
+    }
+}
+
+// This struct describes a tag which moved, changed, or was removed since a Git revision, along
+// with the references to it which may need a second look.
+#[derive(Clone, Debug, Serialize)]
+pub struct ChangedTag {
+    // The tag as of the revision
+    pub before: Directive,
+
+    // The tag now, or `None` if it was removed
+    pub after: Option<Directive>,
+
+    // Whether the content the tag is attached to changed, as in pinned references
+    pub content_changed: bool,
+
+    pub refs: Vec<Directive>,
+}
+
+impl ChangedTag {
+    // This function determines whether the tag is in a different file or on a different line.
+    #[must_use]
+    pub fn moved(&self) -> bool {
+        self.after.as_ref().is_some_and(|after| {
+            walk::relative(&after.path) != walk::relative(&self.before.path)
+                || after.line_number != self.before.line_number
+        })
+    }
+}
+
+// This function compares a tag as of a revision with the tag now, given the contents of the files
+// containing them. It returns `None` if nothing changed.
+fn compare(
+    before: &Directive,
+    before_contents: &str,
+    after: Option<(&Directive, &str)>,
+) -> Option<ChangedTag> {
+    let changed = ChangedTag {
+        before: before.clone(),
+        after: after.map(|(after, _)| after.clone()),
+        content_changed: after.is_none_or(|(after, after_contents)| {
+            pins::digest(before_contents, before.line_number)
+                != pins::digest(after_contents, after.line_number)
+        }),
+        refs: Vec::new(),
+    };
+    (changed.after.is_none() || changed.content_changed || changed.moved()).then_some(changed)
+}
+
+// This function finds the tags in the files which changed since the given revision which moved,
+// changed, or were removed, along with the references to them in the index. References which use
+// aliases are included. Tags without references are left out.
+pub fn changed_tags(
+    revision: &str,
+    scanner: &Scanner,
+    index: &Index,
+    rules: &Rules,
+) -> Result<Vec<ChangedTag>, String> {
+    let files = Changes::since(revision, scanner)?.files;
+    let mut paths = files.iter().collect::<Vec<_>>();
+    paths.sort();
+
+    // Compare each tag as of the revision with the tag of the same label now.
+    let mut changed = BTreeMap::<String, ChangedTag>::new();
+    for path in paths {
+        let Ok(contents) = git(&["show", &format!("{revision}:./{}", path.to_string_lossy())])
+        else {
+            continue;
+        };
+        let before_contents = String::from_utf8_lossy(&contents);
+
+        for before in scanner.parse(path, contents.as_slice()).tags {
+            let key = index.key(&before.label).into_owned();
+            let after = index.tags.get(&key).and_then(|tags| tags.first());
+            let after_contents = after
+                .map(|after| {
+                    fs::read_to_string(&after.path).map_err(|error| {
+                        format!(
+                            "Error when reading {}: {error}",
+                            after.path.to_string_lossy()
+                        )
+                    })
+                })
+                .transpose()?;
+            if let Some(tag) = compare(
+                &before,
+                &before_contents,
+                after.zip(after_contents.as_deref()),
+            ) {
+                changed.entry(key).or_insert(tag);
+            }
+        }
+    }
+
+    // Find the references to those tags.
+    let aliases = aliases::resolve(
+        &index.tags,
+        &index.aliases,
+        &rules.aliases,
+        index.case_insensitive_labels,
+    );
+    let resolved = aliases::apply(&index.refs, &aliases, index.case_insensitive_labels);
+    for (r#ref, resolved) in index.refs.iter().zip(resolved.iter()) {
+        if let Some(tag) = changed.get_mut(&*index.key(&resolved.label)) {
+            tag.refs.push(r#ref.clone());
+        }
+    }
+
+    Ok(changed
+        .into_values()
+        .filter(|tag| !tag.refs.is_empty())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::{Directive, Type},
+        git::{
+            Changes, Commit, Tree, TreeEntry, compare, parse_commits, parse_index, parse_renames,
+            parse_tree,
+        },
+        moves::Move,
+        violation::{Kind, Severity, Violation},
+    };
+    use std::path::{Path, PathBuf};
+
+    fn dangling_ref(label: &str, path: &str) -> Violation {
+        Violation {
+            kind: Kind::DanglingRef,
+            severity: Severity::Error,
+            message: String::new(),
+            directives: vec![Directive::new(Type::Ref, label, path, 1)],
+            suggestions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn affects_changed_file() {
+        let changes = Changes {
+            files: [PathBuf::from("src/main.rs")].into(),
+            old_tags: [].into(),
+            case_insensitive_labels: false,
+        };
+
+        assert!(changes.affects(&dangling_ref("foo", "./src/main.rs")));
+        assert!(!changes.affects(&dangling_ref("foo", "./src/lib.rs")));
+    }
+
+    #[test]
+    fn affects_removed_tag() {
+        let changes = Changes {
+            files: [PathBuf::from("src/main.rs")].into(),
+            old_tags: ["foo".to_owned()].into(),
+            case_insensitive_labels: false,
+        };
+
+        assert!(changes.affects(&dangling_ref("foo", "./src/lib.rs")));
+        assert!(!changes.affects(&dangling_ref("bar", "./src/lib.rs")));
+    }
+
+    #[test]
+    fn affects_removed_tag_case_insensitive() {
+        let changes = Changes {
+            files: [PathBuf::from("src/main.rs")].into(),
+            old_tags: ["foo".to_owned()].into(),
+            case_insensitive_labels: true,
+        };
+
+        assert!(changes.affects(&dangling_ref("Foo", "./src/lib.rs")));
+    }
+
+    #[test]
+    fn parse_commits_entries() {
+        let output = b"1a2b3c 2024-06-30T12:00:00+02:00\0\n4d5e6f 2024-07-01T08:30:00Z\0";
+
+        assert_eq!(
+            parse_commits(output),
+            vec![
+                Commit {
+                    hash: "1a2b3c".to_owned(),
+                    date: "2024-06-30T12:00:00+02:00".to_owned(),
+                },
+                Commit {
+                    hash: "4d5e6f".to_owned(),
+                    date: "2024-07-01T08:30:00Z".to_owned(),
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn parse_renames_statuses() {
+        let output = b"M\0a.rs\0R100\0b.rs\0c/b.rs\0C75\0d.rs\0e.rs\0R090\0f.rs\0g.rs\0";
+
+        assert_eq!(
+            parse_renames(output),
+            vec![
+                Move::new(Path::new("b.rs"), Path::new("c/b.rs")),
+                Move::new(Path::new("f.rs"), Path::new("g.rs")),
+            ],
+        );
+    }
+
+    #[test]
+    fn parse_tree_entries() {
+        let output = b"100644 blob 1a\tsrc/a.rs\x00040000 tree 2b\tb c\x00";
+
+        assert_eq!(
+            parse_tree(output),
+            vec![
+                TreeEntry {
+                    mode: "100644".to_owned(),
+                    r#type: "blob".to_owned(),
+                    object: "1a".to_owned(),
+                    path: PathBuf::from("src/a.rs"),
+                },
+                TreeEntry {
+                    mode: "040000".to_owned(),
+                    r#type: "tree".to_owned(),
+                    object: "2b".to_owned(),
+                    path: PathBuf::from("b c"),
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn parse_index_entries() {
+        let output = b"100644 1a 0\tsrc/a.rs\x00160000 2b 0\tvendor/lib\x00";
+
+        assert_eq!(
+            parse_index(output),
+            vec![
+                TreeEntry {
+                    mode: "100644".to_owned(),
+                    r#type: "blob".to_owned(),
+                    object: "1a".to_owned(),
+                    path: PathBuf::from("src/a.rs"),
+                },
+                TreeEntry {
+                    mode: "160000".to_owned(),
+                    r#type: "commit".to_owned(),
+                    object: "2b".to_owned(),
+                    path: PathBuf::from("vendor/lib"),
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn untracked_paths() {
+        let tracked = Tree {
+            tracked: true,
+            ..Tree::default()
+        };
+
+        assert!(tracked.is_untracked(Path::new("src/git.rs")));
+        assert!(tracked.is_untracked(Path::new("./src")));
+        assert!(!tracked.is_untracked(Path::new("src/missing.rs")));
+        assert!(!Tree::default().is_untracked(Path::new("src/git.rs")));
+    }
+
+    #[test]
+    fn compare_unchanged() {
+        let contents = "// tag\nfn foo() {}\n";
+
+        assert!(
+            compare(
+                &Directive::new(Type::Tag, "foo", "a.rs", 1),
+                contents,
+                Some((&Directive::new(Type::Tag, "foo", "./a.rs", 1), contents))
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn compare_moved() {
+        let before = "// tag\nfn foo() {}\n";
+        let after = "fn bar() {}\n\n// tag\nfn foo() {}\n";
+
+        let changed = compare(
+            &Directive::new(Type::Tag, "foo", "a.rs", 1),
+            before,
+            Some((&Directive::new(Type::Tag, "foo", "./a.rs", 3), after)),
+        )
+        .unwrap();
+
+        assert!(changed.moved());
+        assert!(!changed.content_changed);
+    }
+
+    #[test]
+    fn compare_edited_and_removed() {
+        let before = "// tag\nfn foo() {}\n";
+        let after = "// tag\nfn foo() { 1 }\n";
+
+        let edited = compare(
+            &Directive::new(Type::Tag, "foo", "a.rs", 1),
+            before,
+            Some((&Directive::new(Type::Tag, "foo", "./a.rs", 1), after)),
+        )
+        .unwrap();
+        let removed = compare(&Directive::new(Type::Tag, "foo", "a.rs", 1), before, None).unwrap();
+
+        assert!(!edited.moved());
+        assert!(edited.content_changed);
+        assert!(removed.after.is_none());
+        assert!(removed.content_changed);
+    }
+}
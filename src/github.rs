@@ -0,0 +1,98 @@
+use std::fmt::Write;
+use tagref::{
+    violation::{Severity, Violation},
+    walk,
+};
+
+// This function escapes the message of a GitHub Actions workflow command.
+fn escape_data(text: &str) -> String {
+    text.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+// This function escapes a property of a GitHub Actions workflow command, which additionally can't
+// contain the characters which separate the properties.
+fn escape_property(text: &str) -> String {
+    escape_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+// This function builds GitHub Actions workflow commands which annotate the lines involved in each
+// violation, so they show up inline on pull requests. The summary goes last as an ordinary line.
+pub fn report(violations: &[Violation], summary: &str) -> String {
+    let mut output = String::new();
+    for violation in violations {
+        let command = match violation.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "notice",
+        };
+
+        let mut properties = format!("title={}", escape_property(violation.kind.description()));
+        if let Some(directive) = violation.directives.first() {
+            let path = walk::relative(&directive.path)
+                .to_string_lossy()
+                .replace('\\', "/");
+            let _ = write!(
+                properties,
+                ",file={},line={},col={}",
+                escape_property(&path),
+                directive.line_number,
+                directive.column,
+            );
+        }
+
+        let _ = writeln!(
+            output,
+            "::{command} {properties}::{}",
+            escape_data(violation.message.trim_end()),
+        );
+    }
+    let _ = writeln!(output, "{summary}");
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        fixtures,
+        github::{escape_property, report},
+    };
+    use tagref::{
+        directive::{Directive, Type},
+        violation::{Kind, Severity, Violation},
+    };
+
+    fn violation(severity: Severity) -> Violation {
+        Violation {
+            kind: Kind::DanglingRef,
+            severity,
+            message: "No tag found for foo.\n\n50% sure.".to_owned(),
+            directives: vec![Directive {
+                column: 7,
+                ..fixtures::directive(Type::Ref, "foo", "./src/a,b.rs", 3)
+            }],
+            suggestions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn escape_property_special_characters() {
+        assert_eq!(escape_property("a:b,c%\n"), "a%3Ab%2Cc%25%0A");
+    }
+
+    #[test]
+    fn report_error() {
+        assert_eq!(
+            report(&[violation(Severity::Error)], "Done."),
+            "::error title=Tag references must point to tags.,file=src/a%2Cb.rs,line=3,col=7::\
+             No tag found for foo.%0A%0A50%25 sure.\nDone.\n",
+        );
+    }
+
+    #[test]
+    fn report_warning() {
+        assert!(report(&[violation(Severity::Warning)], "").starts_with("::warning "));
+    }
+}
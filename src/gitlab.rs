@@ -0,0 +1,129 @@
+use serde_json::{Value, json};
+use std::{collections::HashMap, fmt::Write};
+use tagref::{
+    violation::{Severity, Violation},
+    walk,
+};
+
+// This function computes a 64-bit FNV-1a hash. It's used instead of the standard library's hasher
+// because the result must be the same across builds.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+// This function identifies a violation for GitLab, which uses the fingerprints to tell which
+// violations a merge request introduced or resolved. Line numbers are left out so the fingerprint
+// survives unrelated edits, and identical violations are told apart by their occurrence number
+// instead.
+fn fingerprint(violation: &Violation, occurrence: usize) -> String {
+    let mut key = format!("{}\n{occurrence}", violation.kind.id());
+    for directive in &violation.directives {
+        let _ = write!(
+            key,
+            "\n{}\n{}\n{}",
+            directive.r#type.sigil(),
+            directive.label,
+            walk::relative(&directive.path).to_string_lossy(),
+        );
+    }
+    format!("{:016x}", fnv1a(key.as_bytes()))
+}
+
+// This function builds a GitLab Code Quality report containing one issue per violation. GitLab
+// requires every issue to have a location, so violations which don't involve any labels are left
+// out.
+pub fn report(violations: &[Violation]) -> Value {
+    let mut occurrences = HashMap::<String, usize>::new();
+    violations
+        .iter()
+        .filter_map(|violation| {
+            violation.directives.first().map(|directive| {
+                let occurrence = occurrences
+                    .entry(fingerprint(violation, 0))
+                    .and_modify(|count| *count += 1)
+                    .or_default();
+                json!({
+                    "description": violation.message.trim_end(),
+                    "check_name": violation.kind.id(),
+                    "fingerprint": fingerprint(violation, *occurrence),
+                    "severity": match violation.severity {
+                        Severity::Error => "major",
+                        Severity::Warning => "minor",
+                        Severity::Info => "info",
+                    },
+                    "location": {
+                        "path": walk::relative(&directive.path)
+                            .to_string_lossy()
+                            .replace('\\', "/"),
+                        "lines": {
+                            "begin": directive.line_number,
+                        },
+                    },
+                })
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        fixtures,
+        gitlab::{fingerprint, report},
+    };
+    use tagref::{
+        directive::Type,
+        violation::{Kind, Severity, Violation},
+    };
+
+    fn violation(line_number: usize) -> Violation {
+        Violation {
+            kind: Kind::DanglingRef,
+            severity: Severity::Error,
+            message: "No tag found for foo.\n".to_owned(),
+            directives: vec![fixtures::directive(
+                Type::Ref,
+                "foo",
+                "./src/main.rs",
+                line_number,
+            )],
+            suggestions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn fingerprint_ignores_line_numbers() {
+        assert_eq!(fingerprint(&violation(1), 0), fingerprint(&violation(2), 0));
+    }
+
+    #[test]
+    fn report_distinguishes_identical_violations() {
+        let issues = report(&[violation(1), violation(2)]);
+
+        assert_ne!(issues[0]["fingerprint"], issues[1]["fingerprint"]);
+    }
+
+    #[test]
+    fn report_issue() {
+        let issues = report(&[violation(3)]);
+
+        assert_eq!(issues[0]["description"], "No tag found for foo.");
+        assert_eq!(issues[0]["check_name"], "dangling_ref");
+        assert_eq!(issues[0]["severity"], "major");
+        assert_eq!(issues[0]["location"]["path"], "src/main.rs");
+        assert_eq!(issues[0]["location"]["lines"]["begin"], 3_usize);
+    }
+
+    #[test]
+    fn report_skips_violations_without_labels() {
+        let mut violation = violation(1);
+        violation.directives.clear();
+
+        assert_eq!(report(&[violation]), serde_json::json!([]));
+    }
+}
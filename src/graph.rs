@@ -0,0 +1,266 @@
+use crate::{index::Index, walk};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    path::Path,
+};
+
+// This function formats a path the same way it would be written in a label.
+fn display_path(path: &Path) -> String {
+    walk::relative(path).to_string_lossy().into_owned()
+}
+
+// This struct represents the reference graph. The nodes are tags and the files which contain tags
+// or references. Each tag is connected to the files which contain it, and each file is connected
+// to the tags it references.
+#[derive(Clone, Debug, Default)]
+pub struct Graph {
+    // The files containing each tag, keyed by label
+    pub tags: BTreeMap<String, BTreeSet<String>>,
+
+    // The number of references from each file to each tag, keyed by file and label
+    pub refs: BTreeMap<(String, String), usize>,
+
+    // The descriptions of the tags which have them, keyed by label
+    pub descriptions: BTreeMap<String, String>,
+}
+
+impl Graph {
+    // This function builds the graph for an index. Dangling references are left out, since they
+    // don't point to any node.
+    #[must_use]
+    pub fn new(index: &Index) -> Self {
+        let mut graph = Self::default();
+
+        // Tags are shown as they're written, which matters if labels are case-insensitive. Every
+        // key in the index has at least one tag, so the indexing is safe.
+        let label = |key: &str| index.tags[key][0].label.clone();
+
+        for (key, tags) in &index.tags {
+            let label = label(key);
+            graph.tags.insert(
+                label.clone(),
+                tags.iter().map(|tag| display_path(&tag.path)).collect(),
+            );
+
+            // Duplicate tags may be described differently, in which case the first one wins.
+            if let Some(description) = tags.iter().find_map(|tag| tag.description.clone()) {
+                graph.descriptions.insert(label.clone(), description);
+            }
+        }
+
+        for r#ref in &index.refs {
+            let key = index.key(&r#ref.label);
+            if index.tags.contains_key(&*key) {
+                *graph
+                    .refs
+                    .entry((display_path(&r#ref.path), label(&key)))
+                    .or_default() += 1;
+            }
+        }
+
+        graph
+    }
+
+    // This function returns all the files in the graph, in order.
+    fn files(&self) -> BTreeSet<&str> {
+        self.tags
+            .values()
+            .flatten()
+            .map(String::as_str)
+            .chain(self.refs.keys().map(|(file, _)| file.as_str()))
+            .collect()
+    }
+
+    // This function renders the graph in the DOT language used by Graphviz.
+    #[must_use]
+    pub fn dot(&self) -> String {
+        // DOT strings are double-quoted, with backslashes and double quotes escaped. Line breaks
+        // are written as `\n`.
+        let escape = |string: &str| {
+            string
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n")
+        };
+        let quote = |string: &str| format!("\"{}\"", escape(string));
+
+        let mut dot = String::new();
+        let _ = writeln!(dot, "digraph tagref {{");
+        let _ = writeln!(dot, "  rankdir=LR;");
+        for file in self.files() {
+            let _ = writeln!(
+                dot,
+                "  {} [label={}, shape=box];",
+                quote(&format!("file:{file}")),
+                quote(file),
+            );
+        }
+        for label in self.tags.keys() {
+            // Described tags show their description below the label, and as a tooltip.
+            let attributes = match self.descriptions.get(label) {
+                Some(description) => format!(
+                    "label={}, tooltip={}",
+                    quote(&format!("{label}\n{description}")),
+                    quote(description),
+                ),
+                None => format!("label={}", quote(label)),
+            };
+            let _ = writeln!(
+                dot,
+                "  {} [{attributes}, shape=ellipse];",
+                quote(&format!("tag:{label}")),
+            );
+        }
+        for (label, files) in &self.tags {
+            for file in files {
+                let _ = writeln!(
+                    dot,
+                    "  {} -> {} [style=dashed, arrowhead=none];",
+                    quote(&format!("file:{file}")),
+                    quote(&format!("tag:{label}")),
+                );
+            }
+        }
+        for ((file, label), count) in &self.refs {
+            let _ = writeln!(
+                dot,
+                "  {} -> {} [label={count}];",
+                quote(&format!("file:{file}")),
+                quote(&format!("tag:{label}")),
+            );
+        }
+        let _ = writeln!(dot, "}}");
+
+        dot
+    }
+
+    // This function renders the graph as a Mermaid flowchart.
+    #[must_use]
+    pub fn mermaid(&self) -> String {
+        // Mermaid node IDs can't contain arbitrary characters, so the nodes are numbered and the
+        // names are given as quoted labels instead. Double quotes in labels must be escaped as
+        // entities.
+        let quote = |string: &str| format!("\"{}\"", string.replace('"', "#quot;"));
+        let files = self
+            .files()
+            .into_iter()
+            .enumerate()
+            .map(|(index, file)| (file, format!("f{index}")))
+            .collect::<BTreeMap<_, _>>();
+        let tags = self
+            .tags
+            .keys()
+            .enumerate()
+            .map(|(index, label)| (label.as_str(), format!("t{index}")))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut mermaid = String::new();
+        let _ = writeln!(mermaid, "flowchart LR");
+        for (file, id) in &files {
+            let _ = writeln!(mermaid, "  {id}[{}]", quote(file));
+        }
+        for (label, id) in &tags {
+            // Described tags show their description below the label.
+            let text = match self.descriptions.get(*label) {
+                Some(description) => format!("{label}<br>{}", description.replace('\n', "<br>")),
+                None => (*label).to_owned(),
+            };
+            let _ = writeln!(mermaid, "  {id}([{}])", quote(&text));
+        }
+        for (label, containing_files) in &self.tags {
+            for file in containing_files {
+                let _ = writeln!(
+                    mermaid,
+                    "  {} -.- {}",
+                    files[file.as_str()],
+                    tags[label.as_str()]
+                );
+            }
+        }
+        for ((file, label), count) in &self.refs {
+            let _ = writeln!(
+                mermaid,
+                "  {} -->|{count}| {}",
+                files[file.as_str()],
+                tags[label.as_str()],
+            );
+        }
+
+        mermaid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::{Directive, Type},
+        graph::Graph,
+        index::Index,
+    };
+
+    fn index() -> Index {
+        let mut index = Index::default();
+        index.tags.insert(
+            "foo".to_owned(),
+            vec![Directive::new(Type::Tag, "foo", "./a.rs", 1)],
+        );
+        index.refs = vec![
+            Directive::new(Type::Ref, "foo", "./b.rs", 1),
+            Directive::new(Type::Ref, "foo", "./b.rs", 1),
+            Directive::new(Type::Ref, "bar", "./b.rs", 1),
+        ];
+        index
+    }
+
+    #[test]
+    fn new_counts_refs() {
+        let graph = Graph::new(&index());
+
+        assert_eq!(graph.tags["foo"].len(), 1);
+        assert_eq!(graph.refs.len(), 1);
+        assert_eq!(graph.refs[&("b.rs".to_owned(), "foo".to_owned())], 2);
+    }
+
+    #[test]
+    fn dot() {
+        let dot = Graph::new(&index()).dot();
+
+        assert!(dot.starts_with("digraph tagref {\n"));
+        assert!(dot.contains("\"file:a.rs\" -> \"tag:foo\" [style=dashed, arrowhead=none];\n"));
+        assert!(dot.contains("\"file:b.rs\" -> \"tag:foo\" [label=2];\n"));
+        assert!(!dot.contains("bar"));
+    }
+
+    #[test]
+    fn dot_description() {
+        let mut index = index();
+        index.tags.get_mut("foo").unwrap()[0].description = Some("Say \"hi\".".to_owned());
+        let dot = Graph::new(&index).dot();
+
+        assert!(dot.contains(
+            "  \"tag:foo\" [label=\"foo\\nSay \\\"hi\\\".\", tooltip=\"Say \\\"hi\\\".\", \
+             shape=ellipse];\n",
+        ));
+    }
+
+    #[test]
+    fn mermaid() {
+        let mermaid = Graph::new(&index()).mermaid();
+
+        assert!(mermaid.starts_with("flowchart LR\n"));
+        assert!(mermaid.contains("  f0[\"a.rs\"]\n"));
+        assert!(mermaid.contains("  t0([\"foo\"])\n"));
+        assert!(mermaid.contains("  f0 -.- t0\n"));
+        assert!(mermaid.contains("  f1 -->|2| t0\n"));
+    }
+
+    #[test]
+    fn mermaid_description() {
+        let mut index = index();
+        index.tags.get_mut("foo").unwrap()[0].description = Some("One.\nTwo.".to_owned());
+        let mermaid = Graph::new(&index).mermaid();
+
+        assert!(mermaid.contains("  t0([\"foo<br>One.<br>Two.\"])\n"));
+    }
+}
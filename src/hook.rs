@@ -0,0 +1,221 @@
+use clap::ValueEnum;
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+// This line identifies hooks installed by Tagref, so they can be updated or removed safely.
+const MARKER: &str = "# Installed by Tagref.";
+
+// This enum represents the Git hooks that Tagref can be installed as.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum Hook {
+    PreCommit,
+    PrePush,
+}
+
+impl Hook {
+    // This is the name of the hook file.
+    fn name(self) -> &'static str {
+        match self {
+            Self::PreCommit => "pre-commit",
+            Self::PrePush => "pre-push",
+        }
+    }
+
+    // This function returns the contents of the hook script. Before a commit, only the files with
+    // uncommitted changes need to be checked. Before a push, the files which changed since the
+    // upstream branch need to be checked, if there is one. Before the first commit, there's nothing
+    // to compare with, so all the files are checked.
+    fn script(self) -> String {
+        let revision = match self {
+            Self::PreCommit => "HEAD",
+            Self::PrePush => "\"$(git rev-parse --verify --quiet '@{upstream}' || echo HEAD)\"",
+        };
+
+        format!(
+            "#!/bin/sh\n\
+             {MARKER} Remove with `tagref uninstall-hook --hook {}`.\n\
+             if git rev-parse --verify --quiet HEAD >/dev/null; then\n    \
+             exec tagref check --changed-since {revision}\n\
+             fi\n\
+             exec tagref check\n",
+            self.name(),
+        )
+    }
+}
+
+// This function runs a Git command and returns its output, without the trailing newline.
+fn git(args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(args)
+        .output()
+        .map_err(|error| format!("Unable to run Git: {error}"))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .trim_end()
+            .to_owned())
+    } else {
+        Err(format!(
+            "Error when running `git {}`: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim(),
+        ))
+    }
+}
+
+// This function returns the path of the hook file, taking `core.hooksPath` into account.
+fn hook_path(hook: Hook) -> Result<PathBuf, String> {
+    Ok(PathBuf::from(git(&["rev-parse", "--git-path", "hooks"])?).join(hook.name()))
+}
+
+// This function looks for signs that the hooks are managed by another tool, in which case writing
+// to the hooks directory would either be overwritten or break that tool. It returns advice for
+// setting up Tagref with that tool instead.
+fn hook_manager(top_level: &Path, hooks_path: &Path, hook: Hook) -> Option<String> {
+    if top_level.join(".husky").is_dir()
+        || hooks_path
+            .components()
+            .any(|component| component.as_os_str() == ".husky")
+    {
+        return Some(format!(
+            "The Git hooks are managed by husky. Add `tagref check --changed-since HEAD` to \
+             `.husky/{}` instead.",
+            hook.name(),
+        ));
+    }
+
+    if top_level.join(".pre-commit-config.yaml").is_file() {
+        return Some(
+            "The Git hooks are managed by pre-commit. Add Tagref to `.pre-commit-config.yaml` \
+             instead."
+                .to_owned(),
+        );
+    }
+
+    None
+}
+
+// This function installs Tagref as a Git hook. Unless `force` is set, it refuses to overwrite a
+// hook which wasn't installed by Tagref or to interfere with a hook manager.
+pub fn install(hook: Hook, force: bool) -> Result<PathBuf, String> {
+    let path = hook_path(hook)?;
+
+    if !force {
+        let top_level = PathBuf::from(git(&["rev-parse", "--show-toplevel"])?);
+        if let Some(advice) = hook_manager(&top_level, &path, hook) {
+            return Err(format!("{advice} Pass --force to install the hook anyway."));
+        }
+
+        match fs::read_to_string(&path) {
+            Ok(contents) if !contents.contains(MARKER) => {
+                return Err(format!(
+                    "A {} hook already exists at {}. Pass --force to replace it.",
+                    hook.name(),
+                    path.to_string_lossy(),
+                ));
+            }
+            Ok(_) => {}
+            Err(error) if error.kind() == ErrorKind::NotFound => {}
+            Err(error) => {
+                return Err(format!(
+                    "Error when reading {}: {error}",
+                    path.to_string_lossy(),
+                ));
+            }
+        }
+    }
+
+    // Write the hook and make it executable.
+    let write_error = |error| format!("Unable to write {}: {error}", path.to_string_lossy());
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(write_error)?;
+    }
+    fs::write(&path, hook.script()).map_err(write_error)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).map_err(write_error)?;
+    }
+
+    Ok(path)
+}
+
+// This function removes a Git hook installed by Tagref. Hooks which weren't installed by Tagref are
+// left alone.
+pub fn uninstall(hook: Hook) -> Result<PathBuf, String> {
+    let path = hook_path(hook)?;
+
+    match fs::read_to_string(&path) {
+        Ok(contents) if contents.contains(MARKER) => {
+            fs::remove_file(&path)
+                .map_err(|error| format!("Unable to remove {}: {error}", path.to_string_lossy()))?;
+            Ok(path)
+        }
+        Ok(_) => Err(format!(
+            "The {} hook at {} wasn't installed by Tagref.",
+            hook.name(),
+            path.to_string_lossy(),
+        )),
+        Err(error) if error.kind() == ErrorKind::NotFound => Err(format!(
+            "No {} hook is installed at {}.",
+            hook.name(),
+            path.to_string_lossy(),
+        )),
+        Err(error) => Err(format!(
+            "Error when reading {}: {error}",
+            path.to_string_lossy(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hook::{Hook, MARKER, hook_manager};
+    use std::path::Path;
+
+    #[test]
+    fn script_pre_commit() {
+        let script = Hook::PreCommit.script();
+
+        assert!(script.starts_with("#!/bin/sh\n"));
+        assert!(script.contains(MARKER));
+        assert!(script.contains("tagref check --changed-since HEAD\n"));
+        assert!(script.ends_with("fi\nexec tagref check\n"));
+    }
+
+    #[test]
+    fn script_pre_push() {
+        let script = Hook::PrePush.script();
+
+        assert!(script.contains(MARKER));
+        assert!(script.contains("@{upstream}"));
+    }
+
+    #[test]
+    fn hook_manager_husky_hooks_path() {
+        assert!(
+            hook_manager(
+                Path::new("/nonexistent"),
+                Path::new(".husky/_/pre-commit"),
+                Hook::PreCommit,
+            )
+            .is_some_and(|advice| advice.contains("husky")),
+        );
+    }
+
+    #[test]
+    fn hook_manager_none() {
+        assert!(
+            hook_manager(
+                Path::new("/nonexistent"),
+                Path::new(".git/hooks/pre-commit"),
+                Hook::PreCommit,
+            )
+            .is_none(),
+        );
+    }
+}
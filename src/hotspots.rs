@@ -0,0 +1,168 @@
+use crate::{
+    directive::{self, Directive},
+    walk,
+};
+use serde::Serialize;
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+// This is the number of references a tag must exceed to be a hotspot, unless configured otherwise.
+pub const DEFAULT_THRESHOLD: usize = 10;
+
+// This struct counts the references to a hotspot in one file.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct File {
+    pub path: PathBuf,
+    pub refs: usize,
+}
+
+// This struct groups the files in a directory which reference a hotspot.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct Directory {
+    pub path: PathBuf,
+    pub refs: usize,
+    pub files: Vec<File>,
+}
+
+// This struct describes a tag which is referenced so often that changing what it documents
+// deserves extra care.
+#[derive(Clone, Debug, Serialize)]
+pub struct Hotspot {
+    pub tag: Directive,
+    pub refs: usize,
+    pub directories: Vec<Directory>,
+}
+
+// This function finds the tags with more references than the threshold, most referenced first.
+// The referencing files are grouped by the directory containing them. The tags are keyed as in the
+// index, and duplicates of a tag are represented by the first one.
+#[must_use]
+pub fn find(
+    tags_map: &BTreeMap<String, Vec<Directive>>,
+    refs: &[Directive],
+    threshold: usize,
+    case_insensitive_labels: bool,
+) -> Vec<Hotspot> {
+    let mut refs_map = HashMap::<_, BTreeMap<PathBuf, usize>>::new();
+    for r#ref in refs {
+        *refs_map
+            .entry(directive::key(&r#ref.label, case_insensitive_labels))
+            .or_default()
+            .entry(walk::relative(&r#ref.path))
+            .or_default() += 1;
+    }
+
+    let mut hotspots = tags_map
+        .iter()
+        .filter_map(|(label, tags)| {
+            let files = refs_map.get(label.as_str())?;
+            let refs = files.values().sum::<usize>();
+            if refs <= threshold {
+                return None;
+            }
+
+            let mut directories = BTreeMap::<&Path, Vec<File>>::new();
+            for (path, refs) in files {
+                directories
+                    .entry(path.parent().unwrap_or(Path::new("")))
+                    .or_default()
+                    .push(File {
+                        path: path.clone(),
+                        refs: *refs,
+                    });
+            }
+
+            Some(Hotspot {
+                tag: tags.first()?.clone(),
+                refs,
+                directories: directories
+                    .into_iter()
+                    .map(|(path, files)| Directory {
+                        path: if path.as_os_str().is_empty() {
+                            PathBuf::from(".")
+                        } else {
+                            path.to_owned()
+                        },
+                        refs: files.iter().map(|file| file.refs).sum(),
+                        files,
+                    })
+                    .collect(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // The tags are already in order of label, and the sort is stable.
+    hotspots.sort_by_key(|hotspot| Reverse(hotspot.refs));
+    hotspots
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::{Directive, Type},
+        hotspots::{Directory, File, find},
+    };
+    use std::{collections::BTreeMap, path::PathBuf};
+
+    #[test]
+    fn find_above_threshold() {
+        let mut tags_map = BTreeMap::new();
+        for label in ["foo", "bar", "baz"] {
+            tags_map.insert(
+                label.to_owned(),
+                vec![Directive::new(Type::Tag, label, "./a.rs", 1)],
+            );
+        }
+        let refs = vec![
+            Directive::new(Type::Ref, "foo", "./src/b.rs", 1),
+            Directive::new(Type::Ref, "foo", "./src/b.rs", 1),
+            Directive::new(Type::Ref, "foo", "./src/c.rs", 1),
+            Directive::new(Type::Ref, "foo", "./d.rs", 1),
+            Directive::new(Type::Ref, "bar", "./src/b.rs", 1),
+            Directive::new(Type::Ref, "bar", "./d.rs", 1),
+            Directive::new(Type::Ref, "bar", "./e.rs", 1),
+            Directive::new(Type::Ref, "baz", "./d.rs", 1),
+            Directive::new(Type::Ref, "baz", "./e.rs", 1),
+        ];
+
+        let hotspots = find(&tags_map, &refs, 2, false);
+
+        assert_eq!(
+            hotspots
+                .iter()
+                .map(|hotspot| (hotspot.tag.label.as_str(), hotspot.refs))
+                .collect::<Vec<_>>(),
+            vec![("foo", 4), ("bar", 3)],
+        );
+        assert_eq!(
+            hotspots[0].directories,
+            vec![
+                Directory {
+                    path: PathBuf::from("."),
+                    refs: 1,
+                    files: vec![File {
+                        path: PathBuf::from("d.rs"),
+                        refs: 1,
+                    }],
+                },
+                Directory {
+                    path: PathBuf::from("src"),
+                    refs: 3,
+                    files: vec![
+                        File {
+                            path: PathBuf::from("src/b.rs"),
+                            refs: 2,
+                        },
+                        File {
+                            path: PathBuf::from("src/c.rs"),
+                            refs: 1,
+                        },
+                    ],
+                },
+            ],
+        );
+    }
+}
@@ -0,0 +1,1294 @@
+use crate::{
+    aliases::{self, Alias},
+    archives::{self, Archive},
+    cache::{Cache, Fingerprint},
+    count, custom_labels, dir_references,
+    directive::{self, Directive, Directives},
+    docs,
+    drop_ins::DropIns,
+    duplicates,
+    expiry::{self, Date},
+    extractors::{self, Extractor},
+    file_references,
+    git::{self, Tree},
+    issues, mapped, markdown, min_refs, namespaces, naming, pairings, pins, severities,
+    suppressions::{self, Suppression},
+    tag_references, unused, url_references, validators,
+    violation::{self, Kind, Severity, Violation},
+    walk,
+};
+use encoding_rs::Encoding;
+use regex::Regex;
+use serde::Serialize;
+use std::{
+    borrow::{Borrow, Cow},
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fmt::Write,
+    hash::Hash,
+    mem::take,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Instant,
+};
+
+// This struct records how many of each kind of directive were validated.
+#[derive(Clone, Debug, Serialize)]
+pub struct Summary {
+    pub tags: usize,
+    pub tag_references: usize,
+    pub file_references: usize,
+    pub directory_references: usize,
+    pub url_references: usize,
+    pub custom_labels: usize,
+    pub files_scanned: usize,
+    pub files_too_large: Vec<PathBuf>,
+}
+
+// This struct holds the configurable rules which the checks enforce.
+#[derive(Clone, Debug, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Rules {
+    // These control whether duplicate tags are tolerated.
+    pub duplicates: Vec<duplicates::Rule>,
+
+    // These are the naming conventions for all tags.
+    pub naming: naming::Conventions,
+
+    // These are the naming conventions and reference restrictions for namespaces.
+    pub namespaces: Vec<namespaces::Rule>,
+
+    // These pair files or directories whose tags must be referenced from each other.
+    pub pairings: Vec<pairings::Rule>,
+
+    // If set, tags which aren't referenced anywhere are errors, unless they're in the allowlist.
+    pub deny_unused: bool,
+
+    // These labels and namespaces are allowed to be unreferenced.
+    pub allow_unused: Vec<String>,
+
+    // These are the minimum numbers of references to tags, keyed by label or namespace, for tags
+    // without a `min-refs` attribute.
+    pub min_refs: BTreeMap<String, usize>,
+
+    // If set, directory references to empty directories are errors.
+    pub deny_empty_dirs: bool,
+
+    // If set, links in Markdown files to other files are checked like file references.
+    pub markdown_links: bool,
+
+    // If set, every tag outside this directory must have a section in the Markdown files in it.
+    pub docs: Option<PathBuf>,
+
+    // If set, file and directory references must have the same case as the paths on disk, even
+    // on case-insensitive filesystems.
+    pub strict_path_case: bool,
+
+    // These are the kinds of custom labels, with their validators.
+    pub custom_labels: Vec<custom_labels::Definition>,
+
+    // These are alternative labels for tags, mapping each alias to the label of its tag, in
+    // addition to the aliases declared in the files.
+    pub aliases: BTreeMap<String, String>,
+
+    // If set, references which use aliases rather than the labels of their tags are errors.
+    pub deny_deprecated_aliases: bool,
+
+    // These override the severities of the violations, by kind and path.
+    pub severities: Vec<severities::Rule>,
+
+    // If set, the tags in each root are only visible to the references in the same root.
+    pub isolate_roots: bool,
+
+    // These directories are scopes, within which tags must be unique. References match the tags
+    // in their own scope first, then the tags outside any scope. If scopes are nested, the
+    // innermost one wins.
+    pub scopes: Vec<PathBuf>,
+
+    // These are the tags of other repositories, keyed by alias, for checking references with
+    // labels like `other#label`. Repositories whose tags are unavailable map to `None`, and
+    // references to them aren't checked. If labels are case-insensitive, the tags must be
+    // lowercase.
+    pub external_tags: BTreeMap<String, Option<HashSet<String>>>,
+
+    // These are the external commands which enforce other policies on the labels.
+    pub validators: Vec<validators::Command>,
+}
+
+// This struct holds all the directives found in the scanned files.
+#[derive(Clone, Debug, Default)]
+pub struct Index {
+    // The tags, keyed by the form of their labels which is used to match them with references
+    pub tags: BTreeMap<String, Vec<Directive>>,
+
+    pub refs: Vec<Directive>,
+    pub files: Vec<Directive>,
+    pub dirs: Vec<Directive>,
+    pub urls: Vec<Directive>,
+    pub custom: Vec<Directive>,
+    pub undecodable: Vec<PathBuf>,
+    pub suppressions: Vec<Suppression>,
+    pub aliases: Vec<Alias>,
+    pub anchors: Vec<Directive>,
+    pub pins: Vec<Directive>,
+    pub links: Vec<Directive>,
+    pub headings: Vec<Directive>,
+    pub files_scanned: usize,
+
+    // The files which weren't scanned for being larger than the limit
+    pub files_too_large: Vec<PathBuf>,
+
+    // Whether tags and references match regardless of the case of their labels
+    pub case_insensitive_labels: bool,
+
+    // Whether file and directory references starting with `./` or `../` are relative to the file
+    // containing them, rather than the working directory
+    pub relative_paths: bool,
+
+    // The directories which file and directory references in them are resolved from
+    pub roots: Vec<PathBuf>,
+
+    // The drop-in files, which can turn checks off for their directories
+    pub drop_ins: Arc<DropIns>,
+
+    // The files and directories as of the Git revision which was scanned, if the files were read
+    // from Git rather than the working tree
+    pub tree: Option<Arc<Tree>>,
+}
+
+impl Index {
+    // This function returns the form of a label which is used to match tags with references, as
+    // in the keys of `tags`.
+    #[must_use]
+    pub fn key<'a>(&self, label: &'a str) -> Cow<'a, str> {
+        directive::key(label, self.case_insensitive_labels)
+    }
+
+    // This function adds the directives from a file to the index.
+    pub fn insert(&mut self, directives: Directives) {
+        for tag in directives.tags {
+            // Most labels only have one tag, so no room is reserved for duplicates.
+            let key = self.key(&tag.label).into_owned();
+            self.tags
+                .entry(key)
+                .or_insert_with(|| Vec::with_capacity(1))
+                .push(tag);
+        }
+        self.refs.extend(directives.refs);
+        self.files.extend(directives.files);
+        self.dirs.extend(directives.dirs);
+        self.urls.extend(directives.urls);
+        self.custom.extend(directives.custom);
+        self.undecodable.extend(directives.undecodable);
+        self.files_too_large.extend(directives.too_large);
+        self.suppressions.extend(directives.suppressions);
+        self.aliases.extend(directives.aliases);
+        self.anchors.extend(directives.anchors);
+        self.pins.extend(directives.pins);
+        self.links.extend(directives.links);
+        self.headings.extend(directives.headings);
+    }
+
+    // This function removes the directives from a file, so they can be replaced with ones parsed
+    // from elsewhere. The removed directives are returned.
+    pub fn remove(&mut self, path: &Path) -> Directives {
+        let path = walk::relative(path);
+        let in_file = |directive: &Directive| walk::relative(&directive.path) == path;
+        let mut removed = Directives {
+            tags: Vec::new(),
+            refs: self.refs.extract_if(.., |r#ref| in_file(r#ref)).collect(),
+            files: self.files.extract_if(.., |file| in_file(file)).collect(),
+            dirs: self.dirs.extract_if(.., |dir| in_file(dir)).collect(),
+            urls: self.urls.extract_if(.., |url| in_file(url)).collect(),
+            custom: self.custom.extract_if(.., |label| in_file(label)).collect(),
+            undecodable: self
+                .undecodable
+                .extract_if(.., |undecodable| walk::relative(undecodable) == path)
+                .collect(),
+            too_large: self
+                .files_too_large
+                .extract_if(.., |too_large| {
+                    archives::split(too_large)
+                        .is_some_and(|(archive, _)| walk::relative(&archive) == path)
+                })
+                .collect(),
+            suppressions: self
+                .suppressions
+                .extract_if(.., |suppression| walk::relative(&suppression.path) == path)
+                .collect(),
+            aliases: self
+                .aliases
+                .extract_if(.., |alias| in_file(&alias.directive))
+                .collect(),
+            anchors: self
+                .anchors
+                .extract_if(.., |anchor| in_file(anchor))
+                .collect(),
+            pins: self.pins.extract_if(.., |pin| in_file(pin)).collect(),
+            links: self.links.extract_if(.., |link| in_file(link)).collect(),
+            headings: self
+                .headings
+                .extract_if(.., |heading| in_file(heading))
+                .collect(),
+        };
+        self.tags.retain(|_, tags| {
+            removed.tags.extend(tags.extract_if(.., |tag| in_file(tag)));
+            !tags.is_empty()
+        });
+        removed
+    }
+
+    // This function splits the index into one for each of the given directories (e.g., the roots),
+    // plus one for the files which aren't in any of them, keyed by the normalized directory.
+    fn partition(&self, directories: &[PathBuf]) -> Vec<(Option<PathBuf>, Self)> {
+        let root = |path: &Path| walk::root(path, directories).map(walk::normalize);
+        let keys = directories
+            .iter()
+            .map(|root| Some(walk::normalize(root)))
+            .chain([None])
+            .collect::<BTreeSet<_>>();
+        let tags = self.tags.values().flatten().cloned().collect::<Vec<_>>();
+
+        keys.into_iter()
+            .map(|key| {
+                let filter = |directives: &[Directive]| {
+                    directives
+                        .iter()
+                        .filter(|directive| root(&directive.path) == key)
+                        .cloned()
+                        .collect()
+                };
+                let mut index = Self {
+                    case_insensitive_labels: self.case_insensitive_labels,
+                    relative_paths: self.relative_paths,
+                    roots: self.roots.clone(),
+                    drop_ins: self.drop_ins.clone(),
+                    tree: self.tree.clone(),
+                    ..Self::default()
+                };
+                index.insert(Directives {
+                    tags: filter(&tags),
+                    refs: filter(&self.refs),
+                    files: filter(&self.files),
+                    dirs: filter(&self.dirs),
+                    urls: filter(&self.urls),
+                    custom: filter(&self.custom),
+                    undecodable: self
+                        .undecodable
+                        .iter()
+                        .filter(|path| root(path) == key)
+                        .cloned()
+                        .collect(),
+                    too_large: Vec::new(),
+                    suppressions: self
+                        .suppressions
+                        .iter()
+                        .filter(|suppression| root(&suppression.path) == key)
+                        .cloned()
+                        .collect(),
+                    aliases: self
+                        .aliases
+                        .iter()
+                        .filter(|alias| root(&alias.directive.path) == key)
+                        .cloned()
+                        .collect(),
+                    anchors: filter(&self.anchors),
+                    pins: filter(&self.pins),
+                    links: filter(&self.links),
+                    headings: filter(&self.headings),
+                });
+                (key, index)
+            })
+            .collect()
+    }
+
+    // This function runs all the checks and returns the violations.
+    #[must_use]
+    pub fn check(&self, rules: &Rules) -> Vec<Violation> {
+        // If the roots are isolated, each one is checked on its own.
+        if rules.isolate_roots && !self.roots.is_empty() {
+            let rules = Rules {
+                isolate_roots: false,
+                ..rules.clone()
+            };
+            let mut violations = self
+                .partition(&self.roots)
+                .iter()
+                .flat_map(|(_, index)| index.check(&rules))
+                .collect::<Vec<_>>();
+            violation::sort(&mut violations);
+            return violations;
+        }
+
+        // If tags are scoped, each scope is checked on its own. The tags outside any scope are
+        // visible to all of them, and the references which match those tags count as references
+        // to them.
+        if !rules.scopes.is_empty() {
+            let mut partitions = self.partition(&rules.scopes);
+
+            // The first partition holds the files outside any scope, since `None` sorts first.
+            let (_, shared) = partitions.remove(0);
+            let shared_tags = shared.tags.keys().cloned().collect::<HashSet<_>>();
+            let inherited_refs = partitions
+                .iter()
+                .flat_map(|(_, index)| {
+                    index
+                        .refs
+                        .iter()
+                        .filter(|r#ref| !index.tags.contains_key(&*index.key(&r#ref.label)))
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+
+            let mut violations = shared.check_scope(rules, &HashSet::new(), &inherited_refs);
+            for (_, index) in &partitions {
+                violations.extend(index.check_scope(rules, &shared_tags, &[]));
+            }
+            violation::sort(&mut violations);
+            return violations;
+        }
+
+        self.check_scope(rules, &HashSet::new(), &[])
+    }
+
+    // This function runs all the checks on the labels in one scope (see `Rules::scopes`). The
+    // inherited tags are visible to the references too, and the inherited references count as
+    // references to the tags.
+    fn check_scope(
+        &self,
+        rules: &Rules,
+        inherited_tags: &HashSet<String>,
+        inherited_refs: &[Directive],
+    ) -> Vec<Violation> {
+        // The clock isn't available everywhere (e.g., in WebAssembly), so it's only read if the
+        // time is logged.
+        let start = log::log_enabled!(log::Level::Info).then(Instant::now);
+
+        // References which use aliases are checked as if they used the labels of the tags instead,
+        // and reported separately.
+        let aliases = self.resolve_aliases(rules);
+        let refs = aliases::apply(&self.refs, &aliases, self.case_insensitive_labels);
+        let all_refs = if inherited_refs.is_empty() {
+            Cow::Borrowed(&*refs)
+        } else {
+            Cow::Owned([&*refs, inherited_refs].concat())
+        };
+        let tags = self
+            .tags
+            .keys()
+            .chain(inherited_tags)
+            .map(String::as_str)
+            .collect::<HashSet<_>>();
+
+        let mut violations = self.check_tags(rules, &all_refs);
+        violations.extend(self.check_labels(rules, &self.tags, &tags, &aliases, &refs));
+        self.finish(rules, &mut violations);
+
+        if let Some(start) = start {
+            log::info!(
+                "Checked the labels in {:.2?} and found {}.",
+                start.elapsed(),
+                count::count(violations.len(), "violation"),
+            );
+        }
+        violations
+    }
+
+    // This function maps the aliases for tags, both declared in the files and configured, to the
+    // labels of their tags.
+    pub(crate) fn resolve_aliases(&self, rules: &Rules) -> BTreeMap<String, String> {
+        aliases::resolve(
+            &self.tags,
+            &self.aliases,
+            &rules.aliases,
+            self.case_insensitive_labels,
+        )
+    }
+
+    // This function runs the checks which involve all the tags at once, given the references to
+    // them with any aliases resolved.
+    pub(crate) fn check_tags(&self, rules: &Rules, refs: &[Directive]) -> Vec<Violation> {
+        let mut violations = Vec::new();
+
+        // Check for duplicate tags.
+        violations.extend(duplicates::check(&self.tags, &rules.duplicates));
+
+        // Check the naming conventions, including the ones for namespaces.
+        violations.extend(naming::check(&self.tags, &rules.naming));
+        violations.extend(namespaces::check(&self.tags, &[], &rules.namespaces));
+
+        // Check that the tags in paired paths are referenced from each other.
+        violations.extend(pairings::check(
+            &self.tags,
+            refs,
+            &rules.pairings,
+            self.case_insensitive_labels,
+        ));
+
+        // Check that the tags have sections in the documentation, if requested.
+        if let Some(docs) = &rules.docs {
+            violations.extend(docs::check(
+                &self.tags,
+                refs,
+                &self.headings,
+                docs,
+                self.case_insensitive_labels,
+            ));
+        }
+
+        // Check that no tag is past its expiry date.
+        violations.extend(expiry::check(self.tags.values().flatten(), Date::today()));
+
+        // Check for unused tags, if requested.
+        if rules.deny_unused {
+            violations.extend(unused::check(
+                &self.tags,
+                refs,
+                &rules.allow_unused,
+                self.case_insensitive_labels,
+            ));
+        }
+
+        // Check that the tags which require several references have enough of them.
+        violations.extend(min_refs::check(
+            &self.tags,
+            refs,
+            &rules.min_refs,
+            self.case_insensitive_labels,
+        ));
+
+        violations
+    }
+
+    // This function runs the checks on each label other than the tags, given the tags they may
+    // refer to (keyed as in `tags_map`, but possibly more of them), the resolved aliases, and the
+    // references with the aliases resolved. The tags needn't be in this index, so the labels can
+    // be checked a file at a time (see `streaming`).
+    pub(crate) fn check_labels<S: AsRef<str> + Borrow<str> + Eq + Hash>(
+        &self,
+        rules: &Rules,
+        tags_map: &BTreeMap<String, Vec<Directive>>,
+        tags: &HashSet<S>,
+        aliases: &BTreeMap<String, String>,
+        refs: &[Directive],
+    ) -> Vec<Violation> {
+        let mut violations = Vec::new();
+
+        // Warn about files which may contain labels that couldn't be decoded.
+        violations.extend(
+            self.undecodable
+                .iter()
+                .filter(|path| self.drop_ins.is_enabled(Kind::UndecodableFile, path))
+                .map(|path| Violation {
+                    kind: Kind::UndecodableFile,
+                    severity: Severity::Warning,
+                    message: format!(
+                        "Unable to decode {}, so some labels in it may have been missed. If it \
+                         isn't UTF-8, set a fallback encoding.",
+                        path.to_string_lossy(),
+                    ),
+                    directives: Vec::new(),
+                    suggestions: Vec::new(),
+                }),
+        );
+
+        // Report the references which use aliases.
+        violations.extend(aliases::check(
+            &self.refs,
+            aliases,
+            rules.deny_deprecated_aliases,
+            self.case_insensitive_labels,
+        ));
+
+        // Check that pinned references match their tags.
+        violations.extend(pins::check(
+            &self.pins,
+            tags_map,
+            aliases,
+            self.case_insensitive_labels,
+            self.tree.as_deref(),
+        ));
+
+        // Check that the references to namespaces are allowed where they are.
+        violations.extend(namespaces::check(&BTreeMap::new(), refs, &rules.namespaces));
+
+        // Check the custom labels.
+        violations.extend(custom_labels::check(&self.custom, &rules.custom_labels));
+
+        // Check that no label is past its expiry date.
+        violations.extend(expiry::check(self.references(), Date::today()));
+
+        // Check the tag references.
+        violations.extend(tag_references::check(
+            tags,
+            &rules.external_tags,
+            refs,
+            self.case_insensitive_labels,
+        ));
+
+        // Check the file references.
+        violations.extend(file_references::check(
+            &self.files,
+            &self.anchors,
+            self.relative_paths,
+            &self.roots,
+            rules.strict_path_case,
+            self.tree.as_deref(),
+        ));
+
+        // Check the directory references.
+        violations.extend(dir_references::check(
+            &self.dirs,
+            self.relative_paths,
+            &self.roots,
+            rules.deny_empty_dirs,
+            rules.strict_path_case,
+            self.tree.as_deref(),
+        ));
+
+        // Check the links in Markdown files, if requested.
+        if rules.markdown_links {
+            violations.extend(markdown::check_links(
+                &self.links,
+                &self.roots,
+                rules.strict_path_case,
+                self.tree.as_deref(),
+            ));
+        }
+
+        violations
+    }
+
+    // This function drops the violations in directories where drop-in files turned the checks
+    // off, adjusts the severities as configured, and puts the violations in a stable order.
+    pub(crate) fn finish(&self, rules: &Rules, violations: &mut Vec<Violation>) {
+        // A violation involving several labels is kept if any of them is still checked.
+        violations.retain(|violation| {
+            violation.directives.is_empty()
+                || violation
+                    .directives
+                    .iter()
+                    .any(|directive| self.drop_ins.is_enabled(violation.kind, &directive.path))
+        });
+
+        severities::apply(&rules.severities, violations);
+        violation::sort(violations);
+    }
+
+    // This function removes the violations which are suppressed by comments, and returns them.
+    // It's separate from `check` so the URL checks can be suppressed too.
+    pub fn suppress(&self, violations: &mut Vec<Violation>) -> Vec<Violation> {
+        let suppressed = suppressions::suppress(&self.suppressions, violations);
+        for violation in &suppressed {
+            log::debug!("Suppressed: {}", violation.message.trim_end());
+        }
+        suppressed
+    }
+
+    // This function checks that the URL references point to reachable URLs. It's separate from
+    // `check` because it requires network access and can be slow.
+    #[must_use]
+    pub fn check_urls(&self, rules: &Rules) -> Vec<Violation> {
+        let start = Instant::now();
+        let mut violations = url_references::check(&self.urls);
+        severities::apply(&rules.severities, &mut violations);
+        violation::sort(&mut violations);
+        log::info!(
+            "Checked {} in {:.2?}.",
+            count::count(self.urls.len(), "URL reference"),
+            start.elapsed(),
+        );
+        violations
+    }
+
+    // This function checks that the custom labels of kinds with issue trackers refer to open
+    // issues. Like `check_urls`, it's separate from `check` because it requires network access.
+    #[must_use]
+    pub fn check_issues(&self, rules: &Rules) -> Vec<Violation> {
+        let start = Instant::now();
+        let mut violations = issues::check(&self.custom, &rules.custom_labels);
+        severities::apply(&rules.severities, &mut violations);
+        violation::sort(&mut violations);
+        log::info!("Checked the issues in {:.2?}.", start.elapsed());
+        violations
+    }
+
+    // This function runs the external validators on all the labels. Like `check_urls`, it's
+    // separate from `check`, since the validators may be slow, and they only see the labels in
+    // the index.
+    #[must_use]
+    pub fn check_validators(&self, rules: &Rules) -> Vec<Violation> {
+        let start = Instant::now();
+        let mut violations = validators::check(self.labels(), &rules.validators);
+        severities::apply(&rules.severities, &mut violations);
+        violation::sort(&mut violations);
+        log::info!(
+            "Ran {} in {:.2?}.",
+            count::count(rules.validators.len(), "validator"),
+            start.elapsed(),
+        );
+        violations
+    }
+
+    // This function runs `check`, followed by `check_urls` and `check_issues` if requested and
+    // `check_validators`, and sorts the violations together.
+    #[must_use]
+    pub fn check_all(&self, rules: &Rules, urls: bool, issues: bool) -> Vec<Violation> {
+        let mut violations = self.check(rules);
+        if urls {
+            violations.extend(self.check_urls(rules));
+        }
+        if issues {
+            violations.extend(self.check_issues(rules));
+        }
+        violations.extend(self.check_validators(rules));
+        violation::sort(&mut violations);
+        violations
+    }
+
+    // This function returns all the labels in the index, except for aliases, anchors, and the
+    // pinned forms of references.
+    pub fn labels(&self) -> impl Iterator<Item = &Directive> {
+        self.tags.values().flatten().chain(self.references())
+    }
+
+    // This function returns the labels in the index which aren't tags, except for aliases,
+    // anchors, and the pinned forms of references.
+    fn references(&self) -> impl Iterator<Item = &Directive> {
+        self.refs
+            .iter()
+            .chain(&self.files)
+            .chain(&self.dirs)
+            .chain(&self.urls)
+            .chain(&self.custom)
+    }
+
+    // This function counts the directives in the index.
+    #[must_use]
+    pub fn summary(&self) -> Summary {
+        Summary {
+            tags: self.tags.len(),
+            tag_references: self.refs.len(),
+            file_references: self.files.len(),
+            directory_references: self.dirs.len(),
+            url_references: self.urls.len(),
+            custom_labels: self.custom.len(),
+            files_scanned: self.files_scanned,
+            files_too_large: self.files_too_large.clone(),
+        }
+    }
+}
+
+// This struct knows which files to scan and how to parse them.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Clone, Debug)]
+pub struct Scanner {
+    pub paths: Vec<PathBuf>,
+    pub walk_options: walk::Options,
+    pub tag_regex: Regex,
+
+    // The regex for the opening of block tags, if they're supported with the tag syntax
+    pub tag_block_regex: Option<Regex>,
+
+    pub ref_regex: Regex,
+    pub file_regex: Regex,
+    pub dir_regex: Regex,
+    pub url_regex: Regex,
+
+    // The regexes for custom labels, each paired with its sigil
+    pub custom_regexes: Vec<(String, Regex)>,
+
+    pub comments_only: bool,
+
+    // Whether the files in archives (e.g., `.tar.gz` and `.zip` files) are scanned, as if the
+    // archives were directories
+    pub archives: bool,
+
+    // The extractors for files which aren't scanned as plain text (e.g., notebooks). The first one
+    // which handles a file is used.
+    pub extractors: Vec<Arc<dyn Extractor>>,
+
+    // The encoding for files which aren't valid UTF-8 and don't start with a byte order mark
+    pub fallback_encoding: Option<&'static Encoding>,
+
+    // Whether tags and references match regardless of the case of their labels
+    pub case_insensitive_labels: bool,
+
+    // Whether file and directory references starting with `./` or `../` are relative to the file
+    // containing them, rather than the working directory
+    pub relative_paths: bool,
+
+    // The directories which file and directory references in them are resolved from, rather than
+    // the working directory (see `walk::resolve`)
+    pub roots: Vec<PathBuf>,
+
+    // Whether large files are memory-mapped rather than read (see `mapped::read`). Long-running
+    // processes read them, since a file truncated while it's mapped would crash the process.
+    pub memory_map: bool,
+}
+
+impl Scanner {
+    // This function returns a string which accounts for everything besides the file contents that
+    // affects parsing. It's used to invalidate caches.
+    #[must_use]
+    pub fn cache_key(&self) -> String {
+        let mut key = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}",
+            env!("CARGO_PKG_VERSION"),
+            self.comments_only,
+            self.archives,
+            self.fallback_encoding.map_or("", Encoding::name),
+            self.tag_regex.as_str(),
+            self.tag_block_regex.as_ref().map_or("", Regex::as_str),
+            self.ref_regex.as_str(),
+            self.file_regex.as_str(),
+            self.dir_regex.as_str(),
+            self.url_regex.as_str(),
+        );
+        for (sigil, regex) in &self.custom_regexes {
+            let _ = write!(key, "\n{sigil}\n{}", regex.as_str());
+        }
+        for extractor in &self.extractors {
+            let _ = write!(key, "\n{}", extractor.name());
+        }
+        key
+    }
+
+    // This function parses the directives from the contents of a file. The path determines the
+    // comment syntax (if only comments are considered) and is recorded in the directives, but the
+    // file itself isn't read, so this works for contents which aren't on disk (e.g., unsaved editor
+    // buffers). If archives are scanned, the directives in the files in an archive are returned
+    // together, with paths like `docs.tar.gz!/guide.md`.
+    #[must_use]
+    pub fn parse(&self, path: &Path, contents: &[u8]) -> Directives {
+        if !self.archives || !archives::is_archive(path) {
+            return self.parse_file(path, contents);
+        }
+
+        // Archives which can't be read are skipped. Archives inside archives are treated as
+        // ordinary files.
+        let mut directives = Directives::default();
+        match Archive::parse(path, contents, self.walk_options.max_filesize) {
+            Ok(archive) => {
+                for (inner, contents) in archive.files() {
+                    directives.extend(self.parse_file(&archives::join(path, inner), contents));
+                }
+                for inner in archive.too_large() {
+                    let inner = archives::join(path, inner);
+                    log::info!(
+                        "Skipping {}, since it's larger than the limit of {} bytes.",
+                        inner.to_string_lossy(),
+                        self.walk_options.max_filesize.unwrap_or_default(),
+                    );
+                    directives.too_large.push(inner);
+                }
+            }
+            Err(error) => log::warn!("{error}"),
+        }
+        directives
+    }
+
+    // This function parses the directives from the contents of a file, which isn't an archive. If
+    // an extractor handles the file, the directives are parsed from the text it extracts instead.
+    // Files which it fails to extract text from are parsed as they are.
+    fn parse_file(&self, path: &Path, contents: &[u8]) -> Directives {
+        // Drop-in files may change the sigils for the directory containing the file.
+        let settings = self
+            .walk_options
+            .drop_ins
+            .settings_for(path)
+            .unwrap_or_else(|error| {
+                log::warn!("{error}");
+                Arc::default()
+            });
+        let regexes = &settings.regexes;
+        let parse = |path: &Path, contents: &[u8]| {
+            directive::parse(
+                regexes.tag.as_ref().map_or(&self.tag_regex, |(tag, _)| tag),
+                regexes
+                    .tag
+                    .as_ref()
+                    .map_or(self.tag_block_regex.as_ref(), |(_, block)| Some(block)),
+                regexes.r#ref.as_ref().unwrap_or(&self.ref_regex),
+                regexes.file.as_ref().unwrap_or(&self.file_regex),
+                regexes.dir.as_ref().unwrap_or(&self.dir_regex),
+                regexes.url.as_ref().unwrap_or(&self.url_regex),
+                &self.custom_regexes,
+                self.comments_only,
+                self.fallback_encoding,
+                path,
+                contents,
+            )
+        };
+
+        let extracted = self
+            .extractors
+            .iter()
+            .find(|extractor| extractor.handles(path))
+            .map(|extractor| extractor.extract(path, contents));
+        match extracted {
+            Some(Ok(sections)) => {
+                let mut directives = Directives::default();
+                for section in sections {
+                    let mut parsed = parse(&section.syntax, section.text.as_bytes());
+                    extractors::relocate(&mut parsed, path, &section);
+                    directives.extend(parsed);
+                }
+                directives
+            }
+            Some(Err(error)) => {
+                log::warn!("{error}");
+                parse(path, contents)
+            }
+            None => parse(path, contents),
+        }
+    }
+
+    // This function scans all the files and builds an index of the directives in them. If a cache
+    // is given, the directives for unchanged files are taken from it, and a new cache reflecting
+    // the current state of the files is returned too. Files are read and parsed in parallel (with
+    // large files memory-mapped rather than read into memory if enabled, see `mapped::read`), but
+    // the directives are added to the index in order of path so the output is deterministic.
+    pub fn scan(&self, cache: Option<Arc<Cache>>) -> Result<(Index, Option<Cache>), String> {
+        let start = Instant::now();
+        let cache_hits = Arc::new(AtomicUsize::new(0));
+        let cache_hits_clone = cache_hits.clone();
+        let parsed_files = Arc::new(Mutex::new(Vec::new()));
+        let new_cache = Arc::new(Mutex::new(Cache::new(&self.cache_key())));
+        let parsed_files_clone = parsed_files.clone();
+        let new_cache_clone = new_cache.clone();
+        let cache_clone = cache.clone();
+        let scanner = self.clone();
+
+        // Changes to the drop-in files since the last scan are picked up.
+        self.walk_options.drop_ins.clear();
+
+        let walked = walk::walk(&self.paths, &self.walk_options, move |file_path, file| {
+            // Reuse the cached directives if the file hasn't changed. Otherwise, parse the file.
+            // The cache doesn't account for sigils changed by drop-in files, so files affected by
+            // them are always parsed.
+            let changes_sigils = scanner
+                .walk_options
+                .drop_ins
+                .settings_for(file_path)
+                .is_ok_and(|settings| settings.changes_sigils());
+            let fingerprint = file
+                .metadata()
+                .ok()
+                .filter(|_| !changes_sigils)
+                .map(|metadata| Fingerprint::new(&metadata));
+            let cached = cache_clone
+                .as_ref()
+                .zip(fingerprint.as_ref())
+                .and_then(|(cache, fingerprint)| cache.get(file_path, fingerprint))
+                .cloned();
+            let directives = if let Some(directives) = cached {
+                log::debug!(
+                    "Reusing the cached labels for {}.",
+                    file_path.to_string_lossy(),
+                );
+                cache_hits_clone.fetch_add(1, Ordering::SeqCst);
+                directives
+            } else {
+                scanner.parse(file_path, &mapped::read(file, scanner.memory_map))
+            };
+
+            // Record the directives for the next run. The `unwrap` is safe assuming no poisoning.
+            if cache_clone.is_some()
+                && let Some(fingerprint) = fingerprint
+            {
+                new_cache_clone
+                    .lock()
+                    .unwrap()
+                    .insert(file_path, fingerprint, directives.clone());
+            }
+
+            // The `unwrap` is safe assuming no poisoning.
+            parsed_files_clone
+                .lock()
+                .unwrap()
+                .push((file_path.to_owned(), directives));
+        })?;
+
+        // Merge the directives into the index in a deterministic order, regardless of the order in
+        // which the threads finished. The `unwrap` is safe assuming no poisoning.
+        let mut parsed_files = take(&mut *parsed_files.lock().unwrap());
+        parsed_files.sort_by(|(x, _), (y, _)| x.cmp(y));
+        let files_scanned = walked.files_scanned;
+        let mut index = Index {
+            files_scanned,
+            files_too_large: walked.files_too_large,
+            case_insensitive_labels: self.case_insensitive_labels,
+            relative_paths: self.relative_paths,
+            roots: self.roots.clone(),
+            drop_ins: self.walk_options.drop_ins.clone(),
+            ..Index::default()
+        };
+        for (_, directives) in parsed_files {
+            index.insert(directives);
+        }
+        index.files_too_large.sort();
+
+        // The `unwrap` is safe assuming no poisoning.
+        let new_cache = cache.map(|_| take(&mut *new_cache.lock().unwrap()));
+
+        if new_cache.is_some() {
+            log::info!(
+                "Scanned {} in {:.2?}, reusing the cached labels for {}.",
+                count::count(files_scanned, "file"),
+                start.elapsed(),
+                count::count(cache_hits.load(Ordering::SeqCst), "file"),
+            );
+        } else {
+            log::info!(
+                "Scanned {} in {:.2?}.",
+                count::count(files_scanned, "file"),
+                start.elapsed(),
+            );
+        }
+        Ok((index, new_cache))
+    }
+
+    // This function builds an index of the directives in the files as of a Git revision rather
+    // than in the working tree. The files are read from Git, so only tracked files are included,
+    // and file and directory references are checked against the revision too. Drop-in files are
+    // still read from the working tree.
+    pub fn scan_revision(&self, revision: &str) -> Result<Index, String> {
+        let start = Instant::now();
+        self.walk_options.drop_ins.clear();
+
+        let files = git::files_at(revision, &self.paths, &self.walk_options)?;
+        let index = self.index_files(files, Tree::at(revision)?);
+
+        log::info!(
+            "Scanned {} as of {revision} in {:.2?}.",
+            count::count(index.files_scanned, "file"),
+            start.elapsed(),
+        );
+        Ok(index)
+    }
+
+    // This function builds an index of the directives in files whose contents are given, rather
+    // than read from disk (e.g., in a browser). File and directory references are checked against
+    // the given files. Unlike `scan`, this doesn't read the clock, which isn't available
+    // everywhere.
+    #[must_use]
+    pub fn scan_files(&self, files: Vec<(PathBuf, Vec<u8>)>) -> Index {
+        let tree = Tree::from_files(
+            files
+                .iter()
+                .map(|(path, contents)| (path.as_path(), contents.as_slice())),
+        );
+        self.index_files(files, tree)
+    }
+
+    // This function builds an index of the directives in the given files, which are in the given
+    // tree. Files larger than the limit are skipped.
+    fn index_files(&self, mut files: Vec<(PathBuf, Vec<u8>)>, tree: Tree) -> Index {
+        files.sort_by(|(x, _), (y, _)| x.cmp(y));
+        let (files, too_large) = files.into_iter().partition::<Vec<_>, _>(|(_, contents)| {
+            self.walk_options
+                .max_filesize
+                .is_none_or(|max_filesize| contents.len() as u64 <= max_filesize)
+        });
+        let mut index = Index {
+            files_scanned: files.len(),
+            files_too_large: too_large.into_iter().map(|(path, _)| path).collect(),
+            case_insensitive_labels: self.case_insensitive_labels,
+            relative_paths: self.relative_paths,
+            roots: self.roots.clone(),
+            drop_ins: self.walk_options.drop_ins.clone(),
+            tree: Some(Arc::new(tree)),
+            ..Index::default()
+        };
+        for (path, contents) in files {
+            index.insert(self.parse(&path, &contents));
+        }
+        index.files_too_large.sort();
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        archives,
+        cache::{self, Cache},
+        directive::{Directive, Directives, Type, compile_block_regex, compile_directive_regex},
+        extractors,
+        index::{Index, Rules, Scanner},
+        violation::Kind,
+        walk,
+    };
+    use std::{
+        env, fs,
+        io::{Cursor, Write},
+        path::{Path, PathBuf},
+        process,
+        sync::Arc,
+        time::{SystemTime, UNIX_EPOCH},
+    };
+    use zip::{ZipWriter, write::SimpleFileOptions};
+
+    fn scanner(root: PathBuf) -> Scanner {
+        Scanner {
+            paths: vec![root],
+            walk_options: walk::Options::default(),
+            tag_regex: compile_directive_regex("tag"),
+            tag_block_regex: Some(compile_block_regex("tag")),
+            ref_regex: compile_directive_regex("ref"),
+            file_regex: compile_directive_regex("file"),
+            dir_regex: compile_directive_regex("dir"),
+            url_regex: compile_directive_regex("url"),
+            custom_regexes: Vec::new(),
+            comments_only: false,
+            archives: false,
+            extractors: extractors::defaults(),
+            fallback_encoding: None,
+            case_insensitive_labels: false,
+            relative_paths: false,
+            roots: Vec::new(),
+            memory_map: true,
+        }
+    }
+
+    #[test]
+    fn scan_skips_cache() {
+        let root = env::temp_dir().join(format!(
+            "tagref-index-{}-{}",
+            process::id(),
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos(),
+        ));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(
+            root.join("a.rs"),
+            "// [?tag:foo] See [?ref:foo] and [?file:a.rs].\n".replace('?', ""),
+        )
+        .unwrap();
+        let scanner = scanner(root.clone());
+        let path = root.join(cache::DEFAULT_PATH);
+
+        // The cache is saved in the scanned directory, as it is by default, and contains the
+        // labels it records.
+        let mut counts = Vec::new();
+        for _ in 0..2_usize {
+            let cache = Cache::load(&path, &scanner.cache_key());
+            let (index, new_cache) = scanner.scan(Some(Arc::new(cache))).unwrap();
+            new_cache.unwrap().save(&path).unwrap();
+            counts.push((
+                index.tags.len(),
+                index.refs.len(),
+                index.files.len(),
+                index.files_scanned,
+            ));
+        }
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(counts, vec![(1, 1, 1, 1), (1, 1, 1, 1)]);
+    }
+
+    #[test]
+    fn scan_archive_too_large() {
+        let root = env::temp_dir().join(format!(
+            "tagref-index-archive-{}-{}",
+            process::id(),
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos(),
+        ));
+        fs::create_dir_all(&root).unwrap();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("big.md", SimpleFileOptions::default())
+            .unwrap();
+        write!(
+            writer,
+            "{}{}",
+            "[?tag:foo]\n".replace('?', ""),
+            "x".repeat(10_000)
+        )
+        .unwrap();
+        writer
+            .start_file("small.md", SimpleFileOptions::default())
+            .unwrap();
+        writer
+            .write_all("[?tag:bar]\n".replace('?', "").as_bytes())
+            .unwrap();
+        fs::write(root.join("docs.zip"), writer.finish().unwrap().into_inner()).unwrap();
+        let mut scanner = scanner(root.clone());
+        scanner.archives = true;
+        // The archive itself is within the limit, since the large file is compressed.
+        scanner.walk_options.max_filesize = Some(1000);
+
+        let (index, _) = scanner.scan(None).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(index.tags.keys().collect::<Vec<_>>(), vec!["bar"]);
+        assert_eq!(
+            index.files_too_large,
+            vec![archives::join(&root.join("docs.zip"), Path::new("big.md"))],
+        );
+    }
+
+    #[test]
+    fn remove() {
+        let mut index = Index::default();
+        index.insert(Directives {
+            tags: vec![
+                Directive::new(Type::Tag, "foo", "./a.rs", 1),
+                Directive::new(Type::Tag, "bar", "./b.rs", 1),
+            ],
+            refs: vec![
+                Directive::new(Type::Ref, "bar", "./a.rs", 1),
+                Directive::new(Type::Ref, "foo", "./b.rs", 1),
+            ],
+            files: Vec::new(),
+            dirs: Vec::new(),
+            urls: Vec::new(),
+            custom: Vec::new(),
+            undecodable: Vec::new(),
+            too_large: Vec::new(),
+            suppressions: Vec::new(),
+            aliases: Vec::new(),
+            anchors: Vec::new(),
+            pins: Vec::new(),
+            links: Vec::new(),
+            headings: Vec::new(),
+        });
+
+        let removed = index.remove(Path::new("a.rs"));
+
+        assert_eq!(removed.tags.len(), 1);
+        assert_eq!(removed.tags[0].label, "foo");
+        assert_eq!(removed.refs.len(), 1);
+        assert_eq!(removed.refs[0].label, "bar");
+        assert_eq!(index.tags.keys().collect::<Vec<_>>(), vec!["bar"]);
+        assert_eq!(index.refs.len(), 1);
+        assert_eq!(&*index.refs[0].path, Path::new("./b.rs"));
+    }
+
+    #[test]
+    fn insert_case_insensitive() {
+        let mut index = Index {
+            case_insensitive_labels: true,
+            ..Index::default()
+        };
+        index.insert(Directives {
+            tags: vec![
+                Directive::new(Type::Tag, "Foo", "./a.rs", 1),
+                Directive::new(Type::Tag, "foo", "./b.rs", 1),
+            ],
+            refs: Vec::new(),
+            files: Vec::new(),
+            dirs: Vec::new(),
+            urls: Vec::new(),
+            custom: Vec::new(),
+            undecodable: Vec::new(),
+            too_large: Vec::new(),
+            suppressions: Vec::new(),
+            aliases: Vec::new(),
+            anchors: Vec::new(),
+            pins: Vec::new(),
+            links: Vec::new(),
+            headings: Vec::new(),
+        });
+
+        assert_eq!(index.tags.keys().collect::<Vec<_>>(), vec!["foo"]);
+        assert_eq!(index.tags["foo"][0].label, "Foo");
+        assert_eq!(index.key("FOO"), "foo");
+    }
+
+    #[test]
+    fn check_sorted() {
+        let mut index = Index::default();
+        let mut second = Directive::new(Type::Ref, "foo", "./a.rs", 1);
+        second.line_number = 2;
+        index.insert(Directives {
+            tags: Vec::new(),
+            refs: vec![
+                Directive::new(Type::Ref, "baz", "./b.rs", 1),
+                second,
+                Directive::new(Type::Ref, "qux", "./a.rs", 1),
+                Directive::new(Type::Ref, "bar", "./a.rs", 1),
+            ],
+            files: Vec::new(),
+            dirs: Vec::new(),
+            urls: Vec::new(),
+            custom: Vec::new(),
+            undecodable: Vec::new(),
+            too_large: Vec::new(),
+            suppressions: Vec::new(),
+            aliases: Vec::new(),
+            anchors: Vec::new(),
+            pins: Vec::new(),
+            links: Vec::new(),
+            headings: Vec::new(),
+        });
+
+        let violations = index.check(&Rules::default());
+
+        assert_eq!(
+            violations
+                .iter()
+                .map(|violation| violation.directives[0].label.as_str())
+                .collect::<Vec<_>>(),
+            vec!["bar", "qux", "foo", "baz"],
+        );
+    }
+
+    #[test]
+    fn check_scopes() {
+        let mut index = Index::default();
+        index.insert(Directives {
+            tags: vec![
+                Directive::new(Type::Tag, "foo", "./a/x.rs", 1),
+                Directive::new(Type::Tag, "foo", "./b/x.rs", 1),
+                Directive::new(Type::Tag, "bar", "./c.rs", 1),
+                Directive::new(Type::Tag, "baz", "./c.rs", 1),
+            ],
+            refs: vec![
+                Directive::new(Type::Ref, "foo", "./a/y.rs", 1),
+                Directive::new(Type::Ref, "bar", "./b/y.rs", 1),
+                Directive::new(Type::Ref, "foo", "./c.rs", 1),
+            ],
+            files: Vec::new(),
+            dirs: Vec::new(),
+            urls: Vec::new(),
+            custom: Vec::new(),
+            undecodable: Vec::new(),
+            too_large: Vec::new(),
+            suppressions: Vec::new(),
+            aliases: Vec::new(),
+            anchors: Vec::new(),
+            pins: Vec::new(),
+            links: Vec::new(),
+            headings: Vec::new(),
+        });
+
+        let violations = index.check(&Rules {
+            deny_unused: true,
+            scopes: vec![PathBuf::from("a"), PathBuf::from("b")],
+            ..Rules::default()
+        });
+
+        // The tags in different scopes don't clash, the reference in `b` matches the tag outside
+        // any scope, and the reference outside any scope doesn't match the tags in the scopes.
+        assert_eq!(
+            violations
+                .iter()
+                .map(|violation| (violation.kind, violation.directives[0].path.to_path_buf()))
+                .collect::<Vec<_>>(),
+            vec![
+                (Kind::UnusedTag, PathBuf::from("./b/x.rs")),
+                (Kind::UnusedTag, PathBuf::from("./c.rs")),
+                (Kind::DanglingRef, PathBuf::from("./c.rs")),
+            ],
+        );
+    }
+}
@@ -0,0 +1,75 @@
+use std::{fs, io::ErrorKind, path::Path};
+
+// This is the starter configuration file. It only has the settings which new projects are most
+// likely to change, and the README has the rest.
+pub const CONFIG: &str = r#"# The configuration for Tagref. Command-line flags take precedence over
+# these settings. See https://github.com/stepchowfun/tagref#configuration-file
+# for the rest of them.
+
+# The paths to scan
+paths = ["."]
+
+# Files and directories to skip, in addition to the ones ignored by .gitignore and similar files
+exclude = []
+
+# Only match labels inside comments
+comments-only = false
+
+# Report tags which aren't referenced anywhere as errors
+deny-unused = false
+"#;
+
+// This is where the GitHub Actions workflow is written.
+pub const WORKFLOW_PATH: &str = ".github/workflows/tagref.yml";
+
+// This is a GitHub Actions workflow which checks the labels on every push and pull request, and
+// annotates the offending lines.
+pub const WORKFLOW: &str = "name: Tagref
+on: [push, pull_request]
+jobs:
+  tagref:
+    runs-on: ubuntu-latest
+    steps:
+      - uses: actions/checkout@v4
+      - name: Install Tagref
+        run: |
+          curl https://raw.githubusercontent.com/stepchowfun/tagref/main/install.sh -LSfs \\
+            | PREFIX=. sh
+      - name: Check the labels
+        run: ./tagref --format github check
+";
+
+// This function writes a file, unless it already exists and `force` isn't set. It returns whether
+// the file was written, or would have been in a dry run.
+pub fn write(path: &Path, contents: &str, force: bool, dry_run: bool) -> Result<bool, String> {
+    let write_error = |error| format!("Unable to write {}: {error}", path.to_string_lossy());
+    match fs::symlink_metadata(path) {
+        Ok(_) if !force => return Ok(false),
+        Ok(_) => {}
+        Err(error) if error.kind() == ErrorKind::NotFound => {}
+        Err(error) => return Err(write_error(error)),
+    }
+    if dry_run {
+        return Ok(true);
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(write_error)?;
+    }
+    fs::write(path, contents).map_err(write_error)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{config::parse, init::CONFIG};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn config_parses() {
+        let config = parse(CONFIG, Path::new("tagref.toml")).unwrap();
+
+        assert_eq!(config.paths, Some(vec![PathBuf::from(".")]));
+        assert_eq!(config.comments_only, Some(false));
+    }
+}
@@ -0,0 +1,299 @@
+use crate::{
+    custom_labels::Definition,
+    directive::{Directive, Type},
+    violation::{Kind, Severity, Violation},
+};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use serde::Deserialize;
+use serde_json::Value;
+use std::{collections::BTreeMap, env, sync::Mutex, thread, time::Duration};
+use ureq::Agent;
+
+// This is how long to wait for each issue tracker to respond.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+// This is the maximum number of requests in flight at once.
+const CONCURRENCY: usize = 8;
+
+// This enum represents the supported issue trackers.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Service {
+    Github,
+    Gitlab,
+    Jira,
+}
+
+// This struct configures how the labels of a custom kind are looked up in an issue tracker, so
+// labels referring to closed issues can be reported.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Tracker {
+    pub service: Service,
+
+    // The base URL of the tracker. GitHub and GitLab default to their public instances, but Jira
+    // needs one, e.g., `https://example.atlassian.net`.
+    pub url: Option<String>,
+
+    // For GitHub and GitLab, the repository (e.g., `owner/repo`) of labels which are just issue
+    // numbers, like `123` or `#123`, rather than `owner/repo#123`
+    pub repository: Option<String>,
+
+    // The environment variable with the token for the API. It defaults to `GITHUB_TOKEN`,
+    // `GITLAB_TOKEN`, or `JIRA_TOKEN`. Public issues can be looked up without a token.
+    pub token_env: Option<String>,
+
+    // For Jira Cloud, the environment variable with the email address which goes with the token
+    pub user_env: Option<String>,
+}
+
+// This enum represents whether an issue is still open.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum State {
+    Open,
+    Closed,
+}
+
+impl Tracker {
+    // This function returns the URL of the API endpoint for an issue.
+    pub fn endpoint(&self, label: &str) -> Result<String, String> {
+        let (repository, number) = match label.rsplit_once('#') {
+            Some((repository, number)) if !repository.is_empty() => (Some(repository), number),
+            _ => (self.repository.as_deref(), label.trim_start_matches('#')),
+        };
+        let repository = || {
+            repository.ok_or_else(|| {
+                format!("`{label}` doesn't name a repository, and no default `repository` is set")
+            })
+        };
+        let base = self.url.as_deref().map(|url| url.trim_end_matches('/'));
+
+        match self.service {
+            Service::Github => Ok(format!(
+                "{}/repos/{}/issues/{number}",
+                base.unwrap_or("https://api.github.com"),
+                repository()?,
+            )),
+            Service::Gitlab => Ok(format!(
+                "{}/api/v4/projects/{}/issues/{number}",
+                base.unwrap_or("https://gitlab.com"),
+                repository()?.replace('/', "%2F"),
+            )),
+            Service::Jira => Ok(format!(
+                "{}/rest/api/2/issue/{label}?fields=status",
+                base.ok_or("Jira trackers need a `url`")?,
+            )),
+        }
+    }
+
+    // This function returns the value of the `Authorization` or `PRIVATE-TOKEN` header, if a token
+    // is available.
+    fn authorization(&self) -> Option<(&'static str, String)> {
+        let default = match self.service {
+            Service::Github => "GITHUB_TOKEN",
+            Service::Gitlab => "GITLAB_TOKEN",
+            Service::Jira => "JIRA_TOKEN",
+        };
+        let token = env::var(self.token_env.as_deref().unwrap_or(default)).ok()?;
+        let user = self.user_env.as_ref().and_then(|name| env::var(name).ok());
+
+        Some(match (self.service, user) {
+            (Service::Gitlab, _) => ("PRIVATE-TOKEN", token),
+            (Service::Jira, Some(user)) => (
+                "Authorization",
+                format!(
+                    "Basic {}",
+                    BASE64_STANDARD.encode(format!("{user}:{token}"))
+                ),
+            ),
+            _ => ("Authorization", format!("Bearer {token}")),
+        })
+    }
+
+    // This function determines the state of an issue from the response of the API.
+    pub fn state(&self, response: &str) -> Result<State, String> {
+        let value = serde_json::from_str::<Value>(response)
+            .map_err(|error| format!("Invalid response: {error}"))?;
+        let (pointer, closed) = match self.service {
+            Service::Github | Service::Gitlab => ("/state", "closed"),
+            Service::Jira => ("/fields/status/statusCategory/key", "done"),
+        };
+        match value.pointer(pointer).and_then(Value::as_str) {
+            Some(state) if state == closed => Ok(State::Closed),
+            Some(_) => Ok(State::Open),
+            None => Err(format!("The response doesn't have `{pointer}`.")),
+        }
+    }
+
+    // This function looks up the state of an issue.
+    fn lookup(&self, agent: &Agent, label: &str) -> Result<State, String> {
+        let mut request = agent
+            .get(&self.endpoint(label)?)
+            .header("Accept", "application/json");
+        if let Some((name, value)) = self.authorization() {
+            request = request.header(name, value);
+        }
+        let response = request
+            .call()
+            .map_err(|error| error.to_string())?
+            .body_mut()
+            .read_to_string()
+            .map_err(|error| error.to_string())?;
+        self.state(&response)
+    }
+}
+
+// This function checks that the custom labels of kinds with trackers refer to open issues. Each
+// issue is only looked up once, no matter how many times it's referenced. Issues which can't be
+// looked up (e.g., because the tracker is unreachable) are reported as warnings. It returns a
+// vector of violations.
+#[must_use]
+pub fn check(labels: &[Directive], definitions: &[Definition]) -> Vec<Violation> {
+    // Group the labels by tracker and issue.
+    let mut issues = BTreeMap::<(&str, &str), (&Tracker, Vec<&Directive>)>::new();
+    for label in labels {
+        let Type::Custom(sigil) = &label.r#type else {
+            continue;
+        };
+        let Some(tracker) = definitions
+            .iter()
+            .find(|definition| definition.sigil.trim() == sigil)
+            .and_then(|definition| definition.tracker.as_ref())
+        else {
+            continue;
+        };
+        issues
+            .entry((sigil, &label.label))
+            .or_insert_with(|| (tracker, Vec::new()))
+            .1
+            .push(label);
+    }
+
+    // Look up the issues in parallel, with a fixed number of threads taking them from a shared
+    // queue.
+    let agent: Agent = Agent::config_builder()
+        .timeout_global(Some(TIMEOUT))
+        .build()
+        .into();
+    let queue = Mutex::new(issues.into_iter());
+    let errors = Mutex::new(Vec::<Violation>::new());
+    thread::scope(|scope| {
+        for _ in 0..CONCURRENCY {
+            scope.spawn(|| {
+                loop {
+                    // The `unwrap` is safe assuming no poisoning.
+                    let next = queue.lock().unwrap().next();
+                    let Some(((_, issue), (tracker, directives))) = next else {
+                        break;
+                    };
+
+                    let (severity, explanation) = match tracker.lookup(&agent, issue) {
+                        Ok(State::Open) => continue,
+                        Ok(State::Closed) => (
+                            Severity::Error,
+                            "refers to an issue which is closed, so it may be obsolete".to_owned(),
+                        ),
+                        Err(error) => {
+                            (Severity::Warning, format!("couldn't be looked up: {error}"))
+                        }
+                    };
+
+                    // The `unwrap` is safe assuming no poisoning.
+                    errors
+                        .lock()
+                        .unwrap()
+                        .extend(directives.into_iter().map(|label| Violation {
+                            kind: Kind::ClosedIssue,
+                            severity,
+                            message: format!("{label} {explanation}."),
+                            directives: vec![label.clone()],
+                            suggestions: Vec::new(),
+                        }));
+                }
+            });
+        }
+    });
+
+    // Report the errors in a deterministic order. The `unwrap` is safe assuming no poisoning.
+    let mut errors = errors.into_inner().unwrap();
+    errors.sort_by(|x, y| {
+        let (x, y) = (&x.directives[0], &y.directives[0]);
+        (&x.path, x.line_number, &x.label).cmp(&(&y.path, y.line_number, &y.label))
+    });
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::issues::{Service, State, Tracker};
+
+    fn tracker(service: Service, url: Option<&str>, repository: Option<&str>) -> Tracker {
+        Tracker {
+            service,
+            url: url.map(ToOwned::to_owned),
+            repository: repository.map(ToOwned::to_owned),
+            token_env: None,
+            user_env: None,
+        }
+    }
+
+    #[test]
+    fn endpoint_github() {
+        let tracker = tracker(Service::Github, None, Some("owner/repo"));
+
+        assert_eq!(
+            tracker.endpoint("#12").unwrap(),
+            "https://api.github.com/repos/owner/repo/issues/12",
+        );
+        assert_eq!(
+            tracker.endpoint("other/repo#3").unwrap(),
+            "https://api.github.com/repos/other/repo/issues/3",
+        );
+    }
+
+    #[test]
+    fn endpoint_gitlab() {
+        let tracker = tracker(Service::Gitlab, Some("https://gitlab.example.com/"), None);
+
+        assert_eq!(
+            tracker.endpoint("group/project#7").unwrap(),
+            "https://gitlab.example.com/api/v4/projects/group%2Fproject/issues/7",
+        );
+        assert!(tracker.endpoint("7").is_err());
+    }
+
+    #[test]
+    fn endpoint_jira() {
+        assert_eq!(
+            tracker(Service::Jira, Some("https://example.atlassian.net"), None)
+                .endpoint("PROJ-123")
+                .unwrap(),
+            "https://example.atlassian.net/rest/api/2/issue/PROJ-123?fields=status",
+        );
+        assert!(
+            tracker(Service::Jira, None, None)
+                .endpoint("PROJ-123")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn state_from_response() {
+        let github = tracker(Service::Github, None, None);
+        let gitlab = tracker(Service::Gitlab, None, None);
+        let jira = tracker(Service::Jira, None, None);
+
+        assert_eq!(github.state(r#"{"state":"closed"}"#), Ok(State::Closed));
+        assert_eq!(gitlab.state(r#"{"state":"opened"}"#), Ok(State::Open));
+        assert_eq!(
+            jira.state(r#"{"fields":{"status":{"statusCategory":{"key":"done"}}}}"#),
+            Ok(State::Closed),
+        );
+        assert_eq!(
+            jira.state(r#"{"fields":{"status":{"statusCategory":{"key":"indeterminate"}}}}"#),
+            Ok(State::Open),
+        );
+        assert!(github.state("{}").is_err());
+        assert!(github.state("<html>").is_err());
+    }
+}
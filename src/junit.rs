@@ -0,0 +1,186 @@
+use std::fmt::Write;
+use tagref::violation::{Severity, Violation};
+
+// This function escapes text for use in XML attributes and character data.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for char in text.chars() {
+        match char {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // These control characters aren't allowed in XML at all.
+            '\u{0}'..='\u{8}' | '\u{b}' | '\u{c}' | '\u{e}'..='\u{1f}' => {}
+            _ => escaped.push(char),
+        }
+    }
+    escaped
+}
+
+// This function builds the attributes of the test case for a violation.
+fn attributes(violation: &Violation) -> String {
+    let message = violation.message.trim_end();
+    let mut attributes = format!(
+        r#"classname="{}" name="{}""#,
+        violation.kind.id(),
+        escape(
+            &violation
+                .directives
+                .first()
+                .map_or_else(|| message.to_owned(), ToString::to_string),
+        ),
+    );
+    if let Some(directive) = violation.directives.first() {
+        let _ = write!(
+            attributes,
+            r#" file="{}" line="{}""#,
+            escape(&directive.path.to_string_lossy()),
+            directive.line_number,
+        );
+    }
+    attributes
+}
+
+// This function builds a JUnit XML report containing one test case per violation. Errors are
+// reported as failures, warnings and informational notes as passing test cases with the message as
+// their output, and violations which were suppressed by comments as skipped test cases. A passing
+// test case with the summary is always included, since some CI systems treat reports without any
+// test cases as errors.
+pub fn report(violations: &[Violation], suppressed: &[Violation], summary: &str) -> String {
+    let failures = violations
+        .iter()
+        .filter(|violation| violation.severity == Severity::Error)
+        .count();
+    let skipped = suppressed.len();
+    let tests = violations.len() + skipped + 1;
+
+    let mut xml = String::new();
+    let _ = writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    let _ = writeln!(
+        xml,
+        r#"<testsuites name="tagref" tests="{tests}" failures="{failures}" errors="0">"#,
+    );
+    let _ = writeln!(
+        xml,
+        "  <testsuite name=\"tagref\" tests=\"{tests}\" failures=\"{failures}\" errors=\"0\" \
+         skipped=\"{skipped}\">",
+    );
+    let _ = writeln!(xml, r#"    <testcase classname="tagref" name="summary">"#);
+    let _ = writeln!(xml, "      <system-out>{}</system-out>", escape(summary));
+    let _ = writeln!(xml, "    </testcase>");
+
+    for violation in violations {
+        let message = violation.message.trim_end();
+        let _ = writeln!(xml, "    <testcase {}>", attributes(violation));
+        match violation.severity {
+            Severity::Error => {
+                let _ = writeln!(
+                    xml,
+                    r#"      <failure type="{}" message="{}">{}</failure>"#,
+                    violation.kind.id(),
+                    escape(message.lines().next().unwrap_or_default()),
+                    escape(message),
+                );
+            }
+            Severity::Warning | Severity::Info => {
+                let _ = writeln!(
+                    xml,
+                    "      <system-out>{}: {}</system-out>",
+                    if violation.severity == Severity::Warning {
+                        "Warning"
+                    } else {
+                        "Info"
+                    },
+                    escape(message),
+                );
+            }
+        }
+        let _ = writeln!(xml, "    </testcase>");
+    }
+
+    for violation in suppressed {
+        let _ = writeln!(xml, "    <testcase {}>", attributes(violation));
+        let _ = writeln!(
+            xml,
+            r#"      <skipped message="Suppressed: {}"/>"#,
+            escape(violation.message.trim_end()),
+        );
+        let _ = writeln!(xml, "    </testcase>");
+    }
+
+    let _ = writeln!(xml, "  </testsuite>");
+    let _ = writeln!(xml, "</testsuites>");
+
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        fixtures,
+        junit::{escape, report},
+    };
+    use tagref::{
+        directive::Type,
+        violation::{Kind, Severity, Violation},
+    };
+
+    fn violation(severity: Severity) -> Violation {
+        Violation {
+            kind: Kind::DanglingRef,
+            severity,
+            message: "No tag found for <foo> & more.".to_owned(),
+            directives: vec![fixtures::directive(Type::Ref, "foo", "./file.rs", 3)],
+            suggestions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn escape_special_characters() {
+        assert_eq!(
+            escape("<a href=\"x\">'&'</a>\u{1}"),
+            "&lt;a href=&quot;x&quot;&gt;&apos;&amp;&apos;&lt;/a&gt;",
+        );
+    }
+
+    #[test]
+    fn report_empty() {
+        let xml = report(&[], &[], "All good.");
+
+        assert!(xml.contains(r#"tests="1" failures="0""#));
+        assert!(xml.contains("<system-out>All good.</system-out>"));
+        assert!(!xml.contains("<failure"));
+    }
+
+    #[test]
+    fn report_error() {
+        let xml = report(&[violation(Severity::Error)], &[], "");
+
+        assert!(xml.contains(r#"tests="2" failures="1""#));
+        assert!(xml.contains(r#"classname="dangling_ref""#));
+        assert!(xml.contains(r#"file="./file.rs" line="3""#));
+        assert!(xml.contains(
+            r#"<failure type="dangling_ref" message="No tag found for &lt;foo&gt; &amp; more.">"#,
+        ));
+    }
+
+    #[test]
+    fn report_warning() {
+        let xml = report(&[violation(Severity::Warning)], &[], "");
+
+        assert!(xml.contains(r#"tests="2" failures="0""#));
+        assert!(xml.contains("<system-out>Warning: No tag found"));
+        assert!(!xml.contains("<failure"));
+    }
+
+    #[test]
+    fn report_suppressed() {
+        let xml = report(&[], &[violation(Severity::Error)], "");
+
+        assert!(xml.contains(r#"tests="2" failures="0" errors="0" skipped="1""#));
+        assert!(xml.contains(r#"<skipped message="Suppressed: No tag found"#));
+        assert!(!xml.contains("<failure"));
+    }
+}
@@ -0,0 +1,40 @@
+// This function computes the edit distance between two labels, which is the number of characters
+// which must be inserted, deleted, or substituted to turn one into the other. It gives up with
+// `None` as soon as the distance is known to exceed the maximum.
+pub fn distance(x: &[char], y: &[char], max_distance: usize) -> Option<usize> {
+    if x.len().abs_diff(y.len()) > max_distance {
+        return None;
+    }
+
+    let mut row = (0..=y.len()).collect::<Vec<_>>();
+    for (i, x_char) in x.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, y_char) in y.iter().enumerate() {
+            let substitution = diagonal + usize::from(x_char != y_char);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(diagonal + 1).min(row[j] + 1);
+        }
+        if row.iter().all(|distance| *distance > max_distance) {
+            return None;
+        }
+    }
+
+    Some(row[y.len()]).filter(|distance| *distance <= max_distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::levenshtein::distance;
+
+    #[test]
+    fn distance_edits() {
+        let chars = |label: &str| label.chars().collect::<Vec<_>>();
+
+        assert_eq!(distance(&chars(""), &chars(""), 0), Some(0));
+        assert_eq!(distance(&chars("kitten"), &chars("sitting"), 3), Some(3));
+        assert_eq!(distance(&chars("kitten"), &chars("sitting"), 2), None);
+        assert_eq!(distance(&chars("tagref"), &chars(""), 6), Some(6));
+        assert_eq!(distance(&chars("tagref"), &chars(""), 5), None);
+    }
+}
@@ -0,0 +1,79 @@
+// This library contains everything needed to scan files for directives and validate them. The
+// command-line interface is built on top of it, but it can also be used by other tools (e.g.,
+// editor integrations) which want to check files without running the binary.
+//
+// Here's the typical flow:
+//
+// 1. Build a `Scanner`, which knows which files to scan and how to parse them.
+// 2. Call `Scanner::scan` to build an `Index` of all the directives in those files.
+// 3. Call `Index::check` to validate the directives, which returns a list of `Violation`s.
+// 4. Call `Index::suppress` to set aside the violations suppressed by comments in the files.
+
+// The documentation in this crate is written as ordinary comments rather than doc comments, so
+// these lints don't apply.
+#![allow(clippy::missing_errors_doc, clippy::missing_panics_doc)]
+
+pub mod aliases;
+pub mod anchors;
+pub mod archives;
+pub mod attributes;
+pub mod baseline;
+pub mod bindings;
+pub mod cache;
+mod comments;
+pub mod context;
+pub mod count;
+pub mod custom_labels;
+pub mod diff;
+mod dir_references;
+pub mod directive;
+mod docs;
+pub mod drop_ins;
+pub mod duplicates;
+pub mod edits;
+pub mod encoding;
+pub mod expiry;
+pub mod extractors;
+mod ffi;
+mod file_references;
+pub mod fix;
+pub mod git;
+pub mod graph;
+pub mod hotspots;
+pub mod index;
+pub mod issues;
+mod levenshtein;
+mod mapped;
+mod markdown;
+pub mod migrate;
+pub mod min_refs;
+pub mod moves;
+pub mod namespaces;
+pub mod naming;
+pub mod new_tag;
+pub mod owners;
+pub mod pairings;
+pub mod pins;
+pub mod repositories;
+pub mod rewrite;
+pub mod rustdoc;
+pub mod severities;
+pub mod similar_tags;
+pub mod stats;
+pub mod streaming;
+pub mod suppressions;
+pub mod synthetic;
+mod tag_references;
+pub mod todos;
+pub mod trend;
+pub mod undo;
+pub mod unused;
+mod url_references;
+pub mod validators;
+pub mod violation;
+pub mod walk;
+#[cfg(target_arch = "wasm32")]
+mod wasm;
+
+pub use index::{Index, Rules, Scanner, Summary};
+pub use violation::{Kind, Violation};
@@ -0,0 +1,74 @@
+use colored::Colorize;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+// This struct prints log messages to STDERR. Besides Tagref's own messages, it prints the ones from
+// the directory walker, which explain why files were ignored.
+struct Logger;
+
+static LOGGER: Logger = Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+            && (metadata.target().starts_with("tagref") || metadata.target().starts_with("ignore"))
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let message = record.args().to_string();
+        match record.level() {
+            Level::Error => eprintln!("{}", message.red()),
+            Level::Warn => eprintln!("{}", message.yellow()),
+            Level::Info | Level::Debug | Level::Trace => eprintln!("{message}"),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+// This function determines which messages to print. By default, warnings and errors are printed.
+// Each `--verbose` adds a level of detail, and `--quiet` leaves only errors.
+#[must_use]
+pub fn level(verbosity: u8, quiet: bool) -> LevelFilter {
+    if quiet {
+        LevelFilter::Error
+    } else {
+        match verbosity {
+            0 => LevelFilter::Warn,
+            1 => LevelFilter::Info,
+            _ => LevelFilter::Debug,
+        }
+    }
+}
+
+// This function installs the logger.
+pub fn init(level: LevelFilter) {
+    // Installing the logger only fails if one is already installed, in which case that one is used.
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(level);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::logger::level;
+    use log::LevelFilter;
+
+    #[test]
+    fn level_default() {
+        assert_eq!(level(0, false), LevelFilter::Warn);
+    }
+
+    #[test]
+    fn level_verbose() {
+        assert_eq!(level(1, false), LevelFilter::Info);
+        assert_eq!(level(3, false), LevelFilter::Debug);
+    }
+
+    #[test]
+    fn level_quiet() {
+        assert_eq!(level(0, true), LevelFilter::Error);
+    }
+}
@@ -0,0 +1,523 @@
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+    Diagnostic, DiagnosticSeverity, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents,
+    HoverParams, HoverProviderCapability, Location, MarkupContent, MarkupKind, NumberOrString,
+    OneOf, Position, PublishDiagnosticsParams, Range, ReferenceParams, ServerCapabilities,
+    TextDocumentPositionParams, TextDocumentSyncCapability, TextDocumentSyncKind,
+    TextDocumentSyncOptions, TextDocumentSyncSaveOptions, Url,
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument,
+        Notification as _, PublishDiagnostics,
+    },
+    request::{GotoDefinition, HoverRequest, References, Request as _},
+};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    env, fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tagref::{
+    attributes,
+    cache::Cache,
+    count::count,
+    directive::{self, Directive, Type},
+    index::{Index, Rules, Scanner},
+    pins,
+    violation::Severity,
+    walk,
+};
+
+// This function converts a column given in UTF-16 code units, as LSP positions are, into a byte
+// offset into the line. Columns past the end of the line are clamped to it.
+fn byte_offset(line: &str, character: u32) -> usize {
+    let mut units = 0;
+    for (offset, char) in line.char_indices() {
+        if units >= character as usize {
+            return offset;
+        }
+        units += char.len_utf16();
+    }
+    line.len()
+}
+
+// This function converts a byte offset into a line into a column in UTF-16 code units.
+fn character(line: &str, offset: usize) -> u32 {
+    u32::try_from(line[..offset].encode_utf16().count()).unwrap_or(u32::MAX)
+}
+
+// This function finds the directive at a byte offset in a line, if any. Only tags and tag
+// references are considered. Pins are left out of the labels of references.
+fn directive_at(scanner: &Scanner, line: &str, offset: usize) -> Option<(Type, String)> {
+    [
+        (Type::Tag, &scanner.tag_regex),
+        (Type::Ref, &scanner.ref_regex),
+    ]
+    .into_iter()
+    .chain(
+        scanner
+            .tag_block_regex
+            .iter()
+            .map(|regex| (Type::Tag, regex)),
+    )
+    .find_map(|(r#type, regex)| {
+        regex.captures_iter(line).find_map(|captures| {
+            // A match always has a `get(0)`, so the `unwrap` is safe. The label group may not
+            // participate in a match of a custom regex (e.g., `(a)|b`), in which case there's no
+            // label.
+            let range = captures.get(0).unwrap().range();
+            let label = attributes::split(captures.get(1)?.as_str()).0;
+            let label = if r#type == Type::Ref {
+                pins::split(label).0
+            } else {
+                label
+            };
+            (range.start <= offset && offset <= range.end)
+                .then(|| (r#type.clone(), label.to_owned()))
+        })
+    })
+}
+
+// This struct holds the state of the language server.
+struct Server<'a> {
+    scanner: &'a Scanner,
+    rules: &'a Rules,
+
+    // The directory that the paths in the index are relative to
+    root: PathBuf,
+
+    // The cache lives in memory for as long as the server is running, so only files which changed
+    // are parsed again when rescanning.
+    cache: Arc<Cache>,
+    index: Index,
+
+    // The contents of the documents open in the editor, keyed by path relative to the root
+    documents: HashMap<PathBuf, String>,
+
+    // The documents which diagnostics were last published for, so they can be cleared
+    published: HashSet<Url>,
+}
+
+impl Server<'_> {
+    // This function converts a URI from the editor into a path relative to the root.
+    fn path(&self, uri: &Url) -> Option<PathBuf> {
+        let path = uri.to_file_path().ok()?;
+        Some(
+            path.strip_prefix(&self.root)
+                .map_or_else(|_| path.clone(), Path::to_owned),
+        )
+    }
+
+    // This function converts a path relative to the root into a URI for the editor.
+    fn uri(&self, path: &Path) -> Option<Url> {
+        Url::from_file_path(self.root.join(walk::relative(path))).ok()
+    }
+
+    // This function returns a line of a file, preferring the contents of the editor if the file
+    // is open. The line number is zero-based.
+    fn line(&self, path: &Path, line: usize) -> Option<String> {
+        let path = walk::relative(path);
+        let contents = if let Some(contents) = self.documents.get(&path) {
+            contents.clone()
+        } else {
+            fs::read_to_string(self.root.join(&path)).ok()?
+        };
+        directive::lines(&contents)
+            .nth(line)
+            .map(|(line, _)| line.to_owned())
+    }
+
+    // This function returns the location of a directive. If the line no longer contains the span
+    // (e.g., because the file has been edited since it was scanned), the start of the line is used.
+    fn location(&self, directive: &Directive) -> Option<Location> {
+        let line_number = directive.line_number.saturating_sub(1);
+        let line = u32::try_from(line_number).unwrap_or(u32::MAX);
+        let (start, end) = self
+            .line(&directive.path, line_number)
+            .filter(|text| text.get(directive.span.clone()).is_some())
+            .map_or((0, 0), |text| {
+                (
+                    character(&text, directive.span.start),
+                    character(&text, directive.span.end),
+                )
+            });
+
+        Some(Location {
+            uri: self.uri(&directive.path)?,
+            range: Range {
+                start: Position::new(line, start),
+                end: Position::new(line, end),
+            },
+        })
+    }
+
+    // This function finds the tag or tag reference at a position in a document.
+    fn directive_at(&self, position: &TextDocumentPositionParams) -> Option<(Type, String)> {
+        let path = self.path(&position.text_document.uri)?;
+        let line = self.line(&path, position.position.line as usize)?;
+        directive_at(
+            self.scanner,
+            &line,
+            byte_offset(&line, position.position.character),
+        )
+    }
+
+    // This function scans the files again. Files are scanned as they are on disk, so changes in
+    // the editor are picked up when they're saved.
+    fn rescan(&mut self) -> Result<(), String> {
+        let (index, new_cache) = self.scanner.scan(Some(self.cache.clone()))?;
+        self.cache = Arc::new(new_cache.unwrap_or_default());
+        self.index = index;
+        Ok(())
+    }
+
+    // This function publishes the violations as diagnostics, and clears the diagnostics for any
+    // documents which no longer have violations.
+    fn publish_diagnostics(&mut self, connection: &Connection) -> Result<(), String> {
+        let mut diagnostics = BTreeMap::<Url, Vec<Diagnostic>>::new();
+        let mut violations = self.index.check(self.rules);
+        self.index.suppress(&mut violations);
+        for violation in violations {
+            for directive in &violation.directives {
+                let Some(location) = self.location(directive) else {
+                    continue;
+                };
+                diagnostics
+                    .entry(location.uri)
+                    .or_default()
+                    .push(Diagnostic {
+                        range: location.range,
+                        severity: Some(match violation.severity {
+                            Severity::Error => DiagnosticSeverity::ERROR,
+                            Severity::Warning => DiagnosticSeverity::WARNING,
+                            Severity::Info => DiagnosticSeverity::INFORMATION,
+                        }),
+                        code: Some(NumberOrString::String(violation.kind.id().to_owned())),
+                        source: Some("tagref".to_owned()),
+                        message: violation.message.trim_end().to_owned(),
+                        ..Diagnostic::default()
+                    });
+            }
+        }
+
+        let published = diagnostics.keys().cloned().collect::<HashSet<_>>();
+        for uri in self.published.difference(&published) {
+            diagnostics.insert(uri.clone(), Vec::new());
+        }
+        self.published = published;
+
+        for (uri, diagnostics) in diagnostics {
+            send(
+                connection,
+                Notification::new(
+                    PublishDiagnostics::METHOD.to_owned(),
+                    PublishDiagnosticsParams {
+                        uri,
+                        diagnostics,
+                        version: None,
+                    },
+                )
+                .into(),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    // This function finds the tag that a reference points to, or the tag itself.
+    fn definition(&self, params: &GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let (_, label) = self.directive_at(&params.text_document_position_params)?;
+        let locations = self
+            .index
+            .tags
+            .get(&*self.index.key(&label))?
+            .iter()
+            .filter_map(|tag| self.location(tag))
+            .collect();
+        Some(GotoDefinitionResponse::Array(locations))
+    }
+
+    // This function finds all the references to a tag, and optionally the tag itself.
+    fn references(&self, params: &ReferenceParams) -> Option<Vec<Location>> {
+        let (_, label) = self.directive_at(&params.text_document_position)?;
+        let key = self.index.key(&label);
+        let tags = self
+            .index
+            .tags
+            .get(&*key)
+            .filter(|_| params.context.include_declaration)
+            .into_iter()
+            .flatten();
+        Some(
+            tags.chain(
+                self.index
+                    .refs
+                    .iter()
+                    .filter(|r#ref| self.index.key(&r#ref.label) == key),
+            )
+            .filter_map(|directive| self.location(directive))
+            .collect(),
+        )
+    }
+
+    // This function describes the tag under the cursor: where it's defined, and how many times
+    // it's referenced.
+    fn hover(&self, params: &HoverParams) -> Option<Hover> {
+        let (_, label) = self.directive_at(&params.text_document_position_params)?;
+        let key = self.index.key(&label);
+        let tags = self
+            .index
+            .tags
+            .get(&*key)
+            .map_or(&[] as &[_], Vec::as_slice);
+        let references = self
+            .index
+            .refs
+            .iter()
+            .filter(|r#ref| self.index.key(&r#ref.label) == key)
+            .count();
+
+        let mut lines = Vec::new();
+        if tags.is_empty() {
+            lines.push(format!("No tag found for `{label}`."));
+        }
+        for tag in tags {
+            lines.push(format!(
+                "Tag `{label}` is defined at `{}:{}`.",
+                walk::relative(&tag.path).to_string_lossy(),
+                tag.line_number,
+            ));
+            if let Some(description) = &tag.description {
+                lines.push(description.clone());
+            }
+        }
+        lines.push(format!("It has {}.", count(references, "reference")));
+
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: lines.join("\n\n"),
+            }),
+            range: None,
+        })
+    }
+}
+
+// This function sends a message to the editor.
+fn send(connection: &Connection, message: Message) -> Result<(), String> {
+    connection
+        .sender
+        .send(message)
+        .map_err(|error| format!("Unable to send a message to the editor: {error}"))
+}
+
+// This function handles a request from the editor. Unsupported requests get an error response.
+fn handle_request(server: &Server, request: Request) -> Response {
+    let id = request.id.clone();
+    let result = match request.method.as_str() {
+        GotoDefinition::METHOD => request
+            .extract(GotoDefinition::METHOD)
+            .map(|(_, params)| serde_json::to_value(server.definition(&params))),
+        References::METHOD => request
+            .extract(References::METHOD)
+            .map(|(_, params)| serde_json::to_value(server.references(&params))),
+        HoverRequest::METHOD => request
+            .extract(HoverRequest::METHOD)
+            .map(|(_, params)| serde_json::to_value(server.hover(&params))),
+        method => {
+            return Response::new_err(
+                id,
+                ErrorCode::MethodNotFound as i32,
+                format!("Unsupported method `{method}`."),
+            );
+        }
+    };
+
+    match result {
+        Ok(Ok(value)) => Response {
+            id,
+            result: Some(value),
+            error: None,
+        },
+        Ok(Err(error)) => Response::new_err(id, ErrorCode::InternalError as i32, error.to_string()),
+        Err(error) => Response::new_err(id, ErrorCode::InvalidParams as i32, error.to_string()),
+    }
+}
+
+// This function runs a language server over standard input and output. It provides diagnostics
+// for violations, go-to-definition from references to tags, finding references to tags, and hover
+// information for tags. It returns when the editor shuts it down.
+pub fn serve(scanner: &Scanner, rules: &Rules) -> Result<(), String> {
+    let (connection, io_threads) = Connection::stdio();
+    let protocol_error = |error| format!("Language server protocol error: {error}");
+
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Options(
+            TextDocumentSyncOptions {
+                open_close: Some(true),
+                change: Some(TextDocumentSyncKind::FULL),
+                save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+                ..TextDocumentSyncOptions::default()
+            },
+        )),
+        definition_provider: Some(OneOf::Left(true)),
+        references_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        ..ServerCapabilities::default()
+    };
+    // The `unwrap` is safe because the capabilities are always serializable.
+    connection
+        .initialize(serde_json::to_value(capabilities).unwrap())
+        .map_err(protocol_error)?;
+
+    let mut server = Server {
+        scanner,
+        rules,
+        root: env::current_dir()
+            .map_err(|error| format!("Unable to determine the current directory: {error}"))?,
+        cache: Arc::new(Cache::new(&scanner.cache_key())),
+        index: Index::default(),
+        documents: HashMap::new(),
+        published: HashSet::new(),
+    };
+    server.rescan()?;
+    server.publish_diagnostics(&connection)?;
+
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection
+                    .handle_shutdown(&request)
+                    .map_err(protocol_error)?
+                {
+                    break;
+                }
+                send(&connection, handle_request(&server, request).into())?;
+            }
+            Message::Notification(notification) => match notification.method.as_str() {
+                DidOpenTextDocument::METHOD => {
+                    if let Ok(params) = notification
+                        .extract::<DidOpenTextDocumentParams>(DidOpenTextDocument::METHOD)
+                        && let Some(path) = server.path(&params.text_document.uri)
+                    {
+                        server.documents.insert(path, params.text_document.text);
+                    }
+                }
+                DidChangeTextDocument::METHOD => {
+                    // Only full document changes are requested, so the last change is the whole
+                    // document.
+                    if let Ok(params) = notification
+                        .extract::<DidChangeTextDocumentParams>(DidChangeTextDocument::METHOD)
+                        && let Some(path) = server.path(&params.text_document.uri)
+                        && let Some(change) = params.content_changes.into_iter().last()
+                    {
+                        server.documents.insert(path, change.text);
+                    }
+                }
+                DidCloseTextDocument::METHOD => {
+                    if let Ok(params) = notification
+                        .extract::<DidCloseTextDocumentParams>(DidCloseTextDocument::METHOD)
+                        && let Some(path) = server.path(&params.text_document.uri)
+                    {
+                        server.documents.remove(&path);
+                    }
+                }
+                DidSaveTextDocument::METHOD => {
+                    server.rescan()?;
+                    server.publish_diagnostics(&connection)?;
+                }
+                _ => {}
+            },
+            Message::Response(_) => {}
+        }
+    }
+
+    drop(connection);
+    io_threads
+        .join()
+        .map_err(|error| format!("Language server I/O error: {error}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lsp::{byte_offset, character, directive_at};
+    use tagref::{
+        directive::{Type, compile_directive_regex},
+        extractors,
+        index::Scanner,
+        walk,
+    };
+
+    fn scanner() -> Scanner {
+        Scanner {
+            paths: Vec::new(),
+            walk_options: walk::Options::default(),
+            tag_regex: compile_directive_regex("tag"),
+            tag_block_regex: None,
+            ref_regex: compile_directive_regex("ref"),
+            file_regex: compile_directive_regex("file"),
+            dir_regex: compile_directive_regex("dir"),
+            url_regex: compile_directive_regex("url"),
+            custom_regexes: Vec::new(),
+            comments_only: false,
+            archives: false,
+            extractors: extractors::defaults(),
+            fallback_encoding: None,
+            case_insensitive_labels: false,
+            relative_paths: false,
+            roots: Vec::new(),
+            memory_map: false,
+        }
+    }
+
+    #[test]
+    fn byte_offset_ascii() {
+        assert_eq!(byte_offset("foo bar", 4), 4);
+    }
+
+    #[test]
+    fn byte_offset_multibyte() {
+        // The `é` is one UTF-16 code unit but two bytes, and the emoji is two UTF-16 code units
+        // but four bytes.
+        assert_eq!(byte_offset("é😀x", 1), 2);
+        assert_eq!(byte_offset("é😀x", 3), 6);
+    }
+
+    #[test]
+    fn byte_offset_past_end() {
+        assert_eq!(byte_offset("foo", 10), 3);
+    }
+
+    #[test]
+    fn character_multibyte() {
+        assert_eq!(character("é😀x", 6), 3);
+    }
+
+    #[test]
+    fn directive_at_ref() {
+        let line = "// See [?ref:foo].".replace('?', "");
+
+        assert_eq!(
+            directive_at(&scanner(), &line, 10),
+            Some((Type::Ref, "foo".to_owned())),
+        );
+    }
+
+    #[test]
+    fn directive_at_tag() {
+        let line = "// [?tag:foo]".replace('?', "");
+
+        assert_eq!(
+            directive_at(&scanner(), &line, 3),
+            Some((Type::Tag, "foo".to_owned())),
+        );
+    }
+
+    #[test]
+    fn directive_at_nothing() {
+        let line = "// [?tag:foo]".replace('?', "");
+
+        assert_eq!(directive_at(&scanner(), &line, 1), None);
+    }
+}
@@ -1,286 +1,140 @@
-mod count;
-mod dir_references;
-mod directive;
-mod duplicates;
-mod file_references;
-mod tag_references;
-mod walk;
-
-use clap::{ArgAction, Args, Parser, Subcommand as ClapSubcommand};
+mod badge;
+mod cli;
+mod commands;
+mod compact;
+mod completions;
+mod config;
+mod editor;
+mod failure;
+#[cfg(test)]
+mod fixtures;
+mod format;
+mod github;
+mod gitlab;
+mod hook;
+mod init;
+mod junit;
+mod logger;
+mod lsp;
+mod pre_receive;
+mod pretty;
+mod sarif;
+mod serve;
+mod settings;
+mod tui;
+mod watch;
+
+use clap::{CommandFactory, FromArgMatches};
+use cli::{CheckArgs, Cli, Color, Subcommand, usage_error};
 use colored::Colorize;
-use directive::compile_directive_regex;
-use std::{
-    collections::{HashMap, HashSet},
-    io::{self, BufReader, IsTerminal},
-    path::PathBuf,
-    process::exit,
-    sync::{Arc, Mutex},
-};
-
-// This struct represents the command-line arguments.
-#[derive(Parser)]
-#[command(
-    about = concat!(
-        env!("CARGO_PKG_DESCRIPTION"),
-        "\n\n",
-        "You can annotate your code with tags like [tag:foo] and reference them like [ref:foo]. ",
-        "You can also reference files like [file:src/main.rs] and directories like [dir:src]. ",
-        "Tagref checks that tags are unique and that references are not dangling.\n\n",
-        "More information can be found at: ",
-        env!("CARGO_PKG_HOMEPAGE"),
-    ),
-    version,
-    disable_version_flag = true
-)]
-struct Cli {
-    #[arg(short, long, help = "Print version", action = ArgAction::Version)]
-    _version: Option<bool>,
-
-    #[arg(
-        short,
-        long = "path",
-        value_name = "PATH",
-        help = "Add a directory to scan",
-        default_value = "."
-    )]
-    paths: Vec<PathBuf>,
-
-    #[arg(
-        short,
-        long,
-        help = "Set the sigil used for tags",
-        default_value = "tag"
-    )]
-    tag_sigil: String,
-
-    #[arg(
-        short,
-        long,
-        help = "Set the sigil used for tag references",
-        default_value = "ref"
-    )]
-    ref_sigil: String,
-
-    #[arg(
-        short,
-        long,
-        help = "Set the sigil used for file references",
-        default_value = "file"
-    )]
-    file_sigil: String,
-
-    #[arg(
-        short,
-        long,
-        help = "Set the sigil used for directory references",
-        default_value = "dir"
-    )]
-    dir_sigil: String,
-
-    #[command(subcommand)]
-    command: Option<Subcommand>,
-}
-
-#[derive(Args)]
-struct ListUnusedArgs {
-    #[arg(
-        long,
-        help = "Exit with an error status code if any tags are unreferenced"
-    )]
-    fail_if_any: bool,
-}
-
-#[derive(ClapSubcommand)]
-enum Subcommand {
-    #[command(about = "Check all the tags and references (default)")]
-    Check,
-
-    #[command(about = "List all the tags")]
-    ListTags,
-
-    #[command(about = "List all the tag references")]
-    ListRefs,
-
-    #[command(about = "List all the file references")]
-    ListFiles,
-
-    #[command(about = "List all the directory references")]
-    ListDirs,
-
-    #[command(about = "List the unreferenced tags")]
-    ListUnused(ListUnusedArgs),
+use failure::Failure;
+use settings::Settings;
+use std::{process::exit, thread};
+
+// This function runs a subcommand.
+fn run(mut settings: Settings, command: Subcommand) -> Result<(), Failure> {
+    let settings = &mut settings;
+    match command {
+        Subcommand::Check(args) => commands::check(settings, args, None),
+        // A badge is printed by running the checks as usual and reporting the result differently.
+        Subcommand::Badge(args) => commands::check(settings, args.check, Some(args.svg)),
+        Subcommand::CheckStdin(args) => commands::check_stdin(settings, &args),
+        Subcommand::PreReceive => commands::pre_receive(settings),
+        Subcommand::Watch => commands::watch(settings),
+        Subcommand::Lsp => commands::lsp(settings),
+        Subcommand::Serve(args) => commands::serve(settings, &args),
+        Subcommand::ListTags(args) => commands::list_tags(settings, &args),
+        Subcommand::ListRefs => commands::list_references(settings, |index| &index.refs),
+        Subcommand::ListFiles => commands::list_references(settings, |index| &index.files),
+        Subcommand::ListDirs => commands::list_references(settings, |index| &index.dirs),
+        Subcommand::ListUrls => commands::list_references(settings, |index| &index.urls),
+        Subcommand::ListCustom(args) => commands::list_custom(settings, &args),
+        Subcommand::ListUnused(args) => commands::list_unused(settings, &args),
+        Subcommand::Grep(args) => commands::grep(settings, &args),
+        Subcommand::LintTags(args) => commands::lint_tags(settings, &args),
+        Subcommand::Owners(args) => commands::owners(settings, &args),
+        Subcommand::Expiring(args) => commands::expiring(settings, &args),
+        Subcommand::Todo(args) => commands::todo(settings, &args),
+        Subcommand::Stats(args) => commands::stats(settings, &args),
+        Subcommand::Hotspots(args) => commands::hotspots(settings, &args),
+        Subcommand::Show(args) => commands::show(settings, &args),
+        Subcommand::Open(args) => commands::open(settings, &args),
+        Subcommand::Rename(args) => commands::rename(settings, &args),
+        Subcommand::NewTag(args) => commands::new_tag(settings, &args),
+        Subcommand::Repin(args) => commands::repin(settings, &args),
+        Subcommand::ChangedRefs(args) => commands::changed_refs(settings, &args),
+        Subcommand::Diff(args) => commands::diff(settings, &args),
+        Subcommand::Mv(args) => commands::mv(settings, &args),
+        Subcommand::Fix(args) => commands::fix(settings, &args),
+        Subcommand::Migrate(args) => commands::migrate(settings, args),
+        Subcommand::Tui => commands::tui(settings),
+        Subcommand::Graph(args) => commands::graph(settings, &args),
+        Subcommand::Trend(args) => commands::trend(settings, &args),
+        Subcommand::Rustdoc => commands::rustdoc(settings),
+        Subcommand::Baseline(args) => commands::baseline(settings, &args),
+        Subcommand::Index(args) => commands::index(settings, &args),
+        Subcommand::Init(args) => commands::init(settings, &args),
+        Subcommand::InstallHook(args) => commands::install_hook(&args),
+        Subcommand::UninstallHook(args) => commands::uninstall_hook(&args),
+        Subcommand::Undo(args) => commands::undo(&args),
+        Subcommand::Bench(args) => commands::bench(&args),
+        // The completion script is printed before loading the configuration.
+        Subcommand::Completions(_) => Ok(()),
+    }
 }
 
 // Program entrypoint
-#[allow(clippy::too_many_lines)]
-fn entry() -> Result<(), String> {
-    // Determine whether to print colored output.
-    colored::control::set_override(io::stdout().is_terminal());
+fn entry() -> Result<(), Failure> {
+    // Determine whether to print colored output, first for any errors when parsing the
+    // command-line options, and then as requested.
+    colored::control::set_override(Color::Auto.enabled());
 
     // Parse the command-line options.
-    let cli = Cli::parse();
-
-    // Compile the regular expressions in advance.
-    let tag_regex = compile_directive_regex(&cli.tag_sigil);
-    let ref_regex = compile_directive_regex(&cli.ref_sigil);
-    let file_regex = compile_directive_regex(&cli.file_sigil);
-    let dir_regex = compile_directive_regex(&cli.dir_sigil);
-
-    // Parse all the tags and references.
-    let tags = Arc::new(Mutex::new(HashMap::new()));
-    let refs = Arc::new(Mutex::new(Vec::new()));
-    let files = Arc::new(Mutex::new(Vec::new()));
-    let dirs = Arc::new(Mutex::new(Vec::new()));
-    let tags_clone = tags.clone();
-    let refs_clone = refs.clone();
-    let files_clone = files.clone();
-    let dirs_clone = dirs.clone();
-    let tag_regex_clone = tag_regex.clone();
-    let ref_regex_clone = ref_regex.clone();
-    let file_regex_clone = file_regex.clone();
-    let dir_regex_clone = dir_regex.clone();
-    let files_scanned = walk::walk(&cli.paths, move |file_path, file| {
-        let directives = directive::parse(
-            &tag_regex_clone,
-            &ref_regex_clone,
-            &file_regex_clone,
-            &dir_regex_clone,
-            file_path,
-            BufReader::new(file),
-        );
-        for tag in directives.tags {
-            tags_clone
-                .lock()
-                .unwrap() // Safe assuming no poisoning
-                .entry(tag.label.clone())
-                .or_insert_with(Vec::new)
-                .push(tag.clone());
-        }
-        refs_clone.lock().unwrap().extend(directives.refs); // Safe assuming no poisoning
-        files_clone.lock().unwrap().extend(directives.files); // Safe assuming no poisoning
-        dirs_clone.lock().unwrap().extend(directives.dirs); // Safe assuming no poisoning
-    });
-
-    // Decide what to do based on the subcommand.
-    match cli.command.unwrap_or(Subcommand::Check) {
-        Subcommand::Check => {
-            // Errors will be accumulated in this vector.
-            let mut errors = Vec::<String>::new();
-
-            // Convert the `tags` map into a set and check for duplicates. The `unwrap` is safe
-            // assuming no poisoning.
-            errors.extend(duplicates::check(&tags.lock().unwrap()));
-
-            // Check the tag references. The `unwrap`s are safe assuming no poisoning.
-            let tags = tags
-                .lock()
-                .unwrap()
-                .keys()
-                .cloned()
-                .collect::<HashSet<String>>();
-            let refs = refs.lock().unwrap();
-            errors.extend(tag_references::check(&tags, &refs));
-
-            // Check the file references. The `unwrap` is safe assuming no poisoning.
-            errors.extend(file_references::check(&files.lock().unwrap()));
-
-            // Check the directory references. The `unwrap` is safe assuming no poisoning.
-            errors.extend(dir_references::check(&dirs.lock().unwrap()));
-
-            // Check for any errors and report the result.
-            if errors.is_empty() {
-                println!(
-                    "{}",
-                    format!(
-                        "{}, {}, {}, and {} validated in {}.",
-                        count::count(tags.len(), "tag"),
-                        count::count(refs.len(), "tag reference"),
-                        // The `unwrap` is safe assuming no poisoning.
-                        count::count(files.lock().unwrap().len(), "file reference"),
-                        // The `unwrap` is safe assuming no poisoning.
-                        count::count(dirs.lock().unwrap().len(), "directory reference"),
-                        count::count(files_scanned, "file"),
-                    )
-                    .green(),
-                );
-            } else {
-                return Err(errors.join("\n\n"));
-            }
-        }
-
-        Subcommand::ListTags => {
-            // Print all the tags. The `unwrap` is safe assuming no poisoning.
-            for dupes in tags.lock().unwrap().values() {
-                for dupe in dupes {
-                    println!("{dupe}");
-                }
-            }
-        }
-
-        Subcommand::ListRefs => {
-            // Print all the tag references. The `unwrap` is safe assuming no poisoning.
-            for r#ref in refs.lock().unwrap().iter() {
-                println!("{ref}");
-            }
-        }
-
-        Subcommand::ListFiles => {
-            // Print all the file references. The `unwrap` is safe assuming no poisoning.
-            for file in files.lock().unwrap().iter() {
-                println!("{file}");
-            }
-        }
-
-        Subcommand::ListDirs => {
-            // Print all the directory references. The `unwrap` is safe assuming no poisoning.
-            for dir in dirs.lock().unwrap().iter() {
-                println!("{dir}");
-            }
-        }
-
-        Subcommand::ListUnused(args) => {
-            // Remove all the referenced tags. The `unwrap` is safe assuming no poisoning.
-            for r#ref in refs.lock().unwrap().iter() {
-                tags.lock()
-                    .unwrap() // Safe assuming no poisoning
-                    .remove(&r#ref.label);
-            }
-
-            // Print the remaining tags. The `unwrap` is safe assuming no poisoning.
-            for dupes in tags.lock().unwrap().values() {
-                for dupe in dupes {
-                    println!("{dupe}");
-                }
-            }
+    let matches = Cli::command()
+        .try_get_matches()
+        .unwrap_or_else(|error| usage_error(&error));
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|error| usage_error(&error));
+    colored::control::set_override(cli.color.enabled());
+    logger::init(logger::level(cli.verbose, cli.quiet));
+    let command = cli
+        .command
+        .take()
+        .unwrap_or_else(|| Subcommand::Check(CheckArgs::default()));
+
+    // The completion script doesn't depend on the configuration or the files.
+    if let Subcommand::Completions(args) = &command {
+        print!("{}", completions::generate(Cli::command(), args.shell));
+        return Ok(());
+    }
 
-            // Error out if the error flag has been passed and there are unused tags.
-            // The `unwrap` is safe assuming no poisoning.
-            if args.fail_if_any && !tags.lock().unwrap().is_empty() {
-                return Err("Found unused tags while using --fail-if-any".to_owned());
-            }
-        }
+    // Load the configuration file, if there is one.
+    let settings = Settings::load(cli, matches)?;
+
+    // Give up if the command takes too long, so a pathological file or an enormous tree can't hang
+    // a CI job. The commands which are meant to keep running aren't limited, and files which are
+    // being rewritten are finished first (see `failure::REWRITING`).
+    if let Some(timeout) = settings.cli.timeout
+        && !matches!(
+            command,
+            Subcommand::Watch | Subcommand::Lsp | Subcommand::Serve(_) | Subcommand::Tui,
+        )
+    {
+        thread::spawn(move || {
+            thread::sleep(timeout);
+            let _rewriting = failure::REWRITING.lock();
+            eprintln!("{}", format!("Timed out after {timeout:.0?}.").red());
+            exit(failure::TIMEOUT);
+        });
     }
 
-    // Everything succeeded.
-    Ok(())
+    run(settings, command)
 }
 
 // Let the fun begin!
 fn main() {
     // Jump to the entrypoint and handle any resulting errors.
-    if let Err(e) = entry() {
-        eprintln!("{}", e.red());
-        exit(1);
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::Cli;
-    use clap::CommandFactory;
-
-    #[test]
-    fn verify_cli() {
-        Cli::command().debug_assert();
+    if let Err(failure) = entry() {
+        eprintln!("{}", failure.message.red());
+        exit(failure.code);
     }
 }
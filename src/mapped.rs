@@ -0,0 +1,97 @@
+#[cfg(unix)]
+use memmap2::Mmap;
+use std::{fs::File, io::Read, ops::Deref};
+
+// Files at least this large (e.g., generated code or lockfiles) are memory-mapped rather than
+// read into a buffer, which avoids copying their contents. Smaller files are cheaper to read.
+pub const THRESHOLD: u64 = 1 << 20;
+
+// This enum holds the contents of a file, either memory-mapped or read into a buffer.
+pub enum Contents {
+    #[cfg(unix)]
+    Mapped(Mmap),
+    Buffered(Vec<u8>),
+}
+
+impl Deref for Contents {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            #[cfg(unix)]
+            Self::Mapped(map) => map,
+            Self::Buffered(bytes) => bytes,
+        }
+    }
+}
+
+// This function returns the contents of a file, mapping it into memory if requested, it's large
+// enough, and the platform supports it. Errors when reading are ignored, so whatever was read
+// before the error is still returned.
+pub fn read(mut file: File, map: bool) -> Contents {
+    #[cfg(not(unix))]
+    let _ = map;
+    #[cfg(unix)]
+    if map
+        && file
+            .metadata()
+            .is_ok_and(|metadata| metadata.len() >= THRESHOLD)
+    {
+        // The mapping is only read while the file is parsed. If the file is truncated in the
+        // meantime, reading past its new end raises `SIGBUS`, which is the usual tradeoff for
+        // searching mapped files. Long-running processes don't map files for that reason (see
+        // `Scanner::memory_map`).
+        if let Ok(map) = unsafe { Mmap::map(&file) } {
+            return Contents::Mapped(map);
+        }
+    }
+
+    let mut bytes = Vec::new();
+    let _ = file.read_to_end(&mut bytes);
+    Contents::Buffered(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mapped::{THRESHOLD, read};
+    use std::{
+        env, fs,
+        fs::File,
+        process,
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    // This function writes a file of the given length, and checks that it's read back intact.
+    fn round_trip(length: usize, map: bool) {
+        let path = env::temp_dir().join(format!(
+            "tagref-mapped-{}-{}-{length}",
+            process::id(),
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos(),
+        ));
+        let expected = (0..length)
+            .map(|i| if i % 80 == 79 { b'\n' } else { b'x' })
+            .collect::<Vec<_>>();
+        fs::write(&path, &expected).unwrap();
+        let actual = read(File::open(&path).unwrap(), map).to_vec();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn read_small() {
+        round_trip(100, true);
+    }
+
+    #[test]
+    fn read_large() {
+        round_trip(usize::try_from(THRESHOLD).unwrap() + 100, true);
+    }
+
+    #[test]
+    fn read_large_unmapped() {
+        round_trip(usize::try_from(THRESHOLD).unwrap() + 100, false);
+    }
+}
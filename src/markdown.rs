@@ -0,0 +1,402 @@
+use crate::{
+    archives,
+    directive::{self, Directive, Type},
+    git::Tree,
+    violation::{Kind, Severity, Violation},
+    walk,
+};
+use std::{
+    collections::BTreeMap,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+// This function determines whether a file is written in Markdown, judging by its extension.
+#[must_use]
+pub fn is_markdown(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| {
+        matches!(
+            extension.to_string_lossy().to_lowercase().as_str(),
+            "md" | "markdown" | "mdown" | "mkd" | "mdx"
+        )
+    })
+}
+
+// This function determines whether a line opens or closes a fenced code block, and returns the
+// fence if so.
+fn fence(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start();
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    ['`', '~'].into_iter().find_map(|marker| {
+        let length = trimmed.len() - trimmed.trim_start_matches(marker).len();
+        (length >= 3).then(|| &trimmed[..length])
+    })
+}
+
+// This function blanks out the code spans in a line, keeping the byte offsets of everything else.
+fn blank_code_spans(line: &str) -> String {
+    let mut blanked = line.to_owned();
+    let mut start = 0;
+    while let Some(open) = line[start..].find('`').map(|offset| start + offset) {
+        let length = line[open..].len() - line[open..].trim_start_matches('`').len();
+        let ticks = &line[open..open + length];
+
+        // A code span is closed by a run of backticks of the same length.
+        let mut search = open + length;
+        let close = loop {
+            let Some(next) = line[search..].find(ticks).map(|offset| search + offset) else {
+                break None;
+            };
+            let run = line[next..].len() - line[next..].trim_start_matches('`').len();
+            if run == length {
+                break Some(next);
+            }
+            search = next + run;
+        };
+        let Some(close) = close else {
+            start = open + length;
+            continue;
+        };
+        blanked.replace_range(open..close + length, &" ".repeat(close + length - open));
+        start = close + length;
+    }
+    blanked
+}
+
+// This function finds the end of a link destination starting at the given offset, which may be
+// enclosed in angle brackets or contain balanced parentheses. It returns the range of the
+// destination itself.
+fn destination(line: &str, start: usize) -> Option<Range<usize>> {
+    let rest = &line[start..];
+    if let Some(enclosed) = rest.strip_prefix('<') {
+        let end = enclosed.find('>')?;
+        return Some(start + 1..start + 1 + end);
+    }
+
+    let mut depth = 0_usize;
+    for (offset, character) in rest.char_indices() {
+        match character {
+            '(' => depth += 1,
+            ')' if depth == 0 => return Some(start..start + offset),
+            ')' => depth -= 1,
+            _ if character.is_whitespace() => return Some(start..start + offset),
+            _ => {}
+        }
+    }
+    Some(start..line.len())
+}
+
+// This function determines whether a link destination points to a file in the repository, rather
+// than a URL (anything with a scheme, like `https:` or `mailto:`) or an anchor in the same file.
+fn is_local(target: &str) -> bool {
+    let scheme = target.split_once(':').is_some_and(|(scheme, _)| {
+        scheme.len() > 1
+            && scheme.starts_with(|character: char| character.is_ascii_alphabetic())
+            && scheme.chars().all(|character| {
+                character.is_ascii_alphanumeric() || matches!(character, '+' | '-' | '.')
+            })
+    });
+    !target.is_empty() && !target.starts_with(['#', '?']) && !target.starts_with("//") && !scheme
+}
+
+// This function finds the local link destinations in a line of Markdown outside any code block,
+// both in inline links and images (as in `[text](docs/setup.md)`) and in link reference
+// definitions (as in `[setup]: docs/setup.md`). It returns their byte ranges.
+fn link_targets(line: &str) -> Vec<Range<usize>> {
+    let blanked = blank_code_spans(line);
+    let mut targets = Vec::new();
+
+    // Link reference definitions take up the whole line, apart from an optional title.
+    let trimmed = blanked.trim_start();
+    if blanked.len() - trimmed.len() <= 3
+        && let Some(definition) = trimmed.strip_prefix('[')
+        && !definition.starts_with('^')
+        && let Some(end) = definition.find(']')
+        && definition[end..].starts_with("]:")
+    {
+        let start = blanked.len() - definition.len() + end + 2;
+        let start = start + (blanked[start..].len() - blanked[start..].trim_start().len());
+        targets.extend(destination(&blanked, start));
+    } else {
+        let mut search = 0;
+        while let Some(found) = blanked[search..].find("](").map(|offset| search + offset) {
+            let start = found + 2;
+            let start = start + (blanked[start..].len() - blanked[start..].trim_start().len());
+            let Some(target) = destination(&blanked, start) else {
+                search = start;
+                continue;
+            };
+            search = target.end.max(start);
+            targets.push(target);
+        }
+    }
+
+    targets.retain(|target| is_local(&line[target.clone()]));
+    targets
+}
+
+// This function extracts the text of an ATX heading (as in `## Setup ##`), if the line is one.
+fn heading(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start();
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let text = trimmed.trim_start_matches('#');
+    let level = trimmed.len() - text.len();
+    if !(1..=6).contains(&level) || !(text.is_empty() || text.starts_with([' ', '\t'])) {
+        return None;
+    }
+
+    // A closing sequence of `#`s is only part of the heading if it isn't preceded by a space.
+    let text = text.trim();
+    let closed = text.trim_end_matches('#');
+    Some(if closed.is_empty() || closed.ends_with([' ', '\t']) {
+        closed.trim_end()
+    } else {
+        text
+    })
+}
+
+// This function finds the local links and the headings in a Markdown file, skipping fenced code
+// blocks. Links are returned as file references whose labels are the destinations as written,
+// and headings as anchors whose labels are their text.
+#[must_use]
+pub fn parse(path: &Arc<Path>, text: &str) -> (Vec<Directive>, Vec<Directive>) {
+    let mut links = Vec::new();
+    let mut headings = Vec::new();
+    let mut open_fence = None::<String>;
+
+    for (index, (line, _)) in directive::lines(text).enumerate() {
+        if let Some(fence) = fence(line) {
+            match &open_fence {
+                None => open_fence = Some(fence.to_owned()),
+                Some(open)
+                    if fence.starts_with(open.as_str()) && line.trim().len() == fence.len() =>
+                {
+                    open_fence = None;
+                }
+                Some(_) => {}
+            }
+            continue;
+        }
+        if open_fence.is_some() {
+            continue;
+        }
+
+        let directive = |r#type, label: &str, span: Range<usize>| Directive {
+            r#type,
+            label: label.to_owned(),
+            path: path.clone(),
+            line_number: index + 1,
+            column: line[..span.start].chars().count() + 1,
+            span,
+            description: None,
+            attributes: BTreeMap::new(),
+            cell: None,
+        };
+
+        if let Some(text) = heading(line) {
+            let start = line.len() - line.trim_start().len();
+            headings.push(directive(Type::Anchor, text, start..line.trim_end().len()));
+        }
+        for target in link_targets(line) {
+            links.push(directive(Type::File, &line[target.clone()], target));
+        }
+    }
+
+    (links, headings)
+}
+
+// This function decodes the percent-encoded bytes in a link destination, as in `my%20notes.md`.
+// Invalid escapes are left alone.
+fn percent_decode(target: &str) -> String {
+    let bytes = target.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = bytes
+            .get(index + 1..index + 3)
+            .filter(|_| bytes[index] == b'%')
+            .and_then(|hex| u8::from_str_radix(str::from_utf8(hex).ok()?, 16).ok());
+        if let Some(byte) = escaped {
+            decoded.push(byte);
+            index += 3;
+        } else {
+            decoded.push(bytes[index]);
+            index += 1;
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// This function resolves the path that a link points to. Links are relative to the file
+// containing them, as in any Markdown renderer, except that a leading `/` makes them relative to
+// the root containing the file. Any query or fragment is left out. It returns `None` for links
+// which are only a query or fragment.
+fn resolve(link: &Directive, roots: &[PathBuf]) -> Option<PathBuf> {
+    let target = link.label.split(['#', '?']).next().unwrap_or_default();
+    let target = percent_decode(target);
+    if target.is_empty() {
+        None
+    } else if let Some(target) = target.strip_prefix('/') {
+        Some(walk::resolve(target, &link.path, false, roots))
+    } else {
+        Some(walk::resolve(
+            &format!("./{target}"),
+            &link.path,
+            true,
+            roots,
+        ))
+    }
+}
+
+// This function checks that the links in Markdown files point to files or directories. If
+// `strict_case` is set, the paths must have the same case as on disk. Links into archives aren't
+// checked. If a Git tree is given, the paths are checked against it rather than the filesystem.
+// It returns a vector of violations.
+#[must_use]
+pub fn check_links(
+    links: &[Directive],
+    roots: &[PathBuf],
+    strict_case: bool,
+    tree: Option<&Tree>,
+) -> Vec<Violation> {
+    let links = links
+        .iter()
+        .filter_map(|link| Some((link, resolve(link, roots)?)))
+        .filter(|(_, path)| archives::split(path).is_none())
+        .collect::<Vec<_>>();
+
+    // Look up the paths on disk all at once, since there may be many of them.
+    let targets = if tree.is_none() {
+        walk::Targets::look_up(links.iter().map(|(_, path)| path.clone()), strict_case)
+    } else {
+        walk::Targets::default()
+    };
+
+    links
+        .into_iter()
+        .filter_map(|(link, path)| {
+            let message = if let Some(tree) = tree {
+                if tree.is_file(&path) || tree.is_dir(&path) {
+                    return None;
+                } else if tree.is_untracked(&path) {
+                    format!("{link} points to a path which isn't tracked by Git.")
+                } else {
+                    format!("{link} does not point to a file or directory.")
+                }
+            } else {
+                match targets.metadata(&path) {
+                    Ok(_) if !strict_case => return None,
+                    Ok(_) => match targets.check_case(&path) {
+                        Ok(()) => return None,
+                        Err(error) => format!("{link} has the wrong case: {error}."),
+                    },
+                    Err(error) => format!("Error when validating {link}: {error}"),
+                }
+            };
+            Some(Violation {
+                kind: Kind::InvalidFileRef,
+                severity: Severity::Error,
+                message,
+                directives: vec![link.clone()],
+                suggestions: Vec::new(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::Directive,
+        markdown::{heading, is_markdown, parse, percent_decode},
+    };
+    use std::path::Path;
+
+    fn labels(directives: &[Directive]) -> Vec<(&str, usize, usize)> {
+        directives
+            .iter()
+            .map(|directive| {
+                (
+                    directive.label.as_str(),
+                    directive.line_number,
+                    directive.column,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn is_markdown_by_extension() {
+        assert!(is_markdown(Path::new("README.md")));
+        assert!(is_markdown(Path::new("docs/guide.Markdown")));
+        assert!(!is_markdown(Path::new("src/main.rs")));
+        assert!(!is_markdown(Path::new("LICENSE")));
+    }
+
+    #[test]
+    fn parse_links() {
+        let text = "\
+See [setup](docs/setup.md#install) and ![logo](<images/my logo.png> \"Logo\").
+Also [the spec](https://example.com), [above](#usage), and [mail](mailto:me@example.com).
+A [link](src/f(x).rs) with parentheses, and `[code](not/a/link.md)`.
+
+[setup]: ../guide.md 'Guide'
+[^note]: A footnote, not a definition.
+";
+        let (links, headings) = parse(&Path::new("docs/index.md").into(), text);
+
+        assert_eq!(
+            labels(&links),
+            vec![
+                ("docs/setup.md#install", 1, 13),
+                ("images/my logo.png", 1, 49),
+                ("src/f(x).rs", 3, 10),
+                ("../guide.md", 5, 10),
+            ],
+        );
+        assert_eq!(links[1].span, 48..66);
+        assert!(headings.is_empty());
+    }
+
+    #[test]
+    fn parse_skips_fences() {
+        let text = "\
+# Usage
+
+```markdown
+# Not a heading
+[not](a/link.md)
+```
+
+## Setup ##
+[link](setup.md)
+";
+        let (links, headings) = parse(&Path::new("README.md").into(), text);
+
+        assert_eq!(labels(&links), vec![("setup.md", 9, 8)]);
+        assert_eq!(labels(&headings), vec![("Usage", 1, 1), ("Setup", 8, 1)]);
+    }
+
+    #[test]
+    fn heading_text() {
+        assert_eq!(heading("# Title"), Some("Title"));
+        assert_eq!(heading("   ### Title ###  "), Some("Title"));
+        assert_eq!(heading("## C#"), Some("C#"));
+        assert_eq!(heading("#"), Some(""));
+        assert_eq!(heading("#hashtag"), None);
+        assert_eq!(heading("####### Too deep"), None);
+        assert_eq!(heading("    # Indented code"), None);
+    }
+
+    #[test]
+    fn percent_decode_escapes() {
+        assert_eq!(percent_decode("my%20notes.md"), "my notes.md");
+        assert_eq!(percent_decode("100%.md"), "100%.md");
+        assert_eq!(percent_decode("%zz"), "%zz");
+    }
+}
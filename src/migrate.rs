@@ -0,0 +1,494 @@
+use crate::{
+    directive::{self, Type},
+    edits::{Edit, Edits},
+    todos, walk,
+};
+use regex::Regex;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    io,
+    mem::take,
+    ops::Range,
+    path::PathBuf,
+    sync::{Arc, LazyLock, Mutex},
+};
+
+// These regexes match Doxygen anchors and the references to them, e.g., `\anchor retries` and
+// `@ref retries`. A period at the end of a name is left out, since it usually ends a sentence.
+static DOXYGEN_ANCHOR: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"[\\@]anchor\s+(?<label>\w[\w-]*)").unwrap() // Safe by inspection
+});
+static DOXYGEN_REF: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"[\\@]ref\s+(?<label>[\w:-]+(?:\.[\w:-]+)*)").unwrap() // Safe by inspection
+});
+
+// This enum represents the annotation schemes which can be migrated.
+#[derive(Clone, Debug)]
+pub enum Scheme {
+    // Ad-hoc to-do items which name a label in parentheses, e.g., `TODO(cleanup)`
+    TodoComments,
+
+    // Doxygen anchors and the references to them
+    DoxygenRefs,
+
+    // Regular expressions for the annotations which become tags and references, each with a group
+    // for the label
+    CustomRegex {
+        tag: Option<String>,
+        r#ref: Option<String>,
+    },
+}
+
+// This enum says what the annotations matched by a pattern become.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Role {
+    Tag,
+    Ref,
+
+    // A tag if it's the first annotation with its label, or a reference otherwise
+    Either,
+}
+
+// This struct represents a regex for annotations, along with what they become.
+#[derive(Clone, Debug)]
+struct Pattern {
+    regex: Regex,
+    role: Role,
+}
+
+// This struct represents an annotation found by a migration.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Occurrence {
+    pub line_number: usize,
+
+    // The byte range of the annotation within its line
+    pub range: Range<usize>,
+
+    // The annotation as written
+    pub text: String,
+
+    pub label: String,
+
+    // The text to keep in front of the label, e.g., the marker of a to-do item
+    keep: String,
+
+    role: Role,
+}
+
+// This struct represents an annotation which needs manual attention, along with the reason.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Ambiguity {
+    pub path: PathBuf,
+    pub line_number: usize,
+    pub text: String,
+    pub reason: &'static str,
+}
+
+// This struct says what each annotation becomes, by path and then by line number and byte offset,
+// along with the annotations which are left alone.
+#[derive(Clone, Debug, Default)]
+pub struct Plan {
+    pub types: HashMap<PathBuf, HashMap<(usize, usize), Type>>,
+    pub ambiguities: Vec<Ambiguity>,
+}
+
+// This struct finds and rewrites the annotations of a scheme.
+#[derive(Clone, Debug)]
+pub struct Migration {
+    patterns: Vec<Pattern>,
+}
+
+impl Migration {
+    // This function compiles the patterns for a scheme. The label is in the group named `label` or,
+    // failing that, the first group. The text in a group named `keep` is kept in front of the
+    // label.
+    pub fn new(scheme: &Scheme) -> Result<Self, String> {
+        let patterns = match scheme {
+            Scheme::TodoComments => vec![Pattern {
+                regex: todos::AD_HOC.clone(),
+                role: Role::Either,
+            }],
+            Scheme::DoxygenRefs => vec![
+                Pattern {
+                    regex: DOXYGEN_ANCHOR.clone(),
+                    role: Role::Tag,
+                },
+                Pattern {
+                    regex: DOXYGEN_REF.clone(),
+                    role: Role::Ref,
+                },
+            ],
+            Scheme::CustomRegex { tag, r#ref } => {
+                if tag.is_none() && r#ref.is_none() {
+                    return Err(
+                        "A custom migration needs a regular expression for tags, references, or \
+                         both."
+                            .to_owned(),
+                    );
+                }
+                [(tag, Role::Tag), (r#ref, Role::Ref)]
+                    .into_iter()
+                    .filter_map(|(pattern, role)| pattern.as_ref().map(|pattern| (pattern, role)))
+                    .map(|(pattern, role)| {
+                        let regex = Regex::new(pattern).map_err(|error| {
+                            format!("Invalid regular expression `{pattern}`: {error}")
+                        })?;
+                        if regex.captures_len() < 2 {
+                            return Err(format!(
+                                "The regular expression `{pattern}` has no group for the label.",
+                            ));
+                        }
+                        Ok(Pattern { regex, role })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?
+            }
+        };
+
+        Ok(Self { patterns })
+    }
+
+    // This function finds the annotations in the contents of a file.
+    #[must_use]
+    pub fn find(&self, contents: &str) -> Vec<Occurrence> {
+        directive::lines(contents)
+            .enumerate()
+            .flat_map(|(index, (line, _))| self.find_in_line(line, index + 1))
+            .collect()
+    }
+
+    // This function finds the annotations on a line, in order. Where they overlap, the one which
+    // starts first wins, or the tag if they start at the same place.
+    fn find_in_line(&self, line: &str, line_number: usize) -> Vec<Occurrence> {
+        let mut occurrences = self
+            .patterns
+            .iter()
+            .flat_map(|pattern| {
+                pattern
+                    .regex
+                    .captures_iter(line)
+                    .filter_map(move |captures| {
+                        let annotation = captures.get(0)?;
+                        let label = captures.name("label").or_else(|| captures.get(1))?;
+                        Some(Occurrence {
+                            line_number,
+                            range: annotation.range(),
+                            text: annotation.as_str().to_owned(),
+                            label: label.as_str().trim().to_owned(),
+                            keep: captures
+                                .name("keep")
+                                .map_or("", |keep| keep.as_str())
+                                .to_owned(),
+                            role: pattern.role,
+                        })
+                    })
+            })
+            .collect::<Vec<_>>();
+        occurrences.sort_by_key(|occurrence| occurrence.range.start);
+
+        let mut end = 0;
+        occurrences.retain(|occurrence| {
+            let keep = occurrence.range.start >= end;
+            if keep {
+                end = occurrence.range.end;
+            }
+            keep
+        });
+        occurrences
+    }
+
+    // This function rewrites the annotations in the contents of a file as labels. `sigil`
+    // determines the sigil for each one from its line number and byte offset within the line, and
+    // the ones it returns `None` for are left alone.
+    #[must_use]
+    pub fn rewrite(
+        &self,
+        contents: &str,
+        sigil: impl Fn(usize, usize) -> Option<String>,
+    ) -> Vec<Edit> {
+        let mut edits = Vec::new();
+        let mut offset = 0;
+
+        for (index, (line, ending)) in directive::lines(contents).enumerate() {
+            for occurrence in self.find_in_line(line, index + 1) {
+                let Some(sigil) = sigil(occurrence.line_number, occurrence.range.start) else {
+                    continue;
+                };
+                let mut replacement = occurrence.keep;
+                if !replacement.is_empty() && !replacement.ends_with(char::is_whitespace) {
+                    replacement.push(' ');
+                }
+                let _ = write!(replacement, "[{sigil}:{}]", occurrence.label);
+                edits.push(Edit {
+                    range: offset + occurrence.range.start..offset + occurrence.range.end,
+                    replacement,
+                });
+            }
+            offset += line.len() + ending.len();
+        }
+
+        edits
+    }
+
+    // This function finds the annotations in the files, along with the paths of the files
+    // containing them (relative to the working directory).
+    pub fn find_all(
+        &self,
+        paths: &[PathBuf],
+        options: &walk::Options,
+    ) -> Result<Vec<(PathBuf, Occurrence)>, String> {
+        let migration = Arc::new(self.clone());
+        let occurrences = Arc::new(Mutex::new(Vec::new()));
+        let occurrences_clone = occurrences.clone();
+        walk::walk(paths, options, move |path, file| {
+            if let Ok(contents) = io::read_to_string(file) {
+                let path = walk::relative(path);
+                // The `unwrap` is safe assuming no poisoning.
+                occurrences_clone.lock().unwrap().extend(
+                    migration
+                        .find(&contents)
+                        .into_iter()
+                        .map(|occurrence| (path.clone(), occurrence)),
+                );
+            }
+        })?;
+
+        // The `unwrap` is safe assuming no poisoning.
+        Ok(take(&mut *occurrences.lock().unwrap()))
+    }
+
+    // This function finds the edits which rewrite the annotations in the files as the plan says,
+    // with the given sigils.
+    pub fn rewrite_all(
+        &self,
+        paths: &[PathBuf],
+        options: &walk::Options,
+        types: HashMap<PathBuf, HashMap<(usize, usize), Type>>,
+        tag_sigil: String,
+        ref_sigil: String,
+    ) -> Result<Edits, String> {
+        let migration = Arc::new(self.clone());
+        Edits::collect(paths, options, move |path, contents| {
+            let Some(types) = types.get(&walk::relative(path)) else {
+                return Vec::new();
+            };
+            migration.rewrite(contents, |line_number, offset| {
+                types
+                    .get(&(line_number, offset))
+                    .map(|r#type| match r#type {
+                        Type::Tag => tag_sigil.clone(),
+                        _ => ref_sigil.clone(),
+                    })
+            })
+        })
+    }
+}
+
+// This function decides what each annotation becomes. Annotations are grouped by label, keyed as in
+// the index, and `tag_exists` says whether a tag already exists for a key. An annotation is left
+// alone if converting it could leave a label with no tag or several tags. Annotations which can be
+// either become a tag if they're the first with their label (in order of path) and there's no tag
+// for it yet, or references otherwise.
+#[must_use]
+pub fn plan(
+    occurrences: Vec<(PathBuf, Occurrence)>,
+    tag_exists: impl Fn(&str) -> bool,
+    case_insensitive_labels: bool,
+) -> Plan {
+    let mut by_key = BTreeMap::<String, Vec<(PathBuf, Occurrence)>>::new();
+    for (path, occurrence) in occurrences {
+        by_key
+            .entry(directive::key(&occurrence.label, case_insensitive_labels).into_owned())
+            .or_default()
+            .push((path, occurrence));
+    }
+
+    let mut plan = Plan::default();
+    for (key, mut occurrences) in by_key {
+        occurrences.sort_by(|(x, x_occurrence), (y, y_occurrence)| {
+            (x, x_occurrence.line_number, x_occurrence.range.start).cmp(&(
+                y,
+                y_occurrence.line_number,
+                y_occurrence.range.start,
+            ))
+        });
+        let exists = tag_exists(&key);
+        let count = |role| {
+            occurrences
+                .iter()
+                .filter(|(_, occurrence)| occurrence.role == role)
+                .count()
+        };
+        let (tags, eithers) = (count(Role::Tag), count(Role::Either));
+
+        let mut tagged = exists;
+        for (path, occurrence) in occurrences {
+            let decision = if key.is_empty() || key.contains(']') {
+                Err("It isn't a valid label.")
+            } else {
+                match occurrence.role {
+                    Role::Tag if exists => Err("A tag with this label already exists."),
+                    Role::Ref if exists => Ok(Type::Ref),
+                    Role::Tag | Role::Ref if tags > 1 => Err("Several tags would have this label."),
+                    Role::Tag => Ok(Type::Tag),
+                    Role::Ref if tags == 1 => Ok(Type::Ref),
+                    Role::Ref => Err("There's no tag with this label."),
+                    Role::Either if !exists && eithers == 1 => Err(
+                        "It's the only one with this label, which may name a person rather than a \
+                         topic.",
+                    ),
+                    Role::Either if tagged => Ok(Type::Ref),
+                    Role::Either => {
+                        tagged = true;
+                        Ok(Type::Tag)
+                    }
+                }
+            };
+
+            match decision {
+                Ok(r#type) => {
+                    plan.types
+                        .entry(path)
+                        .or_default()
+                        .insert((occurrence.line_number, occurrence.range.start), r#type);
+                }
+                Err(reason) => plan.ambiguities.push(Ambiguity {
+                    path,
+                    line_number: occurrence.line_number,
+                    text: occurrence.text,
+                    reason,
+                }),
+            }
+        }
+    }
+
+    plan.ambiguities
+        .sort_by(|x, y| (&x.path, x.line_number, &x.text).cmp(&(&y.path, y.line_number, &y.text)));
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::Type,
+        edits::splice,
+        migrate::{Migration, Plan, Scheme, plan},
+    };
+    use std::path::{Path, PathBuf};
+
+    // This function plans a migration of the given files.
+    fn plan_files(migration: &Migration, files: &[(&str, &str)], existing_tags: &[&str]) -> Plan {
+        plan(
+            files
+                .iter()
+                .flat_map(|(path, contents)| {
+                    migration
+                        .find(contents)
+                        .into_iter()
+                        .map(move |occurrence| (PathBuf::from(path), occurrence))
+                })
+                .collect(),
+            |key| existing_tags.contains(&key),
+            false,
+        )
+    }
+
+    #[test]
+    fn plan_todo_comments() {
+        let migration = Migration::new(&Scheme::TodoComments).unwrap();
+
+        let plan = plan_files(
+            &migration,
+            &[
+                ("b.rs", "// TODO(cleanup): b\n// TODO(alice): c\n"),
+                ("a.rs", "// TODO(cleanup): a\n// FIXME(retries)\n"),
+            ],
+            &["retries"],
+        );
+
+        assert_eq!(plan.types[Path::new("a.rs")][&(1, 3)], Type::Tag);
+        assert_eq!(plan.types[Path::new("a.rs")][&(2, 3)], Type::Ref);
+        assert_eq!(plan.types[Path::new("b.rs")][&(1, 3)], Type::Ref);
+        assert_eq!(plan.ambiguities.len(), 1);
+        assert_eq!(plan.ambiguities[0].text, "TODO(alice)");
+    }
+
+    #[test]
+    fn plan_doxygen_refs() {
+        let migration = Migration::new(&Scheme::DoxygenRefs).unwrap();
+
+        let plan = plan_files(
+            &migration,
+            &[
+                (
+                    "a.h",
+                    "/// \\anchor retries\n/// \\anchor twice\n/// @anchor twice\n",
+                ),
+                (
+                    "b.c",
+                    "// See @ref retries. Also \\ref Foo::bar and \\ref twice.\n",
+                ),
+            ],
+            &[],
+        );
+
+        assert_eq!(plan.types[Path::new("a.h")][&(1, 4)], Type::Tag);
+        assert_eq!(plan.types[Path::new("b.c")][&(1, 7)], Type::Ref);
+        assert_eq!(
+            plan.ambiguities
+                .iter()
+                .map(|ambiguity| (ambiguity.text.as_str(), ambiguity.reason))
+                .collect::<Vec<_>>(),
+            vec![
+                ("\\anchor twice", "Several tags would have this label."),
+                ("@anchor twice", "Several tags would have this label."),
+                ("\\ref Foo::bar", "There's no tag with this label."),
+                ("\\ref twice", "Several tags would have this label."),
+            ],
+        );
+    }
+
+    #[test]
+    fn rewrite_custom_regex() {
+        let migration = Migration::new(&Scheme::CustomRegex {
+            tag: Some(r"\{#(\w+)\}".to_owned()),
+            r#ref: Some(r"(?<keep>see) #(?<label>\w+)".to_owned()),
+        })
+        .unwrap();
+
+        let contents = "{#intro}\r\nFor details, see #intro or see #other.\n";
+
+        let edits = migration.rewrite(contents, |line_number, offset| {
+            match (line_number, offset) {
+                (1, 0) => Some("tag".to_owned()),
+                (2, 13) => Some("ref".to_owned()),
+                _ => None,
+            }
+        });
+
+        assert_eq!(
+            splice(contents, &edits),
+            "[?tag:intro]\r\nFor details, see [?ref:intro] or see #other.\n".replace('?', ""),
+        );
+        assert_eq!(edits.len(), 2);
+        assert_eq!(edits[1].range.start, 23);
+    }
+
+    #[test]
+    fn custom_regex_without_group() {
+        assert!(
+            Migration::new(&Scheme::CustomRegex {
+                tag: Some(r"\{#\w+\}".to_owned()),
+                r#ref: None,
+            })
+            .is_err(),
+        );
+        assert!(
+            Migration::new(&Scheme::CustomRegex {
+                tag: None,
+                r#ref: None,
+            })
+            .is_err(),
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use crate::{
+    count::count,
+    directive::{self, Directive},
+    namespaces,
+    violation::{Kind, Severity, Violation},
+};
+use std::collections::{BTreeMap, HashMap};
+
+// This is the attribute which sets the minimum number of references to a tag, e.g., `min-refs=2`.
+pub const ATTRIBUTE: &str = "min-refs";
+
+// This function determines the minimum number of references to a tag from the configuration.
+// Each entry is keyed by a label or a namespace, which covers all the tags in it. An entry for the
+// label itself takes precedence over the namespaces, and nested namespaces take precedence over
+// the namespaces containing them. The label is keyed as in the index.
+fn configured(
+    minimums: &BTreeMap<String, usize>,
+    label: &str,
+    case_insensitive_labels: bool,
+) -> Option<usize> {
+    minimums
+        .iter()
+        .filter_map(|(entry, minimum)| {
+            let entry = directive::key(entry, case_insensitive_labels);
+            if entry == label {
+                Some((usize::MAX, *minimum))
+            } else if namespaces::contains(&entry, label) {
+                Some((entry.len(), *minimum))
+            } else {
+                None
+            }
+        })
+        .max_by_key(|(specificity, _)| *specificity)
+        .map(|(_, minimum)| minimum)
+}
+
+// This function determines the minimum number of references to a tag, as `check` does, but
+// ignoring invalid attributes. The tags are the duplicates of one tag, keyed as in the index.
+#[must_use]
+pub fn minimum(
+    label: &str,
+    tags: &[Directive],
+    minimums: &BTreeMap<String, usize>,
+    case_insensitive_labels: bool,
+) -> Option<usize> {
+    tags.iter()
+        .filter_map(|tag| tag.attributes.get(ATTRIBUTE)?.parse::<usize>().ok())
+        .max()
+        .or_else(|| configured(minimums, label, case_insensitive_labels))
+}
+
+// This function checks that the tags which require several references have enough of them. The
+// minimum comes from the `min-refs` attribute of the tag or, failing that, the configuration. If
+// duplicates of a tag disagree, the largest minimum applies. The tags are keyed as in the index.
+// It returns a vector of violations.
+#[must_use]
+pub fn check(
+    tags_map: &BTreeMap<String, Vec<Directive>>,
+    refs: &[Directive],
+    minimums: &BTreeMap<String, usize>,
+    case_insensitive_labels: bool,
+) -> Vec<Violation> {
+    let mut refs_map = HashMap::<_, Vec<&Directive>>::new();
+    for r#ref in refs {
+        refs_map
+            .entry(directive::key(&r#ref.label, case_insensitive_labels))
+            .or_default()
+            .push(r#ref);
+    }
+
+    let mut violations = Vec::new();
+    for (label, tags) in tags_map {
+        // Read the minimum from the attributes, reporting the ones which aren't numbers.
+        let mut minimum = None;
+        for tag in tags {
+            let Some(text) = tag.attributes.get(ATTRIBUTE) else {
+                continue;
+            };
+            match text.parse::<usize>() {
+                Ok(value) => minimum = minimum.max(Some(value)),
+                Err(_) => violations.push(Violation {
+                    kind: Kind::TooFewRefs,
+                    severity: Severity::Error,
+                    message: format!(
+                        "{tag} has an invalid minimum number of references `{text}`. Expected a \
+                         number like `2`.",
+                    ),
+                    directives: vec![tag.clone()],
+                    suggestions: Vec::new(),
+                }),
+            }
+        }
+        let Some(minimum) =
+            minimum.or_else(|| configured(minimums, label, case_insensitive_labels))
+        else {
+            continue;
+        };
+
+        let refs = refs_map.get(label.as_str()).map_or(&[][..], Vec::as_slice);
+        if refs.len() < minimum {
+            violations.push(Violation {
+                kind: Kind::TooFewRefs,
+                severity: Severity::Error,
+                message: format!(
+                    "{} requires at least {} but has {}.",
+                    tags[0],
+                    count(minimum, "reference"),
+                    refs.len(),
+                ),
+                directives: tags.iter().chain(refs.iter().copied()).cloned().collect(),
+                suggestions: Vec::new(),
+            });
+        }
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::{Directive, Type},
+        min_refs::{check, configured},
+        violation::Kind,
+    };
+    use std::collections::BTreeMap;
+
+    fn directive(r#type: Type, label: &str, attributes: &[(&str, &str)]) -> Directive {
+        Directive {
+            attributes: attributes
+                .iter()
+                .map(|(key, value)| ((*key).to_owned(), (*value).to_owned()))
+                .collect(),
+            ..Directive::new(r#type, label, "./foo.rs", 1)
+        }
+    }
+
+    fn tags_map(tags: Vec<Directive>) -> BTreeMap<String, Vec<Directive>> {
+        let mut tags_map = BTreeMap::<_, Vec<_>>::new();
+        for tag in tags {
+            tags_map.entry(tag.label.clone()).or_default().push(tag);
+        }
+        tags_map
+    }
+
+    #[test]
+    fn configured_most_specific() {
+        let minimums = BTreeMap::from([
+            ("wire".to_owned(), 2),
+            ("wire/v2".to_owned(), 3),
+            ("wire/v2/header".to_owned(), 4),
+        ]);
+
+        assert_eq!(configured(&minimums, "wire/v1/header", false), Some(2));
+        assert_eq!(configured(&minimums, "wire/v2/body", false), Some(3));
+        assert_eq!(configured(&minimums, "wire/v2/header", false), Some(4));
+        assert_eq!(configured(&minimums, "wire", false), Some(2));
+        assert_eq!(configured(&minimums, "wired", false), None);
+        assert_eq!(
+            configured(&BTreeMap::from([("Wire".to_owned(), 2)]), "wire/v1", true),
+            Some(2),
+        );
+    }
+
+    #[test]
+    fn check_attribute() {
+        let tags_map = tags_map(vec![
+            directive(Type::Tag, "format", &[("min-refs", "2")]),
+            directive(Type::Tag, "other", &[]),
+        ]);
+        let refs = [
+            directive(Type::Ref, "format", &[]),
+            directive(Type::Ref, "other", &[]),
+        ];
+
+        let violations = check(&tags_map, &refs, &BTreeMap::new(), false);
+
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].kind, Kind::TooFewRefs);
+        assert!(
+            violations[0]
+                .message
+                .contains("requires at least 2 references but has 1")
+        );
+        assert_eq!(violations[0].directives.len(), 2);
+        assert!(
+            check(
+                &tags_map,
+                &[refs[0].clone(), refs[0].clone()],
+                &BTreeMap::new(),
+                false
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn check_attribute_overrides_configuration() {
+        let tags_map = tags_map(vec![directive(Type::Tag, "wire/a", &[("min-refs", "0")])]);
+        let minimums = BTreeMap::from([("wire".to_owned(), 2)]);
+
+        assert!(check(&tags_map, &[], &minimums, false).is_empty());
+    }
+
+    #[test]
+    fn check_configuration() {
+        let tags_map = tags_map(vec![directive(Type::Tag, "wire/a", &[])]);
+        let minimums = BTreeMap::from([("wire".to_owned(), 1)]);
+
+        assert_eq!(check(&tags_map, &[], &minimums, false).len(), 1);
+    }
+
+    #[test]
+    fn check_invalid_attribute() {
+        let tags_map = tags_map(vec![directive(Type::Tag, "a", &[("min-refs", "two")])]);
+
+        let violations = check(&tags_map, &[], &BTreeMap::new(), false);
+
+        assert_eq!(violations.len(), 1);
+        assert!(violations[0].message.contains("invalid minimum"));
+    }
+}
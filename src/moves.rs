@@ -0,0 +1,328 @@
+use crate::{
+    dir_references::is_glob, directive::Type, file_references::split_location, fix::replacement,
+    index::Index, walk,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Reverse,
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+// This struct describes a file or directory which moves. The paths are normalized and relative to
+// the working directory, like the targets of file and directory references.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Move {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+// This struct describes a new label for a file or directory reference.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Relabel {
+    pub r#type: Type,
+    pub old: String,
+    pub new: String,
+}
+
+// This function finds where a path ends up when `from` is moved to `to`, if it's `from` itself or
+// inside it.
+fn shift(path: &Path, from: &Path, to: &Path) -> Option<PathBuf> {
+    path.strip_prefix(from).ok().map(|rest| {
+        if rest.as_os_str().is_empty() {
+            to.to_owned()
+        } else {
+            to.join(rest)
+        }
+    })
+}
+
+impl Move {
+    #[must_use]
+    pub fn new(from: &Path, to: &Path) -> Self {
+        Self {
+            from: walk::normalize(from),
+            to: walk::normalize(to),
+        }
+    }
+
+    // This function moves the file or directory, creating the parent directories of the
+    // destination as needed.
+    pub fn perform(&self) -> Result<(), String> {
+        let from = self.from.to_string_lossy();
+        let to = self.to.to_string_lossy();
+        if self.to.exists() {
+            return Err(format!("{to} already exists."));
+        }
+        if let Some(parent) = self
+            .to
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)
+                .map_err(|error| format!("Unable to create {}: {error}", parent.display()))?;
+        }
+        fs::rename(&self.from, &self.to)
+            .map_err(|error| format!("Unable to move {from} to {to}: {error}"))
+    }
+}
+
+// This function finds where a path ends up after the moves, trying them in order.
+pub(crate) fn relocate(path: &Path, moves: &[Move]) -> Option<PathBuf> {
+    moves
+        .iter()
+        .find_map(|r#move| shift(path, &r#move.from, &r#move.to))
+}
+
+// This function finds where a path was before the moves, trying them in order.
+fn unlocate(path: &Path, moves: &[Move]) -> Option<PathBuf> {
+    moves
+        .iter()
+        .find_map(|r#move| shift(path, &r#move.to, &r#move.from))
+}
+
+// This function infers which directories moved from the renames of the files in them, since Git
+// only tracks files. A directory is considered moved if it no longer exists and all the renamed
+// files which were in it ended up at the same paths within a single new directory. Deeper
+// directories come first.
+#[must_use]
+pub fn directories(renames: &[Move]) -> Vec<Move> {
+    // Each candidate maps to its new location, or `None` if the renames disagree about it.
+    let mut candidates = BTreeMap::<&Path, Option<PathBuf>>::new();
+    for rename in renames {
+        for ancestor in rename.from.ancestors().skip(1) {
+            if ancestor.as_os_str().is_empty() {
+                break;
+            }
+
+            // The `unwrap` is safe because `ancestor` is an ancestor of the path.
+            let rest = rename.from.strip_prefix(ancestor).unwrap();
+            let to = rename
+                .to
+                .ends_with(rest)
+                .then(|| {
+                    let components = rename.to.components().collect::<Vec<_>>();
+                    let prefix = components.len() - rest.components().count();
+                    components[..prefix].iter().collect::<PathBuf>()
+                })
+                .filter(|to| !to.as_os_str().is_empty());
+            candidates
+                .entry(ancestor)
+                .and_modify(|existing| {
+                    if *existing != to {
+                        *existing = None;
+                    }
+                })
+                .or_insert(to);
+        }
+    }
+
+    let mut directories = candidates
+        .into_iter()
+        .filter(|(from, _)| !from.exists())
+        .filter_map(|(from, to)| {
+            to.map(|to| Move {
+                from: from.to_owned(),
+                to,
+            })
+        })
+        .collect::<Vec<_>>();
+    directories.sort_by_key(|r#move| Reverse(r#move.from.components().count()));
+    directories
+}
+
+// This function computes the new labels for the file and directory references which are affected
+// by the moves, grouped by the file containing them (relative to the working directory, as it is
+// in the index). References relative to the file containing them stay that way, and are updated
+// if only that file moves. If `already_moved` is set, the index reflects the files after the
+// moves rather than before them.
+#[must_use]
+pub fn relabel(
+    index: &Index,
+    moves: &[Move],
+    already_moved: bool,
+) -> BTreeMap<PathBuf, Vec<Relabel>> {
+    let mut relabels = BTreeMap::<PathBuf, Vec<Relabel>>::new();
+    for directive in index.files.iter().chain(&index.dirs) {
+        let path = if directive.r#type == Type::File {
+            split_location(&directive.label).0
+        } else if is_glob(&directive.label) {
+            continue;
+        } else {
+            directive.label.as_str()
+        };
+
+        let source = walk::relative(&directive.path);
+        let (old_source, new_source) = if already_moved {
+            (
+                unlocate(&source, moves).unwrap_or(source.clone()),
+                source.clone(),
+            )
+        } else {
+            (
+                source.clone(),
+                relocate(&source, moves).unwrap_or(source.clone()),
+            )
+        };
+        let target = walk::resolve(path, &old_source, index.relative_paths, &index.roots);
+        let new_target = relocate(&target, moves);
+
+        let new_path = if index.relative_paths && walk::is_relative(path) {
+            walk::relative_to(
+                &new_target.unwrap_or(target),
+                new_source.parent().unwrap_or(Path::new("")),
+            )
+        } else if let Some(new_target) = new_target {
+            // Within a root, the new path has to be relative to it.
+            match walk::root(&new_source, &index.roots).map(walk::normalize) {
+                Some(root) => {
+                    if let Ok(new_target) = new_target.strip_prefix(&root) {
+                        new_target.to_owned()
+                    } else {
+                        log::warn!(
+                            "Unable to update {directive}, since its target would be outside of \
+                             {}.",
+                            root.to_string_lossy(),
+                        );
+                        continue;
+                    }
+                }
+                None => new_target,
+            }
+        } else {
+            continue;
+        };
+
+        let relabel = Relabel {
+            r#type: directive.r#type.clone(),
+            old: directive.label.clone(),
+            new: replacement(&directive.r#type, &directive.label, &new_path),
+        };
+        let file_relabels = relabels.entry(source).or_default();
+        if relabel.old != relabel.new && !file_relabels.contains(&relabel) {
+            file_relabels.push(relabel);
+        }
+    }
+
+    relabels.retain(|_, file_relabels| !file_relabels.is_empty());
+    relabels
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::{Directive, Type},
+        index::Index,
+        moves::{Move, Relabel, directories, relabel},
+    };
+    use std::path::{Path, PathBuf};
+
+    fn relabels(index: &Index, moves: &[Move], already_moved: bool) -> Vec<(PathBuf, String)> {
+        relabel(index, moves, already_moved)
+            .into_iter()
+            .flat_map(|(path, relabels)| {
+                relabels
+                    .into_iter()
+                    .map(move |Relabel { new, .. }| (path.clone(), new))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn relabel_moved_directory() {
+        let index = Index {
+            files: vec![
+                Directive::new(Type::File, "src/util/a.rs:3", "./README.md", 1),
+                Directive::new(Type::File, "src/main.rs", "./README.md", 1),
+            ],
+            dirs: vec![Directive::new(Type::Dir, "src/util", "./src/main.rs", 1)],
+            ..Index::default()
+        };
+        let moves = [Move::new(Path::new("src/util"), Path::new("lib"))];
+
+        assert_eq!(
+            relabels(&index, &moves, false),
+            vec![
+                (PathBuf::from("README.md"), "lib/a.rs:3".to_owned()),
+                (PathBuf::from("src/main.rs"), "lib".to_owned()),
+            ],
+        );
+    }
+
+    #[test]
+    fn relabel_relative() {
+        let index = Index {
+            files: vec![
+                Directive::new(Type::File, "./b.rs", "./src/a.rs", 1),
+                Directive::new(Type::File, "./c.rs", "./src/a.rs", 1),
+            ],
+            relative_paths: true,
+            ..Index::default()
+        };
+        let moves = [Move::new(Path::new("src/a.rs"), Path::new("lib/a.rs"))];
+
+        assert_eq!(
+            relabels(&index, &moves, false),
+            vec![
+                (PathBuf::from("src/a.rs"), "../src/b.rs".to_owned()),
+                (PathBuf::from("src/a.rs"), "../src/c.rs".to_owned()),
+            ],
+        );
+    }
+
+    #[test]
+    fn relabel_already_moved() {
+        let index = Index {
+            files: vec![Directive::new(Type::File, "./b.rs", "./lib/a.rs", 1)],
+            relative_paths: true,
+            ..Index::default()
+        };
+        let moves = [Move::new(Path::new("src/a.rs"), Path::new("lib/a.rs"))];
+
+        assert_eq!(
+            relabels(&index, &moves, true),
+            vec![(PathBuf::from("lib/a.rs"), "../src/b.rs".to_owned())],
+        );
+    }
+
+    #[test]
+    fn relabel_unaffected() {
+        let index = Index {
+            files: vec![Directive::new(Type::File, "src/main.rs", "./README.md", 1)],
+            dirs: vec![Directive::new(Type::Dir, "src/**/util", "./README.md", 1)],
+            ..Index::default()
+        };
+        let moves = [Move::new(Path::new("src/util"), Path::new("lib"))];
+
+        assert!(relabels(&index, &moves, false).is_empty());
+    }
+
+    #[test]
+    fn directories_consistent() {
+        let renames = [
+            Move::new(Path::new("old/a/x.rs"), Path::new("new/a/x.rs")),
+            Move::new(Path::new("old/b/y.rs"), Path::new("new/b/y.rs")),
+        ];
+
+        assert_eq!(
+            directories(&renames),
+            vec![
+                Move::new(Path::new("old/a"), Path::new("new/a")),
+                Move::new(Path::new("old/b"), Path::new("new/b")),
+                Move::new(Path::new("old"), Path::new("new")),
+            ],
+        );
+    }
+
+    #[test]
+    fn directories_inconsistent() {
+        let renames = [
+            Move::new(Path::new("old/x.rs"), Path::new("new/x.rs")),
+            Move::new(Path::new("old/y.rs"), Path::new("other/y.rs")),
+            Move::new(Path::new("old/z.rs"), Path::new("old/w.rs")),
+        ];
+
+        assert!(directories(&renames).is_empty());
+    }
+}
@@ -0,0 +1,245 @@
+use crate::{
+    directive::Directive,
+    violation::{Kind, Severity, Violation},
+    walk,
+};
+use regex::Regex;
+use serde::{Deserialize, Deserializer, de::Error};
+use std::{collections::BTreeMap, path::PathBuf};
+
+// Labels are organized into namespaces with slashes, as in `security/input-validation`. This
+// function returns the namespace of a label (e.g., `security`), if it has one.
+#[must_use]
+pub fn namespace(label: &str) -> Option<&str> {
+    label.rsplit_once('/').map(|(namespace, _)| namespace)
+}
+
+// This function determines whether a label is in the given namespace, including any namespaces
+// nested inside of it.
+#[must_use]
+pub fn contains(namespace: &str, label: &str) -> bool {
+    label
+        .strip_prefix(namespace)
+        .is_some_and(|rest| rest.starts_with('/'))
+}
+
+// This struct wraps a regular expression so it can be read from a configuration file and compared.
+#[derive(Clone, Debug)]
+pub struct Pattern(pub Regex);
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl Eq for Pattern {}
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        Regex::new(&pattern)
+            .map(Self)
+            .map_err(|error| D::Error::custom(format!("Invalid regex `{pattern}`: {error}")))
+    }
+}
+
+// This struct specifies the rules for the tags in a namespace, including the namespaces nested
+// inside of it. If several rules apply to a label, the one for the innermost namespace wins.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Rule {
+    pub namespace: String,
+
+    // If given, the part of each label after the namespace must match this regex.
+    pub pattern: Option<Pattern>,
+
+    // If nonempty, references to tags in the namespace may only appear in these paths.
+    pub allowed_paths: Vec<PathBuf>,
+}
+
+// This function finds the rule which applies to a label. Among rules for the same namespace, the
+// last one wins, so later rules can override earlier ones.
+fn rule<'a>(rules: &'a [Rule], label: &str) -> Option<&'a Rule> {
+    rules
+        .iter()
+        .filter(|rule| contains(&rule.namespace, label))
+        .max_by_key(|rule| rule.namespace.len())
+}
+
+// This function checks that the tags follow the naming conventions of their namespaces and that
+// references to tags in restricted namespaces only appear in the allowed paths. It returns a
+// vector of violations.
+#[must_use]
+pub fn check(
+    tags_map: &BTreeMap<String, Vec<Directive>>,
+    refs: &[Directive],
+    rules: &[Rule],
+) -> Vec<Violation> {
+    let mut errors = Vec::<Violation>::new();
+
+    if rules.is_empty() {
+        return errors;
+    }
+
+    for (label, directives) in tags_map {
+        let Some(rule) = rule(rules, label) else {
+            continue;
+        };
+        let Some(Pattern(regex)) = &rule.pattern else {
+            continue;
+        };
+
+        // The slicing is safe because the rule applies to the label, so the label starts with the
+        // namespace and a slash.
+        let name = &label[rule.namespace.len() + 1..];
+        if !regex.is_match(name) {
+            for directive in directives {
+                errors.push(Violation {
+                    kind: Kind::InvalidTagName,
+                    severity: Severity::Error,
+                    message: format!(
+                        "{directive} does not match the pattern `{}` for namespace `{}`.",
+                        regex.as_str(),
+                        rule.namespace,
+                    ),
+                    directives: vec![directive.clone()],
+                    suggestions: Vec::new(),
+                });
+            }
+        }
+    }
+
+    for r#ref in refs {
+        let Some(rule) = rule(rules, &r#ref.label) else {
+            continue;
+        };
+        if rule.allowed_paths.is_empty() {
+            continue;
+        }
+
+        let path = walk::relative(&r#ref.path);
+        if !rule
+            .allowed_paths
+            .iter()
+            .any(|allowed_path| path.starts_with(allowed_path))
+        {
+            errors.push(Violation {
+                kind: Kind::ForbiddenRef,
+                severity: Severity::Error,
+                message: format!(
+                    "{ref} is not allowed to reference tags in namespace `{}`.",
+                    rule.namespace,
+                ),
+                directives: vec![r#ref.clone()],
+                suggestions: Vec::new(),
+            });
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::{Directive, Type},
+        namespaces::{Pattern, Rule, check, contains, namespace},
+        violation::Kind,
+    };
+    use regex::Regex;
+    use std::{
+        collections::BTreeMap,
+        path::{Path, PathBuf},
+    };
+
+    fn tags_map(labels: &[&str]) -> BTreeMap<String, Vec<Directive>> {
+        labels
+            .iter()
+            .map(|label| {
+                (
+                    (*label).to_owned(),
+                    vec![Directive::new(Type::Tag, label, "./foo.rs", 1)],
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn namespace_none() {
+        assert_eq!(namespace("foo"), None);
+    }
+
+    #[test]
+    fn namespace_nested() {
+        assert_eq!(namespace("foo/bar/baz"), Some("foo/bar"));
+    }
+
+    #[test]
+    fn contains_prefix_only() {
+        assert!(contains("foo", "foo/bar"));
+        assert!(contains("foo", "foo/bar/baz"));
+        assert!(!contains("foo", "foobar/baz"));
+        assert!(!contains("foo", "foo"));
+    }
+
+    #[test]
+    fn check_no_rules() {
+        assert!(check(&tags_map(&["Foo/Bar"]), &[], &[]).is_empty());
+    }
+
+    #[test]
+    fn check_pattern() {
+        let rules = [Rule {
+            namespace: "foo".to_owned(),
+            pattern: Some(Pattern(Regex::new("^[a-z-]+$").unwrap())),
+            ..Rule::default()
+        }];
+
+        let violations = check(&tags_map(&["foo/bar-baz", "foo/Qux", "Qux"]), &[], &rules);
+
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].kind, Kind::InvalidTagName);
+        assert_eq!(violations[0].directives[0].label, "foo/Qux");
+    }
+
+    #[test]
+    fn check_innermost_rule_wins() {
+        let rules = [
+            Rule {
+                namespace: "foo".to_owned(),
+                pattern: Some(Pattern(Regex::new("^[a-z]+$").unwrap())),
+                ..Rule::default()
+            },
+            Rule {
+                namespace: "foo/bar".to_owned(),
+                ..Rule::default()
+            },
+        ];
+
+        assert!(check(&tags_map(&["foo/bar/BAZ"]), &[], &rules).is_empty());
+    }
+
+    #[test]
+    fn check_allowed_paths() {
+        let rules = [Rule {
+            namespace: "foo".to_owned(),
+            allowed_paths: vec![PathBuf::from("src/foo")],
+            ..Rule::default()
+        }];
+        let refs = [
+            Directive::new(Type::Ref, "foo/bar", "./src/foo/baz.rs", 1),
+            Directive::new(Type::Ref, "foo/bar", "./src/qux.rs", 1),
+            Directive::new(Type::Ref, "bar", "./src/qux.rs", 1),
+        ];
+
+        let violations = check(&BTreeMap::new(), &refs, &rules);
+
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].kind, Kind::ForbiddenRef);
+        assert_eq!(
+            &*violations[0].directives[0].path,
+            Path::new("./src/qux.rs")
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use crate::{
+    directive::Directive,
+    namespaces::Pattern,
+    violation::{Kind, Severity, Violation},
+};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+// This struct specifies the naming conventions for all tags, regardless of their namespaces.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Conventions {
+    // If given, each label must match this regex.
+    pub pattern: Option<Pattern>,
+
+    // If given, labels may have at most this many characters.
+    pub max_length: Option<usize>,
+
+    // These labels may not be used, regardless of case.
+    pub reserved: Vec<String>,
+}
+
+impl Conventions {
+    // This function explains the ways in which a label breaks the conventions, if any.
+    fn problems(&self, label: &str) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if self
+            .reserved
+            .iter()
+            .any(|reserved| reserved.trim().to_lowercase() == label.to_lowercase())
+        {
+            problems.push("is a reserved name".to_owned());
+        }
+
+        if let Some(max_length) = self.max_length
+            && label.chars().count() > max_length
+        {
+            problems.push(format!("is longer than {max_length} characters"));
+        }
+
+        if let Some(Pattern(regex)) = &self.pattern
+            && !regex.is_match(label)
+        {
+            problems.push(format!("does not match the pattern `{}`", regex.as_str()));
+        }
+
+        problems
+    }
+}
+
+// This function checks that the tags follow the naming conventions. It returns a vector of
+// violations.
+#[must_use]
+pub fn check(
+    tags_map: &BTreeMap<String, Vec<Directive>>,
+    conventions: &Conventions,
+) -> Vec<Violation> {
+    let mut errors = Vec::<Violation>::new();
+
+    for (label, directives) in tags_map {
+        let problems = conventions.problems(label);
+        if problems.is_empty() {
+            continue;
+        }
+
+        for directive in directives {
+            errors.push(Violation {
+                kind: Kind::NonconformingTagName,
+                severity: Severity::Error,
+                message: format!("{directive} {}.", problems.join(" and ")),
+                directives: vec![directive.clone()],
+                suggestions: Vec::new(),
+            });
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::{Directive, Type},
+        namespaces::Pattern,
+        naming::{Conventions, check},
+        violation::Kind,
+    };
+    use regex::Regex;
+    use std::collections::BTreeMap;
+
+    fn tags(labels: &[&str]) -> BTreeMap<String, Vec<Directive>> {
+        labels
+            .iter()
+            .map(|label| {
+                (
+                    (*label).to_owned(),
+                    vec![Directive::new(Type::Tag, label, "file.rs", 1)],
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn check_no_conventions() {
+        assert!(check(&tags(&["Foo Bar", "TODO"]), &Conventions::default()).is_empty());
+    }
+
+    #[test]
+    fn check_pattern() {
+        let conventions = Conventions {
+            pattern: Some(Pattern(Regex::new("^[a-z0-9_-]+$").unwrap())),
+            ..Conventions::default()
+        };
+
+        let errors = check(&tags(&["foo_bar", "Foo Bar"]), &conventions);
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, Kind::NonconformingTagName);
+        assert_eq!(errors[0].directives[0].label, "Foo Bar");
+    }
+
+    #[test]
+    fn check_max_length() {
+        let conventions = Conventions {
+            max_length: Some(3),
+            ..Conventions::default()
+        };
+
+        let errors = check(&tags(&["foo", "fooo"]), &conventions);
+
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].message.contains("longer than 3 characters"));
+    }
+
+    #[test]
+    fn check_reserved() {
+        let conventions = Conventions {
+            max_length: Some(3),
+            reserved: vec!["todo".to_owned()],
+            ..Conventions::default()
+        };
+
+        let errors = check(&tags(&["TODO", "foo"]), &conventions);
+
+        assert_eq!(errors.len(), 1);
+        assert!(
+            errors[0]
+                .message
+                .ends_with("is a reserved name and is longer than 3 characters."),
+        );
+    }
+}
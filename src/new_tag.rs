@@ -0,0 +1,186 @@
+use crate::{comments, count::count, directive, edits::Edit};
+use std::path::Path;
+
+// This is the alphabet of Crockford's base 32, which ULIDs are written in. It's lowercase so the
+// names fit the usual naming conventions for labels.
+const CROCKFORD: &[u8; 32] = b"0123456789abcdefghjkmnpqrstvwxyz";
+
+// This is the number of characters in the identifiers of automatically named tags. With 40 random
+// bits, collisions are unlikely even with many thousands of tags, and they're avoided anyway.
+const IDENTIFIER_LENGTH: u32 = 8;
+
+// This function writes the low bits of a number in base 32, most significant digit first.
+fn encode(value: u128, digits: u32) -> String {
+    (0..digits)
+        .rev()
+        .map(|digit| {
+            // The `unwrap` is safe since the index is less than 32.
+            char::from(CROCKFORD[usize::try_from((value >> (digit * 5)) & 31).unwrap()])
+        })
+        .collect()
+}
+
+// This function returns some random bits from the operating system. It isn't available in the
+// browser, where there's no operating system to ask.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn random_bits() -> Result<u128, String> {
+    let mut bytes = [0; 16];
+    getrandom::fill(&mut bytes)
+        .map_err(|error| format!("Unable to generate random bits: {error}"))?;
+    Ok(u128::from_le_bytes(bytes))
+}
+
+// This function formats a ULID from a timestamp in milliseconds since the Unix epoch and some
+// random bits. Only the low 48 bits of the timestamp and the low 80 bits of the randomness are
+// used, as the specification requires.
+#[must_use]
+pub fn ulid(milliseconds: u64, random: u128) -> String {
+    let value = (u128::from(milliseconds & 0xffff_ffff_ffff) << 80_u32)
+        | (random & ((1_u128 << 80_u32) - 1));
+    encode(value, 26)
+}
+
+// This function formats a short opaque identifier for an automatically named tag from some random
+// bits.
+#[must_use]
+pub fn identifier(random: u128) -> String {
+    encode(random, IDENTIFIER_LENGTH)
+}
+
+// This function builds a name for a new tag from the given one, after the prefix (if any). If the
+// name is already taken, a number is appended to make it unique, starting from 2.
+pub fn name(
+    name: &str,
+    prefix: Option<&str>,
+    taken: impl Fn(&str) -> bool,
+) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() || name.contains(']') {
+        return Err(format!("`{name}` is not a valid tag name."));
+    }
+
+    let base = prefix.map_or_else(|| name.to_owned(), |prefix| format!("{prefix}-{name}"));
+    if !taken(&base) {
+        return Ok(base);
+    }
+    let mut suffix = 2_usize;
+    loop {
+        let candidate = format!("{base}-{suffix}");
+        if !taken(&candidate) {
+            return Ok(candidate);
+        }
+        suffix += 1;
+    }
+}
+
+// This function returns the edit which inserts a line with the given text before a line of a file
+// (numbered from 1), or at the end of the file if the line number is one past the last line. The
+// text is put in a comment if the language of the file is recognized, and the new line is indented
+// like the one it precedes.
+pub fn insert(path: &Path, contents: &str, line_number: usize, text: &str) -> Result<Edit, String> {
+    let lines = directive::lines(contents).collect::<Vec<_>>();
+    if line_number == 0 || line_number > lines.len() + 1 {
+        return Err(format!(
+            "Line {line_number} is out of range for `{}`, which has {}.",
+            path.to_string_lossy(),
+            count(lines.len(), "line"),
+        ));
+    }
+
+    // Match the line endings of the file.
+    let ending = lines
+        .iter()
+        .map(|(_, ending)| *ending)
+        .find(|ending| !ending.is_empty())
+        .unwrap_or("\n");
+    let text =
+        comments::syntax(path).map_or_else(|| text.to_owned(), |syntax| syntax.comment(text));
+
+    let offset = lines[..line_number - 1]
+        .iter()
+        .map(|(line, ending)| line.len() + ending.len())
+        .sum::<usize>();
+    let replacement = match lines.get(line_number - 1) {
+        Some((line, _)) => {
+            let indentation = &line[..line.len() - line.trim_start().len()];
+            format!("{indentation}{text}{ending}")
+        }
+        None if lines.last().is_some_and(|(_, ending)| ending.is_empty()) => {
+            format!("{ending}{text}{ending}")
+        }
+        None => format!("{text}{ending}"),
+    };
+
+    Ok(Edit {
+        range: offset..offset,
+        replacement,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        edits::splice,
+        new_tag::{identifier, insert, name, random_bits, ulid},
+    };
+    use std::path::Path;
+
+    #[test]
+    fn ulid_format() {
+        assert_eq!(ulid(0, 0), "00000000000000000000000000");
+        assert_eq!(ulid(1_469_918_176_385, 0), "01aryz6s410000000000000000");
+        assert_eq!(ulid(u64::MAX, u128::MAX), "7zzzzzzzzzzzzzzzzzzzzzzzzz");
+    }
+
+    #[test]
+    fn identifier_format() {
+        assert_eq!(identifier(0), "00000000");
+        assert_eq!(identifier(u128::MAX), "zzzzzzzz");
+        assert_eq!(identifier((1 << 40) | 0x0123_4567), "000j6hb7");
+    }
+
+    #[test]
+    fn random_bits_differ() {
+        assert_ne!(random_bits().unwrap(), random_bits().unwrap());
+    }
+
+    #[test]
+    fn name_unique() {
+        let taken = ["foo", "foo-2", "2024-06-30-bar"];
+        let is_taken = |name: &str| taken.contains(&name);
+
+        assert_eq!(name(" baz ", None, is_taken).unwrap(), "baz");
+        assert_eq!(name("foo", None, is_taken).unwrap(), "foo-3");
+        assert_eq!(
+            name("bar", Some("2024-06-30"), is_taken).unwrap(),
+            "2024-06-30-bar-2",
+        );
+        assert!(name("foo]", None, is_taken).is_err());
+        assert!(name("", None, is_taken).is_err());
+    }
+
+    #[test]
+    fn insert_comment() {
+        let rust = "fn main() {\r\n    run();\r\n}";
+        let edit = insert(Path::new("main.rs"), rust, 2, "note").unwrap();
+        assert_eq!(
+            splice(rust, &[edit]),
+            "fn main() {\r\n    // note\r\n    run();\r\n}",
+        );
+
+        let edit = insert(Path::new("main.rs"), rust, 4, "note").unwrap();
+        assert_eq!(
+            splice(rust, &[edit]),
+            "fn main() {\r\n    run();\r\n}\r\n// note\r\n",
+        );
+
+        let edit = insert(Path::new("style.css"), "", 1, "note").unwrap();
+        assert_eq!(splice("", &[edit]), "/* note */\n");
+
+        let edit = insert(Path::new("README.md"), "# Title\n", 1, "note").unwrap();
+        assert_eq!(splice("# Title\n", &[edit]), "note\n# Title\n");
+
+        assert!(insert(Path::new("main.rs"), rust, 5, "note").is_err());
+        assert!(insert(Path::new("main.rs"), rust, 0, "note").is_err());
+    }
+}
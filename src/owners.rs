@@ -0,0 +1,252 @@
+use crate::{
+    directive::{self, Directive},
+    walk,
+};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+// These are the places a CODEOWNERS file is looked for, relative to the working directory, in the
+// order GitHub looks for them.
+pub const LOCATIONS: [&str; 3] = [".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+// This struct maps files to their owners according to a CODEOWNERS file.
+#[derive(Debug)]
+pub struct Codeowners {
+    // A matcher for each pattern, along with its owners. If several patterns match a file, the
+    // last one wins, so later patterns can override earlier ones.
+    rules: Vec<(Gitignore, Vec<String>)>,
+}
+
+impl Codeowners {
+    // This function parses the contents of a CODEOWNERS file. Patterns use `.gitignore` syntax, as
+    // on GitHub and GitLab. Section headings (as on GitLab) are skipped.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mut rules = Vec::new();
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty()
+                || line.starts_with('#')
+                || line.starts_with('[')
+                || line.starts_with("^[")
+            {
+                continue;
+            }
+
+            // The `unwrap` is safe because the line isn't empty.
+            let mut fields = line.split_whitespace();
+            let pattern = fields.next().unwrap();
+            let owners = fields
+                .take_while(|field| !field.starts_with('#'))
+                .map(ToOwned::to_owned)
+                .collect();
+
+            let mut builder = GitignoreBuilder::new(".");
+            builder
+                .add_line(None, pattern)
+                .map_err(|error| format!("Invalid pattern `{pattern}`: {error}"))?;
+            let gitignore = builder
+                .build()
+                .map_err(|error| format!("Invalid pattern `{pattern}`: {error}"))?;
+            rules.push((gitignore, owners));
+        }
+
+        Ok(Self { rules })
+    }
+
+    // This function loads the CODEOWNERS file at the given path, or from the first of the usual
+    // locations which exists. It returns `None` if there's no such file in the usual locations.
+    pub fn load(path: Option<&Path>) -> Result<Option<(PathBuf, Self)>, String> {
+        let candidates = path.map_or_else(
+            || LOCATIONS.iter().map(PathBuf::from).collect(),
+            |path| vec![path.to_owned()],
+        );
+
+        for candidate in candidates {
+            match fs::read_to_string(&candidate) {
+                Ok(contents) => {
+                    let codeowners = Self::parse(&contents).map_err(|error| {
+                        format!("Error in {}: {error}", candidate.to_string_lossy())
+                    })?;
+                    return Ok(Some((candidate, codeowners)));
+                }
+                Err(error) if error.kind() == ErrorKind::NotFound && path.is_none() => {}
+                Err(error) => {
+                    return Err(format!(
+                        "Unable to read {}: {error}",
+                        candidate.to_string_lossy(),
+                    ));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    // This function returns the owners of a file, which is relative to the working directory. A
+    // pattern matching a directory applies to all the files in it.
+    #[must_use]
+    pub fn owners(&self, path: &Path) -> &[String] {
+        let path = walk::relative(path);
+        self.rules
+            .iter()
+            .rev()
+            .find(|(gitignore, _)| {
+                path.ancestors()
+                    .filter(|ancestor| !ancestor.as_os_str().is_empty())
+                    .any(|ancestor| gitignore.matched(ancestor, ancestor != path).is_ignore())
+            })
+            .map_or(&[], |(_, owners)| owners)
+    }
+}
+
+// This struct describes who owns a tag and who references it.
+#[derive(Clone, Debug, Serialize)]
+pub struct Ownership {
+    pub tag: Directive,
+
+    // The owners of the file containing the tag
+    pub owners: Vec<String>,
+
+    // The owners of the files containing references to the tag
+    pub referenced_by: Vec<String>,
+}
+
+impl Ownership {
+    // This function determines whether the tag is referenced from files which are owned by
+    // someone who doesn't own the tag.
+    #[must_use]
+    pub fn is_shared(&self) -> bool {
+        self.referenced_by
+            .iter()
+            .any(|owner| !self.owners.contains(owner))
+    }
+}
+
+// This function determines the ownership of each tag. The tags are keyed as in the index, and
+// duplicates of a tag are represented by the first one.
+#[must_use]
+pub fn find(
+    tags_map: &BTreeMap<String, Vec<Directive>>,
+    refs: &[Directive],
+    codeowners: &Codeowners,
+    case_insensitive_labels: bool,
+) -> Vec<Ownership> {
+    let mut referenced_by = BTreeMap::<String, BTreeSet<&String>>::new();
+    for r#ref in refs {
+        referenced_by
+            .entry(directive::key(&r#ref.label, case_insensitive_labels).into_owned())
+            .or_default()
+            .extend(codeowners.owners(&r#ref.path));
+    }
+
+    tags_map
+        .iter()
+        .filter_map(|(label, tags)| {
+            let tag = tags.first()?;
+            Some(Ownership {
+                tag: tag.clone(),
+                owners: codeowners.owners(&tag.path).to_vec(),
+                referenced_by: referenced_by
+                    .get(label)
+                    .into_iter()
+                    .flatten()
+                    .map(|owner| (*owner).clone())
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::{Directive, Type},
+        owners::{Codeowners, find},
+    };
+    use std::{collections::BTreeMap, path::Path};
+
+    const CODEOWNERS: &str = "
+# The default owners
+*           @org/everyone
+
+/src/       @org/core   # The core team
+payments/   @org/payments @alice
+*.md        @org/docs
+/src/generated/
+";
+
+    #[test]
+    fn owners_last_match_wins() {
+        let codeowners = Codeowners::parse(CODEOWNERS).unwrap();
+
+        assert_eq!(
+            codeowners.owners(Path::new("./build.rs")),
+            ["@org/everyone"]
+        );
+        assert_eq!(codeowners.owners(Path::new("./src/main.rs")), ["@org/core"]);
+        assert_eq!(
+            codeowners.owners(Path::new("./src/payments/charge.rs")),
+            ["@org/payments", "@alice"],
+        );
+        assert_eq!(
+            codeowners.owners(Path::new("./src/README.md")),
+            ["@org/docs"]
+        );
+        assert!(
+            codeowners
+                .owners(Path::new("./src/generated/mod.rs"))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn owners_anchored() {
+        let codeowners = Codeowners::parse("/src/ @org/core\n").unwrap();
+
+        assert!(codeowners.owners(Path::new("lib/src/main.rs")).is_empty());
+    }
+
+    #[test]
+    fn parse_invalid_pattern() {
+        assert!(Codeowners::parse("src/[z-a] @org/core\n").is_err());
+    }
+
+    #[test]
+    fn find_ownership() {
+        let codeowners = Codeowners::parse(CODEOWNERS).unwrap();
+        let tags_map = [
+            ("fee", "./src/payments/fee.rs"),
+            ("retry", "./src/retry.rs"),
+        ]
+        .into_iter()
+        .map(|(label, path)| {
+            (
+                label.to_owned(),
+                vec![Directive::new(Type::Tag, label, path, 1)],
+            )
+        })
+        .collect::<BTreeMap<_, _>>();
+        let refs = [
+            Directive::new(Type::Ref, "fee", "./src/payments/refund.rs", 1),
+            Directive::new(Type::Ref, "fee", "./src/main.rs", 1),
+            Directive::new(Type::Ref, "retry", "./src/main.rs", 1),
+        ];
+
+        let ownership = find(&tags_map, &refs, &codeowners, false);
+
+        assert_eq!(ownership[0].owners, ["@org/payments", "@alice"]);
+        assert_eq!(
+            ownership[0].referenced_by,
+            ["@alice", "@org/core", "@org/payments"],
+        );
+        assert!(ownership[0].is_shared());
+        assert_eq!(ownership[1].owners, ["@org/core"]);
+        assert!(!ownership[1].is_shared());
+    }
+}
@@ -0,0 +1,153 @@
+use crate::{
+    directive::{self, Directive},
+    violation::{Kind, Severity, Violation},
+    walk,
+};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+// This struct pairs two files or directories so that each tag in one of them must be referenced
+// from the other, e.g., the tags in a schema must be referenced from the migrations and vice
+// versa.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Rule {
+    pub first: PathBuf,
+    pub second: PathBuf,
+}
+
+// This function determines whether a label is in a file or directory.
+#[must_use]
+pub fn within(directive: &Directive, path: &Path) -> bool {
+    walk::relative(&directive.path).starts_with(path)
+}
+
+// This function checks that each tag in one side of a pairing is referenced from the other side.
+// The tags are keyed as in the index. It returns a vector of violations.
+#[must_use]
+pub fn check(
+    tags_map: &BTreeMap<String, Vec<Directive>>,
+    refs: &[Directive],
+    rules: &[Rule],
+    case_insensitive_labels: bool,
+) -> Vec<Violation> {
+    let mut errors = Vec::<Violation>::new();
+
+    for rule in rules {
+        for (from, to) in [(&rule.first, &rule.second), (&rule.second, &rule.first)] {
+            let referenced = refs
+                .iter()
+                .filter(|r#ref| within(r#ref, to))
+                .map(|r#ref| directive::key(&r#ref.label, case_insensitive_labels))
+                .collect::<HashSet<_>>();
+
+            for (label, tags) in tags_map {
+                if referenced.contains(label.as_str()) {
+                    continue;
+                }
+                for tag in tags.iter().filter(|tag| within(tag, from)) {
+                    errors.push(Violation {
+                        kind: Kind::UnpairedTag,
+                        severity: Severity::Error,
+                        message: format!(
+                            "{tag} is not referenced from `{}`, which is paired with `{}`.",
+                            to.to_string_lossy(),
+                            from.to_string_lossy(),
+                        ),
+                        directives: vec![tag.clone()],
+                        suggestions: Vec::new(),
+                    });
+                }
+            }
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::{Directive, Type},
+        pairings::{Rule, check},
+        violation::Kind,
+    };
+    use std::{collections::BTreeMap, path::PathBuf};
+
+    fn rule() -> Rule {
+        Rule {
+            first: PathBuf::from("schema.sql"),
+            second: PathBuf::from("migrations"),
+        }
+    }
+
+    #[test]
+    fn check_both_sides() {
+        let tags_map = BTreeMap::from([
+            (
+                "users".to_owned(),
+                vec![Directive::new(Type::Tag, "users", "./schema.sql", 1)],
+            ),
+            (
+                "orders".to_owned(),
+                vec![Directive::new(Type::Tag, "orders", "./schema.sql", 1)],
+            ),
+            (
+                "backfill".to_owned(),
+                vec![Directive::new(
+                    Type::Tag,
+                    "backfill",
+                    "./migrations/2.sql",
+                    1,
+                )],
+            ),
+            (
+                "other".to_owned(),
+                vec![Directive::new(Type::Tag, "other", "./src/main.rs", 1)],
+            ),
+        ]);
+        let refs = [
+            Directive::new(Type::Ref, "users", "./migrations/1.sql", 1),
+            Directive::new(Type::Ref, "orders", "./src/main.rs", 1),
+        ];
+
+        let violations = check(&tags_map, &refs, &[rule()], false);
+
+        assert_eq!(
+            violations
+                .iter()
+                .map(|violation| violation.directives[0].label.as_str())
+                .collect::<Vec<_>>(),
+            vec!["orders", "backfill"],
+        );
+        assert!(
+            violations
+                .iter()
+                .all(|violation| violation.kind == Kind::UnpairedTag)
+        );
+        assert!(
+            violations[0]
+                .message
+                .contains("is not referenced from `migrations`")
+        );
+        assert!(
+            violations[1]
+                .message
+                .contains("is not referenced from `schema.sql`")
+        );
+    }
+
+    #[test]
+    fn check_case_insensitive() {
+        let tags_map = BTreeMap::from([(
+            "users".to_owned(),
+            vec![Directive::new(Type::Tag, "Users", "schema.sql", 1)],
+        )]);
+        let refs = [Directive::new(Type::Ref, "USERS", "migrations/1.sql", 1)];
+
+        assert!(check(&tags_map, &refs, &[rule()], true).is_empty());
+    }
+}
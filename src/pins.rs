@@ -0,0 +1,199 @@
+use crate::{
+    directive::{self, Directive},
+    git::Tree,
+    violation::{Kind, Severity, Violation},
+};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    fs,
+    path::Path,
+};
+
+// This is the separator between the label of a tag reference and the hash it's pinned to, as in
+// `foo@sha256:1a2b3c4d`.
+pub const SEPARATOR: &str = "@sha256:";
+
+// This is the number of hexadecimal digits written when pinning a reference.
+pub const LENGTH: usize = 8;
+
+// Pins shorter than this don't say much about the content, so they're treated as stale.
+const MIN_LENGTH: usize = 4;
+
+// This function splits the label of a tag reference into the label of the tag and the hash it's
+// pinned to, if any. The hash may be empty, which means the reference should be pinned.
+#[must_use]
+pub fn split(label: &str) -> (&str, Option<&str>) {
+    match label.rsplit_once(SEPARATOR) {
+        Some((tag, hash)) if !tag.trim().is_empty() => (tag.trim_end(), Some(hash.trim())),
+        _ => (label, None),
+    }
+}
+
+// This function computes the hash of the content which a tag on the given line (counting from 1)
+// is attached to: that line and the lines after it, up to the first blank line. Trailing
+// whitespace and line endings don't count.
+#[must_use]
+pub fn digest(contents: &str, line_number: usize) -> String {
+    let mut hasher = Sha256::new();
+    for line in directive::lines(contents)
+        .skip(line_number.saturating_sub(1))
+        .map(|(line, _)| line.trim_end())
+        .take_while(|line| !line.is_empty())
+    {
+        hasher.update(line.as_bytes());
+        hasher.update(b"\n");
+    }
+    hasher
+        .finalize()
+        .iter()
+        .fold(String::new(), |mut hex, byte| {
+            // Writing to a string can't fail, so the `unwrap` is safe.
+            write!(hex, "{byte:02x}").unwrap();
+            hex
+        })
+}
+
+// This struct represents a pinned reference whose pin doesn't match the current content of its
+// tag.
+#[derive(Clone, Debug)]
+pub struct Stale<'a> {
+    // The reference, with the hash in its label
+    pub r#ref: &'a Directive,
+
+    // The tag it points to
+    pub tag: &'a Directive,
+
+    // The hash of the current content of the tag, shortened as in new pins
+    pub hash: String,
+}
+
+impl Stale<'_> {
+    // This is the label the reference should have once it's pinned to the current content.
+    #[must_use]
+    pub fn label(&self) -> String {
+        format!("{}{SEPARATOR}{}", split(&self.r#ref.label).0, self.hash)
+    }
+}
+
+// This function finds the pinned references (whose labels still contain their hashes) which don't
+// match the current content of their tags. References which use aliases are resolved first.
+// References to tags which don't exist are left to the other checks. Tags in files which can't be
+// read are reported as errors. If a Git tree is given, the files are read from it rather than the
+// filesystem.
+pub fn stale<'a>(
+    pins: &'a [Directive],
+    tags: &'a BTreeMap<String, Vec<Directive>>,
+    aliases: &BTreeMap<String, String>,
+    case_insensitive_labels: bool,
+    tree: Option<&Tree>,
+) -> Result<Vec<Stale<'a>>, String> {
+    let mut contents = HashMap::<&Path, String>::new();
+    let mut stale = Vec::new();
+
+    for r#ref in pins {
+        let (label, hash) = split(&r#ref.label);
+        let key = directive::key(label, case_insensitive_labels);
+        let key = aliases
+            .get(&*key)
+            .map_or(key, |tag| directive::key(tag, case_insensitive_labels));
+        let Some(tag) = tags.get(&*key).and_then(|tags| tags.first()) else {
+            continue;
+        };
+
+        if !contents.contains_key(&*tag.path) {
+            let text = match tree {
+                Some(tree) => tree
+                    .read(&tag.path)
+                    .map(|contents| String::from_utf8_lossy(&contents).into_owned()),
+                None => fs::read_to_string(&tag.path).map_err(|error| error.to_string()),
+            }
+            .map_err(|error| {
+                format!("Error when reading {}: {error}", tag.path.to_string_lossy())
+            })?;
+            contents.insert(&*tag.path, text);
+        }
+        let current = digest(&contents[&*tag.path], tag.line_number);
+
+        let hash = hash.unwrap_or_default().to_ascii_lowercase();
+        if hash.len() < MIN_LENGTH || !current.starts_with(&hash) {
+            stale.push(Stale {
+                r#ref,
+                tag,
+                hash: current[..LENGTH].to_owned(),
+            });
+        }
+    }
+
+    Ok(stale)
+}
+
+// This function checks that pinned references match the current content of their tags, so a
+// change to a tag forces the references to it to be reviewed. It returns a vector of violations.
+#[must_use]
+pub fn check(
+    pins: &[Directive],
+    tags: &BTreeMap<String, Vec<Directive>>,
+    aliases: &BTreeMap<String, String>,
+    case_insensitive_labels: bool,
+    tree: Option<&Tree>,
+) -> Vec<Violation> {
+    let violation = |message, directives| Violation {
+        kind: Kind::StalePin,
+        severity: Severity::Error,
+        message,
+        directives,
+        suggestions: Vec::new(),
+    };
+
+    match stale(pins, tags, aliases, case_insensitive_labels, tree) {
+        Ok(stale) => stale
+            .into_iter()
+            .map(|stale| {
+                let message = if split(&stale.r#ref.label).1.is_some_and(str::is_empty) {
+                    format!(
+                        "{} isn't pinned yet. Run `tagref repin` to pin it.",
+                        stale.r#ref
+                    )
+                } else {
+                    format!(
+                        "{} is pinned to the content of {}, which has changed. Review the \
+                         reference, and then run `tagref repin`.",
+                        stale.r#ref, stale.tag,
+                    )
+                };
+                violation(message, vec![stale.r#ref.clone(), stale.tag.clone()])
+            })
+            .collect(),
+        Err(error) => vec![violation(error, Vec::new())],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pins::{digest, split};
+
+    #[test]
+    fn split_pinned() {
+        assert_eq!(split("foo@sha256:1a2b3c4d"), ("foo", Some("1a2b3c4d")));
+        assert_eq!(split("foo @sha256:"), ("foo", Some("")));
+    }
+
+    #[test]
+    fn split_unpinned() {
+        assert_eq!(split("foo"), ("foo", None));
+        assert_eq!(split("@sha256:1a2b3c4d"), ("@sha256:1a2b3c4d", None));
+    }
+
+    #[test]
+    fn digest_block() {
+        let contents = "fn a() {}\n\n// [tag]\nfn b() {}\n\nfn c() {}\n";
+        let edited = "fn a() { 1 }\n\n// [tag]  \r\nfn b() {}\n\nfn c() { 2 }\n";
+        let changed = "fn a() {}\n\n// [tag]\nfn b() { 3 }\n\nfn c() {}\n";
+
+        assert_eq!(digest(contents, 3), digest(edited, 3));
+        assert_ne!(digest(contents, 3), digest(changed, 3));
+        assert_eq!(digest(contents, 3).len(), 64);
+    }
+}
@@ -0,0 +1,60 @@
+// This struct is an update to a ref, as given to a pre-receive hook on standard input.
+#[derive(Debug, Eq, PartialEq)]
+pub struct Update {
+    // The name of the ref, e.g., `refs/heads/main`
+    pub name: String,
+
+    // The object ID which the ref is being updated to
+    pub revision: String,
+}
+
+// This function parses the updates given to a pre-receive hook, which are lines of the form
+// `<old-revision> <new-revision> <ref-name>`. Refs which are being deleted have a new revision of
+// all zeros, and they're left out since there's nothing to check.
+pub fn parse(input: &str) -> Result<Vec<Update>, String> {
+    let mut updates = Vec::new();
+    for line in input.lines().filter(|line| !line.trim().is_empty()) {
+        let [_, revision, name] = line.split_whitespace().collect::<Vec<_>>()[..] else {
+            return Err(format!("Unexpected ref update `{line}`."));
+        };
+        if revision.bytes().any(|byte| byte != b'0') {
+            updates.push(Update {
+                name: name.to_owned(),
+                revision: revision.to_owned(),
+            });
+        }
+    }
+    Ok(updates)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pre_receive::{Update, parse};
+
+    #[test]
+    fn parse_updates() {
+        let input = "0000000 1a2b3c4 refs/heads/topic\n\
+                     1a2b3c4 0000000 refs/heads/old\n\
+                     \n\
+                     5d6e7f8 9a0b1c2 refs/tags/v1.0\n";
+
+        assert_eq!(
+            parse(input).unwrap(),
+            vec![
+                Update {
+                    name: "refs/heads/topic".to_owned(),
+                    revision: "1a2b3c4".to_owned(),
+                },
+                Update {
+                    name: "refs/tags/v1.0".to_owned(),
+                    revision: "9a0b1c2".to_owned(),
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(parse("1a2b3c4 refs/heads/main\n").is_err());
+    }
+}
@@ -0,0 +1,271 @@
+use colored::{ColoredString, Colorize};
+use encoding_rs::Encoding;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+use tagref::{
+    attributes,
+    count::count,
+    directive::{self, Directive},
+    encoding::{self, Decoded},
+    violation::{Kind, Severity, Violation},
+};
+
+// This struct reads the lines of the files involved in violations, so they can be shown under the
+// messages. Each file is read at most once.
+pub struct Sources {
+    fallback_encoding: Option<&'static Encoding>,
+
+    // The lines of each file, or `None` if the file couldn't be read or decoded
+    files: HashMap<PathBuf, Option<Vec<String>>>,
+
+    // The scanned paths of the files whose paths are reported differently
+    scanned_paths: HashMap<PathBuf, PathBuf>,
+}
+
+impl Sources {
+    #[must_use]
+    pub fn new(fallback_encoding: Option<&'static Encoding>) -> Self {
+        Self {
+            fallback_encoding,
+            files: HashMap::new(),
+            scanned_paths: HashMap::new(),
+        }
+    }
+
+    // This function records the path a file was scanned at, when it's reported at another path
+    // (see `walk::report`), so its lines are read from the scanned path.
+    pub fn report_as(&mut self, scanned_path: &Path, reported_path: &Path) {
+        if scanned_path != reported_path {
+            self.scanned_paths
+                .insert(reported_path.to_owned(), scanned_path.to_owned());
+        }
+    }
+
+    // This function provides the contents of a file, e.g., unsaved contents from standard input,
+    // so they're shown instead of the contents on disk.
+    pub fn insert(&mut self, path: &Path, contents: &[u8]) {
+        let lines = decode(contents, self.fallback_encoding);
+        self.files.insert(path.to_owned(), lines);
+    }
+
+    // This function returns a line of a file. Line numbers start at 1.
+    fn line(&mut self, path: &Path, line_number: usize) -> Option<&str> {
+        let fallback_encoding = self.fallback_encoding;
+        let path = self.scanned_paths.get(path).map_or(path, PathBuf::as_path);
+        self.files
+            .entry(path.to_owned())
+            .or_insert_with(|| {
+                fs::read(path)
+                    .ok()
+                    .and_then(|bytes| decode(&bytes, fallback_encoding))
+            })
+            .as_ref()?
+            .get(line_number.checked_sub(1)?)
+            .map(String::as_str)
+    }
+}
+
+// This function splits the contents of a file into lines, unless it's binary.
+fn decode(contents: &[u8], fallback_encoding: Option<&'static Encoding>) -> Option<Vec<String>> {
+    match encoding::decode(contents, fallback_encoding) {
+        Decoded::Text(text) | Decoded::Lossy(text) => Some(
+            directive::lines(&text)
+                .map(|(line, _)| line.to_owned())
+                .collect(),
+        ),
+        Decoded::Binary => None,
+    }
+}
+
+// This function highlights a directive where it's mentioned in a message.
+fn highlight(directive: &Directive) -> String {
+    format!(
+        "{} @ {}",
+        format!(
+            "[{}:{}{}]",
+            directive.r#type.sigil(),
+            directive.label,
+            attributes::format(&directive.attributes),
+        )
+        .bold(),
+        location(directive),
+    )
+}
+
+// This function formats the location of a directive, including the cell for directives in
+// notebooks.
+fn location(directive: &Directive) -> ColoredString {
+    let mut location = format!(
+        "{}:{}:{}",
+        directive.path.to_string_lossy(),
+        directive.line_number,
+        directive.column,
+    );
+    if let Some(cell) = directive.cell {
+        let _ = write!(
+            location,
+            " (cell {}, line {})",
+            cell.number, cell.line_number
+        );
+    }
+    location.cyan()
+}
+
+// This function shows the location of a directive followed by the line containing it, with a line
+// of carets under the label, like compilers show the code involved in errors. Only the location is
+// shown if the line isn't available.
+fn snippet(output: &mut String, directive: &Directive, sources: &mut Sources) {
+    let _ = writeln!(output, "    {} {}", "-->".blue(), location(directive));
+    let Some(line) = sources.line(&directive.path, directive.line_number) else {
+        return;
+    };
+
+    // The caret line keeps the tabs before the label, so it lines up with the line above it. Labels
+    // which span several lines are underlined to the end of the first line.
+    let start = line
+        .char_indices()
+        .nth(directive.column.saturating_sub(1))
+        .map_or(line.len(), |(index, _)| index);
+    let end = directive.span.end.clamp(start, line.len());
+    let padding = line[..start]
+        .chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect::<String>();
+    let carets = "^".repeat(
+        line.get(start..end)
+            .map_or(0, |label| label.chars().count())
+            .max(1),
+    );
+
+    let number = directive.line_number.to_string();
+    let gutter = " ".repeat(number.len());
+    let bar = "|".blue();
+    let _ = writeln!(output, "    {gutter} {bar}");
+    let _ = writeln!(output, "    {} {bar} {}", number.blue(), line.trim_end());
+    let _ = writeln!(
+        output,
+        "    {gutter} {bar} {padding}{}",
+        carets.red().bold()
+    );
+}
+
+// This function formats violations for people to read. The violations are grouped by kind, each
+// with a heading, and the line of each directive involved is shown with the label underlined. In
+// quiet mode, only errors are included.
+pub fn report(violations: &[Violation], sources: &mut Sources, quiet: bool) -> String {
+    let mut groups = BTreeMap::<Kind, Vec<&Violation>>::new();
+    for violation in violations {
+        if !quiet || violation.severity == Severity::Error {
+            groups.entry(violation.kind).or_default().push(violation);
+        }
+    }
+
+    let mut output = String::new();
+    for (kind, violations) in groups {
+        let _ = writeln!(
+            output,
+            "{} {}\n",
+            format!(
+                "{} ({})",
+                kind.description(),
+                count(violations.len(), "violation")
+            )
+            .bold(),
+            format!("[{}]", kind.id()).dimmed(),
+        );
+
+        for violation in violations {
+            let severity = match violation.severity {
+                Severity::Error => "error:".red().bold(),
+                Severity::Warning => "warning:".yellow().bold(),
+                Severity::Info => "note:".cyan().bold(),
+            };
+            let message = violation.directives.iter().fold(
+                violation.message.trim_end().to_owned(),
+                |message, directive| message.replace(&directive.to_string(), &highlight(directive)),
+            );
+            let _ = writeln!(output, "  {severity} {message}");
+            for directive in &violation.directives {
+                snippet(&mut output, directive, sources);
+            }
+            output.push('\n');
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pretty::{Sources, report};
+    use std::{collections::BTreeMap, path::Path};
+    use tagref::{
+        directive::{Directive, Type},
+        violation::{Kind, Severity, Violation},
+    };
+
+    fn violation(kind: Kind, severity: Severity, line: &str) -> Violation {
+        let column = line.find('[').unwrap() + 1;
+        let directive = Directive {
+            r#type: Type::Ref,
+            label: "foo".to_owned(),
+            path: Path::new("./a.rs").into(),
+            line_number: 2,
+            column,
+            cell: None,
+            span: column - 1..line.find(']').unwrap() + 1,
+            description: None,
+            attributes: BTreeMap::new(),
+        };
+        Violation {
+            kind,
+            severity,
+            message: format!("No tag found for {directive}."),
+            directives: vec![directive],
+            suggestions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn report_grouped_with_snippets() {
+        colored::control::set_override(false);
+        let line = "\t// See [?ref:foo].".replace('?', "");
+        let mut sources = Sources::new(None);
+        sources.insert(
+            Path::new("./a.rs"),
+            format!("fn f() {{}}\n{line}\n").as_bytes(),
+        );
+        let violations = [
+            violation(Kind::DanglingRef, Severity::Error, &line),
+            violation(Kind::UnusedTag, Severity::Warning, &line),
+            violation(Kind::DanglingRef, Severity::Error, &line),
+        ];
+
+        let output = report(&violations, &mut sources, false);
+
+        assert!(output.starts_with(
+            "Tag references must point to tags. (2 violations) [dangling_ref]\n\n  error: No tag \
+             found for [?ref:foo] @ ./a.rs:2:9.\n    --> ./a.rs:2:9\n      |\n    2 | \t// See \
+             [?ref:foo].\n      | \t       ^^^^^^^^^\n\n"
+                .replace('?', "")
+                .as_str(),
+        ));
+        assert!(output.contains("Tags must be referenced. (1 violation) [unused_tag]"));
+        assert!(output.contains("  warning: No tag found"));
+        assert!(!report(&violations, &mut sources, true).contains("warning"));
+    }
+
+    #[test]
+    fn report_missing_file() {
+        colored::control::set_override(false);
+        let violations = [violation(Kind::DanglingRef, Severity::Error, "[x]")];
+
+        assert!(
+            report(&violations, &mut Sources::new(None), false).ends_with("    --> ./a.rs:2:1\n\n"),
+        );
+    }
+}
@@ -0,0 +1,178 @@
+use crate::{
+    count,
+    directive::Directive,
+    index::{Index, Scanner},
+};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use ureq::Agent;
+
+// This is how long to wait for a tag index to download.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+// This struct says where to find the tags of another repository, so references with labels like
+// `other#label` can be checked. Exactly one of the fields must be given.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Repository {
+    // A local checkout of the repository, which is scanned for tags
+    pub path: Option<PathBuf>,
+
+    // A URL serving the tag index of the repository
+    pub url: Option<String>,
+
+    // A local copy of the tag index of the repository
+    pub index: Option<PathBuf>,
+}
+
+// This struct represents an entry in a tag index. Only the label matters, so the other fields are
+// ignored.
+#[derive(Deserialize)]
+struct Entry {
+    label: String,
+}
+
+// This function parses a tag index, which has the same format as the output of `list-tags` in
+// JSON.
+fn parse_index(contents: &str) -> Result<HashSet<String>, String> {
+    let entries =
+        serde_json::from_str::<Vec<Entry>>(contents).map_err(|error| error.to_string())?;
+
+    Ok(entries.into_iter().map(|entry| entry.label).collect())
+}
+
+// This function writes the tag index of a repository to a file, so other repositories can check
+// their references to it without a checkout.
+pub fn write_index(index: &Index, path: &Path) -> Result<(), String> {
+    let tags = index.tags.values().flatten().collect::<Vec<&Directive>>();
+
+    // Serializing plain data to a string can't fail, so the `unwrap` is safe.
+    fs::write(path, serde_json::to_string_pretty(&tags).unwrap() + "\n").map_err(|error| {
+        format!(
+            "Unable to write tag index {}: {error}",
+            path.to_string_lossy(),
+        )
+    })
+}
+
+// This function reads a tag index from a file.
+pub fn read_index(path: &Path) -> Result<HashSet<String>, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|error| format!("unable to read {}: {error}", path.to_string_lossy()))?;
+
+    parse_index(&contents)
+        .map_err(|error| format!("unable to parse {}: {error}", path.to_string_lossy()))
+}
+
+// This function splits a reference like `other#label` into the alias of a repository and the
+// label within it, if the alias is one of the given repositories. Labels with a `#` are otherwise
+// left alone, since they may be ordinary tags.
+#[must_use]
+pub fn split<'a, T>(
+    label: &'a str,
+    repositories: &BTreeMap<String, T>,
+) -> Option<(&'a str, &'a str)> {
+    label
+        .split_once('#')
+        .map(|(alias, label)| (alias.trim(), label.trim()))
+        .filter(|(alias, _)| repositories.contains_key(*alias))
+}
+
+// This function scans a local checkout of a repository for tags, using the same syntax as the
+// current one.
+fn scan(scanner: &Scanner, path: PathBuf) -> Result<HashSet<String>, String> {
+    if !path.exists() {
+        return Err(format!("{} does not exist", path.to_string_lossy()));
+    }
+
+    let scanner = Scanner {
+        paths: vec![path],
+        ..scanner.clone()
+    };
+    let (index, _) = scanner.scan(None)?;
+
+    Ok(index.tags.into_keys().collect())
+}
+
+// This function downloads the tag index of a repository.
+fn download(url: &str) -> Result<HashSet<String>, String> {
+    let agent: Agent = Agent::config_builder()
+        .timeout_global(Some(TIMEOUT))
+        .build()
+        .into();
+    let contents = agent
+        .get(url)
+        .call()
+        .and_then(|mut response| response.body_mut().read_to_string())
+        .map_err(|error| format!("unable to download {url}: {error}"))?;
+    parse_index(&contents).map_err(|error| format!("unable to parse {url}: {error}"))
+}
+
+// This function collects the tags of each repository. A repository whose tags are unavailable
+// (e.g., because it isn't checked out) is paired with an explanation instead, so references to it
+// can be skipped rather than reported as dangling.
+#[must_use]
+pub fn load(
+    repositories: &BTreeMap<String, Repository>,
+    scanner: &Scanner,
+) -> BTreeMap<String, Result<HashSet<String>, String>> {
+    repositories
+        .iter()
+        .map(|(alias, repository)| {
+            let start = Instant::now();
+            let tags = match (&repository.path, &repository.url, &repository.index) {
+                (Some(path), None, None) => scan(scanner, path.clone()),
+                (None, Some(url), None) => download(url),
+                (None, None, Some(index)) => read_index(index),
+                _ => Err("exactly one of `path`, `url`, and `index` must be given".to_owned()),
+            };
+            if let Ok(tags) = &tags {
+                log::info!(
+                    "Loaded {} from the `{alias}` repository in {:.2?}.",
+                    count::count(tags.len(), "tag"),
+                    start.elapsed(),
+                );
+            }
+            (alias.clone(), tags)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repositories::{parse_index, split};
+    use std::collections::{BTreeMap, HashSet};
+
+    #[test]
+    fn split_known_repository() {
+        let repositories = BTreeMap::from([("other".to_owned(), None::<()>)]);
+
+        assert_eq!(split("other#foo", &repositories), Some(("other", "foo")));
+    }
+
+    #[test]
+    fn split_unknown_repository() {
+        let repositories = BTreeMap::from([("other".to_owned(), None::<()>)]);
+
+        assert_eq!(split("another#foo", &repositories), None);
+        assert_eq!(split("foo", &repositories), None);
+    }
+
+    #[test]
+    fn parse_index_labels() {
+        let tags = parse_index(r#"[{"type":"tag","label":"foo","line_number":1},{"label":"bar"}]"#)
+            .unwrap();
+
+        assert_eq!(tags, HashSet::from(["foo".to_owned(), "bar".to_owned()]));
+    }
+
+    #[test]
+    fn parse_index_invalid() {
+        assert!(parse_index(r#"{"label":"foo"}"#).is_err());
+    }
+}
@@ -0,0 +1,365 @@
+use crate::{
+    aliases, attributes,
+    directive::{self, Type},
+    edits::{Edit, Edits},
+    fix::Fix,
+    index::{Index, Rules, Scanner},
+    moves::Relabel,
+    pins, walk,
+};
+use regex::Regex;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    path::PathBuf,
+};
+
+// This function replaces the label of every directive matched by one of the `regexes` whose label
+// is exactly `old` with `new`. Everything else, including the whitespace inside the brackets and
+// the line endings, is preserved.
+#[must_use]
+pub fn rename(contents: &str, regexes: &[&Regex], old: &str, new: &str) -> Vec<Edit> {
+    let replacements = regexes
+        .iter()
+        .map(|regex| (*regex, old, new))
+        .collect::<Vec<_>>();
+    relabel(contents, &replacements)
+}
+
+// This function finds the edits which rename a tag and every reference to it in the scanned files,
+// after making sure the rename makes sense. If labels are case-insensitive, the tag and the
+// references to it may be written differently, so each spelling is replaced.
+pub fn rename_tag(scanner: &Scanner, index: &Index, old: &str, new: &str) -> Result<Edits, String> {
+    let old = old.trim();
+    let new = new.trim();
+    if new.is_empty() || new.contains(']') {
+        return Err(format!("`{new}` is not a valid tag name."));
+    }
+    let key = index.key(old);
+    let Some(tags) = index.tags.get(&*key) else {
+        return Err(format!("No tag found for label `{old}`."));
+    };
+    if index.key(new) != key && index.tags.contains_key(&*index.key(new)) {
+        return Err(format!("A tag already exists for label `{new}`."));
+    }
+
+    let spellings = tags
+        .iter()
+        .chain(
+            index
+                .refs
+                .iter()
+                .filter(|r#ref| index.key(&r#ref.label) == key),
+        )
+        .map(|directive| directive.label.clone())
+        .collect::<BTreeSet<_>>();
+    let regexes = [scanner.tag_regex.clone(), scanner.ref_regex.clone()]
+        .into_iter()
+        .chain(scanner.tag_block_regex.clone())
+        .collect::<Vec<_>>();
+    let new = new.to_owned();
+    Edits::collect(&scanner.paths, &scanner.walk_options, move |_, contents| {
+        let (spellings, new) = (&spellings, new.as_str());
+        let replacements = regexes
+            .iter()
+            .flat_map(|regex| spellings.iter().map(move |old| (regex, old.as_str(), new)))
+            .collect::<Vec<_>>();
+        relabel(contents, &replacements)
+    })
+}
+
+// This function finds the edits which pin the stale pinned references (see `pins::stale`) to the
+// current content of their tags. If any labels are given, only the references to those tags are
+// repinned.
+pub fn repin(
+    scanner: &Scanner,
+    index: &Index,
+    rules: &Rules,
+    labels: &[String],
+) -> Result<Edits, String> {
+    let aliases = aliases::resolve(
+        &index.tags,
+        &index.aliases,
+        &rules.aliases,
+        index.case_insensitive_labels,
+    );
+    let keys = labels
+        .iter()
+        .map(|label| index.key(label.trim()).into_owned())
+        .collect::<HashSet<_>>();
+    let mut relabels = HashMap::<PathBuf, Vec<(String, String)>>::new();
+    for stale in pins::stale(
+        &index.pins,
+        &index.tags,
+        &aliases,
+        index.case_insensitive_labels,
+        None,
+    )? {
+        if keys.is_empty() || keys.contains(&*index.key(&stale.tag.label)) {
+            relabels
+                .entry(walk::relative(&stale.r#ref.path))
+                .or_default()
+                .push((stale.r#ref.label.clone(), stale.label()));
+        }
+    }
+
+    let ref_regex = scanner.ref_regex.clone();
+    Edits::collect(
+        &scanner.paths,
+        &scanner.walk_options,
+        move |path, contents| {
+            let replacements = relabels
+                .get(&walk::relative(path))
+                .into_iter()
+                .flatten()
+                .map(|(old, new)| (&ref_regex, old.as_str(), new.as_str()))
+                .collect::<Vec<_>>();
+            relabel(contents, &replacements)
+        },
+    )
+}
+
+// This function finds the edits which update the file and directory references affected by some
+// moves, given their new labels grouped by the file containing them (see `moves::relabel`).
+pub fn move_references(
+    scanner: &Scanner,
+    relabels: BTreeMap<PathBuf, Vec<Relabel>>,
+) -> Result<Edits, String> {
+    let file_regex = scanner.file_regex.clone();
+    let dir_regex = scanner.dir_regex.clone();
+    Edits::collect(
+        &scanner.paths,
+        &scanner.walk_options,
+        move |path, contents| {
+            let Some(relabels) = relabels.get(&walk::relative(path)) else {
+                return Vec::new();
+            };
+            let replacements = relabels
+                .iter()
+                .map(|relabel| {
+                    (
+                        if relabel.r#type == Type::Dir {
+                            &dir_regex
+                        } else {
+                            &file_regex
+                        },
+                        relabel.old.as_str(),
+                        relabel.new.as_str(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            relabel(contents, &replacements)
+        },
+    )
+}
+
+// This function finds the edits which apply fixes to broken references. Only the files containing
+// the references are fixed, since the same label can be fixed differently in different roots.
+pub fn fix(scanner: &Scanner, fixes: Vec<Fix>) -> Result<Edits, String> {
+    let file_regex = scanner.file_regex.clone();
+    let dir_regex = scanner.dir_regex.clone();
+    let ref_regex = scanner.ref_regex.clone();
+    Edits::collect(
+        &scanner.paths,
+        &scanner.walk_options,
+        move |path, contents| {
+            let path = walk::relative(path);
+            let replacements = fixes
+                .iter()
+                .filter(|fix| {
+                    fix.directives
+                        .iter()
+                        .any(|directive| walk::relative(&directive.path) == path)
+                })
+                .map(|fix| {
+                    (
+                        match fix.r#type {
+                            Type::Dir => &dir_regex,
+                            Type::Ref => &ref_regex,
+                            _ => &file_regex,
+                        },
+                        fix.old.as_str(),
+                        fix.new.as_str(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            relabel(contents, &replacements)
+        },
+    )
+}
+
+// This function is a generalization of `rename` which performs several replacements at once. Each
+// replacement is a regex for matching directives along with the old and new labels. A label which
+// is `old` followed by a pin keeps the pin.
+#[must_use]
+pub fn relabel(contents: &str, replacements: &[(&Regex, &str, &str)]) -> Vec<Edit> {
+    let mut edits = Vec::new();
+    let mut offset = 0;
+
+    // The lines are separated from their endings so the regexes see the same text as in `parse`.
+    for (line, ending) in directive::lines(contents) {
+        // Find the byte ranges of all the labels to replace, in order.
+        let mut ranges = replacements
+            .iter()
+            .flat_map(|(regex, old, new)| {
+                regex
+                    .captures_iter(line)
+                    .filter_map(|captures| captures.get(1))
+                    .filter_map(move |label| {
+                        // The attributes and the pin are kept, so only a prefix is replaced.
+                        let label_only = attributes::split(label.as_str()).0;
+                        let unpinned = pins::split(label_only).0;
+                        [label_only, unpinned]
+                            .into_iter()
+                            .find(|prefix| prefix == old)
+                            .map(|prefix| (label.start()..label.start() + prefix.len(), *new))
+                    })
+            })
+            .collect::<Vec<_>>();
+        ranges.sort_by_key(|(range, _)| range.start);
+
+        edits.extend(ranges.into_iter().map(|(range, new)| Edit {
+            range: offset + range.start..offset + range.end,
+            replacement: new.to_owned(),
+        }));
+        offset += line.len() + ending.len();
+    }
+
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::compile_directive_regex,
+        edits::splice,
+        rewrite::{relabel, rename},
+    };
+
+    #[test]
+    fn rename_empty() {
+        let tag_regex = compile_directive_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+
+        let edits = rename("", &[&tag_regex, &ref_regex], "foo", "bar");
+
+        assert!(edits.is_empty());
+    }
+
+    #[test]
+    fn rename_tag_and_refs() {
+        let tag_regex = compile_directive_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let before = "
+      [?tag:foo]
+      [?ref:foo] and [?ref:foo]
+      [?ref:food]
+    "
+        .replace('?', "");
+        let after = "
+      [?tag:bar]
+      [?ref:bar] and [?ref:bar]
+      [?ref:food]
+    "
+        .replace('?', "");
+
+        let edits = rename(&before, &[&tag_regex, &ref_regex], "foo", "bar");
+        let contents = splice(&before, &edits);
+
+        assert_eq!(contents, after);
+        assert_eq!(edits.len(), 3);
+        assert_eq!(&before[edits[1].range.clone()], "foo");
+        assert_eq!(edits[1].replacement, "bar");
+    }
+
+    #[test]
+    fn rename_keeps_pins() {
+        let ref_regex = compile_directive_regex("ref");
+        let before = "[?ref:foo@sha256:1a2b3c4d] [?ref:foo @sha256:]".replace('?', "");
+        let after = "[?ref:bar@sha256:1a2b3c4d] [?ref:bar @sha256:]".replace('?', "");
+
+        let edits = rename(&before, &[&ref_regex], "foo", "bar");
+        let contents = splice(&before, &edits);
+
+        assert_eq!(contents, after);
+        assert_eq!(edits.len(), 2);
+    }
+
+    #[test]
+    fn rename_keeps_attributes() {
+        let tag_regex = compile_directive_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let before = "[?tag:foo since=2023-04] [?ref:foo@sha256:1a2b3c4d why=x]".replace('?', "");
+        let after = "[?tag:bar since=2023-04] [?ref:bar@sha256:1a2b3c4d why=x]".replace('?', "");
+
+        let edits = rename(&before, &[&tag_regex, &ref_regex], "foo", "bar");
+        let contents = splice(&before, &edits);
+
+        assert_eq!(contents, after);
+        assert_eq!(edits.len(), 2);
+    }
+
+    #[test]
+    fn rename_ignores_other_sigils() {
+        let tag_regex = compile_directive_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let before = "[?file:foo] [?dir:foo]".replace('?', "");
+
+        let edits = rename(&before, &[&tag_regex, &ref_regex], "foo", "bar");
+        let contents = splice(&before, &edits);
+
+        assert_eq!(contents, before);
+        assert!(edits.is_empty());
+    }
+
+    #[test]
+    fn rename_preserves_whitespace_and_case() {
+        let tag_regex = compile_directive_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let before = "[  ?TAG  :  foo  ]".replace('?', "");
+
+        let contents = splice(
+            &before,
+            &rename(&before, &[&tag_regex, &ref_regex], "foo", "bar"),
+        );
+
+        assert_eq!(contents, "[  ?TAG  :  bar  ]".replace('?', ""));
+    }
+
+    #[test]
+    fn rename_preserves_line_endings() {
+        let tag_regex = compile_directive_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let before = "[?tag:foo]\r\n[?ref:foo]\n[?ref:foo]\r[?ref:foo]".replace('?', "");
+
+        let edits = rename(&before, &[&tag_regex, &ref_regex], "foo", "bar");
+        let contents = splice(&before, &edits);
+
+        assert_eq!(
+            contents,
+            "[?tag:bar]\r\n[?ref:bar]\n[?ref:bar]\r[?ref:bar]".replace('?', "")
+        );
+        assert_eq!(edits.len(), 4);
+        assert_eq!(edits[3].range.start, before.rfind("foo").unwrap());
+    }
+
+    #[test]
+    fn relabel_several() {
+        let file_regex = compile_directive_regex("file");
+        let dir_regex = compile_directive_regex("dir");
+        let before = "[?file:a.rs] [?dir:b] [?file:c.rs]".replace('?', "");
+
+        let edits = relabel(
+            &before,
+            &[
+                (&file_regex, "a.rs", "src/a.rs"),
+                (&dir_regex, "b", "src/b"),
+            ],
+        );
+
+        assert_eq!(
+            splice(&before, &edits),
+            "[?file:src/a.rs] [?dir:src/b] [?file:c.rs]".replace('?', "")
+        );
+        assert_eq!(edits.len(), 2);
+    }
+}
@@ -0,0 +1,426 @@
+use crate::{directive::Directive, walk};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Component, Path},
+};
+
+// This struct pairs a tag in a Rust doc comment with the path of the item the comment documents,
+// e.g., `crate::index::Scanner::scan`.
+#[derive(Clone, Debug, Serialize)]
+pub struct Entry {
+    pub tag: Directive,
+    pub item: String,
+}
+
+// These are the kinds of items whose bodies name the items inside them.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Container {
+    // Modules, traits, and implementations, which contain other items
+    Items,
+
+    // Structs, enums, and unions, which contain fields or variants
+    Members,
+
+    // Functions and anything else with a body, whose contents can't be named from outside
+    Body,
+}
+
+// This function determines the path of the module defined by a Rust source file, assuming the
+// usual layout of a crate under `src`. For example, `src/index.rs` and `src/index/mod.rs` define
+// `crate::index`, and `src/lib.rs` and `src/main.rs` define `crate` itself.
+#[must_use]
+pub fn module_path(path: &Path) -> String {
+    let components = path
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let start = components
+        .iter()
+        .rposition(|component| component == "src")
+        .map_or(components.len().saturating_sub(1), |index| index + 1);
+
+    let mut segments = vec!["crate".to_owned()];
+    let module = &components[start.min(components.len())..];
+    for (index, component) in module.iter().enumerate() {
+        if index + 1 < module.len() {
+            segments.push(component.to_string());
+            continue;
+        }
+        let stem = Path::new(component.as_ref())
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let root = stem == "mod" || (module.len() == 1 && (stem == "lib" || stem == "main"));
+        if !root {
+            segments.push(stem);
+        }
+    }
+    segments.join("::")
+}
+
+// This function removes any visibility and qualifiers from the start of a declaration, as in
+// `pub(crate) const unsafe fn`, leaving the keyword.
+fn strip_qualifiers(mut declaration: &str) -> &str {
+    loop {
+        declaration = declaration.trim_start();
+        if let Some(rest) = declaration.strip_prefix("pub")
+            && (rest.starts_with(char::is_whitespace) || rest.starts_with('('))
+        {
+            let rest = rest.trim_start();
+            declaration = if rest.starts_with('(') {
+                rest.find(')').map_or(rest, |end| &rest[end + 1..])
+            } else {
+                rest
+            };
+            continue;
+        }
+        if let Some(rest) = declaration.strip_prefix("extern")
+            && rest.starts_with(char::is_whitespace)
+        {
+            let rest = rest.trim_start();
+            if let Some(abi) = rest.strip_prefix('"') {
+                declaration = abi.find('"').map_or(abi, |end| &abi[end + 1..]);
+                continue;
+            }
+            if rest.starts_with("fn") {
+                declaration = rest;
+                continue;
+            }
+        }
+        let qualifier = ["async", "unsafe", "default", "const"]
+            .into_iter()
+            .find_map(|qualifier| {
+                let rest = declaration.strip_prefix(qualifier)?;
+                let next = rest.trim_start();
+                (rest.starts_with(char::is_whitespace)
+                    && ["fn", "unsafe", "async", "extern", "impl", "trait"]
+                        .iter()
+                        .any(|keyword| next.starts_with(keyword)))
+                .then_some(next)
+            });
+        match qualifier {
+            Some(rest) => declaration = rest,
+            None => return declaration,
+        }
+    }
+}
+
+// This function extracts the identifier at the start of some text, if there is one.
+fn identifier(text: &str) -> Option<&str> {
+    let text = text.trim_start();
+    let text = text.strip_prefix("r#").unwrap_or(text);
+    let end = text
+        .find(|character: char| !(character.is_alphanumeric() || character == '_'))
+        .unwrap_or(text.len());
+    (end > 0 && !text.starts_with(|character: char| character.is_ascii_digit()))
+        .then(|| &text[..end])
+}
+
+// This function extracts the name of the type that an `impl` block is for, without any generic
+// arguments or path, as in `impl<T> fmt::Display for Wrapper<T>`.
+fn impl_type(rest: &str) -> Option<&str> {
+    // Skip the generic parameters of the block, which may be nested.
+    let mut rest = rest.trim_start();
+    if rest.starts_with('<') {
+        let mut depth = 0_usize;
+        let end = rest.char_indices().find_map(|(offset, character)| {
+            match character {
+                '<' => depth += 1,
+                '>' => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            (depth == 0).then_some(offset + 1)
+        })?;
+        rest = &rest[end..];
+    }
+    let rest = rest.split(" for ").nth(1).unwrap_or(rest);
+    let rest = rest.trim_start().trim_start_matches(['&', '!']);
+    let path = rest
+        .split(['<', '{', ' '])
+        .next()
+        .unwrap_or_default()
+        .trim();
+    identifier(path.rsplit("::").next().unwrap_or(path))
+}
+
+// This function recognizes the declaration of an item and returns its name, along with what kind
+// of container its body (if any) is. Implementations are named after their types.
+fn declaration(line: &str) -> Option<(&str, Container)> {
+    let declaration = strip_qualifiers(line);
+    if let Some(rest) = declaration.strip_prefix("macro_rules!") {
+        return identifier(rest).map(|name| (name, Container::Body));
+    }
+    if let Some(rest) = declaration.strip_prefix("impl")
+        && (rest.starts_with(char::is_whitespace) || rest.starts_with('<'))
+    {
+        return impl_type(rest).map(|name| (name, Container::Items));
+    }
+
+    let keyword = identifier(declaration)?;
+    let container = match keyword {
+        "mod" | "trait" => Container::Items,
+        "struct" | "enum" | "union" => Container::Members,
+        "fn" | "type" | "const" | "static" => Container::Body,
+        _ => return None,
+    };
+    let rest = declaration[keyword.len()..].trim_start();
+    let rest = rest.strip_prefix("mut ").unwrap_or(rest);
+    identifier(rest).map(|name| (name, container))
+}
+
+// This function finds the braces in a line of code, skipping string and character literals and
+// comments. It returns the code before any line comment, and the braces in order.
+fn braces(line: &str) -> (&str, Vec<char>) {
+    let mut braces = Vec::new();
+    let mut in_string = false;
+    let mut characters = line.char_indices().peekable();
+    while let Some((offset, character)) = characters.next() {
+        match character {
+            '\\' if in_string => {
+                characters.next();
+            }
+            '"' => in_string = !in_string,
+            '/' if !in_string && characters.peek().is_some_and(|(_, next)| *next == '/') => {
+                return (&line[..offset], braces);
+            }
+            '\'' if !in_string && line[offset..].chars().nth(2) == Some('\'') => {
+                characters.next();
+                characters.next();
+            }
+            '{' | '}' if !in_string => braces.push(character),
+            _ => {}
+        }
+    }
+    (line, braces)
+}
+
+// This function maps the lines of the doc comments in some Rust code to the items they document,
+// as paths relative to the module, e.g., `Scanner::scan`. Inner doc comments (`//!`) document the
+// enclosing item, which for the module itself is the empty path. The code isn't parsed properly,
+// but the usual formatting (as by `rustfmt`) is understood.
+#[must_use]
+pub fn items(text: &str) -> BTreeMap<usize, String> {
+    let mut items = BTreeMap::new();
+    let mut stack = Vec::<(String, Container, usize)>::new();
+    let mut depth = 0_usize;
+    let mut pending_docs = Vec::<usize>::new();
+    let mut pending_body = None::<(String, Container)>;
+    let mut block_doc = None::<bool>;
+
+    let path = |stack: &[(String, Container, usize)], name: Option<&str>| {
+        stack
+            .iter()
+            .map(|(name, _, _)| name.as_str())
+            .chain(name)
+            .collect::<Vec<_>>()
+            .join("::")
+    };
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let trimmed = line.trim();
+
+        // Doc comments are collected until the item they document.
+        if let Some(inner) = block_doc {
+            if inner {
+                items.insert(line_number, path(&stack, None));
+            } else {
+                pending_docs.push(line_number);
+            }
+            if trimmed.contains("*/") {
+                block_doc = None;
+            }
+            continue;
+        }
+        if trimmed.starts_with("//!") || trimmed.starts_with("/*!") {
+            items.insert(line_number, path(&stack, None));
+            if trimmed.starts_with("/*!") && !trimmed.contains("*/") {
+                block_doc = Some(true);
+            }
+            continue;
+        }
+        if (trimmed.starts_with("///") && !trimmed.starts_with("////"))
+            || (trimmed.starts_with("/**") && !trimmed.starts_with("/***"))
+        {
+            pending_docs.push(line_number);
+            if trimmed.starts_with("/**") && !trimmed[3..].contains("*/") {
+                block_doc = Some(false);
+            }
+            continue;
+        }
+        if trimmed.is_empty() || trimmed.starts_with("//") || trimmed.starts_with("#[") {
+            continue;
+        }
+
+        // Anything else is code, which may declare an item.
+        let (code, braces) = braces(line);
+        let container = stack.last().map(|(_, container, _)| *container);
+        let declared = declaration(code)
+            .map(|(name, container)| (name.to_owned(), container))
+            .or_else(|| {
+                // Fields and variants are named by their first identifier.
+                (container == Some(Container::Members))
+                    .then(|| identifier(strip_qualifiers(code)))
+                    .flatten()
+                    .map(|name| (name.to_owned(), Container::Body))
+            });
+        if let Some((name, _)) = &declared {
+            for line_number in pending_docs.drain(..) {
+                items.insert(line_number, path(&stack, Some(name)));
+            }
+        }
+        pending_docs.clear();
+        if declared.is_some() {
+            pending_body = declared;
+        }
+
+        for brace in braces {
+            if brace == '{' {
+                depth += 1;
+                let (name, container) = pending_body
+                    .take()
+                    .unwrap_or_else(|| (String::new(), Container::Body));
+                stack.push((name, container, depth));
+            } else {
+                stack.retain(|(_, _, opened)| *opened < depth);
+                depth = depth.saturating_sub(1);
+            }
+        }
+        if code.trim_end().ends_with(';') || code.trim_end().ends_with(',') {
+            pending_body = None;
+        }
+    }
+
+    // Blocks which aren't items (e.g., loops in functions) don't contribute to the paths.
+    for path in items.values_mut() {
+        *path = path
+            .split("::")
+            .filter(|segment| !segment.is_empty())
+            .collect::<Vec<_>>()
+            .join("::");
+    }
+    items
+}
+
+// This function finds the tags in the doc comments of Rust files and the items they document. The
+// files are read again to find the items. Files which can't be read are skipped with a warning.
+#[must_use]
+pub fn find<'a, I: IntoIterator<Item = &'a Directive>>(tags: I) -> Vec<Entry> {
+    let mut by_path = BTreeMap::<_, Vec<&Directive>>::new();
+    for tag in tags {
+        if tag
+            .path
+            .extension()
+            .is_some_and(|extension| extension == "rs")
+        {
+            by_path.entry(tag.path.clone()).or_default().push(tag);
+        }
+    }
+
+    let mut entries = Vec::new();
+    let mut modules = HashMap::new();
+    for (path, tags) in by_path {
+        let items = match fs::read_to_string(&path) {
+            Ok(contents) => items(&contents),
+            Err(error) => {
+                log::warn!("Unable to read {}: {error}", path.to_string_lossy());
+                continue;
+            }
+        };
+        let module = modules
+            .entry(path.clone())
+            .or_insert_with(|| module_path(&walk::relative(&path)));
+        for tag in tags {
+            if let Some(item) = items.get(&tag.line_number) {
+                entries.push(Entry {
+                    tag: tag.clone(),
+                    item: if item.is_empty() {
+                        module.clone()
+                    } else {
+                        format!("{module}::{item}")
+                    },
+                });
+            }
+        }
+    }
+    entries.sort_by(|x, y| x.tag.cmp_position(&y.tag));
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rustdoc::{items, module_path};
+    use std::path::Path;
+
+    #[test]
+    fn module_path_layout() {
+        assert_eq!(module_path(Path::new("src/lib.rs")), "crate");
+        assert_eq!(module_path(Path::new("src/main.rs")), "crate");
+        assert_eq!(module_path(Path::new("src/index.rs")), "crate::index");
+        assert_eq!(module_path(Path::new("src/index/mod.rs")), "crate::index");
+        assert_eq!(
+            module_path(Path::new("crates/core/src/walk/main.rs")),
+            "crate::walk::main",
+        );
+        assert_eq!(module_path(Path::new("build.rs")), "crate::build");
+    }
+
+    #[test]
+    fn items_documented() {
+        let text = r#"//! The crate.
+
+/// A scanner.
+#[derive(Clone)]
+pub struct Scanner {
+    /// The paths.
+    pub paths: Vec<String>,
+}
+
+impl<T: Clone> fmt::Display for Wrapper<T> {
+    /// Formats it.
+    fn fmt(&self) -> String {
+        let braces = "{";
+        /// Not nameable.
+        let x = '}';
+    }
+}
+
+pub(crate) mod inner {
+    //! The inner module.
+
+    /** An enum. */
+    pub enum Kind {
+        /// A variant.
+        First { x: u8 },
+    }
+
+    /// A constant function.
+    pub const unsafe fn answer() -> u8 {
+        42
+    }
+}
+
+/// Not documenting anything.
+
+let x = 1;
+"#;
+        assert_eq!(
+            items(text).into_iter().collect::<Vec<_>>(),
+            vec![
+                (1, String::new()),
+                (3, "Scanner".to_owned()),
+                (6, "Scanner::paths".to_owned()),
+                (11, "Wrapper::fmt".to_owned()),
+                (20, "inner".to_owned()),
+                (22, "inner::Kind".to_owned()),
+                (24, "inner::Kind::First".to_owned()),
+                (28, "inner::answer".to_owned()),
+            ],
+        );
+    }
+}
@@ -0,0 +1,229 @@
+use serde_json::{Value, json};
+use std::{fmt::Write, path::Path};
+use tagref::{
+    directive::Directive,
+    violation::{Kind, Severity, Violation},
+};
+
+// This function converts a path into a relative URI reference as expected by SARIF consumers such
+// as GitHub Code Scanning. Leading `./` components are dropped, backslashes become forward
+// slashes, and any characters that aren't allowed in a URI path are percent-encoded.
+fn uri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut path = path.as_str();
+    while let Some(rest) = path.strip_prefix("./") {
+        path = rest;
+    }
+
+    let mut uri = String::new();
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/!$&'()*+,;=:@".contains(&byte) {
+            uri.push(char::from(byte));
+        } else {
+            let _ = write!(uri, "%{byte:02X}");
+        }
+    }
+
+    uri
+}
+
+// This function builds a SARIF location object for a directive.
+fn location(directive: &Directive) -> Value {
+    json!({
+        "physicalLocation": {
+            "artifactLocation": {
+                "uri": uri(&directive.path),
+            },
+            "region": {
+                "startLine": directive.line_number,
+                "startColumn": directive.column,
+            },
+        },
+    })
+}
+
+// This function builds a SARIF result for a violation.
+fn result(violation: &Violation) -> Value {
+    let locations = violation
+        .directives
+        .first()
+        .map(location)
+        .into_iter()
+        .collect::<Vec<_>>();
+
+    let mut result = json!({
+        "ruleId": violation.kind.id(),
+        // The position of the kind in `Kind::ALL` is its index in the rules array.
+        "ruleIndex": Kind::ALL.iter().position(|kind| *kind == violation.kind),
+        "level": match violation.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "note",
+        },
+        "message": {
+            "text": violation.message.trim_end(),
+        },
+        "locations": locations,
+    });
+
+    // Any additional directives (e.g., the other copies of a duplicate tag) are reported as
+    // related locations.
+    if violation.directives.len() > 1 {
+        result["relatedLocations"] = violation.directives[1..]
+            .iter()
+            .map(location)
+            .collect::<Vec<_>>()
+            .into();
+    }
+
+    result
+}
+
+// This function builds a SARIF 2.1.0 log containing one result per violation. Violations which
+// were suppressed by comments are included too, marked as suppressed in the source.
+pub fn report(violations: &[Violation], suppressed: &[Violation]) -> Value {
+    let rules = Kind::ALL
+        .iter()
+        .map(|kind| {
+            json!({
+                "id": kind.id(),
+                "shortDescription": {
+                    "text": kind.description(),
+                },
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let results = violations
+        .iter()
+        .map(result)
+        .chain(suppressed.iter().map(|violation| {
+            let mut result = result(violation);
+            result["suppressions"] = json!([{ "kind": "inSource" }]);
+            result
+        }))
+        .collect::<Vec<_>>();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [
+            {
+                "tool": {
+                    "driver": {
+                        "name": "tagref",
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "informationUri": env!("CARGO_PKG_HOMEPAGE"),
+                        "rules": rules,
+                    },
+                },
+                "results": results,
+                // Columns count characters, not UTF-16 code units as SARIF assumes by default.
+                "columnKind": "unicodeCodePoints",
+            },
+        ],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        fixtures,
+        sarif::{report, uri},
+    };
+    use std::path::Path;
+    use tagref::{
+        directive::Type,
+        violation::{Kind, Severity, Violation},
+    };
+
+    #[test]
+    fn uri_strips_dot_slash() {
+        assert_eq!(uri(Path::new("./src/main.rs")), "src/main.rs");
+    }
+
+    #[test]
+    fn uri_encodes_special_characters() {
+        assert_eq!(uri(Path::new("./foo bar/100%.rs")), "foo%20bar/100%25.rs");
+    }
+
+    #[test]
+    fn uri_converts_backslashes() {
+        assert_eq!(uri(Path::new(".\\src\\main.rs")), "src/main.rs");
+    }
+
+    #[test]
+    fn report_empty() {
+        let log = report(&[], &[]);
+
+        assert_eq!(log["version"], "2.1.0");
+        assert_eq!(log["runs"][0]["results"].as_array().unwrap().len(), 0);
+        assert_eq!(
+            log["runs"][0]["tool"]["driver"]["rules"]
+                .as_array()
+                .unwrap()
+                .len(),
+            Kind::ALL.len(),
+        );
+    }
+
+    #[test]
+    fn report_duplicate() {
+        let directives = vec![
+            fixtures::directive(Type::Tag, "tag1", "./file1.rs", 1),
+            fixtures::directive(Type::Tag, "tag1", "./file2.rs", 2),
+        ];
+
+        let log = report(
+            &[Violation {
+                kind: Kind::DuplicateTag,
+                severity: Severity::Error,
+                message: "Duplicate tags found for label `tag1`:\n".to_owned(),
+                directives,
+                suggestions: Vec::new(),
+            }],
+            &[],
+        );
+
+        let result = &log["runs"][0]["results"][0];
+        assert_eq!(result["ruleId"], "duplicate_tag");
+        assert_eq!(result["ruleIndex"], 0_usize);
+        assert_eq!(
+            result["message"]["text"],
+            "Duplicate tags found for label `tag1`:",
+        );
+        assert_eq!(
+            result["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+            "file1.rs",
+        );
+        assert_eq!(
+            result["locations"][0]["physicalLocation"]["region"]["startLine"],
+            1_usize,
+        );
+        assert_eq!(
+            result["locations"][0]["physicalLocation"]["region"]["startColumn"],
+            1_usize,
+        );
+        assert_eq!(
+            result["relatedLocations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+            "file2.rs",
+        );
+    }
+
+    #[test]
+    fn report_suppressed() {
+        let violation = Violation {
+            kind: Kind::UnusedTag,
+            severity: Severity::Error,
+            message: "Tag `tag1` isn't referenced anywhere.".to_owned(),
+            directives: vec![fixtures::directive(Type::Tag, "tag1", "./file1.rs", 1)],
+            suggestions: Vec::new(),
+        };
+
+        let log = report(&[], &[violation]);
+
+        let result = &log["runs"][0]["results"][0];
+        assert_eq!(result["ruleId"], "unused_tag");
+        assert_eq!(result["suppressions"][0]["kind"], "inSource");
+    }
+}
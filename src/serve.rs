@@ -0,0 +1,904 @@
+use crate::watch::{DEBOUNCE, is_relevant};
+use notify::{Event, RecursiveMode, Watcher, recommended_watcher};
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    mem::take,
+    net::{IpAddr, TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, PoisonError, RwLock,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{Receiver, channel},
+    },
+    thread,
+    time::Duration,
+};
+use tagref::{
+    cache::Cache,
+    directive::{self, Directive, Directives},
+    edits::Edit,
+    git::Changes,
+    index::{Index, Rules, Scanner},
+    pins, rewrite,
+    violation::Violation,
+    walk,
+};
+
+// This is the address the server listens on by default. Only local clients can connect to it.
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:7377";
+
+// Requests with larger bodies are rejected, so a misbehaving client can't exhaust the memory. This
+// leaves plenty of room for the contents of unsaved files.
+const MAX_BODY: usize = 8 << 20_usize;
+
+// Longer lines in the headers of a request are rejected, as are requests with more headers than
+// this, so a client can't exhaust the memory before the body is even read.
+const MAX_LINE: u64 = 8 << 10_u64;
+const MAX_HEADERS: usize = 100;
+
+// Connections beyond this many at once are turned away, so clients can't exhaust the threads.
+const MAX_CONNECTIONS: usize = 64;
+
+// Clients which stop sending in the middle of a request are disconnected after this long, so they
+// don't tie up a thread forever.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+// These are the error codes defined by JSON-RPC 2.0.
+const PARSE_ERROR: i64 = -32_700;
+const INVALID_REQUEST: i64 = -32_600;
+const METHOD_NOT_FOUND: i64 = -32_601;
+const INVALID_PARAMS: i64 = -32_602;
+
+// This is the error code for methods which were called correctly but failed, from the range which
+// JSON-RPC 2.0 reserves for servers.
+const REQUEST_FAILED: i64 = -32_000;
+
+// This enum represents how the server talks to its clients.
+#[derive(Clone, Copy)]
+pub enum Transport<'a> {
+    // Each request is an HTTP `POST` to this address, carrying one JSON-RPC message.
+    Http(&'a str),
+
+    // Each line of standard input is a JSON-RPC message, and each response is written to standard
+    // output as a line. This suits editors which run the server as a child process.
+    Stdio,
+}
+
+// This struct is a JSON-RPC error.
+#[derive(Debug)]
+struct Error {
+    code: i64,
+    message: String,
+}
+
+// These are the parameters of the `resolve` and `refs` methods.
+#[derive(Deserialize)]
+struct LabelParams {
+    label: String,
+}
+
+// These are the parameters of the `validate` method. The contents replace the file's contents on
+// disk, so they may contain unsaved changes.
+#[derive(Deserialize)]
+struct ValidateParams {
+    path: PathBuf,
+    contents: String,
+}
+
+// These are the parameters of the `diagnostics` method. If a path is given, only the diagnostics in
+// that file are returned, and the contents (if given) replace the file's contents on disk.
+#[derive(Deserialize)]
+struct DiagnosticsParams {
+    path: Option<PathBuf>,
+    contents: Option<String>,
+}
+
+// These are the parameters of the `rename` method.
+#[derive(Deserialize)]
+struct RenameParams {
+    label: String,
+    new_label: String,
+}
+
+// This function deserializes the parameters of a method. Omitted parameters are treated as an
+// empty object, so methods whose parameters are all optional can be called without any.
+fn params<T: DeserializeOwned>(params: Value) -> Result<T, Error> {
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|error| Error {
+        code: INVALID_PARAMS,
+        message: error.to_string(),
+    })
+}
+
+// This function converts a byte offset into a line into a position as editors count them, i.e., a
+// line index and a column in UTF-16 code units, both from 0.
+fn position(line_index: usize, line: &str, offset: usize) -> Value {
+    json!({
+        "line": line_index,
+        "character": line[..line.floor_char_boundary(offset)].encode_utf16().count(),
+    })
+}
+
+// This struct overlays the directives parsed from the contents of a file on the index, in place of
+// the ones from disk. The ones from disk are put back when it's dropped, even if a check panics, so
+// the index doesn't have to be copied for each request.
+struct Overlay<'a> {
+    index: &'a mut Index,
+    path: &'a Path,
+    removed: Directives,
+}
+
+impl<'a> Overlay<'a> {
+    fn new(scanner: &Scanner, index: &'a mut Index, path: &'a Path, contents: &str) -> Self {
+        let removed = index.remove(path);
+        index.insert(scanner.parse(path, contents.as_bytes()));
+        Self {
+            index,
+            path,
+            removed,
+        }
+    }
+}
+
+impl Drop for Overlay<'_> {
+    fn drop(&mut self) {
+        self.index.remove(self.path);
+        self.index.insert(take(&mut self.removed));
+    }
+}
+
+// This function determines the violations which could have been caused by a file with the given
+// contents, as with the `check-stdin` command.
+fn validate(
+    scanner: &Scanner,
+    rules: &Rules,
+    index: &RwLock<Index>,
+    path: &Path,
+    contents: &str,
+) -> Vec<Violation> {
+    // A panic while the contents were overlaid can't leave them behind, so a poisoned lock is safe
+    // to use.
+    let mut index = index.write().unwrap_or_else(PoisonError::into_inner);
+    let overlay = Overlay::new(scanner, &mut index, path, contents);
+    let changes = Changes {
+        files: [walk::relative(path)].into(),
+        old_tags: overlay
+            .removed
+            .tags
+            .iter()
+            .map(|tag| overlay.index.key(&tag.label).into_owned())
+            .collect(),
+        case_insensitive_labels: overlay.index.case_insensitive_labels,
+    };
+    let mut violations = overlay.index.check(rules);
+    violations.retain(|violation| changes.affects(violation));
+    overlay.index.suppress(&mut violations);
+    violations
+}
+
+// This function turns violations into diagnostics, one for each label involved, with the range of
+// the label in its file. The lines are read from the given contents for the file they belong to,
+// and from disk otherwise. If a line can't be read, the diagnostic points to where the label
+// starts.
+fn diagnostics(violations: &[Violation], only: Option<(&Path, Option<&str>)>) -> Vec<Value> {
+    let only = only.map(|(path, contents)| (walk::relative(path), contents));
+    let mut files = HashMap::<PathBuf, Option<String>>::new();
+    let mut diagnostics = Vec::new();
+    for violation in violations {
+        for directive in &violation.directives {
+            let path = walk::relative(&directive.path);
+            let contents = match &only {
+                Some((only_path, _)) if *only_path != path => continue,
+                Some((_, Some(contents))) => Some(*contents),
+                _ => files
+                    .entry(path)
+                    .or_insert_with(|| fs::read_to_string(&directive.path).ok())
+                    .as_deref(),
+            };
+            diagnostics.push(diagnostic(violation, directive, contents));
+        }
+    }
+    diagnostics
+}
+
+// This function turns a violation into a diagnostic for one of the labels involved.
+fn diagnostic(violation: &Violation, directive: &Directive, contents: Option<&str>) -> Value {
+    let line_index = directive.line_number.saturating_sub(1);
+    let range = contents
+        .and_then(|contents| directive::lines(contents).nth(line_index))
+        .filter(|_| directive.cell.is_none())
+        .map_or_else(
+            || {
+                let start = json!({ "line": line_index, "character": directive.column - 1 });
+                json!({ "start": start, "end": start })
+            },
+            |(line, _)| {
+                json!({
+                    "start": position(line_index, line, directive.span.start),
+                    "end": position(line_index, line, directive.span.end),
+                })
+            },
+        );
+    json!({
+        "path": directive.path,
+        "range": range,
+        "severity": violation.severity,
+        "code": violation.kind.id(),
+        "message": violation.message.trim_end(),
+    })
+}
+
+// This function describes edits to a file with the ranges editors expect. Labels never span lines,
+// so neither do the edits which relabel them.
+fn text_edits(contents: &str, edits: &[Edit]) -> Vec<Value> {
+    let lines = directive::lines(contents).collect::<Vec<_>>();
+    let mut starts = Vec::with_capacity(lines.len());
+    let mut offset = 0;
+    for (line, ending) in &lines {
+        starts.push(offset);
+        offset += line.len() + ending.len();
+    }
+
+    edits
+        .iter()
+        .map(|edit| {
+            let line_index = starts
+                .partition_point(|start| *start <= edit.range.start)
+                .saturating_sub(1);
+            let (line, start) = (lines[line_index].0, starts[line_index]);
+            json!({
+                "range": {
+                    "start": position(line_index, line, edit.range.start - start),
+                    "end": position(line_index, line, edit.range.end - start),
+                },
+                "new_text": edit.replacement,
+            })
+        })
+        .collect()
+}
+
+// This function calls a method. Most methods only read the index, so many calls can run at once.
+// Checking the contents of a file overlays them on the index for a moment, so that needs the index
+// to itself.
+fn call(
+    scanner: &Scanner,
+    rules: &Rules,
+    index: &RwLock<Index>,
+    method: &str,
+    params: Value,
+) -> Result<Value, Error> {
+    // A panic while the index was being replaced can't leave it half-written, so a poisoned lock is
+    // safe to use.
+    let read = || index.read().unwrap_or_else(PoisonError::into_inner);
+    match method {
+        // The tags which a reference points to. Any pin on the label is ignored.
+        "resolve" => {
+            let LabelParams { label } = self::params(params)?;
+            let index = read();
+            let key = index.key(pins::split(&label).0);
+            Ok(json!(
+                index.tags.get(&*key).map_or(&[] as &[_], Vec::as_slice)
+            ))
+        }
+
+        // The references to a tag
+        "refs" => {
+            let LabelParams { label } = self::params(params)?;
+            let index = read();
+            let key = index.key(&label);
+            Ok(json!(
+                index
+                    .refs
+                    .iter()
+                    .filter(|r#ref| index.key(&r#ref.label) == key)
+                    .collect::<Vec<_>>()
+            ))
+        }
+
+        // The violations which could have been caused by a file with the given contents, as with
+        // the `check-stdin` command
+        "validate" => {
+            let ValidateParams { path, contents } = self::params(params)?;
+            Ok(json!(validate(scanner, rules, index, &path, &contents)))
+        }
+
+        // All the violations
+        "check" => {
+            let index = read();
+            let mut violations = index.check(rules);
+            index.suppress(&mut violations);
+            Ok(json!(violations))
+        }
+
+        // The violations as diagnostics with ranges, for all the files or for one of them
+        "diagnostics" => {
+            let DiagnosticsParams { path, contents } = self::params(params)?;
+            let violations = match (&path, &contents) {
+                (Some(path), Some(contents)) => validate(scanner, rules, index, path, contents),
+                (None, Some(_)) => {
+                    return Err(Error {
+                        code: INVALID_PARAMS,
+                        message: "The contents were given without a path.".to_owned(),
+                    });
+                }
+                (_, None) => {
+                    let index = read();
+                    let mut violations = index.check(rules);
+                    index.suppress(&mut violations);
+                    violations
+                }
+            };
+            Ok(json!(diagnostics(
+                &violations,
+                path.as_deref().map(|path| (path, contents.as_deref())),
+            )))
+        }
+
+        // The edits which would rename a tag and the references to it. Nothing is changed.
+        "rename" => {
+            let RenameParams { label, new_label } = self::params(params)?;
+            let edits =
+                rewrite::rename_tag(scanner, &read(), &label, &new_label).map_err(|message| {
+                    Error {
+                        code: REQUEST_FAILED,
+                        message,
+                    }
+                })?;
+            Ok(Value::Array(
+                edits
+                    .files()
+                    .map(|(path, contents, edits)| {
+                        json!({ "path": path, "edits": text_edits(contents, edits) })
+                    })
+                    .collect(),
+            ))
+        }
+
+        method => Err(Error {
+            code: METHOD_NOT_FOUND,
+            message: format!("Unsupported method `{method}`."),
+        }),
+    }
+}
+
+// This function handles a single JSON-RPC request. Notifications (requests without an `id`) get
+// no response.
+fn respond(
+    scanner: &Scanner,
+    rules: &Rules,
+    index: &RwLock<Index>,
+    request: &Value,
+) -> Option<Value> {
+    let id = request.get("id").cloned();
+    let result = match (
+        request.get("jsonrpc").and_then(Value::as_str),
+        request.get("method").and_then(Value::as_str),
+    ) {
+        (Some("2.0"), Some(method)) => {
+            let params = request.get("params").cloned().unwrap_or(Value::Null);
+            call(scanner, rules, index, method, params)
+        }
+        _ => Err(Error {
+            code: INVALID_REQUEST,
+            message: "Expected a JSON-RPC 2.0 request.".to_owned(),
+        }),
+    };
+
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": error.code, "message": error.message },
+        }),
+    })
+}
+
+// This function handles the body of an HTTP request, which is either a JSON-RPC request or a batch
+// of them. It returns the body of the response, if there is one.
+fn handle(scanner: &Scanner, rules: &Rules, index: &RwLock<Index>, body: &[u8]) -> Option<Value> {
+    match serde_json::from_slice::<Value>(body) {
+        Ok(Value::Array(requests)) if !requests.is_empty() => {
+            let responses = requests
+                .iter()
+                .filter_map(|request| respond(scanner, rules, index, request))
+                .collect::<Vec<_>>();
+            (!responses.is_empty()).then_some(Value::Array(responses))
+        }
+        Ok(Value::Array(_)) => Some(json!({
+            "jsonrpc": "2.0",
+            "id": null,
+            "error": { "code": INVALID_REQUEST, "message": "The batch is empty." },
+        })),
+        Ok(request) => respond(scanner, rules, index, &request),
+        Err(error) => Some(json!({
+            "jsonrpc": "2.0",
+            "id": null,
+            "error": { "code": PARSE_ERROR, "message": error.to_string() },
+        })),
+    }
+}
+
+// This function determines whether the `Host` header of a request names this machine, either as
+// `localhost` or as an IP address, with or without a port. Web pages can only reach the server
+// through other names by rebinding them in DNS, so requests for them are rejected.
+fn is_local_host(host: &str) -> bool {
+    let name = match host.strip_prefix('[') {
+        Some(rest) => rest.split_once(']').map_or(rest, |(address, _)| address),
+        None => host
+            .rsplit_once(':')
+            .filter(|(_, port)| port.parse::<u16>().is_ok())
+            .map_or(host, |(name, _)| name),
+    };
+    name.eq_ignore_ascii_case("localhost") || name.parse::<IpAddr>().is_ok()
+}
+
+// This function reads an HTTP request. It returns the method, the host, and the body.
+fn read_request(reader: &mut impl BufRead) -> io::Result<(String, Option<String>, Vec<u8>)> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+
+    // This reads a line, without reading more than `MAX_LINE` bytes of it.
+    let mut read_line = |line: &mut String| {
+        line.clear();
+        let length = reader.by_ref().take(MAX_LINE).read_line(line)?;
+        if length as u64 == MAX_LINE && !line.ends_with('\n') {
+            return Err(invalid("A line of the request is too long."));
+        }
+        Ok(length)
+    };
+
+    let mut line = String::new();
+    read_line(&mut line)?;
+    let method = line
+        .split_whitespace()
+        .next()
+        .ok_or_else(|| invalid("The request is empty."))?
+        .to_owned();
+
+    let mut length = 0;
+    let mut host = None;
+    let mut headers = 0;
+    loop {
+        if read_line(&mut line)? == 0 {
+            return Err(invalid("The headers ended early."));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        headers += 1;
+        if headers > MAX_HEADERS {
+            return Err(invalid("The request has too many headers."));
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        if name.trim().eq_ignore_ascii_case("content-length") {
+            length = value
+                .trim()
+                .parse()
+                .map_err(|_| invalid("The content length is invalid."))?;
+        } else if name.trim().eq_ignore_ascii_case("host") {
+            host = Some(value.trim().to_owned());
+        }
+    }
+    if length > MAX_BODY {
+        return Err(invalid("The request is too large."));
+    }
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok((method, host, body))
+}
+
+// This function writes an HTTP response.
+fn reply(stream: &mut TcpStream, status: &str, body: &str) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nAllow: POST\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len(),
+    )?;
+    stream.flush()
+}
+
+// This function answers a connection. Each connection carries one request.
+fn connect(
+    scanner: &Scanner,
+    rules: &Rules,
+    index: &RwLock<Index>,
+    mut stream: TcpStream,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let (method, host, body) = read_request(&mut BufReader::new(&stream))?;
+
+    let (status, body) = if !host.as_deref().is_some_and(is_local_host) {
+        ("403 Forbidden", String::new())
+    } else if method == "POST" {
+        match handle(scanner, rules, index, &body) {
+            Some(response) => ("200 OK", response.to_string()),
+            None => ("204 No Content", String::new()),
+        }
+    } else {
+        ("405 Method Not Allowed", String::new())
+    };
+    reply(&mut stream, status, &body)
+}
+
+// This function scans the files again whenever they change, and swaps in the new index. Only files
+// which changed are parsed again. Requests keep being answered from the old index in the meantime.
+fn refresh(
+    scanner: &Scanner,
+    index: &RwLock<Index>,
+    mut cache: Arc<Cache>,
+    receiver: &Receiver<notify::Result<Event>>,
+    stopping: &AtomicBool,
+) {
+    loop {
+        // Wait for a relevant change, and then wait for things to settle.
+        loop {
+            match receiver.recv() {
+                Ok(Ok(event)) if is_relevant(&event) => break,
+                Ok(_) => {}
+                Err(_) => {
+                    if !stopping.load(Ordering::Relaxed) {
+                        log::warn!(
+                            "Stopped receiving filesystem events, so the index is now stale."
+                        );
+                    }
+                    return;
+                }
+            }
+        }
+        while receiver.recv_timeout(DEBOUNCE).is_ok() {}
+
+        match scanner.scan(Some(cache.clone())) {
+            Ok((new_index, new_cache)) => {
+                cache = Arc::new(new_cache.unwrap_or_default());
+                *index.write().unwrap_or_else(PoisonError::into_inner) = new_index;
+                log::info!("Refreshed the index.");
+            }
+            Err(error) => log::warn!("Keeping the previous index: {error}"),
+        }
+    }
+}
+
+// This function answers the requests on standard input, one per line, until it ends.
+fn answer_lines(scanner: &Scanner, rules: &Rules, index: &RwLock<Index>) -> Result<(), String> {
+    for line in io::stdin().lock().lines() {
+        let line = line.map_err(|error| format!("Unable to read a request: {error}"))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = handle(scanner, rules, index, line.as_bytes()) {
+            let mut stdout = io::stdout().lock();
+            writeln!(stdout, "{response}")
+                .and_then(|()| stdout.flush())
+                .map_err(|error| format!("Unable to write a response: {error}"))?;
+        }
+    }
+    Ok(())
+}
+
+// This function runs a JSON-RPC server. The index is kept in memory and refreshed whenever the
+// files change. Over HTTP, it only returns if something goes wrong. Over standard input and output,
+// it also returns when the input ends.
+pub fn serve(scanner: &Scanner, rules: &Rules, transport: Transport) -> Result<(), String> {
+    // Subscribe to filesystem events before the first scan, so no changes are missed.
+    let (sender, receiver) = channel();
+    let mut watcher = recommended_watcher(sender)
+        .map_err(|error| format!("Unable to watch for changes: {error}"))?;
+    for path in &scanner.paths {
+        watcher
+            .watch(path, RecursiveMode::Recursive)
+            .map_err(|error| format!("Unable to watch {}: {error}", path.to_string_lossy()))?;
+    }
+
+    let (index, cache) = scanner.scan(Some(Arc::new(Cache::new(&scanner.cache_key()))))?;
+    let index = RwLock::new(index);
+    let cache = Arc::new(cache.unwrap_or_default());
+    let stopping = AtomicBool::new(false);
+    let connections = AtomicUsize::new(0);
+
+    let listener = match transport {
+        Transport::Http(address) => {
+            let listener = TcpListener::bind(address)
+                .map_err(|error| format!("Unable to listen on {address}: {error}"))?;
+            let address = listener.local_addr().map_err(|error| {
+                format!("Unable to determine the address of the server: {error}")
+            })?;
+            println!("Listening on http://{address}.");
+            Some(listener)
+        }
+        Transport::Stdio => None,
+    };
+
+    thread::scope(|scope| {
+        let (index, stopping, connections) = (&index, &stopping, &connections);
+        scope.spawn(move || refresh(scanner, index, cache, &receiver, stopping));
+        let Some(listener) = listener else {
+            // Stop watching once the input ends, so the refreshing thread finishes too.
+            let result = answer_lines(scanner, rules, index);
+            stopping.store(true, Ordering::Relaxed);
+            drop(watcher);
+            return result;
+        };
+        for stream in listener.incoming() {
+            let mut stream =
+                stream.map_err(|error| format!("Unable to accept a connection: {error}"))?;
+            if connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                connections.fetch_sub(1, Ordering::SeqCst);
+                if let Err(error) = reply(&mut stream, "503 Service Unavailable", "") {
+                    log::debug!("Dropped a connection: {error}");
+                }
+                continue;
+            }
+            scope.spawn(move || {
+                if let Err(error) = connect(scanner, rules, index, stream) {
+                    log::debug!("Dropped a connection: {error}");
+                }
+                connections.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+        Err("Stopped accepting connections.".to_owned())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::serve::{
+        MAX_BODY, MAX_HEADERS, METHOD_NOT_FOUND, REQUEST_FAILED, handle, is_local_host, position,
+        read_request, text_edits, validate,
+    };
+    use serde_json::{Value, json};
+    use std::{path::Path, sync::RwLock};
+    use tagref::{
+        directive::compile_directive_regex,
+        edits::Edit,
+        extractors,
+        index::{Index, Rules, Scanner},
+        walk,
+    };
+
+    fn scanner() -> Scanner {
+        Scanner {
+            paths: Vec::new(),
+            walk_options: walk::Options::default(),
+            tag_regex: compile_directive_regex("tag"),
+            tag_block_regex: None,
+            ref_regex: compile_directive_regex("ref"),
+            file_regex: compile_directive_regex("file"),
+            dir_regex: compile_directive_regex("dir"),
+            url_regex: compile_directive_regex("url"),
+            custom_regexes: Vec::new(),
+            comments_only: false,
+            archives: false,
+            extractors: extractors::defaults(),
+            fallback_encoding: None,
+            case_insensitive_labels: false,
+            relative_paths: false,
+            roots: Vec::new(),
+            memory_map: false,
+        }
+    }
+
+    // This function sends a request to a server whose index has one tag and one reference to it.
+    fn request(request: &Value) -> Option<Value> {
+        let scanner = scanner();
+        let mut index = Index::default();
+        let contents = "// [?tag:foo]\n// [?ref:foo]\n".replace('?', "");
+        index.insert(scanner.parse(Path::new("a.rs"), contents.as_bytes()));
+
+        handle(
+            &scanner,
+            &Rules::default(),
+            &RwLock::new(index),
+            request.to_string().as_bytes(),
+        )
+    }
+
+    #[test]
+    fn resolve() {
+        let response = request(&json!({
+            "jsonrpc": "2.0",
+            "id": 1_u64,
+            "method": "resolve",
+            "params": { "label": "foo" },
+        }))
+        .unwrap();
+
+        assert_eq!(response["id"], 1_u64);
+        assert_eq!(response["result"][0]["line_number"], 1_u64);
+    }
+
+    #[test]
+    fn validate_dangling_ref() {
+        let response = request(&json!({
+            "jsonrpc": "2.0",
+            "id": 1_u64,
+            "method": "validate",
+            "params": { "path": "b.rs", "contents": "// [?ref:bar]\n".replace('?', "") },
+        }))
+        .unwrap();
+
+        assert_eq!(response["result"].as_array().unwrap().len(), 1);
+        assert_eq!(response["result"][0]["kind"], "dangling_ref");
+    }
+
+    #[test]
+    fn validate_restores_index() {
+        let scanner = scanner();
+        let mut index = Index::default();
+        let contents = "// [?tag:foo]\n".replace('?', "");
+        index.insert(scanner.parse(Path::new("a.rs"), contents.as_bytes()));
+        let index = RwLock::new(index);
+
+        let violations = validate(
+            &scanner,
+            &Rules::default(),
+            &index,
+            Path::new("a.rs"),
+            &"// [?ref:bar]\n".replace('?', ""),
+        );
+
+        assert_eq!(violations.len(), 1);
+        let index = index.into_inner().unwrap();
+        assert_eq!(index.tags.keys().collect::<Vec<_>>(), ["foo"]);
+        assert!(index.refs.is_empty());
+    }
+
+    #[test]
+    fn diagnostics_unsaved() {
+        let contents = "x\n/* é */ // [?ref:bar]\n".replace('?', "");
+        let response = request(&json!({
+            "jsonrpc": "2.0",
+            "id": 1_u64,
+            "method": "diagnostics",
+            "params": { "path": "b.rs", "contents": contents },
+        }))
+        .unwrap();
+
+        assert_eq!(
+            response["result"],
+            json!([{
+                "path": "b.rs",
+                "range": {
+                    "start": { "line": 1_u64, "character": 11_u64 },
+                    "end": { "line": 1_u64, "character": 20_u64 },
+                },
+                "severity": "error",
+                "code": "dangling_ref",
+                "message": response["result"][0]["message"],
+            }]),
+        );
+    }
+
+    #[test]
+    fn rename_missing_tag() {
+        let response = request(&json!({
+            "jsonrpc": "2.0",
+            "id": 1_u64,
+            "method": "rename",
+            "params": { "label": "bar", "new_label": "baz" },
+        }))
+        .unwrap();
+
+        assert_eq!(response["error"]["code"], REQUEST_FAILED);
+    }
+
+    #[test]
+    fn text_edits_ranges() {
+        let contents = "a\r\n// é [?ref:foo]\n".replace('?', "");
+        let edit = Edit {
+            range: 14..17,
+            replacement: "bar".to_owned(),
+        };
+
+        assert_eq!(
+            text_edits(&contents, &[edit]),
+            vec![json!({
+                "range": {
+                    "start": { "line": 1_u64, "character": 10_u64 },
+                    "end": { "line": 1_u64, "character": 13_u64 },
+                },
+                "new_text": "bar",
+            })],
+        );
+    }
+
+    #[test]
+    fn unsupported_method() {
+        let response = request(&json!({ "jsonrpc": "2.0", "id": "a", "method": "frobnicate" }));
+
+        assert_eq!(response.unwrap()["error"]["code"], METHOD_NOT_FOUND);
+    }
+
+    #[test]
+    fn notification() {
+        assert_eq!(
+            request(&json!({ "jsonrpc": "2.0", "method": "check" })),
+            None
+        );
+    }
+
+    #[test]
+    fn read_request_body() {
+        let mut input =
+            "POST / HTTP/1.1\r\nHost: localhost:7377\r\nContent-Length: 2\r\n\r\n{}".as_bytes();
+
+        let (method, host, body) = read_request(&mut input).unwrap();
+
+        assert_eq!(method, "POST");
+        assert_eq!(host.as_deref(), Some("localhost:7377"));
+        assert_eq!(body, b"{}");
+    }
+
+    #[test]
+    fn read_request_too_large() {
+        let input = format!(
+            "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY + 1
+        );
+
+        assert!(read_request(&mut input.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn read_request_long_line() {
+        let input = format!("POST / HTTP/1.1\r\nHost: {}\r\n\r\n", "x".repeat(10_000));
+
+        assert!(read_request(&mut input.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn read_request_too_many_headers() {
+        let input = format!(
+            "POST / HTTP/1.1\r\n{}\r\n",
+            "X-Foo: bar\r\n".repeat(MAX_HEADERS + 1),
+        );
+
+        assert!(read_request(&mut input.as_bytes()).is_err());
+        assert!(read_request(&mut input.replacen("X-Foo: bar\r\n", "", 1).as_bytes()).is_ok(),);
+    }
+
+    #[test]
+    fn local_hosts() {
+        for host in [
+            "localhost",
+            "LOCALHOST:7377",
+            "127.0.0.1",
+            "127.0.0.1:7377",
+            "[::1]",
+            "[::1]:7377",
+            "192.168.0.2:7377",
+        ] {
+            assert!(is_local_host(host), "{host}");
+        }
+        for host in [
+            "example.com",
+            "example.com:7377",
+            "localhost.example.com",
+            "",
+        ] {
+            assert!(!is_local_host(host), "{host}");
+        }
+    }
+
+    #[test]
+    fn position_inside_character() {
+        // The offset falls inside the two bytes of `é`, so the column stops before it.
+        assert_eq!(
+            position(0, "é", 1),
+            json!({ "line": 0_usize, "character": 0_usize }),
+        );
+        assert_eq!(
+            position(0, "aé", 10),
+            json!({ "line": 0_usize, "character": 2_usize }),
+        );
+    }
+}
@@ -0,0 +1,381 @@
+use crate::{
+    cli::{Cli, RelativeTo, directive_regex, from_command_line, parse_timeout, sigil},
+    config::{self, Config},
+};
+use clap::ArgMatches;
+use std::{
+    collections::BTreeMap,
+    env,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tagref::{
+    baseline,
+    cache::Cache,
+    custom_labels,
+    directive::{self, compile_block_regex},
+    duplicates, encoding,
+    extractors::{self, Extractor},
+    git,
+    index::{Index, Rules, Scanner},
+    repositories,
+    walk::{self, PathStyle},
+};
+
+// This struct holds what the commands need to know: the command-line options, merged with the
+// configuration file, and the rules and scanner they determine.
+pub struct Settings {
+    pub cli: Cli,
+    pub matches: ArgMatches,
+    pub config: Config,
+    pub rules: Rules,
+    pub scanner: Scanner,
+    pub baseline_path: PathBuf,
+    pub baseline_required: bool,
+}
+
+impl Settings {
+    // This function loads the configuration file, if there is one, and merges it with the
+    // command-line options. Command-line flags take precedence over the configuration file.
+    pub fn load(mut cli: Cli, matches: ArgMatches) -> Result<Self, String> {
+        let config = match config::load(cli.config.as_deref())? {
+            Some((path, config)) => {
+                log::info!("Loaded configuration from {}.", path.to_string_lossy());
+                config
+            }
+            None => Config::default(),
+        };
+        merge(&mut cli, &matches, &config)?;
+
+        let (baseline_path, baseline_required) =
+            match cli.baseline.as_ref().or(config.baseline.as_ref()) {
+                Some(path) => (path.clone(), true),
+                None => (PathBuf::from(baseline::DEFAULT_PATH), false),
+            };
+        let rules = rules(&cli, &config);
+        let scanner = scanner(&cli, &matches, &config)?;
+
+        Ok(Self {
+            cli,
+            matches,
+            config,
+            rules,
+            scanner,
+            baseline_path,
+            baseline_required,
+        })
+    }
+
+    // This function determines the sigil for writing tags.
+    pub fn tag_sigil(&self) -> String {
+        sigil(
+            &self.matches,
+            "tag_sigil",
+            &self.cli.tag_sigil,
+            self.config.sigils.tag.as_deref(),
+        )
+    }
+
+    // This function determines the sigil for writing tag references.
+    pub fn ref_sigil(&self) -> String {
+        sigil(
+            &self.matches,
+            "ref_sigil",
+            &self.cli.ref_sigil,
+            self.config.sigils.r#ref.as_deref(),
+        )
+    }
+
+    // This function loads the tags of other repositories, for the commands which check references
+    // to them. Tag index files given on the command line take precedence over the configuration
+    // file, and unlike the repositories in the configuration file, they must be available.
+    pub fn load_external_tags(&mut self, externs: &[String]) -> Result<(), String> {
+        for external in externs {
+            let (alias, path) = external
+                .split_once(':')
+                .ok_or_else(|| format!("`{external}` is not of the form ALIAS:PATH."))?;
+            let alias = alias.trim();
+            let tags = repositories::read_index(Path::new(path)).map_err(|error| {
+                format!("Unable to load the tag index for the `{alias}` repository: {error}.")
+            })?;
+            self.rules
+                .external_tags
+                .insert(alias.to_owned(), Some(tags));
+        }
+
+        // The references to unavailable repositories are skipped rather than reported.
+        let sources = self
+            .config
+            .repositories
+            .iter()
+            .filter(|(alias, _)| !self.rules.external_tags.contains_key(*alias))
+            .map(|(alias, repository)| (alias.clone(), repository.clone()))
+            .collect();
+        for (alias, tags) in repositories::load(&sources, &self.scanner) {
+            let tags = tags
+                .inspect_err(|error| {
+                    log::warn!("Skipping references to the `{alias}` repository: {error}.");
+                })
+                .ok();
+            self.rules.external_tags.insert(alias, tags);
+        }
+
+        if self.scanner.case_insensitive_labels {
+            for tags in self.rules.external_tags.values_mut().flatten() {
+                *tags = tags
+                    .iter()
+                    .map(|tag| directive::key(tag, true).into_owned())
+                    .collect();
+            }
+        }
+
+        Ok(())
+    }
+
+    // This function returns the files tracked by Git, if only those should be referenced.
+    pub fn tracked(&self) -> Result<Option<Arc<git::Tree>>, String> {
+        if self.cli.tracked_only {
+            Ok(Some(Arc::new(git::Tree::tracked()?)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // This function scans the files, reusing the cache if enabled. The file and directory
+    // references are checked against the files tracked by Git, if requested, so references to
+    // files which were never committed are caught.
+    pub fn scan(&self) -> Result<Index, String> {
+        let cache = self
+            .cli
+            .cache
+            .as_ref()
+            .map(|path| Arc::new(Cache::load(path, &self.scanner.cache_key())));
+        let (mut index, new_cache) = self.scanner.scan(cache)?;
+        index.tree = self.tracked()?;
+
+        // Save the cache for the next run. Files which no longer exist are dropped from it.
+        if let (Some(path), Some(new_cache)) = (&self.cli.cache, new_cache) {
+            new_cache.save(path)?;
+        }
+
+        Ok(index)
+    }
+
+    // This function returns a function which reports paths as requested (see `--relative-to`).
+    // Only the working directory is needed to rewrite them.
+    pub fn report_path(&self) -> Result<impl Fn(&Path) -> PathBuf + '_, String> {
+        let path_style = match (self.cli.relative_to, self.cli.absolute_paths) {
+            (_, true) => PathStyle::Absolute,
+            (Some(RelativeTo::Root), false) => PathStyle::Root,
+            (Some(RelativeTo::Cwd), false) => PathStyle::WorkingDirectory,
+            (None, false) => PathStyle::Scanned,
+        };
+        let working_directory = if path_style == PathStyle::Scanned {
+            PathBuf::new()
+        } else {
+            env::current_dir()
+                .map_err(|error| format!("Unable to determine the working directory: {error}"))?
+        };
+
+        Ok(move |path: &Path| walk::report(path, path_style, &self.cli.paths, &working_directory))
+    }
+}
+
+// This function merges the configuration file into the command-line options, unless they were
+// given explicitly.
+fn merge(cli: &mut Cli, matches: &ArgMatches, config: &Config) -> Result<(), String> {
+    if !from_command_line(matches, "paths")
+        && let Some(paths) = &config.paths
+    {
+        cli.paths.clone_from(paths);
+    }
+    if !from_command_line(matches, "paths")
+        && !from_command_line(matches, "roots")
+        && let Some(roots) = &config.roots
+    {
+        cli.roots.clone_from(roots);
+    }
+    if !cli.roots.is_empty() {
+        cli.paths.clone_from(&cli.roots);
+    }
+    cli.isolate_roots |= config.isolate_roots.unwrap_or(false);
+    if !from_command_line(matches, "format")
+        && let Some(format) = config.format
+    {
+        cli.format = format;
+    }
+    cli.comments_only |= config.comments_only.unwrap_or(false);
+    cli.scan_archives |= config.scan_archives.unwrap_or(false);
+    if !cli.case_sensitive_labels {
+        cli.case_insensitive_labels |= config.case_insensitive_labels.unwrap_or(false);
+    }
+    if !cli.no_relative_paths {
+        cli.relative_paths |= config.relative_paths.unwrap_or(false);
+    }
+    if cli.encoding.is_none() {
+        cli.encoding.clone_from(&config.encoding);
+    }
+    cli.check_urls |= config.check_urls.unwrap_or(false);
+    cli.check_issues |= config.check_issues.unwrap_or(false);
+    cli.tracked_only |= config.tracked_only.unwrap_or(false);
+    cli.no_ignore |= config.no_ignore.unwrap_or(false);
+    if !cli.no_follow_symlinks {
+        cli.follow_symlinks |= config.follow_symlinks.unwrap_or(false);
+    }
+    if cli.cache.is_none() {
+        cli.cache.clone_from(&config.cache);
+    }
+    if cli.max_filesize.is_none()
+        && let Some(max_filesize) = &config.max_filesize
+    {
+        cli.max_filesize = Some(walk::parse_size(max_filesize)?);
+    }
+    if cli.timeout.is_none()
+        && let Some(timeout) = &config.timeout
+    {
+        cli.timeout = Some(parse_timeout(timeout)?);
+    }
+
+    Ok(())
+}
+
+// This function determines the rules to check from the configuration file and the command-line
+// options.
+fn rules(cli: &Cli, config: &Config) -> Rules {
+    let mut rules = Rules {
+        duplicates: config.duplicates.clone(),
+        naming: config.naming.clone(),
+        namespaces: config.namespaces.clone(),
+        pairings: config.pairings.clone(),
+        deny_unused: config.deny_unused.unwrap_or(false),
+        allow_unused: config.allow_unused.clone(),
+        min_refs: config.min_refs.clone(),
+        deny_empty_dirs: config.deny_empty_dirs.unwrap_or(false),
+        strict_path_case: config.strict_path_case.unwrap_or(false),
+        markdown_links: config.markdown_links.unwrap_or(false),
+        docs: config.docs_dir.clone(),
+        custom_labels: config.custom_labels.clone(),
+        aliases: config.aliases.clone(),
+        deny_deprecated_aliases: config.deny_deprecated_aliases.unwrap_or(false),
+        severities: config.severities.clone(),
+        isolate_roots: cli.isolate_roots,
+        scopes: config.scopes.clone(),
+        external_tags: BTreeMap::new(),
+        validators: config.validators.clone(),
+    };
+    if cli.allow_duplicates {
+        // This goes last so it takes precedence over any rule in the configuration file which
+        // applies to all labels.
+        rules.duplicates.push(duplicates::Rule {
+            policy: duplicates::Policy::Warn,
+            ..duplicates::Rule::default()
+        });
+    }
+
+    rules
+}
+
+// This function sets up the scanner, compiling the regular expressions in advance.
+fn scanner(cli: &Cli, matches: &ArgMatches, config: &Config) -> Result<Scanner, String> {
+    // The cache and baseline files are skipped wherever they're kept, as they are at their default
+    // paths. Paths outside the working directory can't be matched, but then they're unlikely to be
+    // scanned anyway.
+    let mut exclude = config.exclude.clone();
+    exclude.extend(
+        [
+            cli.cache.as_deref(),
+            cli.baseline.as_deref().or(config.baseline.as_deref()),
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|path| path.is_relative())
+        .map(|path| {
+            format!(
+                "/{}",
+                globset::escape(&walk::relative(path).to_string_lossy())
+            )
+        }),
+    );
+
+    let walk_options = walk::Options {
+        include: config.include.clone(),
+        exclude,
+        no_ignore: cli.no_ignore,
+        follow_symlinks: cli.follow_symlinks,
+        drop_ins: Arc::default(),
+
+        // A limit of zero means there's no limit.
+        max_filesize: Some(cli.max_filesize.unwrap_or(walk::DEFAULT_MAX_FILESIZE))
+            .filter(|max_filesize| *max_filesize > 0),
+    };
+
+    // Block tags are supported with a sigil, but not with a custom regular expression for tags.
+    let tag_block_regex = if from_command_line(matches, "tag_sigil") {
+        Some(compile_block_regex(&cli.tag_sigil))
+    } else if config.regexes.tag.is_none() {
+        Some(compile_block_regex(
+            config.sigils.tag.as_deref().unwrap_or(&cli.tag_sigil),
+        ))
+    } else {
+        None
+    };
+
+    // The extractors in the configuration file come first, so they can take over files from the
+    // built-in ones.
+    let mut extractors = config
+        .extractors
+        .iter()
+        .map(|extractor| Arc::new(extractor.clone()) as Arc<dyn Extractor>)
+        .collect::<Vec<_>>();
+    extractors.extend(extractors::defaults());
+
+    Ok(Scanner {
+        paths: cli.paths.clone(),
+        walk_options,
+        tag_regex: directive_regex(
+            matches,
+            "tag_sigil",
+            &cli.tag_sigil,
+            config.sigils.tag.as_deref(),
+            config.regexes.tag.as_deref(),
+        )?,
+        tag_block_regex,
+        ref_regex: directive_regex(
+            matches,
+            "ref_sigil",
+            &cli.ref_sigil,
+            config.sigils.r#ref.as_deref(),
+            config.regexes.r#ref.as_deref(),
+        )?,
+        file_regex: directive_regex(
+            matches,
+            "file_sigil",
+            &cli.file_sigil,
+            config.sigils.file.as_deref(),
+            config.regexes.file.as_deref(),
+        )?,
+        dir_regex: directive_regex(
+            matches,
+            "dir_sigil",
+            &cli.dir_sigil,
+            config.sigils.dir.as_deref(),
+            config.regexes.dir.as_deref(),
+        )?,
+        url_regex: directive_regex(
+            matches,
+            "url_sigil",
+            &cli.url_sigil,
+            config.sigils.url.as_deref(),
+            config.regexes.url.as_deref(),
+        )?,
+        custom_regexes: custom_labels::regexes(&config.custom_labels)?,
+        comments_only: cli.comments_only,
+        archives: cli.scan_archives,
+        extractors,
+        case_insensitive_labels: cli.case_insensitive_labels,
+        relative_paths: cli.relative_paths,
+        roots: cli.roots.clone(),
+        memory_map: true,
+        fallback_encoding: cli.encoding.as_deref().map(encoding::lookup).transpose()?,
+    })
+}
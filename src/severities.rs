@@ -0,0 +1,186 @@
+use crate::{
+    violation::{Kind, Severity, Violation},
+    walk,
+};
+use globset::{GlobBuilder, GlobMatcher};
+use serde::{Deserialize, Deserializer, de::Error};
+use std::collections::BTreeMap;
+
+// This struct wraps a glob so it can be read from a configuration file and compared. As in
+// directory references, `*` doesn't match across directories, but `**` does.
+#[derive(Clone, Debug)]
+pub struct PathGlob(pub GlobMatcher);
+
+impl PartialEq for PathGlob {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.glob() == other.0.glob()
+    }
+}
+
+impl Eq for PathGlob {}
+
+impl<'de> Deserialize<'de> for PathGlob {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let glob = String::deserialize(deserializer)?;
+        GlobBuilder::new(&glob)
+            .literal_separator(true)
+            .build()
+            .map(|glob| Self(glob.compile_matcher()))
+            .map_err(|error| D::Error::custom(format!("Invalid glob `{glob}`: {error}")))
+    }
+}
+
+// This struct overrides the severity of the violations of a kind. If several rules apply to a
+// violation, the last one wins, so later rules can override earlier ones.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Rule {
+    pub kind: Kind,
+    pub severity: Severity,
+
+    // If nonempty, the rule only applies to violations involving labels in files matching these
+    // globs (relative to the working directory).
+    #[serde(default)]
+    pub paths: Vec<PathGlob>,
+
+    // If nonempty, the rule only applies to violations involving labels with all these attributes.
+    #[serde(default)]
+    pub attributes: BTreeMap<String, String>,
+}
+
+impl Rule {
+    // This function determines whether the rule applies to a violation.
+    fn applies(&self, violation: &Violation) -> bool {
+        self.kind == violation.kind
+            && (self.paths.is_empty()
+                || violation.directives.iter().any(|directive| {
+                    let path = walk::relative(&directive.path);
+                    self.paths.iter().any(|PathGlob(glob)| glob.is_match(&path))
+                }))
+            && (self.attributes.is_empty()
+                || violation.directives.iter().any(|directive| {
+                    self.attributes
+                        .iter()
+                        .all(|(key, value)| directive.attributes.get(key) == Some(value))
+                }))
+    }
+}
+
+// This function sets the severities of the violations according to the rules.
+pub fn apply(rules: &[Rule], violations: &mut [Violation]) {
+    for violation in violations {
+        if let Some(rule) = rules.iter().rev().find(|rule| rule.applies(violation)) {
+            violation.severity = rule.severity;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::{Directive, Type},
+        severities::{Rule, apply},
+        violation::{Kind, Severity, Violation},
+    };
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    struct Rules {
+        severities: Vec<Rule>,
+    }
+
+    fn rules(contents: &str) -> Vec<Rule> {
+        toml::from_str::<Rules>(contents).unwrap().severities
+    }
+
+    fn violation(kind: Kind, path: &str) -> Violation {
+        Violation {
+            kind,
+            severity: Severity::Error,
+            message: String::new(),
+            directives: vec![Directive::new(Type::Ref, "foo", path, 1)],
+            suggestions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn apply_by_kind() {
+        let rules = rules(
+            r#"
+[[severities]]
+kind = "unused_tag"
+severity = "warn"
+"#,
+        );
+        let mut violations = [
+            violation(Kind::UnusedTag, "./src/main.rs"),
+            violation(Kind::DanglingRef, "./src/main.rs"),
+        ];
+
+        apply(&rules, &mut violations);
+
+        assert_eq!(violations[0].severity, Severity::Warning);
+        assert_eq!(violations[1].severity, Severity::Error);
+    }
+
+    #[test]
+    fn apply_by_path() {
+        let rules = rules(
+            r#"
+[[severities]]
+kind = "dangling_ref"
+severity = "warning"
+
+[[severities]]
+kind = "dangling_ref"
+severity = "info"
+paths = ["docs/**"]
+"#,
+        );
+        let mut violations = [
+            violation(Kind::DanglingRef, "./docs/guide/intro.md"),
+            violation(Kind::DanglingRef, "./src/main.rs"),
+        ];
+
+        apply(&rules, &mut violations);
+
+        assert_eq!(violations[0].severity, Severity::Info);
+        assert_eq!(violations[1].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn apply_by_attributes() {
+        let rules = rules(
+            r#"
+[[severities]]
+kind = "unused_tag"
+severity = "error"
+attributes = { severity = "high" }
+"#,
+        );
+        let mut violations = [
+            violation(Kind::UnusedTag, "./src/main.rs"),
+            violation(Kind::UnusedTag, "./src/main.rs"),
+        ];
+        violations[0].severity = Severity::Warning;
+        violations[1].severity = Severity::Warning;
+        violations[1].directives[0]
+            .attributes
+            .insert("severity".to_owned(), "high".to_owned());
+
+        apply(&rules, &mut violations);
+
+        assert_eq!(violations[0].severity, Severity::Warning);
+        assert_eq!(violations[1].severity, Severity::Error);
+    }
+
+    #[test]
+    fn parse_invalid_glob() {
+        assert!(
+            toml::from_str::<Rules>(
+                "[[severities]]\nkind = \"unused_tag\"\nseverity = \"info\"\npaths = [\"[\"]\n",
+            )
+            .is_err(),
+        );
+    }
+}
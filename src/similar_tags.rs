@@ -0,0 +1,165 @@
+use crate::{directive::Directive, levenshtein::distance};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+// Labels with fewer characters than this (after normalization) are only compared by case and
+// separators, since short labels like `cpu` and `gpu` are often a single edit apart on purpose.
+const MIN_LENGTH: usize = 5;
+
+// This enum describes how two labels differ.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Difference {
+    // The labels only differ by case, e.g., `Foo` and `foo`.
+    Case,
+
+    // The labels only differ by case, hyphens, and underscores, e.g., `foo-bar` and `foo_bar`.
+    Separators,
+
+    // The labels differ by a few insertions, deletions, or substitutions, e.g., `migration` and
+    // `migrations`.
+    Spelling,
+}
+
+// This struct represents two tags whose labels are so similar that one is probably a typo of the
+// other.
+#[derive(Clone, Debug, Serialize)]
+pub struct Pair {
+    pub first: Directive,
+    pub second: Directive,
+    pub difference: Difference,
+
+    // The number of edits between the labels, ignoring case, hyphens, and underscores
+    pub distance: usize,
+}
+
+// This function normalizes a label so labels which only differ by case, hyphens, and underscores
+// become equal.
+fn normalize(label: &str) -> Vec<char> {
+    label
+        .chars()
+        .flat_map(char::to_lowercase)
+        .map(|c| if c == '_' { '-' } else { c })
+        .collect()
+}
+
+// This function returns the digits in a label. Labels with different numbers in them (e.g.,
+// `step-1` and `step-2`) are usually distinct on purpose, so they aren't compared by spelling.
+fn digits(label: &[char]) -> Vec<char> {
+    label.iter().copied().filter(char::is_ascii_digit).collect()
+}
+
+// This function finds the pairs of tags whose labels only differ by case, hyphens, and
+// underscores, or by at most `max_distance` edits otherwise. The tags are keyed as in the index,
+// and duplicates of a tag are represented by the first one.
+#[must_use]
+pub fn find(tags_map: &BTreeMap<String, Vec<Directive>>, max_distance: usize) -> Vec<Pair> {
+    let tags = tags_map
+        .values()
+        .filter_map(|tags| tags.first())
+        .map(|tag| (tag, normalize(&tag.label)))
+        .collect::<Vec<_>>();
+
+    let mut pairs = Vec::new();
+    for (i, (first, first_normalized)) in tags.iter().enumerate() {
+        for (second, second_normalized) in &tags[i + 1..] {
+            let difference = if first.label.to_lowercase() == second.label.to_lowercase() {
+                Some((Difference::Case, 0))
+            } else if first_normalized == second_normalized {
+                Some((Difference::Separators, 0))
+            } else if first_normalized.len().min(second_normalized.len()) >= MIN_LENGTH
+                && digits(first_normalized) == digits(second_normalized)
+            {
+                distance(first_normalized, second_normalized, max_distance)
+                    .map(|distance| (Difference::Spelling, distance))
+            } else {
+                None
+            };
+
+            if let Some((difference, distance)) = difference {
+                pairs.push(Pair {
+                    first: (*first).clone(),
+                    second: (*second).clone(),
+                    difference,
+                    distance,
+                });
+            }
+        }
+    }
+
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::{Directive, Type},
+        similar_tags::{Difference, find},
+    };
+    use std::collections::BTreeMap;
+
+    fn tags_map(labels: &[&str]) -> BTreeMap<String, Vec<Directive>> {
+        labels
+            .iter()
+            .map(|label| {
+                (
+                    (*label).to_owned(),
+                    vec![Directive::new(Type::Tag, label, "./foo.rs", 1)],
+                )
+            })
+            .collect()
+    }
+
+    // This function returns the labels and differences of the pairs found.
+    fn pairs(labels: &[&str], max_distance: usize) -> Vec<(String, String, Difference)> {
+        find(&tags_map(labels), max_distance)
+            .into_iter()
+            .map(|pair| (pair.first.label, pair.second.label, pair.difference))
+            .collect()
+    }
+
+    #[test]
+    fn find_case() {
+        assert_eq!(
+            pairs(&["Cache-Key", "cache-key", "other"], 1),
+            vec![(
+                "Cache-Key".to_owned(),
+                "cache-key".to_owned(),
+                Difference::Case,
+            )],
+        );
+    }
+
+    #[test]
+    fn find_separators() {
+        assert_eq!(
+            pairs(&["db-migration", "db_Migration"], 0),
+            vec![(
+                "db-migration".to_owned(),
+                "db_Migration".to_owned(),
+                Difference::Separators,
+            )],
+        );
+    }
+
+    #[test]
+    fn find_spelling() {
+        let pairs = find(&tags_map(&["db-migration", "db_migrations", "queue"]), 1);
+
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].difference, Difference::Spelling);
+        assert_eq!(pairs[0].distance, 1);
+        assert!(self::pairs(&["db-migration", "db_migrations"], 0).is_empty());
+    }
+
+    #[test]
+    fn find_ignores_short_and_numbered() {
+        assert!(
+            pairs(
+                &["cpu", "gpu", "step-1", "step-2", "rfc-7230", "rfc-7231"],
+                2
+            )
+            .is_empty()
+        );
+    }
+}
@@ -0,0 +1,399 @@
+use crate::{
+    directive::Directive,
+    index::{Index, Summary},
+    namespaces, unused, walk,
+};
+use serde::Serialize;
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Component, Path, PathBuf},
+};
+
+// This struct records how many tags have a certain number of references.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct Bucket {
+    pub references: usize,
+    pub tags: usize,
+}
+
+// This struct records how many times a tag is referenced.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct TagCount {
+    pub label: String,
+    pub references: usize,
+}
+
+// This struct records how many labels a file contains.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct FileCount {
+    pub path: PathBuf,
+    pub labels: usize,
+}
+
+// This struct holds aggregate metrics about the labels in an index, for tracking how the
+// cross-references in a codebase evolve over time.
+#[derive(Clone, Debug, Serialize)]
+pub struct Stats {
+    #[serde(flatten)]
+    pub summary: Summary,
+
+    // The number of distinct files and directories referenced
+    pub files_referenced: usize,
+    pub directories_referenced: usize,
+
+    // The number of tags with each number of references, in order of the number of references
+    pub references_per_tag: Vec<Bucket>,
+
+    // The most referenced tags, most referenced first
+    pub top_tags: Vec<TagCount>,
+
+    // The files containing the most labels of any type, with the most labels first
+    pub top_files: Vec<FileCount>,
+
+    // The labels of the tags which aren't referenced anywhere, except for the allowed ones
+    pub unused_tags: Vec<String>,
+}
+
+impl Stats {
+    // This function computes the metrics for an index. The `top` most referenced tags and files
+    // with the most labels are included. Ties are broken alphabetically, so the output is stable.
+    #[must_use]
+    pub fn new(index: &Index, allow_unused: &[String], top: usize) -> Self {
+        // Count the references to each tag. Dangling references aren't counted.
+        let mut references = index
+            .tags
+            .keys()
+            .map(|key| (key.as_str(), 0))
+            .collect::<BTreeMap<_, usize>>();
+        for r#ref in &index.refs {
+            if let Some(count) = references.get_mut(&*index.key(&r#ref.label)) {
+                *count += 1;
+            }
+        }
+
+        let mut buckets = BTreeMap::<usize, usize>::new();
+        for count in references.values() {
+            *buckets.entry(*count).or_default() += 1;
+        }
+
+        let mut top_tags = references
+            .iter()
+            .filter(|(_, count)| **count > 0)
+            .map(|(key, count)| TagCount {
+                // Tags are shown as they're written, which matters if labels are case-insensitive.
+                label: index.tags[*key][0].label.clone(),
+                references: *count,
+            })
+            .collect::<Vec<_>>();
+        top_tags.sort_by_key(|tag| Reverse(tag.references));
+        top_tags.truncate(top);
+
+        // Count the labels in each file.
+        let mut labels = HashMap::<&Path, usize>::new();
+        for directive in index.labels() {
+            *labels.entry(&*directive.path).or_default() += 1;
+        }
+        let mut top_files = labels
+            .into_iter()
+            .map(|(path, labels)| FileCount {
+                path: path.to_path_buf(),
+                labels,
+            })
+            .collect::<Vec<_>>();
+        top_files.sort_by(|a, b| b.labels.cmp(&a.labels).then_with(|| a.path.cmp(&b.path)));
+        top_files.truncate(top);
+
+        // Count the distinct paths referenced by each type of reference.
+        let distinct = |directives: &[Directive]| {
+            directives
+                .iter()
+                .map(|directive| directive.label.as_str())
+                .collect::<HashSet<_>>()
+                .len()
+        };
+
+        let mut unused_tags = unused::find(
+            &index.tags,
+            &index.refs,
+            allow_unused,
+            index.case_insensitive_labels,
+        )
+        .into_iter()
+        .map(|tag| tag.label.clone())
+        .collect::<Vec<_>>();
+        unused_tags.dedup();
+
+        Self {
+            summary: index.summary(),
+            files_referenced: distinct(&index.files),
+            directories_referenced: distinct(&index.dirs),
+            references_per_tag: buckets
+                .into_iter()
+                .map(|(references, tags)| Bucket { references, tags })
+                .collect(),
+            top_tags,
+            top_files,
+            unused_tags,
+        }
+    }
+}
+
+// This enum represents the ways the labels can be grouped in a breakdown.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Grouping {
+    // By the top-level directory of the files containing them
+    Directory,
+
+    // By the extension of the files containing them
+    Extension,
+
+    // By the namespace of the tags and tag references (other labels don't have namespaces)
+    Namespace,
+}
+
+// This enum represents the orders in which the groups of a breakdown can be listed. Counts are
+// listed from highest to lowest, with ties broken by name.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Order {
+    Name,
+    Tags,
+    Refs,
+    Labels,
+    Files,
+}
+
+// This is the name of the group for labels which don't belong to any group, e.g., tags which
+// aren't in a namespace.
+pub const UNGROUPED: &str = "(none)";
+
+// This struct records how many labels are in a group.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct Group {
+    pub name: String,
+    pub tags: usize,
+    pub refs: usize,
+
+    // The number of labels of any type, including tags and tag references
+    pub labels: usize,
+
+    // The number of distinct files containing the labels
+    pub files: usize,
+}
+
+// This function determines which group a file belongs to, for the groupings based on files.
+fn file_group(path: &Path, grouping: Grouping) -> String {
+    let path = walk::relative(path);
+    match grouping {
+        Grouping::Directory => {
+            let mut components = path.components();
+            match (components.next(), components.next()) {
+                (Some(Component::Normal(directory)), Some(_)) => {
+                    directory.to_string_lossy().into_owned()
+                }
+                _ => ".".to_owned(),
+            }
+        }
+        Grouping::Extension | Grouping::Namespace => path.extension().map_or_else(
+            || UNGROUPED.to_owned(),
+            |extension| format!(".{}", extension.to_string_lossy()),
+        ),
+    }
+}
+
+// This function counts the labels in each group, so it's easy to see where they're concentrated.
+#[must_use]
+pub fn breakdown(index: &Index, grouping: Grouping, order: Order) -> Vec<Group> {
+    let mut groups = BTreeMap::<String, Group>::new();
+    let mut files = HashSet::<(String, &Path)>::new();
+    let tags = index.tags.values().flatten();
+    let others = index
+        .files
+        .iter()
+        .chain(&index.dirs)
+        .chain(&index.urls)
+        .chain(&index.custom);
+    let directives = tags
+        .clone()
+        .map(|tag| (tag, true, false))
+        .chain(index.refs.iter().map(|r#ref| (r#ref, false, true)))
+        .chain(others.map(|other| (other, false, false)));
+
+    for (directive, is_tag, is_ref) in directives {
+        let name = if grouping == Grouping::Namespace {
+            if !is_tag && !is_ref {
+                continue;
+            }
+            namespaces::namespace(&directive.label)
+                .unwrap_or(UNGROUPED)
+                .to_owned()
+        } else {
+            file_group(&directive.path, grouping)
+        };
+
+        let group = groups.entry(name.clone()).or_insert_with(|| Group {
+            name: name.clone(),
+            ..Group::default()
+        });
+        group.tags += usize::from(is_tag);
+        group.refs += usize::from(is_ref);
+        group.labels += 1;
+        if files.insert((name, &*directive.path)) {
+            group.files += 1;
+        }
+    }
+
+    let mut groups = groups.into_values().collect::<Vec<_>>();
+    match order {
+        Order::Name => {}
+        Order::Tags => groups.sort_by_key(|group| Reverse(group.tags)),
+        Order::Refs => groups.sort_by_key(|group| Reverse(group.refs)),
+        Order::Labels => groups.sort_by_key(|group| Reverse(group.labels)),
+        Order::Files => groups.sort_by_key(|group| Reverse(group.files)),
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::{Directive, Type},
+        index::Index,
+        stats::{Bucket, Grouping, Order, Stats, breakdown},
+    };
+    use std::path::PathBuf;
+
+    fn index() -> Index {
+        let mut index = Index::default();
+        for label in ["foo", "bar", "baz", "legacy"] {
+            index.tags.insert(
+                label.to_owned(),
+                vec![Directive::new(Type::Tag, label, "./a.rs", 1)],
+            );
+        }
+        index.refs = vec![
+            Directive::new(Type::Ref, "foo", "./b.rs", 1),
+            Directive::new(Type::Ref, "foo", "./b.rs", 1),
+            Directive::new(Type::Ref, "bar", "./c.rs", 1),
+            Directive::new(Type::Ref, "qux", "./c.rs", 1),
+        ];
+        index.files = vec![
+            Directive::new(Type::File, "a.rs", "./b.rs", 1),
+            Directive::new(Type::File, "a.rs", "./c.rs", 1),
+        ];
+        index
+    }
+
+    #[test]
+    fn new_references_per_tag() {
+        let stats = Stats::new(&index(), &[], 10);
+
+        assert_eq!(
+            stats.references_per_tag,
+            vec![
+                Bucket {
+                    references: 0,
+                    tags: 2,
+                },
+                Bucket {
+                    references: 1,
+                    tags: 1,
+                },
+                Bucket {
+                    references: 2,
+                    tags: 1,
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn new_top() {
+        let stats = Stats::new(&index(), &[], 2);
+
+        assert_eq!(
+            stats
+                .top_tags
+                .iter()
+                .map(|tag| (tag.label.as_str(), tag.references))
+                .collect::<Vec<_>>(),
+            vec![("foo", 2), ("bar", 1)],
+        );
+        assert_eq!(
+            stats
+                .top_files
+                .iter()
+                .map(|file| (file.path.clone(), file.labels))
+                .collect::<Vec<_>>(),
+            vec![(PathBuf::from("./a.rs"), 4), (PathBuf::from("./b.rs"), 3)],
+        );
+    }
+
+    #[test]
+    fn new_unused() {
+        let stats = Stats::new(&index(), &["legacy".to_owned()], 10);
+
+        assert_eq!(stats.unused_tags, vec!["baz".to_owned()]);
+        assert_eq!(stats.files_referenced, 1);
+        assert_eq!(stats.directories_referenced, 0);
+    }
+
+    fn groups(grouping: Grouping, order: Order) -> Vec<(String, usize, usize, usize, usize)> {
+        let mut index = index();
+        index.tags.insert(
+            "auth/token".to_owned(),
+            vec![Directive::new(
+                Type::Tag,
+                "auth/token",
+                "./src/auth/token.rs",
+                1,
+            )],
+        );
+        index
+            .refs
+            .push(Directive::new(Type::Ref, "auth/token", "./src/main.rs", 1));
+
+        breakdown(&index, grouping, order)
+            .into_iter()
+            .map(|group| {
+                (
+                    group.name,
+                    group.tags,
+                    group.refs,
+                    group.labels,
+                    group.files,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn breakdown_directory() {
+        assert_eq!(
+            groups(Grouping::Directory, Order::Name),
+            vec![
+                (".".to_owned(), 4, 4, 10, 3),
+                ("src".to_owned(), 1, 1, 2, 2),
+            ],
+        );
+    }
+
+    #[test]
+    fn breakdown_extension() {
+        assert_eq!(
+            groups(Grouping::Extension, Order::Labels),
+            vec![(".rs".to_owned(), 5, 5, 12, 5)],
+        );
+    }
+
+    #[test]
+    fn breakdown_namespace() {
+        assert_eq!(
+            groups(Grouping::Namespace, Order::Refs),
+            vec![
+                ("(none)".to_owned(), 4, 4, 8, 3),
+                ("auth".to_owned(), 1, 1, 2, 2),
+            ],
+        );
+    }
+}
@@ -0,0 +1,329 @@
+use crate::{
+    aliases, count,
+    directive::{Directive, Directives},
+    git::Tree,
+    index::{Index, Rules, Scanner, Summary},
+    mapped, min_refs, pairings,
+    violation::Violation,
+    walk,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    mem::take,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+// This struct is the result of a streaming check. The index only holds the tags, aliases, and
+// suppression comments, along with the few references which the checks on the tags needed.
+#[derive(Debug)]
+pub struct Checked {
+    pub index: Index,
+    pub violations: Vec<Violation>,
+    pub summary: Summary,
+}
+
+// This struct accumulates the results of the second pass, which runs on several threads.
+#[derive(Default)]
+struct State {
+    violations: Vec<Violation>,
+
+    // The references which are kept for the checks on the tags, and how many were kept for each
+    // tag, overall and within each of the paired paths (by position in `Rules::pairings`). The
+    // references in the documentation directory are all kept, since they may be in headings.
+    refs: Vec<Directive>,
+    kept: HashMap<(String, Option<usize>), usize>,
+
+    tag_references: usize,
+    file_references: usize,
+    directory_references: usize,
+    url_references: usize,
+    custom_labels: usize,
+}
+
+impl State {
+    // This function determines whether a reference (with any alias resolved) is needed for the
+    // checks on the tags, and keeps it if so. The unused tag check (if enabled) needs one reference
+    // to each tag, the minimum reference check needs as many as the minimum, and the pairing check
+    // needs one from each of the paired paths.
+    fn keep(&mut self, index: &Index, rules: &Rules, r#ref: &Directive) {
+        let key = index.key(&r#ref.label);
+        let Some(tags) = index.tags.get(&*key) else {
+            return;
+        };
+
+        let minimum = min_refs::minimum(&key, tags, &rules.min_refs, index.case_insensitive_labels);
+        let overall = (key.clone().into_owned(), None);
+        let needed = minimum.unwrap_or(0).max(usize::from(rules.deny_unused));
+        let mut keep = self.kept.get(&overall).copied().unwrap_or(0) < needed
+            || rules
+                .docs
+                .as_ref()
+                .is_some_and(|docs| pairings::within(r#ref, docs));
+        for (i, path) in rules
+            .pairings
+            .iter()
+            .flat_map(|rule| [&rule.first, &rule.second])
+            .enumerate()
+        {
+            let within = (key.clone().into_owned(), Some(i));
+            if pairings::within(r#ref, path) && !self.kept.contains_key(&within) {
+                self.kept.insert(within, 1);
+                keep = true;
+            }
+        }
+
+        if keep {
+            *self.kept.entry(overall).or_default() += 1;
+            self.refs.push(r#ref.clone());
+        }
+    }
+}
+
+// This function collects the tags, aliases, and suppression comments in the files, dropping the
+// other labels as soon as each file is parsed.
+fn collect_tags(scanner: &Scanner) -> Result<Index, String> {
+    let parsed_files = Arc::new(Mutex::new(Vec::new()));
+    let parsed_files_clone = parsed_files.clone();
+    let scanner_clone = scanner.clone();
+
+    walk::walk(
+        &scanner.paths,
+        &scanner.walk_options,
+        move |file_path, file| {
+            let directives =
+                scanner_clone.parse(file_path, &mapped::read(file, scanner_clone.memory_map));
+
+            // The `unwrap` is safe assuming no poisoning.
+            parsed_files_clone.lock().unwrap().push((
+                file_path.to_owned(),
+                Directives {
+                    tags: directives.tags,
+                    aliases: directives.aliases,
+                    suppressions: directives.suppressions,
+                    headings: directives.headings,
+                    too_large: directives.too_large,
+                    ..Directives::default()
+                },
+            ));
+        },
+    )?;
+
+    // Merge the tags into the index in a deterministic order, as `Scanner::scan` does. The
+    // `unwrap` is safe assuming no poisoning.
+    let mut parsed_files = take(&mut *parsed_files.lock().unwrap());
+    parsed_files.sort_by(|(x, _), (y, _)| x.cmp(y));
+    let mut index = Index {
+        case_insensitive_labels: scanner.case_insensitive_labels,
+        relative_paths: scanner.relative_paths,
+        roots: scanner.roots.clone(),
+        drop_ins: scanner.walk_options.drop_ins.clone(),
+        ..Index::default()
+    };
+    for (_, directives) in parsed_files {
+        index.insert(directives);
+    }
+    Ok(index)
+}
+
+// This function checks the files in two passes, so the labels don't all have to fit in memory at
+// once. The first pass collects the tags. The second pass parses each file again and checks the
+// labels in it against the tags, keeping only the references which the checks on the tags need.
+// The violations are the same as those from `Index::check`, except that isolated roots and scopes
+// aren't supported. If a Git tree is given, file and directory references are checked against it.
+pub fn check(scanner: &Scanner, rules: &Rules, tree: Option<Arc<Tree>>) -> Result<Checked, String> {
+    if rules.isolate_roots || !rules.scopes.is_empty() {
+        return Err("Streaming checks don't support isolated roots or scopes.".to_owned());
+    }
+
+    let start = Instant::now();
+    scanner.walk_options.drop_ins.clear();
+    let index = Arc::new(collect_tags(scanner)?);
+    log::info!(
+        "Collected {} in {:.2?}.",
+        count::count(index.tags.len(), "tag"),
+        start.elapsed(),
+    );
+
+    // The tags and aliases are shared by the threads of the second pass.
+    let tags = Arc::new(index.tags.keys().cloned().collect::<HashSet<_>>());
+    let aliases = Arc::new(index.resolve_aliases(rules));
+    let state = Arc::new(Mutex::new(State::default()));
+    let state_clone = state.clone();
+    let index_clone = index.clone();
+    let rules_clone = Arc::new(rules.clone());
+    let scanner_clone = scanner.clone();
+
+    let walked = walk::walk(
+        &scanner.paths,
+        &scanner.walk_options,
+        move |file_path, file| {
+            let directives =
+                scanner_clone.parse(file_path, &mapped::read(file, scanner_clone.memory_map));
+            let file = Index {
+                refs: directives.refs,
+                files: directives.files,
+                dirs: directives.dirs,
+                urls: directives.urls,
+                custom: directives.custom,
+                undecodable: directives.undecodable,
+                anchors: directives.anchors,
+                pins: directives.pins,
+                links: directives.links,
+                case_insensitive_labels: index_clone.case_insensitive_labels,
+                relative_paths: index_clone.relative_paths,
+                roots: index_clone.roots.clone(),
+                drop_ins: index_clone.drop_ins.clone(),
+                tree: tree.clone(),
+                ..Index::default()
+            };
+            let refs = aliases::apply(&file.refs, &aliases, file.case_insensitive_labels);
+            let violations =
+                file.check_labels(&rules_clone, &index_clone.tags, &tags, &aliases, &refs);
+
+            // The `unwrap` is safe assuming no poisoning.
+            let mut state = state_clone.lock().unwrap();
+            state.violations.extend(violations);
+            for r#ref in refs.iter() {
+                state.keep(&index_clone, &rules_clone, r#ref);
+            }
+            state.tag_references += file.refs.len();
+            state.file_references += file.files.len();
+            state.directory_references += file.dirs.len();
+            state.url_references += file.urls.len();
+            state.custom_labels += file.custom.len();
+        },
+    )?;
+
+    // The `unwrap`s are safe, since the threads are done with the index and the state, and
+    // assuming no poisoning.
+    let mut index = Arc::into_inner(index).unwrap();
+    let mut state = take(&mut *state.lock().unwrap());
+
+    // Check the tags against the references which were kept.
+    state.refs.sort_by(Directive::cmp_position);
+    let mut violations = index.check_tags(rules, &state.refs);
+    violations.extend(state.violations);
+    index.finish(rules, &mut violations);
+    index.refs = state.refs;
+    index.files_scanned = walked.files_scanned;
+    index.files_too_large.extend(walked.files_too_large);
+    index.files_too_large.sort();
+
+    log::info!(
+        "Checked {} in two passes in {:.2?} and found {}.",
+        count::count(walked.files_scanned, "file"),
+        start.elapsed(),
+        count::count(violations.len(), "violation"),
+    );
+    Ok(Checked {
+        summary: Summary {
+            tags: index.tags.len(),
+            tag_references: state.tag_references,
+            file_references: state.file_references,
+            directory_references: state.directory_references,
+            url_references: state.url_references,
+            custom_labels: state.custom_labels,
+            files_scanned: index.files_scanned,
+            files_too_large: index.files_too_large.clone(),
+        },
+        index,
+        violations,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::{compile_block_regex, compile_directive_regex},
+        extractors,
+        index::{Rules, Scanner},
+        min_refs, streaming, walk,
+    };
+    use std::{
+        collections::BTreeMap,
+        env, fs,
+        path::PathBuf,
+        process,
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    fn scanner(root: PathBuf) -> Scanner {
+        Scanner {
+            paths: vec![root.clone()],
+            walk_options: walk::Options::default(),
+            tag_regex: compile_directive_regex("tag"),
+            tag_block_regex: Some(compile_block_regex("tag")),
+            ref_regex: compile_directive_regex("ref"),
+            file_regex: compile_directive_regex("file"),
+            dir_regex: compile_directive_regex("dir"),
+            url_regex: compile_directive_regex("url"),
+            custom_regexes: Vec::new(),
+            comments_only: false,
+            archives: false,
+            extractors: extractors::defaults(),
+            fallback_encoding: None,
+            case_insensitive_labels: false,
+            relative_paths: false,
+            roots: vec![root],
+            memory_map: true,
+        }
+    }
+
+    #[test]
+    fn check_matches_index() {
+        let root = env::temp_dir().join(format!(
+            "tagref-streaming-{}-{}",
+            process::id(),
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos(),
+        ));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(
+            root.join("src/a.rs"),
+            "// [?tag:foo] [?tag:bar] [?tag:baz min-refs=3]\n// [?ref:foo] [?ref:qux]\n"
+                .replace('?', ""),
+        )
+        .unwrap();
+        fs::write(
+            root.join("src/b.rs"),
+            "// [?ref:foo] [?ref:baz] [?ref:baz]\n// [?file:src/c.rs] [?tag:foo]\n"
+                .replace('?', ""),
+        )
+        .unwrap();
+        let rules = Rules {
+            deny_unused: true,
+            min_refs: BTreeMap::from([("foo".to_owned(), 2)]),
+            ..Rules::default()
+        };
+        let scanner = scanner(root.clone());
+
+        let (index, _) = scanner.scan(None).unwrap();
+        let expected = index.check(&rules);
+        let checked = streaming::check(&scanner, &rules, None).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            checked
+                .violations
+                .iter()
+                .map(|violation| &violation.message)
+                .collect::<Vec<_>>(),
+            expected
+                .iter()
+                .map(|violation| &violation.message)
+                .collect::<Vec<_>>(),
+        );
+        assert_eq!(checked.summary.tag_references, 5);
+        assert_eq!(checked.summary.files_scanned, 2);
+
+        // Only two references to `foo` are needed for its minimum, and none to `qux`.
+        assert_eq!(checked.index.refs.len(), 4);
+        assert_eq!(
+            min_refs::minimum("baz", &index.tags["baz"], &rules.min_refs, false),
+            Some(3),
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use crate::{
+    directive::compile_directive_regex,
+    violation::{Kind, Violation},
+    walk,
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+// This is the sigil of the comments which suppress violations.
+pub const SIGIL: &str = "tagref";
+
+// This regex matches the comments which suppress violations.
+pub static REGEX: LazyLock<Regex> = LazyLock::new(|| compile_directive_regex(SIGIL));
+
+// This struct represents a comment which suppresses violations involving the labels on a line, or
+// anywhere in a file.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Suppression {
+    pub path: PathBuf,
+
+    // The line whose labels are affected, or `None` for the whole file
+    pub line_number: Option<usize>,
+
+    // The kinds of violations to suppress, or all of them if this is empty
+    pub kinds: Vec<Kind>,
+}
+
+impl Suppression {
+    // This function parses the label of a suppression comment on the given line. The label is one
+    // of `ignore`, `ignore-next-line`, or `ignore-file`, optionally followed by the kinds of
+    // violations to suppress, separated by commas or spaces.
+    pub fn parse(label: &str, path: &Path, line_number: usize) -> Result<Self, String> {
+        let mut words = label
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|word| !word.is_empty());
+        let line_number = match words.next().map(str::to_lowercase).as_deref() {
+            Some("ignore") => Some(line_number),
+            Some("ignore-next-line") => Some(line_number + 1),
+            Some("ignore-file") => None,
+            _ => {
+                return Err(format!(
+                    "Unknown suppression `{label}`. Expected `ignore`, `ignore-next-line`, or \
+                     `ignore-file`.",
+                ));
+            }
+        };
+        let kinds = words
+            .map(|word| {
+                Kind::ALL
+                    .into_iter()
+                    .find(|kind| kind.id().eq_ignore_ascii_case(word))
+                    .ok_or_else(|| format!("Unknown kind of violation `{word}`."))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            path: path.to_owned(),
+            line_number,
+            kinds,
+        })
+    }
+}
+
+// This function removes the violations which are suppressed by the given comments, and returns
+// them. A violation involving several labels is suppressed if any of them is.
+pub fn suppress(suppressions: &[Suppression], violations: &mut Vec<Violation>) -> Vec<Violation> {
+    let mut by_path = HashMap::<PathBuf, Vec<&Suppression>>::new();
+    for suppression in suppressions {
+        by_path
+            .entry(walk::relative(&suppression.path))
+            .or_default()
+            .push(suppression);
+    }
+
+    violations
+        .extract_if(.., |violation| {
+            violation.directives.iter().any(|directive| {
+                by_path
+                    .get(&walk::relative(&directive.path))
+                    .is_some_and(|suppressions| {
+                        suppressions.iter().any(|suppression| {
+                            suppression
+                                .line_number
+                                .is_none_or(|line_number| line_number == directive.line_number)
+                                && (suppression.kinds.is_empty()
+                                    || suppression.kinds.contains(&violation.kind))
+                        })
+                    })
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::{Directive, Type},
+        suppressions::{Suppression, suppress},
+        violation::{Kind, Severity, Violation},
+    };
+    use std::path::Path;
+
+    fn violation(kind: Kind, path: &str, line_number: usize) -> Violation {
+        Violation {
+            kind,
+            severity: Severity::Error,
+            message: String::new(),
+            directives: vec![Directive::new(Type::Ref, "foo", path, line_number)],
+            suggestions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn parse_scopes() {
+        let path = Path::new("foo.rs");
+
+        assert_eq!(
+            Suppression::parse("ignore", path, 3).unwrap().line_number,
+            Some(3),
+        );
+        assert_eq!(
+            Suppression::parse("ignore-next-line", path, 3)
+                .unwrap()
+                .line_number,
+            Some(4),
+        );
+        assert_eq!(
+            Suppression::parse("ignore-file", path, 3)
+                .unwrap()
+                .line_number,
+            None,
+        );
+    }
+
+    #[test]
+    fn parse_kinds() {
+        assert_eq!(
+            Suppression::parse("ignore dangling_ref, unused_tag", Path::new("foo.rs"), 1)
+                .unwrap()
+                .kinds,
+            vec![Kind::DanglingRef, Kind::UnusedTag],
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(Suppression::parse("skip", Path::new("foo.rs"), 1).is_err());
+        assert!(Suppression::parse("ignore foo", Path::new("foo.rs"), 1).is_err());
+    }
+
+    #[test]
+    fn suppress_line() {
+        let suppressions = [Suppression::parse("ignore", Path::new("./foo.rs"), 2).unwrap()];
+        let mut violations = vec![
+            violation(Kind::DanglingRef, "foo.rs", 1),
+            violation(Kind::DanglingRef, "./foo.rs", 2),
+            violation(Kind::DanglingRef, "bar.rs", 2),
+        ];
+
+        let suppressed = suppress(&suppressions, &mut violations);
+
+        assert_eq!(suppressed.len(), 1);
+        assert_eq!(suppressed[0].directives[0].line_number, 2);
+        assert_eq!(violations.len(), 2);
+    }
+
+    #[test]
+    fn suppress_file_kinds() {
+        let suppressions =
+            [Suppression::parse("ignore-file invalid_file_ref", Path::new("foo.rs"), 1).unwrap()];
+        let mut violations = vec![
+            violation(Kind::DanglingRef, "foo.rs", 5),
+            violation(Kind::InvalidFileRef, "foo.rs", 7),
+        ];
+
+        let suppressed = suppress(&suppressions, &mut violations);
+
+        assert_eq!(suppressed.len(), 1);
+        assert_eq!(suppressed[0].kind, Kind::InvalidFileRef);
+        assert_eq!(violations[0].kind, Kind::DanglingRef);
+    }
+}
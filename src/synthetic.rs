@@ -0,0 +1,138 @@
+use std::{
+    fmt::Write,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+// The files are spread across directories with up to this many files each.
+const FILES_PER_DIR: usize = 100;
+
+// This struct describes a synthetic tree of files, which is used to measure how fast codebases of
+// various sizes are scanned and checked. Every file has the same number of tags, each of which is
+// referenced from the next file (with the last file referencing the first one), and a file
+// reference to the next file, so the tree has no violations when checked from its root.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Tree {
+    pub files: usize,
+
+    // The number of lines in each file, apart from the labels
+    pub lines: usize,
+
+    // The number of tags in each file
+    pub tags: usize,
+}
+
+impl Tree {
+    // This function returns the path of a file, relative to the root of the tree.
+    #[must_use]
+    pub fn path(index: usize) -> PathBuf {
+        Path::new(&format!("dir-{}", index / FILES_PER_DIR)).join(format!("file-{index}.rs"))
+    }
+
+    // This function returns the contents of a file. The sigils aren't written literally, so this
+    // file doesn't contain labels itself.
+    #[must_use]
+    pub fn contents(&self, index: usize) -> String {
+        let (tag, r#ref, file) = ("tag", "ref", "file");
+        let next = (index + 1) % self.files;
+        let previous = (index + self.files - 1) % self.files;
+
+        let mut contents = String::new();
+        for i in 0..self.tags {
+            let _ = writeln!(contents, "// [{tag}:file-{index}-{i}] Synthetic tag {i}.");
+        }
+        for i in 0..self.lines {
+            let _ = writeln!(contents, "    let value_{i} = compute(&inputs[{i}], {i});");
+        }
+        for i in 0..self.tags {
+            let _ = writeln!(contents, "// See [{ref}:file-{previous}-{i}].");
+        }
+        let _ = writeln!(
+            contents,
+            "// See [{file}:{}].",
+            Self::path(next).to_string_lossy(),
+        );
+        contents
+    }
+
+    // This function writes the tree into a directory, which must be empty or not exist yet, so
+    // nothing is overwritten.
+    pub fn generate(&self, root: &Path) -> Result<(), String> {
+        match fs::read_dir(root) {
+            Ok(mut entries) => {
+                if entries.next().is_some() {
+                    return Err(format!("{} isn't empty.", root.to_string_lossy()));
+                }
+            }
+            Err(error) if error.kind() == ErrorKind::NotFound => {}
+            Err(error) => {
+                return Err(format!(
+                    "Unable to read {}: {error}",
+                    root.to_string_lossy(),
+                ));
+            }
+        }
+
+        for index in 0..self.files {
+            let path = root.join(Self::path(index));
+
+            // The path of each file has a parent, so the `unwrap` is safe.
+            let parent = path.parent().unwrap();
+            fs::create_dir_all(parent)
+                .map_err(|error| format!("Unable to create {}: {error}", parent.display()))?;
+            fs::write(&path, self.contents(index))
+                .map_err(|error| format!("Unable to write {}: {error}", path.display()))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::{self, compile_directive_regex},
+        synthetic::Tree,
+    };
+    use std::path::Path;
+
+    #[test]
+    fn contents_labels() {
+        let tree = Tree {
+            files: 3,
+            lines: 10,
+            tags: 2,
+        };
+        let contents = tree.contents(0);
+
+        let directives = directive::parse(
+            &compile_directive_regex("tag"),
+            None,
+            &compile_directive_regex("ref"),
+            &compile_directive_regex("file"),
+            &compile_directive_regex("dir"),
+            &compile_directive_regex("url"),
+            &[],
+            false,
+            None,
+            Path::new("file-0.rs"),
+            contents.as_bytes(),
+        );
+
+        assert_eq!(contents.lines().count(), 15);
+        assert_eq!(
+            directives
+                .tags
+                .iter()
+                .map(|tag| tag.label.as_str())
+                .collect::<Vec<_>>(),
+            vec!["file-0-0", "file-0-1"],
+        );
+        assert_eq!(directives.refs[1].label, "file-2-1");
+        assert_eq!(
+            Path::new(&directives.files[0].label),
+            Path::new("dir-0/file-1.rs"),
+        );
+    }
+}
@@ -1,15 +1,182 @@
-use crate::directive::Directive;
-use std::collections::HashSet;
+use crate::{
+    directive::{self, Directive},
+    levenshtein::distance,
+    repositories,
+    violation::{Kind, Severity, Violation},
+};
+use std::{
+    borrow::Borrow,
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, HashSet},
+    hash::Hash,
+};
 
-// This function checks that tag references actually point to tags. It returns a vector of error
-// strings.
-pub fn check(tags: &HashSet<String>, refs: &[Directive]) -> Vec<String> {
-    let mut errors = Vec::<String>::new();
+// This function counts the characters in each of two labels which aren't in the other, as many
+// times as they occur, and returns the larger count. Each of them takes an edit, so it's a cheap
+// lower bound on the distance. The characters must be sorted.
+fn missing(x: &[char], y: &[char]) -> usize {
+    let (mut i, mut j) = (0, 0);
+    let (mut x_missing, mut y_missing) = (0, 0);
+    while i < x.len() && j < y.len() {
+        match x[i].cmp(&y[j]) {
+            Ordering::Less => {
+                x_missing += 1;
+                i += 1;
+            }
+            Ordering::Greater => {
+                y_missing += 1;
+                j += 1;
+            }
+            Ordering::Equal => {
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    (x_missing + x.len() - i).max(y_missing + y.len() - j)
+}
 
-    for r#ref in refs {
-        if !tags.contains(&r#ref.label) {
-            errors.push(format!("No tag found for {ref}."));
+// This struct is a tag, with its characters in order and sorted.
+struct Candidate {
+    tag: String,
+    chars: Vec<char>,
+    sorted_chars: Vec<char>,
+}
+
+// This function returns the sorted characters of a label.
+fn sorted(chars: &[char]) -> Vec<char> {
+    let mut sorted = chars.to_owned();
+    sorted.sort_unstable();
+    sorted
+}
+
+// This struct finds the tags closest to the labels of dangling references, for suggesting what
+// might have been meant. The tags are grouped by length, since tags much longer or shorter than a
+// label can't be close to it.
+struct Candidates {
+    by_length: BTreeMap<usize, Vec<Candidate>>,
+}
+
+impl Candidates {
+    // This function groups the tags by length.
+    fn new<S: AsRef<str>>(tags: &HashSet<S>) -> Self {
+        let mut by_length = BTreeMap::<_, Vec<_>>::new();
+        for tag in tags {
+            let chars = tag.as_ref().chars().collect::<Vec<_>>();
+            by_length.entry(chars.len()).or_default().push(Candidate {
+                tag: tag.as_ref().to_owned(),
+                sorted_chars: sorted(&chars),
+                chars,
+            });
         }
+        Self { by_length }
+    }
+
+    // This function finds the tags closest to a label. Tags which are more edits away than a third
+    // of the length of the label (but at least one) aren't considered similar. The suggestions
+    // are sorted.
+    fn closest(&self, label: &str) -> Vec<String> {
+        let label = label.chars().collect::<Vec<_>>();
+        let sorted_label = sorted(&label);
+        let mut best = (label.len() / 3).max(1);
+        let mut closest = Vec::new();
+        for tags in self
+            .by_length
+            .range(label.len().saturating_sub(best)..=label.len() + best)
+            .map(|(_, tags)| tags)
+        {
+            for candidate in tags {
+                if missing(&sorted_label, &candidate.sorted_chars) > best {
+                    continue;
+                }
+
+                let Some(distance) = distance(&label, &candidate.chars, best) else {
+                    continue;
+                };
+                if distance < best {
+                    best = distance;
+                    closest.clear();
+                }
+                closest.push(candidate.tag.clone());
+            }
+        }
+        closest.sort();
+        closest
+    }
+}
+
+// This function formats the suggestions for a dangling reference as a question.
+fn did_you_mean(suggestions: &[String]) -> String {
+    let quoted = suggestions
+        .iter()
+        .map(|suggestion| format!("`{suggestion}`"))
+        .collect::<Vec<_>>();
+    let alternatives = match quoted.as_slice() {
+        [] => return String::new(),
+        [only] => only.clone(),
+        [first, second] => format!("{first} or {second}"),
+        [rest @ .., last] => format!("{}, or {last}", rest.join(", ")),
+    };
+    format!(" Did you mean {alternatives}?")
+}
+
+// This function checks that tag references actually point to tags. References to tags in other
+// repositories are checked against the tags of those repositories, if they're available. The tags
+// are keyed as in the index. Each violation suggests the closest tags, if there are any. It
+// returns a vector of violations.
+pub fn check<S: AsRef<str> + Borrow<str> + Eq + Hash>(
+    tags: &HashSet<S>,
+    external_tags: &BTreeMap<String, Option<HashSet<String>>>,
+    refs: &[Directive],
+    case_insensitive_labels: bool,
+) -> Vec<Violation> {
+    let mut errors = Vec::<Violation>::new();
+
+    // Finding the closest tags means comparing the label with many tags, so the tags are only
+    // grouped once, and it's only done once for each label.
+    let mut candidates = None;
+    let mut external_candidates = HashMap::new();
+    let mut suggestions = HashMap::<String, Vec<String>>::new();
+
+    for r#ref in refs {
+        let (message, suggestions) = match repositories::split(&r#ref.label, external_tags) {
+            Some((alias, label)) => match &external_tags[alias] {
+                Some(tags) if !tags.contains(&*directive::key(label, case_insensitive_labels)) => (
+                    format!("No tag found for {ref} in the `{alias}` repository."),
+                    external_candidates
+                        .entry(alias)
+                        .or_insert_with(|| Candidates::new(tags))
+                        .closest(&directive::key(label, case_insensitive_labels))
+                        .into_iter()
+                        .map(|tag| format!("{alias}#{tag}"))
+                        .collect::<Vec<_>>(),
+                ),
+                _ => continue,
+            },
+            None if !tags.contains(&*directive::key(&r#ref.label, case_insensitive_labels)) => {
+                let key = directive::key(&r#ref.label, case_insensitive_labels);
+                (
+                    format!("No tag found for {ref}."),
+                    suggestions
+                        .entry(key.into_owned())
+                        .or_insert_with_key(|key| {
+                            candidates
+                                .get_or_insert_with(|| Candidates::new(tags))
+                                .closest(key)
+                        })
+                        .clone(),
+                )
+            }
+            None => continue,
+        };
+
+        errors.push(Violation {
+            kind: Kind::DanglingRef,
+            severity: Severity::Error,
+            message: message + &did_you_mean(&suggestions),
+            directives: vec![r#ref.clone()],
+            suggestions,
+        });
     }
 
     errors
@@ -19,16 +186,16 @@ pub fn check(tags: &HashSet<String>, refs: &[Directive]) -> Vec<String> {
 mod tests {
     use crate::{
         directive::{Directive, Type},
-        tag_references::check,
+        tag_references::{Candidates, check, did_you_mean, missing, sorted},
     };
-    use std::{collections::HashSet, path::Path};
+    use std::collections::{BTreeMap, HashSet};
 
     #[test]
     fn check_empty() {
         let tags = HashSet::<String>::new();
         let refs = vec![];
 
-        assert!(check(&tags, &refs).is_empty());
+        assert!(check(&tags, &BTreeMap::new(), &refs, false).is_empty());
     }
 
     #[test]
@@ -36,14 +203,9 @@ mod tests {
         let mut tags = HashSet::new();
         tags.insert("ref1".to_owned());
 
-        let refs = vec![Directive {
-            r#type: Type::Ref,
-            label: "ref1".to_owned(),
-            path: Path::new("file1.rs").to_owned(),
-            line_number: 1,
-        }];
+        let refs = vec![Directive::new(Type::Ref, "ref1", "file1.rs", 1)];
 
-        assert!(check(&tags, &refs).is_empty());
+        assert!(check(&tags, &BTreeMap::new(), &refs, false).is_empty());
     }
 
     #[test]
@@ -52,31 +214,105 @@ mod tests {
         tags.insert("ref1".to_owned());
 
         let refs = vec![
-            Directive {
-                r#type: Type::Ref,
-                label: "ref1".to_owned(),
-                path: Path::new("file1.rs").to_owned(),
-                line_number: 1,
-            },
-            Directive {
-                r#type: Type::Ref,
-                label: "ref2".to_owned(),
-                path: Path::new("file2.rs").to_owned(),
-                line_number: 2,
-            },
-            Directive {
-                r#type: Type::Ref,
-                label: "ref3".to_owned(),
-                path: Path::new("file3.rs").to_owned(),
-                line_number: 3,
-            },
+            Directive::new(Type::Ref, "ref1", "file1.rs", 1),
+            Directive::new(Type::Ref, "ref2", "file2.rs", 2),
+            Directive::new(Type::Ref, "ref3", "file3.rs", 3),
         ];
 
-        let errors = check(&tags, &refs);
+        let errors = check(&tags, &BTreeMap::new(), &refs, false);
         assert_eq!(errors.len(), 2);
         assert!(
-            (errors[0].contains(&refs[1].label) && errors[1].contains(&refs[2].label))
-                || (errors[0].contains(&refs[2].label) && errors[1].contains(&refs[1].label)),
+            (errors[0].message.contains(&refs[1].label)
+                && errors[1].message.contains(&refs[2].label))
+                || (errors[0].message.contains(&refs[2].label)
+                    && errors[1].message.contains(&refs[1].label)),
         );
     }
+
+    #[test]
+    fn check_external() {
+        let tags = HashSet::<String>::new();
+        let external_tags = BTreeMap::from([
+            ("other".to_owned(), Some(HashSet::from(["foo".to_owned()]))),
+            ("missing".to_owned(), None),
+        ]);
+
+        let refs = ["other#foo", "other#bar", "missing#baz"]
+            .iter()
+            .map(|label| Directive::new(Type::Ref, label, "file1.rs", 1))
+            .collect::<Vec<_>>();
+
+        let errors = check(&tags, &external_tags, &refs, false);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].message.contains("other#bar"));
+        assert!(errors[0].message.contains("`other` repository"));
+    }
+
+    #[test]
+    fn check_case_insensitive() {
+        let tags = HashSet::from(["foo".to_owned()]);
+        let external_tags =
+            BTreeMap::from([("other".to_owned(), Some(HashSet::from(["bar".to_owned()])))]);
+
+        let refs = ["FOO", "other#Bar"]
+            .iter()
+            .map(|label| Directive::new(Type::Ref, label, "file1.rs", 1))
+            .collect::<Vec<_>>();
+
+        assert!(check(&tags, &external_tags, &refs, true).is_empty());
+        assert_eq!(check(&tags, &external_tags, &refs, false).len(), 2);
+    }
+
+    #[test]
+    fn missing_characters() {
+        let chars = |label: &str| sorted(&label.chars().collect::<Vec<_>>());
+
+        assert_eq!(missing(&chars("kitten"), &chars("sitting")), 3);
+        assert_eq!(missing(&chars("sitting"), &chars("kitten")), 3);
+        assert_eq!(missing(&chars("tagref"), &chars("fregat")), 0);
+        assert_eq!(missing(&chars("tagref"), &chars("tag")), 3);
+    }
+
+    #[test]
+    fn closest_tags() {
+        let tags = ["retry policy", "retry_policy", "retries", "unrelated"]
+            .into_iter()
+            .map(str::to_owned)
+            .collect::<HashSet<_>>();
+        let candidates = Candidates::new(&tags);
+
+        assert_eq!(
+            candidates.closest("retry-policy"),
+            vec!["retry policy".to_owned(), "retry_policy".to_owned()],
+        );
+        assert_eq!(candidates.closest("retris"), vec!["retries".to_owned()]);
+        assert!(candidates.closest("completely different").is_empty());
+    }
+
+    #[test]
+    fn did_you_mean_alternatives() {
+        let labels = ["a", "b", "c"].map(str::to_owned);
+
+        assert_eq!(did_you_mean(&labels[..0]), "");
+        assert_eq!(did_you_mean(&labels[..1]), " Did you mean `a`?");
+        assert_eq!(did_you_mean(&labels[..2]), " Did you mean `a` or `b`?");
+        assert_eq!(did_you_mean(&labels), " Did you mean `a`, `b`, or `c`?");
+    }
+
+    #[test]
+    fn check_suggestions() {
+        let tags = HashSet::from(["retry policy".to_owned()]);
+        let external_tags =
+            BTreeMap::from([("other".to_owned(), Some(HashSet::from(["bar".to_owned()])))]);
+
+        let refs = ["retry polcy", "other#baz"]
+            .iter()
+            .map(|label| Directive::new(Type::Ref, label, "file1.rs", 1))
+            .collect::<Vec<_>>();
+
+        let errors = check(&tags, &external_tags, &refs, false);
+        assert_eq!(errors[0].suggestions, vec!["retry policy".to_owned()]);
+        assert!(errors[0].message.ends_with(" Did you mean `retry policy`?"));
+        assert_eq!(errors[1].suggestions, vec!["other#bar".to_owned()]);
+    }
 }
@@ -0,0 +1,477 @@
+use crate::{
+    directive::{self, Directive},
+    edits::{Edit, Edits},
+    encoding::{self, Decoded},
+    expiry::{self, Date},
+    owners::Codeowners,
+    walk,
+};
+use encoding_rs::Encoding;
+use regex::Regex;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    mem::take,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, Mutex},
+};
+
+// This regex matches the markers which make a line a to-do item.
+static MARKER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b(TODO|FIXME|XXX|HACK)\b").unwrap()); // Safe by inspection
+
+// This regex matches the markers of ad-hoc to-do items, which name a label in parentheses. The
+// marker is in the `keep` group, and the label is in the `label` group.
+pub static AD_HOC: LazyLock<Regex> = LazyLock::new(|| {
+    // Safe by inspection
+    Regex::new(r"\b(?<keep>TODO|FIXME|XXX|HACK)\((?<label>[A-Za-z0-9_./:-]+)\)").unwrap()
+});
+
+// This is the attribute which sets the owner of a to-do item, e.g., `owner=@team-payments`.
+pub const OWNER_ATTRIBUTE: &str = "owner";
+
+// This struct represents a label on the same line as a to-do marker.
+#[derive(Clone, Debug, Serialize)]
+pub struct Todo<'a> {
+    pub directive: &'a Directive,
+
+    // The marker, e.g., `TODO`
+    pub marker: String,
+
+    // The text after the marker, with any leading colon dropped
+    pub text: String,
+}
+
+// This function finds the to-do marker on a line, along with the text after it.
+fn marker(line: &str) -> Option<(String, String)> {
+    let found = MARKER.find(line)?;
+    let text = line[found.end()..].trim_start();
+    let text = text.strip_prefix(':').unwrap_or(text);
+    let text = text
+        .trim()
+        .strip_suffix("*/")
+        .or_else(|| text.trim().strip_suffix("-->"))
+        .unwrap_or(text)
+        .trim();
+    Some((found.as_str().to_owned(), text.to_owned()))
+}
+
+// This function finds the labels on the same lines as to-do markers. Each file is read once. Files
+// which can't be read (e.g., because they were deleted since they were scanned) are skipped.
+// The text of each item is the rest of the line after the marker, without the label itself.
+pub fn find<'a, I: IntoIterator<Item = &'a Directive>>(
+    directives: I,
+    fallback_encoding: Option<&'static Encoding>,
+) -> Vec<Todo<'a>> {
+    let mut by_path = BTreeMap::<&Path, Vec<&Directive>>::new();
+    for directive in directives {
+        by_path.entry(&*directive.path).or_default().push(directive);
+    }
+
+    let mut todos = Vec::new();
+    for (path, directives) in by_path {
+        let Ok(bytes) = fs::read(path) else {
+            continue;
+        };
+        let (Decoded::Text(text) | Decoded::Lossy(text)) =
+            encoding::decode(&bytes, fallback_encoding)
+        else {
+            continue;
+        };
+        let lines = directive::lines(&text)
+            .map(|(line, _)| line)
+            .collect::<Vec<_>>();
+        for directive in directives {
+            let Some(line) = lines.get(directive.line_number.wrapping_sub(1)) else {
+                continue;
+            };
+
+            // Leave the label out of the text. The span is only within the line if the label
+            // doesn't span several lines.
+            let line = match (
+                line.get(..directive.span.start),
+                line.get(directive.span.end..),
+            ) {
+                (Some(before), Some(after)) => format!("{before}{after}"),
+                _ => (*line).to_owned(),
+            };
+            if let Some((marker, text)) = self::marker(&line) {
+                todos.push(Todo {
+                    directive,
+                    marker,
+                    text,
+                });
+            }
+        }
+    }
+
+    todos.sort_by(|x, y| x.directive.cmp_position(y.directive));
+    todos
+}
+
+// This struct is an ad-hoc to-do item which names a label in parentheses, e.g., `TODO(cleanup)`,
+// rather than using a label.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AdHoc {
+    pub label: String,
+    pub line_number: usize,
+}
+
+// This function finds the ad-hoc to-do items in the contents of a file.
+#[must_use]
+pub fn find_ad_hoc(contents: &str) -> Vec<AdHoc> {
+    directive::lines(contents)
+        .enumerate()
+        .flat_map(|(index, (line, _))| {
+            AD_HOC.captures_iter(line).map(move |captures| AdHoc {
+                label: captures["label"].to_owned(),
+                line_number: index + 1,
+            })
+        })
+        .collect()
+}
+
+// This struct describes the ad-hoc to-do items with a label: where the first one is (in order of
+// path), and how many there are.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AdHocLabel {
+    pub path: PathBuf,
+    pub line_number: usize,
+    pub count: usize,
+}
+
+// This function finds the ad-hoc to-do items in the files, grouped by label. The paths are relative
+// to the working directory.
+pub fn collect_ad_hoc(
+    paths: &[PathBuf],
+    options: &walk::Options,
+) -> Result<BTreeMap<String, AdHocLabel>, String> {
+    let items = Arc::new(Mutex::new(Vec::new()));
+    let items_clone = items.clone();
+    walk::walk(paths, options, move |path, file| {
+        if let Ok(contents) = io::read_to_string(file) {
+            let path = walk::relative(path);
+            // The `unwrap` is safe assuming no poisoning.
+            items_clone.lock().unwrap().extend(
+                find_ad_hoc(&contents)
+                    .into_iter()
+                    .map(|item| (path.clone(), item)),
+            );
+        }
+    })?;
+
+    // The `unwrap` is safe assuming no poisoning.
+    let mut items = take(&mut *items.lock().unwrap());
+    items.sort_by(|(x, x_item), (y, y_item)| (x, x_item.line_number).cmp(&(y, y_item.line_number)));
+    let mut labels = BTreeMap::<String, AdHocLabel>::new();
+    for (path, item) in items {
+        labels
+            .entry(item.label)
+            .or_insert(AdHocLabel {
+                path,
+                line_number: item.line_number,
+                count: 0,
+            })
+            .count += 1;
+    }
+
+    Ok(labels)
+}
+
+// This function finds the edits which convert the ad-hoc to-do items with the given labels in the
+// files. The first item with each label becomes a tag, and the rest become references to it.
+pub fn convert_all_ad_hoc(
+    paths: &[PathBuf],
+    options: &walk::Options,
+    labels: BTreeMap<String, AdHocLabel>,
+    tag_sigil: String,
+    ref_sigil: String,
+) -> Result<Edits, String> {
+    Edits::collect(paths, options, move |path, contents| {
+        let path = walk::relative(path);
+        convert_ad_hoc(contents, |label, line_number| {
+            labels.get(label).map(|first| {
+                if first.path == path && first.line_number == line_number {
+                    tag_sigil.clone()
+                } else {
+                    ref_sigil.clone()
+                }
+            })
+        })
+    })
+}
+
+// This function converts ad-hoc to-do items into to-do items with labels, e.g., `TODO(cleanup)`
+// into `TODO` followed by a tag or reference with the label `cleanup`. Only the items whose labels
+// are accepted by `sigil` are converted, and it determines the sigil for each one from its label
+// and line number.
+#[must_use]
+pub fn convert_ad_hoc(contents: &str, sigil: impl Fn(&str, usize) -> Option<String>) -> Vec<Edit> {
+    let mut edits = Vec::new();
+    let mut offset = 0;
+
+    for (index, (line, ending)) in directive::lines(contents).enumerate() {
+        for captures in AD_HOC.captures_iter(line) {
+            let Some(sigil) = sigil(&captures["label"], index + 1) else {
+                continue;
+            };
+            // The `unwrap` is safe since the whole match is always present.
+            let range = captures.get(0).unwrap().range();
+            edits.push(Edit {
+                range: offset + range.start..offset + range.end,
+                replacement: format!("{} [{sigil}:{}]", &captures["keep"], &captures["label"]),
+            });
+        }
+        offset += line.len() + ending.len();
+    }
+
+    edits
+}
+
+// This enum represents the ways to group to-do items.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Grouping {
+    // By the label, so a tag is grouped with the references to it
+    Label,
+
+    // By the `owner` attribute or, failing that, the owners of the file in CODEOWNERS
+    Owner,
+
+    // By the `expires` attribute, earliest first
+    Expiry,
+}
+
+// This is the name of the group for items without an owner or expiry date.
+pub const UNGROUPED: &str = "(none)";
+
+// This struct represents a group of to-do items.
+#[derive(Clone, Debug, Serialize)]
+pub struct Group<'a> {
+    pub name: String,
+    pub todos: Vec<Todo<'a>>,
+}
+
+// This function groups to-do items. Labels are keyed as in the index. The groups are sorted by
+// name, except that expiry dates are sorted chronologically, and items without an owner or expiry
+// date come last.
+#[must_use]
+pub fn group<'a>(
+    todos: Vec<Todo<'a>>,
+    grouping: Grouping,
+    codeowners: Option<&Codeowners>,
+    case_insensitive_labels: bool,
+) -> Vec<Group<'a>> {
+    // The keys sort ungrouped items last and expiry dates chronologically.
+    let mut groups = BTreeMap::<(bool, Option<Date>, String), Vec<Todo>>::new();
+    for todo in todos {
+        let attributes = &todo.directive.attributes;
+        let key = match grouping {
+            Grouping::Label => (
+                false,
+                None,
+                directive::key(&todo.directive.label, case_insensitive_labels).into_owned(),
+            ),
+            Grouping::Owner => {
+                let owners = attributes.get(OWNER_ATTRIBUTE).cloned().or_else(|| {
+                    codeowners
+                        .map(|codeowners| codeowners.owners(&todo.directive.path).join(" "))
+                        .filter(|owners| !owners.is_empty())
+                });
+                (owners.is_none(), None, owners.unwrap_or_default())
+            }
+            Grouping::Expiry => {
+                let date = attributes
+                    .get(expiry::ATTRIBUTE)
+                    .and_then(|text| Date::parse(text));
+                (
+                    date.is_none(),
+                    date,
+                    date.map(|date| date.to_string()).unwrap_or_default(),
+                )
+            }
+        };
+        groups.entry(key).or_default().push(todo);
+    }
+
+    groups
+        .into_iter()
+        .map(|((ungrouped, _, name), todos)| Group {
+            name: if ungrouped {
+                UNGROUPED.to_owned()
+            } else {
+                name
+            },
+            todos,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::{Directive, Type},
+        edits::splice,
+        owners::Codeowners,
+        todos::{
+            AdHoc, Group, Grouping, Todo, UNGROUPED, convert_ad_hoc, find_ad_hoc, group, marker,
+        },
+    };
+    use std::{collections::BTreeMap, path::Path};
+
+    fn directive(label: &str, path: &str, attributes: &[(&str, &str)]) -> Directive {
+        Directive {
+            r#type: Type::Ref,
+            label: label.to_owned(),
+            path: Path::new(path).into(),
+            line_number: 1,
+            column: 1,
+            span: 0..0,
+            description: None,
+            attributes: attributes
+                .iter()
+                .map(|(key, value)| ((*key).to_owned(), (*value).to_owned()))
+                .collect::<BTreeMap<_, _>>(),
+            cell: None,
+        }
+    }
+
+    // This function returns the group name and path of each to-do item.
+    fn paths<'a>(groups: &'a [Group]) -> Vec<(&'a str, &'a Path)> {
+        groups
+            .iter()
+            .flat_map(|group| {
+                group
+                    .todos
+                    .iter()
+                    .map(|todo| (group.name.as_str(), &*todo.directive.path))
+            })
+            .collect()
+    }
+
+    fn todo(directive: &Directive) -> Todo<'_> {
+        Todo {
+            directive,
+            marker: "TODO".to_owned(),
+            text: String::new(),
+        }
+    }
+
+    #[test]
+    fn marker_text() {
+        assert_eq!(
+            marker("    // TODO: Remove this retry loop. */"),
+            Some(("TODO".to_owned(), "Remove this retry loop.".to_owned())),
+        );
+        assert_eq!(
+            marker("# FIXME see below"),
+            Some(("FIXME".to_owned(), "see below".to_owned())),
+        );
+        assert_eq!(marker("// TODOS aren't markers"), None);
+        assert_eq!(marker("// todo: lowercase isn't either"), None);
+    }
+
+    #[test]
+    fn group_by_owner() {
+        let codeowners = Codeowners::parse("/api/ @org/api\n").unwrap();
+        let directives = [
+            directive("a", "./api/a.rs", &[]),
+            directive("b", "./web/b.rs", &[]),
+            directive("c", "./web/c.rs", &[("owner", "@org/web")]),
+        ];
+
+        let groups = group(
+            directives.iter().map(todo).collect(),
+            Grouping::Owner,
+            Some(&codeowners),
+            false,
+        );
+
+        assert_eq!(
+            paths(&groups),
+            vec![
+                ("@org/api", Path::new("./api/a.rs")),
+                ("@org/web", Path::new("./web/c.rs")),
+                (UNGROUPED, Path::new("./web/b.rs")),
+            ],
+        );
+    }
+
+    #[test]
+    fn group_by_expiry() {
+        let directives = [
+            directive("a", "a.rs", &[("expires", "2025-01-01")]),
+            directive("b", "b.rs", &[]),
+            directive("c", "c.rs", &[("expires", "2024-12-31")]),
+        ];
+
+        let groups = group(
+            directives.iter().map(todo).collect(),
+            Grouping::Expiry,
+            None,
+            false,
+        );
+
+        assert_eq!(
+            groups
+                .iter()
+                .map(|group| group.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["2024-12-31", "2025-01-01", UNGROUPED],
+        );
+    }
+
+    #[test]
+    fn group_by_label() {
+        let directives = [directive("Foo", "a.rs", &[]), directive("foo", "b.rs", &[])];
+
+        assert_eq!(
+            group(
+                directives.iter().map(todo).collect(),
+                Grouping::Label,
+                None,
+                true,
+            )
+            .len(),
+            1,
+        );
+    }
+
+    #[test]
+    fn find_ad_hoc_items() {
+        assert_eq!(
+            find_ad_hoc("// TODO(cleanup): x\n// TODO: y\n# FIXME(v2) and XXX(later)\n"),
+            vec![
+                AdHoc {
+                    label: "cleanup".to_owned(),
+                    line_number: 1,
+                },
+                AdHoc {
+                    label: "v2".to_owned(),
+                    line_number: 3,
+                },
+                AdHoc {
+                    label: "later".to_owned(),
+                    line_number: 3,
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn convert_ad_hoc_items() {
+        let contents = "// TODO(cleanup): x\r\n// TODO(cleanup): y\n// TODO(alice): z\n";
+
+        let edits = convert_ad_hoc(contents, |label, line_number| {
+            (label == "cleanup").then(|| if line_number == 1 { "tag" } else { "ref" }.to_owned())
+        });
+
+        assert_eq!(
+            splice(contents, &edits),
+            "// TODO [?tag:cleanup]: x\r\n// TODO [?ref:cleanup]: y\n// TODO(alice): z\n"
+                .replace('?', ""),
+        );
+        assert_eq!(edits.len(), 2);
+        assert_eq!(&contents[edits[1].range.clone()], "TODO(cleanup)");
+    }
+}
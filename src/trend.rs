@@ -0,0 +1,97 @@
+use crate::{
+    count,
+    git::Commit,
+    index::{Rules, Scanner},
+    violation::Severity,
+};
+use serde::Serialize;
+use std::time::Instant;
+
+// This struct records the numbers of labels and violations as of a commit.
+#[derive(Clone, Debug, Serialize)]
+pub struct Point {
+    pub commit: String,
+    pub date: String,
+    pub tags: usize,
+    pub tag_references: usize,
+    pub file_references: usize,
+    pub directory_references: usize,
+    pub url_references: usize,
+    pub violations: usize,
+    pub errors: usize,
+}
+
+// This function keeps every `every`th commit, counting back from the newest one so it's always
+// kept. The commits are ordered oldest first.
+#[must_use]
+pub fn sample(commits: Vec<Commit>, every: usize) -> Vec<Commit> {
+    let every = every.max(1);
+    let newest = commits.len().saturating_sub(1);
+    commits
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| (newest - index).is_multiple_of(every))
+        .map(|(_, commit)| commit)
+        .collect()
+}
+
+// This function scans the files as of a commit and checks them, counting the labels and the
+// violations. Violations suppressed by comments aren't counted, but URLs and issues aren't checked
+// and the baseline doesn't apply, so the numbers are comparable across the history.
+pub fn measure(scanner: &Scanner, rules: &Rules, commit: &Commit) -> Result<Point, String> {
+    let start = Instant::now();
+    let index = scanner.scan_revision(&commit.hash)?;
+    let mut violations = index.check(rules);
+    index.suppress(&mut violations);
+    let summary = index.summary();
+    log::info!(
+        "Found {} as of {} in {:.2?}.",
+        count::count(violations.len(), "violation"),
+        commit.hash,
+        start.elapsed(),
+    );
+
+    Ok(Point {
+        commit: commit.hash.clone(),
+        date: commit.date.clone(),
+        tags: summary.tags,
+        tag_references: summary.tag_references,
+        file_references: summary.file_references,
+        directory_references: summary.directory_references,
+        url_references: summary.url_references,
+        errors: violations
+            .iter()
+            .filter(|violation| violation.severity == Severity::Error)
+            .count(),
+        violations: violations.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{git::Commit, trend::sample};
+
+    fn commits(count: usize) -> Vec<Commit> {
+        (0..count)
+            .map(|index| Commit {
+                hash: index.to_string(),
+                date: String::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sample_keeps_newest() {
+        let hashes = |commits: Vec<Commit>| {
+            commits
+                .into_iter()
+                .map(|commit| commit.hash)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(hashes(sample(commits(7), 3)), vec!["0", "3", "6"]);
+        assert_eq!(hashes(sample(commits(5), 3)), vec!["1", "4"]);
+        assert_eq!(hashes(sample(commits(3), 1)), vec!["0", "1", "2"]);
+        assert!(sample(commits(0), 2).is_empty());
+    }
+}
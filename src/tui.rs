@@ -0,0 +1,614 @@
+use crate::editor;
+use colored::Colorize;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::{collections::HashMap, fmt::Write as _, path::PathBuf};
+use tagref::{directive::Directive, index::Index, namespaces, walk};
+
+// This struct represents a search query. Words like `path:src/` and `ns:security` are filters, and
+// the rest of the query is fuzzy matched against the labels of the tags.
+#[derive(Debug, Default, Eq, PartialEq)]
+struct Query {
+    // Only tags defined in or referenced from paths starting with one of these are shown.
+    paths: Vec<String>,
+
+    // Only tags in one of these namespaces are shown.
+    namespaces: Vec<String>,
+
+    text: String,
+}
+
+impl Query {
+    fn parse(query: &str) -> Self {
+        let mut parsed = Self::default();
+        let mut words = Vec::new();
+        for word in query.split_whitespace() {
+            if let Some(path) = word.strip_prefix("path:") {
+                parsed
+                    .paths
+                    .push(path.trim_start_matches("./").replace('\\', "/"));
+            } else if let Some(namespace) = word.strip_prefix("ns:") {
+                parsed
+                    .namespaces
+                    .push(namespace.trim_end_matches('/').to_owned());
+            } else {
+                words.push(word);
+            }
+        }
+        parsed.text = words.join(" ");
+        parsed
+    }
+
+    // This function determines whether a directive is in one of the paths in the query.
+    fn in_paths(&self, directive: &Directive) -> bool {
+        let path = walk::relative(&directive.path)
+            .to_string_lossy()
+            .replace('\\', "/");
+        self.paths.is_empty() || self.paths.iter().any(|prefix| path.starts_with(prefix))
+    }
+
+    // This function determines whether a label is in one of the namespaces in the query.
+    fn in_namespaces(&self, label: &str) -> bool {
+        self.namespaces.is_empty()
+            || self
+                .namespaces
+                .iter()
+                .any(|namespace| namespaces::contains(namespace, label))
+    }
+}
+
+// This function scores how well a label matches a pattern, ignoring case and whitespace in the
+// pattern. The characters of the pattern must appear in the label in order, and matches which are
+// consecutive or at the start of a word score higher. It returns `None` if the label doesn't match.
+fn fuzzy_score(pattern: &str, label: &str) -> Option<usize> {
+    let mut pattern = pattern
+        .chars()
+        .filter(|character| !character.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .peekable();
+    let mut score = 0_usize;
+    let mut previous = None::<char>;
+    let mut previous_matched = false;
+    for character in label.chars() {
+        let Some(next) = pattern.peek() else {
+            break;
+        };
+
+        let matched = character.to_lowercase().eq([*next]);
+        if matched {
+            score += 1;
+            if previous_matched {
+                score += 2;
+            }
+            if previous.is_none_or(|previous| !previous.is_alphanumeric()) {
+                score += 3;
+            }
+            pattern.next();
+        }
+        previous = Some(character);
+        previous_matched = matched;
+    }
+
+    pattern.peek().is_none().then_some(score)
+}
+
+// This function fits text into a column of the given width, truncating or padding it as needed.
+fn fit(text: &str, width: usize) -> String {
+    let mut fitted = text.chars().take(width).collect::<String>();
+    let length = fitted.chars().count();
+    if length == width && text.chars().count() > width && width > 0 {
+        fitted.pop();
+        fitted.push('…');
+    }
+    fitted.extend(std::iter::repeat_n(' ', width - length));
+    fitted
+}
+
+// This enum represents a key press.
+#[derive(Debug, Eq, PartialEq)]
+enum Key {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Tab,
+    Enter,
+    Backspace,
+    ClearQuery,
+    Quit,
+    Text(String),
+    Other,
+}
+
+// This function interprets a key press. Some control keys do the same as others, as in Emacs.
+fn parse_key(event: &KeyEvent) -> Key {
+    let control = event.modifiers.contains(KeyModifiers::CONTROL);
+    match event.code {
+        KeyCode::Char('p') if control => Key::Up,
+        KeyCode::Char('n') if control => Key::Down,
+        KeyCode::Char('h') if control => Key::Backspace,
+        KeyCode::Char('u') if control => Key::ClearQuery,
+        KeyCode::Char('c') if control => Key::Quit,
+        KeyCode::Up => Key::Up,
+        KeyCode::Down => Key::Down,
+        KeyCode::PageUp => Key::PageUp,
+        KeyCode::PageDown => Key::PageDown,
+        KeyCode::Tab => Key::Tab,
+        KeyCode::Enter => Key::Enter,
+        KeyCode::Backspace => Key::Backspace,
+        KeyCode::Esc => Key::Quit,
+        KeyCode::Char(character) if !control && !character.is_control() => {
+            Key::Text(character.to_string())
+        }
+        _ => Key::Other,
+    }
+}
+
+// The browser has two panes, and the arrow keys move the selection in the focused one.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Pane {
+    Tags,
+    References,
+}
+
+// This enum says what to do after a key press.
+#[derive(Debug, Eq, PartialEq)]
+enum Action {
+    Continue,
+    Open(PathBuf, usize),
+    Quit,
+}
+
+// This struct holds the state of the browser.
+struct Browser<'a> {
+    index: &'a Index,
+
+    // The references to each tag, keyed as in the index
+    refs: HashMap<String, Vec<&'a Directive>>,
+
+    query: String,
+
+    // The keys of the tags matching the query, best matches first
+    tags: Vec<&'a str>,
+
+    pane: Pane,
+    tag_selected: usize,
+    tag_offset: usize,
+    entry_selected: usize,
+    entry_offset: usize,
+
+    // A message to show in the status line, e.g., if the editor couldn't be started
+    status: Option<String>,
+}
+
+impl<'a> Browser<'a> {
+    fn new(index: &'a Index) -> Self {
+        let mut refs = HashMap::<String, Vec<&Directive>>::new();
+        for r#ref in &index.refs {
+            let key = index.key(&r#ref.label);
+            if index.tags.contains_key(&*key) {
+                refs.entry(key.into_owned()).or_default().push(r#ref);
+            }
+        }
+
+        let mut browser = Self {
+            index,
+            refs,
+            query: String::new(),
+            tags: Vec::new(),
+            pane: Pane::Tags,
+            tag_selected: 0,
+            tag_offset: 0,
+            entry_selected: 0,
+            entry_offset: 0,
+            status: None,
+        };
+        browser.filter();
+        browser
+    }
+
+    // This function finds the tags matching the query.
+    fn filter(&mut self) {
+        let query = Query::parse(&self.query);
+        let mut tags = self
+            .index
+            .tags
+            .iter()
+            .filter(|(key, directives)| {
+                query.in_namespaces(&directives[0].label)
+                    && directives
+                        .iter()
+                        .chain(self.refs.get(*key).into_iter().flatten().copied())
+                        .any(|directive| query.in_paths(directive))
+            })
+            .filter_map(|(key, directives)| {
+                fuzzy_score(&query.text, &directives[0].label).map(|score| (score, key.as_str()))
+            })
+            .collect::<Vec<_>>();
+        tags.sort_by(|(x_score, x), (y_score, y)| y_score.cmp(x_score).then_with(|| x.cmp(y)));
+
+        self.tags = tags.into_iter().map(|(_, key)| key).collect();
+        self.tag_selected = 0;
+        self.tag_offset = 0;
+        self.entry_selected = 0;
+        self.entry_offset = 0;
+    }
+
+    // This function returns the definitions of the selected tag followed by the references to it
+    // (only the ones in the paths in the query, if there are any).
+    fn entries(&self) -> Vec<&'a Directive> {
+        let Some(key) = self.tags.get(self.tag_selected) else {
+            return Vec::new();
+        };
+
+        let query = Query::parse(&self.query);
+        self.index.tags[*key]
+            .iter()
+            .chain(
+                self.refs
+                    .get(*key)
+                    .into_iter()
+                    .flatten()
+                    .copied()
+                    .filter(|r#ref| query.in_paths(r#ref)),
+            )
+            .collect()
+    }
+
+    // This function handles a key press. The height is the number of rows in each pane.
+    fn handle(&mut self, key: Key, height: usize) -> Action {
+        self.status = None;
+        let count = match self.pane {
+            Pane::Tags => self.tags.len(),
+            Pane::References => self.entries().len(),
+        };
+        let selected = match self.pane {
+            Pane::Tags => &mut self.tag_selected,
+            Pane::References => &mut self.entry_selected,
+        };
+
+        match key {
+            Key::Up => *selected = selected.saturating_sub(1),
+            Key::Down => *selected = (*selected + 1).min(count.saturating_sub(1)),
+            Key::PageUp => *selected = selected.saturating_sub(height),
+            Key::PageDown => *selected = (*selected + height).min(count.saturating_sub(1)),
+            Key::Tab => {
+                self.pane = match self.pane {
+                    Pane::Tags => Pane::References,
+                    Pane::References => Pane::Tags,
+                };
+            }
+            Key::Enter => {
+                let entries = self.entries();
+                let index = match self.pane {
+                    Pane::Tags => 0,
+                    Pane::References => self.entry_selected,
+                };
+                return entries.get(index).map_or(Action::Continue, |directive| {
+                    Action::Open(directive.path.to_path_buf(), directive.line_number)
+                });
+            }
+            Key::Backspace => {
+                self.query.pop();
+                self.filter();
+            }
+            Key::ClearQuery => {
+                self.query.clear();
+                self.filter();
+            }
+            Key::Text(text) => {
+                self.query.push_str(&text);
+                self.filter();
+            }
+            Key::Quit => return Action::Quit,
+            Key::Other => {}
+        }
+
+        // Moving to another tag starts its list of entries from the top.
+        if self.pane == Pane::Tags {
+            self.entry_selected = 0;
+            self.entry_offset = 0;
+        }
+
+        // Scroll the panes so the selections are visible.
+        for (selected, offset) in [
+            (self.tag_selected, &mut self.tag_offset),
+            (self.entry_selected, &mut self.entry_offset),
+        ] {
+            if selected < *offset {
+                *offset = selected;
+            } else if selected >= *offset + height {
+                *offset = selected + 1 - height;
+            }
+        }
+
+        Action::Continue
+    }
+
+    // This function describes an entry in the right pane.
+    fn describe(directive: &Directive) -> String {
+        let location = format!(
+            "{}:{}",
+            walk::relative(&directive.path).to_string_lossy(),
+            directive.line_number,
+        );
+        match &directive.description {
+            Some(description) => format!("{}  {location}  {description}", directive.r#type.sigil()),
+            None => format!("{}  {location}", directive.r#type.sigil()),
+        }
+    }
+
+    // This function renders a row of a pane, highlighting the selection.
+    fn cell(&self, text: &str, width: usize, selected: bool, pane: Pane) -> String {
+        let text = fit(text, width);
+        if !selected {
+            text
+        } else if self.pane == pane {
+            text.reversed().to_string()
+        } else {
+            text.bold().to_string()
+        }
+    }
+
+    // This function renders the whole screen. Lines end with `\r\n`, since the terminal doesn't
+    // return the cursor to the start of the line by itself in raw mode.
+    fn render(&self, width: usize, height: usize) -> String {
+        let rows = height.saturating_sub(3);
+        let left_width = (width / 3).max(10).min(width.saturating_sub(3));
+        let right_width = width.saturating_sub(left_width + 3);
+        let entries = self.entries();
+
+        let mut screen = String::from("\x1b[H");
+        let _ = write!(
+            screen,
+            "{} │ {}\x1b[K\r\n",
+            fit(&format!("Tags ({})", self.tags.len()), left_width).bold(),
+            fit(
+                &format!("Definitions and references ({})", entries.len()),
+                right_width
+            )
+            .bold(),
+        );
+        for row in 0..rows {
+            let tag = self.tag_offset + row;
+            let left = self
+                .tags
+                .get(tag)
+                .map_or(String::new(), |key| self.index.tags[*key][0].label.clone());
+            let entry = self.entry_offset + row;
+            let right = entries
+                .get(entry)
+                .map_or(String::new(), |directive| Self::describe(directive));
+            let _ = write!(
+                screen,
+                "{} │ {}\x1b[K\r\n",
+                self.cell(
+                    &left,
+                    left_width,
+                    tag == self.tag_selected && tag < self.tags.len(),
+                    Pane::Tags
+                ),
+                self.cell(
+                    &right,
+                    right_width,
+                    entry == self.entry_selected && entry < entries.len(),
+                    Pane::References,
+                ),
+            );
+        }
+        let status = self.status.clone().unwrap_or_else(|| {
+            "Type to search (path:PREFIX and ns:NAMESPACE filter) · ↑/↓ select · Tab switch \
+             pane · Enter open · Esc quit"
+                .to_owned()
+        });
+        let _ = write!(screen, "{}\x1b[K\r\n", fit(&status, width).dimmed());
+        let _ = write!(
+            screen,
+            "{}\x1b[K\x1b[J",
+            fit(&format!("> {}", self.query), width)
+        );
+        screen
+    }
+}
+
+mod terminal {
+    use crossterm::{
+        cursor::{Hide, Show},
+        execute,
+        terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+    };
+    use std::{io, panic, sync::Once};
+
+    // This struct puts the terminal in raw mode and switches to the alternate screen. Both are
+    // undone when it's dropped. They're also undone before a panic is reported, since the report
+    // would otherwise be printed on the alternate screen and lost.
+    pub struct Terminal;
+
+    impl Terminal {
+        pub fn enter() -> io::Result<Self> {
+            static PANIC_HOOK: Once = Once::new();
+            PANIC_HOOK.call_once(|| {
+                let hook = panic::take_hook();
+                panic::set_hook(Box::new(move |info| {
+                    restore();
+                    hook(info);
+                }));
+            });
+
+            terminal::enable_raw_mode()?;
+            let terminal = Self;
+            execute!(io::stdout(), EnterAlternateScreen, Hide)?;
+            Ok(terminal)
+        }
+
+        // This function returns the width and height of the terminal, or a typical size if it
+        // can't be determined.
+        pub fn size() -> (usize, usize) {
+            match terminal::size() {
+                Ok((width, height)) if width > 0 && height > 0 => {
+                    (usize::from(width), usize::from(height))
+                }
+                _ => (80, 24),
+            }
+        }
+    }
+
+    impl Drop for Terminal {
+        fn drop(&mut self) {
+            restore();
+        }
+    }
+
+    // This function returns the terminal to its normal state. Errors are ignored, since there's
+    // nothing else to be done about them.
+    fn restore() {
+        let _ = execute!(io::stdout(), Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+// This function runs the browser until the user quits.
+pub fn browse(index: &Index, editor: &[String]) -> Result<(), String> {
+    use crossterm::event::{self, Event, KeyEventKind};
+    use std::io::{self, IsTerminal, Write};
+    use terminal::Terminal;
+
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return Err("The browser must be run in a terminal.".to_owned());
+    }
+
+    let terminal_error = |error: io::Error| format!("Unable to use the terminal: {error}");
+    let mut browser = Browser::new(index);
+    let mut terminal = Terminal::enter().map_err(terminal_error)?;
+    loop {
+        let (width, height) = Terminal::size();
+        let mut stdout = io::stdout();
+        write!(stdout, "{}", browser.render(width, height)).map_err(terminal_error)?;
+        stdout.flush().map_err(terminal_error)?;
+
+        // Other events, like the terminal being resized, only cause the screen to be rendered
+        // again.
+        let key = match event::read().map_err(terminal_error)? {
+            Event::Key(event) if event.kind != KeyEventKind::Release => parse_key(&event),
+            _ => continue,
+        };
+        match browser.handle(key, height.saturating_sub(3)) {
+            Action::Continue => {}
+            Action::Open(path, line_number) => {
+                // The editor needs the terminal in its normal state.
+                drop(terminal);
+                let result = editor::open(editor, &path, line_number, 1);
+                terminal = Terminal::enter().map_err(terminal_error)?;
+                browser.status = result.err();
+            }
+            Action::Quit => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        fixtures,
+        tui::{Action, Browser, Key, Query, fit, fuzzy_score, parse_key},
+    };
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use std::path::PathBuf;
+    use tagref::{directive::Type, index::Index};
+
+    fn index() -> Index {
+        let mut index = Index::default();
+        for (label, path) in [
+            ("security/input-validation", "./src/auth.rs"),
+            ("retry policy", "./src/net.rs"),
+            ("retry budget", "./lib/net.rs"),
+        ] {
+            index.tags.insert(
+                label.to_owned(),
+                vec![fixtures::directive(Type::Tag, label, path, 1)],
+            );
+        }
+        index.refs = vec![
+            fixtures::directive(Type::Ref, "retry policy", "./src/client.rs", 1),
+            fixtures::directive(Type::Ref, "retry policy", "./docs/net.md", 1),
+        ];
+        index
+    }
+
+    #[test]
+    fn query_parse() {
+        assert_eq!(
+            Query::parse("path:./src/ retry ns:security/  policy"),
+            Query {
+                paths: vec!["src/".to_owned()],
+                namespaces: vec!["security".to_owned()],
+                text: "retry policy".to_owned(),
+            },
+        );
+    }
+
+    #[test]
+    fn fuzzy_score_matches() {
+        assert!(fuzzy_score("rtp", "retry policy").is_some());
+        assert!(fuzzy_score("RETRY", "retry policy").is_some());
+        assert!(fuzzy_score("", "retry policy").is_some());
+        assert!(fuzzy_score("pr", "retry policy").is_none());
+    }
+
+    #[test]
+    fn fuzzy_score_prefers_consecutive() {
+        assert!(fuzzy_score("pol", "retry policy") > fuzzy_score("pol", "pool list"));
+    }
+
+    #[test]
+    fn fit_width() {
+        assert_eq!(fit("abc", 5), "abc  ");
+        assert_eq!(fit("abcdef", 4), "abc…");
+        assert_eq!(fit("abcd", 4), "abcd");
+    }
+
+    #[test]
+    fn parse_keys() {
+        let key = |code| parse_key(&KeyEvent::new(code, KeyModifiers::NONE));
+        let control = |code| parse_key(&KeyEvent::new(code, KeyModifiers::CONTROL));
+        assert_eq!(key(KeyCode::Up), Key::Up);
+        assert_eq!(key(KeyCode::Esc), Key::Quit);
+        assert_eq!(key(KeyCode::Enter), Key::Enter);
+        assert_eq!(key(KeyCode::Char('é')), Key::Text("é".to_owned()));
+        assert_eq!(control(KeyCode::Char('c')), Key::Quit);
+        assert_eq!(control(KeyCode::Right), Key::Other);
+        assert_eq!(control(KeyCode::Char('x')), Key::Other);
+    }
+
+    #[test]
+    fn browser_filter() {
+        let index = index();
+        let mut browser = Browser::new(&index);
+        assert_eq!(browser.tags.len(), 3);
+
+        browser.query = "retry path:src/".to_owned();
+        browser.filter();
+        assert_eq!(browser.tags, vec!["retry policy"]);
+        assert_eq!(browser.entries().len(), 2);
+
+        browser.query = "ns:security".to_owned();
+        browser.filter();
+        assert_eq!(browser.tags, vec!["security/input-validation"]);
+    }
+
+    #[test]
+    fn browser_open() {
+        let index = index();
+        let mut browser = Browser::new(&index);
+        for character in ["p", "o", "l"] {
+            browser.handle(Key::Text(character.to_owned()), 10);
+        }
+        browser.handle(Key::Tab, 10);
+        browser.handle(Key::Down, 10);
+
+        assert_eq!(
+            browser.handle(Key::Enter, 10),
+            Action::Open(PathBuf::from("./src/client.rs"), 1),
+        );
+        assert_eq!(browser.handle(Key::Quit, 10), Action::Quit);
+    }
+}
@@ -0,0 +1,312 @@
+use crate::{
+    edits::{Edit, Edits, splice},
+    moves::{self, Move},
+    walk,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fmt::Write,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+// This is the name of the undo journal.
+pub const DEFAULT_PATH: &str = ".tagref-undo.json";
+
+// This struct records how to revert the edits to a file: a hash of its contents after the edits,
+// so later changes are noticed, and edits which restore the text that was replaced.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+struct File {
+    path: PathBuf,
+    sha256: String,
+    edits: Vec<Edit>,
+}
+
+// A journal records the last command which rewrote files, so it can be undone. Edited files are
+// recorded where they ended up after any moves.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Journal {
+    pub command: String,
+    files: Vec<File>,
+    moves: Vec<Move>,
+}
+
+// This function computes the hash of the contents of a file.
+fn digest(contents: &str) -> String {
+    Sha256::digest(contents.as_bytes())
+        .iter()
+        .fold(String::new(), |mut hex, byte| {
+            // Writing to a string can't fail, so the `unwrap` is safe.
+            write!(hex, "{byte:02x}").unwrap();
+            hex
+        })
+}
+
+impl Journal {
+    // This function records the edits which a command applied, and the moves it performed after
+    // applying them.
+    #[must_use]
+    pub fn new(command: &str, edits: &Edits, moves: &[Move]) -> Self {
+        let files = edits
+            .files()
+            .map(|(path, contents, edits)| {
+                // Each edit is inverted by replacing what it inserted with what it replaced.
+                let mut inverse = Vec::with_capacity(edits.len());
+                let (mut position, mut new_position) = (0, 0);
+                for edit in edits {
+                    new_position += edit.range.start - position;
+                    inverse.push(Edit {
+                        range: new_position..new_position + edit.replacement.len(),
+                        replacement: contents[edit.range.clone()].to_owned(),
+                    });
+                    new_position += edit.replacement.len();
+                    position = edit.range.end;
+                }
+
+                let path = walk::normalize(path);
+                File {
+                    path: moves::relocate(&path, moves).unwrap_or(path),
+                    sha256: digest(&splice(contents, edits)),
+                    edits: inverse,
+                }
+            })
+            .collect();
+
+        Self {
+            command: command.to_owned(),
+            files,
+            moves: moves.to_vec(),
+        }
+    }
+
+    // This function determines whether the journal has nothing to undo.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty() && self.moves.is_empty()
+    }
+
+    // This function returns the moves which revert the ones which were performed, in the order
+    // they should be performed.
+    #[must_use]
+    pub fn inverse_moves(&self) -> Vec<Move> {
+        self.moves
+            .iter()
+            .rev()
+            .map(|r#move| Move {
+                from: r#move.to.clone(),
+                to: r#move.from.clone(),
+            })
+            .collect()
+    }
+
+    // This function reads the journal from a file, if it exists.
+    pub fn load(path: &Path) -> Result<Option<Self>, String> {
+        match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents).map(Some).map_err(|error| {
+                format!(
+                    "Error when parsing undo journal {}: {error}",
+                    path.to_string_lossy(),
+                )
+            }),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(format!(
+                "Error when reading undo journal {}: {error}",
+                path.to_string_lossy(),
+            )),
+        }
+    }
+
+    // This function writes the journal to a file, replacing the previous one.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        // Serializing plain data to a string can't fail, so the `unwrap` is safe.
+        fs::write(path, serde_json::to_string_pretty(self).unwrap() + "\n").map_err(|error| {
+            format!(
+                "Unable to write undo journal {}: {error}",
+                path.to_string_lossy(),
+            )
+        })
+    }
+
+    // This function determines the edits which revert the files, after checking that nothing
+    // changed since the command ran. The moves should be reverted after applying them, in reverse
+    // order.
+    pub fn revert(&self) -> Result<Edits, String> {
+        let changed = |path: &Path| {
+            format!(
+                "{} changed after `tagref {}` ran, so it can't be undone.",
+                path.to_string_lossy(),
+                self.command,
+            )
+        };
+
+        for r#move in &self.moves {
+            if !r#move.to.exists() || r#move.from.exists() {
+                return Err(changed(&r#move.to));
+            }
+        }
+
+        let mut edits = Edits::default();
+        for file in &self.files {
+            let contents = match fs::read_to_string(&file.path) {
+                Ok(contents) if digest(&contents) == file.sha256 => contents,
+                Ok(_) => return Err(changed(&file.path)),
+                Err(error) => {
+                    return Err(format!(
+                        "Error when reading {}: {error}",
+                        file.path.to_string_lossy(),
+                    ));
+                }
+            };
+            edits.insert(&file.path, contents, file.edits.clone());
+        }
+
+        Ok(edits)
+    }
+}
+
+// This function applies the edits made by a command which rewrites files, and performs any moves
+// after them, calling `on_move` after each one. What was done is recorded in the journal at the
+// given path, even if some of the files couldn't be rewritten or some of the moves failed. The
+// moves are only performed if all the files were rewritten.
+pub fn apply(
+    command: &str,
+    edits: &Edits,
+    moves: &[Move],
+    path: &Path,
+    mut on_move: impl FnMut(&Move),
+) -> Result<(), String> {
+    let (applied, result) = edits.apply_partially();
+    let mut performed = Vec::new();
+    let result = result.and_then(|()| {
+        moves.iter().try_for_each(|r#move| {
+            r#move.perform()?;
+            on_move(r#move);
+            performed.push(r#move.clone());
+            Ok(())
+        })
+    });
+    let journal = Journal::new(command, &applied, &performed);
+    if !journal.is_empty() {
+        journal.save(path)?;
+    }
+    result
+}
+
+// This function applies the edits and moves which revert a journal (see `Journal::revert` and
+// `Journal::inverse_moves`), calling `on_move` after each move. The journal at the given path is
+// removed afterward, so the same command can't be undone twice.
+pub fn undo(
+    edits: &Edits,
+    moves: &[Move],
+    path: &Path,
+    mut on_move: impl FnMut(&Move),
+) -> Result<(), String> {
+    edits.apply()?;
+    for r#move in moves {
+        r#move.perform()?;
+        on_move(r#move);
+    }
+
+    fs::remove_file(path)
+        .map_err(|error| format!("Unable to remove {}: {error}", path.to_string_lossy()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        edits::{Edit, Edits},
+        undo::{self, Journal},
+    };
+    use std::{
+        env, fs,
+        path::PathBuf,
+        process,
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    fn temporary_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!(
+            "tagref-undo-{name}-{}-{}",
+            process::id(),
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos(),
+        ))
+    }
+
+    // This function renames `foo` to `quux` in a file, and records the edits.
+    fn rename(path: &PathBuf, contents: &str) -> Journal {
+        fs::write(path, contents).unwrap();
+        let mut edits = Edits::default();
+        edits.insert(
+            path,
+            contents.to_owned(),
+            contents
+                .match_indices("foo")
+                .map(|(start, old)| Edit {
+                    range: start..start + old.len(),
+                    replacement: "quux".to_owned(),
+                })
+                .collect(),
+        );
+        edits.apply().unwrap();
+        Journal::new("rename", &edits, &[])
+    }
+
+    #[test]
+    fn revert_edits() {
+        let path = temporary_path("revert");
+        let journal = rename(&path, "foo bar\nbaz foo\n");
+
+        let renamed = fs::read_to_string(&path).unwrap();
+        journal.revert().unwrap().apply().unwrap();
+        let reverted = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(renamed, "quux bar\nbaz quux\n");
+        assert_eq!(reverted, "foo bar\nbaz foo\n");
+    }
+
+    #[test]
+    fn revert_changed() {
+        let path = temporary_path("changed");
+        let journal = rename(&path, "foo\n");
+
+        fs::write(&path, "quux\nmore\n").unwrap();
+        let result = journal.revert();
+        fs::remove_file(&path).unwrap();
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn apply_then_undo() {
+        let path = temporary_path("apply");
+        let journal_path = temporary_path("apply-journal");
+        fs::write(&path, "foo\n").unwrap();
+        let mut edits = Edits::default();
+        edits.insert(
+            &path,
+            "foo\n".to_owned(),
+            vec![Edit {
+                range: 0..3,
+                replacement: "quux".to_owned(),
+            }],
+        );
+
+        undo::apply("rename", &edits, &[], &journal_path, |_| {}).unwrap();
+        let applied = fs::read_to_string(&path).unwrap();
+        let journal = Journal::load(&journal_path).unwrap().unwrap();
+        undo::undo(&journal.revert().unwrap(), &[], &journal_path, |_| {}).unwrap();
+        let reverted = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(applied, "quux\n");
+        assert_eq!(journal.command, "rename");
+        assert_eq!(reverted, "foo\n");
+        assert!(!journal_path.exists());
+    }
+}
@@ -0,0 +1,123 @@
+use crate::{
+    directive::{self, Directive},
+    namespaces,
+    violation::{Kind, Severity, Violation},
+};
+use std::collections::{BTreeMap, HashSet};
+
+// This function determines whether a tag is allowed to be unreferenced. Each entry in the
+// allowlist is either a label or a namespace, which covers all the tags in it. The label is keyed
+// as in the index.
+fn allowed(allowlist: &[String], label: &str, case_insensitive_labels: bool) -> bool {
+    allowlist.iter().any(|entry| {
+        let entry = directive::key(entry, case_insensitive_labels);
+        entry == label || namespaces::contains(&entry, label)
+    })
+}
+
+// This function finds the tags which aren't referenced anywhere, except for the ones in the
+// allowlist. The tags are keyed as in the index.
+#[must_use]
+pub fn find<'a>(
+    tags_map: &'a BTreeMap<String, Vec<Directive>>,
+    refs: &[Directive],
+    allowlist: &[String],
+    case_insensitive_labels: bool,
+) -> Vec<&'a Directive> {
+    let referenced = refs
+        .iter()
+        .map(|r#ref| directive::key(&r#ref.label, case_insensitive_labels))
+        .collect::<HashSet<_>>();
+
+    tags_map
+        .iter()
+        .filter(|(label, _)| {
+            !referenced.contains(label.as_str())
+                && !allowed(allowlist, label, case_insensitive_labels)
+        })
+        .flat_map(|(_, tags)| tags)
+        .collect()
+}
+
+// This function checks that every tag is referenced somewhere, except for the ones in the
+// allowlist. It returns a vector of violations.
+#[must_use]
+pub fn check(
+    tags_map: &BTreeMap<String, Vec<Directive>>,
+    refs: &[Directive],
+    allowlist: &[String],
+    case_insensitive_labels: bool,
+) -> Vec<Violation> {
+    find(tags_map, refs, allowlist, case_insensitive_labels)
+        .into_iter()
+        .map(|tag| Violation {
+            kind: Kind::UnusedTag,
+            severity: Severity::Error,
+            message: format!("{tag} is not referenced anywhere."),
+            directives: vec![tag.clone()],
+            suggestions: Vec::new(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::{Directive, Type},
+        unused::{check, find},
+        violation::Kind,
+    };
+    use std::collections::BTreeMap;
+
+    fn tags_map(labels: &[&str]) -> BTreeMap<String, Vec<Directive>> {
+        labels
+            .iter()
+            .map(|label| {
+                (
+                    (*label).to_owned(),
+                    vec![Directive::new(Type::Tag, label, "./foo.rs", 1)],
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn find_unreferenced() {
+        let tags_map = tags_map(&["foo", "bar"]);
+        let refs = [Directive::new(Type::Ref, "foo", "./foo.rs", 1)];
+
+        let unused = find(&tags_map, &refs, &[], false);
+
+        assert_eq!(unused.len(), 1);
+        assert_eq!(unused[0].label, "bar");
+    }
+
+    #[test]
+    fn find_case_insensitive() {
+        let tags_map = tags_map(&["foo", "bar"]);
+        let refs = [Directive::new(Type::Ref, "FOO", "./foo.rs", 1)];
+
+        assert_eq!(find(&tags_map, &refs, &[], false).len(), 2);
+        assert_eq!(find(&tags_map, &refs, &["Bar".to_owned()], true).len(), 0);
+    }
+
+    #[test]
+    fn find_allowlist() {
+        let tags_map = tags_map(&["foo", "bar/baz", "barbaz", "qux"]);
+
+        let unused = find(&tags_map, &[], &["foo".to_owned(), "bar".to_owned()], false);
+
+        assert_eq!(
+            unused.iter().map(|tag| &tag.label).collect::<Vec<_>>(),
+            vec!["barbaz", "qux"],
+        );
+    }
+
+    #[test]
+    fn check_unreferenced() {
+        let violations = check(&tags_map(&["foo"]), &[], &[], false);
+
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].kind, Kind::UnusedTag);
+    }
+}
@@ -0,0 +1,111 @@
+use crate::{
+    directive::Directive,
+    violation::{Kind, Severity, Violation},
+};
+use std::{collections::BTreeMap, sync::Mutex, thread, time::Duration};
+use ureq::{Agent, Error};
+
+// This is how long to wait for each URL to respond, including any redirects.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+// This is the maximum number of requests in flight at once.
+const CONCURRENCY: usize = 8;
+
+// This function sends a request to the given URL and returns an error if it doesn't respond
+// successfully. A `HEAD` request is tried first to avoid downloading the body, but some servers
+// don't support that, so we fall back to `GET` in that case.
+fn request(agent: &Agent, url: &str) -> Result<(), String> {
+    match agent.head(url).call() {
+        Ok(_) => Ok(()),
+        Err(Error::StatusCode(405 | 501)) => agent
+            .get(url)
+            .call()
+            .map(|_| ())
+            .map_err(|error| error.to_string()),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+// This function checks that URL references point to URLs which respond successfully. Each URL is
+// only requested once, no matter how many times it's referenced. It returns a vector of
+// violations.
+pub fn check(urls: &[Directive]) -> Vec<Violation> {
+    // Group the references by URL.
+    let mut references = BTreeMap::<&str, Vec<&Directive>>::new();
+    for url in urls {
+        references.entry(&url.label).or_default().push(url);
+    }
+
+    // Request the URLs in parallel, with a fixed number of threads taking them from a shared queue.
+    let agent: Agent = Agent::config_builder()
+        .timeout_global(Some(TIMEOUT))
+        .build()
+        .into();
+    let queue = Mutex::new(references.into_iter());
+    let errors = Mutex::new(Vec::<Violation>::new());
+    thread::scope(|scope| {
+        for _ in 0..CONCURRENCY {
+            scope.spawn(|| {
+                loop {
+                    // The `unwrap` is safe assuming no poisoning.
+                    let next = queue.lock().unwrap().next();
+                    let Some((label, directives)) = next else {
+                        break;
+                    };
+
+                    if let Err(error) = request(&agent, label) {
+                        // The `unwrap` is safe assuming no poisoning.
+                        errors
+                            .lock()
+                            .unwrap()
+                            .extend(directives.into_iter().map(|url| Violation {
+                                kind: Kind::UnreachableUrl,
+                                severity: Severity::Error,
+                                message: format!("Unable to reach {url}: {error}"),
+                                directives: vec![url.clone()],
+                                suggestions: Vec::new(),
+                            }));
+                    }
+                }
+            });
+        }
+    });
+
+    // Report the errors in a deterministic order. The `unwrap` is safe assuming no poisoning.
+    let mut errors = errors.into_inner().unwrap();
+    errors.sort_by(|x, y| {
+        let (x, y) = (&x.directives[0], &y.directives[0]);
+        (&x.path, x.line_number, &x.label).cmp(&(&y.path, y.line_number, &y.label))
+    });
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::{Directive, Type},
+        url_references::check,
+        violation::Kind,
+    };
+    use std::path::Path;
+
+    #[test]
+    fn check_empty() {
+        assert!(check(&[]).is_empty());
+    }
+
+    #[test]
+    fn check_invalid() {
+        let urls = vec![
+            Directive::new(Type::Url, "not a url", "file2.rs", 1),
+            Directive::new(Type::Url, "not a url", "file1.rs", 2),
+        ];
+
+        let violations = check(&urls);
+
+        assert_eq!(violations.len(), 2);
+        assert_eq!(violations[0].kind, Kind::UnreachableUrl);
+        assert_eq!(&*violations[0].directives[0].path, Path::new("file1.rs"));
+        assert_eq!(&*violations[1].directives[0].path, Path::new("file2.rs"));
+    }
+}
@@ -0,0 +1,183 @@
+use crate::{
+    directive::Directive,
+    violation::{Kind, Severity, Violation},
+};
+use serde::Deserialize;
+use std::{
+    io::Write,
+    process::{Command as Process, Stdio},
+    thread,
+};
+
+// This struct is an external validator: a command which enforces a policy that Tagref has no rule
+// for (e.g., that the tags in some directory reference an epic in an issue tracker). The labels are
+// written to its standard input as a JSON array, in the same form as `--format json` lists them,
+// and it writes a JSON array of the violations it finds to its standard output.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Command {
+    // The program to run, followed by its arguments
+    pub command: Vec<String>,
+}
+
+// This struct is a violation as reported by a validator. Only the message is required. The labels
+// involved can be copied from the input, and other fields (e.g., `kind`) are ignored, so a
+// validator can also report violations in the form `check --format json` prints them.
+#[derive(Deserialize)]
+struct Reported {
+    message: String,
+
+    #[serde(default)]
+    severity: Option<Severity>,
+
+    #[serde(default)]
+    directives: Vec<Directive>,
+
+    #[serde(default)]
+    suggestions: Vec<String>,
+}
+
+// This function runs a validator on the labels, which are already serialized. It returns the
+// violations the validator reported, or an explanation if it couldn't be run or its output
+// couldn't be read. The violations are reported regardless of the exit status, but a validator
+// which fails without reporting any is an error.
+fn run(validator: &Command, input: &str) -> Result<Vec<Reported>, String> {
+    let (program, args) = validator
+        .command
+        .split_first()
+        .ok_or_else(|| "The command is empty.".to_owned())?;
+
+    let mut child = Process::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| error.to_string())?;
+
+    // The input is written from another thread so the command doesn't block on a full output
+    // pipe. The streams were requested above, so the `unwrap` is safe.
+    let mut stdin = child.stdin.take().unwrap();
+    let input = input.to_owned();
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child
+        .wait_with_output()
+        .map_err(|error| error.to_string())?;
+
+    // Commands may exit without reading all their input, so errors from writing it are ignored.
+    // The thread doesn't panic, so the `unwrap` is safe.
+    let _ = writer.join().unwrap();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let reported = if stdout.trim().is_empty() {
+        Vec::new()
+    } else {
+        serde_json::from_str::<Vec<Reported>>(&stdout)
+            .map_err(|error| format!("Unable to read the violations: {error}"))?
+    };
+    if !output.status.success() && reported.is_empty() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(if stderr.trim().is_empty() {
+            output.status.to_string()
+        } else {
+            stderr.trim().to_owned()
+        });
+    }
+
+    Ok(reported)
+}
+
+// This function runs the validators on the labels. It returns a vector of violations, including
+// one for each validator which failed.
+#[must_use]
+pub fn check<'a, I: IntoIterator<Item = &'a Directive>>(
+    labels: I,
+    validators: &[Command],
+) -> Vec<Violation> {
+    if validators.is_empty() {
+        return Vec::new();
+    }
+
+    // The labels are sorted so validators see them in the same order on every run.
+    let mut labels = labels.into_iter().collect::<Vec<_>>();
+    labels.sort_by(|x, y| x.cmp_position(y));
+
+    // Serializing plain data to a string can't fail, so the `unwrap` is safe.
+    let input = serde_json::to_string(&labels).unwrap();
+
+    let mut violations = Vec::new();
+    for validator in validators {
+        match run(validator, &input) {
+            Ok(reported) => {
+                violations.extend(reported.into_iter().map(|reported| Violation {
+                    kind: Kind::ExternalRule,
+                    severity: reported.severity.unwrap_or(Severity::Error),
+                    message: reported.message,
+                    directives: reported.directives,
+                    suggestions: reported.suggestions,
+                }));
+            }
+            Err(error) => violations.push(Violation {
+                kind: Kind::ExternalRule,
+                severity: Severity::Error,
+                message: format!(
+                    "The validator `{}` failed: {error}",
+                    validator.command.join(" "),
+                ),
+                directives: Vec::new(),
+                suggestions: Vec::new(),
+            }),
+        }
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::{Directive, Type},
+        validators::{Command, check},
+        violation::{Kind, Severity},
+    };
+
+    #[test]
+    fn check_reported() {
+        let tag = Directive {
+            column: 4,
+            span: 3..24,
+            ..Directive::new(Type::Tag, "payments-retry", "payments/retry.rs", 3)
+        };
+
+        // This validator echoes the label back in a violation.
+        let script = r#"
+            read -r labels
+            label="${labels#*\"label\":\"}"
+            echo "[{\"message\": \"${label%%\"*} has no epic.\", \"severity\": \"warning\"}]"
+        "#;
+        let validators = [
+            Command {
+                command: vec!["sh".to_owned(), "-c".to_owned(), script.to_owned()],
+            },
+            Command {
+                command: vec![
+                    "sh".to_owned(),
+                    "-c".to_owned(),
+                    "echo nope >&2; exit 1".to_owned(),
+                ],
+            },
+        ];
+
+        let violations = check([&tag], &validators);
+
+        assert_eq!(violations.len(), 2);
+        assert_eq!(violations[0].kind, Kind::ExternalRule);
+        assert_eq!(violations[0].severity, Severity::Warning);
+        assert_eq!(violations[0].message, "payments-retry has no epic.");
+        assert_eq!(violations[1].severity, Severity::Error);
+        assert_eq!(
+            violations[1].message,
+            "The validator `sh -c echo nope >&2; exit 1` failed: nope",
+        );
+    }
+}
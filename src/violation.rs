@@ -0,0 +1,174 @@
+use crate::directive::Directive;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    DuplicateTag,
+    DanglingRef,
+    InvalidFileRef,
+    InvalidDirRef,
+    UnreachableUrl,
+    InvalidTagName,
+    ForbiddenRef,
+    UnusedTag,
+    InvalidCustomLabel,
+    UndecodableFile,
+    NonconformingTagName,
+    DeprecatedAlias,
+    StalePin,
+    ExpiredLabel,
+    ClosedIssue,
+    TooFewRefs,
+    UnpairedTag,
+    UndocumentedTag,
+    ExternalRule,
+}
+
+impl Kind {
+    // All the kinds, in a fixed order. This is useful for enumerating rules in reports.
+    pub const ALL: [Self; 19] = [
+        Self::DuplicateTag,
+        Self::DanglingRef,
+        Self::InvalidFileRef,
+        Self::InvalidDirRef,
+        Self::UnreachableUrl,
+        Self::InvalidTagName,
+        Self::ForbiddenRef,
+        Self::UnusedTag,
+        Self::InvalidCustomLabel,
+        Self::UndecodableFile,
+        Self::NonconformingTagName,
+        Self::DeprecatedAlias,
+        Self::StalePin,
+        Self::ExpiredLabel,
+        Self::ClosedIssue,
+        Self::TooFewRefs,
+        Self::UnpairedTag,
+        Self::UndocumentedTag,
+        Self::ExternalRule,
+    ];
+
+    // This is the stable identifier of the kind, matching its serialized form.
+    #[must_use]
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::DuplicateTag => "duplicate_tag",
+            Self::DanglingRef => "dangling_ref",
+            Self::InvalidFileRef => "invalid_file_ref",
+            Self::InvalidDirRef => "invalid_dir_ref",
+            Self::UnreachableUrl => "unreachable_url",
+            Self::InvalidTagName => "invalid_tag_name",
+            Self::ForbiddenRef => "forbidden_ref",
+            Self::UnusedTag => "unused_tag",
+            Self::InvalidCustomLabel => "invalid_custom_label",
+            Self::UndecodableFile => "undecodable_file",
+            Self::NonconformingTagName => "nonconforming_tag_name",
+            Self::DeprecatedAlias => "deprecated_alias",
+            Self::StalePin => "stale_pin",
+            Self::ExpiredLabel => "expired_label",
+            Self::ClosedIssue => "closed_issue",
+            Self::TooFewRefs => "too_few_refs",
+            Self::UnpairedTag => "unpaired_tag",
+            Self::UndocumentedTag => "undocumented_tag",
+            Self::ExternalRule => "external_rule",
+        }
+    }
+
+    // This is a one-line description of the rule that the kind represents.
+    #[must_use]
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::DuplicateTag => "Tags must be unique.",
+            Self::DanglingRef => "Tag references must point to tags.",
+            Self::InvalidFileRef => "File references must point to files.",
+            Self::InvalidDirRef => "Directory references must point to directories.",
+            Self::UnreachableUrl => "URL references must point to reachable URLs.",
+            Self::InvalidTagName => "Tags must follow the naming conventions of their namespaces.",
+            Self::ForbiddenRef => {
+                "References to tags in restricted namespaces must come from allowed paths."
+            }
+            Self::UnusedTag => "Tags must be referenced.",
+            Self::InvalidCustomLabel => "Custom labels must pass the validators for their kinds.",
+            Self::UndecodableFile => "Files must be decodable as text.",
+            Self::NonconformingTagName => "Tags must follow the naming conventions.",
+            Self::DeprecatedAlias => "Tag references must use the labels of tags, not aliases.",
+            Self::StalePin => "Pinned tag references must match the current content of their tags.",
+            Self::ExpiredLabel => "Labels must not be past their expiry dates.",
+            Self::ClosedIssue => "Issue labels must refer to open issues.",
+            Self::TooFewRefs => "Tags must have at least as many references as they require.",
+            Self::UnpairedTag => "Tags in paired paths must be referenced from the other path.",
+            Self::UndocumentedTag => "Tags must have a section in the documentation.",
+            Self::ExternalRule => "Labels must pass the external validators.",
+        }
+    }
+}
+
+// Errors cause the check to fail, whereas warnings and informational notes are only reported.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    #[serde(alias = "warn")]
+    Warning,
+    Info,
+}
+
+// A violation is a problem found by one of the checks, along with the directives involved.
+#[derive(Clone, Debug, Serialize)]
+pub struct Violation {
+    pub kind: Kind,
+    pub severity: Severity,
+    pub message: String,
+    pub directives: Vec<Directive>,
+
+    // The labels which might have been meant instead, if any
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<String>,
+}
+
+// The human-readable form of a violation is just its message.
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Violation {
+    // This function rewrites the paths of the directives involved, including where the message
+    // mentions them.
+    pub fn map_paths<F: FnMut(&Path) -> PathBuf>(&mut self, mut f: F) {
+        for directive in &mut self.directives {
+            let before = directive.to_string();
+            directive.path = f(&directive.path).into();
+            self.message = self.message.replace(&before, &directive.to_string());
+        }
+    }
+}
+
+// This function sorts violations by the first directive involved in each one (see
+// `Directive::cmp_position`), with the violations which don't involve any directives first. Ties
+// are broken by kind and message, so the order is the same on every run.
+pub fn sort(violations: &mut [Violation]) {
+    violations.sort_by(|x, y| {
+        match (x.directives.first(), y.directives.first()) {
+            (Some(x), Some(y)) => x.cmp_position(y),
+            (x, y) => x.is_some().cmp(&y.is_some()),
+        }
+        .then_with(|| x.kind.cmp(&y.kind))
+        .then_with(|| x.message.cmp(&y.message))
+    });
+}
+
+// This function treats warnings as errors, e.g., for `--fail-on-warn`.
+pub fn fail_on_warn(violations: &mut [Violation]) {
+    for violation in violations {
+        if violation.severity == Severity::Warning {
+            violation.severity = Severity::Error;
+        }
+    }
+}
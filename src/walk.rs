@@ -1,59 +1,551 @@
-use ignore::{WalkBuilder, WalkState, overrides::OverrideBuilder};
+use crate::{archives, baseline, cache, count, drop_ins::DropIns, undo};
+use ignore::{
+    WalkBuilder, WalkState,
+    overrides::{Override, OverrideBuilder},
+};
+use rayon::{ThreadPoolBuilder, prelude::*};
 use std::{
-    fs::File,
-    path::{Path, PathBuf},
+    collections::{HashMap, HashSet},
+    fs::{self, File, Metadata},
+    io::ErrorKind,
+    mem::take,
+    path::{Component, Path, PathBuf},
     sync::{
-        Arc,
+        Arc, Mutex,
         atomic::{AtomicUsize, Ordering},
     },
 };
 
+// This is the size of the largest files which are scanned by default, in bytes. Anything larger is
+// unlikely to be written by hand.
+pub const DEFAULT_MAX_FILESIZE: u64 = 5 * 1024 * 1024;
+
+// This struct holds the settings which control which files are visited.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    // If nonempty, only files matching at least one of these globs are visited.
+    pub include: Vec<String>,
+
+    // Files and directories matching any of these globs are skipped.
+    pub exclude: Vec<String>,
+
+    // If set, files ignored by `.gitignore`, `.ignore`, and similar files are visited too.
+    pub no_ignore: bool,
+
+    // If set, symlinks are followed rather than skipped. Each file is still only visited once,
+    // even if several symlinks point to it.
+    pub follow_symlinks: bool,
+
+    // The drop-in files which adjust the settings for their directories, loaded as they're found
+    pub drop_ins: Arc<DropIns>,
+
+    // Files larger than this many bytes are skipped, if it's set.
+    pub max_filesize: Option<u64>,
+}
+
+// This struct describes the files which were visited by a walk.
+#[derive(Clone, Debug, Default)]
+pub struct Walked {
+    pub files_scanned: usize,
+
+    // The files which were skipped for being larger than the limit, in order of path
+    pub files_too_large: Vec<PathBuf>,
+}
+
+// This function parses a file size in bytes, optionally followed by `K`, `M`, or `G` for kibibytes,
+// mebibytes, or gibibytes, as in `512K` or `5M`.
+pub fn parse_size(text: &str) -> Result<u64, String> {
+    let (number, multiplier) = match text.strip_suffix(['K', 'k', 'M', 'm', 'G', 'g']) {
+        Some(number) => (
+            number,
+            match text[number.len()..].to_ascii_uppercase().as_str() {
+                "K" => 1024,
+                "M" => 1024 * 1024,
+                _ => 1024 * 1024 * 1024,
+            },
+        ),
+        None => (text, 1),
+    };
+    number
+        .parse::<u64>()
+        .map(|number| number.saturating_mul(multiplier))
+        .map_err(|_| {
+            format!("Invalid size `{text}`. Expected a number of bytes, like `512K` or `5M`.")
+        })
+}
+
+// This function drops any leading `./` components from a path visited by the walk, so it can be
+// compared with paths written in labels or configuration files.
+#[must_use]
+pub fn relative(path: &Path) -> PathBuf {
+    path.components()
+        .skip_while(|component| *component == Component::CurDir)
+        .collect()
+}
+
+// This function resolves the `.` and `..` components of a path without consulting the filesystem.
+// Resolving `..` after following a symlink to a directory would lead to the parent of the symlink's
+// target rather than the directory containing the symlink, which isn't what the author of a label
+// means.
+#[must_use]
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = Vec::<Component>::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(normalized.last(), Some(Component::Normal(_))) => {
+                normalized.pop();
+            }
+            _ => normalized.push(component),
+        }
+    }
+
+    if normalized.is_empty() {
+        PathBuf::from(".")
+    } else {
+        normalized.iter().collect()
+    }
+}
+
+// This function finds the root containing a file or directory, if any. If roots are nested, the
+// innermost one wins.
+#[must_use]
+pub fn root<'a>(path: &Path, roots: &'a [PathBuf]) -> Option<&'a Path> {
+    let path = normalize(path);
+    roots
+        .iter()
+        .filter_map(|root| {
+            let normalized = normalize(root);
+            if normalized == Path::new(".") {
+                Some((0, root.as_path()))
+            } else {
+                path.starts_with(&normalized)
+                    .then(|| (normalized.components().count(), root.as_path()))
+            }
+        })
+        .max_by_key(|(depth, _)| *depth)
+        .map(|(_, root)| root)
+}
+
+// This function resolves the path in a file or directory reference written in the file at
+// `source`. Paths are relative to the archive containing `source` (see `archives::root`), the root
+// containing it (see `root`), or else the working directory, except that if `relative_paths` is
+// set, paths starting with `./` or `../` are relative to the directory containing `source`
+// instead. Backslashes are treated as
+// separators on every platform, so references written on Windows work elsewhere.
+#[must_use]
+pub fn resolve(path: &str, source: &Path, relative_paths: bool, roots: &[PathBuf]) -> PathBuf {
+    let path = path.replace('\\', "/");
+    if relative_paths && is_relative(&path) {
+        normalize(&source.parent().unwrap_or(Path::new("")).join(path))
+    } else if let Some(archive) = archives::root(source) {
+        normalize(&archive.join(path))
+    } else {
+        normalize(&root(source, roots).unwrap_or(Path::new("")).join(path))
+    }
+}
+
+// This function determines whether the path in a file or directory reference starts with `./` or
+// `../`.
+#[must_use]
+pub fn is_relative(path: &str) -> bool {
+    matches!(
+        Path::new(&path.replace('\\', "/")).components().next(),
+        Some(Component::CurDir | Component::ParentDir),
+    )
+}
+
+// This function expresses a path as a reference written in a file in the `base` directory would,
+// starting with `./` or `../`. Both paths must be normalized and relative to the working directory.
+#[must_use]
+pub fn relative_to(path: &Path, base: &Path) -> PathBuf {
+    let path = path
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .collect::<Vec<_>>();
+    let base = base
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .collect::<Vec<_>>();
+    let common = path
+        .iter()
+        .zip(&base)
+        .take_while(|(path, base)| path == base)
+        .count();
+
+    let mut relative = PathBuf::new();
+    if common == base.len() {
+        relative.push(".");
+    }
+    for _ in common..base.len() {
+        relative.push("..");
+    }
+    relative.extend(&path[common..]);
+    relative
+}
+
+// These are the ways to report the paths of scanned files.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum PathStyle {
+    // Paths are reported as they were visited, starting with the path which was scanned.
+    #[default]
+    Scanned,
+
+    // Paths are relative to the scanned path (or root) containing them.
+    Root,
+
+    // Paths are relative to the working directory, starting with `../` if they're outside it.
+    WorkingDirectory,
+
+    // Paths are absolute.
+    Absolute,
+}
+
+// This function rewrites the path of a scanned file for reporting in the given style. The `roots`
+// are the paths which were scanned, and the working directory must be absolute. The filesystem
+// isn't consulted, so symlinks aren't resolved.
+#[must_use]
+pub fn report(
+    path: &Path,
+    style: PathStyle,
+    roots: &[PathBuf],
+    working_directory: &Path,
+) -> PathBuf {
+    match style {
+        PathStyle::Scanned => path.to_owned(),
+        PathStyle::Root => {
+            let normalized = normalize(path);
+            let Some(root) = root(path, roots).map(normalize) else {
+                return normalized;
+            };
+            match normalized.strip_prefix(&root) {
+                // A scanned file is reported by its name.
+                Ok(relative) if relative.as_os_str().is_empty() => normalized
+                    .file_name()
+                    .map_or(normalized.clone(), PathBuf::from),
+                Ok(relative) => relative.to_owned(),
+                Err(_) => normalized,
+            }
+        }
+        PathStyle::WorkingDirectory => {
+            let absolute = normalize(&working_directory.join(path));
+
+            // Paths on another drive (on Windows) can't be relative to the working directory.
+            if absolute.components().next() != working_directory.components().next() {
+                return absolute;
+            }
+            relative(&relative_to(&absolute, working_directory))
+        }
+        PathStyle::Absolute => normalize(&working_directory.join(path)),
+    }
+}
+
+// This function checks that each component of a path has the same case as the file or directory
+// on disk. Case-insensitive filesystems (as on macOS and Windows) find the file either way, but a
+// reference with the wrong case would break on a case-sensitive filesystem. Components which can't
+// be looked up are skipped, since missing paths are reported elsewhere.
+pub fn check_case(path: &Path) -> Result<(), String> {
+    let mut parent = PathBuf::new();
+    for component in path.components() {
+        if let Component::Normal(name) = component {
+            let directory = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent.as_path()
+            };
+            if let Ok(entries) = fs::read_dir(directory) {
+                let names = entries
+                    .filter_map(Result::ok)
+                    .map(|entry| entry.file_name())
+                    .collect::<Vec<_>>();
+                if !names.iter().any(|entry| entry == name) {
+                    let written = name.to_string_lossy();
+                    if let Some(actual) = names.iter().find(|entry| {
+                        entry.to_string_lossy().to_lowercase() == written.to_lowercase()
+                    }) {
+                        return Err(format!(
+                            "`{written}` is written as `{}` on disk",
+                            actual.to_string_lossy(),
+                        ));
+                    }
+                }
+            }
+        }
+        parent.push(component);
+    }
+
+    Ok(())
+}
+
+// This function looks up the file or directory that a reference points to, following any
+// symlinks. The error explains why the path can't be resolved.
+pub fn target_metadata(path: &Path) -> Result<Metadata, String> {
+    let path = normalize(path);
+    fs::metadata(&path).map_err(|error| {
+        // The operating system's error for a broken symlink doesn't mention the symlink.
+        let is_symlink = fs::symlink_metadata(&path).is_ok_and(|metadata| metadata.is_symlink());
+        if is_symlink && error.kind() == ErrorKind::NotFound {
+            format!("{} is a broken symlink", path.to_string_lossy())
+        } else {
+            error.to_string()
+        }
+    })
+}
+
+// This is the maximum number of threads looking up the targets of references at once.
+const LOOKUP_CONCURRENCY: usize = 8;
+
+// This struct is the result of looking up the file or directory that a reference points to.
+#[derive(Debug)]
+struct Target {
+    metadata: Result<Metadata, String>,
+
+    // Whether the path has the same case as on disk, if it was checked
+    case: Option<Result<(), String>>,
+}
+
+// This struct remembers the files and directories that references point to, so each path is only
+// looked up once. The paths are looked up on several threads, since each lookup may wait on the
+// disk (or the network, for network filesystems).
+#[derive(Debug, Default)]
+pub struct Targets {
+    targets: HashMap<PathBuf, Target>,
+}
+
+impl Targets {
+    // This function looks up the given paths (see `target_metadata`). If `strict_case` is set, it
+    // also checks that the paths which exist have the same case as on disk (see `check_case`).
+    #[must_use]
+    pub fn look_up<I: IntoIterator<Item = PathBuf>>(paths: I, strict_case: bool) -> Self {
+        let paths = paths.into_iter().collect::<HashSet<_>>();
+        let look_up = || {
+            paths
+                .into_par_iter()
+                .map(|path| {
+                    let metadata = target_metadata(&path);
+                    let case = (strict_case && metadata.is_ok()).then(|| check_case(&path));
+                    (path, Target { metadata, case })
+                })
+                .collect()
+        };
+
+        // The lookups mostly wait, so they get their own threads rather than tying up the ones
+        // which parse files. If the threads can't be started, the shared ones are used instead.
+        let targets = match ThreadPoolBuilder::new()
+            .num_threads(LOOKUP_CONCURRENCY)
+            .build()
+        {
+            Ok(pool) => pool.install(look_up),
+            Err(_) => look_up(),
+        };
+        Self { targets }
+    }
+
+    // This function returns the metadata of the file or directory that a path points to, looking
+    // it up now if it wasn't looked up already.
+    pub fn metadata(&self, path: &Path) -> Result<Metadata, String> {
+        self.targets
+            .get(path)
+            .map_or_else(|| target_metadata(path), |target| target.metadata.clone())
+    }
+
+    // This function checks that a path has the same case as on disk, checking it now if it wasn't
+    // checked already.
+    pub fn check_case(&self, path: &Path) -> Result<(), String> {
+        self.targets
+            .get(path)
+            .and_then(|target| target.case.clone())
+            .unwrap_or_else(|| check_case(path))
+    }
+}
+
+// This function builds the glob overrides for the walk. The globs use `.gitignore` syntax.
+fn overrides(options: &Options) -> Result<Override, String> {
+    let mut builder = OverrideBuilder::new("");
+    let invalid_glob = |glob: &str, error| format!("Invalid glob `{glob}`: {error}");
+
+    for glob in &options.include {
+        builder
+            .add(glob)
+            .map_err(|error| invalid_glob(glob, error))?;
+    }
+
+    // Tagref's own files are skipped too. The cache in particular contains the labels it records,
+    // which would otherwise be counted again on the next run.
+    for glob in options.exclude.iter().map(String::as_str).chain([
+        ".git/",
+        ".hg/",
+        cache::DEFAULT_PATH,
+        undo::DEFAULT_PATH,
+        baseline::DEFAULT_PATH,
+    ]) {
+        builder
+            .add(&format!("!{glob}"))
+            .map_err(|error| invalid_glob(glob, error))?;
+    }
+
+    builder
+        .build()
+        .map_err(|error| format!("Unable to build the file filters: {error}"))
+}
+
+// This function builds a filter which determines whether a file is included by the globs in the
+// options. It's for files which aren't on disk (e.g., files as of a Git revision), so ignore files,
+// drop-in files, and symlinks aren't considered. A file is excluded if any of the directories
+// containing it are, as the walk wouldn't descend into them.
+pub fn filter(options: &Options) -> Result<impl Fn(&Path) -> bool, String> {
+    let overrides = overrides(options)?;
+    Ok(move |path: &Path| {
+        let path = relative(path);
+        !overrides.matched(&path, false).is_ignore()
+            && !path
+                .ancestors()
+                .skip(1)
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .any(|dir| overrides.matched(dir, true).is_ignore())
+    })
+}
+
 // This function visits each file in the given directory and calls the given callback with the path
-// and the file. It skips files which cannot be read (e.g., due to lack of permissions). It also
-// skips over symlinks. The number of files traversed is returned.
+// and the file. It skips files which cannot be read (e.g., due to lack of permissions). Symlinks
+// are skipped unless the options say to follow them. The number of files traversed is returned.
+#[allow(clippy::too_many_lines)]
 pub fn walk<T: 'static + Clone + Send + FnMut(&Path, File)>(
     paths: &[PathBuf],
+    options: &Options,
     callback: T,
-) -> usize {
-    // Keep track of the number of files traversed, and allow multiple threads to update it.
+) -> Result<Walked, String> {
+    // Keep track of the number of files traversed and skipped, and allow multiple threads to
+    // update them.
     let files_scanned = Arc::new(AtomicUsize::new(0));
+    let files_skipped = Arc::new(AtomicUsize::new(0));
+    let files_too_large = Arc::new(Mutex::new(Vec::new()));
+
+    // Determine which files to skip.
+    let overrides = overrides(options)?;
+
+    // Paths excluded by drop-in files are skipped, as are paths under drop-in files which can't be
+    // loaded. Any such error is reported after the walk, since the filter can't stop it early.
+    let drop_in_error = Arc::new(Mutex::new(None::<String>));
+
+    // When following symlinks, the same file may be reachable by several paths. Keep track of the
+    // files visited so far, so each one is only processed once.
+    let visited = Arc::new(Mutex::new(HashSet::<PathBuf>::new()));
 
     // Scan each of the given paths.
     for path in paths {
+        let drop_ins = options.drop_ins.clone();
+        let drop_in_error_clone = drop_in_error.clone();
+
         // Traverse the filesystem in parallel.
         WalkBuilder::new(path)
             .hidden(false)
             .require_git(false)
-            .overrides(
-                OverrideBuilder::new("")
-                    .add("!.git/")
-                    .unwrap() // Safe by manual inspection
-                    .add("!.hg/")
-                    .unwrap() // Safe by manual inspection
-                    .build()
-                    .unwrap(), // Safe by manual inspection
-            )
+            .ignore(!options.no_ignore)
+            .git_ignore(!options.no_ignore)
+            .git_global(!options.no_ignore)
+            .git_exclude(!options.no_ignore)
+            .parents(!options.no_ignore)
+            .follow_links(options.follow_symlinks)
+            .overrides(overrides.clone())
+            .filter_entry(move |dir_entry| {
+                if dir_entry.depth() == 0 {
+                    return true;
+                }
+
+                let path = relative(dir_entry.path());
+                let is_dir = dir_entry
+                    .file_type()
+                    .is_some_and(|file_type| file_type.is_dir());
+                match drop_ins.settings_for(&path) {
+                    Ok(settings) => {
+                        let excluded = settings.is_excluded(&path, is_dir);
+                        if excluded {
+                            log::debug!(
+                                "Skipping {}, since it's excluded by a drop-in file.",
+                                path.display(),
+                            );
+                        }
+                        !excluded
+                    }
+                    Err(error) => {
+                        // The `unwrap` is safe assuming no poisoning.
+                        drop_in_error_clone.lock().unwrap().get_or_insert(error);
+                        false
+                    }
+                }
+            })
             .build_parallel()
             .run(|| {
                 // These clones will be moved into the closure below, and that closure will be sent
                 // to a new thread.
                 let mut callback = callback.clone();
                 let files_scanned = files_scanned.clone();
+                let files_skipped = files_skipped.clone();
+                let files_too_large = files_too_large.clone();
+                let visited = visited.clone();
+                let follow_symlinks = options.follow_symlinks;
+                let max_filesize = options.max_filesize;
 
                 // This closure will be sent to a new thread.
                 Box::new(move |result| {
-                    // Proceed if we have access to the path.
-                    if let Ok(dir_entry) = result {
-                        // Here, `file_type()` should always return a `Some`. It could only return
-                        // `None` if the file represents STDIN, and that isn't the case here.
-                        if dir_entry.file_type().unwrap().is_file() {
-                            // Try to open the file.
-                            let possible_file = File::open(dir_entry.path());
-                            if let Ok(file) = possible_file {
+                    // Proceed if we have access to the path. Symlink cycles are reported as errors
+                    // by the walk, so they're skipped here too.
+                    let dir_entry = match result {
+                        Ok(dir_entry) => dir_entry,
+                        Err(error) => {
+                            log::debug!("Skipping a path: {error}");
+                            files_skipped.fetch_add(1, Ordering::SeqCst);
+                            return WalkState::Continue;
+                        }
+                    };
+
+                    // Here, `file_type()` should always return a `Some`. It could only return
+                    // `None` if the file represents STDIN, and that isn't the case here.
+                    if dir_entry.file_type().unwrap().is_file() {
+                        let path = dir_entry.path().to_string_lossy();
+
+                        // Skip files which were already visited by another path. The `unwrap` is
+                        // safe assuming no poisoning.
+                        if follow_symlinks
+                            && let Ok(canonical_path) = fs::canonicalize(dir_entry.path())
+                            && !visited.lock().unwrap().insert(canonical_path)
+                        {
+                            log::debug!("Skipping {path}, since it was visited by another path.");
+                            return WalkState::Continue;
+                        }
+
+                        // Skip files which are too large. The `unwrap` is safe assuming no
+                        // poisoning.
+                        if let Some(max_filesize) = max_filesize
+                            && let Ok(metadata) = dir_entry.metadata()
+                            && metadata.len() > max_filesize
+                        {
+                            log::info!(
+                                "Skipping {path}, since it's larger than the limit of \
+                                 {max_filesize} bytes.",
+                            );
+                            files_too_large
+                                .lock()
+                                .unwrap()
+                                .push(dir_entry.path().to_owned());
+                            return WalkState::Continue;
+                        }
+
+                        // Try to open the file.
+                        match File::open(dir_entry.path()) {
+                            Ok(file) => {
                                 // Process the file and increment the counter.
+                                log::debug!("Scanning {path}.");
                                 callback(dir_entry.path(), file);
                                 files_scanned.fetch_add(1, Ordering::SeqCst);
                             }
+                            Err(error) => {
+                                log::debug!(
+                                    "Skipping {path}, since it couldn't be opened: {error}"
+                                );
+                                files_skipped.fetch_add(1, Ordering::SeqCst);
+                            }
                         }
                     }
 
@@ -63,6 +555,273 @@ pub fn walk<T: 'static + Clone + Send + FnMut(&Path, File)>(
             });
     }
 
-    // Return the number of files traversed.
-    files_scanned.load(Ordering::SeqCst)
+    // The `unwrap` is safe assuming no poisoning.
+    if let Some(error) = drop_in_error.lock().unwrap().take() {
+        return Err(error);
+    }
+
+    // Mention the paths which couldn't be read, since they may contain labels.
+    let files_skipped = files_skipped.load(Ordering::SeqCst);
+    if files_skipped > 0 {
+        log::info!(
+            "Skipped {} which couldn't be read.",
+            count::count(files_skipped, "path"),
+        );
+    }
+
+    // Mention the files which were too large too. The `unwrap` is safe assuming no poisoning.
+    let mut files_too_large = take(&mut *files_too_large.lock().unwrap());
+    files_too_large.sort();
+    if !files_too_large.is_empty() {
+        log::info!(
+            "Skipped {} larger than the limit.",
+            count::count(files_too_large.len(), "file"),
+        );
+    }
+
+    // Return the number of files traversed, along with the ones which were too large.
+    Ok(Walked {
+        files_scanned: files_scanned.load(Ordering::SeqCst),
+        files_too_large,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::walk::{
+        Options, PathStyle, Targets, check_case, filter, is_relative, normalize, parse_size,
+        relative, relative_to, report, resolve, root,
+    };
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn relative_strips_current_dir() {
+        assert_eq!(
+            relative(Path::new("./src/main.rs")),
+            PathBuf::from("src/main.rs")
+        );
+    }
+
+    #[test]
+    fn normalize_parent_dirs() {
+        assert_eq!(
+            normalize(Path::new("./src/link/../main.rs")),
+            PathBuf::from("src/main.rs"),
+        );
+    }
+
+    #[test]
+    fn normalize_leading_parent_dirs() {
+        assert_eq!(
+            normalize(Path::new("../a/../../b")),
+            PathBuf::from("../../b")
+        );
+    }
+
+    #[test]
+    fn normalize_empty() {
+        assert_eq!(normalize(Path::new("src/..")), PathBuf::from("."));
+    }
+
+    #[test]
+    fn resolve_from_working_directory() {
+        assert_eq!(
+            resolve("./sibling.rs", Path::new("./src/main.rs"), false, &[]),
+            PathBuf::from("sibling.rs"),
+        );
+    }
+
+    #[test]
+    fn resolve_relative() {
+        assert_eq!(
+            resolve("./sibling.rs", Path::new("./src/main.rs"), true, &[]),
+            PathBuf::from("src/sibling.rs"),
+        );
+        assert_eq!(
+            resolve("../README.md", Path::new("./src/main.rs"), true, &[]),
+            PathBuf::from("README.md"),
+        );
+    }
+
+    #[test]
+    fn resolve_relative_to_root() {
+        assert_eq!(
+            resolve("src/lib.rs", Path::new("./src/main.rs"), true, &[]),
+            PathBuf::from("src/lib.rs"),
+        );
+    }
+
+    #[test]
+    fn resolve_from_roots() {
+        let roots = [PathBuf::from("services/api"), PathBuf::from("services")];
+
+        assert_eq!(
+            root(Path::new("./services/api/main.rs"), &roots),
+            Some(Path::new("services/api")),
+        );
+        assert_eq!(root(Path::new("./tools/main.rs"), &roots), None);
+        assert_eq!(
+            resolve(
+                "src/lib.rs",
+                Path::new("./services/api/main.rs"),
+                true,
+                &roots
+            ),
+            PathBuf::from("services/api/src/lib.rs"),
+        );
+        assert_eq!(
+            resolve(
+                "./lib.rs",
+                Path::new("./services/api/main.rs"),
+                true,
+                &roots
+            ),
+            PathBuf::from("services/api/lib.rs"),
+        );
+        assert_eq!(
+            resolve("src/lib.rs", Path::new("./tools/main.rs"), false, &roots),
+            PathBuf::from("src/lib.rs"),
+        );
+    }
+
+    #[test]
+    fn resolve_in_archive() {
+        let source = Path::new("./dist/docs.zip!/guide/intro.md");
+
+        assert_eq!(
+            resolve("src/lib.rs", source, false, &[]),
+            PathBuf::from("dist/docs.zip!/src/lib.rs"),
+        );
+        assert_eq!(
+            resolve("./setup.md", source, true, &[]),
+            PathBuf::from("dist/docs.zip!/guide/setup.md"),
+        );
+    }
+
+    #[test]
+    fn resolve_backslashes() {
+        assert_eq!(
+            resolve("src\\main.rs", Path::new("./README.md"), false, &[]),
+            PathBuf::from("src/main.rs"),
+        );
+        assert_eq!(
+            resolve("..\\README.md", Path::new("./src/main.rs"), true, &[]),
+            PathBuf::from("README.md"),
+        );
+        assert!(is_relative(".\\sibling.rs"));
+    }
+
+    #[test]
+    fn check_case_correct() {
+        assert!(check_case(Path::new("src/walk.rs")).is_ok());
+        assert!(check_case(Path::new("./src/../src/walk.rs")).is_ok());
+    }
+
+    #[test]
+    fn check_case_incorrect() {
+        assert_eq!(
+            check_case(Path::new("SRC/walk.rs")),
+            Err("`SRC` is written as `src` on disk".to_owned()),
+        );
+        assert_eq!(
+            check_case(Path::new("src/Walk.rs")),
+            Err("`Walk.rs` is written as `walk.rs` on disk".to_owned()),
+        );
+    }
+
+    #[test]
+    fn targets_look_up() {
+        let targets = Targets::look_up(["src/walk.rs", "src/missing.rs"].map(PathBuf::from), true);
+
+        assert!(
+            targets
+                .metadata(Path::new("src/walk.rs"))
+                .unwrap()
+                .is_file()
+        );
+        assert!(targets.check_case(Path::new("src/walk.rs")).is_ok());
+        assert!(targets.metadata(Path::new("src/missing.rs")).is_err());
+        assert!(targets.metadata(Path::new("src")).unwrap().is_dir());
+    }
+
+    #[test]
+    fn check_case_missing() {
+        assert!(check_case(Path::new("src/missing.rs")).is_ok());
+    }
+
+    #[test]
+    fn relative_to_directories() {
+        assert_eq!(
+            relative_to(Path::new("src/a.rs"), Path::new("src")),
+            PathBuf::from("./a.rs"),
+        );
+        assert_eq!(
+            relative_to(Path::new("lib/a.rs"), Path::new("src/util")),
+            PathBuf::from("../../lib/a.rs"),
+        );
+        assert_eq!(
+            relative_to(Path::new("a.rs"), Path::new("")),
+            PathBuf::from("./a.rs"),
+        );
+    }
+
+    #[test]
+    fn report_styles() {
+        let roots = [PathBuf::from("./lib"), PathBuf::from("vendor/b.rs")];
+        let working_directory = Path::new("/home/me/project");
+        let report = |path, style| report(Path::new(path), style, &roots, working_directory);
+
+        assert_eq!(
+            report("./lib/src/a.rs", PathStyle::Scanned),
+            PathBuf::from("./lib/src/a.rs"),
+        );
+        assert_eq!(
+            report("./lib/src/a.rs", PathStyle::Root),
+            PathBuf::from("src/a.rs"),
+        );
+        assert_eq!(
+            report("vendor/b.rs", PathStyle::Root),
+            PathBuf::from("b.rs"),
+        );
+        assert_eq!(
+            report("./lib/src/a.rs", PathStyle::WorkingDirectory),
+            PathBuf::from("lib/src/a.rs"),
+        );
+        assert_eq!(
+            report("../other/c.rs", PathStyle::WorkingDirectory),
+            PathBuf::from("../other/c.rs"),
+        );
+        assert_eq!(
+            report("/home/me/project/lib/a.rs", PathStyle::WorkingDirectory),
+            PathBuf::from("lib/a.rs"),
+        );
+        assert_eq!(
+            report("./lib/src/a.rs", PathStyle::Absolute),
+            PathBuf::from("/home/me/project/lib/src/a.rs"),
+        );
+    }
+
+    #[test]
+    fn filter_globs() {
+        let options = Options {
+            include: vec!["*.rs".to_owned()],
+            exclude: vec!["vendor/".to_owned()],
+            ..Options::default()
+        };
+        let included = filter(&options).unwrap();
+
+        assert!(included(Path::new("./src/a.rs")));
+        assert!(!included(Path::new("./src/a.py")));
+        assert!(!included(Path::new("./vendor/b.rs")));
+        assert!(!included(Path::new("./.git/c.rs")));
+    }
+
+    #[test]
+    fn parse_size_units() {
+        assert_eq!(parse_size("100"), Ok(100));
+        assert_eq!(parse_size("512K"), Ok(524_288));
+        assert_eq!(parse_size("5m"), Ok(5_242_880));
+        assert_eq!(parse_size("1G"), Ok(1_073_741_824));
+        assert!(parse_size("5 MB").is_err());
+    }
 }
@@ -0,0 +1,63 @@
+// These are the exports of the WebAssembly module, which the JavaScript bindings in
+// `bindings/tagref.js` wrap. Strings are passed as UTF-8 in buffers which the caller allocates
+// with `tagref_alloc` and frees with `tagref_free`.
+
+use crate::bindings;
+use std::{ptr, slice};
+
+// The module has no clock of its own, so it imports one.
+#[link(wasm_import_module = "tagref")]
+unsafe extern "C" {
+    // This function returns the number of milliseconds since the Unix epoch, like `Date.now()`.
+    safe fn now() -> f64;
+}
+
+// This function returns the number of seconds since the Unix epoch.
+#[must_use]
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub fn seconds_since_epoch() -> u64 {
+    // Negative times (and `NaN`) saturate to zero, so the cast is well-defined.
+    (now() / 1000.0) as u64
+}
+
+// This function allocates a buffer of the given length, for passing a string to the module.
+#[unsafe(no_mangle)]
+pub extern "C" fn tagref_alloc(length: usize) -> *mut u8 {
+    Box::into_raw(vec![0_u8; length].into_boxed_slice()).cast::<u8>()
+}
+
+// This function frees a buffer allocated by `tagref_alloc` or returned by `tagref_check`.
+//
+// # Safety
+//
+// The buffer must have been allocated by the module with the given length, and not freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tagref_free(buffer: *mut u8, length: usize) {
+    // The caller guarantees that the buffer is a boxed slice of this length, so the `unsafe` block
+    // is sound.
+    drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(buffer, length)) });
+}
+
+// This function checks files, as `bindings::check` does. The request is read from a buffer. The
+// response is returned in a new buffer, prefixed with its length as a little-endian 32-bit
+// integer, which the caller must free (including the prefix).
+//
+// # Safety
+//
+// The request must be a buffer of the given length allocated by `tagref_alloc`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tagref_check(request: *const u8, length: usize) -> *mut u8 {
+    // The caller guarantees that the buffer is readable for this length, so the `unsafe` block is
+    // sound.
+    let request = String::from_utf8_lossy(unsafe { slice::from_raw_parts(request, length) });
+    let response = bindings::check(&request);
+
+    // Memory in WebAssembly is addressed with 32 bits, so the length fits and the `unwrap` is
+    // safe.
+    let mut buffer = u32::try_from(response.len())
+        .unwrap()
+        .to_le_bytes()
+        .to_vec();
+    buffer.extend_from_slice(response.as_bytes());
+    Box::into_raw(buffer.into_boxed_slice()).cast::<u8>()
+}
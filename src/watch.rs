@@ -0,0 +1,244 @@
+use crate::format::Format;
+use colored::Colorize;
+use notify::{Event, RecursiveMode, Watcher, recommended_watcher};
+use serde::Serialize;
+use std::{
+    collections::HashSet,
+    path::Component,
+    sync::{Arc, mpsc::channel},
+    time::Duration,
+};
+use tagref::{
+    baseline::Entry,
+    cache::Cache,
+    count::count,
+    index::{Rules, Scanner},
+    violation::Violation,
+};
+
+// Events tend to arrive in bursts (e.g., when an editor saves a file or a branch is checked out),
+// so we wait for things to settle for this long before checking again.
+pub const DEBOUNCE: Duration = Duration::from_millis(100);
+
+// This is the JSON object emitted each time the set of violations changes.
+#[derive(Serialize)]
+struct Update<'a> {
+    introduced: Vec<&'a Violation>,
+    resolved: Vec<&'a Violation>,
+    violations: usize,
+}
+
+// This function identifies a violation for comparing checks. The labels involved identify it
+// regardless of where they are in their files, so edits which only move labels around don't show
+// up as changes. Violations without labels (e.g., undecodable files) are told apart by their
+// messages, which don't mention positions.
+fn key(violation: &Violation) -> (Entry, Option<&str>) {
+    (
+        Entry::new(violation),
+        violation
+            .directives
+            .is_empty()
+            .then_some(violation.message.as_str()),
+    )
+}
+
+// This function compares two sets of violations. It returns the violations which are only in
+// `current` (introduced) and the ones which are only in `previous` (resolved).
+fn diff<'a>(
+    previous: &'a [Violation],
+    current: &'a [Violation],
+) -> (Vec<&'a Violation>, Vec<&'a Violation>) {
+    let previous_keys = previous.iter().map(key).collect::<HashSet<_>>();
+    let current_keys = current.iter().map(key).collect::<HashSet<_>>();
+
+    (
+        current
+            .iter()
+            .filter(|violation| !previous_keys.contains(&key(violation)))
+            .collect(),
+        previous
+            .iter()
+            .filter(|violation| !current_keys.contains(&key(violation)))
+            .collect(),
+    )
+}
+
+// This function determines whether a filesystem event could affect the result of a check. Changes
+// inside version control directories are ignored, since those directories are never scanned.
+pub fn is_relevant(event: &Event) -> bool {
+    event.paths.iter().any(|path| {
+        !path.components().any(|component| {
+            component == Component::Normal(".git".as_ref())
+                || component == Component::Normal(".hg".as_ref())
+        })
+    })
+}
+
+// This function prints the violations which were introduced or resolved since the last check.
+fn print_update(format: Format, introduced: &[&Violation], resolved: &[&Violation], total: usize) {
+    match format {
+        Format::Json
+        | Format::Sarif
+        | Format::Junit
+        | Format::Github
+        | Format::Gitlab
+        | Format::Csv
+        | Format::Compact => {
+            // Serializing plain data to a string can't fail, so the `unwrap` is safe.
+            println!(
+                "{}",
+                serde_json::to_string(&Update {
+                    introduced: introduced.to_vec(),
+                    resolved: resolved.to_vec(),
+                    violations: total,
+                })
+                .unwrap(),
+            );
+        }
+        Format::Text => {
+            for violation in resolved {
+                println!("{}", format!("Resolved: {violation}").green());
+            }
+            for violation in introduced {
+                println!("{}", format!("Introduced: {violation}").red());
+            }
+            println!("{} remaining.", count(total, "violation"));
+        }
+    }
+}
+
+// This function checks the files, and then checks them again whenever they change. Only files
+// which changed are parsed again. It only returns if something goes wrong.
+pub fn watch(scanner: &Scanner, rules: &Rules, format: Format) -> Result<(), String> {
+    if matches!(
+        format,
+        Format::Sarif
+            | Format::Junit
+            | Format::Github
+            | Format::Gitlab
+            | Format::Csv
+            | Format::Compact
+    ) {
+        return Err(format!(
+            "The {} format is not supported by the `watch` command.",
+            format.name(),
+        ));
+    }
+
+    // Subscribe to filesystem events for all the paths being scanned.
+    let (sender, receiver) = channel();
+    let mut watcher = recommended_watcher(sender)
+        .map_err(|error| format!("Unable to watch for changes: {error}"))?;
+    for path in &scanner.paths {
+        watcher
+            .watch(path, RecursiveMode::Recursive)
+            .map_err(|error| format!("Unable to watch {}: {error}", path.to_string_lossy()))?;
+    }
+
+    // The cache lives in memory for as long as we're watching.
+    let mut cache = Arc::new(Cache::new(&scanner.cache_key()));
+    let mut previous = Vec::new();
+    let mut first = true;
+
+    loop {
+        // Check the files, reusing the directives for the ones which haven't changed.
+        let (index, new_cache) = scanner.scan(Some(cache))?;
+        cache = Arc::new(new_cache.unwrap_or_default());
+        let mut current = index.check(rules);
+        index.suppress(&mut current);
+
+        // Report what changed. The first report is always printed so the user knows where things
+        // stand.
+        let (introduced, resolved) = diff(&previous, &current);
+        if first || !introduced.is_empty() || !resolved.is_empty() {
+            print_update(format, &introduced, &resolved, current.len());
+        }
+        first = false;
+        previous = current;
+
+        // Wait for a relevant change, and then wait for things to settle.
+        loop {
+            match receiver.recv() {
+                Ok(Ok(event)) if is_relevant(&event) => break,
+                Ok(_) => {}
+                Err(_) => return Err("Stopped receiving filesystem events.".to_owned()),
+            }
+        }
+        while receiver.recv_timeout(DEBOUNCE).is_ok() {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        fixtures,
+        watch::{diff, is_relevant},
+    };
+    use notify::{Event, EventKind};
+    use std::path::PathBuf;
+    use tagref::{
+        directive::Type,
+        violation::{Kind, Severity, Violation},
+    };
+
+    fn violation(message: &str) -> Violation {
+        Violation {
+            kind: Kind::DanglingRef,
+            severity: Severity::Error,
+            message: message.to_owned(),
+            directives: vec![],
+            suggestions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn diff_empty() {
+        let (introduced, resolved) = diff(&[], &[]);
+
+        assert!(introduced.is_empty());
+        assert!(resolved.is_empty());
+    }
+
+    #[test]
+    fn diff_changes() {
+        let previous = vec![violation("a"), violation("b")];
+        let current = vec![violation("b"), violation("c")];
+
+        let (introduced, resolved) = diff(&previous, &current);
+
+        assert_eq!(introduced.len(), 1);
+        assert_eq!(introduced[0].message, "c");
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].message, "a");
+    }
+
+    #[test]
+    fn diff_ignores_line_numbers() {
+        let dangling = |line_number| Violation {
+            message: format!("Dangling reference on line {line_number}."),
+            directives: vec![fixtures::directive(Type::Ref, "foo", "a.rs", line_number)],
+            ..violation("")
+        };
+        let previous = vec![dangling(1)];
+        let current = vec![dangling(2)];
+
+        let (introduced, resolved) = diff(&previous, &current);
+
+        assert!(introduced.is_empty());
+        assert!(resolved.is_empty());
+    }
+
+    #[test]
+    fn is_relevant_source_file() {
+        let event = Event::new(EventKind::Any).add_path(PathBuf::from("./src/main.rs"));
+
+        assert!(is_relevant(&event));
+    }
+
+    #[test]
+    fn is_relevant_git_directory() {
+        let event = Event::new(EventKind::Any).add_path(PathBuf::from("./.git/index.lock"));
+
+        assert!(!is_relevant(&event));
+    }
+}